@@ -0,0 +1,3 @@
+#!/usr/bin/env bash
+# the windows service is only compiled for windows, type check it and its tests on linux
+cargo check --all-targets --target x86_64-pc-windows-gnu
@@ -114,6 +114,7 @@ export interface Credentials {
 
 export interface TargetUser {
     target: string;
+    scoped?: boolean;
     credentials: Credentials[];
 }
 
@@ -0,0 +1,51 @@
+use std::cell::RefCell;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+
+use m3u_filter_core::bench_data::generate_bench_playlist;
+use m3u_filter_core::filter::{get_filter, MockValueProcessor};
+use m3u_filter_core::m3u::{parse_m3u, write_m3u, M3uEntry, M3uParseReport, M3uStrictness};
+use m3u_filter_core::mapper::Mapper;
+
+const GROUPS: usize = 100;
+const ITEMS: usize = 200;
+
+const FILTER: &str = r#"(Group ~ "^(DE|UK|FR)" AND NOT Name ~ "(?i)\bSD\b") OR Type = video"#;
+
+const MAPPER: &str = r#"
+pattern: 'Name ~ "^(?P<country>[A-Z]{2}): (?P<name>.*?)(?P<quality> HD| FHD| 4K)?$"'
+attributes:
+  name: <name>
+  group: <country> TV
+"#;
+
+fn parse(content: &str) -> Vec<M3uEntry> {
+    parse_m3u(content.lines(), M3uStrictness::Lenient, &mut M3uParseReport::default())
+}
+
+fn bench_pipeline(c: &mut Criterion) {
+    let content = generate_bench_playlist(GROUPS, ITEMS);
+    let filter = get_filter(FILTER, None).expect("valid bench filter");
+    let mut mapper: Mapper = serde_yaml::from_str(MAPPER).expect("valid bench mapper");
+    mapper.prepare(None, None).expect("valid bench mapper");
+
+    c.bench_function("parse", |b| b.iter(|| parse(&content)));
+    c.bench_function("filter", |b| b.iter_batched(
+        || parse(&content),
+        |entries| entries.into_iter().filter(|entry| filter.filter(entry, &mut MockValueProcessor {})).collect::<Vec<M3uEntry>>(),
+        BatchSize::LargeInput,
+    ));
+    c.bench_function("map", |b| b.iter_batched(
+        || parse(&content).into_iter().map(RefCell::new).collect::<Vec<RefCell<M3uEntry>>>(),
+        |entries| entries.iter().for_each(|entry| {
+            let provider = entry.borrow().clone();
+            mapper.apply(&provider, entry);
+        }),
+        BatchSize::LargeInput,
+    ));
+    let entries = parse(&content);
+    c.bench_function("serialize", |b| b.iter(|| write_m3u(&entries)));
+}
+
+criterion_group!(benches, bench_pipeline);
+criterion_main!(benches);
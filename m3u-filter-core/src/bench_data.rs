@@ -0,0 +1,115 @@
+use std::io::Write;
+
+const COUNTRIES: [&str; 10] = ["DE", "UK", "FR", "US", "IT", "ES", "NL", "PL", "TR", "AR"];
+const CATEGORIES: [&str; 8] = ["News", "Sports", "Movies", "Kids", "Music", "Documentary", "Entertainment", "Regional"];
+const GENRES: [&str; 6] = ["Action", "Comedy", "Drama", "Horror", "Sci-Fi", "Thriller"];
+const WORDS: [&str; 16] = ["One", "Plus", "Max", "World", "Premium", "Gold", "Extra", "Channel", "Arena", "Cinema",
+    "Première", "Ñoño", "Ærø", "Ünal", "Prime", "Classic"];
+/// quality suffixes with their weight, most channels have none
+const QUALITIES: [(&str, usize); 5] = [("", 40), (" HD", 30), (" FHD", 15), (" SD", 10), (" 4K", 5)];
+const BENCH_SEED: u64 = 0x6d33_7566;
+
+/// splitmix64, the playlist has to be reproducible and not random.
+struct BenchRng(u64);
+
+impl BenchRng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut value = self.0;
+        value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        value ^ (value >> 31)
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn gen_range(&mut self, start: usize, end_inclusive: usize) -> usize {
+        start + (self.next() % (end_inclusive - start + 1) as u64) as usize
+    }
+
+    /// `true` with a probability of `percent`.
+    fn gen_bool(&mut self, percent: u64) -> bool {
+        self.next() % 100 < percent
+    }
+}
+
+/// Parses the size of the generated playlist, `<groups>x<items>` like `100x500`.
+pub fn parse_bench_size(size: &str) -> Option<(usize, usize)> {
+    let (groups, items) = size.trim().split_once(['x', 'X'])?;
+    Some((groups.trim().parse().ok().filter(|&groups| groups > 0)?, items.trim().parse().ok().filter(|&items| items > 0)?))
+}
+
+fn pick_quality(rng: &mut BenchRng) -> &'static str {
+    let total: usize = QUALITIES.iter().map(|(_, weight)| weight).sum();
+    let mut value = rng.gen_range(0, total - 1);
+    for (quality, weight) in QUALITIES {
+        if value < weight {
+            return quality;
+        }
+        value -= weight;
+    }
+    ""
+}
+
+/// Writes a synthetic m3u playlist with `groups` groups of `items` channels.
+/// Most groups are live groups of a country, every tenth group is a movie or series group.
+/// Names, epg ids, logos, channel numbers and qualities are distributed like in provider playlists.
+/// The content is the same for the same size.
+pub fn write_bench_playlist<W: Write>(writer: &mut W, groups: usize, items: usize) -> std::io::Result<()> {
+    let mut rng = BenchRng(BENCH_SEED);
+    let mut stream_id: usize = 0;
+    writeln!(writer, "#EXTM3U")?;
+    for group_index in 0..groups {
+        let country = COUNTRIES[group_index % COUNTRIES.len()];
+        let (group, kind, extension) = match group_index % 10 {
+            8 => (format!("VOD | {}", GENRES[group_index / 10 % GENRES.len()]), "movie", "mkv"),
+            9 => (format!("SERIES | {}", GENRES[group_index / 10 % GENRES.len()]), "series", "mp4"),
+            _ => (format!("{country} | {}", CATEGORIES[group_index / COUNTRIES.len() % CATEGORIES.len()]), "live", "ts"),
+        };
+        for _ in 0..items {
+            stream_id += 1;
+            let word = WORDS[rng.gen_range(0, WORDS.len() - 1)];
+            let name = if kind == "live" {
+                format!("{country}: {word} {}{}", rng.gen_range(1, 30), pick_quality(&mut rng))
+            } else {
+                format!("{word} {} ({})", stream_id, rng.gen_range(1970, 2025))
+            };
+            write!(writer, "#EXTINF:-1")?;
+            if kind == "live" && rng.gen_bool(70) {
+                write!(writer, " tvg-id=\"{}.{}\"", name.split_whitespace().nth(1).unwrap_or("tv").to_lowercase(), country.to_lowercase())?;
+            }
+            write!(writer, " tvg-name=\"{name}\"")?;
+            if rng.gen_bool(80) {
+                write!(writer, " tvg-logo=\"http://logos.example.com/{stream_id}.png\"")?;
+            }
+            if kind == "live" && rng.gen_bool(30) {
+                write!(writer, " tvg-chno=\"{stream_id}\"")?;
+            }
+            writeln!(writer, " group-title=\"{group}\",{name}")?;
+            writeln!(writer, "http://provider.example.com/{kind}/user/pass/{stream_id}.{extension}")?;
+        }
+    }
+    writer.flush()
+}
+
+/// Returns the synthetic playlist of `write_bench_playlist` as string.
+pub fn generate_bench_playlist(groups: usize, items: usize) -> String {
+    let mut content = vec![];
+    let _ = write_bench_playlist(&mut content, groups, items);
+    String::from_utf8(content).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bench_data::{generate_bench_playlist, parse_bench_size};
+
+    #[test]
+    fn test_bench_playlist() {
+        assert_eq!(parse_bench_size("100x500"), Some((100, 500)));
+        assert_eq!(parse_bench_size("0x500"), None);
+        assert_eq!(parse_bench_size("100"), None);
+        let content = generate_bench_playlist(10, 5);
+        assert_eq!(content.lines().filter(|line| line.starts_with("#EXTINF")).count(), 50);
+        assert_eq!(content.lines().filter(|line| line.contains("/movie/")).count(), 5);
+        assert_eq!(content, generate_bench_playlist(10, 5));
+    }
+}
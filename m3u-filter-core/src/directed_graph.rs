@@ -199,11 +199,11 @@ where
 
 #[cfg(test)]
 mod tests {
-    use crate::utils::directed_graph::DirectedGraph;
+    use crate::directed_graph::DirectedGraph;
     use std::collections::HashSet;
 
     fn are_vecs_equal(vec1: &Vec<&str>, vec2: Vec<&str>) -> bool {
-        let set1: HashSet<String> = vec1.into_iter().map(|s| s.to_string()).collect();
+        let set1: HashSet<String> = vec1.iter().map(|s| s.to_string()).collect();
         let set2: HashSet<String> = vec2.into_iter().map(|s| s.to_string()).collect();
 
         set1 == set2
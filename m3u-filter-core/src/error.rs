@@ -0,0 +1,27 @@
+use std::fmt::Display;
+
+/// Error of an invalid filter, mapper or template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoreError {
+    pub message: String,
+}
+
+impl CoreError {
+    pub fn new(message: String) -> Self {
+        Self { message }
+    }
+}
+
+impl Display for CoreError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for CoreError {}
+
+macro_rules! core_error_result {
+    ($($arg:tt)*) => {
+        Err($crate::error::CoreError::new(format!($($arg)*)))
+    };
+}
@@ -0,0 +1,85 @@
+use std::fmt::Display;
+use std::rc::Rc;
+
+use enum_iterator::Sequence;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Sequence)]
+pub enum ItemField {
+    #[serde(rename = "group")]
+    Group,
+    #[serde(rename = "name")]
+    Name,
+    #[serde(rename = "title")]
+    Title,
+    #[serde(rename = "url")]
+    Url,
+    #[serde(rename = "type")]
+    Type,
+    #[serde(rename = "country")]
+    Country,
+    #[serde(rename = "language")]
+    Language,
+}
+
+impl ItemField {
+    const GROUP: &'static str = "Group";
+    const NAME: &'static str = "Name";
+    const TITLE: &'static str = "Title";
+    const URL: &'static str = "Url";
+    const TYPE: &'static str = "Type";
+    const COUNTRY: &'static str = "Country";
+    const LANGUAGE: &'static str = "Language";
+}
+
+impl Display for ItemField {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", match *self {
+            Self::Group => Self::GROUP,
+            Self::Name => Self::NAME,
+            Self::Title => Self::TITLE,
+            Self::Url => Self::URL,
+            Self::Type => Self::TYPE,
+            Self::Country => Self::COUNTRY,
+            Self::Language => Self::LANGUAGE,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize, PartialEq, Eq)]
+pub enum StreamStatus {
+    #[serde(rename = "alive")]
+    Alive,
+    #[serde(rename = "dead")]
+    Dead,
+}
+
+impl StreamStatus {
+    const ALIVE: &'static str = "alive";
+    const DEAD: &'static str = "dead";
+}
+
+impl Display for StreamStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", match *self {
+            Self::Alive => Self::ALIVE,
+            Self::Dead => Self::DEAD,
+        })
+    }
+}
+
+/// The values of an item which are compared by a filter.
+pub trait FieldProvider {
+    /// `ItemField::Type` is `live`, `video`, `series` or `series-info`.
+    fn get(&self, field: &ItemField) -> Rc<String>;
+
+    /// The last known status of the stream, `None` if the stream was not checked.
+    fn status(&self) -> Option<StreamStatus> {
+        None
+    }
+}
+
+/// The named fields of an item which are read and written by the mappers, like `name`, `logo` or `epg_channel_id`.
+pub trait FieldAccessor {
+    fn get_field(&self, field: &str) -> Option<Rc<String>>;
+    fn set_field(&mut self, field: &str, value: &str) -> bool;
+}
@@ -0,0 +1,892 @@
+#![allow(clippy::empty_docs)]
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+
+use enum_iterator::all;
+use log::{debug, error, log_enabled, trace, Level};
+use pest::iterators::Pair;
+use pest::Parser;
+use pest_derive::Parser;
+
+use crate::directed_graph::DirectedGraph;
+use crate::error::CoreError;
+use crate::field::{FieldProvider, ItemField, StreamStatus};
+
+pub trait ValueProcessor {
+    fn process(&mut self, field: &ItemField, value: &str, rewc: &RegexWithCaptures) -> bool;
+}
+
+pub struct MockValueProcessor {}
+
+impl ValueProcessor for MockValueProcessor {
+    fn process(&mut self, _: &ItemField, _: &str, _: &RegexWithCaptures) -> bool {
+        false
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PatternTemplate {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct RegexWithCaptures {
+    pub restr: String,
+    pub re: regex::Regex,
+    pub captures: Vec<String>,
+}
+
+/// Channel names and regular expressions of a filter file, one entry per line.
+/// Names are compared case-insensitive, lines starting with `~` are regular expressions and lines starting with `#` are comments.
+#[derive(Debug, Default)]
+struct FilterFileEntries {
+    modified: Option<SystemTime>,
+    hash: [u8; 32],
+    names: HashSet<String>,
+    regexps: Vec<regex::Regex>,
+}
+
+impl FilterFileEntries {
+    fn parse(content: &str, modified: Option<SystemTime>) -> Result<Self, CoreError> {
+        let mut entries = Self { modified, hash: *blake3::hash(content.as_bytes()).as_bytes(), ..Self::default() };
+        for line in content.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+            if let Some(pattern) = line.strip_prefix('~') {
+                match regex::Regex::new(pattern.trim()) {
+                    Ok(re) => entries.regexps.push(re),
+                    Err(err) => return core_error_result!("cant parse regex {}: {}", pattern.trim(), err),
+                }
+            } else {
+                entries.names.insert(line.to_lowercase());
+            }
+        }
+        Ok(entries)
+    }
+
+    fn contains(&self, value: &str) -> bool {
+        self.names.contains(&value.to_lowercase()) || self.regexps.iter().any(|re| re.is_match(value))
+    }
+}
+
+/// File of an `IN_FILE` comparison, the entries are read by `Filter::load_files`.
+#[derive(Debug)]
+pub struct FilterFile {
+    pub file: String,
+    entries: RwLock<FilterFileEntries>,
+}
+
+impl FilterFile {
+    fn new(file: String) -> Self {
+        Self { file, entries: RwLock::new(FilterFileEntries::default()) }
+    }
+
+    /// Reads the file if it changed since the last load, a relative path is resolved against `base_dir`.
+    /// The previous entries are kept if the file can't be read.
+    fn load(&self, base_dir: &Path) -> Result<(), CoreError> {
+        let path = base_dir.join(PathBuf::from(&self.file));
+        let modified = std::fs::metadata(&path).and_then(|metadata| metadata.modified()).ok();
+        if modified.is_some() && self.entries.read().is_ok_and(|entries| entries.modified == modified) {
+            return Ok(());
+        }
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(err) => return core_error_result!("cant read filter file {}: {}", path.display(), err),
+        };
+        let entries = FilterFileEntries::parse(&content, modified)?;
+        debug!("Filter file {} has {} names and {} regular expressions", path.display(), entries.names.len(), entries.regexps.len());
+        if let Ok(mut current) = self.entries.write() {
+            *current = entries;
+        }
+        Ok(())
+    }
+
+    fn contains(&self, value: &str) -> bool {
+        self.entries.read().is_ok_and(|entries| entries.contains(value))
+    }
+
+    /// Hash of the loaded content.
+    pub fn content_hash(&self) -> [u8; 32] {
+        self.entries.read().map(|entries| entries.hash).unwrap_or_default()
+    }
+}
+
+#[derive(Parser)]
+#[grammar_inline = r#"
+WHITESPACE = _{ " " | "\t" | "\r" | "\n"}
+field = { ^"group" | ^"title" | ^"name" | ^"url" | ^"country" | ^"language" }
+and = { ^"and" }
+or = { ^"or" }
+not = { ^"not" }
+regexp = @{ "\"" ~ ( "\\\"" | (!"\"" ~ ANY) )* ~ "\"" }
+type_value = { ^"live" | ^"vod" | ^"series" }
+type_comparison = { ^"type" ~ "=" ~ type_value }
+status_value = { ^"alive" | ^"dead" }
+status_comparison = { ^"status" ~ "=" ~ status_value }
+field_comparison_value = _{ regexp }
+field_comparison = { field ~ "~" ~ field_comparison_value }
+field_equality = { field ~ "=" ~ regexp }
+field_in_file = { field ~ ^"in_file" ~ regexp }
+comparison = { field_comparison | field_equality | field_in_file | type_comparison | status_comparison }
+bool_op = { and | or }
+expr_group = { "(" ~ expr ~ ")" }
+basic_expr = _{ comparison | expr_group }
+not_expr = _{ not ~ basic_expr }
+expr = {
+  not_expr ~ (bool_op ~ expr)?
+  | basic_expr ~ (bool_op ~ expr)*
+}
+stmt = { expr ~ (bool_op ~ expr)* }
+main = _{ SOI ~ stmt ~ EOI }
+"#]
+struct FilterParser;
+
+/// The item types of a `Type = ...` comparison, series info is handled as series.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterItemType {
+    Live,
+    Vod,
+    Series,
+}
+
+#[derive(Debug, Clone)]
+pub enum UnaryOperator {
+    Not
+}
+
+#[derive(Debug, Clone)]
+pub enum BinaryOperator {
+    And,
+    Or,
+}
+
+impl BinaryOperator {
+    const OP_OR: &'static str = "OR";
+    const OP_AND: &'static str = "AND";
+}
+
+impl std::fmt::Display for BinaryOperator {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", match *self {
+            Self::Or => Self::OP_OR,
+            Self::And => Self::OP_AND,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Filter {
+    Group(Box<Filter>),
+    FieldComparison(ItemField, RegexWithCaptures),
+    /// case-insensitive equality
+    FieldEquality(ItemField, String),
+    /// the value is a name or matches a regular expression of the file
+    FieldInFile(ItemField, Arc<FilterFile>),
+    TypeComparison(ItemField, FilterItemType),
+    StatusComparison(StreamStatus),
+    UnaryExpression(UnaryOperator, Box<Filter>),
+    BinaryExpression(Box<Filter>, BinaryOperator, Box<Filter>),
+}
+
+impl Filter {
+    pub fn filter(&self, provider: &dyn FieldProvider, processor: &mut dyn ValueProcessor) -> bool {
+        match self {
+            Self::FieldComparison(field, rewc) => {
+                let value = provider.get(field);
+                let is_match = rewc.re.is_match(value.as_str());
+                if log_enabled!(Level::Trace) {
+                    if is_match {
+                        debug!("Match found: {:?} {} => {}={}", &rewc, &rewc.restr, &field, &value);
+                    } else {
+                        debug!("Match failed: {self}: {:?} {} => {}={}", &rewc, &rewc.restr, &field, &value);
+                    }
+                }
+                if is_match {
+                    processor.process(field, &value, rewc);
+                }
+                is_match
+            }
+            Self::FieldEquality(field, expected) => {
+                let value = provider.get(field);
+                let is_match = value.eq_ignore_ascii_case(expected);
+                if log_enabled!(Level::Trace) {
+                    if is_match {
+                        debug!("Match found: {field}={value}");
+                    } else {
+                        debug!("Match failed: {self}: {field}={value}");
+                    }
+                }
+                is_match
+            }
+            Self::FieldInFile(field, file) => {
+                let value = provider.get(field);
+                let is_match = file.contains(&value);
+                if log_enabled!(Level::Trace) {
+                    if is_match {
+                        debug!("Match found: {field}={value} in {}", file.file);
+                    } else {
+                        debug!("Match failed: {self}: {field}={value}");
+                    }
+                }
+                is_match
+            }
+            Self::TypeComparison(field, item_type) => {
+                let value = provider.get(field);
+                get_filter_item_type(value.as_str()).is_some_and(|pli_type| {
+                        let is_match = pli_type.eq(item_type);
+                        if log_enabled!(Level::Trace) {
+                            if is_match {
+                                debug!("Match found: {:?} {}", &field, value);
+                            } else {
+                                debug!("Match failed: {self}: {:?} {}", &field, &value);
+                            }
+                        }
+                        is_match
+                    })
+            }
+            Self::StatusComparison(status) => {
+                // streams which are not checked are handled as alive
+                let stream_status = provider.status().unwrap_or(StreamStatus::Alive);
+                let is_match = stream_status == *status;
+                if log_enabled!(Level::Trace) {
+                    if is_match {
+                        debug!("Match found: Status {stream_status}");
+                    } else {
+                        debug!("Match failed: {self}: Status {stream_status}");
+                    }
+                }
+                is_match
+            }
+            Self::Group(expr) => {
+                expr.filter(provider, processor)
+            }
+            Self::UnaryExpression(op, expr) => {
+                match op {
+                    UnaryOperator::Not => !expr.filter(provider, processor),
+                }
+            }
+            Self::BinaryExpression(left, op, right) => {
+                match op {
+                    BinaryOperator::And => left.filter(provider, processor)
+                        && right.filter(provider, processor),
+                    BinaryOperator::Or => left.filter(provider, processor)
+                        || right.filter(provider, processor),
+                }
+            }
+        }
+    }
+}
+
+impl Filter {
+    /// Loads the files of the `IN_FILE` comparisons, unchanged files are not read again.
+    pub fn load_files(&self, base_dir: &Path) -> Result<(), CoreError> {
+        match self {
+            Self::FieldInFile(_, file) => file.load(base_dir),
+            Self::Group(expr) | Self::UnaryExpression(_, expr) => expr.load_files(base_dir),
+            Self::BinaryExpression(left, _, right) => {
+                left.load_files(base_dir)?;
+                right.load_files(base_dir)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The files of the `IN_FILE` comparisons.
+    pub fn get_files(&self) -> Vec<&FilterFile> {
+        match self {
+            Self::FieldInFile(_, file) => vec![file.as_ref()],
+            Self::Group(expr) | Self::UnaryExpression(_, expr) => expr.get_files(),
+            Self::BinaryExpression(left, _, right) => {
+                let mut files = left.get_files();
+                files.extend(right.get_files());
+                files
+            }
+            _ => vec![],
+        }
+    }
+}
+
+impl Filter {
+    const LIVE: &'static str = "live";
+    const VOD: &'static str = "vod";
+    const SERIES: &'static str = "series";
+}
+
+impl std::fmt::Display for Filter {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::FieldComparison(field, rewc) => {
+                write!(f, "{} ~ \"{}\"", field, String::from(&rewc.restr))
+            }
+            Self::FieldEquality(field, value) => {
+                write!(f, "{field} = \"{value}\"")
+            }
+            Self::FieldInFile(field, file) => {
+                write!(f, "{field} IN_FILE \"{}\"", file.file)
+            }
+            Self::TypeComparison(field, item_type) => {
+                write!(f, "{} = {}", field, match item_type {
+                    FilterItemType::Live => Self::LIVE,
+                    FilterItemType::Vod => Self::VOD,
+                    FilterItemType::Series => Self::SERIES,
+                })
+            }
+            Self::StatusComparison(status) => {
+                write!(f, "Status = {status}")
+            }
+            Self::Group(stmt) => {
+                write!(f, "({stmt})")
+            }
+            Self::UnaryExpression(op, expr) => {
+                let flt = match op {
+                    UnaryOperator::Not => format!("NOT {expr}"),
+                };
+                write!(f, "{flt}")
+            }
+            Self::BinaryExpression(left, op, right) => {
+                write!(f, "{left} {op} {right}")
+            }
+        }
+    }
+}
+
+fn get_parser_item_field(expr: &Pair<Rule>) -> Result<ItemField, CoreError> {
+    if expr.as_rule() == Rule::field {
+        let field_text = expr.as_str();
+        for item in all::<ItemField>() {
+            if field_text.eq_ignore_ascii_case(item.to_string().as_str()) {
+                return Ok(item);
+            }
+        }
+    }
+    core_error_result!("unknown field: {}", expr.as_str())
+}
+
+fn get_parser_regexp(expr: &Pair<Rule>, templates: &Vec<PatternTemplate>) -> Result<RegexWithCaptures, CoreError> {
+    if expr.as_rule() == Rule::regexp {
+        let mut parsed_text = String::from(expr.as_str());
+        parsed_text.pop();
+        parsed_text.remove(0);
+        let regstr = apply_templates_to_pattern(&parsed_text, templates);
+        let re = regex::Regex::new(regstr.as_str());
+        if re.is_err() {
+            return core_error_result!("cant parse regex: {}", regstr);
+        }
+        let regexp = re.unwrap();
+        let captures = regexp.capture_names()
+            .flatten().map(String::from).filter(|x| !x.is_empty()).collect::<Vec<String>>();
+        if log_enabled!(Level::Trace) {
+            trace!("Created regex: {} with captures: [{}]", regstr, captures.join(", "));
+        }
+        return Ok(RegexWithCaptures {
+            restr: regstr,
+            re: regexp,
+            captures,
+        });
+    }
+    core_error_result!("unknown field: {}", expr.as_str())
+}
+
+fn get_parser_field_comparison(expr: Pair<Rule>, templates: &Vec<PatternTemplate>) -> Result<Filter, CoreError> {
+    let mut expr_inner = expr.into_inner();
+    match get_parser_item_field(&expr_inner.next().unwrap()) {
+        Ok(field) => {
+            match get_parser_regexp(&expr_inner.next().unwrap(), templates) {
+                Ok(regexp) => Ok(Filter::FieldComparison(field, regexp)),
+                Err(err) => Err(err),
+            }
+        }
+        Err(err) => Err(err)
+    }
+}
+
+fn get_parser_field_equality(expr: Pair<Rule>) -> Result<Filter, CoreError> {
+    let mut expr_inner = expr.into_inner();
+    let field = get_parser_item_field(&expr_inner.next().unwrap())?;
+    let text = expr_inner.next().unwrap().as_str();
+    Ok(Filter::FieldEquality(field, text[1..text.len() - 1].replace("\\\"", "\"")))
+}
+
+fn get_parser_field_in_file(expr: Pair<Rule>) -> Result<Filter, CoreError> {
+    let mut expr_inner = expr.into_inner();
+    let field = get_parser_item_field(&expr_inner.next().unwrap())?;
+    let text = expr_inner.next().unwrap().as_str();
+    Ok(Filter::FieldInFile(field, Arc::new(FilterFile::new(text[1..text.len() - 1].replace("\\\"", "\"")))))
+}
+
+fn get_filter_item_type(text_item_type: &str) -> Option<FilterItemType> {
+    if text_item_type.eq_ignore_ascii_case("live") {
+        Some(FilterItemType::Live)
+    } else if text_item_type.eq_ignore_ascii_case("movie") || text_item_type.eq_ignore_ascii_case("video") || text_item_type.eq_ignore_ascii_case("vod") {
+        Some(FilterItemType::Vod)
+    } else if text_item_type.eq_ignore_ascii_case("series") {
+        Some(FilterItemType::Series)
+    } else if text_item_type.eq_ignore_ascii_case("series-info") {
+        // this is necessarry to avoid series and series-info confusion in filter!
+        // we can now use series  for filtering series and series-info (series-info are categories)
+        Some(FilterItemType::Series)
+    } else {
+        None
+    }
+}
+
+fn get_parser_type_comparison(expr: Pair<Rule>) -> Result<Filter, CoreError> {
+    let expr_inner = expr.into_inner();
+    let text_item_type = expr_inner.as_str();
+    let item_type = get_filter_item_type(text_item_type);
+    item_type.map_or_else(|| core_error_result!("cant parse item type: {text_item_type}"), |itype| Ok(Filter::TypeComparison(ItemField::Type, itype)))
+}
+
+fn get_parser_status_comparison(expr: Pair<Rule>) -> Result<Filter, CoreError> {
+    let expr_inner = expr.into_inner();
+    let text_status = expr_inner.as_str();
+    if text_status.eq_ignore_ascii_case("alive") {
+        Ok(Filter::StatusComparison(StreamStatus::Alive))
+    } else if text_status.eq_ignore_ascii_case("dead") {
+        Ok(Filter::StatusComparison(StreamStatus::Dead))
+    } else {
+        core_error_result!("cant parse status: {text_status}")
+    }
+}
+
+macro_rules! handle_expr {
+    ($bop: expr, $uop: expr, $stmts: expr, $exp: expr) => {
+        {
+            let result = match $bop {
+                Some(binop) => {
+                    let lhs = $stmts.pop().unwrap();
+                    $bop = None;
+                    Filter::BinaryExpression(Box::new(lhs), binop.clone(), Box::new($exp))
+                }
+                _ => match $uop {
+                    Some(unop) => {
+                        $uop = None;
+                        Filter::UnaryExpression(unop.clone(), Box::new($exp))
+                    }
+                    _ => $exp
+                }
+            };
+            $stmts.push(result);
+        }
+    }
+}
+
+fn get_parser_expression(expr: Pair<Rule>, templates: &Vec<PatternTemplate>, errors: &mut Vec<String>) -> Result<Filter, CoreError> {
+    let mut stmts = Vec::new();
+    let pairs = expr.into_inner();
+    let mut bop: Option<BinaryOperator> = None;
+    let mut uop: Option<UnaryOperator> = None;
+
+    for pair in pairs {
+        match pair.as_rule() {
+            Rule::field_comparison => {
+                let comp_res = get_parser_field_comparison(pair, templates);
+                match comp_res {
+                    Ok(comp) => handle_expr!(bop, uop, stmts, comp),
+                    Err(err) => errors.push(err.to_string()),
+                }
+            }
+            Rule::field_equality => {
+                match get_parser_field_equality(pair) {
+                    Ok(comp) => handle_expr!(bop, uop, stmts, comp),
+                    Err(err) => errors.push(err.to_string()),
+                }
+            }
+            Rule::field_in_file => {
+                match get_parser_field_in_file(pair) {
+                    Ok(comp) => handle_expr!(bop, uop, stmts, comp),
+                    Err(err) => errors.push(err.to_string()),
+                }
+            }
+            Rule::type_comparison => {
+                let comp_res = get_parser_type_comparison(pair);
+                match comp_res {
+                    Ok(comp) => handle_expr!(bop, uop, stmts, comp),
+                    Err(err) => errors.push(err.to_string()),
+                }
+            }
+            Rule::status_comparison => {
+                let comp_res = get_parser_status_comparison(pair);
+                match comp_res {
+                    Ok(comp) => handle_expr!(bop, uop, stmts, comp),
+                    Err(err) => errors.push(err.to_string()),
+                }
+            }
+            Rule::comparison | Rule::expr => {
+                handle_expr!(bop, uop, stmts, get_parser_expression(pair, templates, errors)?);
+            }
+            Rule::expr_group => {
+                handle_expr!(bop, uop, stmts, Filter::Group(Box::new(get_parser_expression(pair.into_inner().next().unwrap(), templates, errors)?)));
+            }
+            Rule::not => {
+                uop = Some(UnaryOperator::Not);
+            }
+            Rule::bool_op => {
+                match get_parser_binary_op(&pair.into_inner().next().unwrap()) {
+                    Ok(binop) => {
+                        bop = Some(binop);
+                    }
+                    Err(err) => {
+                        errors.push(format!("{err}"));
+                    }
+                }
+            }
+            _ => {
+                errors.push(format!("did not expect rule: {pair:?}"));
+            }
+        }
+    }
+    if stmts.is_empty() {
+        return core_error_result!("Invalid Filter, could not parse {errors:?}");
+    }
+    if stmts.len() > 1 {
+        return core_error_result!("did not expect multiple rule: {stmts:?}, {errors:?}");
+    }
+
+    Ok(stmts.pop().unwrap())
+}
+
+fn get_parser_binary_op(expr: &Pair<Rule>) -> Result<BinaryOperator, CoreError> {
+    match expr.as_rule() {
+        Rule::and => Ok(BinaryOperator::And),
+        Rule::or => Ok(BinaryOperator::Or),
+        _ => core_error_result!("Unknown binary operator {}", expr.as_str())
+    }
+}
+
+pub fn get_filter(filter_text: &str, templates: Option<&Vec<PatternTemplate>>) -> Result<Filter, CoreError> {
+    let empty_list = Vec::new();
+    let template_list: &Vec<PatternTemplate> = templates.unwrap_or(&empty_list);
+    let source = apply_templates_to_pattern(filter_text, template_list);
+
+    match FilterParser::parse(Rule::main, &source) {
+        Ok(pairs) => {
+            let mut errors = Vec::new();
+            let mut result: Option<Filter> = None;
+            let mut op: Option<BinaryOperator> = None;
+            for pair in pairs {
+                match pair.as_rule() {
+                    Rule::stmt => {
+                        for expr in pair.into_inner() {
+                            match expr.as_rule() {
+                                Rule::expr => {
+                                    let expr = get_parser_expression(expr, template_list, &mut errors)?;
+                                    match &op {
+                                        Some(binop) => {
+                                            result = Some(Filter::BinaryExpression(Box::new(result.unwrap()), binop.clone(), Box::new(expr)));
+                                            op = None;
+                                        }
+                                        _ => result = Some(expr)
+                                    }
+                                }
+                                Rule::bool_op => {
+                                    match get_parser_binary_op(&expr.into_inner().next().unwrap()) {
+                                        Ok(binop) => {
+                                            op = Some(binop);
+                                        }
+                                        Err(err) => {
+                                            errors.push(err.to_string());
+                                        }
+                                    }
+                                }
+                                _ => {
+                                    errors.push(format!("unknown expression {expr:?}"));
+                                }
+                            }
+                        }
+                    }
+                    Rule::EOI => {}
+                    _ => {
+                        errors.push(format!("unknown: {}", pair.as_str()));
+                    }
+                }
+            }
+
+            if !errors.is_empty() {
+                errors.push(format!("Unable to parse filter: {}", &filter_text));
+                return Err(CoreError::new(errors.join("\n")));
+            }
+
+            result.map_or_else(|| core_error_result!("Unable to parse filter: {}", &filter_text), Ok)
+        }
+        Err(err) => core_error_result!("{}", err)
+    }
+}
+
+fn build_dependency_graph(templates: &Vec<PatternTemplate>) -> Result<DirectedGraph<String>, CoreError> {
+    let mut graph = DirectedGraph::<String>::new();
+    let re_template= regex::Regex::new("!(.*?)!").unwrap();
+    for template in templates {
+        graph.add_node(&template.name);
+        re_template.captures_iter(&template.value)
+            .filter(|caps| caps.len() > 1)
+            .filter_map(|caps| caps.get(1))
+            .map(|caps| String::from(caps.as_str()))
+            .for_each(|e| {
+                graph.add_node(&e);
+                graph.add_edge(&template.name, &e);
+            });
+    }
+    let cycles = graph.find_cycles();
+    for cyclic in &cycles {
+        error!("Cyclic template dependencies detected [{}]", cyclic.join(" <-> "));
+    }
+    if !cycles.is_empty() {
+        return core_error_result!("Cyclic dependencies in templates detected!");
+    }
+    Ok(graph)
+}
+
+pub fn prepare_templates(templates: &Vec<PatternTemplate>) -> Result<Vec<PatternTemplate>, CoreError> {
+    let graph = build_dependency_graph(templates)?;
+    let mut template_values = HashMap::<String, String>::new();
+    let mut template_map: HashMap<String, PatternTemplate> = templates.iter()
+        .map(|item| {
+            template_values.insert(item.name.clone(), item.value.clone());
+            (item.name.clone(), item.clone())
+        })
+        .collect();
+
+    if let Some(dependencies) = graph.get_dependencies() {
+        if let Some(sorted) = graph.topological_sort() {
+            for template_name in sorted {
+                if let Some(depends_on) = dependencies.get(&template_name) {
+                    let mut templ_value = template_values.get(&template_name).unwrap().to_string();
+                    for dep_templ_name in depends_on {
+                        let dep_value = template_values.get(dep_templ_name).unwrap();
+                        templ_value = templ_value.replace(format!("!{dep_templ_name}!").as_str(), dep_value);
+                    }
+                    template_values.insert(template_name.clone(), templ_value);
+                }
+            }
+
+            for (k, v) in template_values {
+                let template = template_map.get_mut(&k).unwrap();
+                template.value = v;
+            }
+        }
+    }
+    Ok(template_map.into_values().collect())
+}
+
+pub fn apply_templates_to_pattern(pattern: &str, templates: &Vec<PatternTemplate>) -> String {
+    let mut new_pattern = pattern.to_string();
+    for template in templates {
+        new_pattern = new_pattern.replace(format!("!{}!", &template.name).as_str(), &template.value);
+    }
+    new_pattern
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use regex::Regex;
+
+    use crate::field::{FieldProvider, ItemField};
+    use crate::filter::{get_filter, MockValueProcessor};
+
+    #[derive(Default)]
+    struct MockItem {
+        name: Rc<String>,
+        group: Rc<String>,
+        country: Rc<String>,
+    }
+
+    impl FieldProvider for MockItem {
+        fn get(&self, field: &ItemField) -> Rc<String> {
+            match field {
+                ItemField::Name => Rc::clone(&self.name),
+                ItemField::Group => Rc::clone(&self.group),
+                ItemField::Country => Rc::clone(&self.country),
+                _ => Rc::new(String::new()),
+            }
+        }
+    }
+
+    fn create_mock_pli(name: &str, group: &str) -> MockItem {
+        MockItem { name: Rc::new(name.to_string()), group: Rc::new(group.to_string()), ..MockItem::default() }
+    }
+
+    #[test]
+    fn test_filter_1() {
+        let flt1 = r#"(Group ~ "A" OR Group ~ "B") AND (Name ~ "C" OR Name ~ "D" OR Name ~ "E") OR (NOT (Title ~ "F") AND NOT Title ~ "K")"#;
+        match get_filter(flt1, None) {
+            Ok(filter) => {
+                assert_eq!(format!("{filter}"), flt1);
+            }
+            Err(e) => {
+                panic!("{}", e)
+            }
+        }
+    }
+
+    #[test]
+    fn test_filter_2() {
+        let flt2 = r#"Group ~ "d" AND ((Name ~ "e" AND NOT ((Name ~ "c" OR Name ~ "f"))) OR (Name ~ "a" OR Name ~ "b"))"#;
+        match get_filter(flt2, None) {
+            Ok(filter) => {
+                assert_eq!(format!("{filter}"), flt2);
+            }
+            Err(e) => {
+                panic!("{}", e)
+            }
+        }
+    }
+
+    #[test]
+    fn test_filter_3() {
+        let flt = r#"Group ~ "d" AND ((Name ~ "e" AND NOT ((Name ~ "c" OR Name ~ "f"))) OR (Name ~ "a" OR Name ~ "b")) AND (Type = vod)"#;
+        match get_filter(flt, None) {
+            Ok(filter) => {
+                assert_eq!(format!("{filter}"), flt);
+            }
+            Err(e) => {
+                panic!("{}", e)
+            }
+        }
+    }
+
+    #[test]
+    fn test_filter_status() {
+        let flt = r#"Group ~ "d" AND Status = alive"#;
+        match get_filter(flt, None) {
+            Ok(filter) => {
+                assert_eq!(format!("{filter}"), flt);
+                // unchecked streams are alive
+                let channel = create_mock_pli("Entertainment", "d");
+                assert!(filter.filter(&channel, &mut MockValueProcessor {}));
+            }
+            Err(e) => {
+                panic!("{}", e)
+            }
+        }
+    }
+
+    #[test]
+    fn test_filter_country() {
+        let flt = r#"Country = "de" AND NOT Language ~ "^EN$""#;
+        let filter = get_filter(flt, None).unwrap();
+        assert_eq!(format!("{filter}"), r#"Country = "de" AND NOT Language ~ "^EN$""#);
+        let mut channel = create_mock_pli("ZDF", "News");
+        assert!(!filter.filter(&channel, &mut MockValueProcessor {}));
+        channel.country = Rc::new("DE".to_string());
+        assert!(filter.filter(&channel, &mut MockValueProcessor {}));
+    }
+
+    #[test]
+    fn test_filter_in_file() {
+        let temp_dir = tempfile::Builder::new().prefix("m3u_filter_in_file_").tempdir().unwrap();
+        let dir = temp_dir.path();
+        std::fs::write(dir.join("blocked.txt"), "# blocked channels\nZDF\n~^US: \n").unwrap();
+        let flt = r#"NOT Name IN_FILE "blocked.txt" AND Group ~ "TV""#;
+        let filter = get_filter(flt, None).unwrap();
+        assert_eq!(format!("{filter}"), flt);
+        filter.load_files(dir).unwrap();
+        let channels = [
+            create_mock_pli("zdf", "TV"),
+            create_mock_pli("US: CNN", "TV"),
+            create_mock_pli("ARD", "TV"),
+        ];
+        let mut processor = MockValueProcessor {};
+        let filtered: Vec<String> = channels.iter().filter(|&chan| {
+            filter.filter(chan, &mut processor)
+        }).map(|chan| chan.name.to_string()).collect();
+        assert_eq!(filtered, vec!["ARD"]);
+        assert!(filter.load_files(&dir.join("missing")).is_err());
+    }
+
+    #[test]
+    fn test_filter_4() {
+        let flt = r#"NOT (Name ~ ".*24/7.*" AND Group ~ "^US.*")"#;
+        match get_filter(flt, None) {
+            Ok(filter) => {
+                assert_eq!(format!("{filter}"), flt);
+                let channels = [
+                    create_mock_pli("24/7: Cars", "FR Channels"),
+                    create_mock_pli("24/7: Cars", "US Channels"),
+                    create_mock_pli("Entertainment", "US Channels"),
+                ];
+                let mut processor = MockValueProcessor {};
+                let filtered: Vec<&MockItem> = channels.iter().filter(|&chan| {
+                    filter.filter(chan, &mut processor)
+                }).collect();
+                assert_eq!(filtered.len(), 2);
+                assert!(filtered.iter().any(|&chan| {
+                    chan.name.as_str().eq("24/7: Cars") && chan.group.as_str().eq("FR Channels")
+                }));
+                assert!(filtered.iter().any(|&chan| {
+                    chan.name.as_str().eq("Entertainment") && chan.group.as_str().eq("US Channels")
+                }));
+                assert!(!filtered.iter().any(|&chan| {
+                    chan.name.as_str().eq("24/7: Cars") && chan.group.as_str().eq("US Channels")
+                }));
+            }
+            Err(e) => {
+                panic!("{}", e)
+            }
+        }
+    }
+
+    #[test]
+    fn test_filter_5() {
+        let flt = r#"NOT (Name ~ "NC" OR Group ~ "GA") AND (Name ~ "NA" AND Group ~ "GA") OR (Name ~ "NB" AND Group ~ "GB")"#;
+        match get_filter(flt, None) {
+            Ok(filter) => {
+                assert_eq!(format!("{filter}"), flt);
+                let channels = [
+                    create_mock_pli("NA", "GA"),
+                    create_mock_pli("NB", "GB"),
+                    create_mock_pli("NA", "GB"),
+                    create_mock_pli("NB", "GA"),
+                    create_mock_pli("NC", "GA"),
+                    create_mock_pli("NA", "GC"),
+                ];
+                let mut processor = MockValueProcessor {};
+                let filtered: Vec<&MockItem> = channels.iter().filter(|&chan| {
+                    filter.filter(chan, &mut processor)
+                }).collect();
+                assert_eq!(filtered.len(), 1);
+            }
+            Err(e) => {
+                panic!("{}", e)
+            }
+        }
+    }
+
+    #[test]
+    fn test_filter_6() {
+        let flt = r####"
+            Group ~ "^EU \| FRANCE.*"
+            OR  Group ~ "^VOD \| FR.*"
+            OR  Group ~ "\[FR\].*"
+            OR  Group ~ "^SRS \| FR.*"
+            AND NOT (Group ~ ".* LQ.*"
+            OR Title ~ ".* LQ.*"
+            OR Group ~ ".* SD.*"
+            OR Title ~ ".* SD.*"
+            OR Group ~ ".* HD.*"
+            OR Title ~ ".* HD.*"
+            OR Group ~ "(?i).*sport.*"
+            OR Group ~ "(?i).*DAZN.*"
+            OR Group ~ "(?i).*EQUIPE.*"
+            OR Group ~ "DOM TOM.*"
+            OR Group ~ "(?i).*PLUTO.*"
+            OR Title ~ "(?i).*GOLD.*"
+            OR Title ~ "###.*")"####;
+
+        match get_filter(flt, None) {
+            Ok(filter) => {
+                let re = Regex::new(r"\s+").unwrap();
+                let result = re.replace_all(flt, " ");
+                assert_eq!(format!("{filter}"), result.trim());
+            }
+            Err(e) => {
+                panic!("{}", e)
+            }
+        }
+    }
+}
\ No newline at end of file
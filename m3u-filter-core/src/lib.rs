@@ -0,0 +1,17 @@
+//! Playlist processing of m3u-filter without the server and its configuration.
+//!
+//! - [`m3u`] parses and writes m3u playlists, [`m3u::parse_m3u`] and [`m3u::write_m3u`] work on [`m3u::M3uEntry`].
+//! - [`filter`] parses the filter expressions, [`filter::get_filter`] compiles a filter which is applied
+//!   to every item implementing [`field::FieldProvider`].
+//! - [`mapper`] maps the fields of an item implementing [`field::FieldAccessor`] with the captures of a filter.
+//! - [`bench_data`] generates reproducible playlists for benchmarks and tests.
+//!
+//! The `m3u-filter` binary uses this crate for its inputs and targets.
+#[macro_use]
+pub mod error;
+pub mod field;
+pub mod filter;
+pub mod mapper;
+pub mod m3u;
+pub mod bench_data;
+mod directed_graph;
@@ -0,0 +1,461 @@
+use std::rc::Rc;
+
+use serde::Serialize;
+
+use crate::field::{FieldAccessor, FieldProvider, ItemField};
+
+pub const EXTINF: &str = "#EXTINF";
+const EXTGRP: &str = "#EXTGRP";
+/// Urls with these extensions are videos, the others are live streams.
+pub const VIDEO_EXTENSIONS: &[&str] = &["mkv", "avi", "mp4"];
+
+/// Handling of malformed `#EXTINF` lines of m3u inputs.
+#[derive(Debug, Copy, Clone, serde::Serialize, serde::Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum M3uStrictness {
+    /// the lines are parsed as they are
+    #[default]
+    Lenient,
+    /// unescaped quotes, missing commas and duplicated attributes are repaired
+    Repair,
+    /// malformed lines are skipped
+    Strict,
+}
+
+/// A malformed `#EXTINF` line, `line` is the line number in the playlist.
+#[derive(Debug, Clone, Serialize)]
+pub struct M3uReportLine {
+    pub line: usize,
+    pub issues: Vec<String>,
+    pub content: String,
+}
+
+/// The `#EXTINF` lines of an input which were repaired or skipped.
+#[derive(Debug, Default, Serialize)]
+pub struct M3uParseReport {
+    pub repaired: Vec<M3uReportLine>,
+    pub skipped: Vec<M3uReportLine>,
+}
+
+fn starts_with_attribute(text: &str) -> bool {
+    let key_len = text.find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_')).unwrap_or(text.len());
+    key_len > 0 && text[key_len..].starts_with('=')
+}
+
+/// A quote closes the value if it is followed by the next attribute, the title comma or the line end,
+/// other quotes are part of the value. Without such a quote the title follows the first quote without comma.
+fn find_closing_quote(text: &str, start: usize) -> Option<usize> {
+    let mut quotes = text[start..].match_indices('"').map(|(idx, _)| start + idx);
+    quotes.clone().find(|&idx| {
+        let rest = text[idx + 1..].trim_start();
+        rest.is_empty() || rest.starts_with(',') || starts_with_attribute(rest)
+    }).or_else(|| quotes.find(|&idx| text[idx + 1..].starts_with(char::is_whitespace)))
+}
+
+/// Repairs a missing duration, unescaped quotes, unquoted values, duplicated attributes and a missing title comma.
+/// Returns the rebuilt line and the issues, the line is returned unchanged if it has no issues.
+pub fn repair_extinf(line: &str) -> (String, Vec<String>) {
+    let mut issues = vec![];
+    let rest = &line[EXTINF.len().min(line.len())..];
+    let rest = rest.strip_prefix(':').unwrap_or_else(|| {
+        issues.push("missing colon".to_string());
+        rest
+    }).trim_start();
+    let duration_len = rest.find(|c: char| !(c == '-' || c == '+' || c == '.' || c.is_ascii_digit())).unwrap_or(rest.len());
+    let duration = if duration_len == 0 {
+        issues.push("missing duration".to_string());
+        "-1"
+    } else {
+        &rest[..duration_len]
+    };
+
+    let mut attributes: Vec<(String, String)> = vec![];
+    let mut title = None;
+    let mut pos = duration_len;
+    loop {
+        let remaining = rest[pos..].trim_start();
+        pos = rest.len() - remaining.len();
+        if remaining.is_empty() {
+            break;
+        }
+        if let Some(text) = remaining.strip_prefix(',') {
+            title = Some(text.trim().to_string());
+            break;
+        }
+        if !starts_with_attribute(remaining) {
+            issues.push("missing comma before title".to_string());
+            title = Some(remaining.trim().to_string());
+            break;
+        }
+        let key = remaining[..remaining.find('=').unwrap_or_default()].to_string();
+        let value_start = pos + key.len() + 1;
+        let (value, end) = if rest[value_start..].starts_with('"') {
+            if let Some(close) = find_closing_quote(rest, value_start + 1) {
+                let value = &rest[value_start + 1..close];
+                if value.contains('"') {
+                    issues.push(format!("unescaped quote in {key}"));
+                }
+                (value.to_string(), close + 1)
+            } else {
+                // the value ends at the title comma
+                issues.push(format!("unterminated quote in {key}"));
+                let close = rest[value_start..].find(',').map_or(rest.len(), |idx| value_start + idx);
+                (rest[value_start + 1..close].to_string(), close)
+            }
+        } else {
+            issues.push(format!("unquoted value of {key}"));
+            let close = rest[value_start..].find(|c: char| c.is_whitespace() || c == ',').map_or(rest.len(), |idx| value_start + idx);
+            (rest[value_start..close].to_string(), close)
+        };
+        if attributes.iter().any(|(name, _)| name.eq_ignore_ascii_case(&key)) {
+            issues.push(format!("duplicated attribute {key}"));
+        } else {
+            attributes.push((key, value));
+        }
+        pos = end;
+    }
+
+    let title = title.filter(|title| !title.is_empty()).unwrap_or_else(|| {
+        issues.push("missing title".to_string());
+        attributes.iter().find(|(name, _)| name.eq_ignore_ascii_case("tvg-name")).map(|(_, value)| value.clone()).unwrap_or_default()
+    });
+    if title.contains('"') {
+        issues.push("quote in title".to_string());
+    }
+    if issues.is_empty() {
+        return (line.to_string(), issues);
+    }
+    // the header parser reads the values and the title until the next quote
+    let attributes: String = attributes.iter().map(|(name, value)| format!(" {name}=\"{}\"", value.replace('"', "'"))).collect();
+    (format!("{EXTINF}:{duration}{attributes},{}", title.replace('"', "'")), issues)
+}
+
+#[inline]
+fn token_value(it: &mut std::str::Chars) -> String {
+    // Use .find() to skip until the first double quote (") character.
+    if it.any(|ch| ch == '"') {
+        // If a quote is found, call get_value to extract the value.
+        return get_value(it);
+    }
+    // If no double quote is found, return an empty string.
+    String::new()
+}
+
+fn get_value(it: &mut std::str::Chars) -> String {
+    let mut result = String::with_capacity(128);
+    for oc in it.by_ref() {
+        if oc == '"' {
+            break;
+        }
+        result.push(oc);
+    }
+    result.shrink_to_fit();
+    result
+}
+
+fn token_till(it: &mut std::str::Chars, stop_char: char, start_with_alpha: bool) -> Option<String> {
+    let mut result = String::with_capacity(128);
+    let mut skip_non_alpha = start_with_alpha;
+
+    for ch in it.by_ref() {
+        if ch == stop_char {
+            break;
+        }
+        if result.is_empty() && ch.is_whitespace() {
+            continue;
+        }
+
+        if skip_non_alpha {
+            if ch.is_alphabetic() {
+                skip_non_alpha = false;
+            } else {
+                continue;
+            }
+        }
+        result.push(ch);
+    }
+
+    if result.is_empty() {
+        None
+    } else {
+        result.shrink_to_fit();
+        Some(result)
+    }
+}
+
+#[inline]
+fn skip_digit(it: &mut std::str::Chars) -> Option<char> {
+    loop {
+        match it.next() {
+            Some(c) => {
+                if !(c == '-' || c == '+' || c.is_ascii_digit()) {
+                    return Some(c);
+                }
+            }
+            None => return None,
+        }
+    }
+}
+
+
+/// The title and the attributes of an `#EXTINF` line, the attribute names are lowercase.
+#[derive(Debug, Default)]
+pub struct M3uExtinf {
+    pub title: String,
+    pub attributes: Vec<(String, String)>,
+}
+
+/// Reads the title and the attributes of the `#EXTINF` line, `None` if the line is no `#EXTINF` line.
+/// A value is read until the next quote, so the line should be repaired if it has unescaped quotes.
+pub fn parse_extinf(content: &str) -> Option<M3uExtinf> {
+    let mut it = content.chars();
+    let line_token = token_till(&mut it, ':', false);
+    if line_token.as_deref() != Some(EXTINF) {
+        return None;
+    }
+    let mut extinf = M3uExtinf::default();
+    let mut c = skip_digit(&mut it);
+    loop {
+        if c.is_none() {
+            break;
+        }
+        if c.unwrap() == ',' {
+            extinf.title = get_value(&mut it);
+        } else {
+            let token = token_till(&mut it, '=', true);
+            if let Some(t) = token {
+                let value = token_value(&mut it);
+                extinf.attributes.push((t.to_lowercase(), value));
+            }
+        }
+        c = it.next();
+    }
+    Some(extinf)
+}
+
+/// Assembles the `#EXTINF`, `#EXTGRP` and url lines of the playlist, `visit` is called with the `#EXTINF` line,
+/// the `#EXTGRP` group and the url of each item. With `M3uStrictness::Repair` or `M3uStrictness::Strict`
+/// the malformed `#EXTINF` lines are repaired or skipped and added to the report.
+pub fn consume_m3u_lines<I, S, F>(lines: I, strictness: M3uStrictness, report: &mut M3uParseReport, mut visit: F)
+where
+    I: Iterator<Item=S>,
+    S: AsRef<str>,
+    F: FnMut(&str, Option<&str>, &str),
+{
+    let mut header: Option<String> = None;
+    let mut group: Option<String> = None;
+    for (line_idx, line) in lines.enumerate() {
+        let line = line.as_ref();
+        if line.starts_with(EXTINF) {
+            if strictness == M3uStrictness::Lenient {
+                header = Some(String::from(line));
+                continue;
+            }
+            let (repaired, issues) = repair_extinf(line);
+            let report_line = |issues| M3uReportLine { line: line_idx + 1, issues, content: line.to_string() };
+            header = if issues.is_empty() {
+                Some(repaired)
+            } else if strictness == M3uStrictness::Repair {
+                report.repaired.push(report_line(issues));
+                Some(repaired)
+            } else {
+                report.skipped.push(report_line(issues));
+                None
+            };
+            continue;
+        }
+        if line.starts_with(EXTGRP) {
+            group = Some(String::from(&line[8..]));
+            continue;
+        }
+        if line.starts_with('#') {
+            continue;
+        }
+        if let Some(header_value) = &header {
+            visit(header_value, group.as_deref(), line);
+        }
+        header = None;
+        group = None;
+    }
+}
+
+pub fn extract_id_from_url(url: &str) -> Option<String> {
+    if let Some(filename) = url.split('/').next_back() {
+        return filename.rfind('.').map_or_else(|| Some(filename.to_string()), |index| Some(filename[..index].to_string()));
+    }
+    None
+}
+
+// other implementations like calculating text_distance on all titles took too much time
+// we keep it now as simple as possible and less memory intensive.
+pub fn get_title_group(text: &str) -> String {
+    let alphabetic_only: String = text.chars().map(|c| if c.is_alphanumeric() { c } else { ' ' }).collect();
+    let parts = alphabetic_only.split_whitespace();
+    let mut combination = String::new();
+    for p in parts {
+        combination = format!("{combination} {p}").trim().to_string();
+        if combination.len() > 2 {
+            return combination;
+        }
+    }
+    text.to_string()
+}
+
+/// The `#EXTINF` line with the attributes and the title, followed by the url line.
+pub fn write_extinf(attributes: &[(&str, &str)], title: &str, url: &str) -> String {
+    let mut line = String::from("#EXTINF:-1");
+    for (name, value) in attributes {
+        line.push_str(&format!(" {name}=\"{value}\""));
+    }
+    format!("{line},{title}\n{url}")
+}
+
+/// The attributes of the `#EXTINF` line which are read and written as fields by the mappers.
+const ENTRY_FIELDS: &[(&str, &str)] = &[
+    ("id", "tvg-id"), ("name", "tvg-name"), ("group", "group-title"), ("chno", "tvg-chno"), ("logo", "tvg-logo"),
+    ("logo_small", "tvg-logo-small"), ("parent_code", "parent-code"), ("audio_track", "audio-track"),
+    ("time_shift", "timeshift"), ("rec", "tvg-rec"), ("epg_channel_id", "tvg-id"), ("epg_id", "tvg-id"),
+    ("epg_timeshift", "tvg-shift"), ("country", "tvg-country"), ("language", "tvg-language"),
+];
+
+/// An item of an m3u playlist, the attributes of the `#EXTINF` line are kept in their order.
+/// `tvg-name` and `group-title` are always set, like the items of the m3u-filter inputs.
+#[derive(Debug, Clone, Default)]
+pub struct M3uEntry {
+    pub title: Rc<String>,
+    pub url: Rc<String>,
+    pub attributes: Vec<(String, Rc<String>)>,
+}
+
+impl M3uEntry {
+    pub fn attribute(&self, name: &str) -> Option<&Rc<String>> {
+        self.attributes.iter().find(|(key, _)| key == name).map(|(_, value)| value)
+    }
+
+    pub fn set_attribute(&mut self, name: &str, value: Rc<String>) {
+        match self.attributes.iter_mut().find(|(key, _)| key == name) {
+            Some((_, current)) => *current = value,
+            None => self.attributes.push((name.to_string(), value)),
+        }
+    }
+
+    /// The `#EXTINF` and url lines of the entry.
+    pub fn to_m3u(&self) -> String {
+        let attributes: Vec<(&str, &str)> = self.attributes.iter().map(|(name, value)| (name.as_str(), value.as_str())).collect();
+        write_extinf(&attributes, &self.title, &self.url)
+    }
+}
+
+impl FieldProvider for M3uEntry {
+    fn get(&self, field: &ItemField) -> Rc<String> {
+        let attribute = |name| self.attribute(name).map_or_else(|| Rc::new(String::new()), Rc::clone);
+        match field {
+            ItemField::Group => attribute("group-title"),
+            ItemField::Name => attribute("tvg-name"),
+            ItemField::Title => Rc::clone(&self.title),
+            ItemField::Url => Rc::clone(&self.url),
+            ItemField::Type => {
+                let is_video = VIDEO_EXTENSIONS.iter().any(|extension| self.url.ends_with(extension));
+                Rc::new(if is_video { "video" } else { "live" }.to_string())
+            }
+            ItemField::Country => attribute("tvg-country"),
+            ItemField::Language => attribute("tvg-language"),
+        }
+    }
+}
+
+impl FieldAccessor for M3uEntry {
+    fn get_field(&self, field: &str) -> Option<Rc<String>> {
+        match field {
+            "title" => Some(Rc::clone(&self.title)),
+            "url" => Some(Rc::clone(&self.url)),
+            _ => ENTRY_FIELDS.iter().find(|(name, _)| *name == field)
+                .map(|(_, attribute)| self.attribute(attribute).map_or_else(|| Rc::new(String::new()), Rc::clone)),
+        }
+    }
+
+    fn set_field(&mut self, field: &str, value: &str) -> bool {
+        match field {
+            "title" => self.title = Rc::new(value.to_string()),
+            "url" => self.url = Rc::new(value.to_string()),
+            _ => match ENTRY_FIELDS.iter().find(|(name, _)| *name == field) {
+                Some((_, attribute)) => self.set_attribute(attribute, Rc::new(value.to_string())),
+                None => return false,
+            }
+        }
+        true
+    }
+}
+
+/// Parses the lines of an m3u playlist. A missing `tvg-name` is the title
+/// and a missing `group-title` is the `#EXTGRP` group or the beginning of the title.
+pub fn parse_m3u<I, S>(lines: I, strictness: M3uStrictness, report: &mut M3uParseReport) -> Vec<M3uEntry>
+where
+    I: Iterator<Item=S>,
+    S: AsRef<str>,
+{
+    let mut entries = vec![];
+    consume_m3u_lines(lines, strictness, report, |header, group, url| {
+        let extinf = parse_extinf(header).unwrap_or_default();
+        let mut entry = M3uEntry {
+            title: Rc::new(extinf.title),
+            url: Rc::new(url.to_string()),
+            attributes: Vec::with_capacity(extinf.attributes.len()),
+        };
+        for (name, value) in extinf.attributes {
+            entry.set_attribute(&name, Rc::new(value));
+        }
+        if entry.attribute("tvg-name").is_none_or(|name| name.is_empty()) {
+            entry.set_attribute("tvg-name", Rc::clone(&entry.title));
+        }
+        if entry.attribute("group-title").is_none_or(|group| group.is_empty()) {
+            let group = group.map_or_else(|| get_title_group(&entry.title), ToString::to_string);
+            entry.set_attribute("group-title", Rc::new(group));
+        }
+        entries.push(entry);
+    });
+    entries
+}
+
+/// The m3u text of the entries.
+pub fn write_m3u(entries: &[M3uEntry]) -> String {
+    let mut content = String::from("#EXTM3U\n");
+    for entry in entries {
+        content.push_str(&entry.to_m3u());
+        content.push('\n');
+    }
+    content
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::field::{FieldAccessor, FieldProvider, ItemField};
+    use crate::m3u::{parse_m3u, repair_extinf, write_m3u, M3uParseReport, M3uStrictness};
+
+    #[test]
+    fn test_repair_extinf() {
+        let line = r#"#EXTINF:-1 tvg-id="de.zdf" group-title="DE",ZDF HD"#;
+        assert_eq!(repair_extinf(line), (line.to_string(), vec![]));
+
+        let (repaired, issues) = repair_extinf(r#"#EXTINF:-1 tvg-name="The "Best" Show" tvg-id="best" tvg-id="other" group-title="Shows" The Best Show"#);
+        assert_eq!(repaired, r#"#EXTINF:-1 tvg-name="The 'Best' Show" tvg-id="best" group-title="Shows",The Best Show"#);
+        assert_eq!(issues, vec!["unescaped quote in tvg-name", "duplicated attribute tvg-id", "missing comma before title"]);
+
+        let (repaired, issues) = repair_extinf(r#"#EXTINF:tvg-id=zdf tvg-name="ZDF, HD""#);
+        assert_eq!(repaired, r#"#EXTINF:-1 tvg-id="zdf" tvg-name="ZDF, HD",ZDF, HD"#);
+        assert_eq!(issues, vec!["missing duration", "unquoted value of tvg-id", "missing title"]);
+    }
+
+    #[test]
+    fn test_parse_write_m3u() {
+        let content = "#EXTM3U\n#EXTINF:-1 tvg-id=\"zdf.de\" group-title=\"DE\",ZDF HD\nhttp://a/live/1.ts\n#EXTINF:-1,Movie\n#EXTGRP:VOD\nhttp://a/movie/2.mkv\n";
+        let entries = parse_m3u(content.lines(), M3uStrictness::Lenient, &mut M3uParseReport::default());
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].get(&ItemField::Name).as_str(), "ZDF HD");
+        assert_eq!(entries[1].get(&ItemField::Group).as_str(), "VOD");
+        assert_eq!(entries[1].get(&ItemField::Type).as_str(), "video");
+        assert_eq!(entries[0].get_field("epg_channel_id").as_deref().map(String::as_str), Some("zdf.de"));
+        assert_eq!(write_m3u(&entries), "#EXTM3U\n#EXTINF:-1 tvg-id=\"zdf.de\" group-title=\"DE\" tvg-name=\"ZDF HD\",ZDF HD\nhttp://a/live/1.ts\n\
+            #EXTINF:-1 tvg-name=\"Movie\" group-title=\"VOD\",Movie\nhttp://a/movie/2.mkv\n");
+    }
+}
@@ -0,0 +1,417 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::rc::Rc;
+use std::str::FromStr;
+
+use enum_iterator::Sequence;
+use log::{debug, error, trace};
+use regex::Regex;
+
+use crate::error::CoreError;
+use crate::field::{FieldAccessor, FieldProvider, ItemField};
+use crate::filter::{apply_templates_to_pattern, get_filter, Filter, MockValueProcessor, PatternTemplate, RegexWithCaptures, ValueProcessor};
+
+pub const MAPPER_ATTRIBUTE_FIELDS: &[&str] = &[
+    "name", "title", "group", "id", "chno", "logo",
+    "logo_small", "parent_code", "audio_track",
+    "time_shift", "rec", "url", "epg_channel_id", "epg_id", "epg_timeshift",
+    "country", "language"
+];
+
+pub const AFFIX_FIELDS: &[&str] = &["name", "title", "group"];
+
+fn capitalize(value: &str) -> String {
+    let mut chars = value.chars();
+    chars.next().map_or_else(String::new, |first_char| first_char.to_uppercase().collect::<String>() + chars.as_str())
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Default)]
+pub struct MappingTag {
+    pub name: String,
+    pub captures: Vec<String>,
+    #[serde(default)]
+    pub concat: String,
+    #[serde(default)]
+    pub prefix: String,
+    #[serde(default)]
+    pub suffix: String,
+}
+
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Sequence, PartialEq, Eq, Default)]
+pub enum TransformModifier {
+    #[serde(rename = "lowercase")]
+    #[default]
+    Lowercase,
+    #[serde(rename = "uppercase")]
+    Uppercase,
+    #[serde(rename = "capitalize")]
+    Capitalize,
+}
+
+impl TransformModifier {
+    const LOWERCASE: &'static str = "lowercase";
+    const UPPERCASE: &'static str = "uppercase";
+    const CAPITALIZE: &'static str = "capitalize";
+}
+
+impl Display for TransformModifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", match self {
+            Self::Lowercase => Self::LOWERCASE,
+            Self::Uppercase => Self::UPPERCASE,
+            Self::Capitalize => Self::CAPITALIZE,
+        })
+    }
+}
+
+impl FromStr for TransformModifier {
+    type Err = CoreError;
+
+    fn from_str(s: &str) -> Result<Self, CoreError> {
+        if s.eq("lowercase") {
+            Ok(Self::Lowercase)
+        } else if s.eq("uppercase") {
+            Ok(Self::Uppercase)
+        } else if s.eq("capitalize") {
+            Ok(Self::Capitalize)
+        } else {
+            core_error_result!("Unknown TransformModifier: {}", s)
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct MapperTransform {
+    pub field: String,
+    pub modifier: TransformModifier,
+    pub pattern: Option<String>,
+    #[serde(skip_serializing, skip_deserializing)]
+    t_pattern: Option<Regex>,
+}
+
+impl MapperTransform {
+    pub fn prepare(&mut self, templates: Option<&Vec<PatternTemplate>>) -> Result<(), CoreError> {
+        match &self.pattern {
+            None => self.t_pattern = None,
+            Some(pattern) => {
+                let mut new_pattern = pattern.to_string();
+                match templates {
+                    None => {}
+                    Some(template_list) => {
+                        new_pattern = apply_templates_to_pattern(pattern, template_list);
+                    }
+                }
+                let re = Regex::new(&new_pattern);
+                if re.is_err() {
+                    return core_error_result!("cant parse regex: {}", new_pattern);
+                }
+                self.t_pattern = Some(re.unwrap());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Default)]
+pub struct Mapper {
+    pub filter: Option<String>,
+    pub pattern: String,
+    #[serde(default)]
+    attributes: HashMap<String, String>,
+    #[serde(default)]
+    suffix: HashMap<String, String>,
+    #[serde(default)]
+    prefix: HashMap<String, String>,
+    #[serde(default)]
+    assignments: HashMap<String, String>,
+    #[serde(default)]
+    transform: Option<Vec<MapperTransform>>,
+    #[serde(skip_serializing, skip_deserializing)]
+    t_filter: Option<Filter>,
+    #[serde(skip_serializing, skip_deserializing)]
+    t_pattern: Option<Filter>,
+    #[serde(skip_serializing, skip_deserializing)]
+    t_tags: Vec<MappingTag>,
+    #[serde(skip_serializing, skip_deserializing)]
+    t_tagre: Option<Regex>,
+    #[serde(skip_serializing, skip_deserializing)]
+    t_attre: Option<Regex>,
+}
+
+impl Mapper {
+    pub fn prepare(&mut self, templates: Option<&Vec<PatternTemplate>>, tags: Option<&Vec<MappingTag>>) -> Result<(), CoreError> {
+        for key in self.attributes.keys() {
+            if !MAPPER_ATTRIBUTE_FIELDS.contains(&key.as_str()) {
+                return Err(CoreError::new(format!("Invalid mapper attribute field {key}")));
+            }
+        }
+        for key in self.suffix.keys() {
+            if !AFFIX_FIELDS.contains(&key.as_str()) {
+                return Err(CoreError::new(format!("Invalid mapper suffix field {key}")));
+            }
+        }
+        for key in self.prefix.keys() {
+            if !AFFIX_FIELDS.contains(&key.as_str()) {
+                return Err(CoreError::new(format!("Invalid mapper prefix field {key}")));
+            }
+        }
+        for (key, value) in &self.assignments {
+            if !MAPPER_ATTRIBUTE_FIELDS.contains(&key.as_str()) {
+                return Err(CoreError::new(format!("Invalid mapper assignment field {key}")));
+            }
+            if !MAPPER_ATTRIBUTE_FIELDS.contains(&value.as_str()) {
+                return Err(CoreError::new(format!("Invalid mapper assignment field {value}")));
+            }
+        }
+
+        match &mut self.transform {
+            None => {}
+            Some(transforms) => {
+                for t in transforms {
+                    let field = t.field.as_str();
+                    if !AFFIX_FIELDS.contains(&field) {
+                        return Err(CoreError::new(format!("Invalid mapper transform field {field}")));
+                    }
+                    t.prepare(templates)?;
+                }
+            }
+        }
+
+        match get_filter(&self.pattern, templates) {
+            Ok(pattern) => {
+                self.t_pattern = Some(pattern);
+                match &self.filter {
+                    Some(flt) => {
+                        match get_filter(flt, templates) {
+                            Ok(filter) => self.t_filter = Some(filter),
+                            Err(err) => return Err(err),
+                        }
+                    }
+                    _ => self.t_filter = None
+                }
+                self.t_tags = tags.map_or_else(std::vec::Vec::new, std::clone::Clone::clone);
+                self.t_tagre = Some(Regex::new("<tag:(.*?)>").unwrap());
+                self.t_attre = Some(Regex::new("<(.*?)>").unwrap());
+                Ok(())
+            }
+            Err(err) => Err(err)
+        }
+    }
+
+    /// The compiled pattern and filter of the mapper.
+    pub fn filters(&self) -> impl Iterator<Item=&Filter> {
+        self.t_pattern.iter().chain(self.t_filter.iter())
+    }
+
+    /// Maps the item if it matches the `filter`, the values for the mapping are captured by the `pattern`.
+    pub fn apply<T: FieldAccessor>(&self, provider: &dyn FieldProvider, item: &RefCell<T>) {
+        if self.t_filter.as_ref().is_none_or(|filter| filter.filter(provider, &mut MockValueProcessor {})) {
+            if let Some(pattern) = &self.t_pattern {
+                pattern.filter(provider, &mut MappingValueProcessor { item, mapper: self });
+            }
+        }
+    }
+}
+
+/// Applies the attributes, affixes, assignments and transforms of the mapper to the item
+/// with the captures of the matching pattern.
+pub struct MappingValueProcessor<'a, T: FieldAccessor> {
+    pub item: &'a RefCell<T>,
+    pub mapper: &'a Mapper,
+}
+
+impl<T: FieldAccessor> MappingValueProcessor<'_, T> {
+    fn get_property(&self, key: &str) -> Option<Rc<String>> {
+        self.item.borrow().get_field(key)
+    }
+
+    fn set_property(&self, key: &str, value: &str) {
+        if !self.item.borrow_mut().set_field(key, value) {
+            error!("Cant set unknown field {} to {}", key, value);
+        }
+        trace!("Property {} set to {}", key, value);
+    }
+
+    fn apply_attributes(&self, captured_names: &HashMap<&str, &str>) {
+        let mapper = self.mapper;
+        let attr_re = &mapper.t_attre.as_ref().unwrap();
+        let attributes = &mapper.attributes;
+        for (key, value) in attributes {
+            if value.contains('<') { // possible replacement
+                let replaced = attr_re.replace_all(value, |captures: &regex::Captures| {
+                    let capture_name = &captures[1];
+                    (*captured_names.get(&capture_name).unwrap_or(&&captures[0])).to_string()
+                });
+                self.set_property(key, &replaced);
+            } else {
+                self.set_property(key, value);
+            }
+        }
+    }
+
+    fn apply_tags(&self, value: &String, captures: &HashMap<&str, &str>) -> Option<String> {
+        let mut new_value = String::from(value);
+        let tag_captures = self.mapper.t_tagre.as_ref().unwrap().captures_iter(value)
+            .filter(|caps| caps.len() > 1)
+            .filter_map(|caps| caps.get(1))
+            .map(|caps| caps.as_str())
+            .collect::<Vec<&str>>();
+
+        for tag_capture in tag_captures {
+            for mapping_tag in &self.mapper.t_tags {
+                if mapping_tag.name.eq(tag_capture) {
+                    // we have the right tag, now get all captured values
+                    let mut captured_tag_values: Vec<&str> = Vec::new();
+                    for cap in &mapping_tag.captures {
+                        if let Some(cap_value) = captures.get(cap.as_str()) {
+                            captured_tag_values.push(cap_value);
+                        } else {
+                            debug!("Cant find any tag match for {}", tag_capture);
+                            return None;
+                        }
+                    }
+                    if !captured_tag_values.is_empty() {
+                        let captured_text = captured_tag_values.join(&mapping_tag.concat);
+                        let replacement = if captured_text.trim().is_empty() {
+                            // nothing found so replace tag with empty string
+                            String::new()
+                        } else {
+                            // Now we have all our captured values, lets create the tag
+                            format!("{}{captured_text}{}", &mapping_tag.prefix, &mapping_tag.suffix)
+                        };
+                        new_value = new_value.replace(format!("<tag:{}>", mapping_tag.name).as_str(), replacement.as_str());
+                    }
+                }
+            }
+        }
+        Some(new_value)
+    }
+
+    fn apply_suffix(&self, captures: &HashMap<&str, &str>) {
+        let mapper = self.mapper;
+        let suffix = &mapper.suffix;
+
+        for (key, value) in suffix {
+            if let Some(suffix) = self.apply_tags(value, captures) {
+                if let Some(old_value) = self.get_property(key) {
+                    let new_value = format!("{}{}", &old_value, suffix);
+                    self.set_property(key, &new_value);
+                }
+            }
+        }
+    }
+
+    fn apply_prefix(&self, captures: &HashMap<&str, &str>) {
+        let mapper = self.mapper;
+        let prefix = &mapper.prefix;
+        for (key, value) in prefix {
+            if let Some(prefix) = self.apply_tags(value, captures) {
+                if let Some(old_value) = self.get_property(key) {
+                    let new_value = format!("{}{}", prefix, &old_value);
+                    self.set_property(key, &new_value);
+                }
+            }
+        }
+    }
+
+    fn apply_assignments(&self) {
+        let mapper = self.mapper;
+        let assignments = &mapper.assignments;
+        for (key, value) in assignments {
+            if let Some(prop_value) = self.get_property(value) {
+                self.set_property(key, &prop_value);
+            }
+        }
+    }
+
+    fn apply_transform_modifier(modifier: &TransformModifier, value: &str) -> String {
+        match modifier {
+            TransformModifier::Uppercase => value.to_uppercase(),
+            TransformModifier::Lowercase => value.to_lowercase(),
+            TransformModifier::Capitalize => capitalize(value),
+        }
+    }
+
+    fn apply_transform(&self) {
+        let mapper = self.mapper;
+        match &mapper.transform {
+            None => {}
+            Some(transform_list) => {
+                for transform in transform_list {
+                    if let Some(prop_value) = self.get_property(&transform.field) {
+                        let value = transform.t_pattern.as_ref().map_or_else(|| Cow::from(Self::apply_transform_modifier(&transform.modifier, prop_value.as_str())), |regex| regex.replace_all(&prop_value, |caps: &regex::Captures| {
+                            Self::apply_transform_modifier(&transform.modifier, &caps[0])
+                        }));
+                        self.set_property(&transform.field, &value);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<T: FieldAccessor> ValueProcessor for MappingValueProcessor<'_, T> {
+    fn process<'a>(&mut self, _: &ItemField, value: &str, rewc: &RegexWithCaptures) -> bool {
+        let mut captured_values = HashMap::new();
+        if !rewc.captures.is_empty() {
+            rewc.re.captures_iter(value)
+                .filter(|caps| caps.len() > 1)
+                .for_each(|captures|
+                    for capture_name in &rewc.captures {
+                        let match_opt = captures.name(capture_name.as_str());
+                        let capture_value = if match_opt.is_some() {
+                            match_opt.map_or("", |m| m.as_str())
+                        } else {
+                            ""
+                        };
+                        debug!("match {}: {}", capture_name, capture_value);
+                        captured_values.insert(capture_name.as_str(), capture_value);
+                    }
+                );
+        }
+        Self::apply_attributes(self, &captured_values);
+        Self::apply_suffix(self, &captured_values);
+        Self::apply_prefix(self, &captured_values);
+        Self::apply_assignments(self);
+        Self::apply_transform(self);
+        true
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use crate::field::FieldAccessor;
+    use crate::m3u::{parse_m3u, M3uEntry, M3uParseReport, M3uStrictness};
+    use crate::mapper::Mapper;
+
+    #[test]
+    fn test_mapper_apply() {
+        let mut mapper: Mapper = serde_yaml::from_str(r#"
+filter: 'NOT Group ~ "VOD"'
+pattern: 'Name ~ "^(?P<country>[A-Z]{2}): (?P<name>.*)"'
+attributes:
+  name: <name>
+  group: <country> TV
+transform:
+  - field: name
+    modifier: uppercase
+"#).unwrap();
+        mapper.prepare(None, None).unwrap();
+        let content = "#EXTINF:-1 group-title=\"News\",DE: Welt\nhttp://a/1.ts\n#EXTINF:-1 group-title=\"VOD\",UK: Movie\nhttp://a/2.mkv";
+        let entries: Vec<RefCell<M3uEntry>> = parse_m3u(content.lines(), M3uStrictness::Lenient, &mut M3uParseReport::default())
+            .into_iter().map(RefCell::new).collect();
+        for entry in &entries {
+            let provider = entry.borrow().clone();
+            mapper.apply(&provider, entry);
+        }
+        let field = |idx: usize, name: &str| entries[idx].borrow().get_field(name).unwrap().to_string();
+        assert_eq!((field(0, "name"), field(0, "group")), ("WELT".to_string(), "DE TV".to_string()));
+        assert_eq!((field(1, "name"), field(1, "group")), ("UK: Movie".to_string(), "VOD".to_string()));
+    }
+}
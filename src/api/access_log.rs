@@ -0,0 +1,198 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use actix_web::body::{BodySize, MessageBody};
+use actix_web::HttpRequest;
+use actix_web::HttpResponse;
+use log::error;
+use serde::Serialize;
+
+use crate::api::api_utils::{get_client_ip, get_user_agent};
+use crate::api::connection_tracker::StreamDetails;
+use crate::model::config::{AccessLogConfig, Config};
+use crate::utils::file_utils;
+
+pub const ACCESS_LOG_KIND_PLAYLIST: &str = "playlist";
+pub const ACCESS_LOG_KIND_STREAM: &str = "stream";
+
+/// One line of the access log, written as json.
+#[derive(Serialize)]
+pub struct AccessLogEntry<'a> {
+    pub time: String,
+    pub kind: &'a str,
+    pub username: &'a str,
+    pub endpoint: &'a str,
+    pub channel: &'a str,
+    pub client_ip: &'a str,
+    pub user_agent: &'a str,
+    pub status: u16,
+    pub bytes: u64,
+    pub duration_ms: u64,
+}
+
+struct AccessLogFile {
+    path: PathBuf,
+    max_size: u64,
+    max_files: u32,
+    file: Option<File>,
+    size: u64,
+}
+
+impl AccessLogFile {
+    fn get_rotated_path(&self, index: u32) -> PathBuf {
+        let mut name = self.path.file_name().unwrap_or_default().to_os_string();
+        name.push(format!(".{index}"));
+        self.path.with_file_name(name)
+    }
+
+    /// Renames `access.log` to `access.log.1`, `access.log.1` to `access.log.2` and so on.
+    /// The oldest file is deleted.
+    fn rotate(&mut self) {
+        self.file = None;
+        if self.max_files == 0 {
+            let _ = std::fs::remove_file(&self.path);
+        } else {
+            let _ = std::fs::remove_file(self.get_rotated_path(self.max_files));
+            for index in (1..self.max_files).rev() {
+                let from = self.get_rotated_path(index);
+                if from.exists() {
+                    let _ = std::fs::rename(&from, self.get_rotated_path(index + 1));
+                }
+            }
+            let _ = std::fs::rename(&self.path, self.get_rotated_path(1));
+        }
+        self.size = 0;
+    }
+
+    fn open(path: &Path) -> std::io::Result<(File, u64)> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+        Ok((file, size))
+    }
+
+    fn write_line(&mut self, line: &[u8]) -> std::io::Result<()> {
+        if self.file.is_none() {
+            let (file, size) = Self::open(&self.path)?;
+            self.file = Some(file);
+            self.size = size;
+        }
+        if self.size > 0 && self.size + line.len() as u64 > self.max_size {
+            self.rotate();
+            let (file, size) = Self::open(&self.path)?;
+            self.file = Some(file);
+            self.size = size;
+        }
+        if let Some(file) = self.file.as_mut() {
+            file.write_all(line)?;
+            self.size += line.len() as u64;
+        }
+        Ok(())
+    }
+}
+
+/// Writes playlist and stream requests to a dedicated rotating log file, separate from the application log.
+pub struct AccessLog {
+    file: Option<Mutex<AccessLogFile>>,
+}
+
+impl AccessLog {
+    pub fn new(cfg: &Config) -> Self {
+        let file = cfg.access_log.as_ref().filter(|access_log| access_log.enabled).and_then(|access_log: &AccessLogConfig| {
+            file_utils::get_file_path(&cfg.working_dir, Some(PathBuf::from(&access_log.path))).map(|path| Mutex::new(AccessLogFile {
+                path,
+                max_size: access_log.max_size,
+                max_files: access_log.max_files,
+                file: None,
+                size: 0,
+            }))
+        });
+        Self { file }
+    }
+
+    pub const fn is_enabled(&self) -> bool {
+        self.file.is_some()
+    }
+
+    pub fn log(&self, entry: &AccessLogEntry) {
+        if let Some(file) = &self.file {
+            match serde_json::to_vec(entry) {
+                Ok(mut line) => {
+                    line.push(b'\n');
+                    let mut access_log = file.lock().unwrap();
+                    if let Err(err) = access_log.write_line(&line) {
+                        error!("Failed to write access log {}: {err}", access_log.path.to_str().unwrap_or("?"));
+                    }
+                }
+                Err(err) => error!("Failed to serialize access log entry: {err}"),
+            }
+        }
+    }
+
+    /// Logs a playlist or api request, the endpoint is the route pattern and never contains credentials.
+    pub fn log_request(&self, req: &HttpRequest, username: &str, action: &str, started: Instant, response: &HttpResponse) {
+        if !self.is_enabled() {
+            return;
+        }
+        let endpoint = get_endpoint(req);
+        let endpoint = if action.is_empty() { endpoint } else { format!("{endpoint}?action={action}") };
+        let bytes = match response.body().size() {
+            BodySize::Sized(size) => size,
+            _ => 0,
+        };
+        self.log(&AccessLogEntry {
+            time: chrono::Local::now().to_rfc3339(),
+            kind: ACCESS_LOG_KIND_PLAYLIST,
+            username,
+            endpoint: &endpoint,
+            channel: "",
+            client_ip: &get_client_ip(req),
+            user_agent: &get_user_agent(req),
+            status: response.status().as_u16(),
+            bytes,
+            duration_ms: u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX),
+        });
+    }
+
+    pub fn log_stream(&self, details: &StreamDetails, bytes: u64, duration_ms: u64) {
+        self.log(&AccessLogEntry {
+            time: chrono::Local::now().to_rfc3339(),
+            kind: ACCESS_LOG_KIND_STREAM,
+            username: &details.username,
+            endpoint: &details.endpoint,
+            channel: &details.channel,
+            client_ip: &details.client_ip,
+            user_agent: &details.user_agent,
+            status: 200,
+            bytes,
+            duration_ms,
+        });
+    }
+}
+
+/// Returns the route pattern of the request, e.g. `/live/{username}/{password}/{stream_id}`.
+pub fn get_endpoint(req: &HttpRequest) -> String {
+    req.match_pattern().unwrap_or_else(|| req.path().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::access_log::AccessLogFile;
+    use crate::utils::test_utils::create_temp_dir;
+
+    #[test]
+    fn test_rotate() {
+        let temp_dir = create_temp_dir("access_log");
+        let dir = temp_dir.path();
+        let mut access_log = AccessLogFile { path: dir.join("access.log"), max_size: 10, max_files: 2, file: None, size: 0 };
+        for line in [b"line 1\n", b"line 2\n", b"line 3\n", b"line 4\n"] {
+            access_log.write_line(line).unwrap();
+        }
+        assert_eq!(std::fs::read_to_string(dir.join("access.log")).unwrap(), "line 4\n");
+        assert_eq!(std::fs::read_to_string(dir.join("access.log.1")).unwrap(), "line 3\n");
+        assert_eq!(std::fs::read_to_string(dir.join("access.log.2")).unwrap(), "line 2\n");
+        assert!(!dir.join("access.log.3").exists());
+    }
+}
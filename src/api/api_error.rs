@@ -0,0 +1,109 @@
+use actix_web::http::StatusCode;
+use actix_web::HttpResponse;
+use serde::Serialize;
+
+/// Stable machine-readable codes of the admin api errors, a UI localizes the message by the code.
+/// Codes are only added, never renamed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiErrorCode {
+    /// the request body is not valid
+    InvalidContent,
+    /// a query or body parameter is not valid, `field` is the parameter
+    InvalidParameter,
+    /// a required parameter is missing, `field` is the parameter
+    MissingParameter,
+    /// a section of the server config is missing, `field` is the config path
+    ConfigMissing,
+    TargetNotFound,
+    InputNotFound,
+    ChannelNotFound,
+    ProgrammeNotFound,
+    JobNotFound,
+    RecordingNotFound,
+    StreamNotFound,
+    BanNotFound,
+    SnapshotNotFound,
+    FileNotFound,
+    JobFinished,
+    /// a cluster refresh needs targets with `incremental`
+    IncrementalRequired,
+    XtreamOutputRequired,
+    /// the playlist of the input could not be downloaded
+    InputFailed,
+    SaveFailed,
+    /// the role of the credentials is not sufficient for the route
+    Forbidden,
+    Internal,
+}
+
+impl ApiErrorCode {
+    pub const fn status(self) -> StatusCode {
+        match self {
+            Self::TargetNotFound | Self::InputNotFound | Self::ChannelNotFound | Self::ProgrammeNotFound | Self::JobNotFound
+            | Self::RecordingNotFound | Self::StreamNotFound | Self::BanNotFound | Self::SnapshotNotFound | Self::FileNotFound => StatusCode::NOT_FOUND,
+            Self::Forbidden => StatusCode::FORBIDDEN,
+            Self::SaveFailed | Self::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+/// Error payload of the admin api, `message` is the english text for logs and clients without localization.
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiError {
+    pub code: ApiErrorCode,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+}
+
+impl ApiError {
+    pub fn new(code: ApiErrorCode, message: impl Into<String>) -> Self {
+        Self { code, message: message.into(), field: None, hint: None }
+    }
+
+    #[must_use]
+    pub fn with_field(mut self, field: &str) -> Self {
+        self.field = Some(field.to_string());
+        self
+    }
+
+    #[must_use]
+    pub fn with_hint(mut self, hint: &str) -> Self {
+        self.hint = Some(hint.to_string());
+        self
+    }
+
+    pub fn target_not_found(target_name: &str) -> Self {
+        Self::new(ApiErrorCode::TargetNotFound, format!("Unknown target {target_name}")).with_field("target")
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::new(ApiErrorCode::Internal, message)
+    }
+}
+
+impl From<ApiError> for HttpResponse {
+    fn from(err: ApiError) -> Self {
+        Self::build(err.code.status()).json(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::StatusCode;
+    use crate::api::api_error::{ApiError, ApiErrorCode};
+
+    #[test]
+    fn test_api_error() {
+        let err = ApiError::new(ApiErrorCode::InvalidParameter, "Invalid cluster").with_field("cluster").with_hint("live, vod or series");
+        assert_eq!(serde_json::to_value(&err).unwrap(), serde_json::json!({
+            "code": "invalid_parameter", "message": "Invalid cluster", "field": "cluster", "hint": "live, vod or series"}));
+        assert_eq!(serde_json::to_value(ApiError::internal("failed")).unwrap(), serde_json::json!({"code": "internal", "message": "failed"}));
+        assert_eq!(ApiErrorCode::TargetNotFound.status(), StatusCode::NOT_FOUND);
+        assert_eq!(ApiErrorCode::SaveFailed.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
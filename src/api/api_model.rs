@@ -8,8 +8,15 @@ use chrono::{Duration, Local};
 use serde::{Deserialize, Serialize};
 use unidecode::unidecode;
 
+use crate::api::access_log::AccessLog;
+use crate::api::connection_tracker::ConnectionTracker;
+use crate::api::rate_limiter::RateLimiter;
+use crate::api::request_coalescer::RequestCoalescer;
+use crate::api::stream_broker::StreamBroker;
+use crate::processing::job_queue::JobQueue;
+use crate::processing::recorder::Recorder;
 use crate::model::api_proxy::{ApiProxyConfig, ApiProxyServerInfo, ProxyUserCredentials};
-use crate::model::config::{Config, ConfigApi, ConfigRename, ConfigSort, ConfigTargetOptions, InputType, MessagingConfig, ProcessTargets, TargetOutput, VideoConfig, VideoDownloadConfig};
+use crate::model::config::{AccessLogConfig, AccountCheckConfig, ChannelAliasesConfig, Config, ConfigApi, ConfigRename, ConfigSort, ConfigTargetOptions, DnsConfig, HealthCheckConfig, InputType, LogConfig, LogoCacheConfig, PersistRetention, MessagingConfig, ProcessingConfig, ProxyConfig, PublisherConfig, RateLimitConfig, ReadinessConfig, RecordingConfig, ReverseProxyConfig, SchedulesDirectConfig, ShortEpgConfig, TargetOutput, TargetPublishConfig, TmdbConfig, TraktConfig, VideoConfig, VideoDownloadConfig, WatchHistoryConfig, WatchdogConfig};
 use crate::model::config::ProcessingOrder;
 use crate::repository::storage::{hash_string_as_hex};
 
@@ -125,8 +132,14 @@ pub struct DownloadQueue {
 
 pub struct AppState {
     pub config: Arc<Config>,
-    pub targets: Arc<ProcessTargets>,
     pub downloads: Arc<DownloadQueue>,
+    pub connections: Arc<ConnectionTracker>,
+    pub stream_broker: Arc<StreamBroker>,
+    pub info_requests: Arc<RequestCoalescer>,
+    pub rate_limiter: Arc<RateLimiter>,
+    pub access_log: Arc<AccessLog>,
+    pub recorder: Arc<Recorder>,
+    pub jobs: Arc<JobQueue>,
 }
 
 #[derive(Serialize)]
@@ -200,6 +213,23 @@ impl XtreamAuthorizationResponse {
 }
 
 
+/// Stream path parameters, extracted by name so that the `/t/{tenant}` prefix is ignored.
+#[derive(Debug, Deserialize)]
+pub struct StreamPath {
+    pub username: String,
+    pub password: String,
+    pub stream_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TimeshiftStreamPath {
+    pub username: String,
+    pub password: String,
+    pub duration: String,
+    pub start: String,
+    pub stream_id: String,
+}
+
 #[derive(Debug, serde::Serialize, serde::Deserialize, Default)]
 pub struct UserApiRequest {
     #[serde(default)]
@@ -228,6 +258,10 @@ pub struct UserApiRequest {
     pub stream: String,
     #[serde(default)]
     pub duration: String,
+    #[serde(default)]
+    pub pin: String,
+    #[serde(default)]
+    pub search: String,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -238,6 +272,7 @@ pub struct ServerInputConfig {
     pub username: Option<String>,
     pub password: Option<String>,
     pub persist: Option<String>,
+    pub persist_retention: Option<PersistRetention>,
     pub name: Option<String>,
     pub enabled: bool,
 }
@@ -256,6 +291,9 @@ pub struct ServerTargetConfig {
     pub mapping: Option<Vec<String>>,
     pub processing_order: ProcessingOrder,
     pub watch: Option<Vec<String>>,
+    pub snapshots: usize,
+    pub incremental: bool,
+    pub publish: Option<Vec<TargetPublishConfig>>,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -268,6 +306,7 @@ pub struct ServerSourceConfig {
 pub struct ServerConfig {
     pub api: ConfigApi,
     pub threads: u8,
+    pub target_threads: u8,
     pub working_dir: String,
     pub backup_dir: Option<String>,
     pub schedule: Option<String>,
@@ -275,6 +314,26 @@ pub struct ServerConfig {
     pub messaging: Option<MessagingConfig>,
     pub video: Option<VideoConfig>,
     pub api_proxy: Option<ApiProxyConfig>,
+    pub reverse_proxy: Option<ReverseProxyConfig>,
+    pub health_check: Option<HealthCheckConfig>,
+    pub logo_cache: Option<LogoCacheConfig>,
+    pub short_epg: Option<ShortEpgConfig>,
+    pub tmdb: Option<TmdbConfig>,
+    pub trakt: Option<TraktConfig>,
+    pub schedules_direct: Option<SchedulesDirectConfig>,
+    pub channel_aliases: Option<ChannelAliasesConfig>,
+    pub watch_history: Option<WatchHistoryConfig>,
+    pub publishers: Option<Vec<PublisherConfig>>,
+    pub account_check: Option<AccountCheckConfig>,
+    pub rate_limit: Option<RateLimitConfig>,
+    pub access_log: Option<AccessLogConfig>,
+    pub watchdog: Option<WatchdogConfig>,
+    pub readiness: Option<ReadinessConfig>,
+    pub proxy: Option<ProxyConfig>,
+    pub dns: Option<DnsConfig>,
+    pub recording: Option<RecordingConfig>,
+    pub log: Option<LogConfig>,
+    pub processing: Option<ProcessingConfig>,
 }
 
 
@@ -1,12 +1,22 @@
 use std::collections::HashMap;
+use std::net::IpAddr;
+use std::str::FromStr;
 use std::path::{Path};
-use actix_web::http::header::{CACHE_CONTROL, HeaderValue};
-use actix_web::{HttpRequest, HttpResponse};
+use std::sync::Arc;
+use actix_web::http::header::{CACHE_CONTROL, CONTENT_LENGTH, HeaderValue, USER_AGENT, X_FORWARDED_FOR};
+use actix_web::{web, HttpRequest, HttpResponse};
 use log::{debug, error, log_enabled, Level};
+use bytes::Bytes;
+use futures::Stream;
+use reqwest::header::HeaderMap;
 use url::Url;
 use crate::api::api_model::{AppState, UserApiRequest};
+use crate::api::connection_tracker::{StreamDetails, TrackedStream};
+use crate::api::rate_limiter::RateLimitResult;
+use crate::api::stream_broker::{StreamBroker, StreamHeaders, StreamKey};
 use crate::model::api_proxy::{ApiProxyServerInfo, ProxyUserCredentials};
-use crate::model::config::{Config, ConfigTarget, ConfigInput};
+use crate::model::config::{Config, ConfigTarget, ConfigInput, TrustedProxy};
+use crate::processing::token_refresh;
 use crate::utils::request_utils;
 use crate::utils::request_utils::mask_sensitive_info;
 
@@ -23,47 +33,196 @@ pub async fn serve_file(file_path: &Path, req: &HttpRequest, mime_type: mime::Mi
     HttpResponse::NoContent().finish()
 }
 
+/// Returns the tenant of a request under `/t/{tenant}/`.
+pub fn get_tenant(req: &HttpRequest) -> Option<&str> {
+    req.match_info().get("tenant")
+}
+
+/// Returns the url prefix for the tenant, empty if the request is not namespaced.
+pub fn get_tenant_path(tenant: Option<&str>) -> String {
+    tenant.map_or_else(String::new, |name| format!("/t/{name}"))
+}
+
 pub fn get_user_target_by_credentials<'a>(username: &str, password: &str, api_req: &'a UserApiRequest,
-                                                 app_state: &'a AppState) -> Option<(ProxyUserCredentials, &'a ConfigTarget)> {
-    if !username.is_empty() && !password.is_empty() {
-        app_state.config.get_target_for_user(username, password)
+                                                 app_state: &'a AppState, req: &HttpRequest) -> Option<(ProxyUserCredentials, &'a ConfigTarget)> {
+    let tenant = get_tenant(req);
+    let user_target = if !username.is_empty() && !password.is_empty() {
+        app_state.config.get_target_for_user(username, password, tenant)
     } else {
         let token = api_req.token.as_str().trim();
         if token.is_empty() {
             None
         } else {
-            app_state.config.get_target_for_user_by_token(token)
+            app_state.config.get_target_for_user_by_token(token, tenant)
         }
+    };
+    user_target.map(|(mut user, target)| {
+        user.t_adult_unlocked = is_adult_unlocked(req, app_state, &user, api_req.pin.trim());
+        (user, target)
+    })
+}
+
+/// A wrong pin is counted against the client, the pin of a banned client or of a client with too many wrong pins is not checked.
+fn is_adult_unlocked(req: &HttpRequest, app_state: &AppState, user: &ProxyUserCredentials, pin: &str) -> bool {
+    if pin.is_empty() {
+        return false;
+    }
+    let client_ip = get_client_ip(req);
+    if app_state.rate_limiter.is_pin_locked(&client_ip) {
+        return false;
+    }
+    if user.matches_parental_pin(pin) {
+        true
+    } else {
+        app_state.rate_limiter.register_failed_pin(&client_ip);
+        false
     }
 }
 
-pub fn get_user_target<'a>(api_req: &'a UserApiRequest, app_state: &'a AppState) -> Option<(ProxyUserCredentials, &'a ConfigTarget)> {
+pub fn get_user_target<'a>(api_req: &'a UserApiRequest, app_state: &'a AppState, req: &HttpRequest) -> Option<(ProxyUserCredentials, &'a ConfigTarget)> {
     let username = api_req.username.as_str().trim();
     let password = api_req.password.as_str().trim();
-    get_user_target_by_credentials(username, password, api_req, app_state)
+    get_user_target_by_credentials(username, password, api_req, app_state, req)
+}
+
+/// Returns the error response if the client is banned or exceeds the rate limit.
+pub fn check_rate_limit(req: &HttpRequest, app_state: &AppState, username: &str) -> Option<HttpResponse> {
+    match app_state.rate_limiter.check(&get_client_ip(req), get_tenant(req), username) {
+        RateLimitResult::Allowed => None,
+        RateLimitResult::Limited => Some(HttpResponse::TooManyRequests().finish()),
+        RateLimitResult::Banned => Some(HttpResponse::Forbidden().finish()),
+    }
+}
+
+/// Counts the failed login of the client, too many failed logins lead to a temporary ban.
+pub fn register_failed_login(req: &HttpRequest, app_state: &AppState) {
+    app_state.rate_limiter.register_failed_login(&get_client_ip(req));
 }
 
 pub fn get_user_server_info(cfg: &Config, user: &ProxyUserCredentials) -> ApiProxyServerInfo {
     let server_info_list = cfg.t_api_proxy.read().unwrap().as_ref().unwrap().server.clone();
     let server_info_name = user.server.as_ref().map_or("default", |server_name| server_name.as_str());
-    server_info_list.iter().find(|c| c.name.eq(server_info_name)).map_or_else(|| server_info_list.first().unwrap().clone(), std::clone::Clone::clone)
+    let server_info = server_info_list.iter().find(|c| c.name.eq(server_info_name)).map_or_else(|| server_info_list.first().unwrap().clone(), std::clone::Clone::clone);
+    match cfg.api.base_url.as_ref() {
+        Some(base_url) if server_info_name == "default" => server_info.with_base_url(base_url),
+        _ => server_info,
+    }
+}
+
+/// The base url of the `default` server for the written playlist files, which are not requested by a user.
+/// `None` if neither an api-proxy server nor `api.base_url` is configured.
+pub fn get_default_base_url(cfg: &Config) -> Option<String> {
+    let server_info = cfg.t_api_proxy.read().unwrap().as_ref().and_then(|api_proxy| {
+        api_proxy.server.iter().find(|c| c.name.eq("default")).or_else(|| api_proxy.server.first()).cloned()
+    });
+    match (server_info, cfg.api.base_url.as_ref()) {
+        (Some(server_info), Some(base_url)) => Some(server_info.with_base_url(base_url).get_base_url()),
+        (Some(server_info), None) => Some(server_info.get_base_url()),
+        (None, Some(base_url)) => Some(base_url.trim_end_matches('/').to_string()),
+        (None, None) => None,
+    }
+}
+
+/// The client is the last address of `X-Forwarded-For` which is not a trusted proxy.
+fn resolve_client_ip(peer_ip: IpAddr, forwarded_for: Option<&str>, trusted_proxies: &[TrustedProxy]) -> IpAddr {
+    let is_trusted = |ip: &IpAddr| trusted_proxies.iter().any(|proxy| proxy.contains(ip));
+    if !is_trusted(&peer_ip) {
+        return peer_ip;
+    }
+    let mut client_ip = peer_ip;
+    for ip in forwarded_for.unwrap_or_default().rsplit(',').map_while(|addr| IpAddr::from_str(addr.trim()).ok()) {
+        client_ip = ip;
+        if !is_trusted(&ip) {
+            break;
+        }
+    }
+    client_ip
+}
+
+pub fn get_client_ip(req: &HttpRequest) -> String {
+    let Some(peer_ip) = req.peer_addr().map(|addr| addr.ip()) else {
+        return String::new();
+    };
+    match req.app_data::<web::Data<AppState>>() {
+        Some(app_state) if !app_state.config.api.t_trusted_proxies.is_empty() => {
+            let forwarded_for = req.headers().get(X_FORWARDED_FOR).and_then(|value| value.to_str().ok());
+            resolve_client_ip(peer_ip, forwarded_for, &app_state.config.api.t_trusted_proxies).to_string()
+        }
+        _ => peer_ip.to_string(),
+    }
+}
+
+pub fn get_user_agent(req: &HttpRequest) -> String {
+    req.headers().get(USER_AGENT).and_then(|value| value.to_str().ok()).unwrap_or_default().to_string()
+}
+
+fn shared_stream_response<S>(app_state: &AppState, headers: &[(String, Vec<u8>)], stream: S, details: StreamDetails) -> HttpResponse
+where
+    S: Stream<Item=Result<Bytes, std::io::Error>> + 'static,
+{
+    let mut response_builder = HttpResponse::Ok();
+    headers.iter()
+        .filter(|(k, _)| !k.eq_ignore_ascii_case(CONTENT_LENGTH.as_str()))
+        .for_each(|(k, v)| {
+            response_builder.insert_header((k.as_str(), v.as_slice()));
+        });
+    let active_stream = app_state.connections.register(details);
+    let tracked_stream = TrackedStream::new(Box::pin(stream), Arc::clone(&app_state.connections), active_stream, Arc::clone(&app_state.access_log));
+    response_builder.body(actix_web::body::BodyStream::new(tracked_stream))
+}
+
+fn get_stream_buffer_size(cfg: &Config) -> Option<usize> {
+    cfg.reverse_proxy.as_ref()
+        .and_then(|reverse_proxy| reverse_proxy.stream_buffer.as_ref())
+        .filter(|stream_buffer| stream_buffer.enabled)
+        .map(|stream_buffer| stream_buffer.size)
 }
 
-pub async fn stream_response(stream_url: &str, req: &HttpRequest, input: Option<&ConfigInput>) -> HttpResponse {
+/// The `stream_headers` of the input and the target, the headers of the target win.
+fn get_stream_headers(target: &ConfigTarget, input: Option<&ConfigInput>) -> HeaderMap {
+    let mut headers = input.map(|input| request_utils::to_header_map(&input.stream_headers)).unwrap_or_default();
+    if let Some(options) = target.options.as_ref() {
+        headers.extend(request_utils::to_header_map(&options.stream_headers));
+    }
+    headers
+}
+
+/// Streams the provider url to the client.
+/// If a `stream_key` is given and the stream buffer is enabled, the upstream connection is shared
+/// between all clients requesting the same stream. The `stream_headers` of the target and the input replace the client headers.
+pub async fn stream_response(app_state: &AppState, stream_url: &str, req: &HttpRequest, target: &ConfigTarget, input: Option<&ConfigInput>, details: StreamDetails, stream_key: Option<StreamKey>) -> HttpResponse {
+    let shared = stream_key.and_then(|key| get_stream_buffer_size(&app_state.config).map(|size| (key, size)));
+    if let Some((key, _)) = &shared {
+        if let Some((headers, stream)) = StreamBroker::subscribe(&app_state.stream_broker, key) {
+            if log_enabled!(Level::Debug) {
+                debug!("Using shared stream {}", mask_sensitive_info(stream_url));
+            }
+            return shared_stream_response(app_state, &headers, stream, details);
+        }
+    }
+    let stream_url = token_refresh::rewrite_token_url(input, stream_url);
+    let stream_url = stream_url.as_ref();
     let req_headers: HashMap<&str, &[u8]> = req.headers().iter().map(|(k, v)| (k.as_str(), v.as_bytes())).collect();
     if log_enabled!(Level::Debug) {
         debug!("Try to open stream {}", mask_sensitive_info(stream_url));
     }
     if let Ok(url) = Url::parse(stream_url) {
-        let client = request_utils::get_client_request(input, &url, Some(&req_headers));
-        match client.send().await {
+        let client = request_utils::get_client_request(input, &url, Some(&req_headers)).headers(get_stream_headers(target, input));
+        match request_utils::send_with_retries(input, client).await {
             Ok(response) => {
                 if response.status().is_success() {
+                    if let Some((key, size)) = shared {
+                        let headers: StreamHeaders = response.headers().iter().map(|(k, v)| (k.to_string(), v.as_bytes().to_vec())).collect();
+                        let (headers, stream) = StreamBroker::register(&app_state.stream_broker, key, response.bytes_stream(), headers, size);
+                        return shared_stream_response(app_state, &headers, stream, details);
+                    }
                     let mut response_builder = HttpResponse::Ok();
                     response.headers().iter().for_each(|(k, v)| {
                         response_builder.insert_header((k.as_str(), v.as_ref()));
                     });
-                    return response_builder.body(actix_web::body::BodyStream::new(response.bytes_stream()));
+                    let active_stream = app_state.connections.register(details);
+                    let tracked_stream = TrackedStream::new(Box::pin(response.bytes_stream()), Arc::clone(&app_state.connections), active_stream, Arc::clone(&app_state.access_log));
+                    return response_builder.body(actix_web::body::BodyStream::new(tracked_stream));
                 }
                 if log_enabled!(Level::Debug) {
                     debug!("Failed to open stream got status {} for {}", response.status(), mask_sensitive_info(stream_url));
@@ -78,3 +237,26 @@ pub async fn stream_response(stream_url: &str, req: &HttpRequest, input: Option<
     }
     HttpResponse::BadRequest().finish()
 }
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+    use std::str::FromStr;
+
+    use crate::api::api_utils::resolve_client_ip;
+    use crate::model::config::TrustedProxy;
+
+    #[test]
+    fn test_resolve_client_ip() {
+        let trusted = vec![TrustedProxy::from_str("10.0.0.0/8").unwrap(), TrustedProxy::from_str("::1").unwrap()];
+        let ip = |addr: &str| IpAddr::from_str(addr).unwrap();
+        assert_eq!(resolve_client_ip(ip("10.1.2.3"), Some("203.0.113.7, 10.0.0.2"), &trusted), ip("203.0.113.7"));
+        assert_eq!(resolve_client_ip(ip("::1"), Some("1.1.1.1, 203.0.113.7"), &trusted), ip("203.0.113.7"));
+        // untrusted peers can't spoof the client address
+        assert_eq!(resolve_client_ip(ip("192.168.1.5"), Some("203.0.113.7"), &trusted), ip("192.168.1.5"));
+        assert_eq!(resolve_client_ip(ip("10.1.2.3"), None, &trusted), ip("10.1.2.3"));
+        assert!(TrustedProxy::from_str("10.0.0.0/33").is_err());
+        // ipv4 peers of a dual stack listener
+        assert_eq!(resolve_client_ip(ip("::ffff:10.1.2.3"), Some("203.0.113.7"), &trusted), ip("203.0.113.7"));
+    }
+}
@@ -0,0 +1,41 @@
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse};
+use chrono::Local;
+use log::error;
+use serde::Serialize;
+
+use crate::api::api_model::AppState;
+use crate::api::api_utils::get_client_ip;
+use crate::auth::authenticator::Principal;
+use crate::repository::audit_repository::{audit_diff, audit_log_append, audit_log_query, AuditEntry, AuditQuery, AuditSection};
+
+/// Without `web_auth` the changes are recorded without user.
+const ANONYMOUS_USER: &str = "anonymous";
+
+/// Records the changes between the old and the new config section in the audit log.
+/// A failed audit log write is logged, the saved config is not rolled back.
+pub fn audit_config_change<T: Serialize + ?Sized, S: Serialize + ?Sized>(req: &HttpRequest, app_state: &AppState, section: AuditSection, old: &T, new: &S) {
+    let (Ok(old), Ok(new)) = (serde_json::to_value(old), serde_json::to_value(new)) else {
+        error!("Failed to serialize the config change for the audit log");
+        return;
+    };
+    let principal = req.extensions().get::<Principal>().cloned();
+    let entry = AuditEntry {
+        timestamp: Local::now().timestamp(),
+        user: principal.as_ref().map_or_else(|| ANONYMOUS_USER.to_string(), |principal| principal.name.clone()),
+        role: principal.map(|principal| principal.role),
+        client_ip: get_client_ip(req),
+        section,
+        changes: audit_diff(&old, &new),
+    };
+    if let Err(err) = audit_log_append(&app_state.config, &entry) {
+        error!("{err}");
+    }
+}
+
+/// The config changes, the latest first.
+pub async fn audit_log(
+    query: web::Query<AuditQuery>,
+    app_state: web::Data<AppState>,
+) -> HttpResponse {
+    HttpResponse::Ok().json(audit_log_query(&app_state.config, &query))
+}
@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use bytes::Bytes;
+use futures::Stream;
+use futures::task::AtomicWaker;
+use serde::Serialize;
+
+use crate::api::access_log::AccessLog;
+
+/// Information about a proxied stream, provided when the stream is opened.
+pub struct StreamDetails {
+    pub username: String,
+    pub channel: String,
+    pub input: String,
+    pub client_ip: String,
+    pub user_agent: String,
+    pub endpoint: String,
+}
+
+/// A currently proxied stream.
+pub struct ActiveStream {
+    pub id: u64,
+    pub details: StreamDetails,
+    pub start_time: i64,
+    bytes: AtomicU64,
+    kicked: AtomicBool,
+    waker: AtomicWaker,
+}
+
+/// Snapshot of an active stream for the api.
+#[derive(Serialize)]
+pub struct ActiveStreamInfo {
+    pub id: u64,
+    pub username: String,
+    pub channel: String,
+    pub input: String,
+    pub client_ip: String,
+    pub start_time: i64,
+    pub bytes: u64,
+}
+
+/// Keeps track of all streams which are currently proxied to clients.
+#[derive(Default)]
+pub struct ConnectionTracker {
+    next_id: AtomicU64,
+    streams: RwLock<HashMap<u64, Arc<ActiveStream>>>,
+}
+
+impl ConnectionTracker {
+    pub fn register(&self, details: StreamDetails) -> Arc<ActiveStream> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let stream = Arc::new(ActiveStream {
+            id,
+            details,
+            start_time: chrono::Local::now().timestamp(),
+            bytes: AtomicU64::new(0),
+            kicked: AtomicBool::new(false),
+            waker: AtomicWaker::new(),
+        });
+        self.streams.write().unwrap().insert(id, Arc::clone(&stream));
+        stream
+    }
+
+    pub fn unregister(&self, id: u64) {
+        self.streams.write().unwrap().remove(&id);
+    }
+
+    /// Marks the stream to be closed and wakes it, the stream ends even when the upstream sends no data.
+    pub fn kick(&self, id: u64) -> bool {
+        self.streams.write().unwrap().remove(&id).is_some_and(|stream| {
+            stream.kicked.store(true, Ordering::Release);
+            stream.waker.wake();
+            true
+        })
+    }
+
+    pub fn get_active_streams(&self) -> Vec<ActiveStreamInfo> {
+        let mut result: Vec<ActiveStreamInfo> = self.streams.read().unwrap().values().map(|stream| ActiveStreamInfo {
+            id: stream.id,
+            username: stream.details.username.clone(),
+            channel: stream.details.channel.clone(),
+            input: stream.details.input.clone(),
+            client_ip: stream.details.client_ip.clone(),
+            start_time: stream.start_time,
+            bytes: stream.bytes.load(Ordering::Relaxed),
+        }).collect();
+        result.sort_by_key(|s| s.id);
+        result
+    }
+}
+
+/// Wraps the upstream byte stream, counts the transferred bytes
+/// and unregisters the stream from the tracker when the client disconnects.
+pub struct TrackedStream<E> {
+    inner: Pin<Box<dyn Stream<Item=Result<Bytes, E>>>>,
+    tracker: Arc<ConnectionTracker>,
+    stream: Arc<ActiveStream>,
+    access_log: Arc<AccessLog>,
+    started: Instant,
+}
+
+impl<E> TrackedStream<E> {
+    pub fn new(inner: Pin<Box<dyn Stream<Item=Result<Bytes, E>>>>, tracker: Arc<ConnectionTracker>, stream: Arc<ActiveStream>, access_log: Arc<AccessLog>) -> Self {
+        Self { inner, tracker, stream, access_log, started: Instant::now() }
+    }
+}
+
+impl<E> Stream for TrackedStream<E> {
+    type Item = Result<Bytes, E>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        // register before checking the flag, otherwise a kick in between would be lost
+        self.stream.waker.register(cx.waker());
+        if self.stream.kicked.load(Ordering::Acquire) {
+            return Poll::Ready(None);
+        }
+        let result = self.inner.as_mut().poll_next(cx);
+        if let Poll::Ready(Some(Ok(chunk))) = &result {
+            self.stream.bytes.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+        }
+        result
+    }
+}
+
+impl<E> Drop for TrackedStream<E> {
+    fn drop(&mut self) {
+        self.tracker.unregister(self.stream.id);
+        if self.access_log.is_enabled() {
+            let duration_ms = u64::try_from(self.started.elapsed().as_millis()).unwrap_or(u64::MAX);
+            self.access_log.log_stream(&self.stream.details, self.stream.bytes.load(Ordering::Relaxed), duration_ms);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use bytes::Bytes;
+    use futures::StreamExt;
+
+    use crate::api::access_log::AccessLog;
+    use crate::api::connection_tracker::{ConnectionTracker, StreamDetails, TrackedStream};
+    use crate::model::config::Config;
+
+    #[actix_rt::test]
+    async fn test_kick_pending_stream() {
+        let tracker = Arc::new(ConnectionTracker::default());
+        let details = StreamDetails {
+            username: "user".to_string(),
+            channel: "News".to_string(),
+            input: "input".to_string(),
+            client_ip: "127.0.0.1".to_string(),
+            user_agent: String::new(),
+            endpoint: "live".to_string(),
+        };
+        let stream = tracker.register(details);
+        let id = stream.id;
+        let mut tracked = TrackedStream::<std::io::Error>::new(Box::pin(futures::stream::pending::<Result<Bytes, std::io::Error>>()),
+                                                               Arc::clone(&tracker), stream, Arc::new(AccessLog::new(&Config::default())));
+        let kicker = Arc::clone(&tracker);
+        actix_rt::spawn(async move {
+            actix_rt::time::sleep(std::time::Duration::from_millis(50)).await;
+            assert!(kicker.kick(id));
+        });
+        let next = actix_rt::time::timeout(std::time::Duration::from_secs(5), tracked.next()).await;
+        assert!(matches!(next, Ok(None)));
+        assert!(tracker.get_active_streams().is_empty());
+    }
+}
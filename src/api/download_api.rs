@@ -5,15 +5,30 @@ use std::ops::Deref;
 use std::sync::{Arc, RwLock};
 use actix_web::{HttpResponse, web};
 use serde_json::{json, Value};
+use crate::api::api_error::{ApiError, ApiErrorCode};
 use crate::api::api_model::{AppState, DownloadQueue, FileDownload, FileDownloadRequest};
-use crate::model::config::{VideoDownloadConfig};
+use crate::model::config::{Config, ConfigInput, VideoDownloadConfig};
 use futures::stream::TryStreamExt;
 use log::{info};
+use crate::repository::storage::hash_string;
+use crate::repository::url_index::url_index_find;
 use crate::utils::{request_utils};
 
-async fn download_file(active: Arc<RwLock<Option<FileDownload>>>, client: &reqwest::Client) -> Result<(), String> {
+/// The input of a provider url from the url index, otherwise the input with the same host.
+fn get_download_input<'a>(cfg: &'a Config, url: &reqwest::Url) -> Option<&'a ConfigInput> {
+    url_index_find(cfg, &hash_string(url.as_str())).first().and_then(|entry| cfg.get_input_by_id(entry.input_id))
+        .or_else(|| cfg.sources.iter().flat_map(|source| &source.inputs)
+            .find(|input| reqwest::Url::parse(&input.url).is_ok_and(|input_url| input_url.host_str().is_some() && input_url.host_str() == url.host_str())))
+}
+
+/// The file is downloaded with the client and the headers of its input, the headers of the download config take precedence.
+async fn download_file(active: Arc<RwLock<Option<FileDownload>>>, cfg: &Config, download_cfg: &VideoDownloadConfig) -> Result<(), String> {
     let file_download = { active.read().unwrap().as_ref().unwrap().clone() };
-    match client.get(file_download.url.clone()).send().await {
+    let input = get_download_input(cfg, &file_download.url);
+    let mut headers = request_utils::get_request_headers(input.map(|i| &i.headers), None);
+    headers.extend(request_utils::get_request_headers(Some(&download_cfg.headers), None));
+    let client = request_utils::get_http_client(cfg, input);
+    match client.get(file_download.url.clone()).headers(headers).send().await {
         Ok(response) => {
             match fs::create_dir_all(&file_download.file_dir) {
                 Ok(()) => {
@@ -58,43 +73,38 @@ async fn download_file(active: Arc<RwLock<Option<FileDownload>>>, client: &reqwe
     }
 }
 
-fn run_download_queue(download_cfg: &VideoDownloadConfig, download_queue: &Arc<DownloadQueue>) -> Result<(), String> {
+fn run_download_queue(cfg: &Arc<Config>, download_cfg: &VideoDownloadConfig, download_queue: &Arc<DownloadQueue>) {
     let next_download = download_queue.as_ref().queue.lock().unwrap().pop_front();
     if next_download.is_some() {
         { *download_queue.as_ref().active.write().unwrap() = next_download; }
-        let headers = request_utils::get_request_headers(Some(&download_cfg.headers), None);
         let dq = Arc::clone(download_queue);
-        match reqwest::Client::builder().default_headers(headers).build() {
-            Ok(client) => {
-                actix_rt::spawn(async move {
-                    loop {
-                        if dq.active.read().unwrap().deref().is_some() {
-                            match download_file(Arc::clone(&dq.active), &client).await {
-                                Ok(()) => {
-                                    if let Some(fd) = &mut *dq.active.write().unwrap() {
-                                        fd.finished = true;
-                                        dq.finished.write().unwrap().push(fd.clone());
-                                    }
-                                }
-                                Err(err) => {
-                                    if let Some(fd) = &mut *dq.active.write().unwrap() {
-                                        fd.finished = true;
-                                        fd.error = Some(err);
-                                        dq.finished.write().unwrap().push(fd.clone());
-                                    }
-                                }
+        let cfg = Arc::clone(cfg);
+        let download_cfg = download_cfg.clone();
+        actix_rt::spawn(async move {
+            loop {
+                if dq.active.read().unwrap().deref().is_some() {
+                    match download_file(Arc::clone(&dq.active), &cfg, &download_cfg).await {
+                        Ok(()) => {
+                            if let Some(fd) = &mut *dq.active.write().unwrap() {
+                                fd.finished = true;
+                                dq.finished.write().unwrap().push(fd.clone());
+                            }
+                        }
+                        Err(err) => {
+                            if let Some(fd) = &mut *dq.active.write().unwrap() {
+                                fd.finished = true;
+                                fd.error = Some(err);
+                                dq.finished.write().unwrap().push(fd.clone());
                             }
-                            *dq.active.write().unwrap() = dq.queue.lock().unwrap().pop_front();
-                        } else {
-                            break;
                         }
                     }
-                });
+                    *dq.active.write().unwrap() = dq.queue.lock().unwrap().pop_front();
+                } else {
+                    break;
+                }
             }
-            Err(_) => return Err("Failed to build http client".to_string()),
-        }
+        });
     }
-    Ok(())
 }
 
 
@@ -112,24 +122,21 @@ pub async fn queue_download_file(
 ) -> HttpResponse {
     if let Some(download_cfg) = &app_state.config.video.as_ref().unwrap().download {
         if download_cfg.directory.is_none() {
-            return HttpResponse::BadRequest().json(json!({"error": "Server config missing video.download.directory configuration"}));
+            return ApiError::new(ApiErrorCode::ConfigMissing, "Server config missing video.download.directory configuration").with_field("video.download.directory").into();
         }
         match FileDownload::new(req.url.as_str(), req.filename.as_str(), download_cfg) {
             Some(file_download) => {
                 let response = HttpResponse::Ok().json(download_info!(file_download));
                 app_state.downloads.queue.lock().unwrap().push_back(file_download);
                 if app_state.downloads.active.read().unwrap().is_none() {
-                    match run_download_queue(download_cfg, &app_state.downloads) {
-                        Ok(()) => {}
-                        Err(err) => return HttpResponse::InternalServerError().json(json!({"error": err})),
-                    }
+                    run_download_queue(&app_state.config, download_cfg, &app_state.downloads);
                 }
                 response
             }
-            None => HttpResponse::BadRequest().json(json!({"error": "Invalid Arguments"})),
+            None => ApiError::new(ApiErrorCode::InvalidParameter, "Invalid url or filename").with_field("url").into(),
         }
     } else {
-        HttpResponse::BadRequest().json(json!({"error": "Server config missing video.download configuration"}))
+        ApiError::new(ApiErrorCode::ConfigMissing, "Server config missing video.download configuration").with_field("video.download").into()
     }
 }
 
@@ -0,0 +1,68 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use log::error;
+
+use crate::api::api_model::{AppState, UserApiRequest};
+use crate::api::api_utils::{check_rate_limit, get_user_target, register_failed_login};
+use crate::repository::favorites_repository::{favorites_get_virtual_ids, favorites_update};
+
+async fn favorites_list(
+    req: HttpRequest,
+    api_req: web::Query<UserApiRequest>,
+    app_state: web::Data<AppState>,
+) -> HttpResponse {
+    if let Some(response) = check_rate_limit(&req, &app_state, api_req.username.trim()) {
+        return response;
+    }
+    match get_user_target(&api_req, &app_state, &req) {
+        Some((user, target)) => HttpResponse::Ok().json(favorites_get_virtual_ids(&app_state.config, &target.name, &user.username)),
+        None => {
+            register_failed_login(&req, &app_state);
+            HttpResponse::BadRequest().finish()
+        }
+    }
+}
+
+fn favorites_set(req: &HttpRequest, api_req: &UserApiRequest, stream_id: &str, app_state: &AppState, favorite: bool) -> HttpResponse {
+    if let Some(response) = check_rate_limit(req, app_state, api_req.username.trim()) {
+        return response;
+    }
+    let Some((user, target)) = get_user_target(api_req, app_state, req) else {
+        register_failed_login(req, app_state);
+        return HttpResponse::BadRequest().finish();
+    };
+    let Ok(virtual_id) = stream_id.trim().parse::<u32>() else {
+        return HttpResponse::BadRequest().finish();
+    };
+    match favorites_update(&app_state.config, &target.name, &user.username, virtual_id, favorite) {
+        Ok(()) => HttpResponse::Ok().finish(),
+        Err(err) => {
+            error!("Failed to update favorites of user {}: {err}", user.username);
+            HttpResponse::NotFound().finish()
+        }
+    }
+}
+
+async fn favorites_add(
+    req: HttpRequest,
+    api_req: web::Query<UserApiRequest>,
+    stream_id: web::Path<String>,
+    app_state: web::Data<AppState>,
+) -> HttpResponse {
+    favorites_set(&req, &api_req, &stream_id, &app_state, true)
+}
+
+async fn favorites_remove(
+    req: HttpRequest,
+    api_req: web::Query<UserApiRequest>,
+    stream_id: web::Path<String>,
+    app_state: web::Data<AppState>,
+) -> HttpResponse {
+    favorites_set(&req, &api_req, &stream_id, &app_state, false)
+}
+
+pub fn favorites_api_register(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/favorites").route(web::get().to(favorites_list)))
+        .service(web::resource("/favorites/{stream_id}")
+            .route(web::put().to(favorites_add))
+            .route(web::delete().to(favorites_remove)));
+}
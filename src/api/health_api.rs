@@ -0,0 +1,96 @@
+use std::path::PathBuf;
+
+use actix_web::{web, HttpResponse};
+
+use crate::api::api_model::AppState;
+use crate::model::config::Config;
+use crate::model::healthcheck::{Healthcheck, Readiness, ReadinessCheck};
+use crate::VERSION;
+
+/// File written into the `working_dir` to check that the storage is writable.
+const STORAGE_PROBE_FILE: &str = ".readyz";
+
+const fn check(ok: bool, detail: String) -> ReadinessCheck {
+    ReadinessCheck { ok, detail }
+}
+
+fn check_config(cfg: &Config) -> ReadinessCheck {
+    let targets = cfg.sources.iter().map(|source| source.targets.len()).sum::<usize>();
+    check(targets > 0, format!("{} sources, {targets} targets", cfg.sources.len()))
+}
+
+async fn check_storage(cfg: &Config) -> ReadinessCheck {
+    let path = PathBuf::from(&cfg.working_dir).join(STORAGE_PROBE_FILE);
+    match tokio::fs::write(&path, VERSION).await {
+        Ok(()) => {
+            let _ = tokio::fs::remove_file(&path).await;
+            check(true, cfg.working_dir.clone())
+        }
+        Err(err) => check(false, format!("{} is not writable: {err}", cfg.working_dir)),
+    }
+}
+
+/// The last successful refresh has to be within `refresh_sla` seconds, a `refresh_sla` of 0 disables the check.
+fn check_refresh(last_completed: Option<i64>, refresh_sla: u64, now: i64) -> ReadinessCheck {
+    match last_completed {
+        Some(finished) => {
+            let age = u64::try_from(now - finished).unwrap_or(0);
+            check(refresh_sla == 0 || age <= refresh_sla, format!("last refresh {age} seconds ago"))
+        }
+        None => check(refresh_sla == 0, "no refresh completed".to_string()),
+    }
+}
+
+/// Liveness, the server answers requests.
+async fn healthcheck() -> HttpResponse {
+    let ts = chrono::offset::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    HttpResponse::Ok().json( Healthcheck {
+        status: "ok".to_string(),
+        version: VERSION.to_string(),
+        time: ts
+    })
+}
+
+/// Readiness, the config is loaded, the storage is writable and the playlists are up to date.
+/// Responds with `503` if one of the checks fails.
+async fn readyz(
+    app_state: web::Data<AppState>,
+) -> HttpResponse {
+    let cfg = &app_state.config;
+    let refresh_sla = cfg.readiness.as_ref().map_or(0, |readiness| readiness.refresh_sla);
+    let config = check_config(cfg);
+    let storage = check_storage(cfg).await;
+    let refresh = check_refresh(app_state.jobs.last_completed(), refresh_sla, chrono::Utc::now().timestamp());
+    let ready = config.ok && storage.ok && refresh.ok;
+    let readiness = Readiness {
+        status: if ready { "ok" } else { "fail" }.to_string(),
+        config,
+        storage,
+        refresh,
+    };
+    if ready {
+        HttpResponse::Ok().json(readiness)
+    } else {
+        HttpResponse::ServiceUnavailable().json(readiness)
+    }
+}
+
+pub fn health_api_register(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/healthcheck").route(web::get().to(healthcheck)));
+    cfg.service(web::resource("/healthz").route(web::get().to(healthcheck)));
+    cfg.service(web::resource("/readyz").route(web::get().to(readyz)));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::health_api::check_refresh;
+
+    #[test]
+    fn test_check_refresh() {
+        assert!(check_refresh(None, 0, 1000).ok);
+        assert!(!check_refresh(None, 60, 1000).ok);
+        assert!(check_refresh(Some(950), 60, 1000).ok);
+        assert!(!check_refresh(Some(900), 60, 1000).ok);
+        assert!(check_refresh(Some(0), 0, 1000).ok);
+    }
+}
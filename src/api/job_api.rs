@@ -0,0 +1,139 @@
+use std::time::Duration;
+
+use actix_web::{http::header, web, HttpResponse};
+use futures::StreamExt;
+use bytes::Bytes;
+use log::error;
+use serde::Deserialize;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::api::api_error::{ApiError, ApiErrorCode};
+use crate::api::api_model::AppState;
+use crate::model::config::validate_targets;
+use crate::model::playlist::XtreamCluster;
+use crate::processing::job_queue::JobEvent;
+use crate::repository::job_repository::JobTrigger;
+use crate::utils::request_utils::mask_sensitive_info;
+
+/// Interval in seconds a comment is sent to keep an idle event stream open.
+const EVENTS_KEEP_ALIVE_SECS: u64 = 15;
+
+#[derive(Debug, Deserialize)]
+pub struct JobRequest {
+    /// target names, all enabled targets if empty
+    #[serde(default)]
+    pub targets: Vec<String>,
+    /// comma separated `live`, `vod` and `series`
+    #[serde(default)]
+    pub cluster: Option<String>,
+}
+
+fn parse_refresh_clusters(value: &str) -> Option<Vec<XtreamCluster>> {
+    let mut clusters = vec![];
+    for name in value.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+        let cluster = match name.to_lowercase().as_str() {
+            "live" => XtreamCluster::Live,
+            "vod" | "video" | "movie" => XtreamCluster::Video,
+            "series" => XtreamCluster::Series,
+            _ => return None,
+        };
+        if !clusters.contains(&cluster) {
+            clusters.push(cluster);
+        }
+    }
+    (!clusters.is_empty()).then_some(clusters)
+}
+
+/// Validates the targets and queues the processing, the response is the queued job.
+/// A cluster refresh needs targets with incremental enabled.
+pub fn enqueue_job(app_state: &AppState, targets: Vec<String>, cluster: Option<&str>) -> HttpResponse {
+    if let Err(err) = validate_targets(Some(&targets).filter(|targets| !targets.is_empty()), &app_state.config.sources) {
+        error!("Failed playlist update {}", mask_sensitive_info(err.to_string().as_str()));
+        return ApiError::new(ApiErrorCode::TargetNotFound, err.to_string()).with_field("targets").into();
+    }
+    let clusters = match cluster {
+        Some(cluster) => {
+            let Some(clusters) = parse_refresh_clusters(cluster) else {
+                return ApiError::new(ApiErrorCode::InvalidParameter, "Invalid cluster").with_field("cluster").with_hint("comma separated live, vod and series").into();
+            };
+            let incremental = !targets.is_empty() && app_state.config.sources.iter().flat_map(|source| &source.targets)
+                .filter(|target| targets.iter().any(|name| target.name.eq_ignore_ascii_case(name)))
+                .all(|target| target.incremental);
+            if !incremental {
+                return ApiError::new(ApiErrorCode::IncrementalRequired, "Cluster refresh needs a target with incremental enabled").with_field("targets").into();
+            }
+            Some(clusters)
+        }
+        None => None,
+    };
+    HttpResponse::Ok().json(app_state.jobs.enqueue(JobTrigger::Api, Some(targets), clusters))
+}
+
+fn job_not_found(id: &str) -> HttpResponse {
+    ApiError::new(ApiErrorCode::JobNotFound, format!("Unknown job {id}")).with_field("id").into()
+}
+
+pub async fn job_list(
+    app_state: web::Data<AppState>,
+) -> HttpResponse {
+    HttpResponse::Ok().json(app_state.jobs.list())
+}
+
+pub async fn job_create(
+    req: web::Json<JobRequest>,
+    app_state: web::Data<AppState>,
+) -> HttpResponse {
+    let JobRequest { targets, cluster } = req.into_inner();
+    enqueue_job(&app_state, targets, cluster.as_deref())
+}
+
+pub async fn job_get(
+    path: web::Path<String>,
+    app_state: web::Data<AppState>,
+) -> HttpResponse {
+    let id = path.into_inner();
+    app_state.jobs.get(&id)
+        .map_or_else(|| job_not_found(&id), |job| HttpResponse::Ok().json(job))
+}
+
+pub async fn job_cancel(
+    path: web::Path<String>,
+    app_state: web::Data<AppState>,
+) -> HttpResponse {
+    let id = path.into_inner();
+    match app_state.jobs.get(&id) {
+        Some(job) if job.status.is_finished() => ApiError::new(ApiErrorCode::JobFinished, "Job is finished").into(),
+        Some(_) => {
+            app_state.jobs.cancel(&id);
+            HttpResponse::Ok().finish()
+        }
+        None => job_not_found(&id),
+    }
+}
+
+/// Streams the job changes, progress and log lines as server-sent events.
+/// The stream starts with the queued and running jobs.
+pub async fn job_events(
+    app_state: web::Data<AppState>,
+) -> HttpResponse {
+    let receiver = app_state.jobs.subscribe();
+    let initial: String = app_state.jobs.list().into_iter()
+        .filter(|job| !job.status.is_finished())
+        .map(|job| JobEvent::Job(job).to_sse())
+        .collect();
+    let body = futures::stream::once(async move { Ok::<Bytes, std::io::Error>(Bytes::from(format!(": connected\n\n{initial}"))) })
+        .chain(futures::stream::unfold(receiver, |mut receiver| async move {
+            loop {
+                match actix_rt::time::timeout(Duration::from_secs(EVENTS_KEEP_ALIVE_SECS), receiver.recv()).await {
+                    Ok(Ok(event)) => return Some((Ok(Bytes::from(event.to_sse())), receiver)),
+                    Ok(Err(RecvError::Lagged(_))) => {}
+                    Ok(Err(RecvError::Closed)) => return None,
+                    Err(_) => return Some((Ok(Bytes::from_static(b": keep-alive\n\n")), receiver)),
+                }
+            }
+        }));
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .streaming(body)
+}
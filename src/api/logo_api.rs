@@ -0,0 +1,42 @@
+use actix_web::http::header::{CACHE_CONTROL, ETAG, IF_NONE_MATCH};
+use actix_web::{web, HttpRequest, HttpResponse};
+use log::error;
+
+use crate::api::api_model::AppState;
+use crate::processing::logo_cache::{get_cached_logo_path, get_logo_mime_type};
+use crate::utils::default_utils::default_logo_cache_max_age;
+
+async fn logo_api(
+    req: HttpRequest,
+    logo_id: web::Path<String>,
+    app_state: web::Data<AppState>,
+) -> HttpResponse {
+    let logo_id = logo_id.into_inner();
+    let Some(path) = get_cached_logo_path(&app_state.config, &logo_id) else {
+        return HttpResponse::NotFound().finish();
+    };
+    let etag = format!("\"{logo_id}\"");
+    let max_age = app_state.config.logo_cache.as_ref().map_or_else(default_logo_cache_max_age, |logo_cache| logo_cache.max_age);
+    let cache_control = format!("public, max-age={max_age}");
+    if req.headers().get(IF_NONE_MATCH).and_then(|value| value.to_str().ok()).is_some_and(|value| value == etag) {
+        return HttpResponse::NotModified()
+            .insert_header((ETAG, etag))
+            .insert_header((CACHE_CONTROL, cache_control))
+            .finish();
+    }
+    match tokio::fs::read(&path).await {
+        Ok(content) => HttpResponse::Ok()
+            .content_type(get_logo_mime_type(&content))
+            .insert_header((ETAG, etag))
+            .insert_header((CACHE_CONTROL, cache_control))
+            .body(content),
+        Err(err) => {
+            error!("Failed to read logo {}: {err}", path.to_str().unwrap_or("?"));
+            HttpResponse::NotFound().finish()
+        }
+    }
+}
+
+pub fn logo_api_register(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/logo/{id}").route(web::get().to(logo_api)));
+}
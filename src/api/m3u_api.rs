@@ -1,29 +1,115 @@
-use actix_web::{HttpRequest, HttpResponse, web};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use actix_web::http::header::{EntityTag, ETag, HeaderValue, HttpDate, IfModifiedSince, IfNoneMatch, ETAG, LAST_MODIFIED, RANGE};
+use actix_web::{HttpMessage, HttpRequest, HttpResponse, web};
 use log::{debug, error};
 use futures::{stream};
 use bytes::Bytes;
 
-use crate::api::api_utils::{get_user_target, get_user_target_by_credentials, stream_response};
-use crate::api::api_model::{AppState, UserApiRequest};
+use crate::api::access_log::get_endpoint;
+use crate::api::api_utils::{check_rate_limit, get_client_ip, get_tenant, get_user_agent, get_user_target, get_user_target_by_credentials, register_failed_login, stream_response};
+use crate::api::connection_tracker::StreamDetails;
+use crate::api::stream_broker::StreamKey;
+use crate::api::api_model::{AppState, StreamPath, UserApiRequest};
+use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorCategory, M3uFilterErrorKind};
 use crate::model::api_proxy::ProxyType;
 use crate::model::config::TargetType;
-use crate::repository::m3u_repository::{m3u_get_file_paths, m3u_get_item_for_stream_id, m3u_load_rewrite_playlist};
+use crate::model::playlist::PlaylistItemType;
+use crate::processing::token_refresh;
+use crate::repository::m3u_repository::{m3u_get_file_paths, m3u_get_item_for_stream_id, m3u_get_playlist_file, m3u_get_playlist_version, m3u_load_rewrite_playlist};
 use crate::repository::storage::get_target_storage_path;
 use crate::utils::request_utils::mask_sensitive_info;
 
-fn m3u_api(
+async fn m3u_api(
+    req: &HttpRequest,
     api_req: &UserApiRequest,
     app_state: &AppState,
 ) -> HttpResponse {
-    match get_user_target(api_req, app_state) {
+    let started = Instant::now();
+    let response = m3u_api_response(req, api_req, app_state).await;
+    app_state.access_log.log_request(req, api_req.username.trim(), "", started, &response);
+    response
+}
+
+/// `If-None-Match` has precedence over `If-Modified-Since`, which is ignored without modification time.
+fn is_not_modified(req: &HttpRequest, etag: &str, modified: Option<SystemTime>) -> bool {
+    if let Some(if_none_match) = req.get_header::<IfNoneMatch>() {
+        let etag = EntityTag::new_strong(etag.to_string());
+        return match if_none_match {
+            IfNoneMatch::Any => true,
+            IfNoneMatch::Items(items) => items.iter().any(|item| item.weak_eq(&etag)),
+        };
+    }
+    // http dates have a precision of seconds
+    let seconds = |time: SystemTime| time.duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_secs());
+    modified.is_some_and(|modified| req.get_header::<IfModifiedSince>()
+        .is_some_and(|IfModifiedSince(since)| seconds(modified) <= seconds(SystemTime::from(since))))
+}
+
+fn add_version_headers(response: &mut HttpResponse, etag: &str, modified: Option<SystemTime>) {
+    let headers = response.headers_mut();
+    if let Ok(value) = HeaderValue::from_str(&EntityTag::new_strong(etag.to_string()).to_string()) {
+        headers.insert(ETAG, value);
+    }
+    if let Some(value) = modified.and_then(|modified| HeaderValue::from_str(&HttpDate::from(modified).to_string()).ok()) {
+        headers.insert(LAST_MODIFIED, value);
+    }
+}
+
+/// Serves the ranges of the written playlist file of the user.
+fn playlist_file_response(req: &HttpRequest, path: &Path, etag: &str, modified: Option<SystemTime>) -> std::io::Result<HttpResponse> {
+    let file = actix_files::NamedFile::open(path)?;
+    let mut response = file.set_content_type(mime::TEXT_PLAIN_UTF_8)
+        .disable_content_disposition().use_etag(false).use_last_modified(false)
+        .into_response(req);
+    add_version_headers(&mut response, etag, modified);
+    Ok(response)
+}
+
+async fn m3u_api_response(
+    req: &HttpRequest,
+    api_req: &UserApiRequest,
+    app_state: &AppState,
+) -> HttpResponse {
+    if let Some(response) = check_rate_limit(req, app_state, api_req.username.trim()) {
+        return response;
+    }
+    let tenant = get_tenant(req);
+    match get_user_target(api_req, app_state, req) {
         Some((user, target)) => {
-            match m3u_load_rewrite_playlist(&app_state.config, target, &user) {
+            let version = m3u_get_playlist_version(&app_state.config, target, &user, tenant);
+            if let Some((etag, modified)) = &version {
+                if is_not_modified(req, etag, *modified) {
+                    return HttpResponse::NotModified()
+                        .insert_header(ETag(EntityTag::new_strong(etag.clone())))
+                        .finish();
+                }
+                if req.headers().contains_key(RANGE) {
+                    // the playlist file is written on the first range request of a version
+                    let cfg = Arc::clone(&app_state.config);
+                    let (target, user, tenant, version) = (target.clone(), user.clone(), tenant.map(String::from), etag.clone());
+                    let written = web::block(move || m3u_get_playlist_file(&cfg, &target, &user, tenant.as_deref(), &version)).await;
+                    match written.map_err(|err| M3uFilterError::new(M3uFilterErrorKind::Info, err.to_string())).and_then(|result| result)
+                        .and_then(|path| playlist_file_response(req, &path, etag, *modified)
+                            .map_err(|err| M3uFilterError::new(M3uFilterErrorKind::Info, err.to_string()).with_category(M3uFilterErrorCategory::Api))) {
+                        Ok(response) => return response,
+                        Err(err) => error!("{}", mask_sensitive_info(err.to_string().as_str())),
+                    }
+                }
+            }
+            match m3u_load_rewrite_playlist(&app_state.config, target, &user, tenant) {
                 Ok(m3u_iter) => {
                     // Convert the iterator into a stream of `Bytes`
                     let content_stream = stream::iter(m3u_iter.map(|line| Ok::<Bytes, String>(Bytes::from(format!("{line}\n")))));
-                    HttpResponse::Ok()
+                    let mut response = HttpResponse::Ok()
                         .content_type(mime::TEXT_PLAIN_UTF_8)
-                        .streaming(content_stream)
+                        .streaming(content_stream);
+                    if let Some((etag, modified)) = &version {
+                        add_version_headers(&mut response, etag, *modified);
+                    }
+                    response
                 }
                 Err(err) => {
                     error!("{}", mask_sensitive_info(err.to_string().as_str()));
@@ -31,43 +117,70 @@ fn m3u_api(
                 }
             }
         }
-        None => HttpResponse::BadRequest().finish(),
+        None => {
+            register_failed_login(req, app_state);
+            HttpResponse::BadRequest().finish()
+        }
     }
 }
 
-async fn m3u_api_get(    api_req: web::Query<UserApiRequest>,
+async fn m3u_api_get(    req: HttpRequest,
+                         api_req: web::Query<UserApiRequest>,
                          app_state: web::Data<AppState>,
 ) -> HttpResponse {
-    m3u_api(&api_req.into_inner(), &app_state)
+    m3u_api(&req, &api_req.into_inner(), &app_state).await
 }
 async fn m3u_api_post(
+    req: HttpRequest,
     api_req: web::Form<UserApiRequest>,
     app_state: web::Data<AppState>,
 ) -> HttpResponse {
-    m3u_api(&api_req.into_inner(), &app_state)
+    m3u_api(&req, &api_req.into_inner(), &app_state).await
 }
 
 async fn m3u_api_stream(
     req: HttpRequest,
     api_req: web::Query<UserApiRequest>,
-    path: web::Path<(String, String, String)>,
+    path: web::Path<StreamPath>,
     app_state: web::Data<AppState>,
 ) -> HttpResponse {
-    let (username, password, stream_id) = path.into_inner();
+    let StreamPath { username, password, stream_id } = path.into_inner();
+    if let Some(response) = check_rate_limit(&req, &app_state, &username) {
+        return response;
+    }
     if let Ok(m3u_stream_id) = stream_id.parse::<u32>() {
-        if let Some((user, target)) = get_user_target_by_credentials(&username, &password, &api_req, &app_state) {
+        let user_target = get_user_target_by_credentials(&username, &password, &api_req, &app_state, &req);
+        if user_target.is_none() {
+            register_failed_login(&req, &app_state);
+        }
+        if let Some((user, target)) = user_target {
             if target.has_output(&TargetType::M3u) {
                 match get_target_storage_path(&app_state.config, target.name.as_str()) {
                     Some(target_path) => {
                         let (m3u_path, idx_path) = m3u_get_file_paths(&target_path);
                         match m3u_get_item_for_stream_id(&app_state.config, m3u_stream_id, &m3u_path, &idx_path) {
                             Ok(m3u_item) => {
-                                if user.proxy == ProxyType::Redirect {
-                                    let stream_url = m3u_item.url;
+                                if target.is_adult_item_hidden(&user, &m3u_item.parent_code) {
+                                    debug!("Adult content {m3u_stream_id} of target {} is locked for user {}", target.name, user.username);
+                                    return HttpResponse::Forbidden().finish();
+                                }
+                                let input = app_state.config.get_input_by_id(m3u_item.input_id);
+                                // udp, rtp and rtsp streams can't be proxied
+                                if user.proxy == ProxyType::Redirect || m3u_item.item_type == PlaylistItemType::LiveDirect {
+                                    let stream_url = token_refresh::rewrite_token_url(input, &m3u_item.url);
                                     debug!("Redirecting stream request to {}", mask_sensitive_info(&stream_url));
                                     return HttpResponse::Found().insert_header(("Location", stream_url.to_string())).finish();
                                 }
-                                return stream_response(m3u_item.url.as_str(), &req, None).await;
+                                let details = StreamDetails {
+                                    username: user.username.clone(),
+                                    channel: m3u_item.title.to_string(),
+                                    input: input.and_then(|input| input.name.clone()).unwrap_or_default(),
+                                    client_ip: get_client_ip(&req),
+                                    user_agent: get_user_agent(&req),
+                                    endpoint: get_endpoint(&req),
+                                };
+                                let stream_key = (m3u_item.item_type == PlaylistItemType::Live).then(|| StreamKey { target: target.name.clone(), virtual_id: m3u_item.virtual_id });
+                                return stream_response(&app_state, m3u_item.url.as_str(), &req, target, input, details, stream_key).await;
                             }
                             Err(err) => {
                                 error!("Failed to get m3u url: {}", mask_sensitive_info(err.to_string().as_str()));
@@ -93,4 +206,45 @@ pub fn m3u_api_register(cfg: &mut web::ServiceConfig) {
         .service(web::resource("/m3u-stream/movie/{username}/{password}/{stream_id}").route(web::get().to(m3u_api_stream)))
         .service(web::resource("/m3u-stream/series/{username}/{password}/{stream_id}").route(web::get().to(m3u_api_stream)))
         .service(web::resource("/m3u-stream/{username}/{password}/{stream_id}").route(web::get().to(m3u_api_stream)));
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use actix_web::http::header::{HttpDate, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, RANGE};
+    use actix_web::http::StatusCode;
+    use actix_web::test::TestRequest;
+
+    use crate::api::m3u_api::{is_not_modified, playlist_file_response};
+    use crate::utils::test_utils::create_temp_dir;
+
+    #[test]
+    fn test_not_modified() {
+        let modified = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let since = HttpDate::from(modified).to_string();
+        let req = TestRequest::default().insert_header((IF_NONE_MATCH, "\"abc\"")).to_http_request();
+        assert!(is_not_modified(&req, "abc", Some(modified)));
+        // a changed etag is served even if the playlist file is not newer
+        let req = TestRequest::default().insert_header((IF_NONE_MATCH, "\"abc\"")).insert_header((IF_MODIFIED_SINCE, since.as_str())).to_http_request();
+        assert!(!is_not_modified(&req, "def", Some(modified)));
+        let req = TestRequest::default().insert_header((IF_MODIFIED_SINCE, since.as_str())).to_http_request();
+        assert!(is_not_modified(&req, "abc", Some(modified)));
+        assert!(!is_not_modified(&req, "abc", Some(modified + Duration::from_secs(1))));
+        // without modification time the content varies per request
+        assert!(!is_not_modified(&req, "abc", None));
+    }
+
+    #[test]
+    fn test_playlist_range() {
+        let temp_dir = create_temp_dir("playlist_range");
+        let path = temp_dir.path().join("playlist.m3u");
+        std::fs::write(&path, "#EXTM3U\n#EXTINF:-1,News\nhttp://localhost/1\n").unwrap();
+        let req = TestRequest::default().insert_header((RANGE, "bytes=0-6")).to_http_request();
+        let response = playlist_file_response(&req, &path, "abc", Some(SystemTime::now())).unwrap();
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers().get(ETAG).unwrap(), "\"abc\"");
+        assert!(response.headers().contains_key(LAST_MODIFIED));
+        let response = playlist_file_response(&req, &path, "abc", None).unwrap();
+        assert!(!response.headers().contains_key(LAST_MODIFIED));
+    }
+}
@@ -4,21 +4,36 @@ use std::path::PathBuf;
 use std::sync::{Arc, Mutex, RwLock};
 
 use actix_cors::Cors;
-use actix_web::{App, HttpResponse, HttpServer, web};
-use actix_web::middleware::{Logger};
+use actix_web::{App, HttpServer, web};
+use actix_web::middleware::{Condition, Logger};
 use log::info;
 
+use crate::api::access_log::AccessLog;
 use crate::api::api_model::{AppState, DownloadQueue};
+use crate::api::connection_tracker::ConnectionTracker;
+use crate::api::favorites_api::favorites_api_register;
+use crate::api::health_api::health_api_register;
+use crate::api::logo_api::logo_api_register;
+use crate::api::openapi::openapi_register;
+use crate::api::rate_limiter::RateLimiter;
+use crate::api::request_coalescer::RequestCoalescer;
+use crate::api::stream_broker::StreamBroker;
+use crate::api::tls_server::create_tls_server;
 use crate::api::m3u_api::m3u_api_register;
 use crate::api::scheduler::start_scheduler;
 use crate::api::v1_api::v1_api_register;
-use crate::api::web_index::index_register;
+use crate::api::watch_history_api::watch_history_api_register;
+use crate::api::web_index::{index_register, static_register};
 use crate::api::xmltv_api::xmltv_api_register;
 use crate::api::xtream_api::xtream_api_register;
-use crate::model::config::{Config, ProcessTargets};
-use crate::model::healthcheck::Healthcheck;
-use crate::processing::playlist_processor;
-use crate::VERSION;
+use crate::model::config::{Config, CorsConfig, ProcessTargets};
+use crate::processing::{account_check, directory_watch, job_queue, recorder, short_epg, stream_health, token_refresh};
+use crate::processing::job_queue::JobQueue;
+use crate::processing::recorder::Recorder;
+use crate::repository::job_repository::JobTrigger;
+use crate::repository::m3u_repository::m3u_cleanup_playlist_files;
+use crate::repository::manifest_repository::manifest_verify_targets;
+use crate::utils::sd_notify;
 
 fn get_web_dir_path(web_ui_enabled: bool, web_root: &str) -> Result<PathBuf, std::io::Error> {
     let web_dir = web_root.to_string();
@@ -30,13 +45,27 @@ fn get_web_dir_path(web_ui_enabled: bool, web_root: &str) -> Result<PathBuf, std
     Ok(web_dir_path)
 }
 
-async fn healthcheck() -> HttpResponse {
-    let ts = chrono::offset::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
-    HttpResponse::Ok().json( Healthcheck {
-        status: "ok".to_string(),
-        version: VERSION.to_string(),
-        time: ts
-    })
+/// Without `cors` config no cors headers are sent, browsers only allow same-origin requests to the api.
+fn create_cors(cors_cfg: Option<&CorsConfig>) -> Condition<Cors> {
+    let Some(cors_cfg) = cors_cfg.filter(|cors_cfg| cors_cfg.enabled) else {
+        return Condition::new(false, Cors::default());
+    };
+    let mut cors = Cors::default()
+        .allowed_methods(cors_cfg.allowed_methods.iter().map(String::as_str))
+        .allow_any_header()
+        .max_age(cors_cfg.max_age);
+    if cors_cfg.is_any_origin() {
+        cors = cors.allow_any_origin();
+    } else {
+        for origin in &cors_cfg.allowed_origins {
+            cors = cors.allowed_origin(origin);
+        }
+    }
+    // credentials are never sent to any origin, prepare rejects the combination
+    if cors_cfg.allow_credentials && !cors_cfg.is_any_origin() {
+        cors = cors.supports_credentials();
+    }
+    Condition::new(true, cors)
 }
 
 #[actix_web::main]
@@ -53,12 +82,18 @@ pub async fn start_server(cfg: Arc<Config>, targets: Arc<ProcessTargets>) -> fut
 
     let shared_data = web::Data::new(AppState {
         config: Arc::clone(&cfg),
-        targets: Arc::clone(&targets),
         downloads: Arc::from(DownloadQueue {
             queue: Arc::from(Mutex::new(VecDeque::new())),
             active: Arc::from(RwLock::new(None)),
             finished: Arc::from(RwLock::new(Vec::new())),
         }),
+        connections: Arc::new(ConnectionTracker::default()),
+        stream_broker: Arc::new(StreamBroker::default()),
+        info_requests: Arc::new(RequestCoalescer::default()),
+        rate_limiter: Arc::new(RateLimiter::new(cfg.rate_limit.as_ref())),
+        access_log: Arc::new(AccessLog::new(&cfg)),
+        recorder: Arc::new(Recorder::new(Arc::clone(&cfg))),
+        jobs: Arc::new(JobQueue::new(Arc::clone(&cfg), targets)),
     });
 
     // Scheduler
@@ -69,49 +104,120 @@ pub async fn start_server(cfg: Arc<Config>, targets: Arc<ProcessTargets>) -> fut
         });
     }
 
+    stream_health::start_health_checker(Arc::clone(&cfg));
+    short_epg::start_short_epg_generator(Arc::clone(&cfg));
+    m3u_cleanup_playlist_files(&cfg);
+    account_check::start_account_checker(Arc::clone(&cfg));
+    token_refresh::start_token_refresh(&cfg);
+    sd_notify::start_watchdog(&cfg);
+    recorder::start_recorder(&shared_data.recorder);
+    job_queue::start_job_worker(&shared_data.jobs);
+    directory_watch::start_directory_watcher(&cfg, &shared_data.jobs);
+
+    let corrupted_targets = manifest_verify_targets(&cfg);
+    if !corrupted_targets.is_empty() {
+        shared_data.jobs.enqueue(JobTrigger::Integrity, Some(corrupted_targets), None);
+    }
     if cfg.update_on_boot {
-        let cfg_clone = Arc::clone(&cfg);
-        let targets_clone = Arc::clone(&targets);
-        actix_rt::spawn(
-            async move { playlist_processor::exec_processing(cfg_clone, targets_clone).await }
-        );
+        shared_data.jobs.enqueue(JobTrigger::Boot, None, None);
     }
 
-    let mut web_auth_enabled = false;
     if web_ui_enabled {
         info!("Web root: {:?}", &web_dir_path);
-        if let Some(web_auth) = &cfg.web_auth {
-            web_auth_enabled = web_auth.enabled;
-        }
     }
+    let web_path = cfg.api.web_path.clone();
+    let cors_cfg = cfg.api.cors.clone();
+    // the admin api is protected even without web ui
+    let web_auth_enabled = cfg.web_auth.as_ref().is_some_and(|web_auth| web_auth.enabled);
 
     // Web Server
-    HttpServer::new(move || {
+    let app_factory = move || {
         App::new()
             .wrap(Logger::default())
-            .wrap(Cors::default()
-                .supports_credentials()
-                .allow_any_origin()
-                .allowed_methods(vec!["GET", "POST", "OPTIONS", "HEAD"])
-                .allow_any_header()
-                .max_age(3600))
+            .wrap(create_cors(cors_cfg.as_ref()))
             .app_data(shared_data.clone())
             // .wrap(Condition::new(web_auth_enabled, ErrorHandlers::new().handler(StatusCode::UNAUTHORIZED, handle_unauthorized)))
             .configure(|srvcfg| {
                 if web_ui_enabled {
-                    srvcfg.service(actix_files::Files::new("/static", web_dir_path.join("static")));
+                    srvcfg.configure(static_register(&web_dir_path, &web_path));
+                }
+                if web_ui_enabled || web_auth_enabled {
                     srvcfg.configure(v1_api_register(web_auth_enabled));
                 }
-                srvcfg.service(web::resource("/healthcheck").route(web::get().to(healthcheck)));
             })
+            .configure(health_api_register)
+            .configure(openapi_register)
+            // the tenant scope has to be registered first, the xtream stream route would match it otherwise
+            .service(web::scope("/t/{tenant}")
+                .configure(favorites_api_register)
+                .configure(watch_history_api_register)
+                .configure(xtream_api_register)
+                .configure(m3u_api_register)
+                .configure(xmltv_api_register))
+            .configure(favorites_api_register)
+            .configure(watch_history_api_register)
             .configure(xtream_api_register)
             .configure(m3u_api_register)
             .configure(xmltv_api_register)
+            .configure(logo_api_register)
             .configure(|srvcfg| {
                 if web_ui_enabled {
-                    srvcfg.configure(index_register(&web_dir_path));
+                    srvcfg.configure(index_register(&web_dir_path, &web_path));
                 }
             })
-    }).bind(format!("{host}:{port}"))?.run().await
+    };
+    let mut http_server = HttpServer::new(app_factory.clone()).bind((host.as_str(), port))?;
+    for addr in &cfg.api.listen {
+        http_server = http_server.bind(addr.as_str())?;
+        info!("Server running: http://{addr}");
+    }
+    let tls_server = match cfg.api.tls.as_ref() {
+        Some(tls) => Some(create_tls_server(tls, app_factory)?),
+        None => None,
+    };
+    let server = http_server.run();
+    #[cfg(windows)]
+    crate::utils::win_service::stop_on_service_stop(std::iter::once(server.handle()).chain(tls_server.as_ref().map(actix_web::dev::Server::handle)).collect());
+    sd_notify::notify_ready();
+    let result = match tls_server {
+        Some(tls_server) => futures::future::try_join(server, tls_server).await.map(|_| ()),
+        None => server.await,
+    };
+    sd_notify::notify_stopping();
+    result
 }
 
+#[cfg(test)]
+mod tests {
+    use actix_web::http::header::{ACCESS_CONTROL_ALLOW_CREDENTIALS, ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_REQUEST_METHOD, ORIGIN};
+    use actix_web::http::Method;
+    use actix_web::test::{call_service, init_service, TestRequest};
+    use actix_web::{web, App, HttpResponse};
+
+    use crate::api::main_api::create_cors;
+    use crate::model::config::CorsConfig;
+
+    #[actix_web::test]
+    async fn test_cors() {
+        let foreign = |method: Method| TestRequest::default().method(method).uri("/api/v1/jobs")
+            .insert_header((ORIGIN, "https://evil.example.com"))
+            .insert_header((ACCESS_CONTROL_REQUEST_METHOD, "DELETE"))
+            .to_request();
+        // same-origin by default, a foreign origin gets no cors headers
+        let app = init_service(App::new().wrap(create_cors(None)).route("/api/v1/jobs", web::get().to(HttpResponse::Ok))).await;
+        assert!(call_service(&app, foreign(Method::GET)).await.headers().get(ACCESS_CONTROL_ALLOW_ORIGIN).is_none());
+        assert!(call_service(&app, foreign(Method::OPTIONS)).await.headers().get(ACCESS_CONTROL_ALLOW_ORIGIN).is_none());
+
+        let mut cors_cfg: CorsConfig = serde_yaml::from_str("allowed_origins: [https://ui.example.com]\nallow_credentials: true").unwrap();
+        cors_cfg.prepare().unwrap();
+        let app = init_service(App::new().wrap(create_cors(Some(&cors_cfg))).route("/api/v1/jobs", web::get().to(HttpResponse::Ok))).await;
+        assert!(call_service(&app, foreign(Method::OPTIONS)).await.headers().get(ACCESS_CONTROL_ALLOW_ORIGIN).is_none());
+        let allowed = TestRequest::get().uri("/api/v1/jobs").insert_header((ORIGIN, "https://ui.example.com")).to_request();
+        let response = call_service(&app, allowed).await;
+        assert_eq!(response.headers().get(ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(), "https://ui.example.com");
+        assert_eq!(response.headers().get(ACCESS_CONTROL_ALLOW_CREDENTIALS).unwrap(), "true");
+
+        let mut any_origin: CorsConfig = serde_yaml::from_str("allow_credentials: true").unwrap();
+        assert!(any_origin.prepare().is_err());
+    }
+}
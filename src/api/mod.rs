@@ -1,10 +1,27 @@
 pub mod api_utils;
 pub mod api_model;
 pub mod main_api;
+pub mod tuner_check;
+mod access_log;
+pub(crate) mod api_error;
+mod connection_tracker;
+mod rate_limiter;
+mod stream_broker;
+mod request_coalescer;
 mod download_api;
+mod recording_api;
+mod job_api;
+mod audit_api;
 mod v1_api;
 mod xtream_api;
+pub(crate) mod xtream_epg;
 mod m3u_api;
-mod xmltv_api;
+mod favorites_api;
+mod watch_history_api;
+pub(crate) mod xmltv_api;
+mod logo_api;
+mod health_api;
+mod openapi;
 mod scheduler;
-mod web_index;
\ No newline at end of file
+mod web_index;
+mod tls_server;
\ No newline at end of file
@@ -0,0 +1,314 @@
+use std::sync::LazyLock;
+
+use actix_web::{web, HttpResponse};
+use serde_json::{json, Map, Value};
+
+use crate::VERSION;
+
+const ADMIN_API_PREFIX: &str = "/api/v1";
+
+const TAG_HEALTH: &str = "health";
+const TAG_AUTH: &str = "auth";
+const TAG_ADMIN: &str = "admin";
+const TAG_XTREAM: &str = "xtream";
+const TAG_M3U: &str = "m3u";
+const TAG_XMLTV: &str = "xmltv";
+const TAG_USER: &str = "user";
+
+const USER_QUERY: &[(&str, &str)] = &[("username", "username of the api-proxy user"), ("password", "password of the api-proxy user"), ("token", "token of the api-proxy user instead of username and password")];
+const PLAYER_API_QUERY: &[(&str, &str)] = &[
+    ("username", "username of the api-proxy user"),
+    ("password", "password of the api-proxy user"),
+    ("action", "`get_live_categories`, `get_live_streams`, `get_vod_info`, `get_series_info`, `get_short_epg`, ..."),
+    ("category_id", "category of the listed streams"),
+    ("stream_id", "stream of the epg actions"),
+    ("vod_id", "movie of `get_vod_info`"),
+    ("series_id", "series of `get_series_info`"),
+    ("limit", "max number of epg entries"),
+    ("pin", "parental pin for adult groups"),
+];
+const M3U_QUERY: &[(&str, &str)] = &[
+    ("username", "username of the api-proxy user"),
+    ("password", "password of the api-proxy user"),
+    ("token", "token of the api-proxy user instead of username and password"),
+    ("type", "`m3u_plus`"),
+    ("output", "`ts` or `hls`"),
+];
+const TIMESHIFT_QUERY: &[(&str, &str)] = &[("username", "username of the api-proxy user"), ("password", "password of the api-proxy user"), ("stream", "stream id"), ("start", "start like `2024-01-01:20-00`"), ("duration", "minutes")];
+const SEARCH_QUERY: &[(&str, &str)] = &[("q", "text or regular expression"), ("type", "`live`, `video` or `series`"), ("group", "group title"), ("regex", "`q` is a regular expression"), ("page", "page starting with 1"), ("page_size", "items per page")];
+
+/// One operation of the http api, the path parameters are taken from the `{name}` segments of the path.
+struct Operation {
+    method: &'static str,
+    path: &'static str,
+    tag: &'static str,
+    summary: &'static str,
+    query: &'static [(&'static str, &'static str)],
+    /// description of the json request body
+    body: Option<&'static str>,
+}
+
+const fn op(method: &'static str, path: &'static str, tag: &'static str, summary: &'static str) -> Operation {
+    Operation { method, path, tag, summary, query: &[], body: None }
+}
+
+const fn op_query(method: &'static str, path: &'static str, tag: &'static str, summary: &'static str, query: &'static [(&'static str, &'static str)]) -> Operation {
+    Operation { method, path, tag, summary, query, body: None }
+}
+
+const fn op_body(method: &'static str, path: &'static str, tag: &'static str, summary: &'static str, body: &'static str) -> Operation {
+    Operation { method, path, tag, summary, query: &[], body: Some(body) }
+}
+
+/// The endpoints registered by the api modules. Routes which are added to a module have to be listed here.
+const OPERATIONS: &[Operation] = &[
+    op("get", "/healthcheck", TAG_HEALTH, "Status, version and time of the server"),
+    op("get", "/healthz", TAG_HEALTH, "Liveness probe"),
+    op("get", "/readyz", TAG_HEALTH, "Readiness probe with the config, storage and refresh checks"),
+    op("get", "/api/openapi.json", TAG_HEALTH, "This OpenAPI document"),
+    op_body("post", "/auth/token", TAG_AUTH, "Issues a jwt token for a user of the userfile", "`username` and `password`"),
+    op("post", "/auth/refresh", TAG_AUTH, "Issues a new jwt token for a valid bearer token"),
+    op("get", "/api/v1/config", TAG_ADMIN, "Returns the config, sources and api-proxy config"),
+    op_body("post", "/api/v1/config/main", TAG_ADMIN, "Saves the main config", "the main config"),
+    op_body("post", "/api/v1/config/user", TAG_ADMIN, "Saves the api-proxy users", "list of targets with their `credentials`"),
+    op_body("post", "/api/v1/config/apiproxy", TAG_ADMIN, "Saves the api-proxy servers", "list of server infos"),
+    op_query("get", "/api/v1/audit", TAG_ADMIN, "Lists the changes made through the api, the latest first", &[("user", "user or api token"), ("section", "`config`, `api_proxy_user`, `api_proxy_server`, `channel_override`, `channel_quarantine`, `channel_alias`, `ban` or `recording`"), ("from", "unix timestamp"), ("to", "unix timestamp"), ("limit", "max entries")]),
+    op_body("post", "/api/v1/playlist", TAG_ADMIN, "Downloads the playlist of an input or url", "`input_id` or `url`"),
+    op_body("post", "/api/v1/playlist/update", TAG_ADMIN, "Queues a refresh of the targets", "list of target names"),
+    op_query("post", "/api/v1/refresh", TAG_ADMIN, "Queues a refresh of some clusters of a target", &[("target", "target name"), ("cluster", "comma separated `live`, `vod` and `series`")]),
+    op("get", "/api/v1/jobs", TAG_ADMIN, "Lists the jobs"),
+    op_body("post", "/api/v1/jobs", TAG_ADMIN, "Queues a refresh", "`targets` and optional `cluster`"),
+    op("get", "/api/v1/jobs/{id}", TAG_ADMIN, "Returns a job with log and progress"),
+    op("delete", "/api/v1/jobs/{id}", TAG_ADMIN, "Cancels a job"),
+    op("get", "/api/v1/events", TAG_ADMIN, "Server-sent events of the jobs"),
+    op_body("post", "/api/v1/file/download", TAG_ADMIN, "Queues a file download", "`url` and `filename`"),
+    op("get", "/api/v1/file/download/info", TAG_ADMIN, "Returns the queued, active and finished downloads"),
+    op("get", "/api/v1/recordings", TAG_ADMIN, "Lists the recordings"),
+    op_body("post", "/api/v1/recordings", TAG_ADMIN, "Schedules a recording", "`target`, `virtual_id` and optional `start`, `duration` or `programme_id`"),
+    op("delete", "/api/v1/recordings/{id}", TAG_ADMIN, "Stops and deletes a recording"),
+    op("get", "/api/v1/recordings/{id}/download", TAG_ADMIN, "Downloads a recorded file"),
+    op("get", "/api/v1/streams/active", TAG_ADMIN, "Lists the proxied streams"),
+    op("delete", "/api/v1/streams/active/{id}", TAG_ADMIN, "Closes a proxied stream"),
+    op("get", "/api/v1/streams/shared", TAG_ADMIN, "Metrics of the shared provider connections"),
+    op("get", "/api/v1/streams/health", TAG_ADMIN, "Results of the stream health check"),
+    op_query("get", "/api/v1/streams/inputs", TAG_ADMIN, "Lists the inputs which carry a stream", &[("url", "stream url"), ("uuid", "hex encoded hash of the stream url")]),
+    op("get", "/api/v1/cache/items", TAG_ADMIN, "Metrics of the item cache"),
+    op("get", "/api/v1/targets/{name}/snapshots", TAG_ADMIN, "Lists the snapshots of a target"),
+    op_query("get", "/api/v1/targets/{name}/diff", TAG_ADMIN, "Compares two snapshots of a target", &[("from", "snapshot id"), ("to", "snapshot id")]),
+    op_query("get", "/api/v1/targets/{name}/search", TAG_ADMIN, "Searches the playlist of a target", SEARCH_QUERY),
+    op_query("get", "/api/v1/targets/{name}/export", TAG_ADMIN, "Exports the playlist of a target", &[("format", "`json` or `csv`"), ("columns", "comma separated `name`, `group`, `url`, `epg_id`, `type`")]),
+    op_query("get", "/api/v1/targets/{name}/titles", TAG_ADMIN, "Searches the movie and series titles of a target", &[("q", "words of the title"), ("type", "`video` or `series`"), ("limit", "max number of items")]),
+    op("get", "/api/v1/targets/{name}/overrides", TAG_ADMIN, "Lists the channel overrides of a target"),
+    op_body("put", "/api/v1/targets/{name}/overrides/{id}", TAG_ADMIN, "Sets the override of a channel", "`name`, `logo`, `epg_channel_id`, `group` and `hidden`"),
+    op("delete", "/api/v1/targets/{name}/overrides/{id}", TAG_ADMIN, "Removes the override of a channel"),
+    op("get", "/api/v1/targets/{name}/quarantine", TAG_ADMIN, "Lists the quarantined channels of a target"),
+    op_body("put", "/api/v1/targets/{name}/quarantine/{id}", TAG_ADMIN, "Quarantines a channel", "`hours` and `reason`"),
+    op("delete", "/api/v1/targets/{name}/quarantine/{id}", TAG_ADMIN, "Releases a quarantined channel"),
+    op_query("get", "/api/v1/aliases", TAG_ADMIN, "Searches the channel aliases", &[("q", "channel name")]),
+    op_body("post", "/api/v1/aliases", TAG_ADMIN, "Adds a channel alias", "`name`, `aliases` and `epg_channel_id`"),
+    op("get", "/api/v1/inputs/accounts", TAG_ADMIN, "Account status of the xtream inputs"),
+    op("get", "/api/v1/inputs/sources", TAG_ADMIN, "The used primary or failover source of the inputs"),
+    op("get", "/api/v1/inputs/{name}/persisted", TAG_ADMIN, "Lists the persisted files of an input"),
+    op("get", "/api/v1/inputs/{name}/persisted/{file}", TAG_ADMIN, "Downloads a persisted file"),
+    op("get", "/api/v1/bans", TAG_ADMIN, "Lists the banned client ips"),
+    op("delete", "/api/v1/bans", TAG_ADMIN, "Clears all bans"),
+    op("delete", "/api/v1/bans/{ip}", TAG_ADMIN, "Clears the ban of a client ip"),
+    op_query("get", "/player_api.php", TAG_XTREAM, "Xtream player api", PLAYER_API_QUERY),
+    op_query("post", "/player_api.php", TAG_XTREAM, "Xtream player api", PLAYER_API_QUERY),
+    op_query("get", "/panel_api.php", TAG_XTREAM, "Xtream player api", PLAYER_API_QUERY),
+    op_query("post", "/panel_api.php", TAG_XTREAM, "Xtream player api", PLAYER_API_QUERY),
+    op_query("get", "/xtream", TAG_XTREAM, "Xtream player api", PLAYER_API_QUERY),
+    op_query("post", "/xtream", TAG_XTREAM, "Xtream player api", PLAYER_API_QUERY),
+    op("get", "/live/{username}/{password}/{stream_id}", TAG_XTREAM, "Live stream"),
+    op("get", "/movie/{username}/{password}/{stream_id}", TAG_XTREAM, "Movie stream"),
+    op("get", "/series/{username}/{password}/{stream_id}", TAG_XTREAM, "Series episode stream"),
+    op("get", "/{username}/{password}/{stream_id}", TAG_XTREAM, "Live stream"),
+    op("get", "/timeshift/{username}/{password}/{duration}/{start}/{stream_id}", TAG_XTREAM, "Timeshift stream"),
+    op_query("get", "/timeshift.php", TAG_XTREAM, "Timeshift stream", TIMESHIFT_QUERY),
+    op_query("get", "/streaming/timeshift.php", TAG_XTREAM, "Timeshift stream", TIMESHIFT_QUERY),
+    op("get", "/hlsr/{token}/{username}/{password}/{channel}/{hash}/{chunk}", TAG_XTREAM, "Hls segment of a provider stream"),
+    op("get", "/hls/{token}/{chunk}", TAG_XTREAM, "Hls segment"),
+    op("get", "/play/{token}/{type}", TAG_XTREAM, "Stream of a renewed token"),
+    op_query("get", "/get.php", TAG_M3U, "M3u playlist of the user", M3U_QUERY),
+    op_query("post", "/get.php", TAG_M3U, "M3u playlist of the user", M3U_QUERY),
+    op_query("get", "/apiget", TAG_M3U, "M3u playlist of the user", M3U_QUERY),
+    op_query("post", "/apiget", TAG_M3U, "M3u playlist of the user", M3U_QUERY),
+    op_query("get", "/m3u", TAG_M3U, "M3u playlist of the user", M3U_QUERY),
+    op_query("post", "/m3u", TAG_M3U, "M3u playlist of the user", M3U_QUERY),
+    op("get", "/m3u-stream/live/{username}/{password}/{stream_id}", TAG_M3U, "Live stream of the m3u playlist"),
+    op("get", "/m3u-stream/movie/{username}/{password}/{stream_id}", TAG_M3U, "Movie stream of the m3u playlist"),
+    op("get", "/m3u-stream/series/{username}/{password}/{stream_id}", TAG_M3U, "Series stream of the m3u playlist"),
+    op("get", "/m3u-stream/{username}/{password}/{stream_id}", TAG_M3U, "Stream of the m3u playlist"),
+    op_query("get", "/xmltv.php", TAG_XMLTV, "Xmltv epg of the user", USER_QUERY),
+    op_query("post", "/xmltv.php", TAG_XMLTV, "Xmltv epg of the user", USER_QUERY),
+    op_query("get", "/update/epg.php", TAG_XMLTV, "Xmltv epg of the user", USER_QUERY),
+    op_query("post", "/update/epg.php", TAG_XMLTV, "Xmltv epg of the user", USER_QUERY),
+    op_query("get", "/epg", TAG_XMLTV, "Xmltv epg of the user", USER_QUERY),
+    op_query("post", "/epg", TAG_XMLTV, "Xmltv epg of the user", USER_QUERY),
+    op("get", "/logo/{id}", TAG_USER, "Cached logo"),
+    op_query("get", "/favorites", TAG_USER, "Favorite stream ids of the user", USER_QUERY),
+    op_query("put", "/favorites/{stream_id}", TAG_USER, "Adds a favorite", USER_QUERY),
+    op_query("delete", "/favorites/{stream_id}", TAG_USER, "Removes a favorite", USER_QUERY),
+    op_query("get", "/recently_watched", TAG_USER, "Recently watched streams of the user", USER_QUERY),
+    op_query("delete", "/recently_watched", TAG_USER, "Clears the recently watched streams", USER_QUERY),
+];
+
+fn get_path_params(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter_map(|segment| segment.strip_prefix('{').and_then(|segment| segment.strip_suffix('}')))
+}
+
+fn create_operation(operation: &Operation) -> Value {
+    let mut parameters: Vec<Value> = get_path_params(operation.path)
+        .map(|name| json!({"name": name, "in": "path", "required": true, "schema": {"type": "string"}}))
+        .collect();
+    parameters.extend(operation.query.iter()
+        .map(|(name, description)| json!({"name": name, "in": "query", "required": false, "description": description, "schema": {"type": "string"}})));
+    let mut result = json!({
+        "tags": [operation.tag],
+        "summary": operation.summary,
+        "operationId": format!("{}{}", operation.method, operation.path.replace(['/', '.', '{', '}', '-'], "_")),
+        "responses": {
+            "200": {"description": "Success"},
+            "400": {"description": "Invalid request", "content": {"application/json": {"schema": {"$ref": "#/components/schemas/Error"}}}},
+            "404": {"description": "Not found", "content": {"application/json": {"schema": {"$ref": "#/components/schemas/Error"}}}},
+        },
+    });
+    if !parameters.is_empty() {
+        result["parameters"] = Value::Array(parameters);
+    }
+    if let Some(body) = operation.body {
+        result["requestBody"] = json!({"required": true, "description": body, "content": {"application/json": {"schema": {}}}});
+    }
+    if operation.path.starts_with(ADMIN_API_PREFIX) {
+        result["security"] = json!([{"bearerAuth": []}, {"basicAuth": []}]);
+        result["responses"]["401"] = json!({"description": "Missing or invalid credentials"});
+        result["responses"]["403"] = json!({"description": "The role of the credentials is not sufficient",
+            "content": {"application/json": {"schema": {"$ref": "#/components/schemas/Error"}}}});
+    }
+    result
+}
+
+/// The OpenAPI 3 document of the http api. The xtream, m3u, xmltv and user endpoints are served under `/t/{tenant}` too.
+fn create_openapi_document() -> Value {
+    let mut paths = Map::new();
+    for operation in OPERATIONS {
+        let path_item = paths.entry(operation.path).or_insert_with(|| json!({}));
+        path_item[operation.method] = create_operation(operation);
+    }
+    let tags: Vec<Value> = [TAG_HEALTH, TAG_AUTH, TAG_ADMIN, TAG_XTREAM, TAG_M3U, TAG_XMLTV, TAG_USER].iter().map(|tag| json!({"name": tag})).collect();
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "m3u-filter",
+            "version": VERSION,
+            "description": "Admin api, xtream and m3u emulation and xmltv endpoints of m3u-filter. The xtream, m3u, xmltv and user endpoints are served under `/t/{tenant}` too.",
+        },
+        "tags": tags,
+        "paths": paths,
+        "components": {
+            "schemas": {
+                "Error": {
+                    "type": "object",
+                    "required": ["code", "message"],
+                    "properties": {
+                        "code": {"type": "string", "description": "stable machine-readable code like `target_not_found`"},
+                        "message": {"type": "string"},
+                        "field": {"type": "string", "description": "the invalid parameter or config field"},
+                        "hint": {"type": "string"},
+                    },
+                },
+            },
+            "securitySchemes": {
+                "bearerAuth": {"type": "http", "scheme": "bearer", "description": "jwt token or api token of `web_auth`"},
+                "basicAuth": {"type": "http", "scheme": "basic", "description": "user of the `web_auth` userfile"},
+            },
+        },
+    })
+}
+
+static OPENAPI_DOCUMENT: LazyLock<String> = LazyLock::new(|| create_openapi_document().to_string());
+
+async fn openapi_json() -> HttpResponse {
+    HttpResponse::Ok().content_type(mime::APPLICATION_JSON).body(OPENAPI_DOCUMENT.as_str())
+}
+
+pub fn openapi_register(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/api/openapi.json").route(web::get().to(openapi_json)));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use regex::Regex;
+
+    use crate::api::openapi::{create_openapi_document, OPERATIONS};
+
+    /// The sources of the api modules with their register functions, the routes are read from the source
+    /// because actix does not list the registered routes.
+    const REGISTER_SOURCES: &[(&str, &str)] = &[
+        (include_str!("health_api.rs"), "health_api_register"),
+        (include_str!("openapi.rs"), "openapi_register"),
+        (include_str!("v1_api.rs"), "v1_api_register"),
+        (include_str!("web_index.rs"), "index_register"),
+        (include_str!("xtream_api.rs"), "xtream_api_register"),
+        (include_str!("m3u_api.rs"), "m3u_api_register"),
+        (include_str!("xmltv_api.rs"), "xmltv_api_register"),
+        (include_str!("favorites_api.rs"), "favorites_api_register"),
+        (include_str!("watch_history_api.rs"), "watch_history_api_register"),
+        (include_str!("logo_api.rs"), "logo_api_register"),
+    ];
+
+    /// The (method, path) of the `web::resource` and scoped `.route` registrations of the register function.
+    fn registered_routes(source: &str, function: &str) -> HashSet<(String, String)> {
+        let start = source.find(&format!("fn {function}")).unwrap();
+        let end = source[start..].find("\n}\n").map_or(source.len(), |end| start + end);
+        let body = &source[start..end];
+        let resource_re = Regex::new(r#"web::resource\("([^"]+)"\)"#).unwrap();
+        let method_re = Regex::new(r"web::(get|post|put|delete)\(\)").unwrap();
+        let scope_re = Regex::new(r#"(?:web::scope|RoleScope::new)\("([^"]+)"\)"#).unwrap();
+        let scoped_route_re = Regex::new(r#"\.route\("([^"]*)", (?:Role::\w+, )?web::(get|post|put|delete)\(\)"#).unwrap();
+        let mut routes = HashSet::new();
+        let resources: Vec<_> = resource_re.captures_iter(body).map(|caps| (caps.get(0).unwrap().start(), caps[1].to_string())).collect();
+        for (index, (position, path)) in resources.iter().enumerate() {
+            let next = resources.get(index + 1).map_or(body.len(), |(next, _)| *next);
+            for caps in method_re.captures_iter(&body[*position..next]) {
+                routes.insert((caps[1].to_string(), path.clone()));
+            }
+        }
+        // the web ui index is not part of the api
+        for caps in scope_re.captures_iter(body).filter(|caps| caps[1].starts_with('/')) {
+            let scope_start = caps.get(0).unwrap().end();
+            let scope_end = body[scope_start..].find("web::scope(").map_or(body.len(), |end| scope_start + end);
+            for route in scoped_route_re.captures_iter(&body[scope_start..scope_end]) {
+                routes.insert((route[2].to_string(), format!("{}{}", &caps[1], &route[1])));
+            }
+        }
+        routes
+    }
+
+    #[test]
+    fn test_openapi_lists_registered_routes() {
+        let documented: HashSet<(String, String)> = OPERATIONS.iter().map(|operation| (operation.method.to_string(), operation.path.to_string())).collect();
+        let mut missing = vec![];
+        for (source, function) in REGISTER_SOURCES {
+            let routes = registered_routes(source, function);
+            assert!(!routes.is_empty(), "no routes found in {function}");
+            missing.extend(routes.into_iter().filter(|route| !documented.contains(route)));
+        }
+        missing.sort();
+        assert!(missing.is_empty(), "routes missing in the openapi document: {missing:?}");
+    }
+
+    #[test]
+    fn test_openapi_document() {
+        let mut operations = HashSet::new();
+        assert!(OPERATIONS.iter().all(|operation| operations.insert((operation.method, operation.path))));
+        let document = create_openapi_document();
+        let job = &document["paths"]["/api/v1/jobs/{id}"];
+        assert_eq!(job["get"]["parameters"][0]["name"], "id");
+        assert_eq!(job["delete"]["security"][0]["bearerAuth"], serde_json::json!([]));
+        assert!(document["paths"]["/player_api.php"]["get"]["security"].is_null());
+        assert_eq!(document["paths"]["/timeshift/{username}/{password}/{duration}/{start}/{stream_id}"]["get"]["parameters"].as_array().map(Vec::len), Some(5));
+    }
+}
@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use log::warn;
+use serde::Serialize;
+
+use crate::model::config::RateLimitConfig;
+
+/// Prune expired entries if a map grows beyond this size.
+const MAX_ENTRIES_BEFORE_PRUNE: usize = 4096;
+/// Wrong parental pins of a client within `PIN_LOCK_PERIOD` before its pins are no longer checked, also without rate limit config.
+const MAX_FAILED_PINS: u32 = 5;
+const PIN_LOCK_PERIOD: Duration = Duration::from_secs(900);
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum RateLimitResult {
+    Allowed,
+    Limited,
+    Banned,
+}
+
+/// Banned client ip for the api.
+#[derive(Serialize)]
+pub struct BanInfo {
+    pub ip: String,
+    /// unix timestamp when the ban expires
+    pub until: i64,
+}
+
+struct Window {
+    start: Instant,
+    count: u32,
+}
+
+#[derive(Default)]
+struct RateLimitState {
+    ip_windows: HashMap<String, Window>,
+    user_windows: HashMap<String, Window>,
+    failed_logins: HashMap<String, Window>,
+    failed_pins: HashMap<String, Window>,
+    bans: HashMap<String, Instant>,
+}
+
+/// Counts the requests per client ip and per user in fixed windows
+/// and bans client ips with too many failed logins.
+pub struct RateLimiter {
+    config: Option<RateLimitConfig>,
+    state: Mutex<RateLimitState>,
+}
+
+fn count_request(windows: &mut HashMap<String, Window>, key: &str, period: Duration, now: Instant) -> u32 {
+    if windows.len() > MAX_ENTRIES_BEFORE_PRUNE {
+        windows.retain(|_, window| now.duration_since(window.start) < period);
+    }
+    match windows.get_mut(key) {
+        Some(window) if now.duration_since(window.start) < period => {
+            window.count = window.count.saturating_add(1);
+            window.count
+        }
+        _ => {
+            windows.insert(key.to_string(), Window { start: now, count: 1 });
+            1
+        }
+    }
+}
+
+impl RateLimiter {
+    pub fn new(config: Option<&RateLimitConfig>) -> Self {
+        Self {
+            config: config.filter(|rate_limit| rate_limit.enabled).cloned(),
+            state: Mutex::new(RateLimitState::default()),
+        }
+    }
+
+    fn get_config(&self, client_ip: &str) -> Option<&RateLimitConfig> {
+        self.config.as_ref().filter(|rate_limit| !rate_limit.whitelist.iter().any(|ip| ip == client_ip))
+    }
+
+    /// Counts the request, `username` is empty if the request has no credentials.
+    /// The users are counted per tenant, the same username can be used by several tenants.
+    pub fn check(&self, client_ip: &str, tenant: Option<&str>, username: &str) -> RateLimitResult {
+        let Some(config) = self.get_config(client_ip) else {
+            return RateLimitResult::Allowed;
+        };
+        let now = Instant::now();
+        let period = Duration::from_secs(config.period);
+        let mut state = self.state.lock().unwrap();
+        if let Some(until) = state.bans.get(client_ip) {
+            if *until > now {
+                return RateLimitResult::Banned;
+            }
+            state.bans.remove(client_ip);
+        }
+        if config.ip_limit > 0 && count_request(&mut state.ip_windows, client_ip, period, now) > config.ip_limit {
+            return RateLimitResult::Limited;
+        }
+        if config.user_limit > 0 && !username.is_empty() {
+            let user_key = tenant.map_or_else(|| username.to_string(), |name| format!("{}/{username}", name.to_lowercase()));
+            if count_request(&mut state.user_windows, &user_key, period, now) > config.user_limit {
+                return RateLimitResult::Limited;
+            }
+        }
+        RateLimitResult::Allowed
+    }
+
+    /// Bans the client ip if the failed logins exceed `max_failed_logins` within a period.
+    pub fn register_failed_login(&self, client_ip: &str) {
+        let Some(config) = self.get_config(client_ip) else {
+            return;
+        };
+        if config.max_failed_logins == 0 {
+            return;
+        }
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        if count_request(&mut state.failed_logins, client_ip, Duration::from_secs(config.period), now) >= config.max_failed_logins {
+            state.failed_logins.remove(client_ip);
+            state.bans.insert(client_ip.to_string(), now + Duration::from_secs(config.ban_duration));
+            warn!("Client {client_ip} banned for {} seconds after {} failed logins", config.ban_duration, config.max_failed_logins);
+        }
+    }
+
+    /// Counts a wrong parental pin of the client, it is also counted as failed login.
+    pub fn register_failed_pin(&self, client_ip: &str) {
+        self.register_failed_login(client_ip);
+        let mut state = self.state.lock().unwrap();
+        count_request(&mut state.failed_pins, client_ip, PIN_LOCK_PERIOD, Instant::now());
+    }
+
+    /// The pin of a banned client or of a client with too many wrong pins is not checked.
+    pub fn is_pin_locked(&self, client_ip: &str) -> bool {
+        let now = Instant::now();
+        let state = self.state.lock().unwrap();
+        state.bans.get(client_ip).is_some_and(|until| *until > now)
+            || state.failed_pins.get(client_ip).is_some_and(|window| now.duration_since(window.start) < PIN_LOCK_PERIOD && window.count >= MAX_FAILED_PINS)
+    }
+
+    pub fn get_bans(&self) -> Vec<BanInfo> {
+        let now = Instant::now();
+        let timestamp = chrono::Local::now().timestamp();
+        let mut state = self.state.lock().unwrap();
+        state.bans.retain(|_, until| *until > now);
+        let mut result: Vec<BanInfo> = state.bans.iter().map(|(ip, until)| BanInfo {
+            ip: ip.clone(),
+            until: timestamp + i64::try_from(until.duration_since(now).as_secs()).unwrap_or(i64::MAX - timestamp),
+        }).collect();
+        result.sort_by(|a, b| a.ip.cmp(&b.ip));
+        result
+    }
+
+    pub fn clear_ban(&self, client_ip: &str) -> bool {
+        self.state.lock().unwrap().bans.remove(client_ip).is_some()
+    }
+
+    pub fn clear_bans(&self) {
+        self.state.lock().unwrap().bans.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::rate_limiter::{RateLimitResult, RateLimiter};
+    use crate::model::config::RateLimitConfig;
+
+    #[test]
+    fn test_rate_limit_and_ban() {
+        let config = RateLimitConfig {
+            enabled: true,
+            period: 60,
+            ip_limit: 3,
+            user_limit: 2,
+            max_failed_logins: 2,
+            ban_duration: 60,
+            whitelist: vec!["127.0.0.1".to_string()],
+        };
+        let limiter = RateLimiter::new(Some(&config));
+        assert_eq!(limiter.check("10.0.0.1", None, "user"), RateLimitResult::Allowed);
+        assert_eq!(limiter.check("10.0.0.2", None, "user"), RateLimitResult::Allowed);
+        assert_eq!(limiter.check("10.0.0.3", None, "user"), RateLimitResult::Limited);
+        // the user of a tenant is counted separately
+        assert_eq!(limiter.check("10.0.0.5", Some("Tenant"), "user"), RateLimitResult::Allowed);
+        assert_eq!(limiter.check("10.0.0.5", Some("tenant"), "user"), RateLimitResult::Allowed);
+        assert_eq!(limiter.check("10.0.0.6", Some("tenant"), "user"), RateLimitResult::Limited);
+        assert_eq!(limiter.check("10.0.0.1", None, ""), RateLimitResult::Allowed);
+        assert_eq!(limiter.check("10.0.0.1", None, ""), RateLimitResult::Allowed);
+        assert_eq!(limiter.check("10.0.0.1", None, ""), RateLimitResult::Limited);
+
+        limiter.register_failed_login("10.0.0.4");
+        assert_eq!(limiter.check("10.0.0.4", None, ""), RateLimitResult::Allowed);
+        limiter.register_failed_login("10.0.0.4");
+        assert_eq!(limiter.check("10.0.0.4", None, ""), RateLimitResult::Banned);
+        assert_eq!(limiter.get_bans().len(), 1);
+        assert!(limiter.clear_ban("10.0.0.4"));
+        assert!(limiter.get_bans().is_empty());
+
+        for _ in 0..10 {
+            limiter.register_failed_login("127.0.0.1");
+            assert_eq!(limiter.check("127.0.0.1", None, "user"), RateLimitResult::Allowed);
+        }
+    }
+
+    #[test]
+    fn test_failed_pins() {
+        // wrong pins are limited without rate limit config
+        let limiter = RateLimiter::new(None);
+        for _ in 0..4 {
+            limiter.register_failed_pin("10.0.0.1");
+            assert!(!limiter.is_pin_locked("10.0.0.1"));
+        }
+        limiter.register_failed_pin("10.0.0.1");
+        assert!(limiter.is_pin_locked("10.0.0.1"));
+        assert!(!limiter.is_pin_locked("10.0.0.2"));
+
+        // with rate limit config a wrong pin is a failed login and banned clients can't unlock
+        let config = RateLimitConfig { enabled: true, period: 60, ip_limit: 0, user_limit: 0, max_failed_logins: 2, ban_duration: 60, whitelist: vec![] };
+        let limiter = RateLimiter::new(Some(&config));
+        limiter.register_failed_pin("10.0.0.3");
+        assert!(!limiter.is_pin_locked("10.0.0.3"));
+        limiter.register_failed_pin("10.0.0.3");
+        assert_eq!(limiter.check("10.0.0.3", None, ""), RateLimitResult::Banned);
+        assert!(limiter.is_pin_locked("10.0.0.3"));
+    }
+}
@@ -0,0 +1,124 @@
+use std::str::FromStr;
+
+use actix_web::{web, HttpRequest, HttpResponse};
+use log::error;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::api::api_error::{ApiError, ApiErrorCode};
+use crate::api::api_model::AppState;
+use crate::api::audit_api;
+use crate::api::xmltv_api::get_target_epg_path;
+use crate::api::xtream_epg::read_channel_programmes;
+use crate::repository::audit_repository::AuditSection;
+use crate::repository::recording_repository::{Recording, RecordingStatus};
+use crate::repository::search_repository::search_index_find;
+
+#[derive(Debug, Deserialize)]
+pub struct RecordingRequest {
+    pub target: String,
+    pub virtual_id: u32,
+    /// unix timestamp, default is now
+    #[serde(default)]
+    pub start: Option<i64>,
+    /// minutes
+    #[serde(default)]
+    pub duration: Option<u32>,
+    /// the start timestamp of the epg programme, the `id` of the xtream epg listings
+    #[serde(default)]
+    pub programme_id: Option<i64>,
+}
+
+fn recording_not_found(id: &str) -> HttpResponse {
+    ApiError::new(ApiErrorCode::RecordingNotFound, format!("Unknown recording {id}")).with_field("id").into()
+}
+
+pub async fn recording_list(
+    app_state: web::Data<AppState>,
+) -> HttpResponse {
+    HttpResponse::Ok().json(app_state.recorder.list())
+}
+
+pub async fn recording_schedule(
+    http_req: HttpRequest,
+    req: web::Json<RecordingRequest>,
+    app_state: web::Data<AppState>,
+) -> HttpResponse {
+    if app_state.recorder.get_config().is_none() {
+        return ApiError::new(ApiErrorCode::ConfigMissing, "Server config missing recording configuration").with_field("recording").into();
+    }
+    let config = &app_state.config;
+    let Some(target) = config.sources.iter().flat_map(|source| &source.targets).find(|target| target.name == req.target) else {
+        return ApiError::target_not_found(&req.target).into();
+    };
+    let entry = match search_index_find(config, &target.name, req.virtual_id) {
+        Ok(Some(entry)) => entry,
+        Ok(None) => return ApiError::new(ApiErrorCode::ChannelNotFound, format!("Unknown channel {}", req.virtual_id)).with_field("virtual_id").into(),
+        Err(err) => {
+            error!("{err}");
+            return ApiError::internal(err.to_string()).into();
+        }
+    };
+    let (title, start, stop) = if let Some(programme_id) = req.programme_id {
+        let programme = get_target_epg_path(config, target)
+            .filter(|_| !entry.epg_channel_id.is_empty())
+            .and_then(|epg_path| read_channel_programmes(&epg_path, &entry.epg_channel_id, 0).into_iter()
+                .find(|programme| programme.start == programme_id));
+        match programme {
+            Some(programme) => (programme.title, programme.start, programme.stop),
+            None => return ApiError::new(ApiErrorCode::ProgrammeNotFound, format!("Unknown programme {programme_id}")).with_field("programme_id").into(),
+        }
+    } else {
+        let Some(duration) = req.duration.filter(|duration| *duration > 0) else {
+            return ApiError::new(ApiErrorCode::MissingParameter, "duration or programme_id is required").with_field("duration").into();
+        };
+        let start = req.start.unwrap_or_else(|| chrono::Utc::now().timestamp());
+        (entry.name.clone(), start, start + i64::from(duration) * 60)
+    };
+    let recording = Recording {
+        id: format!("{:016x}", rand::random::<u64>()),
+        target: target.name.clone(),
+        virtual_id: entry.virtual_id,
+        name: entry.name,
+        title,
+        start,
+        stop,
+        status: RecordingStatus::Scheduled,
+        file: None,
+        size: 0,
+        error: None,
+        rule: None,
+    };
+    let recording = app_state.recorder.schedule(recording);
+    audit_api::audit_config_change(&http_req, &app_state, AuditSection::Recording, &json!({}), &json!({ &recording.id: &recording }));
+    HttpResponse::Ok().json(recording)
+}
+
+pub async fn recording_delete(
+    http_req: HttpRequest,
+    path: web::Path<String>,
+    app_state: web::Data<AppState>,
+) -> HttpResponse {
+    let id = path.into_inner();
+    let old = app_state.recorder.list().into_iter().find(|recording| recording.id == id);
+    if app_state.recorder.remove(&id) {
+        audit_api::audit_config_change(&http_req, &app_state, AuditSection::Recording, &json!({ &id: old }), &json!({}));
+        HttpResponse::Ok().finish()
+    } else {
+        recording_not_found(&id)
+    }
+}
+
+pub async fn recording_download(
+    req: HttpRequest,
+    path: web::Path<String>,
+    app_state: web::Data<AppState>,
+) -> HttpResponse {
+    let id = path.into_inner();
+    if let Some(file_path) = app_state.recorder.get_file_path(&id) {
+        if let Ok(file) = actix_files::NamedFile::open_async(file_path).await {
+            return file.set_content_type(mime::Mime::from_str("video/mp2t").unwrap_or(mime::APPLICATION_OCTET_STREAM)).into_response(&req);
+        }
+    }
+    recording_not_found(&id)
+}
@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::io::Error;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use log::debug;
+use tokio::sync::OnceCell;
+
+use crate::utils::request_utils::mask_sensitive_info;
+
+/// Seconds a failed upstream request is not repeated.
+const FAILED_REQUEST_TTL_SECS: u64 = 30;
+
+type SharedResult = Arc<OnceCell<Result<String, String>>>;
+
+/// Sends each provider info request only once when several clients request the same url at the same time (single flight),
+/// the waiting clients get the result of the running request. Failed requests are answered from a short-lived
+/// negative cache, the provider is not asked again until it expires.
+pub struct RequestCoalescer {
+    running: Mutex<HashMap<String, SharedResult>>,
+    failed: Mutex<HashMap<String, Instant>>,
+    failed_ttl: Duration,
+}
+
+impl Default for RequestCoalescer {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(FAILED_REQUEST_TTL_SECS))
+    }
+}
+
+impl RequestCoalescer {
+    pub fn new(failed_ttl: Duration) -> Self {
+        Self { running: Mutex::new(HashMap::new()), failed: Mutex::new(HashMap::new()), failed_ttl }
+    }
+
+    fn is_failed(&self, url: &str) -> bool {
+        self.failed.lock().is_ok_and(|mut failed| {
+            failed.retain(|_, expires| *expires > Instant::now());
+            failed.contains_key(url)
+        })
+    }
+
+    /// Returns the content of the url, `fetch` is only called if no request for the url is running.
+    /// If the client of the running request disconnects, one of the waiting clients sends the request.
+    pub async fn get<F, Fut>(&self, url: &str, fetch: F) -> Result<String, Error>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output=Result<String, Error>>,
+    {
+        if self.is_failed(url) {
+            return Err(Error::other(format!("Request failed recently {}", mask_sensitive_info(url))));
+        }
+        let shared_result = match self.running.lock() {
+            Ok(mut running) => Arc::clone(running.entry(url.to_string()).or_default()),
+            Err(_) => return fetch().await,
+        };
+        let result = shared_result.get_or_init(|| async {
+            fetch().await.map_err(|err| err.to_string())
+        }).await.clone();
+        if let Ok(mut running) = self.running.lock() {
+            if running.get(url).is_some_and(|current| Arc::ptr_eq(current, &shared_result)) {
+                running.remove(url);
+                if let (Err(err), Ok(mut failed)) = (&result, self.failed.lock()) {
+                    debug!("Upstream request failed {}: {err}", mask_sensitive_info(url));
+                    failed.insert(url.to_string(), Instant::now() + self.failed_ttl);
+                }
+            }
+        }
+        result.map_err(Error::other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Error;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use crate::api::request_coalescer::RequestCoalescer;
+
+    #[actix_rt::test]
+    async fn test_request_coalescer() {
+        let coalescer = RequestCoalescer::new(Duration::from_secs(60));
+        let calls = AtomicUsize::new(0);
+        let fetch = || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            actix_rt::time::sleep(Duration::from_millis(50)).await;
+            Ok::<String, Error>("info".to_string())
+        };
+        let (first, second) = futures::join!(coalescer.get("http://host/info/1", fetch), coalescer.get("http://host/info/1", fetch));
+        assert_eq!((first.unwrap(), second.unwrap()), ("info".to_string(), "info".to_string()));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        let failing = || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err::<String, Error>(Error::other("banned"))
+        };
+        assert!(coalescer.get("http://host/info/2", failing).await.is_err());
+        // the failed request is not sent again
+        assert!(coalescer.get("http://host/info/2", failing).await.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+}
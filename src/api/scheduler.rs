@@ -6,7 +6,7 @@ use cron::Schedule;
 use log::error;
 use crate::api::api_model::AppState;
 use crate::exit;
-use crate::processing::playlist_processor::exec_processing;
+use crate::repository::job_repository::JobTrigger;
 
 fn datetime_to_instant(datetime: DateTime<FixedOffset>) -> Instant {
     // Convert DateTime<FixedOffset> to SystemTime
@@ -32,7 +32,7 @@ pub async fn start_scheduler(expression: &str, data: Data<AppState>) -> ! {
                 let mut upcoming = schedule.upcoming(offset).take(1);
                 if let Some(datetime) = upcoming.next() {
                     actix_rt::time::sleep_until(actix_rt::time::Instant::from(datetime_to_instant(datetime))).await;
-                    exec_processing(data.config.clone(), data.targets.clone()).await;
+                    data.jobs.enqueue(JobTrigger::Schedule, None, None);
                 }
             }
         }
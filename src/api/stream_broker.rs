@@ -0,0 +1,318 @@
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+use futures::stream::{AbortHandle, Abortable};
+use futures::{Stream, StreamExt};
+use log::{debug, log_enabled, Level};
+use serde::Serialize;
+use tokio::sync::Notify;
+
+/// Response headers of the upstream which are sent to every client.
+pub type StreamHeaders = Vec<(String, Vec<u8>)>;
+
+/// Identifies a shared upstream. Virtual ids are unique per target.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StreamKey {
+    pub target: String,
+    pub virtual_id: u32,
+}
+
+/// Fixed size buffer of the last received chunks.
+/// Every chunk gets a sequence number, clients keep track of their position.
+/// If a client is too slow and the chunks are already dropped, it continues with the oldest available chunk.
+struct RingBuffer {
+    chunks: VecDeque<Bytes>,
+    first_seq: u64,
+    capacity: usize,
+    closed: bool,
+}
+
+impl RingBuffer {
+    fn new(capacity: usize) -> Self {
+        Self {
+            chunks: VecDeque::with_capacity(capacity),
+            first_seq: 0,
+            capacity,
+            closed: false,
+        }
+    }
+
+    fn push(&mut self, chunk: Bytes) {
+        if self.chunks.len() >= self.capacity {
+            self.chunks.pop_front();
+            self.first_seq += 1;
+        }
+        self.chunks.push_back(chunk);
+    }
+
+    fn get(&self, seq: u64) -> Option<(u64, Bytes)> {
+        let seq = seq.max(self.first_seq);
+        usize::try_from(seq - self.first_seq).ok()
+            .and_then(|index| self.chunks.get(index))
+            .map(|chunk| (seq, chunk.clone()))
+    }
+}
+
+/// One upstream connection which is shared between all clients watching the same stream.
+pub struct SharedStream {
+    key: StreamKey,
+    buffer: Mutex<RingBuffer>,
+    notify: Notify,
+    abort_handle: AbortHandle,
+    start_time: i64,
+    clients: AtomicU64,
+    bytes: AtomicU64,
+    headers: StreamHeaders,
+}
+
+impl SharedStream {
+    fn push(&self, chunk: Bytes) {
+        self.bytes.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+        self.buffer.lock().unwrap().push(chunk);
+        self.notify.notify_waiters();
+    }
+
+    fn close(&self) {
+        self.buffer.lock().unwrap().closed = true;
+        self.notify.notify_waiters();
+    }
+}
+
+/// Metrics of a shared upstream.
+#[derive(Serialize)]
+pub struct SharedStreamInfo {
+    pub target: String,
+    pub virtual_id: u32,
+    pub clients: u64,
+    pub bytes: u64,
+    pub start_time: i64,
+}
+
+/// Metrics of the stream broker.
+#[derive(Serialize)]
+pub struct StreamBrokerMetrics {
+    /// Number of upstream connections opened since start.
+    pub upstream_connections: u64,
+    /// Number of client connections served since start.
+    pub client_connections: u64,
+    /// Number of client connections served from an already opened upstream.
+    pub shared_connections: u64,
+    pub streams: Vec<SharedStreamInfo>,
+}
+
+/// Client side of a shared stream, releases the reference when dropped.
+struct SharedStreamClient {
+    broker: Arc<StreamBroker>,
+    stream: Arc<SharedStream>,
+    position: u64,
+}
+
+impl Drop for SharedStreamClient {
+    fn drop(&mut self) {
+        self.broker.release(&self.stream);
+    }
+}
+
+/// Opens each provider stream once and fans it out to all clients.
+/// Streams are reference counted, the upstream is shut down when the last client disconnects.
+#[derive(Default)]
+pub struct StreamBroker {
+    streams: Mutex<HashMap<StreamKey, Arc<SharedStream>>>,
+    upstream_connections: AtomicU64,
+    client_connections: AtomicU64,
+    shared_connections: AtomicU64,
+}
+
+impl StreamBroker {
+    /// Subscribes to an already opened upstream if there is one.
+    pub fn subscribe(broker: &Arc<Self>, key: &StreamKey) -> Option<(StreamHeaders, impl Stream<Item=Result<Bytes, std::io::Error>>)> {
+        let stream = {
+            let streams = broker.streams.lock().unwrap();
+            let stream = streams.get(key).map(Arc::clone)?;
+            stream.clients.fetch_add(1, Ordering::Relaxed);
+            stream
+        };
+        broker.shared_connections.fetch_add(1, Ordering::Relaxed);
+        Some((stream.headers.clone(), Self::client_stream(broker, stream)))
+    }
+
+    /// Registers a new upstream, starts reading it into the ring buffer and subscribes the first client.
+    /// If another client registered the same stream in the meantime, the upstream is dropped and the client
+    /// subscribes to the registered one. Returns the headers of the stream the client is subscribed to.
+    pub fn register<S, E>(broker: &Arc<Self>, key: StreamKey, upstream: S, headers: StreamHeaders, buffer_size: usize) -> (StreamHeaders, impl Stream<Item=Result<Bytes, std::io::Error>>)
+    where
+        S: Stream<Item=Result<Bytes, E>> + 'static,
+    {
+        let (abort_handle, abort_registration) = AbortHandle::new_pair();
+        let registered = match broker.streams.lock().unwrap().entry(key) {
+            Entry::Occupied(entry) => {
+                let stream = Arc::clone(entry.get());
+                stream.clients.fetch_add(1, Ordering::Relaxed);
+                Err(stream)
+            }
+            Entry::Vacant(entry) => {
+                let shared_stream = Arc::new(SharedStream {
+                    key: entry.key().clone(),
+                    buffer: Mutex::new(RingBuffer::new(buffer_size)),
+                    notify: Notify::new(),
+                    abort_handle,
+                    start_time: chrono::Local::now().timestamp(),
+                    clients: AtomicU64::new(1),
+                    bytes: AtomicU64::new(0),
+                    headers,
+                });
+                entry.insert(Arc::clone(&shared_stream));
+                Ok(shared_stream)
+            }
+        };
+        let shared_stream = match registered {
+            Ok(shared_stream) => shared_stream,
+            Err(stream) => {
+                broker.shared_connections.fetch_add(1, Ordering::Relaxed);
+                return (stream.headers.clone(), Self::client_stream(broker, stream));
+            }
+        };
+        broker.upstream_connections.fetch_add(1, Ordering::Relaxed);
+
+        let stream = Arc::clone(&shared_stream);
+        let streams = Arc::clone(broker);
+        actix_rt::spawn(async move {
+            let mut upstream = Abortable::new(Box::pin(upstream), abort_registration);
+            while let Some(Ok(chunk)) = upstream.next().await {
+                stream.push(chunk);
+            }
+            streams.remove(&stream);
+            stream.close();
+            if log_enabled!(Level::Debug) {
+                debug!("Shared stream closed {} {}", stream.key.target, stream.key.virtual_id);
+            }
+        });
+        (shared_stream.headers.clone(), Self::client_stream(broker, shared_stream))
+    }
+
+    fn client_stream(broker: &Arc<Self>, stream: Arc<SharedStream>) -> impl Stream<Item=Result<Bytes, std::io::Error>> {
+        broker.client_connections.fetch_add(1, Ordering::Relaxed);
+        let client = SharedStreamClient { broker: Arc::clone(broker), stream, position: 0 };
+        futures::stream::unfold(client, |mut client| async move {
+            loop {
+                let shared = Arc::clone(&client.stream);
+                // register for notification before the buffer is checked, otherwise we could miss a chunk
+                let notified = shared.notify.notified();
+                {
+                    let buffer = shared.buffer.lock().unwrap();
+                    if let Some((seq, chunk)) = buffer.get(client.position) {
+                        client.position = seq + 1;
+                        return Some((Ok(chunk), client));
+                    }
+                    if buffer.closed {
+                        return None;
+                    }
+                }
+                notified.await;
+            }
+        })
+    }
+
+    /// Removes the stream from the broker if it is still the registered one for its key.
+    fn remove(&self, stream: &Arc<SharedStream>) {
+        let mut streams = self.streams.lock().unwrap();
+        if streams.get(&stream.key).is_some_and(|registered| Arc::ptr_eq(registered, stream)) {
+            streams.remove(&stream.key);
+        }
+    }
+
+    /// Decrements the reference count, the last client shuts the upstream down.
+    fn release(&self, stream: &Arc<SharedStream>) {
+        let mut streams = self.streams.lock().unwrap();
+        if stream.clients.fetch_sub(1, Ordering::Relaxed) == 1 {
+            if streams.get(&stream.key).is_some_and(|registered| Arc::ptr_eq(registered, stream)) {
+                streams.remove(&stream.key);
+            }
+            stream.abort_handle.abort();
+        }
+    }
+
+    pub fn get_metrics(&self) -> StreamBrokerMetrics {
+        let mut streams: Vec<SharedStreamInfo> = self.streams.lock().unwrap().values().map(|stream| SharedStreamInfo {
+            target: stream.key.target.clone(),
+            virtual_id: stream.key.virtual_id,
+            clients: stream.clients.load(Ordering::Relaxed),
+            bytes: stream.bytes.load(Ordering::Relaxed),
+            start_time: stream.start_time,
+        }).collect();
+        streams.sort_by_key(|s| s.start_time);
+        StreamBrokerMetrics {
+            upstream_connections: self.upstream_connections.load(Ordering::Relaxed),
+            client_connections: self.client_connections.load(Ordering::Relaxed),
+            shared_connections: self.shared_connections.load(Ordering::Relaxed),
+            streams,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use bytes::Bytes;
+    use futures::StreamExt;
+
+    use crate::api::stream_broker::{RingBuffer, StreamBroker, StreamKey};
+
+    #[test]
+    fn test_ring_buffer() {
+        let mut buffer = RingBuffer::new(2);
+        assert!(buffer.get(0).is_none());
+        for chunk in ["a", "b", "c"] {
+            buffer.push(Bytes::from(chunk));
+        }
+        // a slow client continues with the oldest buffered chunk
+        assert_eq!(buffer.get(0), Some((1, Bytes::from("b"))));
+        assert_eq!(buffer.get(2), Some((2, Bytes::from("c"))));
+        assert!(buffer.get(3).is_none());
+    }
+
+    #[actix_rt::test]
+    async fn test_shared_stream() {
+        let broker = Arc::new(StreamBroker::default());
+        let key = StreamKey { target: "tv".to_string(), virtual_id: 1 };
+        let (tx, rx) = futures::channel::mpsc::unbounded::<Result<Bytes, std::io::Error>>();
+        let (_, first) = StreamBroker::register(&broker, key.clone(), rx, vec![], 4);
+        let mut first = Box::pin(first);
+        tx.unbounded_send(Ok(Bytes::from("a"))).unwrap();
+        assert_eq!(first.next().await.unwrap().unwrap(), Bytes::from("a"));
+
+        // a late client starts with the buffered chunks of the same upstream
+        let (_, second) = StreamBroker::subscribe(&broker, &key).unwrap();
+        let mut second = Box::pin(second);
+        tx.unbounded_send(Ok(Bytes::from("b"))).unwrap();
+        assert_eq!(second.next().await.unwrap().unwrap(), Bytes::from("a"));
+        assert_eq!(second.next().await.unwrap().unwrap(), Bytes::from("b"));
+        assert_eq!(first.next().await.unwrap().unwrap(), Bytes::from("b"));
+
+        let metrics = broker.get_metrics();
+        assert_eq!((metrics.upstream_connections, metrics.client_connections, metrics.shared_connections), (1, 2, 1));
+        assert_eq!(metrics.streams[0].clients, 2);
+
+        // a client which opened its own upstream concurrently shares the registered one
+        let (_, concurrent) = futures::channel::mpsc::unbounded::<Result<Bytes, std::io::Error>>();
+        let (headers, third) = StreamBroker::register(&broker, key.clone(), concurrent, vec![("x".to_string(), vec![])], 4);
+        assert!(headers.is_empty());
+        let mut third = Box::pin(third);
+        assert_eq!(third.next().await.unwrap().unwrap(), Bytes::from("a"));
+        let metrics = broker.get_metrics();
+        assert_eq!((metrics.upstream_connections, metrics.client_connections, metrics.shared_connections), (1, 3, 2));
+        assert_eq!(metrics.streams.len(), 1);
+        drop(third);
+
+        // the last client shuts the upstream down
+        drop(first);
+        assert_eq!(broker.get_metrics().streams[0].clients, 1);
+        drop(second);
+        assert!(broker.get_metrics().streams.is_empty());
+        assert!(StreamBroker::subscribe(&broker, &key).is_none());
+    }
+}
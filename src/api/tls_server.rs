@@ -0,0 +1,116 @@
+use std::fmt::Debug;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+
+use actix_http::body::MessageBody;
+use actix_http::error::DispatchError;
+use actix_http::{HttpService, Request, Response};
+use actix_server::Server;
+use actix_service::{fn_service, map_config, IntoServiceFactory, ServiceFactory, ServiceFactoryExt};
+use actix_web::dev::AppConfig;
+use log::{error, info};
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::CertifiedKey;
+use tokio::net::TcpStream;
+use tokio_rustls::TlsAcceptor;
+
+use crate::model::config::ConfigApiTls;
+
+fn load_certified_key(cert_path: &Path, key_path: &Path) -> std::io::Result<CertifiedKey> {
+    let certs = rustls_pemfile::certs(&mut BufReader::new(File::open(cert_path)?)).collect::<Result<Vec<_>, _>>()?;
+    if certs.is_empty() {
+        return Err(std::io::Error::other(format!("No certificate found in {}", cert_path.display())));
+    }
+    let key = rustls_pemfile::private_key(&mut BufReader::new(File::open(key_path)?))?
+        .ok_or_else(|| std::io::Error::other(format!("No private key found in {}", key_path.display())))?;
+    let signing_key = rustls::crypto::ring::sign::any_supported_type(&key).map_err(std::io::Error::other)?;
+    Ok(CertifiedKey::new(certs, signing_key))
+}
+
+fn get_modified(cert_path: &Path, key_path: &Path) -> Option<SystemTime> {
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|meta| meta.modified()).ok();
+    modified(cert_path).max(modified(key_path))
+}
+
+/// Serves the certificate and reloads it when the cert or key file changes, a renewed certificate
+/// is used for new connections without restarting the server.
+#[derive(Debug)]
+struct ReloadingCertResolver {
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    current: RwLock<(Option<SystemTime>, Arc<CertifiedKey>)>,
+}
+
+impl ReloadingCertResolver {
+    fn new(cert_path: PathBuf, key_path: PathBuf) -> std::io::Result<Self> {
+        let modified = get_modified(&cert_path, &key_path);
+        let certified_key = Arc::new(load_certified_key(&cert_path, &key_path)?);
+        Ok(Self { cert_path, key_path, current: RwLock::new((modified, certified_key)) })
+    }
+}
+
+impl ResolvesServerCert for ReloadingCertResolver {
+    fn resolve(&self, _client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        let modified = get_modified(&self.cert_path, &self.key_path);
+        if modified.is_some() && modified != self.current.read().ok()?.0 {
+            match load_certified_key(&self.cert_path, &self.key_path) {
+                Ok(certified_key) => {
+                    info!("Reloaded tls certificate {}", self.cert_path.display());
+                    *self.current.write().ok()? = (modified, Arc::new(certified_key));
+                }
+                // keep the old certificate, the files may be in the middle of a renewal
+                Err(err) => error!("Failed to reload tls certificate {}: {err}", self.cert_path.display()),
+            }
+        }
+        self.current.read().ok().map(|current| Arc::clone(&current.1))
+    }
+}
+
+fn create_acceptor(tls: &ConfigApiTls) -> std::io::Result<TlsAcceptor> {
+    let resolver = ReloadingCertResolver::new(PathBuf::from(&tls.cert), PathBuf::from(&tls.key))?;
+    let mut server_config = rustls::ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(std::io::Error::other)?
+        .with_no_client_auth()
+        .with_cert_resolver(Arc::new(resolver));
+    server_config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(TlsAcceptor::from(Arc::new(server_config)))
+}
+
+/// Creates the https listeners for the app of the `HttpServer`, the connections are served with HTTP/1.1.
+pub fn create_tls_server<F, I, S, B>(tls: &ConfigApiTls, app_factory: F) -> std::io::Result<Server>
+where
+    F: Fn() -> I + Send + Clone + 'static,
+    I: IntoServiceFactory<S, Request>,
+    S: ServiceFactory<Request, Config=AppConfig> + 'static,
+    S::Error: Into<actix_web::Error> + 'static,
+    S::InitError: Debug,
+    S::Response: Into<Response<B>> + 'static,
+    B: MessageBody + 'static,
+{
+    let acceptor = create_acceptor(tls)?;
+    let mut builder = Server::build();
+    for (index, addr) in tls.listen.iter().enumerate() {
+        let acceptor = acceptor.clone();
+        let app_factory = app_factory.clone();
+        builder = builder.bind(format!("m3u-filter-tls-{index}"), addr.as_str(), move || {
+            let acceptor = acceptor.clone();
+            let app = map_config(
+                app_factory().into_factory().map_err(|err| err.into().error_response()),
+                |()| AppConfig::default());
+            fn_service(move |stream: TcpStream| {
+                let acceptor = acceptor.clone();
+                async move {
+                    let peer_addr = stream.peer_addr().ok();
+                    let tls_stream = acceptor.accept(stream).await.map_err(DispatchError::Io)?;
+                    Ok::<_, DispatchError>((tls_stream, peer_addr))
+                }
+            }).and_then(HttpService::build().secure().h1(app))
+        })?;
+        info!("Server running: https://{addr}");
+    }
+    Ok(builder.run())
+}
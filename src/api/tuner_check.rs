@@ -0,0 +1,187 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::LazyLock;
+
+use regex::Regex;
+use serde_json::Value;
+
+use crate::model::config::Config;
+
+/// Guides above this size are rejected or truncated by some media servers.
+const MAX_GUIDE_SIZE: usize = 50 * 1024 * 1024;
+
+static M3U_ATTRIBUTE_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"(tvg-id|tvg-chno)="([^"]*)""#).unwrap());
+static XMLTV_CHANNEL_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"<channel\s+id="([^"]*)""#).unwrap());
+
+#[derive(Default)]
+struct TunerCheckReport {
+    errors: Vec<String>,
+    warnings: Vec<String>,
+}
+
+/// Returns the `tvg-id`s of the lineup.
+fn check_lineup(content: &str, report: &mut TunerCheckReport) -> HashSet<String> {
+    let mut channel_numbers: HashMap<String, usize> = HashMap::new();
+    let mut epg_ids: HashMap<String, usize> = HashMap::new();
+    let mut missing_chno = 0;
+    let mut missing_epg_id = 0;
+    let mut channel_count = 0;
+    for line in content.lines().filter(|line| line.starts_with("#EXTINF")) {
+        channel_count += 1;
+        let mut chno = None;
+        let mut epg_id = None;
+        for caps in M3U_ATTRIBUTE_REGEX.captures_iter(line) {
+            let value = caps[2].trim();
+            if !value.is_empty() {
+                if &caps[1] == "tvg-chno" { chno = Some(value.to_string()) } else { epg_id = Some(value.to_string()) }
+            }
+        }
+        match chno {
+            Some(number) => *channel_numbers.entry(number).or_default() += 1,
+            None => missing_chno += 1,
+        }
+        match epg_id {
+            Some(id) => *epg_ids.entry(id).or_default() += 1,
+            None => missing_epg_id += 1,
+        }
+    }
+    if channel_count == 0 {
+        report.errors.push("lineup: the playlist has no channels".to_string());
+    }
+    if missing_chno > 0 {
+        report.errors.push(format!("lineup: {missing_chno} of {channel_count} channels have no channel number (tvg-chno)"));
+    }
+    let duplicate_numbers: Vec<&String> = channel_numbers.iter().filter(|(_, count)| **count > 1).map(|(number, _)| number).collect();
+    if !duplicate_numbers.is_empty() {
+        report.errors.push(format!("lineup: {} channel numbers are used more than once, e.g. {}", duplicate_numbers.len(), duplicate_numbers[0]));
+    }
+    if missing_epg_id > 0 {
+        report.warnings.push(format!("lineup: {missing_epg_id} of {channel_count} channels have no epg id (tvg-id)"));
+    }
+    let duplicate_ids: Vec<&String> = epg_ids.iter().filter(|(_, count)| **count > 1).map(|(id, _)| id).collect();
+    if !duplicate_ids.is_empty() {
+        report.warnings.push(format!("lineup: {} epg ids are used by more than one channel, e.g. {}", duplicate_ids.len(), duplicate_ids[0]));
+    }
+    epg_ids.into_keys().collect()
+}
+
+fn check_live_streams(streams: &Value, report: &mut TunerCheckReport) {
+    let Some(streams) = streams.as_array() else {
+        report.errors.push("lineup: get_live_streams did not return a list".to_string());
+        return;
+    };
+    let mut stream_ids = HashSet::new();
+    let duplicates = streams.iter()
+        .filter_map(|stream| stream.get("stream_id").map(ToString::to_string))
+        .filter(|stream_id| !stream_ids.insert(stream_id.clone()))
+        .count();
+    if duplicates > 0 {
+        report.errors.push(format!("lineup: {duplicates} duplicate stream ids in get_live_streams"));
+    }
+}
+
+fn check_guide(content: &str, epg_ids: &HashSet<String>, report: &mut TunerCheckReport) {
+    if content.len() > MAX_GUIDE_SIZE {
+        report.errors.push(format!("guide: the xmltv file has {} MB, media servers may reject guides above {} MB",
+                                   content.len() / 1024 / 1024, MAX_GUIDE_SIZE / 1024 / 1024));
+    }
+    let guide_ids: HashSet<&str> = XMLTV_CHANNEL_REGEX.captures_iter(content).filter_map(|caps| caps.get(1)).map(|id| id.as_str()).collect();
+    if guide_ids.is_empty() {
+        report.warnings.push("guide: the xmltv file has no channels".to_string());
+        return;
+    }
+    let missing = epg_ids.iter().filter(|id| !guide_ids.contains(id.as_str())).count();
+    if missing > 0 {
+        report.warnings.push(format!("guide: {missing} epg ids of the lineup have no guide data"));
+    }
+}
+
+fn fetch(url: &str) -> Result<String, String> {
+    match reqwest::blocking::get(url) {
+        Ok(response) if response.status().is_success() => response.text().map_err(|err| err.to_string()),
+        Ok(response) => Err(format!("status {}", response.status())),
+        Err(err) => Err(err.without_url().to_string()),
+    }
+}
+
+/// Requests discover (user info), lineup and guide like Plex/Jellyfin/Emby do
+/// from the running server and prints the incompatibilities.
+/// Returns `false` if errors were found.
+pub fn tuner_check(cfg: &Config, username: &str) -> bool {
+    let mut report = TunerCheckReport::default();
+    let credentials = cfg.t_api_proxy.read().unwrap().as_ref().and_then(|api_proxy| api_proxy.get_user_credentials(username));
+    let Some((user, target_name)) = credentials else {
+        println!("error: user {username} not found in the api-proxy config");
+        return false;
+    };
+    // the tenant path accepts the users of scoped and unscoped targets
+    let base_url = format!("http://localhost:{}/t/{target_name}", cfg.api.port);
+    let query = format!("username={}&password={}", user.username, user.password);
+    println!("Checking target {target_name} for user {username} at {base_url}");
+
+    match fetch(&format!("{base_url}/player_api.php?{query}")).map(|content| serde_json::from_str::<Value>(&content)) {
+        Ok(Ok(info)) => {
+            if info.get("user_info").and_then(|user_info| user_info.get("auth")).and_then(Value::as_i64) != Some(1) {
+                report.errors.push("discover: user is not authorized".to_string());
+            }
+        }
+        Ok(Err(err)) => report.errors.push(format!("discover: invalid response {err}")),
+        Err(err) => report.errors.push(format!("discover: request failed {err}")),
+    }
+
+    let epg_ids = match fetch(&format!("{base_url}/get.php?{query}&type=m3u_plus")) {
+        Ok(content) => check_lineup(&content, &mut report),
+        Err(err) => {
+            report.errors.push(format!("lineup: request failed {err}"));
+            HashSet::new()
+        }
+    };
+
+    match fetch(&format!("{base_url}/player_api.php?{query}&action=get_live_streams")).map(|content| serde_json::from_str::<Value>(&content)) {
+        Ok(Ok(streams)) => check_live_streams(&streams, &mut report),
+        Ok(Err(err)) => report.errors.push(format!("lineup: invalid get_live_streams response {err}")),
+        Err(err) => report.warnings.push(format!("lineup: get_live_streams request failed {err}")),
+    }
+
+    match fetch(&format!("{base_url}/xmltv.php?{query}")) {
+        Ok(content) => check_guide(&content, &epg_ids, &mut report),
+        Err(err) => report.warnings.push(format!("guide: request failed {err}")),
+    }
+
+    for warning in &report.warnings {
+        println!("warning: {warning}");
+    }
+    for error in &report.errors {
+        println!("error: {error}");
+    }
+    if report.errors.is_empty() {
+        println!("No incompatibilities found");
+    }
+    report.errors.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::api::tuner_check::{check_guide, check_lineup, TunerCheckReport};
+
+    #[test]
+    fn test_check_lineup_and_guide() {
+        let playlist = "#EXTM3U
+#EXTINF:-1 tvg-id=\"news.de\" tvg-name=\"News\" group-title=\"DE\" tvg-chno=\"1\",News
+http://localhost/1
+#EXTINF:-1 tvg-id=\"sport.de\" tvg-name=\"Sport\" group-title=\"DE\" tvg-chno=\"1\",Sport
+http://localhost/2
+#EXTINF:-1 tvg-id=\"\" tvg-name=\"Kids\" group-title=\"DE\",Kids
+http://localhost/3
+";
+        let mut report = TunerCheckReport::default();
+        let epg_ids = check_lineup(playlist, &mut report);
+        assert_eq!(epg_ids, HashSet::from(["news.de".to_string(), "sport.de".to_string()]));
+        assert_eq!(report.errors.len(), 2);
+        assert_eq!(report.warnings.len(), 1);
+
+        check_guide(r#"<tv><channel id="news.de"><display-name>News</display-name></channel></tv>"#, &epg_ids, &mut report);
+        assert_eq!(report.warnings.len(), 2);
+    }
+}
@@ -1,44 +1,51 @@
-use std::sync::Arc;
+use std::collections::BTreeMap;
+use std::str::FromStr;
 
-use actix_web::{HttpResponse, web};
+use actix_web::{HttpRequest, HttpResponse, web};
+use actix_web::http::header;
 use actix_web::middleware::Condition;
 use actix_web_httpauth::middleware::HttpAuthentication;
 use log::error;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
 
+use crate::api::api_error::{ApiError, ApiErrorCode};
 use crate::api::api_model::{AppState, PlaylistRequest, ServerConfig, ServerInputConfig, ServerSourceConfig, ServerTargetConfig};
-use crate::api::download_api;
+use crate::api::{audit_api, download_api, job_api, recording_api};
 use crate::auth::authenticator::validator;
+use crate::auth::role::{Role, RoleScope};
 use crate::m3u_filter_error::M3uFilterError;
 use crate::model::api_proxy::{ApiProxyConfig, ApiProxyServerInfo, ProxyUserCredentials, TargetUser};
-use crate::model::config::{Config, ConfigDto, ConfigInput, ConfigInputOptions, ConfigSource, ConfigTarget, InputType, validate_targets};
-use crate::processing::playlist_processor;
-use crate::utils::{config_reader, download};
-use crate::utils::request_utils::mask_sensitive_info;
-
-fn intern_save_config_api_proxy(backup_dir: &str, api_proxy: &ApiProxyConfig, file_path: &str) -> Option<M3uFilterError> {
-    match config_reader::save_api_proxy(file_path, backup_dir, api_proxy) {
-        Ok(()) => {}
-        Err(err) => {
-            error!("Failed to save api_proxy.yml {}", err.to_string());
-            return Some(err);
-        }
-    }
-    None
+use crate::model::config::{Config, ConfigDto, ConfigInput, ConfigInputOptions, ConfigSource, ConfigTarget, InputType, M3uStrictness, TargetType};
+use crate::model::playlist::XtreamCluster;
+use crate::processing::input_source::{self, fetch_input, InputContext};
+use crate::processing::{account_check, stream_health};
+use crate::repository::m3u_repository::m3u_cleanup_playlist_files;
+use crate::repository::export_repository::{ExportColumn, ExportFormat};
+use crate::repository::audit_repository::AuditSection;
+use crate::repository::{alias_repository, export_repository, override_repository, persist_repository, quarantine_repository, search_repository, snapshot_repository, xtream_repository};
+use crate::repository::storage::{hash_string, hex_decode_hash, hex_encode};
+use crate::repository::url_index::url_index_find;
+use crate::utils::config_reader;
+use crate::utils::config_reader::ConfigFileChange;
+
+/// Hours a channel is quarantined if the request has no `hours`.
+const DEFAULT_QUARANTINE_HOURS: u32 = 24;
+
+fn intern_save_config_api_proxy(backup_dir: &str, api_proxy: &ApiProxyConfig, file_path: &str) -> Result<ConfigFileChange, M3uFilterError> {
+    config_reader::save_api_proxy(file_path, backup_dir, api_proxy).inspect_err(|err| {
+        error!("Failed to save api_proxy.yml {}", err.to_string());
+    })
 }
 
-fn intern_save_config_main(file_path: &str, backup_dir: &str, cfg: &ConfigDto) -> Option<M3uFilterError> {
-    match config_reader::save_main_config(file_path, backup_dir, cfg) {
-        Ok(()) => {}
-        Err(err) => {
-            error!("Failed to save config.yml {}", err.to_string());
-            return Some(err);
-        }
-    }
-    None
+fn intern_save_config_main(file_path: &str, backup_dir: &str, cfg: &ConfigDto) -> Result<ConfigFileChange, M3uFilterError> {
+    config_reader::save_main_config(file_path, backup_dir, cfg).inspect_err(|err| {
+        error!("Failed to save config.yml {}", err.to_string());
+    })
 }
 
 async fn save_config_api_proxy_user(
+    http_req: HttpRequest,
     req: web::Json<Vec<TargetUser>>,
     app_state: web::Data<AppState>,
 ) -> HttpResponse {
@@ -47,15 +54,18 @@ async fn save_config_api_proxy_user(
     if let Some(api_proxy) = app_state.config.t_api_proxy.write().unwrap().as_mut() {
         let backup_dir = app_state.config.backup_dir.as_ref().unwrap().as_str();
         api_proxy.user = users;
-        if let Some(err) = intern_save_config_api_proxy(backup_dir, api_proxy, app_state.config.t_api_proxy_file_path.as_str()) {
-            return HttpResponse::InternalServerError().json(json!({"error": err.to_string()}));
+        match intern_save_config_api_proxy(backup_dir, api_proxy, app_state.config.t_api_proxy_file_path.as_str()) {
+            Ok(change) => audit_api::audit_config_change(&http_req, &app_state, AuditSection::ApiProxyUser, &change.old.get("user"), &change.new.get("user")),
+            Err(err) => return ApiError::new(ApiErrorCode::SaveFailed, err.to_string()).into(),
         }
         api_proxy.user.iter_mut().flat_map(|t| &mut t.credentials).for_each(|c| c.prepare(true));
     }
+    m3u_cleanup_playlist_files(&app_state.config);
     HttpResponse::Ok().finish()
 }
 
 async fn save_config_main(
+    http_req: HttpRequest,
     req: web::Json<ConfigDto>,
     app_state: web::Data<AppState>,
 ) -> HttpResponse {
@@ -63,30 +73,33 @@ async fn save_config_main(
     if cfg.is_valid() {
         let file_path = app_state.config.t_config_file_path.as_str();
         let backup_dir = app_state.config.backup_dir.as_ref().unwrap().as_str();
-        if let Some(err) = intern_save_config_main(file_path, backup_dir, &cfg) {
-            return HttpResponse::InternalServerError().json(json!({"error": err.to_string()}));
+        match intern_save_config_main(file_path, backup_dir, &cfg) {
+            Ok(change) => audit_api::audit_config_change(&http_req, &app_state, AuditSection::Config, &change.old, &change.new),
+            Err(err) => return ApiError::new(ApiErrorCode::SaveFailed, err.to_string()).into(),
         }
         HttpResponse::Ok().finish()
     } else {
-        HttpResponse::BadRequest().json(json!({"error": "Invalid content"}))
+        ApiError::new(ApiErrorCode::InvalidContent, "Invalid content").with_hint("api host is required, video download episode_pattern must be a valid regular expression").into()
     }
 }
 
 async fn save_config_api_proxy_config(
+    http_req: HttpRequest,
     req: web::Json<Vec<ApiProxyServerInfo>>,
     app_state: web::Data<AppState>,
 ) -> HttpResponse {
     let mut req_api_proxy = req.0;
     for server_info in &mut req_api_proxy {
         if !server_info.is_valid() {
-            return HttpResponse::BadRequest().json(json!({"error": "Invalid content"}));
+            return ApiError::new(ApiErrorCode::InvalidContent, format!("Invalid server {}", server_info.name)).with_field("server").into();
         }
     }
     if let Some(api_proxy) = app_state.config.t_api_proxy.write().unwrap().as_mut() {
         api_proxy.server = req_api_proxy;
         let backup_dir = app_state.config.backup_dir.as_ref().unwrap().as_str();
-        if let Some(err) = intern_save_config_api_proxy(backup_dir, api_proxy, app_state.config.t_api_proxy_file_path.as_str()) {
-            return HttpResponse::InternalServerError().json(json!({"error": err.to_string()}));
+        match intern_save_config_api_proxy(backup_dir, api_proxy, app_state.config.t_api_proxy_file_path.as_str()) {
+            Ok(change) => audit_api::audit_config_change(&http_req, &app_state, AuditSection::ApiProxyServer, &change.old.get("server"), &change.new.get("server")),
+            Err(err) => return ApiError::new(ApiErrorCode::SaveFailed, err.to_string()).into(),
         }
     }
     HttpResponse::Ok().finish()
@@ -96,19 +109,25 @@ async fn playlist_update(
     req: web::Json<Vec<String>>,
     app_state: web::Data<AppState>,
 ) -> HttpResponse {
-    let targets = req.0;
-    let user_targets = if targets.is_empty() { None } else { Some(targets) };
-    let process_targets = validate_targets(user_targets.as_ref(), &app_state.config.sources);
-    match process_targets {
-        Ok(valid_targets) => {
-            actix_rt::spawn(playlist_processor::exec_processing(Arc::clone(&app_state.config), Arc::new(valid_targets)));
-            HttpResponse::Ok().finish()
-        }
-        Err(err) => {
-            error!("Failed playlist update {}", mask_sensitive_info(err.to_string().as_str()));
-            HttpResponse::BadRequest().json(json!({"error": err.to_string()}))
-        }
-    }
+    job_api::enqueue_job(&app_state, req.0, None)
+}
+
+#[derive(Debug, Deserialize)]
+struct ClusterRefreshRequest {
+    target: String,
+    /// comma separated `live`, `vod` and `series`
+    cluster: String,
+}
+
+/// Refreshes only the given clusters of the target, the other clusters are taken from the incremental cache.
+async fn cluster_refresh(
+    query: web::Query<ClusterRefreshRequest>,
+    app_state: web::Data<AppState>,
+) -> HttpResponse {
+    let Some(target) = app_state.config.sources.iter().flat_map(|source| &source.targets).find(|target| target.name == query.target) else {
+        return ApiError::target_not_found(&query.target).into();
+    };
+    job_api::enqueue_job(&app_state, vec![target.name.clone()], Some(&query.cluster))
 }
 
 fn create_config_input_for_url(url: &str) -> ConfigInput {
@@ -121,6 +140,8 @@ fn create_config_input_for_url(url: &str) -> ConfigInput {
             xtream_skip_live: false,
             xtream_skip_vod: false,
             xtream_skip_series: false,
+            xtream_validate_account: false,
+            m3u_strictness: M3uStrictness::Lenient,
         }),
         ..Default::default()
     }
@@ -129,19 +150,15 @@ fn create_config_input_for_url(url: &str) -> ConfigInput {
 async fn get_playlist(cfg_input: Option<&ConfigInput>, cfg: &Config) -> HttpResponse {
     match cfg_input {
         Some(input) => {
-            let (result, errors) =
-                match input.input_type {
-                    InputType::M3u => download::get_m3u_playlist(cfg, input, &cfg.working_dir).await,
-                    InputType::Xtream => download::get_xtream_playlist(input, &cfg.working_dir).await,
-                };
+            let (result, errors) = fetch_input(&InputContext { cfg, clusters: None }, input).await;
             if result.is_empty() {
                 let error_strings: Vec<String> = errors.iter().map(std::string::ToString::to_string).collect();
-                HttpResponse::BadRequest().json(json!({"error": error_strings.join(", ")}))
+                ApiError::new(ApiErrorCode::InputFailed, error_strings.join(", ")).into()
             } else {
                 HttpResponse::Ok().json(result)
             }
         }
-        None => HttpResponse::BadRequest().json(json!({"error": "Invalid Arguments"})),
+        None => ApiError::new(ApiErrorCode::InputNotFound, "Unknown input").with_field("input_id").into(),
     }
 }
 
@@ -168,6 +185,7 @@ async fn config(
         username: i.username.clone(),
         password: i.password.clone(),
         persist: i.persist.clone(),
+        persist_retention: i.persist_retention.clone(),
         name: i.name.clone(),
         enabled: i.enabled,
     };
@@ -184,6 +202,9 @@ async fn config(
         mapping: t.mapping.clone(),
         processing_order: t.processing_order.clone(),
         watch: t.watch.clone(),
+        snapshots: t.snapshots,
+        incremental: t.incremental,
+        publish: t.publish.clone(),
     };
 
     let map_source = |s: &ConfigSource| ServerSourceConfig {
@@ -194,18 +215,40 @@ async fn config(
     let map_config = |config: &Config| ServerConfig {
         api: config.api.clone(),
         threads: config.threads,
+        target_threads: config.target_threads,
         working_dir: config.working_dir.clone(),
         backup_dir: config.backup_dir.clone(),
         schedule: config.schedule.clone(),
         messaging: config.messaging.clone(),
         video: config.video.clone(),
+        reverse_proxy: config.reverse_proxy.clone(),
+        health_check: config.health_check.clone(),
+        logo_cache: config.logo_cache.clone(),
+        short_epg: config.short_epg.clone(),
+        tmdb: config.tmdb.clone(),
+        trakt: config.trakt.clone(),
+        schedules_direct: config.schedules_direct.clone(),
+        channel_aliases: config.channel_aliases.clone(),
+        watch_history: config.watch_history.clone(),
+        publishers: config.publishers.clone(),
+        account_check: config.account_check.clone(),
         sources: config.sources.iter().map(map_source).collect(),
         api_proxy: config_reader::read_api_proxy(app_state.config.t_api_proxy_file_path.as_str(), false),
+        rate_limit: config.rate_limit.clone(),
+        access_log: config.access_log.clone(),
+        watchdog: config.watchdog.clone(),
+        readiness: config.readiness.clone(),
+        proxy: config.proxy.clone(),
+        dns: config.dns.clone(),
+        recording: config.recording.clone(),
+        log: config.log.clone(),
+        processing: config.processing.clone(),
     };
 
+    // the config is read with the `${VAR}` variables, the web ui saves it back without the resolved values
     let mut result = match config_reader::read_config(app_state.config.t_config_path.as_str(),
                                                       app_state.config.t_config_file_path.as_str(),
-                                                      app_state.config.t_sources_file_path.as_str()) {
+                                                      app_state.config.t_sources_file_path.as_str(), false) {
         Ok(mut cfg) => {
             let _ = cfg.prepare(true);
             map_config(&cfg)
@@ -221,17 +264,503 @@ async fn config(
     HttpResponse::Ok().json(result)
 }
 
+async fn active_streams(
+    app_state: web::Data<AppState>,
+) -> HttpResponse {
+    HttpResponse::Ok().json(app_state.connections.get_active_streams())
+}
+
+async fn kick_active_stream(
+    path: web::Path<u64>,
+    app_state: web::Data<AppState>,
+) -> HttpResponse {
+    let id = path.into_inner();
+    if app_state.connections.kick(id) {
+        HttpResponse::Ok().finish()
+    } else {
+        ApiError::new(ApiErrorCode::StreamNotFound, format!("Unknown stream {id}")).with_field("id").into()
+    }
+}
+
+async fn shared_streams(
+    app_state: web::Data<AppState>,
+) -> HttpResponse {
+    HttpResponse::Ok().json(app_state.stream_broker.get_metrics())
+}
+
+async fn item_cache_stats(
+    app_state: web::Data<AppState>,
+) -> HttpResponse {
+    HttpResponse::Ok().json(app_state.config.t_item_cache.get_stats())
+}
+
+async fn streams_health() -> HttpResponse {
+    HttpResponse::Ok().json(stream_health::get_stream_health_list())
+}
+
+async fn input_accounts() -> HttpResponse {
+    HttpResponse::Ok().json(account_check::get_account_status_list())
+}
+
+async fn input_sources() -> HttpResponse {
+    HttpResponse::Ok().json(input_source::get_input_source_status_list())
+}
+
+#[derive(Deserialize)]
+struct StreamInputsRequest {
+    url: Option<String>,
+    uuid: Option<String>,
+}
+
+/// Lists the inputs which carry the stream with the given url or uuid.
+async fn stream_inputs(
+    req: web::Query<StreamInputsRequest>,
+    app_state: web::Data<AppState>,
+) -> HttpResponse {
+    let uuid = match (&req.url, &req.uuid) {
+        (Some(url), _) => Some(hash_string(url.trim())),
+        (None, Some(uuid)) => hex_decode_hash(uuid),
+        (None, None) => None,
+    };
+    match uuid {
+        Some(uuid) => HttpResponse::Ok().json(url_index_find(&app_state.config, &uuid)),
+        None => ApiError::new(ApiErrorCode::MissingParameter, "url or uuid is required").with_field("url").into(),
+    }
+}
+
+#[derive(Deserialize)]
+struct SnapshotDiffRequest {
+    from: Option<i64>,
+    to: Option<i64>,
+}
+
+fn has_target(cfg: &Config, target_name: &str) -> bool {
+    cfg.sources.iter().flat_map(|source| &source.targets).any(|target| target.name == target_name)
+}
+
+async fn target_snapshots(
+    path: web::Path<String>,
+    app_state: web::Data<AppState>,
+) -> HttpResponse {
+    let target_name = path.into_inner();
+    if !has_target(&app_state.config, &target_name) {
+        return ApiError::target_not_found(&target_name).into();
+    }
+    HttpResponse::Ok().json(snapshot_repository::snapshot_list(&app_state.config, &target_name))
+}
+
+/// Compares two snapshots of a target, by default the last two.
+async fn target_diff(
+    path: web::Path<String>,
+    req: web::Query<SnapshotDiffRequest>,
+    app_state: web::Data<AppState>,
+) -> HttpResponse {
+    let target_name = path.into_inner();
+    if !has_target(&app_state.config, &target_name) {
+        return ApiError::target_not_found(&target_name).into();
+    }
+    let snapshots = snapshot_repository::snapshot_list(&app_state.config, &target_name);
+    let to = req.to.or_else(|| snapshots.last().copied());
+    let from = req.from.or_else(|| to.and_then(|to_id| snapshots.iter().rev().find(|id| **id < to_id).copied()));
+    match (from, to) {
+        (Some(from_id), Some(to_id)) => {
+            match (snapshot_repository::snapshot_load(&app_state.config, &target_name, from_id),
+                   snapshot_repository::snapshot_load(&app_state.config, &target_name, to_id)) {
+                (Some(from_snapshot), Some(to_snapshot)) => HttpResponse::Ok().json(snapshot_repository::snapshot_diff(&from_snapshot, &to_snapshot)),
+                _ => ApiError::new(ApiErrorCode::SnapshotNotFound, format!("Unknown snapshot {from_id} or {to_id}")).into(),
+            }
+        }
+        _ => ApiError::new(ApiErrorCode::SnapshotNotFound, "Two snapshots are needed for a diff").with_hint("the target option snapshots keeps the playlists of the last runs").into(),
+    }
+}
+
+async fn target_overrides(
+    path: web::Path<String>,
+    app_state: web::Data<AppState>,
+) -> HttpResponse {
+    let target_name = path.into_inner();
+    if !has_target(&app_state.config, &target_name) {
+        return ApiError::target_not_found(&target_name).into();
+    }
+    HttpResponse::Ok().json(override_repository::override_load(&app_state.config, &target_name))
+}
+
+#[derive(Debug, Deserialize)]
+struct AliasSearchRequest {
+    #[serde(default)]
+    q: String,
+}
+
+/// All aliases, or the alias of the name and the aliases containing the name.
+async fn channel_aliases(
+    query: web::Query<AliasSearchRequest>,
+    app_state: web::Data<AppState>,
+) -> HttpResponse {
+    let db = alias_repository::alias_load(&app_state.config);
+    if query.q.trim().is_empty() {
+        HttpResponse::Ok().json(db.entries())
+    } else {
+        HttpResponse::Ok().json(db.search(&query.q))
+    }
+}
+
+fn get_added_alias(cfg: &Config, name: &str) -> Option<alias_repository::ChannelAlias> {
+    alias_repository::alias_load(cfg).entries().iter().find(|entry| entry.name.eq_ignore_ascii_case(name)).cloned()
+}
+
+async fn add_channel_alias(
+    http_req: HttpRequest,
+    req: web::Json<alias_repository::ChannelAlias>,
+    app_state: web::Data<AppState>,
+) -> HttpResponse {
+    let alias = req.into_inner();
+    let name = alias.name.clone();
+    let old = get_added_alias(&app_state.config, &name);
+    match alias_repository::alias_add(&app_state.config, alias) {
+        Ok(()) => {
+            let new = get_added_alias(&app_state.config, &name);
+            audit_api::audit_config_change(&http_req, &app_state, AuditSection::ChannelAlias, &json!({ &name: old }), &json!({ &name: new }));
+            HttpResponse::Ok().finish()
+        }
+        Err(err) => ApiError::new(ApiErrorCode::InvalidContent, err.to_string()).into(),
+    }
+}
+
+async fn target_search(
+    path: web::Path<String>,
+    query: web::Query<search_repository::SearchQuery>,
+    app_state: web::Data<AppState>,
+) -> HttpResponse {
+    let target_name = path.into_inner();
+    if !has_target(&app_state.config, &target_name) {
+        return ApiError::target_not_found(&target_name).into();
+    }
+    match search_repository::search_query(&app_state.config, &target_name, &query) {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(err) => ApiError::new(ApiErrorCode::InvalidParameter, err.to_string()).with_field("q").into(),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ExportRequest {
+    /// `json` or `csv`
+    #[serde(default)]
+    format: Option<String>,
+    /// comma separated list of `name`, `group`, `url`, `epg_id`, `type`
+    #[serde(default)]
+    columns: Option<String>,
+}
+
+async fn target_export(
+    path: web::Path<String>,
+    query: web::Query<ExportRequest>,
+    app_state: web::Data<AppState>,
+) -> HttpResponse {
+    let target_name = path.into_inner();
+    if !has_target(&app_state.config, &target_name) {
+        return ApiError::target_not_found(&target_name).into();
+    }
+    let format = match query.format.as_deref().map_or(Ok(ExportFormat::Json), ExportFormat::from_str) {
+        Ok(format) => format,
+        Err(err) => return ApiError::new(ApiErrorCode::InvalidParameter, err.to_string()).with_field("format").with_hint("json or csv").into(),
+    };
+    let columns = match ExportColumn::parse_list(query.columns.as_deref().unwrap_or_default()) {
+        Ok(columns) => columns,
+        Err(err) => return ApiError::new(ApiErrorCode::InvalidParameter, err.to_string()).with_field("columns").with_hint("name, group, url, epg_id, type").into(),
+    };
+    let mut content = vec![];
+    match export_repository::playlist_export(&app_state.config, &target_name, format, &columns, &mut content) {
+        Ok(true) => HttpResponse::Ok()
+            .content_type(format.content_type())
+            .insert_header((header::CONTENT_DISPOSITION, format!("attachment; filename=\"{target_name}.{}\"", format.extension())))
+            .body(content),
+        Ok(false) => ApiError::new(ApiErrorCode::FileNotFound, format!("Target {target_name} has no processed playlist")).into(),
+        Err(err) => {
+            error!("{err}");
+            ApiError::internal(err.to_string()).into()
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TitleSearchRequest {
+    #[serde(default)]
+    q: String,
+    /// `video` or `series`
+    #[serde(default, rename = "type")]
+    cluster: Option<String>,
+    #[serde(default)]
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+struct TitleSearchEntry {
+    virtual_id: u32,
+    xtream_cluster: XtreamCluster,
+    name: String,
+    group: String,
+    category_id: u32,
+    logo: String,
+}
+
+async fn target_title_search(
+    path: web::Path<String>,
+    query: web::Query<TitleSearchRequest>,
+    app_state: web::Data<AppState>,
+) -> HttpResponse {
+    let Some(target) = app_state.config.sources.iter().flat_map(|source| &source.targets).find(|target| target.name == *path) else {
+        return ApiError::target_not_found(&path).into();
+    };
+    if !target.has_output(&TargetType::Xtream) {
+        return ApiError::new(ApiErrorCode::XtreamOutputRequired, "Target has no xtream output").with_field("target").into();
+    }
+    let clusters = match query.cluster.as_deref().map(str::trim) {
+        None | Some("") => vec![XtreamCluster::Video, XtreamCluster::Series],
+        Some(value) if value.eq_ignore_ascii_case("video") => vec![XtreamCluster::Video],
+        Some(value) if value.eq_ignore_ascii_case("series") => vec![XtreamCluster::Series],
+        Some(_) => return ApiError::new(ApiErrorCode::InvalidParameter, "Invalid type").with_field("type").with_hint("video or series").into(),
+    };
+    let limit = query.limit.unwrap_or(100).clamp(1, 1000);
+    let mut result = vec![];
+    for cluster in clusters {
+        match xtream_repository::xtream_search_items(cluster, &app_state.config, target, &query.q, limit - result.len()) {
+            Ok(items) => result.extend(items.into_iter().map(|pli| TitleSearchEntry {
+                virtual_id: pli.virtual_id,
+                xtream_cluster: pli.xtream_cluster,
+                name: pli.name.to_string(),
+                group: pli.group.to_string(),
+                category_id: pli.category_id,
+                logo: pli.logo.to_string(),
+            })),
+            Err(err) => return ApiError::internal(err.to_string()).into(),
+        }
+        if result.len() >= limit {
+            break;
+        }
+    }
+    HttpResponse::Ok().json(result)
+}
+
+fn update_target_override(http_req: &HttpRequest, app_state: &AppState, target_name: &str, id: &str, channel_override: Option<override_repository::ChannelOverride>) -> HttpResponse {
+    if !has_target(&app_state.config, target_name) {
+        return ApiError::target_not_found(target_name).into();
+    }
+    let Some(uuid) = hex_decode_hash(id) else {
+        return ApiError::new(ApiErrorCode::InvalidParameter, format!("Invalid channel id {id}")).with_field("id").into();
+    };
+    let uuid = hex_encode(&uuid);
+    let old = override_repository::override_load(&app_state.config, target_name).remove(&uuid);
+    let new = channel_override.clone();
+    match override_repository::override_update(&app_state.config, target_name, &uuid, channel_override) {
+        Ok(()) => {
+            audit_api::audit_config_change(http_req, app_state, AuditSection::ChannelOverride,
+                                           &json!({ target_name: { &uuid: old } }), &json!({ target_name: { &uuid: new } }));
+            HttpResponse::Ok().finish()
+        }
+        Err(err) => {
+            error!("{err}");
+            ApiError::new(ApiErrorCode::SaveFailed, err.to_string()).into()
+        }
+    }
+}
+
+/// The override is applied with the next refresh of the target.
+async fn save_target_override(
+    http_req: HttpRequest,
+    path: web::Path<(String, String)>,
+    req: web::Json<override_repository::ChannelOverride>,
+    app_state: web::Data<AppState>,
+) -> HttpResponse {
+    let (target_name, id) = path.into_inner();
+    update_target_override(&http_req, &app_state, &target_name, &id, Some(req.into_inner()))
+}
+
+async fn delete_target_override(
+    http_req: HttpRequest,
+    path: web::Path<(String, String)>,
+    app_state: web::Data<AppState>,
+) -> HttpResponse {
+    let (target_name, id) = path.into_inner();
+    update_target_override(&http_req, &app_state, &target_name, &id, None)
+}
+
+async fn target_quarantine(
+    path: web::Path<String>,
+    app_state: web::Data<AppState>,
+) -> HttpResponse {
+    let target_name = path.into_inner();
+    if !has_target(&app_state.config, &target_name) {
+        return ApiError::target_not_found(&target_name).into();
+    }
+    HttpResponse::Ok().json(quarantine_repository::quarantine_load(&app_state.config, &target_name, chrono::Utc::now().timestamp()))
+}
+
+#[derive(Debug, Deserialize)]
+struct QuarantineRequest {
+    hours: Option<u32>,
+    reason: Option<String>,
+}
+
+fn update_target_quarantine(http_req: &HttpRequest, app_state: &AppState, target_name: &str, id: &str, quarantine: Option<QuarantineRequest>) -> HttpResponse {
+    if !has_target(&app_state.config, target_name) {
+        return ApiError::target_not_found(target_name).into();
+    }
+    let Some(uuid) = hex_decode_hash(id) else {
+        return ApiError::new(ApiErrorCode::InvalidParameter, format!("Invalid channel id {id}")).with_field("id").into();
+    };
+    let now = chrono::Utc::now().timestamp();
+    let quarantine = quarantine.map(|req| quarantine_repository::ChannelQuarantine {
+        until: now + i64::from(req.hours.unwrap_or(DEFAULT_QUARANTINE_HOURS).max(1)) * 3600,
+        reason: req.reason.filter(|reason| !reason.trim().is_empty()),
+    });
+    let uuid = hex_encode(&uuid);
+    let old = quarantine_repository::quarantine_load(&app_state.config, target_name, now).remove(&uuid);
+    let new = quarantine.clone();
+    match quarantine_repository::quarantine_update(&app_state.config, target_name, &uuid, quarantine, now) {
+        Ok(()) => {
+            audit_api::audit_config_change(http_req, app_state, AuditSection::ChannelQuarantine,
+                                           &json!({ target_name: { &uuid: old } }), &json!({ target_name: { &uuid: new } }));
+            HttpResponse::Ok().finish()
+        }
+        Err(err) => {
+            error!("{err}");
+            ApiError::new(ApiErrorCode::SaveFailed, err.to_string()).into()
+        }
+    }
+}
+
+/// The channel is removed from the outputs with the next refresh of the target.
+async fn save_target_quarantine(
+    http_req: HttpRequest,
+    path: web::Path<(String, String)>,
+    req: web::Json<QuarantineRequest>,
+    app_state: web::Data<AppState>,
+) -> HttpResponse {
+    let (target_name, id) = path.into_inner();
+    update_target_quarantine(&http_req, &app_state, &target_name, &id, Some(req.into_inner()))
+}
+
+async fn delete_target_quarantine(
+    http_req: HttpRequest,
+    path: web::Path<(String, String)>,
+    app_state: web::Data<AppState>,
+) -> HttpResponse {
+    let (target_name, id) = path.into_inner();
+    update_target_quarantine(&http_req, &app_state, &target_name, &id, None)
+}
+
+fn get_input_by_name<'a>(cfg: &'a Config, input_name: &str) -> Option<&'a ConfigInput> {
+    cfg.sources.iter().flat_map(|source| &source.inputs).find(|input| input.name.as_deref() == Some(input_name))
+}
+
+async fn input_persisted(
+    path: web::Path<String>,
+    app_state: web::Data<AppState>,
+) -> HttpResponse {
+    let input_name = path.into_inner();
+    match get_input_by_name(&app_state.config, &input_name) {
+        Some(input) => HttpResponse::Ok().json(persist_repository::persist_list(&app_state.config, input)),
+        None => ApiError::new(ApiErrorCode::InputNotFound, format!("Unknown input {input_name}")).with_field("name").into(),
+    }
+}
+
+async fn input_persisted_download(
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    app_state: web::Data<AppState>,
+) -> HttpResponse {
+    let (input_name, file_name) = path.into_inner();
+    let Some(input) = get_input_by_name(&app_state.config, &input_name) else {
+        return ApiError::new(ApiErrorCode::InputNotFound, format!("Unknown input {input_name}")).with_field("name").into();
+    };
+    if let Some(file_path) = persist_repository::persist_file_path(&app_state.config, input, &file_name) {
+        if let Ok(file) = actix_files::NamedFile::open_async(file_path).await {
+            return file.into_response(&req);
+        }
+    }
+    ApiError::new(ApiErrorCode::FileNotFound, format!("Unknown file {file_name}")).with_field("file").into()
+}
+
+async fn bans(
+    app_state: web::Data<AppState>,
+) -> HttpResponse {
+    HttpResponse::Ok().json(app_state.rate_limiter.get_bans())
+}
+
+async fn clear_bans(
+    http_req: HttpRequest,
+    app_state: web::Data<AppState>,
+) -> HttpResponse {
+    let old: BTreeMap<String, i64> = app_state.rate_limiter.get_bans().into_iter().map(|ban| (ban.ip, ban.until)).collect();
+    app_state.rate_limiter.clear_bans();
+    audit_api::audit_config_change(&http_req, &app_state, AuditSection::Ban, &old, &json!({}));
+    HttpResponse::Ok().finish()
+}
+
+async fn clear_ban(
+    http_req: HttpRequest,
+    path: web::Path<String>,
+    app_state: web::Data<AppState>,
+) -> HttpResponse {
+    let ip = path.into_inner();
+    let old = app_state.rate_limiter.get_bans().into_iter().find(|ban| ban.ip == ip);
+    if app_state.rate_limiter.clear_ban(ip.as_str()) {
+        audit_api::audit_config_change(&http_req, &app_state, AuditSection::Ban, &json!({ &ip: old.map(|ban| ban.until) }), &json!({ &ip: null }));
+        HttpResponse::Ok().finish()
+    } else {
+        ApiError::new(ApiErrorCode::BanNotFound, format!("No ban for {ip}")).with_field("ip").into()
+    }
+}
+
 pub fn v1_api_register(web_auth_enabled: bool) -> impl Fn(&mut web::ServiceConfig) {
     move |cfg: &mut web::ServiceConfig| {
-        cfg.service(web::scope("/api/v1")
-            .wrap(Condition::new(web_auth_enabled, HttpAuthentication::with_fn(validator)))
-            .route("/config", web::get().to(config))
-            .route("/config/main", web::post().to(save_config_main))
-            .route("/config/user", web::post().to(save_config_api_proxy_user))
-            .route("/config/apiproxy", web::post().to(save_config_api_proxy_config))
-            .route("/playlist", web::post().to(playlist))
-            .route("/playlist/update", web::post().to(playlist_update))
-            .route("/file/download", web::post().to(download_api::queue_download_file))
-            .route("/file/download/info", web::get().to(download_api::download_file_info)));
+        cfg.service(RoleScope::new("/api/v1")
+            .route("/config", Role::Admin, web::get().to(config))
+            .route("/config/main", Role::Admin, web::post().to(save_config_main))
+            .route("/config/user", Role::Admin, web::post().to(save_config_api_proxy_user))
+            .route("/config/apiproxy", Role::Admin, web::post().to(save_config_api_proxy_config))
+            .route("/audit", Role::Admin, web::get().to(audit_api::audit_log))
+            .route("/playlist", Role::Operator, web::post().to(playlist))
+            .route("/playlist/update", Role::Operator, web::post().to(playlist_update))
+            .route("/refresh", Role::Operator, web::post().to(cluster_refresh))
+            .route("/jobs", Role::Viewer, web::get().to(job_api::job_list))
+            .route("/jobs", Role::Operator, web::post().to(job_api::job_create))
+            .route("/jobs/{id}", Role::Viewer, web::get().to(job_api::job_get))
+            .route("/jobs/{id}", Role::Operator, web::delete().to(job_api::job_cancel))
+            .route("/events", Role::Viewer, web::get().to(job_api::job_events))
+            .route("/file/download", Role::Operator, web::post().to(download_api::queue_download_file))
+            .route("/file/download/info", Role::Viewer, web::get().to(download_api::download_file_info))
+            .route("/recordings", Role::Viewer, web::get().to(recording_api::recording_list))
+            .route("/recordings", Role::Operator, web::post().to(recording_api::recording_schedule))
+            .route("/recordings/{id}", Role::Operator, web::delete().to(recording_api::recording_delete))
+            .route("/recordings/{id}/download", Role::Operator, web::get().to(recording_api::recording_download))
+            .route("/streams/active", Role::Viewer, web::get().to(active_streams))
+            .route("/streams/active/{id}", Role::Operator, web::delete().to(kick_active_stream))
+            .route("/streams/shared", Role::Viewer, web::get().to(shared_streams))
+            .route("/streams/health", Role::Viewer, web::get().to(streams_health))
+            .route("/streams/inputs", Role::Viewer, web::get().to(stream_inputs))
+            .route("/cache/items", Role::Viewer, web::get().to(item_cache_stats))
+            .route("/targets/{name}/snapshots", Role::Viewer, web::get().to(target_snapshots))
+            .route("/targets/{name}/diff", Role::Viewer, web::get().to(target_diff))
+            .route("/targets/{name}/search", Role::Viewer, web::get().to(target_search))
+            .route("/targets/{name}/export", Role::Operator, web::get().to(target_export))
+            .route("/targets/{name}/titles", Role::Viewer, web::get().to(target_title_search))
+            .route("/targets/{name}/overrides", Role::Viewer, web::get().to(target_overrides))
+            .route("/targets/{name}/overrides/{id}", Role::Operator, web::put().to(save_target_override))
+            .route("/targets/{name}/overrides/{id}", Role::Operator, web::delete().to(delete_target_override))
+            .route("/targets/{name}/quarantine", Role::Viewer, web::get().to(target_quarantine))
+            .route("/targets/{name}/quarantine/{id}", Role::Operator, web::put().to(save_target_quarantine))
+            .route("/targets/{name}/quarantine/{id}", Role::Operator, web::delete().to(delete_target_quarantine))
+            .route("/aliases", Role::Viewer, web::get().to(channel_aliases))
+            .route("/aliases", Role::Operator, web::post().to(add_channel_alias))
+            .route("/inputs/accounts", Role::Viewer, web::get().to(input_accounts))
+            .route("/inputs/sources", Role::Viewer, web::get().to(input_sources))
+            .route("/inputs/{name}/persisted", Role::Operator, web::get().to(input_persisted))
+            .route("/inputs/{name}/persisted/{file}", Role::Admin, web::get().to(input_persisted_download))
+            .route("/bans", Role::Viewer, web::get().to(bans))
+            .route("/bans", Role::Operator, web::delete().to(clear_bans))
+            .route("/bans/{ip}", Role::Operator, web::delete().to(clear_ban))
+            .into_scope()
+            .wrap(Condition::new(web_auth_enabled, HttpAuthentication::with_fn(validator))));
     }
 }
@@ -0,0 +1,55 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use log::error;
+
+use crate::api::api_model::{AppState, UserApiRequest};
+use crate::api::api_utils::{check_rate_limit, get_user_target, register_failed_login};
+use crate::repository::watch_history_repository::{watch_history_clear, watch_history_load};
+
+async fn recently_watched(
+    req: HttpRequest,
+    api_req: web::Query<UserApiRequest>,
+    app_state: web::Data<AppState>,
+) -> HttpResponse {
+    if let Some(response) = check_rate_limit(&req, &app_state, api_req.username.trim()) {
+        return response;
+    }
+    let Some((user, target)) = get_user_target(&api_req, &app_state, &req) else {
+        register_failed_login(&req, &app_state);
+        return HttpResponse::BadRequest().finish();
+    };
+    if app_state.config.watch_history.is_none() {
+        return HttpResponse::NotFound().finish();
+    }
+    let mut entries = watch_history_load(&app_state.config, &target.name, &user.username);
+    if let Ok(limit) = api_req.limit.trim().parse::<usize>() {
+        entries.truncate(limit);
+    }
+    HttpResponse::Ok().json(entries)
+}
+
+async fn recently_watched_clear(
+    req: HttpRequest,
+    api_req: web::Query<UserApiRequest>,
+    app_state: web::Data<AppState>,
+) -> HttpResponse {
+    if let Some(response) = check_rate_limit(&req, &app_state, api_req.username.trim()) {
+        return response;
+    }
+    let Some((user, target)) = get_user_target(&api_req, &app_state, &req) else {
+        register_failed_login(&req, &app_state);
+        return HttpResponse::BadRequest().finish();
+    };
+    match watch_history_clear(&app_state.config, &target.name, &user.username) {
+        Ok(()) => HttpResponse::Ok().finish(),
+        Err(err) => {
+            error!("Failed to clear watch history of user {}: {err}", user.username);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+pub fn watch_history_api_register(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/recently_watched")
+        .route(web::get().to(recently_watched))
+        .route(web::delete().to(recently_watched_clear)));
+}
@@ -3,6 +3,8 @@ use std::path::{Path, PathBuf};
 
 use actix_files::NamedFile;
 use actix_web::{HttpRequest, HttpResponse, web};
+use actix_web::http::header::{HeaderValue, CACHE_CONTROL, LOCATION};
+use actix_web::middleware::{Compress, DefaultHeaders};
 use actix_web_httpauth::extractors::bearer::BearerAuth;
 
 use crate::api::api_model::AppState;
@@ -10,6 +12,10 @@ use crate::auth::authenticator::{create_jwt, verify_token};
 use crate::auth::password::verify_password;
 use crate::auth::user::UserCredential;
 
+/// The index is revalidated, a new release references new assets.
+const INDEX_CACHE_CONTROL: &str = "no-cache";
+const STATIC_CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+
 fn no_web_auth_token() -> HttpResponse {
     HttpResponse::Ok().json(HashMap::from([("token", "authorized")]))
 }
@@ -30,8 +36,9 @@ async fn token(
             if !(username.is_empty() || password.is_empty()) {
                 if let Some(hash) = web_auth.get_user_password(username) {
                     if verify_password(hash, password.as_bytes()) {
+                        let jwt = create_jwt(web_auth, username, web_auth.get_user_role(username));
                         req.zeroize();
-                        if let Ok(token) = create_jwt(web_auth) {
+                        if let Ok(token) = jwt {
                             return HttpResponse::Ok().json(HashMap::from([("token", token)]));
                         }
                     };
@@ -57,8 +64,9 @@ async fn token_refresh(
                 return no_web_auth_token();
             }
             let secret_key = web_auth.secret.as_ref();
-            if verify_token(credentials, secret_key) {
-                if let Ok(token) = create_jwt(app_state.config.web_auth.as_ref().unwrap()) {
+            // the role is evaluated again, a changed role is applied with the next refresh
+            if let Some(claims) = verify_token(credentials, secret_key) {
+                if let Ok(token) = create_jwt(web_auth, &claims.sub, web_auth.get_user_role(&claims.sub)) {
                     return HttpResponse::Ok().json(HashMap::from([("token", token)]));
                 }
             }
@@ -67,21 +75,51 @@ async fn token_refresh(
     }
 }
 
+/// The assets of the web ui are referenced relative to the index, a path prefix needs a trailing slash.
 async fn index(
-    _req: HttpRequest,
+    req: HttpRequest,
     app_state: web::Data<AppState>,
-) -> std::io::Result<NamedFile> {
+) -> HttpResponse {
+    if !req.path().ends_with('/') {
+        return HttpResponse::Found().insert_header((LOCATION, format!("{}/", req.path()))).finish();
+    }
     let path: PathBuf = [&app_state.config.api.web_root, "index.html"].iter().collect();
-    NamedFile::open(path)
+    match NamedFile::open_async(path).await {
+        Ok(file) => {
+            let mut response = file.into_response(&req);
+            response.headers_mut().insert(CACHE_CONTROL, HeaderValue::from_static(INDEX_CACHE_CONTROL));
+            response
+        }
+        Err(_) => HttpResponse::NotFound().finish(),
+    }
 }
 
-pub fn index_register(web_dir_path: &Path) -> impl Fn(&mut web::ServiceConfig) + '_ {
+fn get_scope_path(web_path: &str) -> &str {
+    if web_path.is_empty() { "/" } else { web_path }
+}
+
+/// The static assets have hashed filenames and are cached by the browser.
+/// They are registered before the xtream routes, which would match their paths otherwise.
+pub fn static_register<'a>(web_dir_path: &'a Path, web_path: &'a str) -> impl Fn(&mut web::ServiceConfig) + 'a {
+    move |cfg: &mut web::ServiceConfig| {
+        cfg.service(web::scope(&format!("{web_path}/static"))
+            .wrap(Compress::default())
+            .wrap(DefaultHeaders::new().add((CACHE_CONTROL, STATIC_CACHE_CONTROL)))
+            .service(actix_files::Files::new("/", web_dir_path.join("static"))));
+    }
+}
+
+pub fn index_register<'a>(web_dir_path: &'a Path, web_path: &'a str) -> impl Fn(&mut web::ServiceConfig) + 'a {
     move |cfg: &mut web::ServiceConfig| {
         cfg.service(web::scope("/auth")
             .route("/token", web::post().to(token))
             .route("/refresh", web::post().to(token_refresh)));
-        cfg.service(web::scope("/")
-            .route("", web::get().to(index))
-            .service(actix_files::Files::new("/", web_dir_path)));
+        let mut scope = web::scope(get_scope_path(web_path))
+            .wrap(Compress::default())
+            .route("", web::get().to(index));
+        if !web_path.is_empty() {
+            scope = scope.route("/", web::get().to(index));
+        }
+        cfg.service(scope.service(actix_files::Files::new("/", web_dir_path)));
     }
 }
\ No newline at end of file
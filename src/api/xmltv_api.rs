@@ -1,41 +1,31 @@
 use std::fs::File;
+use std::io::{BufReader, ErrorKind, Write};
 use std::path::{Path, PathBuf};
 
 use actix_web::{HttpRequest, HttpResponse, web, http::header};
-use log::{info};
+use bytes::Bytes;
+use log::{error, info};
 use quick_xml::{Reader, Writer};
 use flate2::write::GzEncoder;
 use flate2::Compression;
 use quick_xml::events::{BytesStart, Event};
-use std::io::{BufReader};
-use chrono::{Duration, NaiveDateTime, TimeDelta};
+use chrono::{Duration, TimeDelta};
+use tokio::sync::mpsc;
 
 use crate::api::api_model::{AppState, UserApiRequest};
 use crate::api::api_utils::{get_user_target, serve_file};
 use crate::model::api_proxy::{ProxyUserCredentials};
 use crate::model::config::{Config, ConfigTarget};
 use crate::model::config::TargetType;
+use crate::processing::short_epg::get_short_epg_file_path;
+use crate::processing::xmltv_parser::{parse_timeshift, time_correct};
 use crate::repository::m3u_repository::m3u_get_epg_file_path;
 use crate::repository::storage::get_target_storage_path;
 use crate::repository::xtream_repository::{xtream_get_epg_file_path, xtream_get_storage_path};
 use crate::utils::{file_utils};
 
-fn time_correct(date_time: &str, correction: &TimeDelta) -> String {
-    // Split the dateTime string into date and time parts
-    let date_time_split: Vec<&str> = date_time.split(' ').collect();
-    if date_time_split.len() != 2 {
-        return date_time.to_string();
-    }
-
-    // Parse the datetime string
-    NaiveDateTime::parse_from_str(date_time_split[0], "%Y%m%d%H%M%S").map_or_else(|_| date_time.to_string(), |native_dt| {
-            let corrected_dt = native_dt + *correction;
-            // Format the corrected datetime back to string
-            let formatted_dt = corrected_dt.format("%Y%m%d%H%M%S").to_string();
-            let result = format!("{} {}", formatted_dt, date_time_split[1]);
-            result
-        })
-}
+const EPG_CHUNK_SIZE: usize = 64 * 1024;
+const EPG_CHANNEL_CAPACITY: usize = 8;
 
 fn get_epg_path_for_target_of_type(target_name: &str, epg_path: PathBuf) -> Option<PathBuf> {
     if file_utils::path_exists(&epg_path) {
@@ -59,47 +49,15 @@ fn get_epg_path_for_target(config: &Config, target: &ConfigTarget) -> Option<Pat
                     return get_epg_path_for_target_of_type(&target.name, xtream_get_epg_file_path(&storage_path));
                 }
             }
-            TargetType::Strm => {}
+            TargetType::Strm | TargetType::Report | TargetType::Library => {}
         }
     }
     None
 }
 
-fn parse_timeshift(time_shift: Option<&String>) -> Option<i32> {
-    time_shift.and_then(|offset| {
-            let sign_factor = if offset.starts_with('-') { -1 } else { 1 };
-            let offset = offset.trim_start_matches(&['-', '+'][..]); // Remove the sign for parsing
-
-            let total_minutes = if offset.contains(':') {
-                // Handle the case with hours and minutes (e.g., "-2:30", "1:45", "+0:15", ":30")
-                let parts: Vec<&str> = offset.split(':').collect();
-
-                let hours: i32 = if parts[0].is_empty() {
-                    0 // Treat empty hour part as 0 hours
-                } else {
-                    parts[0].parse().unwrap_or(0)
-                };
-
-                let minutes: i32 = if parts.len() > 1 {
-                    parts[1].parse().unwrap_or(0)
-                } else {
-                    0
-                };
-
-                // Convert hours to minutes and add the minute part
-                hours * 60 + minutes
-            } else {
-                // Handle single number case (e.g., "2" or "+2")
-                let num: i32 = offset.parse().unwrap_or(0);
-                num * 60
-            };
-
-            if total_minutes > 0 {
-                Some(sign_factor * total_minutes)
-            } else {
-                None
-            }
-        })
+/// The stored epg of the target, the generated short epg if there is none.
+pub(crate) fn get_target_epg_path(config: &Config, target: &ConfigTarget) -> Option<PathBuf> {
+    get_epg_path_for_target(config, target).or_else(|| get_short_epg_file_path(config, target))
 }
 
 async fn serve_epg(epg_path: &Path, req: &HttpRequest, user: &ProxyUserCredentials) -> HttpResponse {
@@ -118,87 +76,168 @@ async fn serve_epg(epg_path: &Path, req: &HttpRequest, user: &ProxyUserCredentia
     }
 }
 
-fn serve_epg_with_timeshift(epg_file: File, offset_minutes: i32) -> HttpResponse {
-    let reader = BufReader::new(epg_file);
-    let encoder = GzEncoder::new(Vec::new(), Compression::default());
-    let mut xml_reader = Reader::from_reader(reader);
-    let mut xml_writer = Writer::new(encoder);
-    let mut buf = Vec::new();
-    let duration = Duration::minutes(i64::from(offset_minutes));
+/// Sends the written data in chunks through a bounded channel.
+/// The writer blocks when the client is slower than the rewriting, this keeps the memory bounded.
+struct ChannelWriter {
+    sender: mpsc::Sender<Bytes>,
+    buffer: Vec<u8>,
+}
 
-    loop {
-        match xml_reader.read_event_into(&mut buf) {
-            Ok(Event::Start(ref e)) if e.name().as_ref() == b"programme" => {
-                // Modify the attributes
-                let mut elem = BytesStart::from(e.name());
-                for attr in e.attributes() {
-                    match attr {
-                        Ok(attr) if attr.key.as_ref() == b"start" => {
-                            let start_value = attr.decode_and_unescape_value(xml_reader.decoder())
-                                .expect("Failed to decode start attribute");
-                            // Modify the start attribute value as needed
-                            elem.push_attribute(("start", time_correct(&start_value, &duration).as_str()));
-                        }
-                        Ok(attr) if attr.key.as_ref() == b"stop" => {
-                            let stop_value = attr.decode_and_unescape_value(xml_reader.decoder())
-                                .expect("Failed to decode stop attribute");
-                            // Modify the stop attribute value as needed
-                            elem.push_attribute(("stop", time_correct(&stop_value, &duration).as_str()));
-                        }
-                        Ok(attr) => {
-                            // Copy any other attributes as they are
-                            elem.push_attribute(attr);
-                        }
-                        Err(e) => {
-                            println!("Error parsing attribute: {e}");
-                        }
-                    }
-                }
+impl ChannelWriter {
+    fn new(sender: mpsc::Sender<Bytes>) -> Self {
+        Self { sender, buffer: Vec::with_capacity(EPG_CHUNK_SIZE) }
+    }
 
-                // Write the modified start event
-                xml_writer.write_event(Event::Start(elem)).expect("Failed to write event");
-            }
-            Ok(Event::Eof) => break, // End of file
-            Ok(event) => {
-                // Write any other event as is
-                xml_writer.write_event(event).expect("Failed to write event");
-            }
-            Err(e) => {
-                println!("Error: {e}");
-                break;
+    fn send_buffer(&mut self) -> std::io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let chunk = Bytes::from(std::mem::replace(&mut self.buffer, Vec::with_capacity(EPG_CHUNK_SIZE)));
+        self.sender.blocking_send(chunk).map_err(|_| std::io::Error::new(ErrorKind::BrokenPipe, "client disconnected"))
+    }
+}
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        if self.buffer.len() >= EPG_CHUNK_SIZE {
+            self.send_buffer()?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.send_buffer()
+    }
+}
+
+fn rewrite_programme_times(xml_reader: &Reader<BufReader<File>>, elem: &BytesStart, duration: &TimeDelta) -> BytesStart<'static> {
+    let mut result = BytesStart::new(String::from_utf8_lossy(elem.name().as_ref()).into_owned());
+    for attr in elem.attributes() {
+        match attr {
+            Ok(attr) if attr.key.as_ref() == b"start" || attr.key.as_ref() == b"stop" => {
+                match attr.decode_and_unescape_value(xml_reader.decoder()) {
+                    Ok(value) => result.push_attribute((attr.key.as_ref(), time_correct(&value, duration).as_bytes())),
+                    Err(_) => result.push_attribute(attr),
+                }
             }
+            Ok(attr) => result.push_attribute(attr),
+            Err(err) => error!("Error parsing epg attribute: {err}"),
         }
+    }
+    result
+}
 
+fn rewrite_epg_with_timeshift<W: Write>(epg_file: File, xml_writer: &mut Writer<W>, duration: &TimeDelta) -> Result<(), quick_xml::Error> {
+    let mut xml_reader = Reader::from_reader(BufReader::new(epg_file));
+    let mut buf = Vec::new();
+    loop {
+        match xml_reader.read_event_into(&mut buf)? {
+            Event::Start(ref e) if e.name().as_ref() == b"programme" => {
+                let elem = rewrite_programme_times(&xml_reader, e, duration);
+                xml_writer.write_event(Event::Start(elem))?;
+            }
+            Event::Eof => break,
+            event => xml_writer.write_event(event)?,
+        }
         buf.clear();
     }
+    Ok(())
+}
+
+fn serve_epg_with_timeshift(epg_file: File, offset_minutes: i32) -> HttpResponse {
+    let duration = Duration::minutes(i64::from(offset_minutes));
+    let (sender, receiver) = mpsc::channel::<Bytes>(EPG_CHANNEL_CAPACITY);
+    actix_rt::task::spawn_blocking(move || {
+        let mut xml_writer = Writer::new(GzEncoder::new(ChannelWriter::new(sender), Compression::default()));
+        let result = rewrite_epg_with_timeshift(epg_file, &mut xml_writer, &duration)
+            .map_err(|err| err.to_string())
+            .and_then(|()| xml_writer.into_inner().finish().and_then(|mut writer| writer.flush()).map_err(|err| err.to_string()));
+        if let Err(err) = result {
+            error!("Failed to rewrite epg with timeshift: {err}");
+        }
+    });
 
-    let compressed_data = xml_writer.into_inner().finish().unwrap();
+    let body = futures::stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|chunk| (Ok::<Bytes, std::io::Error>(chunk), receiver))
+    });
     HttpResponse::Ok()
         .content_type("application/octet-stream")
         .insert_header((header::CONTENT_ENCODING, "gzip")) // Set Content-Encoding header
-        .body(compressed_data)
+        .streaming(body)
 }
 
-async fn xmltv_api(
-    api_req: web::Query<UserApiRequest>,
-    req: HttpRequest,
-    app_state: web::Data<AppState>,
-) -> HttpResponse {
-    if let Some((user, target)) = get_user_target(&api_req, &app_state) {
-        match get_epg_path_for_target(&app_state.config, target) {
+async fn xmltv_api(api_req: &UserApiRequest, req: &HttpRequest, app_state: &AppState) -> HttpResponse {
+    if let Some((user, target)) = get_user_target(api_req, app_state, req) {
+        match get_target_epg_path(&app_state.config, target) {
             None => {
                 // No epg configured,  No processing or timeshift, epg can't be mapped to the channels.
                 // we do not deliver epg
             }
-            Some(epg_path) => return serve_epg(&epg_path, &req, &user).await
+            Some(epg_path) => return serve_epg(&epg_path, req, &user).await
         }
     }
     HttpResponse::Ok().content_type(mime::TEXT_XML).body(
         r#"<?xml version="1.0" encoding="utf-8" ?><!DOCTYPE tv SYSTEM "xmltv.dtd"><tv generator-info-name="Xtream Codes" generator-info-url=""></tv>"#)
 }
 
+async fn xmltv_api_get(
+    api_req: web::Query<UserApiRequest>,
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+) -> HttpResponse {
+    xmltv_api(&api_req, &req, &app_state).await
+}
+
+async fn xmltv_api_post(
+    api_req: web::Form<UserApiRequest>,
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+) -> HttpResponse {
+    xmltv_api(&api_req, &req, &app_state).await
+}
+
 pub fn xmltv_api_register(cfg: &mut web::ServiceConfig) {
-    cfg.service(web::resource("/xmltv.php").route(web::get().to(xmltv_api)))
-        .service(web::resource("/update/epg.php").route(web::get().to(xmltv_api)))
-        .service(web::resource("/epg").route(web::get().to(xmltv_api)));
+    cfg.service(web::resource("/xmltv.php").route(web::get().to(xmltv_api_get)).route(web::post().to(xmltv_api_post)))
+        .service(web::resource("/update/epg.php").route(web::get().to(xmltv_api_get)).route(web::post().to(xmltv_api_post)))
+        .service(web::resource("/epg").route(web::get().to(xmltv_api_get)).route(web::post().to(xmltv_api_post)));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::Read;
+
+    use actix_web::body::to_bytes;
+    use actix_web::http::header;
+    use flate2::read::GzDecoder;
+
+    use crate::api::xmltv_api::serve_epg_with_timeshift;
+    use crate::utils::test_utils::create_temp_dir;
+
+    const EPG: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<tv><channel id="ch1"><display-name>Channel 1</display-name></channel><programme start="20250101003000 +0000" stop="20250101013000 +0000" channel="ch1"><title>News</title></programme></tv>"#;
+
+    async fn rewrite(offset_minutes: i32) -> String {
+        let temp_dir = create_temp_dir("xmltv");
+        let epg_path = temp_dir.path().join("epg.xml");
+        std::fs::write(&epg_path, EPG).unwrap();
+        let response = serve_epg_with_timeshift(File::open(&epg_path).unwrap(), offset_minutes);
+        assert_eq!(response.headers().get(header::CONTENT_ENCODING).unwrap(), "gzip");
+        let body = to_bytes(response.into_body()).await.unwrap();
+        let mut content = String::new();
+        GzDecoder::new(body.as_ref()).read_to_string(&mut content).unwrap();
+        content
+    }
+
+    #[actix_rt::test]
+    async fn test_epg_timeshift() {
+        let shifted = rewrite(120).await;
+        assert!(shifted.contains(r#"<programme start="20250101023000 +0000" stop="20250101033000 +0000" channel="ch1">"#));
+        assert!(shifted.contains(r#"<channel id="ch1"><display-name>Channel 1</display-name></channel>"#));
+        // a negative offset moves the programme to the previous day
+        let shifted = rewrite(-90).await;
+        assert!(shifted.contains(r#"<programme start="20241231230000 +0000" stop="20250101000000 +0000" channel="ch1">"#));
+        assert!(shifted.contains("<title>News</title>"));
+    }
 }
@@ -5,6 +5,7 @@ use std::fmt::{Display, Formatter};
 use std::io::{Error, ErrorKind};
 use std::path::Path;
 use std::str::FromStr;
+use std::time::Instant;
 
 use actix_web::{web, HttpRequest, HttpResponse};
 use bytes::Bytes;
@@ -13,16 +14,26 @@ use futures::Stream;
 use log::{debug, error, log_enabled, warn, Level};
 use serde_json::{Map, Value};
 
-use crate::api::api_model::{AppState, UserApiRequest, XtreamAuthorizationResponse};
-use crate::api::api_utils::{get_user_server_info, get_user_target, get_user_target_by_credentials, serve_file, stream_response};
-use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorKind};
+use crate::api::api_model::{AppState, StreamPath, TimeshiftStreamPath, UserApiRequest, XtreamAuthorizationResponse};
+use crate::api::access_log::get_endpoint;
+use crate::api::api_utils::{check_rate_limit, get_client_ip, get_user_agent, get_user_server_info, get_user_target, get_user_target_by_credentials, register_failed_login, serve_file, stream_response};
+use crate::api::connection_tracker::StreamDetails;
+use crate::api::xmltv_api::get_target_epg_path;
+use crate::api::xtream_epg::{get_short_epg_listings, get_simple_data_table_listings};
+use crate::api::stream_broker::StreamKey;
+use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorCategory, M3uFilterErrorKind};
 use crate::model::api_proxy::{ProxyType, ProxyUserCredentials};
 use crate::model::config::TargetType;
-use crate::model::config::{Config, ConfigInput, ConfigTarget};
+use crate::model::config::{Blackout, Config, ConfigInput, ConfigTarget};
 use crate::model::playlist::{PlaylistItemType, XtreamCluster, XtreamPlaylistItem};
 use crate::model::xtream::XtreamMappingOptions;
-use crate::repository::storage::{get_target_storage_path, hash_string};
+use crate::processing::{input_source, token_refresh};
+use crate::processing::token_refresh::TokenRewrite;
+use crate::processing::xmltv_parser::parse_timeshift;
+use crate::repository::favorites_repository::{favorites_load, FAVORITES_CATEGORY_ID};
+use crate::repository::storage::{get_target_id_mapping_file, get_target_storage_path, hash_string};
 use crate::repository::target_id_mapping::TargetIdMapping;
+use crate::repository::watch_history_repository::watch_history_record;
 use crate::repository::xtream_repository;
 use crate::utils::request_utils::mask_sensitive_info;
 use crate::utils::{json_utils, request_utils};
@@ -39,9 +50,12 @@ const ACTION_GET_SERIES_CATEGORIES: &str = "get_series_categories";
 const ACTION_GET_LIVE_STREAMS: &str = "get_live_streams";
 const ACTION_GET_VOD_STREAMS: &str = "get_vod_streams";
 const ACTION_GET_SERIES: &str = "get_series";
+const ACTION_SEARCH_VOD: &str = "search_vod";
+const ACTION_SEARCH_SERIES: &str = "search_series";
 
 const TAG_ID: &str = "id";
 const TAG_CATEGORY_ID: &str = "category_id";
+const TAG_CATEGORY_NAME: &str = "category_name";
 const TAG_DIRECT_SOURCE: &str = "direct_source";
 const TAG_STREAM_ID: &str = "stream_id";
 const TAG_MOVIE_DATA: &str = "movie_data";
@@ -140,7 +154,7 @@ pub fn serve_query(file_path: &Path, filter: &HashMap<&str, &str>) -> HttpRespon
 }
 
 fn get_xtream_player_api_action_url(input: &ConfigInput, action: &str) -> Option<String> {
-    if let Some(user_info) = input.get_user_info() {
+    if let Some(user_info) = input_source::get_active_user_info(input) {
         Some(format!("{}/player_api.php?username={}&password={}&action={}",
                      &user_info.base_url,
                      &user_info.username,
@@ -163,7 +177,7 @@ fn get_xtream_player_api_info_url(input: &ConfigInput, cluster: XtreamCluster, s
 
 fn get_xtream_player_api_stream_url(input: &ConfigInput, context: &str, action_path: &str, fallback_url: &str) -> Option<String> {
     let ctx_path = if context.is_empty() { String::new() } else { format!("{context}/") };
-    if let Some(user_info) = input.get_user_info() {
+    if let Some(user_info) = input_source::get_active_user_info(input) {
         Some(format!("{}/{}{}/{}/{}",
                      &user_info.base_url,
                      ctx_path,
@@ -197,7 +211,14 @@ async fn xtream_player_api_stream(
     app_state: &web::Data<AppState>,
     stream_req: XtreamApiStreamRequest<'_>,
 ) -> HttpResponse {
-    let (user, target) = try_option_bad_request!(get_user_target_by_credentials(stream_req.username, stream_req.password, api_req, app_state), false, format!("Could not find any user {}", stream_req.username));
+    if let Some(response) = check_rate_limit(req, app_state, stream_req.username) {
+        return response;
+    }
+    let user_target = get_user_target_by_credentials(stream_req.username, stream_req.password, api_req, app_state, req);
+    if user_target.is_none() {
+        register_failed_login(req, app_state);
+    }
+    let (user, target) = try_option_bad_request!(user_target, false, format!("Could not find any user {}", stream_req.username));
     let target_name = &target.name;
     if !target.has_output(&TargetType::Xtream) {
         debug!("Target has no xtream output {}", target_name);
@@ -206,10 +227,17 @@ async fn xtream_player_api_stream(
     let (action_stream_id, stream_ext) = xtream_api_request_separate_number_and_rest(stream_req.stream_id);
     let virtual_id: u32 = try_result_bad_request!(action_stream_id.trim().parse());
     let pli = try_result_bad_request!(xtream_repository::xtream_get_item_for_stream_id(virtual_id, &app_state.config, target, None), true, format!("Failed to read xtream item for stream id {}", virtual_id));
+    if target.is_adult_item_hidden(&user, &pli.parent_code) {
+        debug!("Adult content {virtual_id} of target {target_name} is locked for user {}", user.username);
+        return HttpResponse::Forbidden().finish();
+    }
     let input = try_option_bad_request!(app_state.config.get_input_by_id(pli.input_id), true, format!("Cant find input for target {target_name}, context {}, stream_id {virtual_id}", stream_req.context));
+    if let Err(err) = watch_history_record(&app_state.config, target_name, &user.username, virtual_id, &pli) {
+        error!("{err}");
+    }
 
-    if pli.item_type == PlaylistItemType::LiveHls {
-        let stream_url = pli.url.to_string();
+    if matches!(pli.item_type, PlaylistItemType::LiveHls | PlaylistItemType::LiveDirect) {
+        let stream_url = token_refresh::rewrite_token_url(Some(input), &pli.url).to_string();
         if log_enabled!(Level::Debug) {
             debug!("Redirecting stream request to {}", mask_sensitive_info(&stream_url));
         }
@@ -226,64 +254,74 @@ async fn xtream_player_api_stream(
         if log_enabled!(Level::Debug) {
             debug!("Redirecting stream request to {}", mask_sensitive_info(&pli.url));
         }
-        return HttpResponse::Found().insert_header(("Location", mask_sensitive_info(pli.url.as_str()))).finish();
+        let stream_url = token_refresh::rewrite_token_url(Some(input), &pli.url).to_string();
+        return HttpResponse::Found().insert_header(("Location", stream_url)).finish();
     }
 
     let stream_url = try_option_bad_request!(get_xtream_player_api_stream_url(input, stream_req.context.to_string().as_str(), &query_path, pli.url.as_str()), true, format!("Cant find stream url for target {target_name}, context {}, stream_id {virtual_id}", stream_req.context));
     if log_enabled!(Level::Debug) {
         debug!("Streaming stream request from {}", mask_sensitive_info(&stream_url));
     }
-    stream_response(&stream_url, req, Some(input)).await
+    let details = StreamDetails {
+        username: user.username.clone(),
+        channel: pli.title.to_string(),
+        input: input.name.clone().unwrap_or_default(),
+        client_ip: get_client_ip(req),
+        user_agent: get_user_agent(req),
+        endpoint: get_endpoint(req),
+    };
+    let stream_key = (pli.item_type == PlaylistItemType::Live).then(|| StreamKey { target: target_name.clone(), virtual_id });
+    stream_response(app_state, &stream_url, req, target, Some(input), details, stream_key).await
 }
 
 
 async fn xtream_player_api_live_stream(
     req: HttpRequest,
     api_req: web::Query<UserApiRequest>,
-    path: web::Path<(String, String, String)>,
+    path: web::Path<StreamPath>,
     app_state: web::Data<AppState>,
 ) -> HttpResponse {
-    let (username, password, stream_id) = path.into_inner();
+    let StreamPath { username, password, stream_id } = path.into_inner();
     xtream_player_api_stream(&req, &api_req, &app_state, XtreamApiStreamRequest::from(XtreamApiStreamContext::Live, &username, &password, &stream_id, "")).await
 }
 
 async fn xtream_player_api_live_stream_alt(
     req: HttpRequest,
     api_req: web::Query<UserApiRequest>,
-    path: web::Path<(String, String, String)>,
+    path: web::Path<StreamPath>,
     app_state: web::Data<AppState>,
 ) -> HttpResponse {
-    let (username, password, stream_id) = path.into_inner();
+    let StreamPath { username, password, stream_id } = path.into_inner();
     xtream_player_api_stream(&req, &api_req, &app_state, XtreamApiStreamRequest::from(XtreamApiStreamContext::LiveAlt, &username, &password, &stream_id, "")).await
 }
 
 async fn xtream_player_api_series_stream(
     req: HttpRequest,
     api_req: web::Query<UserApiRequest>,
-    path: web::Path<(String, String, String)>,
+    path: web::Path<StreamPath>,
     app_state: web::Data<AppState>,
 ) -> HttpResponse {
-    let (username, password, stream_id) = path.into_inner();
+    let StreamPath { username, password, stream_id } = path.into_inner();
     xtream_player_api_stream(&req, &api_req, &app_state, XtreamApiStreamRequest::from(XtreamApiStreamContext::Series, &username, &password, &stream_id, "")).await
 }
 
 async fn xtream_player_api_movie_stream(
     req: HttpRequest,
     api_req: web::Query<UserApiRequest>,
-    path: web::Path<(String, String, String)>,
+    path: web::Path<StreamPath>,
     app_state: web::Data<AppState>,
 ) -> HttpResponse {
-    let (username, password, stream_id) = path.into_inner();
+    let StreamPath { username, password, stream_id } = path.into_inner();
     xtream_player_api_stream(&req, &api_req, &app_state, XtreamApiStreamRequest::from(XtreamApiStreamContext::Movie, &username, &password, &stream_id, "")).await
 }
 
 async fn xtream_player_api_timeshift_stream(
     req: HttpRequest,
     api_req: web::Query<UserApiRequest>,
-    path: web::Path<(String, String, String, String, String)>,
+    path: web::Path<TimeshiftStreamPath>,
     app_state: web::Data<AppState>,
 ) -> HttpResponse {
-    let (username, password, duration, start, stream_id) = path.into_inner();
+    let TimeshiftStreamPath { username, password, duration, start, stream_id } = path.into_inner();
     let action_path = format!("{duration}/{start}");
     xtream_player_api_stream(&req, &api_req, &app_state, XtreamApiStreamRequest::from(XtreamApiStreamContext::Timeshift, &username, &password, &stream_id, &action_path)).await
 }
@@ -303,7 +341,7 @@ async fn xtream_player_api_streaming_timeshift(
     xtream_player_api_stream(&req, &api_req, &app_state, XtreamApiStreamRequest::from(XtreamApiStreamContext::Timeshift, username, password, stream_id, &action_path)).await
 }
 
-fn get_xtream_vod_info(target: &ConfigTarget, pli: &XtreamPlaylistItem, content: &str) -> Result<String, Error> {
+fn get_xtream_vod_info(config: &Config, target: &ConfigTarget, pli: &XtreamPlaylistItem, content: &str) -> Result<String, Error> {
     if let Ok(mut doc) = serde_json::from_str::<Map<String, Value>>(content) {
         if let Some(Value::Object(movie_data)) = doc.get_mut(TAG_MOVIE_DATA) {
             let stream_id = pli.virtual_id;
@@ -314,7 +352,8 @@ fn get_xtream_vod_info(target: &ConfigTarget, pli: &XtreamPlaylistItem, content:
             if options.skip_video_direct_source {
                 movie_data.insert(TAG_DIRECT_SOURCE.to_string(), Value::String(String::new()));
             } else {
-                movie_data.insert(TAG_DIRECT_SOURCE.to_string(), Value::String(pli.url.to_string()));
+                let url = TokenRewrite::new(config).and_then(|token_rewrite| token_rewrite.rewrite(pli.input_id, &pli.url));
+                movie_data.insert(TAG_DIRECT_SOURCE.to_string(), Value::String(url.unwrap_or_else(|| pli.url.to_string())));
             }
             if let Ok(result) = serde_json::to_string(&doc) {
                 return Ok(result);
@@ -324,23 +363,50 @@ fn get_xtream_vod_info(target: &ConfigTarget, pli: &XtreamPlaylistItem, content:
     Err(Error::new(ErrorKind::Other, format!("Failed to get vod info for id {}", pli.virtual_id)))
 }
 
+/// The stored series info keeps the provider urls of the episodes, their token is replaced with the renewed token.
+fn rewrite_series_info_tokens(config: &Config, pli: &XtreamPlaylistItem, content: String) -> String {
+    let Some(token_rewrite) = TokenRewrite::new(config) else {
+        return content;
+    };
+    let Ok(mut doc) = serde_json::from_str::<Value>(&content) else {
+        return content;
+    };
+    let mut changed = false;
+    let episodes = doc.get_mut("episodes").and_then(Value::as_object_mut).into_iter()
+        .flat_map(|episodes| episodes.values_mut()).filter_map(Value::as_array_mut).flatten().filter_map(Value::as_object_mut);
+    for episode in episodes {
+        if let Some(url) = episode.get(TAG_DIRECT_SOURCE).and_then(Value::as_str).and_then(|url| token_rewrite.rewrite(pli.input_id, url)) {
+            episode.insert(TAG_DIRECT_SOURCE.to_string(), Value::String(url));
+            changed = true;
+        }
+    }
+    if changed {
+        serde_json::to_string(&doc).unwrap_or(content)
+    } else {
+        content
+    }
+}
+
 async fn xtream_get_stream_info_content(info_url: &str, input: &ConfigInput) -> Result<String, Error> {
     request_utils::download_text_content(input, info_url, None).await
 }
 
-async fn xtream_get_stream_info(config: &Config, input: &ConfigInput, target: &ConfigTarget,
+/// The upstream request is shared with the clients which request the same info at the same time.
+async fn xtream_get_stream_info(app_state: &AppState, input: &ConfigInput, target: &ConfigTarget,
                                 pli: &XtreamPlaylistItem, info_url: &str, cluster: XtreamCluster) -> Result<String, Error> {
+    let config = &app_state.config;
     if cluster == XtreamCluster::Series {
         if let Some(content) = xtream_repository::xtream_load_series_info(config, target.name.as_str(), pli.virtual_id) {
-            return Ok(content);
+            return Ok(rewrite_series_info_tokens(config, pli, content));
         }
     }
 
-    if let Ok(content) = xtream_get_stream_info_content(info_url, input).await {
+    if let Ok(content) = app_state.info_requests.get(info_url, || xtream_get_stream_info_content(info_url, input)).await {
         return match cluster {
             XtreamCluster::Live => Ok(content),
-            XtreamCluster::Video => get_xtream_vod_info(target, pli, &content),
-            XtreamCluster::Series => xtream_repository::write_and_get_xtream_series_info(config, target, pli, &content),
+            XtreamCluster::Video => get_xtream_vod_info(config, target, pli, &content),
+            XtreamCluster::Series => xtream_repository::write_and_get_xtream_series_info(config, target, pli, &content)
+                .map(|content| rewrite_series_info_tokens(config, pli, content)),
         };
     }
 
@@ -357,13 +423,16 @@ async fn xtream_get_stream_info_response(app_state: &AppState, user: &ProxyUserC
     };
 
     if let Ok(pli) = xtream_repository::xtream_get_item_for_stream_id(virtual_id, &app_state.config, target, Some(cluster)) {
+        if target.is_adult_item_hidden(user, &pli.parent_code) {
+            return HttpResponse::Forbidden().finish();
+        }
         let input_id = pli.input_id;
         if let Some(input) = app_state.config.get_input_by_id(input_id) {
             if let Some(info_url) = get_xtream_player_api_info_url(input, cluster, pli.provider_id) {
                 // Redirect is only possible for live streams, vod and series info needs to be modified
                 if user.proxy == ProxyType::Redirect && cluster == XtreamCluster::Live {
                     return HttpResponse::Found().insert_header(("Location", info_url)).finish();
-                } else if let Ok(content) = xtream_get_stream_info(&app_state.config, input, target, &pli, info_url.as_str(), cluster).await {
+                } else if let Ok(content) = xtream_get_stream_info(app_state, input, target, &pli, info_url.as_str(), cluster).await {
                     return HttpResponse::Ok().content_type(mime::APPLICATION_JSON).body(content);
                 }
             }
@@ -376,6 +445,17 @@ async fn xtream_get_stream_info_response(app_state: &AppState, user: &ProxyUserC
     }
 }
 
+/// Creates the epg listings from the stored epg of the target, `None` if the channel has no epg.
+fn xtream_get_stored_epg_response<F>(app_state: &AppState, user: &ProxyUserCredentials, target: &ConfigTarget, pli: &XtreamPlaylistItem, create_listings: F) -> Option<HttpResponse>
+where
+    F: FnOnce(&Path, &str, i32, i64) -> Value,
+{
+    let epg_channel_id = pli.epg_channel_id.as_ref().filter(|epg_channel_id| !epg_channel_id.is_empty())?;
+    let epg_path = get_target_epg_path(&app_state.config, target)?;
+    let offset = parse_timeshift(user.epg_timeshift.as_ref()).unwrap_or(0);
+    Some(HttpResponse::Ok().json(create_listings(&epg_path, epg_channel_id, offset, chrono::Utc::now().timestamp())))
+}
+
 async fn xtream_get_short_epg(app_state: &AppState, user: &ProxyUserCredentials, target: &ConfigTarget, stream_id: &str, limit: &str) -> HttpResponse {
     let target_name = &target.name;
     if target.has_output(&TargetType::Xtream) {
@@ -385,6 +465,10 @@ async fn xtream_get_short_epg(app_state: &AppState, user: &ProxyUserCredentials,
         };
 
         if let Ok(pli) = xtream_repository::xtream_get_item_for_stream_id(virtual_id, &app_state.config, target, None) {
+            if let Some(response) = xtream_get_stored_epg_response(app_state, user, target, &pli,
+                |epg_path, epg_channel_id, offset, now| get_short_epg_listings(epg_path, epg_channel_id, virtual_id, limit, offset, now)) {
+                return response;
+            }
             let input_id: u16 = pli.input_id;
             if let Some(input) = app_state.config.get_input_by_id(input_id) {
                 if let Some(action_url) = get_xtream_player_api_action_url(input, ACTION_GET_SHORT_EPG) {
@@ -411,7 +495,23 @@ async fn xtream_get_short_epg(app_state: &AppState, user: &ProxyUserCredentials,
     HttpResponse::NoContent().finish()
 }
 
-async fn xtream_player_api_handle_content_action(config: &Config, target_name: &str, action: &str, category_id: &str, req: &HttpRequest) -> Option<HttpResponse> {
+/// Serves the categories of the file without the hidden `adult_groups` and the groups of the active blackout windows.
+/// The `favorites` category is served as first category.
+fn serve_filtered_categories(file_path: &Path, filter: &HashMap<&str, &str>, target: &ConfigTarget, hide_adult: bool, blackout: &Blackout, favorites: Option<Value>) -> HttpResponse {
+    let mut categories = json_utils::json_filter_file(file_path, filter);
+    categories.retain(|category| !category.get(TAG_CATEGORY_NAME).and_then(Value::as_str)
+        .is_some_and(|name| (hide_adult && target.is_adult_group(name)) || blackout.is_hidden_group(name)));
+    if let Some(favorites) = favorites {
+        if filter.get(TAG_CATEGORY_ID).is_none_or(|category_id| favorites.get(TAG_CATEGORY_ID).and_then(Value::as_str) == Some(category_id)) {
+            categories.insert(0, favorites);
+        }
+    }
+    HttpResponse::Ok().json(categories)
+}
+
+async fn xtream_player_api_handle_content_action(config: &Config, target: &ConfigTarget, user: &ProxyUserCredentials, action: &str, category_id: &str, req: &HttpRequest) -> Option<HttpResponse> {
+    let hide_adult = target.hide_adult_content(user);
+    let target_name = target.name.as_str();
     if let Ok((path, content)) = match action {
         ACTION_GET_LIVE_CATEGORIES => xtream_repository::xtream_get_collection_path(config, target_name, xtream_repository::COL_CAT_LIVE),
         ACTION_GET_VOD_CATEGORIES => xtream_repository::xtream_get_collection_path(config, target_name, xtream_repository::COL_CAT_VOD),
@@ -420,8 +520,15 @@ async fn xtream_player_api_handle_content_action(config: &Config, target_name: &
     } {
         if let Some(file_path) = path {
             let category_id = category_id.trim();
-            if !category_id.is_empty() {
-                return Some(serve_query(&file_path, &HashMap::from([(TAG_CATEGORY_ID, category_id)])));
+            let filter = if category_id.is_empty() { HashMap::new() } else { HashMap::from([(TAG_CATEGORY_ID, category_id)]) };
+            let blackout = target.get_blackout();
+            let favorites = (action == ACTION_GET_LIVE_CATEGORIES && !favorites_load(config, target_name, &user.username).is_empty())
+                .then(xtream_repository::xtream_favorites_category);
+            if hide_adult || !blackout.is_empty() || favorites.is_some() {
+                return Some(serve_filtered_categories(&file_path, &filter, target, hide_adult, &blackout, favorites));
+            }
+            if !filter.is_empty() {
+                return Some(serve_query(&file_path, &filter));
             }
             return Some(serve_file(&file_path, req, mime::APPLICATION_JSON).await);
         } else if let Some(payload) = content {
@@ -432,16 +539,39 @@ async fn xtream_player_api_handle_content_action(config: &Config, target_name: &
     None
 }
 
-async fn xtream_get_catchup_response(app_state: &AppState, target: &ConfigTarget, stream_id: &str, start: &str, end: &str) -> HttpResponse {
+fn xtream_search_response(app_state: &AppState, user: &ProxyUserCredentials, target: &ConfigTarget, cluster: XtreamCluster, query: &str) -> HttpResponse {
+    if !target.options.as_ref().is_some_and(|options| options.xtream_search) {
+        return HttpResponse::NotFound().finish();
+    }
+    match xtream_repository::xtream_search_rewrite_playlist(cluster, &app_state.config, target, query, user) {
+        Ok(items) => HttpResponse::Ok().json(items),
+        Err(err) => {
+            error!("Failed search for xtream target: {} error: {}", &target.name, err);
+            HttpResponse::NoContent().finish()
+        }
+    }
+}
+
+async fn xtream_get_catchup_response(app_state: &AppState, user: &ProxyUserCredentials, target: &ConfigTarget, stream_id: &str, start: &str, end: &str) -> HttpResponse {
     let virtual_id: u32 = try_result_bad_request!(FromStr::from_str(stream_id));
     let pli = try_result_bad_request!(xtream_repository::xtream_get_item_for_stream_id(virtual_id, &app_state.config, target, Some(XtreamCluster::Live)));
+    if target.is_adult_item_hidden(user, &pli.parent_code) {
+        return HttpResponse::Forbidden().finish();
+    }
     let input = try_option_bad_request!(app_state.config.get_input_by_id(pli.input_id));
-    let info_url = try_option_bad_request!(get_xtream_player_api_action_url(input, ACTION_GET_CATCHUP_TABLE).map(|action_url| format!("{action_url}&{TAG_STREAM_ID}={}&start={start}&end={end}", pli.provider_id)));
+    let Some(info_url) = get_xtream_player_api_action_url(input, ACTION_GET_CATCHUP_TABLE).map(|action_url| format!("{action_url}&{TAG_STREAM_ID}={}&start={start}&end={end}", pli.provider_id)) else {
+        // without xtream provider there is no archive, the table is created from the stored epg
+        let response = xtream_get_stored_epg_response(app_state, user, target, &pli,
+            |epg_path, epg_channel_id, offset, now| get_simple_data_table_listings(epg_path, epg_channel_id, virtual_id, start, end, offset, now));
+        return response.unwrap_or_else(|| HttpResponse::BadRequest().finish());
+    };
     let content = try_result_bad_request!(xtream_get_stream_info_content(info_url.as_str(), input).await);
     let mut doc: Map<String, Value> = try_result_bad_request!(serde_json::from_str(&content));
     let epg_listings = try_option_bad_request!(doc.get_mut(TAG_EPG_LISTINGS).and_then(Value::as_array_mut));
     let target_path = try_option_bad_request!(get_target_storage_path(&app_state.config, target.name.as_str()));
-    let mut target_id_mapping = TargetIdMapping::new(&target_path);
+    let target_id_mapping_file = get_target_id_mapping_file(&target_path);
+    let _file_lock = try_result_bad_request!(app_state.config.file_locks.write_lock(&target_id_mapping_file));
+    let mut target_id_mapping = TargetIdMapping::new(&target_id_mapping_file);
 
     for epg_list_item in epg_listings.iter_mut().filter_map(Value::as_object_mut) {
         // TODO epg_id
@@ -483,7 +613,26 @@ async fn xtream_player_api(
     api_req: UserApiRequest,
     app_state: &web::Data<AppState>,
 ) -> HttpResponse {
-    let user_target = get_user_target(&api_req, app_state);
+    let started = Instant::now();
+    let username = api_req.username.trim().to_string();
+    let action = api_req.action.trim().to_string();
+    let response = xtream_player_api_response(req, api_req, app_state).await;
+    app_state.access_log.log_request(req, &username, &action, started, &response);
+    response
+}
+
+async fn xtream_player_api_response(
+    req: &HttpRequest,
+    api_req: UserApiRequest,
+    app_state: &web::Data<AppState>,
+) -> HttpResponse {
+    if let Some(response) = check_rate_limit(req, app_state, api_req.username.trim()) {
+        return response;
+    }
+    let user_target = get_user_target(&api_req, app_state, req);
+    if user_target.is_none() {
+        register_failed_login(req, app_state);
+    }
     if let Some((user, target)) = user_target {
         if !target.has_output(&TargetType::Xtream) {
             return HttpResponse::Ok().json(get_user_info(&user, &app_state.config));
@@ -517,27 +666,42 @@ async fn xtream_player_api(
                 ).await;
             }
             ACTION_GET_CATCHUP_TABLE => {
-                skip_response_if_flag_set!(skip_live, xtream_get_catchup_response(app_state, target, api_req.stream_id.trim(), api_req.start.trim(), api_req.end.trim()).await);
+                skip_response_if_flag_set!(skip_live, xtream_get_catchup_response(app_state, &user, target, api_req.stream_id.trim(), api_req.start.trim(), api_req.end.trim()).await);
+            }
+            ACTION_SEARCH_VOD => {
+                skip_response_if_flag_set!(skip_vod, xtream_search_response(app_state, &user, target, XtreamCluster::Video, api_req.search.trim()));
+            }
+            ACTION_SEARCH_SERIES => {
+                skip_response_if_flag_set!(skip_series, xtream_search_response(app_state, &user, target, XtreamCluster::Series, api_req.search.trim()));
             }
             _ => {}
         }
 
         // Handle general content actions
         if let Some(response) = xtream_player_api_handle_content_action(
-            &app_state.config, &target.name, action, api_req.category_id.trim(), req,
+            &app_state.config, target, &user, action, api_req.category_id.trim(), req,
         ).await {
             return response;
         }
 
         let category_id = api_req.category_id.trim().parse::<u32>().unwrap_or(0);
+        if action == ACTION_GET_LIVE_STREAMS && category_id == FAVORITES_CATEGORY_ID && !skip_live {
+            return match xtream_repository::xtream_load_favorites(&app_state.config, target, &user) {
+                Ok(items) => HttpResponse::Ok().json(items),
+                Err(err) => {
+                    error!("Failed favorites for xtream target: {} error: {}", &target.name, err);
+                    HttpResponse::NoContent().finish()
+                }
+            };
+        }
         let result = match action {
             ACTION_GET_LIVE_STREAMS =>
-                skip_flag_optional!(skip_live, xtream_repository::xtream_load_rewrite_playlist(XtreamCluster::Live, &app_state.config, target, category_id)),
+                skip_flag_optional!(skip_live, xtream_repository::xtream_load_rewrite_playlist(XtreamCluster::Live, &app_state.config, target, category_id, &user)),
             ACTION_GET_VOD_STREAMS =>
-                skip_flag_optional!(skip_vod, xtream_repository::xtream_load_rewrite_playlist(XtreamCluster::Video, &app_state.config, target, category_id)),
+                skip_flag_optional!(skip_vod, xtream_repository::xtream_load_rewrite_playlist(XtreamCluster::Video, &app_state.config, target, category_id, &user)),
             ACTION_GET_SERIES =>
-                skip_flag_optional!(skip_series, xtream_repository::xtream_load_rewrite_playlist(XtreamCluster::Series, &app_state.config, target, category_id)),
-            _ => Some(Err(M3uFilterError::new(M3uFilterErrorKind::Info, format!("Cant find action: {action} for target: {}", &target.name))
+                skip_flag_optional!(skip_series, xtream_repository::xtream_load_rewrite_playlist(XtreamCluster::Series, &app_state.config, target, category_id, &user)),
+            _ => Some(Err(M3uFilterError::new(M3uFilterErrorKind::Info, format!("Cant find action: {action} for target: {}", &target.name)).with_category(M3uFilterErrorCategory::Api)
             )),
         };
 
@@ -0,0 +1,170 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use chrono::{DateTime, NaiveDateTime};
+use serde_json::{json, Value};
+
+use crate::model::xmltv::{XmlTag, EPG_ATTRIB_CHANNEL, EPG_TAG_PROGRAMME};
+use crate::processing::xmltv_parser::parse_tvguide;
+
+const EPG_TIME_FORMAT: &str = "%Y%m%d%H%M%S";
+const XTREAM_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+/// Xtream panels deliver 4 entries if no limit is given.
+const SHORT_EPG_DEFAULT_LIMIT: usize = 4;
+
+pub(crate) struct EpgProgramme {
+    pub start: i64,
+    pub stop: i64,
+    pub title: String,
+    desc: String,
+    lang: String,
+}
+
+/// Parses `20240101120000 +0100`, times without offset are UTC.
+fn parse_epg_time(value: &str) -> Option<i64> {
+    DateTime::parse_from_str(value.trim(), &format!("{EPG_TIME_FORMAT} %z")).map(|date_time| date_time.timestamp())
+        .or_else(|_| NaiveDateTime::parse_from_str(value.trim(), EPG_TIME_FORMAT).map(|date_time| date_time.and_utc().timestamp()))
+        .ok()
+}
+
+fn get_child<'a>(tag: &'a XmlTag, name: &str) -> Option<&'a XmlTag> {
+    tag.children.as_ref()?.iter().find(|child| child.name == name).map(AsRef::as_ref)
+}
+
+fn to_programme(tag: &XmlTag, offset_seconds: i64) -> Option<EpgProgramme> {
+    let start = parse_epg_time(tag.get_attribute_value("start")?)? + offset_seconds;
+    let stop = parse_epg_time(tag.get_attribute_value("stop")?)? + offset_seconds;
+    let title = get_child(tag, "title")?;
+    Some(EpgProgramme {
+        start,
+        stop,
+        title: title.value.clone().unwrap_or_default(),
+        desc: get_child(tag, "desc").and_then(|desc| desc.value.clone()).unwrap_or_default(),
+        lang: title.get_attribute_value("lang").cloned().unwrap_or_default(),
+    })
+}
+
+/// Reads the programmes of the channels from the stored xmltv file, with the channel id.
+pub(crate) fn read_programmes(epg_path: &Path, epg_channel_ids: &HashSet<String>) -> Vec<(String, EpgProgramme)> {
+    let Ok(file) = File::open(epg_path) else {
+        return vec![];
+    };
+    let mut result = vec![];
+    parse_tvguide(BufReader::new(file), &mut |tag: XmlTag| {
+        if tag.name == EPG_TAG_PROGRAMME {
+            if let Some(channel) = tag.get_attribute_value(EPG_ATTRIB_CHANNEL).filter(|channel| epg_channel_ids.contains(*channel)) {
+                if let Some(programme) = to_programme(&tag, 0) {
+                    result.push((channel.to_string(), programme));
+                }
+            }
+        }
+    });
+    result
+}
+
+/// Reads the programmes of the channel from the stored xmltv file, sorted by start.
+pub(in crate::api) fn read_channel_programmes(epg_path: &Path, epg_channel_id: &str, offset_minutes: i32) -> Vec<EpgProgramme> {
+    let Ok(file) = File::open(epg_path) else {
+        return vec![];
+    };
+    let offset_seconds = i64::from(offset_minutes) * 60;
+    let mut result = vec![];
+    parse_tvguide(BufReader::new(file), &mut |tag: XmlTag| {
+        if tag.name == EPG_TAG_PROGRAMME && tag.get_attribute_value(EPG_ATTRIB_CHANNEL).is_some_and(|channel| channel == epg_channel_id) {
+            if let Some(programme) = to_programme(&tag, offset_seconds) {
+                result.push(programme);
+            }
+        }
+    });
+    result.sort_by_key(|programme| programme.start);
+    result
+}
+
+fn format_timestamp(timestamp: i64) -> String {
+    DateTime::from_timestamp(timestamp, 0).map(|date_time| date_time.format(XTREAM_TIME_FORMAT).to_string()).unwrap_or_default()
+}
+
+fn to_listing(programme: &EpgProgramme, epg_channel_id: &str, stream_id: u32, now: Option<i64>) -> Value {
+    let mut listing = json!({
+        "id": programme.start.to_string(),
+        "epg_id": epg_channel_id,
+        "title": STANDARD.encode(&programme.title),
+        "lang": programme.lang,
+        "start": format_timestamp(programme.start),
+        "end": format_timestamp(programme.stop),
+        "description": STANDARD.encode(&programme.desc),
+        "channel_id": epg_channel_id,
+        "start_timestamp": programme.start.to_string(),
+        "stop_timestamp": programme.stop.to_string(),
+        "stream_id": stream_id.to_string(),
+    });
+    if let (Some(now), Some(doc)) = (now, listing.as_object_mut()) {
+        doc.insert("now_playing".to_string(), json!(u8::from(programme.start <= now && now < programme.stop)));
+        doc.insert("has_archive".to_string(), json!(0));
+    }
+    listing
+}
+
+/// `get_short_epg`: the current and the next programmes of the channel.
+pub(in crate::api) fn get_short_epg_listings(epg_path: &Path, epg_channel_id: &str, stream_id: u32, limit: &str, offset_minutes: i32, now: i64) -> Value {
+    let limit = limit.parse::<usize>().ok().filter(|limit| *limit > 0).unwrap_or(SHORT_EPG_DEFAULT_LIMIT);
+    let listings: Vec<Value> = read_channel_programmes(epg_path, epg_channel_id, offset_minutes).iter()
+        .filter(|programme| programme.stop > now)
+        .take(limit)
+        .map(|programme| to_listing(programme, epg_channel_id, stream_id, None))
+        .collect();
+    json!({ "epg_listings": listings })
+}
+
+/// `get_simple_data_table`: all programmes of the channel, optional restricted to `start` and `end` (unix timestamps).
+pub(in crate::api) fn get_simple_data_table_listings(epg_path: &Path, epg_channel_id: &str, stream_id: u32, start: &str, end: &str, offset_minutes: i32, now: i64) -> Value {
+    let start = start.parse::<i64>().ok();
+    let end = end.parse::<i64>().ok();
+    let listings: Vec<Value> = read_channel_programmes(epg_path, epg_channel_id, offset_minutes).iter()
+        .filter(|programme| start.is_none_or(|start| programme.stop > start) && end.is_none_or(|end| programme.start < end))
+        .map(|programme| to_listing(programme, epg_channel_id, stream_id, Some(now)))
+        .collect();
+    json!({ "epg_listings": listings })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use crate::api::xtream_epg::{get_short_epg_listings, get_simple_data_table_listings, parse_epg_time};
+    use crate::utils::test_utils::create_temp_dir;
+
+    #[test]
+    fn test_epg_listings() {
+        assert_eq!(parse_epg_time("20240101120000 +0100"), Some(1_704_106_800));
+        assert_eq!(parse_epg_time("20240101120000"), Some(1_704_110_400));
+        let temp_dir = create_temp_dir("xtream_epg");
+        let path = temp_dir.path().join("epg.xml");
+        let mut file = std::fs::File::create(&path).unwrap();
+        file.write_all(br#"<?xml version="1.0" encoding="utf-8" ?><tv>
+<channel id="cnn.us"><display-name>CNN</display-name></channel>
+<programme start="20240101130000 +0000" stop="20240101140000 +0000" channel="cnn.us"><title lang="en">News 2</title></programme>
+<programme start="20240101120000 +0000" stop="20240101130000 +0000" channel="cnn.us"><title lang="en">News 1</title><desc>Headlines</desc></programme>
+<programme start="20240101120000 +0000" stop="20240101130000 +0000" channel="bbc.uk"><title>BBC</title></programme>
+</tv>"#).unwrap();
+        let now = 1_704_110_400 + 60;
+        let short_epg = get_short_epg_listings(&path, "cnn.us", 7, "1", 0, now);
+        let listings = short_epg["epg_listings"].as_array().unwrap();
+        assert_eq!(listings.len(), 1);
+        assert_eq!(listings[0]["title"], "TmV3cyAx");
+        assert_eq!(listings[0]["description"], "SGVhZGxpbmVz");
+        assert_eq!(listings[0]["start"], "2024-01-01 12:00:00");
+        assert_eq!(listings[0]["stream_id"], "7");
+        assert_eq!(get_short_epg_listings(&path, "cnn.us", 7, "", 60, now)["epg_listings"].as_array().unwrap().len(), 2);
+
+        let table = get_simple_data_table_listings(&path, "cnn.us", 7, "", "", 0, now);
+        let listings = table["epg_listings"].as_array().unwrap();
+        assert_eq!(listings.len(), 2);
+        assert_eq!(listings[0]["now_playing"], 1);
+        assert_eq!(listings[1]["now_playing"], 0);
+    }
+}
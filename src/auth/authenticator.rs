@@ -1,8 +1,12 @@
-use actix_web::{dev::ServiceRequest, Error, web};
+use actix_web::{dev::ServiceRequest, Error, HttpMessage, web};
+use actix_web::http::header::Header as _;
 use actix_web_httpauth::extractors::bearer::BearerAuth;
+use actix_web_httpauth::headers::authorization::{Authorization, Basic};
 use chrono::{Local, Duration};
 use jsonwebtoken::{Algorithm, DecodingKey, encode, decode, EncodingKey, Header, Validation};
 use crate::api::api_model::AppState;
+use crate::auth::password::verify_password;
+use crate::auth::role::Role;
 use crate::model::config::WebAuthConfig;
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -10,9 +14,20 @@ pub struct Claims {
     iss: String,
     iat: i64,
     exp: i64,
+    #[serde(default)]
+    pub sub: String,
+    #[serde(default)]
+    pub role: Role,
 }
 
-pub fn create_jwt(web_auth_config: &WebAuthConfig) -> Result<String, std::io::Error> {
+/// The authenticated user or api token of an admin request.
+#[derive(Debug, Clone)]
+pub struct Principal {
+    pub name: String,
+    pub role: Role,
+}
+
+pub fn create_jwt(web_auth_config: &WebAuthConfig, username: &str, role: Role) -> Result<String, std::io::Error> {
     let mut header = Header::new(Algorithm::HS256);
     header.typ = Some("JWT".to_string());
     let now = Local::now();
@@ -22,6 +37,8 @@ pub fn create_jwt(web_auth_config: &WebAuthConfig) -> Result<String, std::io::Er
         iss: web_auth_config.issuer.clone(),
         iat,
         exp,
+        sub: username.to_string(),
+        role,
     };
     match encode(&header, &claims, &EncodingKey::from_secret(web_auth_config.secret.as_bytes())) {
         Ok(jwt) => Ok(jwt),
@@ -29,24 +46,50 @@ pub fn create_jwt(web_auth_config: &WebAuthConfig) -> Result<String, std::io::Er
     }
 }
 
-pub fn verify_token(bearer: Option<BearerAuth>, secret_key: &[u8]) -> bool {
-    if let Some(auth) = bearer {
-        let token = auth.token();
-        let token_message = decode::<Claims>(token, &DecodingKey::from_secret(secret_key), &Validation::new(Algorithm::HS256));
-        if token_message.is_ok() {
-            return true;
+pub fn verify_token(bearer: Option<BearerAuth>, secret_key: &[u8]) -> Option<Claims> {
+    let auth = bearer?;
+    decode::<Claims>(auth.token(), &DecodingKey::from_secret(secret_key), &Validation::new(Algorithm::HS256))
+        .ok().map(|token_data| token_data.claims)
+}
+
+fn verify_basic_auth(req: &ServiceRequest, web_auth: &WebAuthConfig) -> Option<Principal> {
+    let auth = Authorization::<Basic>::parse(req).ok()?;
+    let basic = auth.as_ref();
+    if let (Some(hash), Some(password)) = (web_auth.get_user_password(basic.user_id()), basic.password()) {
+        if verify_password(hash, password.as_bytes()) {
+            return Some(Principal { name: basic.user_id().to_string(), role: web_auth.get_user_role(basic.user_id()) });
         }
     }
-    false
+    None
 }
 
+/// Api tokens are named by the start of their hash, the token itself is never logged.
+fn api_token_name(token: &str) -> String {
+    let hash = blake3::hash(token.as_bytes()).to_hex();
+    format!("token:{}", &hash[..8])
+}
+
+/// Admin requests are authorized with a static api token or a jwt as bearer token,
+/// or with the credentials of an admin user as basic auth.
+/// The principal of the credentials is stored in the request extensions for the route middlewares of `role`
+/// and the audit log.
 pub async fn validator(
     req: ServiceRequest,
     credentials: Option<BearerAuth>,
 ) -> Result<ServiceRequest, (Error, ServiceRequest)> {
     let app_state: &web::Data<AppState> = req.app_data::<web::Data<AppState>>().unwrap();
-    let secret_key = app_state.config.web_auth.as_ref().unwrap().secret.as_ref();
-    if verify_token(credentials, secret_key) {
+    let web_auth = app_state.config.web_auth.as_ref().unwrap();
+    let principal = match credentials {
+        Some(bearer) => match web_auth.get_api_token_role(bearer.token()) {
+            Some(role) => Some(Principal { name: api_token_name(bearer.token()), role }),
+            None if web_auth.is_jwt_enabled() => verify_token(Some(bearer), web_auth.secret.as_ref())
+                .map(|claims| Principal { name: claims.sub, role: claims.role }),
+            None => None,
+        },
+        None => verify_basic_auth(&req, web_auth),
+    };
+    if let Some(principal) = principal {
+        req.extensions_mut().insert(principal);
         Ok(req)
     } else {
         Err((actix_web::error::ErrorUnauthorized("Unauthorized"), req))
@@ -22,7 +22,15 @@ pub fn hash(password: &[u8]) -> Option<String> {
     None
 }
 
+fn is_bcrypt_hash(hash: &str) -> bool {
+    ["$2a$", "$2b$", "$2x$", "$2y$"].iter().any(|prefix| hash.starts_with(prefix))
+}
+
+/// Verifies the password against an argon2 or bcrypt hash.
 pub fn verify_password(hash: &str, password: &[u8]) -> bool {
+    if is_bcrypt_hash(hash) {
+        return bcrypt::verify(password, hash).unwrap_or(false);
+    }
     if let Ok(valid) = argon2::verify_encoded(hash, password) {
         return valid;
     }
@@ -50,3 +58,18 @@ pub fn generate_password() -> std::io::Result<String> {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use crate::auth::password::{hash, verify_password};
+
+    #[test]
+    fn test_verify_password() {
+        let argon2_hash = hash(b"secret123").unwrap();
+        assert!(verify_password(&argon2_hash, b"secret123"));
+        assert!(!verify_password(&argon2_hash, b"secret124"));
+        let bcrypt_hash = bcrypt::hash("secret123", 4).unwrap();
+        assert!(verify_password(&bcrypt_hash, b"secret123"));
+        assert!(!verify_password(&bcrypt_hash, b"secret124"));
+    }
+}
@@ -0,0 +1,123 @@
+use actix_web::body::BoxBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::{from_fn, Next};
+use actix_web::{web, Error, HttpMessage, HttpResponse, Route, Scope};
+
+use crate::api::api_error::{ApiError, ApiErrorCode};
+use crate::api::api_model::AppState;
+use crate::auth::authenticator::Principal;
+
+/// Role of an admin api user or token, a role includes the rights of the lower roles.
+/// - `viewer` reads the status and the playlists
+/// - `operator` triggers refreshes and changes channels, recordings and streams
+/// - `admin` edits the config and the users
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    #[default]
+    Viewer,
+    Operator,
+    Admin,
+}
+
+impl Role {
+    /// The role of the request, the validator stores the principal of the credentials.
+    /// Only without `web_auth` a request has no principal, it is not authenticated and has all rights.
+    /// With `web_auth` a request without principal has no role.
+    const fn granted(principal: Option<&Principal>, web_auth_enabled: bool) -> Option<Self> {
+        match principal {
+            Some(principal) => Some(principal.role),
+            None if web_auth_enabled => None,
+            None => Some(Self::Admin),
+        }
+    }
+
+    fn of_request(req: &ServiceRequest) -> Option<Self> {
+        let web_auth_enabled = req.app_data::<web::Data<AppState>>()
+            .is_some_and(|app_state| app_state.config.web_auth.as_ref().is_some_and(|web_auth| web_auth.enabled));
+        Self::granted(req.extensions().get::<Principal>(), web_auth_enabled)
+    }
+}
+
+async fn require_role(role: Role, req: ServiceRequest, next: Next<BoxBody>) -> Result<ServiceResponse<BoxBody>, Error> {
+    match Role::of_request(&req) {
+        Some(granted) if granted >= role => next.call(req).await,
+        granted => {
+            let err = ApiError::new(ApiErrorCode::Forbidden, format!("The route needs the role {role:?}"))
+                .with_hint(&granted.map_or_else(|| "the request is not authenticated".to_string(), |granted| format!("the credentials have the role {granted:?}")));
+            Ok(req.into_response(HttpResponse::from(err)))
+        }
+    }
+}
+
+/// A scope whose routes can only be registered with the role they need, there are no routes without a role check.
+pub struct RoleScope {
+    scope: Scope,
+}
+
+impl RoleScope {
+    pub fn new(path: &str) -> Self {
+        Self { scope: web::scope(path) }
+    }
+
+    #[must_use]
+    pub fn route(self, path: &str, role: Role, route: Route) -> Self {
+        let route = route.wrap(from_fn(move |req: ServiceRequest, next: Next<BoxBody>| require_role(role, req, next)));
+        Self { scope: self.scope.route(path, route) }
+    }
+
+    pub fn into_scope(self) -> Scope {
+        self.scope
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::body::BoxBody;
+    use actix_web::dev::ServiceRequest;
+    use actix_web::http::StatusCode;
+    use actix_web::middleware::{from_fn, Next};
+    use actix_web::test::{call_service, init_service, TestRequest};
+    use actix_web::{web, App, HttpMessage, HttpResponse};
+
+    use crate::auth::authenticator::Principal;
+    use crate::auth::role::{Role, RoleScope};
+
+    #[test]
+    fn test_role_order() {
+        assert!(Role::Admin > Role::Operator);
+        assert!(Role::Operator > Role::Viewer);
+        assert_eq!(serde_yaml::from_str::<Role>("operator").unwrap(), Role::Operator);
+    }
+
+    #[test]
+    fn test_granted_role() {
+        let viewer = Principal { name: "guest".to_string(), role: Role::Viewer };
+        assert_eq!(Role::granted(Some(&viewer), true), Some(Role::Viewer));
+        // without web_auth there are no credentials
+        assert_eq!(Role::granted(None, false), Some(Role::Admin));
+        assert_eq!(Role::granted(None, true), None);
+    }
+
+    #[actix_web::test]
+    async fn test_role_scope() {
+        let scope = RoleScope::new("/api")
+            .route("/status", Role::Viewer, web::get().to(HttpResponse::Ok))
+            .route("/config", Role::Admin, web::get().to(HttpResponse::Ok))
+            .into_scope();
+        // without app state web_auth is disabled, the requests have all rights
+        let app = init_service(App::new().service(scope)).await;
+        assert_eq!(call_service(&app, TestRequest::get().uri("/api/config").to_request()).await.status(), StatusCode::OK);
+
+        let scope = RoleScope::new("/api")
+            .route("/status", Role::Viewer, web::get().to(HttpResponse::Ok))
+            .route("/config", Role::Admin, web::get().to(HttpResponse::Ok))
+            .into_scope();
+        let app = init_service(App::new().service(scope.wrap(from_fn(|req: ServiceRequest, next: Next<BoxBody>| {
+            req.extensions_mut().insert(Principal { name: "guest".to_string(), role: Role::Viewer });
+            async move { next.call(req).await }
+        })))).await;
+        assert_eq!(call_service(&app, TestRequest::get().uri("/api/status").to_request()).await.status(), StatusCode::OK);
+        assert_eq!(call_service(&app, TestRequest::get().uri("/api/config").to_request()).await.status(), StatusCode::FORBIDDEN);
+    }
+}
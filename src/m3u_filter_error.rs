@@ -1,6 +1,9 @@
 use std::error::Error;
 use std::fmt::{Display, Formatter, Result};
 
+use m3u_filter_core::error::CoreError;
+use serde::{Deserialize, Serialize};
+
 #[macro_export]
 macro_rules! get_errors_notify_message {
     ($errors:expr, $size:expr) => {
@@ -28,27 +31,140 @@ pub enum M3uFilterErrorKind {
     Notify, // send with messaging
 }
 
-#[derive(Debug)]
+/// Where an error occurred.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum M3uFilterErrorCategory {
+    /// invalid config, source, mapping or api-proxy file
+    Config,
+    /// the provider could not be requested or returned an unusable response
+    Download,
+    /// a playlist or epg could not be parsed
+    Parse,
+    #[default]
+    Processing,
+    /// a file could not be written or published
+    Persistence,
+    /// a request of the api or of an external service api failed
+    Api,
+}
+
+impl M3uFilterErrorCategory {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Config => "config",
+            Self::Download => "download",
+            Self::Parse => "parse",
+            Self::Processing => "processing",
+            Self::Persistence => "persistence",
+            Self::Api => "api",
+        }
+    }
+}
+
+impl Display for M3uFilterErrorCategory {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("M3uFilter error: {message}")]
 pub struct M3uFilterError {
     pub kind: M3uFilterErrorKind,
+    pub category: M3uFilterErrorCategory,
     pub message: String,
-
+    /// the input or target the error occurred for
+    pub context: Option<String>,
+    #[source]
+    pub source: Option<Box<dyn Error + Send + Sync>>,
 }
 
 impl M3uFilterError {
     pub const fn new(kind: M3uFilterErrorKind, message: String) -> Self {
         Self {
             kind,
+            category: M3uFilterErrorCategory::Processing,
+            message,
+            context: None,
+            source: None,
+        }
+    }
+
+    /// Keeps the underlying error as source instead of only its text.
+    pub fn from_source<E>(kind: M3uFilterErrorKind, category: M3uFilterErrorCategory, source: E, message: String) -> Self
+    where
+        E: Into<Box<dyn Error + Send + Sync>>,
+    {
+        Self {
+            kind,
+            category,
             message,
+            context: None,
+            source: Some(source.into()),
         }
     }
+
+    #[must_use]
+    pub const fn with_category(mut self, category: M3uFilterErrorCategory) -> Self {
+        self.category = category;
+        self
+    }
+
+    /// Sets the input or target name, an existing context is kept.
+    #[must_use]
+    pub fn with_context(mut self, context: &str) -> Self {
+        if self.context.is_none() {
+            self.context = Some(context.to_string());
+        }
+        self
+    }
 }
 
-impl Display for M3uFilterError {
-    fn fmt(&self, f: &mut Formatter) -> Result {
-        write!(f, "M3uFilter error: {}", self.message)
+impl From<CoreError> for M3uFilterError {
+    fn from(err: CoreError) -> Self {
+        Self::new(M3uFilterErrorKind::Info, err.message)
     }
 }
 
-impl Error for M3uFilterError {}
+/// The serializable form of an error for the processing summary and the job api.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct M3uFilterErrorInfo {
+    pub category: M3uFilterErrorCategory,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
+    /// the text of the underlying error
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
 
+impl From<&M3uFilterError> for M3uFilterErrorInfo {
+    fn from(err: &M3uFilterError) -> Self {
+        Self {
+            category: err.category,
+            message: err.message.clone(),
+            context: err.context.clone(),
+            source: err.source.as_ref().map(ToString::to_string),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorCategory, M3uFilterErrorInfo, M3uFilterErrorKind};
+
+    #[test]
+    fn test_error_source() {
+        let io_err = std::io::Error::new(std::io::ErrorKind::NotFound, "missing");
+        let err = M3uFilterError::from_source(M3uFilterErrorKind::Info, M3uFilterErrorCategory::Persistence, io_err, "Failed to save".to_string())
+            .with_context("iptv")
+            .with_context("other");
+        assert_eq!(err.to_string(), "M3uFilter error: Failed to save");
+        assert_eq!(err.source().map(ToString::to_string).as_deref(), Some("missing"));
+        let info = M3uFilterErrorInfo::from(&err);
+        assert_eq!(serde_json::to_string(&info).unwrap(), r#"{"category":"persistence","message":"Failed to save","context":"iptv","source":"missing"}"#);
+    }
+}
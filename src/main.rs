@@ -1,24 +1,26 @@
 #![allow(clippy::module_name_repetitions)]
 extern crate env_logger;
-extern crate pest;
-#[macro_use]
-extern crate pest_derive;
-extern crate core;
 
 use std::fs::File;
+use std::io::{BufWriter, Write};
 use std::path::PathBuf;
+use std::str::FromStr;
 use std::sync::Arc;
 use actix_rt::System;
 
 use clap::Parser;
 use env_logger::Builder;
 use log::{error, info, LevelFilter};
+use m3u_filter_core::bench_data;
 use crate::auth::password::generate_password;
 
-use crate::model::config::{Config, HealthcheckConfig, ProcessTargets, validate_targets};
+use crate::model::config::{Config, HealthcheckConfig, LogConfig, LogFormat, ProcessTargets, validate_inputs, validate_targets};
 use crate::model::healthcheck::Healthcheck;
-use crate::processing::playlist_processor;
+use crate::processing::{playlist_processor, trakt};
+use crate::repository::export_repository::{playlist_export, ExportColumn, ExportFormat};
 use crate::utils::{config_reader, file_utils};
+use crate::utils::file_utils::ProfileFiles;
+use crate::utils::config_schema::ConfigSchema;
 mod m3u_filter_error;
 mod model;
 mod filter;
@@ -44,17 +46,49 @@ struct Args {
     config_file: Option<String>,
 
     /// The source config file
-    #[arg(short = 'i', long = "source")]
+    #[arg(short = 'i', long = "source-file")]
     source_file: Option<String>,
 
     /// The mapping file
     #[arg(short = 'm', long = "mapping")]
     mapping_file: Option<String>,
 
+    /// The profile, uses config.<profile>.yml, source.<profile>.yml, mapping.<profile>.yml and api-proxy.<profile>.yml if present
+    #[arg(short = None, long = "profile")]
+    profile: Option<String>,
+
     /// The target to process
     #[arg(short = 't', long)]
     target: Option<Vec<String>>,
 
+    /// The source to process, selected by the name of its input
+    #[arg(short = None, long = "source", alias = "input")]
+    source: Option<Vec<String>>,
+
+    /// Ignores the incremental cache and the shrink protection
+    #[arg(short = None, long = "force-refresh", default_value_t = false, default_missing_value = "true")]
+    force_refresh: bool,
+
+    /// Prints the channel counts per group without writing the outputs
+    #[arg(short = None, long = "dry-run", default_value_t = false, default_missing_value = "true")]
+    dry_run: bool,
+
+    /// Writes the json summary of the processing to the file instead of stdout
+    #[arg(short = None, long = "summary-file")]
+    summary_file: Option<String>,
+
+    /// Exports the processed playlist of the target (-t) as json or csv to stdout
+    #[arg(short = None, long = "export")]
+    export: Option<String>,
+
+    /// The exported columns: name,group,url,epg_id,type
+    #[arg(short = None, long = "export-columns")]
+    export_columns: Option<String>,
+
+    /// Prints the json schema of a config file: config, source, mapping or api-proxy
+    #[arg(short = None, long = "schema")]
+    schema: Option<String>,
+
     /// The user file
     #[arg(short = 'a', long = "api-proxy")]
     api_proxy: Option<String>,
@@ -63,6 +97,10 @@ struct Args {
     #[arg(short = 's', long, default_value_t = false, default_missing_value = "true")]
     server: bool,
 
+    /// Run in server mode as windows service, the process has to be started by the service control manager
+    #[arg(short = None, long = "service", default_value_t = false, default_missing_value = "true")]
+    service: bool,
+
     /// log level
     #[arg(short = 'l', long = "log-level", default_missing_value = "info")]
     log_level: Option<String>,
@@ -73,24 +111,60 @@ struct Args {
     #[arg(short = None, long = "healthcheck", default_value_t = false, default_missing_value = "true")]
     healthcheck: bool,
 
+    /// Checks the lineup and guide of a user like Plex/Jellyfin/Emby against the running server
+    #[arg(short = None, long = "tuner-check")]
+    tuner_check: Option<String>,
+
+    /// Authorizes the trakt account of the `trakt` config
+    #[arg(short = None, long = "trakt-login", default_value_t = false, default_missing_value = "true")]
+    trakt_login: bool,
+
+    /// Writes a synthetic m3u playlist with <GROUPS>x<ITEMS> channels to stdout, e.g. 100x500
+    #[arg(short = None, long = "bench-data")]
+    bench_data: Option<String>,
+
 }
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 fn main() {
     let args = Args::parse();
-    let default_log_level = std::env::var("M3U_FILTER_LOG").unwrap_or_else(|_| "info".to_string());
-    init_logger(args.log_level.as_ref().unwrap_or(&default_log_level));
-
     let config_path: String = args.config_path.unwrap_or_else(file_utils::get_default_config_path);
-    let config_file: String = args.config_file.unwrap_or_else(|| file_utils::get_default_config_file_path(&config_path));
+    let profile = args.profile.or_else(|| std::env::var("M3U_FILTER_PROFILE").ok()).filter(|profile| !profile.trim().is_empty());
+    let profile_files = profile.as_ref().map(|profile| ProfileFiles::new(&config_path, profile.trim()));
+    let config_file: String = args.config_file.or_else(|| profile_files.as_ref().and_then(|files| files.config_file.clone()))
+        .unwrap_or_else(|| file_utils::get_default_config_file_path(&config_path));
+
+    // the log section is read before the config, the config file errors are logged later
+    let log_config = config_reader::read_log_config(&config_file).unwrap_or_default();
+    let log_level = args.log_level.or_else(|| std::env::var("M3U_FILTER_LOG").ok())
+        .or_else(|| log_config.level.clone())
+        .unwrap_or_else(|| "info".to_string());
+    init_logger(&log_level, &log_config);
+
+    if profile_files.as_ref().is_some_and(ProfileFiles::is_empty) {
+        exit!("No config files found for profile {} in {}", profile.as_deref().unwrap_or_default(), config_path);
+    }
 
     if args.healthcheck {
         healthcheck(config_file.as_str());
     }
 
-    let sources_file: String = args.source_file.unwrap_or_else(|| file_utils::get_default_sources_file_path(&config_path));
-    let mut cfg = config_reader::read_config(config_path.as_str(), config_file.as_str(), sources_file.as_str()).unwrap_or_else(|err| exit!("{}", err));
+    if let Some(name) = args.schema.as_ref() {
+        print_config_schema(name);
+        return;
+    }
+
+    if let Some(size) = args.bench_data.as_ref() {
+        print_bench_data(size);
+        return;
+    }
+
+    let sources_file: String = args.source_file.or_else(|| profile_files.as_ref().and_then(|files| files.sources_file.clone()))
+        .unwrap_or_else(|| file_utils::get_default_sources_file_path(&config_path));
+    let mapping_file = args.mapping_file.or_else(|| profile_files.as_ref().and_then(|files| files.mapping_file.clone()));
+    let api_proxy_file = args.api_proxy.or_else(|| profile_files.as_ref().and_then(|files| files.api_proxy_file.clone()));
+    let mut cfg = config_reader::read_config(config_path.as_str(), config_file.as_str(), sources_file.as_str(), true).unwrap_or_else(|err| exit!("{}", err));
 
     if args.genpwd  {
         match generate_password() {
@@ -100,26 +174,56 @@ fn main() {
         return;
     }
 
+    if let Some(username) = args.tuner_check.as_ref() {
+        config_reader::read_api_proxy_config(api_proxy_file, &mut cfg);
+        std::process::exit(i32::from(!api::tuner_check::tuner_check(&cfg, username)));
+    }
+
     create_directories(&cfg);
 
-    let targets = validate_targets(args.target.as_ref(), &cfg.sources).unwrap_or_else(|err| exit!("{}", err));
+    if args.trakt_login {
+        if let Err(err) = System::new().block_on(async { trakt::trakt_device_login(&cfg).await }) {
+            exit!("{}", err);
+        }
+        return;
+    }
+
+    if let Some(format) = args.export.as_ref() {
+        export_playlist(&cfg, args.target.as_ref(), format, args.export_columns.as_deref());
+        return;
+    }
+
+    let mut targets = validate_targets(args.target.as_ref(), &cfg.sources).unwrap_or_else(|err| exit!("{}", err));
+    validate_inputs(args.source.as_ref(), &cfg.sources, &mut targets).unwrap_or_else(|err| exit!("{}", err));
+    targets.force_refresh = args.force_refresh;
+    targets.dry_run = args.dry_run;
 
     info!("Version: {}", VERSION);
+    if let Some(profile) = profile.as_ref() {
+        info!("Profile: {}", profile);
+    }
     info!("Current time: {}", chrono::offset::Local::now().format("%Y-%m-%d %H:%M:%S").to_string());
     info!("Working dir: {:?}", &cfg.working_dir);
     info!("Config dir: {:?}", &cfg.t_config_path);
     info!("Config file: {}", &config_file);
     info!("Source file: {}", &sources_file);
 
-    if let Err(err) = config_reader::read_mappings(args.mapping_file, &mut cfg) {
+    if let Err(err) = config_reader::read_mappings(mapping_file, &mut cfg) {
         exit!("{}", err);
     }
 
-    if args.server {
-        config_reader::read_api_proxy_config(args.api_proxy, &mut cfg);
-        start_in_server_mode(Arc::new(cfg), Arc::new(targets));
+    if args.server || args.service {
+        if args.dry_run {
+            exit!("--dry-run is not supported in server mode");
+        }
+        config_reader::read_api_proxy_config(api_proxy_file, &mut cfg);
+        if args.service {
+            start_in_service_mode(Arc::new(cfg), Arc::new(targets));
+        } else {
+            start_in_server_mode(Arc::new(cfg), Arc::new(targets));
+        }
     } else {
-        start_in_cli_mode(Arc::new(cfg), Arc::new(targets));
+        start_in_cli_mode(Arc::new(cfg), Arc::new(targets), args.summary_file.as_deref());
     }
 }
 
@@ -148,8 +252,55 @@ fn create_directories(cfg: &Config) {
         });
 }
 
-fn start_in_cli_mode(cfg: Arc<Config>, targets: Arc<ProcessTargets>) {
-    System::new().block_on(async { playlist_processor::exec_processing(cfg, targets).await });
+fn print_config_schema(name: &str) {
+    let Some(schema) = ConfigSchema::from_name(name) else {
+        exit!("Unknown config file {}, supported are config, source, mapping and api-proxy", name);
+    };
+    match schema.json_schema().and_then(|value| serde_json::to_string_pretty(&value).map_err(|err| err.to_string())) {
+        Ok(json) => println!("{json}"),
+        Err(err) => exit!("Failed to create schema: {}", err),
+    }
+}
+
+fn print_bench_data(size: &str) {
+    let Some((groups, items)) = bench_data::parse_bench_size(size) else {
+        exit!("Invalid bench data size {}, expected <groups>x<items> like 100x500", size);
+    };
+    let mut writer = BufWriter::new(std::io::stdout().lock());
+    if let Err(err) = bench_data::write_bench_playlist(&mut writer, groups, items) {
+        exit!("Failed to write bench data: {}", err);
+    }
+}
+
+fn export_playlist(cfg: &Config, targets: Option<&Vec<String>>, format: &str, columns: Option<&str>) {
+    let Some([target_name]) = targets.map(Vec::as_slice) else {
+        exit!("--export needs exactly one target given with -t");
+    };
+    let format = ExportFormat::from_str(format).unwrap_or_else(|err| exit!("{}", err));
+    let columns = ExportColumn::parse_list(columns.unwrap_or_default()).unwrap_or_else(|err| exit!("{}", err));
+    let mut writer = BufWriter::new(std::io::stdout().lock());
+    match playlist_export(cfg, target_name, format, &columns, &mut writer) {
+        Ok(true) => {}
+        Ok(false) => exit!("Target {} has no processed playlist", target_name),
+        Err(err) => exit!("{}", err),
+    }
+}
+
+fn start_in_cli_mode(cfg: Arc<Config>, targets: Arc<ProcessTargets>, summary_file: Option<&str>) {
+    let summary = System::new().block_on(async { playlist_processor::exec_processing(cfg, targets).await });
+    match serde_json::to_string_pretty(&summary) {
+        Ok(json) => match summary_file {
+            Some(path) => if let Err(err) = std::fs::write(path, json) {
+                error!("Failed to write summary file {path}: {err}");
+            },
+            None => println!("{json}"),
+        },
+        Err(err) => error!("Failed to serialize summary: {err}"),
+    }
+    let exit_code = summary.exit_code();
+    if exit_code != 0 {
+        std::process::exit(exit_code);
+    }
 }
 
 fn start_in_server_mode(cfg: Arc<Config>, targets: Arc<ProcessTargets>) {
@@ -159,6 +310,19 @@ fn start_in_server_mode(cfg: Arc<Config>, targets: Arc<ProcessTargets>) {
     };
 }
 
+#[cfg(windows)]
+fn start_in_service_mode(cfg: Arc<Config>, targets: Arc<ProcessTargets>) {
+    info!("Service running: http://{}:{}", &cfg.api.host, &cfg.api.port);
+    if let Err(err) = utils::win_service::run_service(cfg, targets) {
+        exit!("Can't start windows service: {err}");
+    }
+}
+
+#[cfg(not(windows))]
+fn start_in_service_mode(_cfg: Arc<Config>, _targets: Arc<ProcessTargets>) {
+    exit!("--service is only supported on windows, use systemd with Type=notify on linux");
+}
+
 fn get_log_level(log_level: &str) -> LevelFilter {
     match log_level.to_lowercase().as_str() {
         "trace" => LevelFilter::Trace,
@@ -170,9 +334,12 @@ fn get_log_level(log_level: &str) -> LevelFilter {
     }
 }
 
-fn init_logger(log_level: &str) {
+fn init_logger(log_level: &str, log_config: &LogConfig) {
     let mut log_builder = Builder::from_default_env();
 
+    for (module, level) in &log_config.modules {
+        log_builder.filter_module(module, get_log_level(level));
+    }
     if log_level.contains('=') {
         for pair in log_level.split(',').filter(|s| s.contains('=')) {
             let mut kv_iter = pair.split('=').map(str::trim);
@@ -186,6 +353,16 @@ fn init_logger(log_level: &str) {
     }
     log_builder.filter_module("actix_web::middleware::logger", LevelFilter::Error);
     log_builder.filter_module("reqwest::async_impl::client", LevelFilter::Error);
+    if log_config.format == LogFormat::Json {
+        log_builder.format(|buf, record| {
+            writeln!(buf, "{}", serde_json::json!({
+                "timestamp": chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+                "level": record.level().as_str(),
+                "target": record.target(),
+                "message": record.args().to_string(),
+            }))
+        });
+    }
     log_builder.init();
     info!("Log Level {}", get_log_level(log_level));
 }
@@ -1,6 +1,6 @@
 use log::{debug, error};
 use reqwest::header;
-use crate::model::config::{MessagingConfig};
+use crate::model::config::{MessagingConfig, WebhookConfig};
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq, Eq)]
 pub enum MsgKind {
@@ -12,6 +12,31 @@ pub enum MsgKind {
     Error,
     #[serde(rename = "watch")]
     Watch,
+    #[serde(rename = "expiry")]
+    Expiry,
+}
+
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize, PartialEq, Eq)]
+pub enum WebhookEvent {
+    #[serde(rename = "refresh_start")]
+    RefreshStart,
+    #[serde(rename = "refresh_success")]
+    RefreshSuccess,
+    #[serde(rename = "refresh_failure")]
+    RefreshFailure,
+    #[serde(rename = "watch")]
+    Watch,
+}
+
+impl WebhookEvent {
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::RefreshStart => "refresh_start",
+            Self::RefreshSuccess => "refresh_success",
+            Self::RefreshFailure => "refresh_failure",
+            Self::Watch => "watch",
+        }
+    }
 }
 
 fn is_enabled(kind: &MsgKind, cfg: &MessagingConfig) -> bool {
@@ -51,3 +76,70 @@ pub fn send_message(kind: &MsgKind, cfg: Option<&MessagingConfig>, msg: &str) {
     }
 }
 
+/// Escapes the value for the use inside a json string.
+fn json_escape(value: &str) -> String {
+    let quoted = serde_json::Value::String(value.to_string()).to_string();
+    quoted[1..quoted.len() - 1].to_string()
+}
+
+fn create_webhook_body(webhook: &WebhookConfig, event: WebhookEvent, target: &str, msg: &str, timestamp: i64) -> String {
+    match &webhook.template {
+        Some(template) => template
+            .replace("{event}", event.as_str())
+            .replace("{target}", &json_escape(target))
+            .replace("{message}", &json_escape(msg))
+            .replace("{timestamp}", &timestamp.to_string()),
+        None => serde_json::json!({
+            "event": event.as_str(),
+            "target": target,
+            "message": msg,
+            "timestamp": timestamp,
+        }).to_string(),
+    }
+}
+
+/// Fires the webhooks registered for the event, `target` is the name of the processed target.
+pub fn send_webhook(event: WebhookEvent, cfg: Option<&MessagingConfig>, target: &str, msg: &str) {
+    let Some(messaging) = cfg else {
+        return;
+    };
+    let timestamp = chrono::Local::now().timestamp();
+    for webhook in messaging.webhooks.iter().filter(|webhook| webhook.events.is_empty() || webhook.events.contains(&event)) {
+        let url = webhook.url.clone();
+        let headers = webhook.headers.clone();
+        let body = create_webhook_body(webhook, event, target, msg, timestamp);
+        actix_rt::spawn(async move {
+            let mut request = reqwest::Client::new().post(&url)
+                .header(header::CONTENT_TYPE, mime::APPLICATION_JSON.to_string());
+            for (key, value) in &headers {
+                request = request.header(key, value);
+            }
+            match request.body(body).send().await {
+                Ok(response) if response.status().is_success() => debug!("Webhook {} sent successfully", event.as_str()),
+                Ok(response) => error!("Webhook {} failed with status {}", event.as_str(), response.status()),
+                Err(e) => error!("Webhook {} wasn't sent because of: {}", event.as_str(), e.without_url()),
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::messaging::{create_webhook_body, WebhookEvent};
+    use crate::model::config::WebhookConfig;
+
+    #[test]
+    fn test_create_webhook_body() {
+        let mut webhook = WebhookConfig { url: String::new(), headers: HashMap::new(), template: None, events: vec![] };
+        let body: serde_json::Value = serde_json::from_str(&create_webhook_body(&webhook, WebhookEvent::RefreshSuccess, "live", "done", 1)).unwrap();
+        assert_eq!(body["event"], "refresh_success");
+        assert_eq!(body["target"], "live");
+
+        webhook.template = Some(r#"{"text": "{target}: {message}", "at": {timestamp}}"#.to_string());
+        let body: serde_json::Value = serde_json::from_str(&create_webhook_body(&webhook, WebhookEvent::Watch, "live", "Changes \"News\"\n", 2)).unwrap();
+        assert_eq!(body["text"], "live: Changes \"News\"\n");
+        assert_eq!(body["at"], 2);
+    }
+}
@@ -63,6 +63,11 @@ pub struct ProxyUserCredentials {
     pub proxy: ProxyType,
     pub server: Option<String>,
     pub epg_timeshift: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parental_pin: Option<String>,
+    /// set for a request which supplied the parental pin
+    #[serde(default, skip_serializing, skip_deserializing)]
+    pub t_adult_unlocked: bool,
 }
 
 impl ProxyUserCredentials {
@@ -73,6 +78,9 @@ impl ProxyUserCredentials {
             if let Some(tkn) = &self.token {
                 self.token = Some(config_reader::resolve_env_var(tkn));
             }
+            if let Some(pin) = &self.parental_pin {
+                self.parental_pin = Some(config_reader::resolve_env_var(pin));
+            }
             self.trim();
         }
     }
@@ -84,6 +92,10 @@ impl ProxyUserCredentials {
         false
     }
 
+    pub fn matches_parental_pin(&self, pin: &str) -> bool {
+        !pin.is_empty() && self.parental_pin.as_ref().is_some_and(|parental_pin| parental_pin.trim().eq(pin))
+    }
+
     pub fn matches(&self, username: &str, password: &str) -> bool {
         self.username.eq(username) && self.password.eq(password)
     }
@@ -103,6 +115,10 @@ impl ProxyUserCredentials {
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct TargetUser {
     pub target: String,
+    /// The users of a scoped target are only accepted under `/t/{target}/`,
+    /// their usernames and tokens only have to be unique within the target.
+    #[serde(default)]
+    pub scoped: bool,
     pub credentials: Vec<ProxyUserCredentials>,
 }
 
@@ -150,6 +166,9 @@ pub struct ApiProxyServerInfo {
     pub rtmp_port: String,
     pub timezone: String,
     pub message: String,
+    /// path prefix of the `api.base_url` behind a reverse proxy, like `/tv`
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub path: String,
 }
 
 impl ApiProxyServerInfo {
@@ -191,6 +210,24 @@ impl ApiProxyServerInfo {
         true
     }
 
+    /// Replaces protocol, host, port and path with the external url of the `api.base_url` config.
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        if let Ok(url) = url::Url::parse(base_url) {
+            if let Some(host) = url.host_str() {
+                self.protocol = url.scheme().to_string();
+                self.host = host.to_string();
+                self.path = url.path().trim_end_matches('/').to_string();
+                let port = url.port_or_known_default().map(|port| port.to_string()).unwrap_or_default();
+                if self.protocol == "https" {
+                    self.https_port = port;
+                } else {
+                    self.http_port = port;
+                }
+            }
+        }
+        self
+    }
+
     pub fn get_base_url(&self) -> String {
         let port = if self.protocol == "https" {
             &self.https_port
@@ -199,9 +236,9 @@ impl ApiProxyServerInfo {
         };
         let base_url = format!("{}://{}", self.protocol, self.host);
         if port.is_empty() {
-            base_url
+            format!("{base_url}{}", self.path)
         } else {
-            format!("{base_url}:{port}")
+            format!("{base_url}:{port}{}", self.path)
         }
     }
 }
@@ -234,21 +271,23 @@ impl ApiProxyConfig {
                 }
             }
         }
+        // users of unscoped targets are resolved without target, they have to be unique over all unscoped targets
+        let mut target_usernames = HashSet::new();
+        let mut target_tokens = HashSet::new();
         for target_user in &mut self.user {
+            let target = target_user.target.to_lowercase();
             for user in &mut target_user.credentials {
                 user.prepare(resolve_var);
-                if usernames.contains(&user.username) {
+                let unique_username = target_usernames.insert((target.clone(), user.username.to_string()))
+                    && (target_user.scoped || usernames.insert(user.username.to_string()));
+                if !unique_username {
                     errors.push(format!("Non unique username found {}", &user.username));
-                } else {
-                    usernames.insert(user.username.to_string());
                 }
                 if let Some(token) = &user.token {
                     if token.is_empty() {
                         user.token = None;
-                    } else if tokens.contains(token) {
+                    } else if !(target_tokens.insert((target.clone(), token.to_string())) && (target_user.scoped || tokens.insert(token.to_string()))) {
                         errors.push(format!("Non unique token found {}", &user.username));
-                    } else {
-                        tokens.insert(token.to_string());
                     }
                 }
 
@@ -276,12 +315,21 @@ impl ApiProxyConfig {
         }
     }
 
+    /// Users of other targets are ignored when a tenant is given, users of scoped targets are ignored without tenant.
+    fn get_tenant_users<'a>(&'a self, tenant: Option<&'a str>) -> impl Iterator<Item=&'a TargetUser> {
+        self.user.iter().filter(move |target_user| match tenant {
+            Some(name) => target_user.target.eq_ignore_ascii_case(name),
+            None => !target_user.scoped,
+        })
+    }
+
     pub fn get_target_name(
         &self,
         username: &str,
         password: &str,
+        tenant: Option<&str>,
     ) -> Option<(ProxyUserCredentials, String)> {
-        for target_user in &self.user {
+        for target_user in self.get_tenant_users(tenant) {
             if let Some((credentials, target_name)) =
                 target_user.get_target_name(username, password)
             {
@@ -291,12 +339,83 @@ impl ApiProxyConfig {
         None
     }
 
-    pub fn get_target_name_by_token(&self, token: &str) -> Option<(ProxyUserCredentials, String)> {
-        for target_user in &self.user {
+    pub fn get_target_name_by_token(&self, token: &str, tenant: Option<&str>) -> Option<(ProxyUserCredentials, String)> {
+        for target_user in self.get_tenant_users(tenant) {
             if let Some((credentials, target_name)) = target_user.get_target_name_by_token(token) {
                 return Some((credentials.clone(), target_name.to_string()));
             };
         }
         None
     }
+
+    pub fn get_user_credentials(&self, username: &str) -> Option<(ProxyUserCredentials, String)> {
+        self.user.iter().find_map(|target_user| target_user.credentials.iter()
+            .find(|credentials| credentials.username.eq(username))
+            .map(|credentials| (credentials.clone(), target_user.target.clone())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::api_proxy::ApiProxyConfig;
+
+    fn create_api_proxy(user: &str) -> ApiProxyConfig {
+        serde_yaml::from_str(&format!(r#"
+server:
+  - {{name: default, protocol: http, host: localhost, timezone: UTC, message: welcome}}
+user:
+{user}
+"#)).unwrap()
+    }
+
+    #[test]
+    fn test_with_base_url() {
+        let server_info = create_api_proxy("  []").server.remove(0);
+        assert_eq!(server_info.clone().with_base_url("https://tv.example.com").get_base_url(), "https://tv.example.com:443");
+        assert_eq!(server_info.clone().with_base_url("https://example.com:8443/tv/").get_base_url(), "https://example.com:8443/tv");
+        assert_eq!(server_info.get_base_url(), "http://localhost:80");
+    }
+
+    #[test]
+    fn test_tenant_users() {
+        let mut api_proxy = create_api_proxy(r#"
+  - target: pl1
+    credentials:
+      - {username: shared, password: one, token: tkn}
+  - target: pl2
+    scoped: true
+    credentials:
+      - {username: shared, password: two, token: tkn}
+"#);
+        api_proxy.prepare(false).unwrap();
+        assert_eq!(api_proxy.get_target_name("shared", "one", None).map(|(_, target)| target), Some("pl1".to_string()));
+        assert_eq!(api_proxy.get_target_name("shared", "one", Some("PL1")).map(|(_, target)| target), Some("pl1".to_string()));
+        assert!(api_proxy.get_target_name("shared", "one", Some("pl2")).is_none());
+        // users of scoped targets are rejected on unscoped routes
+        assert!(api_proxy.get_target_name("shared", "two", None).is_none());
+        assert_eq!(api_proxy.get_target_name("shared", "two", Some("pl2")).map(|(_, target)| target), Some("pl2".to_string()));
+        assert_eq!(api_proxy.get_target_name_by_token("tkn", None).map(|(credentials, _)| credentials.password), Some("one".to_string()));
+        assert_eq!(api_proxy.get_target_name_by_token("tkn", Some("pl2")).map(|(credentials, _)| credentials.password), Some("two".to_string()));
+    }
+
+    #[test]
+    fn test_non_unique_users() {
+        let mut api_proxy = create_api_proxy(r#"
+  - target: pl1
+    credentials:
+      - {username: shared, password: one}
+  - target: pl2
+    credentials:
+      - {username: shared, password: two}
+"#);
+        assert!(api_proxy.prepare(false).is_err());
+        let mut api_proxy = create_api_proxy(r#"
+  - target: pl1
+    scoped: true
+    credentials:
+      - {username: shared, password: one}
+      - {username: shared, password: two}
+"#);
+        assert!(api_proxy.prepare(false).is_err());
+    }
 }
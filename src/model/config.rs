@@ -1,14 +1,17 @@
 #![allow(clippy::struct_excessive_bools)]
 use enum_iterator::Sequence;
 use std::borrow::BorrowMut;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt::Display;
 use std::fs::File;
 use std::io::BufRead;
-use std::path::PathBuf;
+use std::net::{IpAddr, ToSocketAddrs};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::{Arc, RwLock};
+use std::time::Duration;
 
+use crate::auth::role::Role;
 use crate::auth::user::UserCredential;
 use log::{debug, error, warn};
 use path_clean::PathClean;
@@ -16,22 +19,22 @@ use url::Url;
 
 use crate::filter::{get_filter, prepare_templates, Filter, MockValueProcessor, PatternTemplate, ValueProvider};
 use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorKind};
-use crate::messaging::MsgKind;
+use crate::messaging::{MsgKind, WebhookEvent};
 use crate::model::api_proxy::{ApiProxyConfig, ProxyUserCredentials};
 use crate::model::mapping::Mapping;
 use crate::model::mapping::Mappings;
-use crate::utils::default_utils::{default_as_default, default_as_true, default_as_two_u16};
+use crate::model::playlist::{M3uAttribute, XtreamCluster};
+use crate::processing::job_queue::{JobProgress, JobStage};
+use crate::repository::item_cache::ItemCache;
+use crate::utils::request_limiter::RequestLimiter;
+use crate::utils::default_utils::{default_as_default, default_as_true, default_as_two_u16, default_stream_buffer_size, default_health_check_interval, default_health_check_timeout, default_logo_cache_max_age, default_logo_size, default_logo_placeholder_background, default_logo_placeholder_color, default_short_epg_interval, default_short_epg_limit, default_account_check_interval, default_account_expiry_notify_days, default_rate_limit_period, default_rate_limit_requests, default_rate_limit_max_failed_logins, default_rate_limit_ban_duration, default_access_log_path, default_access_log_max_size, default_access_log_max_files, default_watchdog_refresh_timeout, default_post_process_timeout, default_readiness_refresh_sla, default_recording_directory, default_tvheadend_max_streams, default_wasm_plugin_fuel, default_wasm_plugin_max_memory, default_tmdb_language, default_schedules_direct_days, default_schedules_direct_interval, default_tag_pattern_field, default_watch_history_retention_days, default_watch_history_max_entries, default_item_cache_max_entries, default_processing_nice, default_processing_chunk_size, default_http_backoff, default_http_backoff_max, default_shrink_protection_min_percent, default_token_refresh_interval, default_cors_methods, default_cors_max_age};
+use crate::utils::dns_resolver::DohResolver;
 use crate::utils::file_lock_manager::FileLockManager;
-use crate::utils::{config_reader, file_utils};
-
-pub const MAPPER_ATTRIBUTE_FIELDS: &[&str] = &[
-    "name", "title", "group", "id", "chno", "logo",
-    "logo_small", "parent_code", "audio_track",
-    "time_shift", "rec", "url", "epg_channel_id", "epg_id"
-];
+use crate::utils::{config_reader, file_utils, filename_template, request_utils};
 
+pub use m3u_filter_core::field::ItemField;
+pub use m3u_filter_core::m3u::M3uStrictness;
 
-pub const AFFIX_FIELDS: &[&str] = &["name", "title", "group"];
 pub const COUNTER_FIELDS: &[&str] = &["name", "title", "chno"];
 
 #[macro_export]
@@ -91,12 +94,18 @@ pub enum TargetType {
     Xtream,
     #[serde(rename = "strm")]
     Strm,
+    #[serde(rename = "report")]
+    Report,
+    #[serde(rename = "library")]
+    Library,
 }
 
 impl TargetType {
     const M3U: &'static str = "M3u";
     const XTREAM: &'static str = "Xtream";
     const STRM: &'static str = "Strm";
+    const REPORT: &'static str = "Report";
+    const LIBRARY: &'static str = "Library";
 }
 
 impl Display for TargetType {
@@ -105,6 +114,8 @@ impl Display for TargetType {
             Self::M3u => Self::M3U,
             Self::Xtream => Self::XTREAM,
             Self::Strm => Self::STRM,
+            Self::Report => Self::REPORT,
+            Self::Library => Self::LIBRARY,
         })
     }
 }
@@ -148,40 +159,6 @@ impl Display for ProcessingOrder {
     }
 }
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Sequence)]
-pub enum ItemField {
-    #[serde(rename = "group")]
-    Group,
-    #[serde(rename = "name")]
-    Name,
-    #[serde(rename = "title")]
-    Title,
-    #[serde(rename = "url")]
-    Url,
-    #[serde(rename = "type")]
-    Type,
-}
-
-impl ItemField {
-    const GROUP: &'static str = "Group";
-    const NAME: &'static str = "Name";
-    const TITLE: &'static str = "Title";
-    const URL: &'static str = "Url";
-    const TYPE: &'static str = "Type";
-}
-
-impl Display for ItemField {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{}", match *self {
-            Self::Group => Self::GROUP,
-            Self::Name => Self::NAME,
-            Self::Title => Self::TITLE,
-            Self::Url => Self::URL,
-            Self::Type => Self::TYPE,
-        })
-    }
-}
-
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub enum FilterMode {
     #[serde(rename = "discard")]
@@ -204,6 +181,16 @@ pub struct ProcessTargets {
     pub enabled: bool,
     pub inputs: Vec<u16>,
     pub targets: Vec<u16>,
+    /// only the listed inputs are processed, even if they are disabled
+    pub only_inputs: bool,
+    /// the incremental cache is ignored and a shrunk playlist is accepted
+    pub force_refresh: bool,
+    /// the channel counts are printed instead of writing the outputs
+    pub dry_run: bool,
+    /// only the listed clusters are downloaded and processed, the other clusters are taken from the incremental cache
+    pub clusters: Option<Vec<XtreamCluster>>,
+    /// progress and cancel flag of a queued job
+    pub progress: Option<Arc<JobProgress>>,
 }
 
 impl ProcessTargets {
@@ -214,6 +201,22 @@ impl ProcessTargets {
     pub fn has_input(&self, tid: u16) -> bool {
         matches!(self.inputs.iter().position(|&x| x == tid), Some(_pos))
     }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.progress.as_ref().is_some_and(|progress| progress.is_cancelled())
+    }
+
+    pub fn advance(&self, stage: JobStage) {
+        if let Some(progress) = &self.progress {
+            progress.advance(stage);
+        }
+    }
+
+    pub fn job_log(&self, message: &str) {
+        if let Some(progress) = &self.progress {
+            progress.log(message);
+        }
+    }
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -252,6 +255,8 @@ pub struct ConfigSort {
     pub groups: Option<ConfigSortGroup>,
     #[serde(default)]
     pub channels: Option<Vec<ConfigSortChannel>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collation: Option<SortCollation>,
 }
 
 impl ConfigSort {
@@ -259,10 +264,56 @@ impl ConfigSort {
         if let Some(channels) = self.channels.as_mut() {
             handle_m3u_filter_error_result_list!(M3uFilterErrorKind::Info, channels.iter_mut().map(ConfigSortChannel::prepare));
         }
+        if let Some(collation) = self.collation.as_mut() {
+            collation.prepare();
+        }
         Ok(())
     }
 }
 
+/// How the sorted values are compared, for playlists with mixed languages.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Default)]
+pub struct SortCollation {
+    /// language like `de`, the values are compared case and accent insensitive
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locale: Option<String>,
+    /// numbers are compared by value, `Channel 2` before `Channel 10`
+    #[serde(default)]
+    pub numeric: bool,
+    /// leading articles like `The ` are ignored
+    #[serde(default)]
+    pub ignore_articles: bool,
+    /// replaces the articles of the locale
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub articles: Vec<String>,
+    #[serde(skip)]
+    pub t_articles: Vec<String>,
+}
+
+impl SortCollation {
+    fn locale_articles(locale: &str) -> &'static [&'static str] {
+        match locale.split(['-', '_']).next().unwrap_or_default().to_lowercase().as_str() {
+            "de" => &["der", "die", "das"],
+            "fr" => &["le", "la", "les", "l'"],
+            "es" => &["el", "la", "los", "las"],
+            "it" => &["il", "lo", "la", "i", "gli", "le", "l'"],
+            "nl" => &["de", "het", "een"],
+            "pt" => &["o", "a", "os", "as"],
+            _ => &["the", "a", "an"],
+        }
+    }
+
+    fn prepare(&mut self) {
+        self.t_articles = if !self.ignore_articles {
+            vec![]
+        } else if self.articles.is_empty() {
+            Self::locale_articles(self.locale.as_deref().unwrap_or_default()).iter().map(ToString::to_string).collect()
+        } else {
+            self.articles.iter().map(|article| article.trim().to_lowercase()).filter(|article| !article.is_empty()).collect()
+        };
+    }
+}
+
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct ConfigRename {
     pub field: ItemField,
@@ -304,17 +355,476 @@ pub struct ConfigTargetOptions {
     #[serde(default = "default_as_two_u16")]
     pub xtream_resolve_series_delay: u16,
     #[serde(default)]
+    pub xtream_search: bool,
+    #[serde(default)]
     pub m3u_include_type_in_url: bool,
     #[serde(default)]
     pub m3u_mask_redirect_url: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub m3u_attributes: Option<M3uAttributesConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub udpxy_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub xtream_categories: Option<XtreamCategoriesConfig>,
+    /// headers appended to the m3u urls as `|Name=Value&Name=Value` for players like kodi and vlc
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub m3u_url_headers: BTreeMap<String, String>,
+    /// headers sent to the provider when the streams of the target are relayed
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub stream_headers: HashMap<String, String>,
+    #[serde(skip)]
+    pub t_m3u_url_suffix: Option<String>,
+}
+
+impl ConfigTargetOptions {
+    pub fn prepare(&mut self, target_name: &str) -> Result<(), M3uFilterError> {
+        if let Some(m3u_attributes) = self.m3u_attributes.as_mut() {
+            m3u_attributes.prepare(target_name)?;
+        }
+        request_utils::validate_headers(&self.stream_headers)
+            .map_err(|err| M3uFilterError::new(M3uFilterErrorKind::Info, format!("Invalid stream_headers for target {target_name}: {err}")))?;
+        request_utils::validate_headers(&self.m3u_url_headers)
+            .map_err(|err| M3uFilterError::new(M3uFilterErrorKind::Info, format!("Invalid m3u_url_headers for target {target_name}: {err}")))?;
+        self.t_m3u_url_suffix = (!self.m3u_url_headers.is_empty()).then(|| {
+            // players decode the values as url components, where `+` is no space
+            let params: Vec<String> = self.m3u_url_headers.iter()
+                .map(|(name, value)| format!("{name}={}", url::form_urlencoded::byte_serialize(value.as_bytes()).collect::<String>().replace('+', "%20")))
+                .collect();
+            format!("|{}", params.join("&"))
+        });
+        Ok(())
+    }
+}
+
+#[derive(Debug, Copy, Clone, serde::Serialize, serde::Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum M3uAttributePreset {
+    /// all attributes
+    #[default]
+    Default,
+    /// only `tvg-id`, `tvg-name`, `tvg-logo` and `group-title` for players which fail on unknown attributes
+    Minimal,
+    /// the attribute names of the kodi `pvr.iptvsimple` addon
+    Kodi,
+}
+
+impl M3uAttributePreset {
+    fn attributes(self) -> Vec<(M3uAttribute, &'static str)> {
+        match self {
+            Self::Default => M3uAttribute::ALL.iter().map(|attribute| (*attribute, attribute.name())).collect(),
+            Self::Minimal => vec![(M3uAttribute::TvgId, "tvg-id"), (M3uAttribute::TvgName, "tvg-name"),
+                                  (M3uAttribute::TvgLogo, "tvg-logo"), (M3uAttribute::GroupTitle, "group-title")],
+            Self::Kodi => vec![(M3uAttribute::TvgId, "tvg-id"), (M3uAttribute::TvgName, "tvg-name"), (M3uAttribute::TvgLogo, "tvg-logo"),
+                               (M3uAttribute::TvgChno, "tvg-chno"), (M3uAttribute::GroupTitle, "group-title"),
+                               (M3uAttribute::Timeshift, "tvg-shift"), (M3uAttribute::TvgRec, "catchup-days")],
+        }
+    }
+}
+
+/// The EXTINF attributes written by the m3u output and their order.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Default)]
+pub struct M3uAttributesConfig {
+    #[serde(default)]
+    pub preset: M3uAttributePreset,
+    /// attributes in the written order, replaces the attributes of the preset
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub rename: HashMap<String, String>,
+    /// static attributes added to each item
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub custom: BTreeMap<String, String>,
+    #[serde(skip)]
+    pub t_attributes: Vec<(M3uAttribute, String)>,
+}
+
+impl M3uAttributesConfig {
+    pub fn prepare(&mut self, target_name: &str) -> Result<(), M3uFilterError> {
+        let to_attribute = |name: &String| M3uAttribute::from_name(name.trim())
+            .ok_or_else(|| M3uFilterError::new(M3uFilterErrorKind::Info, format!("Unknown m3u attribute {name} for target {target_name}")));
+        let mut attributes: Vec<(M3uAttribute, String)> = if self.include.is_empty() {
+            self.preset.attributes().into_iter().map(|(attribute, name)| (attribute, name.to_string())).collect()
+        } else {
+            self.include.iter().map(|name| to_attribute(name).map(|attribute| (attribute, attribute.name().to_string()))).collect::<Result<_, _>>()?
+        };
+        for name in &self.exclude {
+            let excluded = to_attribute(name)?;
+            attributes.retain(|(attribute, _)| *attribute != excluded);
+        }
+        for (name, new_name) in &self.rename {
+            let renamed = to_attribute(name)?;
+            if new_name.trim().is_empty() {
+                return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "Empty name for m3u attribute {} for target {}", name, target_name);
+            }
+            attributes.iter_mut().filter(|(attribute, _)| *attribute == renamed).for_each(|(_, attr_name)| *attr_name = new_name.trim().to_string());
+        }
+        self.t_attributes = attributes;
+        Ok(())
+    }
+}
+
+/// Order and names of the categories of the xtream output.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Default)]
+pub struct XtreamCategoriesConfig {
+    /// categories listed first in this order, the other categories follow in playlist order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub order: Vec<String>,
+    /// group title to category name, the category keeps its id
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub rename: HashMap<String, String>,
+}
+
+impl XtreamCategoriesConfig {
+    pub fn get_name<'a>(&'a self, title: &'a str) -> &'a str {
+        self.rename.get(title).map_or(title, String::as_str)
+    }
+
+    /// Position of the category in `order`, unlisted categories are sorted last.
+    pub fn get_position(&self, title: &str) -> usize {
+        let name = self.get_name(title);
+        self.order.iter().position(|entry| entry == title || entry == name).unwrap_or(self.order.len())
+    }
+}
+
+/// Use of the channel alias database for the channels of the target.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TargetChannelAliases {
+    /// renames the channels to the canonical name
+    #[serde(default = "default_as_true")]
+    pub rename: bool,
+    /// sets the epg channel id of the alias to channels without one
+    #[serde(default = "default_as_true")]
+    pub epg: bool,
+    /// keeps only the channel with the best quality of each canonical name
+    #[serde(default)]
+    pub dedup: bool,
+}
+
+/// Trakt lists for the movies and series of the target.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TargetTrakt {
+    /// `watchlist` of the authorized user, `user/watchlist` or `user/list-slug`
+    pub lists: Vec<String>,
+    /// keeps only the movies and series in the lists
+    #[serde(default)]
+    pub filter: bool,
+    /// adds the matched lists as `trakt_lists` to the additional properties
+    #[serde(default = "default_as_true")]
+    pub tag: bool,
+}
+
+impl TargetTrakt {
+    fn prepare(&self, target_name: &str) -> Result<(), M3uFilterError> {
+        if self.lists.iter().all(|list| list.trim().is_empty()) {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "trakt lists of target {} are empty", target_name);
+        }
+        Ok(())
+    }
+}
+
+/// Infers a tag from the channel name or the group, the tag is the capture `tag` of the pattern or the `value`.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TagPattern {
+    /// `name` or `group`
+    #[serde(default = "default_tag_pattern_field")]
+    pub field: String,
+    pub pattern: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    #[serde(skip)]
+    pub t_pattern: Option<regex::Regex>,
+}
+
+impl TagPattern {
+    fn new(field: &str, pattern: &str) -> Self {
+        Self { field: field.to_string(), pattern: pattern.to_string(), value: None, t_pattern: None }
+    }
+
+    fn prepare(&mut self, target_name: &str) -> Result<(), M3uFilterError> {
+        if self.field != "name" && self.field != "group" {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "tagging field of target {} must be name or group: {}", target_name, self.field);
+        }
+        let re = regex::Regex::new(&self.pattern)
+            .map_err(|err| M3uFilterError::new(M3uFilterErrorKind::Info, format!("Invalid tagging pattern of target {target_name}: {err}")))?;
+        if self.value.is_none() && !re.capture_names().flatten().any(|name| name == "tag") {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "tagging pattern of target {} needs a capture tag or a value: {}", target_name, self.pattern);
+        }
+        self.t_pattern = Some(re);
+        Ok(())
+    }
+}
+
+/// Country prefixes like `DE: ` or `[DE]` of the channel name and the group.
+const DEFAULT_COUNTRY_PATTERNS: &[(&str, &str)] = &[
+    ("name", r"^\[?(?P<tag>[A-Z]{2})\]?\s*[:|\-]"),
+    ("group", r"^\[?(?P<tag>[A-Z]{2})\]?\s*[:|\-]"),
+];
+
+/// Country and language tags of the channels, usable in filters and group prefixes.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TaggingConfig {
+    /// the prefixes of [`DEFAULT_COUNTRY_PATTERNS`] if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub country: Option<Vec<TagPattern>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub language: Vec<TagPattern>,
+    /// prefix of the group of tagged channels with the placeholders `{country}` and `{language}`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group_prefix: Option<String>,
+}
+
+impl TaggingConfig {
+    pub fn prepare(&mut self, target_name: &str) -> Result<(), M3uFilterError> {
+        let country = self.country.get_or_insert_with(|| DEFAULT_COUNTRY_PATTERNS.iter().map(|(field, pattern)| TagPattern::new(field, pattern)).collect());
+        for pattern in country.iter_mut().chain(self.language.iter_mut()) {
+            pattern.prepare(target_name)?;
+        }
+        Ok(())
+    }
+}
+
+/// Limits the size of the playlist, the channels of the groups with the lowest priority are dropped first.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Default)]
+pub struct SizeBudgetConfig {
+    /// 0 is unlimited
+    #[serde(default)]
+    pub max_channels: usize,
+    /// size of the m3u playlist in bytes, 0 is unlimited
+    #[serde(default)]
+    pub max_bytes: usize,
+    /// group regular expressions in descending priority
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub priority: Vec<String>,
+    #[serde(skip)]
+    pub t_priority: Vec<regex::Regex>,
+}
+
+impl SizeBudgetConfig {
+    pub fn prepare(&mut self, target_name: &str) -> Result<(), M3uFilterError> {
+        if self.max_channels == 0 && self.max_bytes == 0 {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "budget of target {} needs max_channels or max_bytes", target_name);
+        }
+        self.t_priority = self.priority.iter().map(|s| regex::Regex::new(s)).collect::<Result<Vec<_>, _>>()
+            .map_err(|err| M3uFilterError::new(M3uFilterErrorKind::Info, format!("Invalid budget priority regular expression: {err}")))?;
+        Ok(())
+    }
+}
+
+/// Keeps the previous output of a target when the new playlist has less than `min_percent` of the previous channels.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ShrinkProtectionConfig {
+    #[serde(default = "default_shrink_protection_min_percent")]
+    pub min_percent: u8,
+}
+
+impl ShrinkProtectionConfig {
+    pub fn prepare(&self, target_name: &str) -> Result<(), M3uFilterError> {
+        if self.min_percent == 0 || self.min_percent > 100 {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "shrink_protection min_percent of target {} must be between 1 and 100", target_name);
+        }
+        Ok(())
+    }
+}
+
+/// Hides the matching groups and channels of a target every day between `from` and `to` (local time, `HH:MM`).
+/// A window with `to` before `from` spans midnight.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct BlackoutWindow {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub channels: Vec<String>,
+    pub from: String,
+    pub to: String,
+    #[serde(skip)]
+    pub t_from: chrono::NaiveTime,
+    #[serde(skip)]
+    pub t_to: chrono::NaiveTime,
+    #[serde(skip)]
+    pub t_groups: Vec<regex::Regex>,
+    #[serde(skip)]
+    pub t_channels: Vec<regex::Regex>,
+}
+
+impl BlackoutWindow {
+    pub fn prepare(&mut self, target_name: &str) -> Result<(), M3uFilterError> {
+        if self.groups.is_empty() && self.channels.is_empty() {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "blackout window of target {} needs groups or channels", target_name);
+        }
+        for (time, value) in [(&mut self.t_from, &self.from), (&mut self.t_to, &self.to)] {
+            match chrono::NaiveTime::parse_from_str(value.trim(), "%H:%M") {
+                Ok(parsed) => *time = parsed,
+                Err(_) => return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "Invalid blackout time {} for target {}, expected HH:MM", value, target_name),
+            }
+        }
+        for (regexps, patterns) in [(&mut self.t_groups, &self.groups), (&mut self.t_channels, &self.channels)] {
+            *regexps = patterns.iter().map(|s| regex::Regex::new(s)).collect::<Result<Vec<_>, _>>()
+                .map_err(|err| M3uFilterError::new(M3uFilterErrorKind::Info, format!("Invalid blackout regular expression: {err}")))?;
+        }
+        Ok(())
+    }
+
+    pub fn is_active(&self, time: chrono::NaiveTime) -> bool {
+        if self.t_from <= self.t_to {
+            self.t_from <= time && time < self.t_to
+        } else {
+            time >= self.t_from || time < self.t_to
+        }
+    }
+
+    pub fn matches_group(&self, group: &str) -> bool {
+        self.t_groups.iter().any(|re| re.is_match(group))
+    }
+
+    pub fn matches(&self, group: &str, name: &str) -> bool {
+        self.matches_group(group) || self.t_channels.iter().any(|re| re.is_match(name))
+    }
+}
+
+/// The blackout windows of a target which are active at the time of a request.
+#[derive(Debug, Clone, Default)]
+pub struct Blackout {
+    windows: Vec<BlackoutWindow>,
+}
+
+impl Blackout {
+    pub fn is_empty(&self) -> bool {
+        self.windows.is_empty()
+    }
+
+    pub fn is_hidden(&self, group: &str, name: &str) -> bool {
+        self.windows.iter().any(|window| window.matches(group, name))
+    }
+
+    /// Only groups are hidden as categories, channel windows hide the channels of a category.
+    pub fn is_hidden_group(&self, group: &str) -> bool {
+        self.windows.iter().any(|window| window.matches_group(group))
+    }
+
+    /// Identifies the active windows, it changes the `ETag` of the served playlists.
+    pub fn key(&self) -> String {
+        self.windows.iter().map(|window| format!("{}-{}", window.from, window.to)).collect::<Vec<_>>().join(",")
+    }
+}
+
+/// External command which receives the playlist items as json lines on stdin
+/// and writes the modified items to stdout, items which are not written are dropped.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PostProcessConfig {
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// seconds the command may run, it is killed afterward
+    #[serde(default = "default_post_process_timeout")]
+    pub timeout: u64,
+}
+
+impl PostProcessConfig {
+    pub fn prepare(&mut self, target_name: &str) -> Result<(), M3uFilterError> {
+        self.command = config_reader::resolve_env_var(self.command.trim());
+        if self.command.is_empty() {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "post_process command is required for target {}", target_name);
+        }
+        if self.timeout == 0 {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "post_process timeout must be greater than 0 for target {}", target_name);
+        }
+        Ok(())
+    }
+}
+
+/// WASM module which transforms or drops each playlist item, requires the `wasm` feature.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct WasmPluginConfig {
+    pub path: String,
+    /// maximum number of executed instructions of one transform call
+    #[serde(default = "default_wasm_plugin_fuel")]
+    pub fuel: u64,
+    /// maximum linear memory of the module in MB
+    #[serde(default = "default_wasm_plugin_max_memory")]
+    pub max_memory: u32,
+}
+
+impl WasmPluginConfig {
+    pub fn prepare(&mut self, target_name: &str) -> Result<(), M3uFilterError> {
+        if !cfg!(feature = "wasm") {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "wasm_plugins of target {} require m3u-filter built with feature wasm", target_name);
+        }
+        self.path = config_reader::resolve_env_var(self.path.trim());
+        if self.path.is_empty() || self.fuel == 0 || self.max_memory == 0 {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "wasm plugin path, fuel and max_memory are required for target {}", target_name);
+        }
+        Ok(())
+    }
+}
+
+/// Pushes the playlist url of a target into an `IPTV Automatic Network` of `TVHeadend`.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TvheadendConfig {
+    /// base url of tvheadend, e.g. `http://tvheadend:9981`
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    /// name of the iptv automatic network, it is created if it does not exist
+    pub network: String,
+    /// m3u url of the target which tvheadend fetches, e.g. `http://m3u-filter:8901/get.php?username=..&password=..`
+    pub playlist_url: String,
+    /// number of parallel streams tvheadend uses for scanning
+    #[serde(default = "default_tvheadend_max_streams")]
+    pub max_streams: u16,
+    /// force tvheadend to refetch the playlist after each update
+    #[serde(default = "default_as_true")]
+    pub scan: bool,
+}
+
+impl TvheadendConfig {
+    pub fn prepare(&mut self, target_name: &str) -> Result<(), M3uFilterError> {
+        self.url = self.url.trim().trim_end_matches('/').to_string();
+        self.network = self.network.trim().to_string();
+        self.playlist_url = config_reader::resolve_env_var(self.playlist_url.trim());
+        self.username = self.username.as_ref().map(|username| config_reader::resolve_env_var(username));
+        self.password = self.password.as_ref().map(|password| config_reader::resolve_env_var(password));
+        if self.url.is_empty() || self.network.is_empty() || self.playlist_url.is_empty() {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "tvheadend url, network and playlist_url are required for target {}", target_name);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Copy, Clone, serde::Serialize, serde::Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum FilenameSanitize {
+    #[default]
+    Strict,
+    Safe,
+    None,
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct TargetOutput {
     #[serde(alias = "type")]
     pub target: TargetType,
+    /// supports the `{target}` and `{date}` tokens
     #[serde(skip_serializing_if = "Option::is_none")]
     pub filename: Option<String>,
+    /// file path template of the strm files inside the `filename` directory, default is `{group}/{title}`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    #[serde(default)]
+    pub sanitize: FilenameSanitize,
+    /// m3u output writes one playlist per group and the `filename` as index of the group playlists
+    #[serde(default)]
+    pub split_groups: bool,
+}
+
+impl TargetOutput {
+    /// The filename with the resolved tokens.
+    pub fn get_filename(&self, target_name: &str) -> Option<String> {
+        self.filename.as_ref().map(|filename| filename_template::resolve_output_filename(filename, target_name))
+    }
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Default)]
@@ -340,6 +850,43 @@ pub struct ConfigTarget {
     pub processing_order: ProcessingOrder,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub watch: Option<Vec<String>>,
+    /// number of kept playlist snapshots for the diff api, 0 disables snapshots
+    #[serde(default)]
+    pub snapshots: usize,
+    /// reuse the processed result of unchanged inputs from the previous run
+    #[serde(default)]
+    pub incremental: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tvheadend: Option<TvheadendConfig>,
+    /// uploads the outputs to remote destinations after each refresh
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub publish: Option<Vec<TargetPublishConfig>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_process: Option<PostProcessConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wasm_plugins: Option<Vec<WasmPluginConfig>>,
+    /// group regular expressions for adult content, only visible for users with a `parental_pin`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub adult_groups: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub budget: Option<SizeBudgetConfig>,
+    /// the previous output is kept if the playlist shrinks too much
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shrink_protection: Option<ShrinkProtectionConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel_aliases: Option<TargetChannelAliases>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tagging: Option<TaggingConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trakt: Option<TargetTrakt>,
+    /// fields which are converted to latin letters
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transliterate: Option<Vec<ItemField>>,
+    /// daily time windows in which groups or channels are hidden
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blackout: Option<Vec<BlackoutWindow>>,
+    #[serde(default, skip_serializing, skip_deserializing)]
+    pub t_adult_groups: Option<Vec<regex::Regex>>,
     #[serde(default, skip_serializing, skip_deserializing)]
     pub t_watch_re: Option<Vec<regex::Regex>>,
     #[serde(default, skip_serializing, skip_deserializing)]
@@ -355,22 +902,20 @@ impl ConfigTarget {
         if self.output.is_empty() {
             return Err(M3uFilterError::new(M3uFilterErrorKind::Info, format!("Missing output format for {}", self.name)));
         }
-        let mut m3u_cnt = 0;
-        let mut strm_cnt = 0;
-        let mut xtream_cnt = 0;
+        let mut output_types = HashSet::new();
+        let mut filenames = HashSet::new();
         for format in &self.output {
+            if !output_types.insert(&format.target) {
+                return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "Multiple output formats with same type {}: {}", format.target.to_string().to_lowercase(), self.name);
+            }
             match format.target {
-                TargetType::M3u => {
-                    m3u_cnt += 1;
-                }
-                TargetType::Strm => {
-                    strm_cnt += 1;
+                TargetType::M3u => {}
+                TargetType::Strm | TargetType::Report | TargetType::Library => {
                     if format.filename.is_none() {
-                        return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "filename is required for strm type: {}", self.name);
+                        return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "filename is required for {} type: {}", format.target.to_string().to_lowercase(), self.name);
                     }
                 }
                 TargetType::Xtream => {
-                    xtream_cnt += 1;
                     if default_as_default().eq_ignore_ascii_case(&self.name) {
                         return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "unique target name is required for xtream type: {}", self.name);
                     }
@@ -381,50 +926,132 @@ impl ConfigTarget {
                     }
                 }
             }
+            if let Some(filename) = format.filename.as_ref().filter(|_| format.target != TargetType::Xtream) {
+                if !filenames.insert(filename.trim().to_string()) {
+                    return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "Multiple outputs with same filename {}: {}", filename, self.name);
+                }
+            }
         }
 
-        if m3u_cnt > 1 || strm_cnt > 1 || xtream_cnt > 1 {
-            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "Multiple output formats with same type : {}", self.name);
+        if let Some(tvheadend) = self.tvheadend.as_mut() {
+            tvheadend.prepare(&self.name)?;
         }
 
-        if let Some(watch) = &self.watch {
-            let regexps: Result<Vec<regex::Regex>, _> = watch.iter().map(|s| regex::Regex::new(s)).collect();
-            match regexps {
-                Ok(watch_re) => self.t_watch_re = Some(watch_re),
-                Err(err) => {
-                    return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "Invalid watch regular expression: {}", err);
-                }
-            }
+        if let Some(post_process) = self.post_process.as_mut() {
+            post_process.prepare(&self.name)?;
         }
 
-        match get_filter(&self.filter, templates) {
-            Ok(fltr) => {
-                debug!("Filter: {}", fltr);
-                self.t_filter = Some(fltr);
-                if let Some(renames) = self.rename.as_mut() {
-                    handle_m3u_filter_error_result_list!(M3uFilterErrorKind::Info, renames.iter_mut().map(ConfigRename::prepare));
-                }
-                if let Some(sort) = self.sort.as_mut() {
-                    handle_m3u_filter_error_result!(M3uFilterErrorKind::Info, sort.prepare());
-                }
-                Ok(())
+        if let Some(wasm_plugins) = self.wasm_plugins.as_mut() {
+            for wasm_plugin in wasm_plugins {
+                wasm_plugin.prepare(&self.name)?;
             }
-            Err(err) => Err(err),
         }
-    }
 
-    pub fn filter(&self, provider: &ValueProvider) -> bool {
-        let mut processor = MockValueProcessor {};
+        if let Some(options) = self.options.as_mut() {
+            options.prepare(&self.name)?;
+        }
+
+        if let Some(budget) = self.budget.as_mut() {
+            budget.prepare(&self.name)?;
+        }
+
+        if let Some(shrink_protection) = self.shrink_protection.as_ref() {
+            shrink_protection.prepare(&self.name)?;
+        }
+
+        if let Some(tagging) = self.tagging.as_mut() {
+            tagging.prepare(&self.name)?;
+        }
+
+        if let Some(trakt) = self.trakt.as_ref() {
+            trakt.prepare(&self.name)?;
+        }
+
+        if let Some(field) = self.transliterate.iter().flatten().find(|field| !matches!(field, ItemField::Name | ItemField::Title | ItemField::Group)) {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "transliterate supports name, title and group, not {} for target {}", field, self.name);
+        }
+
+        if let Some(blackout) = self.blackout.as_mut() {
+            for window in blackout {
+                window.prepare(&self.name)?;
+            }
+        }
+
+        if let Some(adult_groups) = &self.adult_groups {
+            let regexps: Result<Vec<regex::Regex>, _> = adult_groups.iter().map(|s| regex::Regex::new(s)).collect();
+            match regexps {
+                Ok(adult_re) => self.t_adult_groups = Some(adult_re),
+                Err(err) => {
+                    return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "Invalid adult_groups regular expression: {}", err);
+                }
+            }
+        }
+
+        if let Some(watch) = &self.watch {
+            let regexps: Result<Vec<regex::Regex>, _> = watch.iter().map(|s| regex::Regex::new(s)).collect();
+            match regexps {
+                Ok(watch_re) => self.t_watch_re = Some(watch_re),
+                Err(err) => {
+                    return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "Invalid watch regular expression: {}", err);
+                }
+            }
+        }
+
+        match get_filter(&self.filter, templates) {
+            Ok(fltr) => {
+                debug!("Filter: {}", fltr);
+                self.t_filter = Some(fltr);
+                if let Some(renames) = self.rename.as_mut() {
+                    handle_m3u_filter_error_result_list!(M3uFilterErrorKind::Info, renames.iter_mut().map(ConfigRename::prepare));
+                }
+                if let Some(sort) = self.sort.as_mut() {
+                    handle_m3u_filter_error_result!(M3uFilterErrorKind::Info, sort.prepare());
+                }
+                Ok(())
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Reads the changed files of the `IN_FILE` comparisons of the filter, relative to the config directory.
+    pub fn load_filter_files(&self, config_path: &str) -> Result<(), M3uFilterError> {
+        self.t_filter.as_ref().map_or(Ok(()), |filter| Ok(filter.load_files(Path::new(config_path))?))
+    }
+
+    pub fn filter(&self, provider: &ValueProvider) -> bool {
+        let mut processor = MockValueProcessor {};
         self.t_filter.as_ref().unwrap().filter(provider, &mut processor)
     }
 
-    pub fn get_m3u_filename(&self) -> Option<&String> {
-        for format in &self.output {
-            if format.target == TargetType::M3u {
-                return format.filename.as_ref();
-            }
+    pub fn get_m3u_output(&self) -> Option<&TargetOutput> {
+        self.output.iter().find(|format| format.target == TargetType::M3u)
+    }
+
+    pub fn is_adult_group(&self, group: &str) -> bool {
+        self.t_adult_groups.as_ref().is_some_and(|adult_re| adult_re.iter().any(|re| re.is_match(group)))
+    }
+
+    /// Adult content is hidden if the target has `adult_groups` and the user did not unlock it with the parental pin.
+    pub fn hide_adult_content(&self, user: &ProxyUserCredentials) -> bool {
+        self.t_adult_groups.is_some() && !user.t_adult_unlocked
+    }
+
+    /// Items with a `parent_code` are adult content, they are neither listed nor served if the adult content is hidden for the user.
+    pub fn is_adult_item_hidden(&self, user: &ProxyUserCredentials, parent_code: &str) -> bool {
+        !parent_code.is_empty() && self.hide_adult_content(user)
+    }
+
+    /// The blackout windows which are active now.
+    pub fn get_blackout(&self) -> Blackout {
+        let now = chrono::Local::now().time();
+        Blackout {
+            windows: self.blackout.as_ref().map(|windows| windows.iter().filter(|window| window.is_active(now)).cloned().collect()).unwrap_or_default(),
         }
-        None
+    }
+
+    /// The series episodes are only written by the m3u, strm and library outputs.
+    pub fn has_series_episode_output(&self) -> bool {
+        self.output.iter().any(|format| matches!(format.target, TargetType::M3u | TargetType::Strm | TargetType::Library))
     }
 
     pub fn has_output(&self, tt: &TargetType) -> bool {
@@ -476,11 +1103,17 @@ pub enum InputType {
     M3u,
     #[serde(rename = "xtream")]
     Xtream,
+    #[serde(rename = "stalker")]
+    Stalker,
+    #[serde(rename = "directory")]
+    Directory,
 }
 
 impl InputType {
     const M3U: &'static str = "m3u";
     const XTREAM: &'static str = "xtream";
+    const STALKER: &'static str = "stalker";
+    const DIRECTORY: &'static str = "directory";
 }
 
 impl Display for InputType {
@@ -488,6 +1121,8 @@ impl Display for InputType {
         write!(f, "{}", match self {
             Self::M3u => Self::M3U,
             Self::Xtream => Self::XTREAM,
+            Self::Stalker => Self::STALKER,
+            Self::Directory => Self::DIRECTORY,
         })
     }
 }
@@ -506,6 +1141,15 @@ impl FromStr for InputType {
     }
 }
 
+/// Retention of the persisted input files, the newest file of each download is always kept.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Default)]
+pub struct PersistRetention {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep_last: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_age_days: Option<u32>,
+}
+
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Default)]
 pub struct ConfigInputOptions {
     #[serde(default)]
@@ -514,6 +1158,170 @@ pub struct ConfigInputOptions {
     pub xtream_skip_vod: bool,
     #[serde(default)]
     pub xtream_skip_series: bool,
+    /// for xtream inputs the account is checked before the playlist is downloaded
+    #[serde(default = "default_as_true")]
+    pub xtream_validate_account: bool,
+    #[serde(default)]
+    pub m3u_strictness: M3uStrictness,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Default)]
+pub struct ProxyConfig {
+    /// `http://`, `https://`, `socks5://` or `socks5h://` proxy url
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+}
+
+impl ProxyConfig {
+    pub fn prepare(&mut self) -> Result<(), M3uFilterError> {
+        self.url = config_reader::resolve_env_var(self.url.trim());
+        self.username = self.username.as_ref().map(|username| config_reader::resolve_env_var(username));
+        self.password = self.password.as_ref().map(|password| config_reader::resolve_env_var(password));
+        match Url::parse(&self.url) {
+            Ok(url) if matches!(url.scheme(), "http" | "https" | "socks5" | "socks5h") => {}
+            _ => return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "proxy url should start with http://, https://, socks5:// or socks5h://"),
+        }
+        if self.username.is_some() != self.password.is_some() {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "proxy username and password should be set together");
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Default)]
+pub struct DnsConfig {
+    /// static host overrides, hostname to ip address
+    #[serde(default)]
+    pub hosts: HashMap<String, String>,
+    /// DNS-over-HTTPS server with json api
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub doh_url: Option<String>,
+    #[serde(skip)]
+    pub t_hosts: Vec<(String, IpAddr)>,
+    #[serde(skip)]
+    pub t_doh_resolver: Option<DohResolver>,
+}
+
+impl DnsConfig {
+    pub fn prepare(&mut self) -> Result<(), M3uFilterError> {
+        self.t_hosts = self.hosts.iter().map(|(host, addr)| match addr.trim().parse::<IpAddr>() {
+            Ok(ip) => Ok((host.trim().to_lowercase(), ip)),
+            Err(_) => create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "dns host {host} has an invalid ip address {addr}"),
+        }).collect::<Result<Vec<_>, _>>()?;
+        self.doh_url = self.doh_url.as_ref().map(|url| url.trim().to_string()).filter(|url| !url.is_empty());
+        if let Some(doh_url) = &self.doh_url {
+            if !doh_url.starts_with("https://") {
+                return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "dns doh_url should start with https://");
+            }
+            self.t_doh_resolver = Some(DohResolver::new(doh_url)
+                .map_err(|err| M3uFilterError::new(M3uFilterErrorKind::Info, format!("dns doh_url is invalid: {err}")))?);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Default)]
+pub struct ConfigInputHttp {
+    /// proxy for the requests of the input, overrides the global `proxy`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<ProxyConfig>,
+    #[serde(default)]
+    pub accept_invalid_certs: bool,
+    /// timeout in seconds for playlist and epg downloads, for streams only the connect timeout
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+    /// failed requests, too many requests and server errors are retried
+    #[serde(default)]
+    pub retries: u8,
+    /// milliseconds before the first retry, doubled with each retry
+    #[serde(default = "default_http_backoff")]
+    pub backoff: u64,
+    /// max milliseconds between two retries
+    #[serde(default = "default_http_backoff_max")]
+    pub backoff_max: u64,
+    /// max requests per second to the provider
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requests_per_second: Option<f64>,
+    /// max number of requests sent at the same time, 0 for no limit
+    #[serde(default)]
+    pub max_parallel: u16,
+    #[serde(skip)]
+    pub t_limiter: Option<Arc<RequestLimiter>>,
+}
+
+impl ConfigInputHttp {
+    fn prepare(&mut self) -> Result<(), M3uFilterError> {
+        if let Some(proxy) = self.proxy.as_mut() {
+            proxy.prepare()?;
+        }
+        if self.timeout == Some(0) {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "http timeout should be greater than 0");
+        }
+        if self.requests_per_second.is_some_and(|rate| !rate.is_finite() || rate <= 0.0) {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "http requests_per_second should be greater than 0");
+        }
+        if self.backoff > self.backoff_max {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "http backoff should not be greater than backoff_max");
+        }
+        if self.requests_per_second.is_some() || self.max_parallel > 0 {
+            self.t_limiter = Some(Arc::new(RequestLimiter::new(self.requests_per_second, usize::from(self.max_parallel))));
+        }
+        Ok(())
+    }
+
+    pub const fn get_backoff(&self) -> (Duration, Duration) {
+        (Duration::from_millis(self.backoff), Duration::from_millis(self.backoff_max))
+    }
+}
+
+/// Tokens are not renewed more often to avoid provider bans.
+const MIN_TOKEN_REFRESH_INTERVAL_SECS: u64 = 60;
+
+/// Renews the short-lived token in the stream urls of an input between the refreshes.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ConfigInputTokenRefresh {
+    /// query parameter of the stream urls which holds the token
+    pub param: String,
+    /// token endpoint, the playlist of the input is downloaded if not given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// field of the json response of the token endpoint, the whole response is the token if not given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub json_field: Option<String>,
+    /// seconds between two renewals
+    #[serde(default = "default_token_refresh_interval")]
+    pub interval: u64,
+}
+
+impl ConfigInputTokenRefresh {
+    fn prepare(&mut self, input_type: &InputType) -> Result<(), M3uFilterError> {
+        self.param = self.param.trim().to_string();
+        if self.param.is_empty() {
+            return Err(M3uFilterError::new(M3uFilterErrorKind::Info, "token_refresh param is mandatory".to_string()));
+        }
+        self.url = self.url.take().filter(|url| !url.trim().is_empty());
+        if self.url.is_none() && *input_type != InputType::M3u {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "token_refresh url is mandatory for input type {}", input_type);
+        }
+        if self.interval < MIN_TOKEN_REFRESH_INTERVAL_SECS {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "token_refresh interval must be at least {} seconds", MIN_TOKEN_REFRESH_INTERVAL_SECS);
+        }
+        Ok(())
+    }
+}
+
+/// Alternate url or account of an input, tried in order when the primary fails.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Default)]
+pub struct ConfigInputFailover {
+    pub url: String,
+    /// the credentials of the input are used if not given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
 }
 
 pub struct InputUserInfo {
@@ -530,16 +1338,30 @@ pub struct ConfigInput {
     pub input_type: InputType,
     #[serde(default)]
     pub headers: HashMap<String, String>,
+    #[serde(default)]
     pub url: String,
+    /// xtream provider host, used to build the `url` if no `url` is given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub epg_url: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub username: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub password: Option<String>,
+    /// mac address of the set-top box for stalker portals
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mac: Option<String>,
+    /// glob patterns of the playlists of a directory input, the files are merged in the order of the patterns
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub files: Option<Vec<String>>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub persist: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub persist_retention: Option<PersistRetention>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub prefix: Option<InputAffix>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub suffix: Option<InputAffix>,
@@ -549,12 +1371,48 @@ pub struct ConfigInput {
     pub enabled: bool,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub options: Option<ConfigInputOptions>,
-
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http: Option<ConfigInputHttp>,
+    /// group regular expressions, only the matching groups are downloaded and parsed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub include_groups: Option<Vec<String>>,
+    /// group regular expressions, the matching groups are dropped while parsing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exclude_groups: Option<Vec<String>>,
+    /// headers which are only sent when the streams of the input are relayed
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub stream_headers: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_refresh: Option<ConfigInputTokenRefresh>,
+    /// alternate urls or accounts, tried in order when the playlist can't be downloaded or is empty
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failover: Option<Vec<ConfigInputFailover>>,
+    #[serde(skip)]
+    pub t_http_client: Option<reqwest::Client>,
+    #[serde(skip)]
+    pub t_include_groups: Option<Vec<regex::Regex>>,
+    #[serde(skip)]
+    pub t_exclude_groups: Option<Vec<regex::Regex>>,
 }
 
 impl ConfigInput {
+    /// Builds the xtream url from `host` and `port`, the scheme defaults to `http`.
+    fn get_xtream_host_url(&self) -> Option<String> {
+        let host = self.host.as_ref().map(|host| host.trim().trim_end_matches('/')).filter(|host| !host.is_empty())?;
+        let url = if host.contains("://") { host.to_string() } else { format!("http://{host}") };
+        Some(match self.port {
+            Some(port) => format!("{url}:{port}"),
+            None => url,
+        })
+    }
+
     pub fn prepare(&mut self, id: u16) -> Result<(), M3uFilterError> {
         self.id = id;
+        if self.url.trim().is_empty() && self.input_type == InputType::Xtream {
+            if let Some(url) = self.get_xtream_host_url() {
+                self.url = url;
+            }
+        }
         if self.url.trim().is_empty() {
             return Err(M3uFilterError::new(M3uFilterErrorKind::Info, "url for input is mandatory".to_string()));
         }
@@ -568,112 +1426,962 @@ impl ConfigInput {
                 self.password = None;
             }
         }
-        match self.input_type {
-            InputType::M3u => {
-                if self.username.is_some() || self.password.is_some() {
-                    debug!("for input type m3u: username and password are ignored");
+        if self.input_type == InputType::M3u && self.url.parse::<Url>().is_err() && Path::new(&self.url).is_dir() {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "url {} of m3u input is a directory, use input type directory", self.url);
+        }
+        match self.input_type {
+            InputType::M3u | InputType::Directory => {
+                if self.username.is_some() || self.password.is_some() {
+                    debug!("for input type {}: username and password are ignored", self.input_type);
+                }
+            }
+            InputType::Xtream => {
+                if self.username.is_none() || self.password.is_none() {
+                    return Err(M3uFilterError::new(M3uFilterErrorKind::Info, "for input type xtream: username and password are mandatory".to_string()));
+                }
+            }
+            InputType::Stalker => {
+                let mac = self.mac.as_ref().map(|mac| mac.trim().to_uppercase()).unwrap_or_default();
+                let valid = mac.split(':').count() == 6 && mac.split(':').all(|part| part.len() == 2 && part.chars().all(|c| c.is_ascii_hexdigit()));
+                if !valid {
+                    return Err(M3uFilterError::new(M3uFilterErrorKind::Info, "for input type stalker: mac is mandatory, e.g. 00:1A:79:00:00:00".to_string()));
+                }
+                self.mac = Some(mac);
+            }
+        }
+        if let Some(persist_path) = &self.persist {
+            if persist_path.trim().is_empty() {
+                self.persist = None;
+            }
+        }
+        if let Some(files) = self.files.as_mut() {
+            files.retain(|pattern| !pattern.trim().is_empty());
+            if files.is_empty() {
+                self.files = None;
+            }
+        }
+        if let Some(http) = self.http.as_mut() {
+            http.prepare()?;
+        }
+        request_utils::validate_headers(&self.stream_headers)
+            .map_err(|err| M3uFilterError::new(M3uFilterErrorKind::Info, format!("Invalid stream_headers for input: {err}")))?;
+        if let Some(token_refresh) = self.token_refresh.as_mut() {
+            token_refresh.prepare(&self.input_type)?;
+        }
+        if let Some(failover) = self.failover.as_mut() {
+            for alternate in failover.iter_mut() {
+                alternate.url = alternate.url.trim().to_string();
+                if alternate.url.is_empty() {
+                    return Err(M3uFilterError::new(M3uFilterErrorKind::Info, "url for input failover is mandatory".to_string()));
+                }
+                alternate.username = alternate.username.take().filter(|username| !username.trim().is_empty());
+                alternate.password = alternate.password.take().filter(|password| !password.trim().is_empty());
+            }
+            if failover.is_empty() {
+                self.failover = None;
+            }
+        }
+        for (patterns, regexps) in [(&self.include_groups, &mut self.t_include_groups), (&self.exclude_groups, &mut self.t_exclude_groups)] {
+            if let Some(patterns) = patterns {
+                match patterns.iter().map(|s| regex::Regex::new(s)).collect::<Result<Vec<_>, _>>() {
+                    Ok(group_re) => *regexps = Some(group_re),
+                    Err(err) => return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "Invalid input group regular expression: {}", err),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// `false` if the group is not part of the `include_groups` or part of the `exclude_groups`.
+    pub fn accepts_group(&self, group: &str) -> bool {
+        self.t_include_groups.as_ref().is_none_or(|include_re| include_re.iter().any(|re| re.is_match(group)))
+            && !self.t_exclude_groups.as_ref().is_some_and(|exclude_re| exclude_re.iter().any(|re| re.is_match(group)))
+    }
+
+    /// The input with the url and credentials of the failover at `index`.
+    pub fn get_failover_input(&self, index: usize) -> Option<ConfigInput> {
+        let alternate = self.failover.as_ref()?.get(index)?;
+        let mut input = self.clone();
+        input.url.clone_from(&alternate.url);
+        if alternate.username.is_some() {
+            input.username.clone_from(&alternate.username);
+        }
+        if alternate.password.is_some() {
+            input.password.clone_from(&alternate.password);
+        }
+        input.failover = None;
+        Some(input)
+    }
+
+    pub fn get_user_info(&self) -> Option<InputUserInfo> {
+        if self.input_type == InputType::Xtream {
+            if self.username.is_some() || self.password.is_some() {
+                return Some(InputUserInfo {
+                    base_url: self.url.clone(),
+                    username: self.username.as_ref().unwrap().to_owned(),
+                    password: self.password.as_ref().unwrap().to_owned(),
+                });
+            }
+        } else if let Ok(url) = Url::parse(&self.url) {
+            let base_url = url.origin().ascii_serialization();
+            let mut username = None;
+            let mut password = None;
+            for (key, value) in url.query_pairs() {
+                if key.eq("username") {
+                    username = Some(value.into_owned());
+                } else if key.eq("password") {
+                    password = Some(value.into_owned());
+                }
+            }
+            if username.is_some() || password.is_some() {
+                return Some(InputUserInfo {
+                    base_url,
+                    username: username.as_ref().unwrap().to_owned(),
+                    password: password.as_ref().unwrap().to_owned(),
+                });
+            }
+        }
+        None
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ConfigApiTls {
+    /// addresses of the https listeners like `0.0.0.0:8443` or `[::]:8443`
+    pub listen: Vec<String>,
+    /// pem encoded certificate chain, reloaded when the file changes
+    pub cert: String,
+    /// pem encoded private key
+    pub key: String,
+}
+
+/// Cross-origin requests to the api, the origins can be `*` for any origin.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CorsConfig {
+    #[serde(default = "default_as_true")]
+    pub enabled: bool,
+    /// origins like `https://ui.example.com`, any origin if empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_origins: Vec<String>,
+    #[serde(default = "default_cors_methods")]
+    pub allowed_methods: Vec<String>,
+    /// only allowed with explicit origins
+    #[serde(default)]
+    pub allow_credentials: bool,
+    /// seconds the preflight response is cached
+    #[serde(default = "default_cors_max_age")]
+    pub max_age: usize,
+}
+
+const CORS_METHODS: &[&str] = &["GET", "POST", "PUT", "DELETE", "PATCH", "OPTIONS", "HEAD"];
+
+impl CorsConfig {
+    pub fn prepare(&mut self) -> Result<(), M3uFilterError> {
+        self.allowed_origins = self.allowed_origins.iter().map(|origin| origin.trim().trim_end_matches('/').to_string()).filter(|origin| !origin.is_empty()).collect();
+        if let Some(origin) = self.allowed_origins.iter().find(|origin| *origin != "*" && !Url::parse(origin).is_ok_and(|url| url.has_host())) {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "Invalid cors origin {origin}");
+        }
+        self.allowed_methods = self.allowed_methods.iter().map(|method| method.trim().to_uppercase()).collect();
+        if let Some(method) = self.allowed_methods.iter().find(|method| !CORS_METHODS.contains(&method.as_str())) {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "Invalid cors method {method}");
+        }
+        if self.allow_credentials && self.is_any_origin() {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "Cors allow_credentials needs allowed_origins, credentials are never allowed for any origin");
+        }
+        Ok(())
+    }
+
+    pub fn is_any_origin(&self) -> bool {
+        self.allowed_origins.is_empty() || self.allowed_origins.iter().any(|origin| origin == "*")
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Default)]
+pub struct ConfigApi {
+    pub host: String,
+    pub port: u16,
+    #[serde(default)]
+    pub web_root: String,
+    /// path prefix of the web ui like `/ui`, default is `/`
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub web_path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cors: Option<CorsConfig>,
+    /// additional http addresses like `[::]:8901`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub listen: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls: Option<ConfigApiTls>,
+    /// external url like `https://tv.example.com`, used for the generated urls instead of the default server of the api-proxy config
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_url: Option<String>,
+    /// addresses or networks like `10.0.0.0/8` of reverse proxies whose `X-Forwarded-For` header is trusted
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub trusted_proxies: Vec<String>,
+    #[serde(skip)]
+    pub t_trusted_proxies: Vec<TrustedProxy>,
+}
+
+/// An address or network of a trusted reverse proxy.
+#[derive(Debug, Clone, Copy)]
+pub struct TrustedProxy {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl TrustedProxy {
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - u32::from(self.prefix_len)).unwrap_or(0);
+                u32::from(network) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - u32::from(self.prefix_len)).unwrap_or(0);
+                u128::from(network) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for TrustedProxy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix_len) = value.trim().split_once('/').map_or((value.trim(), None), |(addr, prefix_len)| (addr, Some(prefix_len)));
+        let addr = IpAddr::from_str(addr).map_err(|err| format!("Invalid trusted proxy {value}: {err}"))?;
+        let max_prefix_len = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match prefix_len {
+            Some(prefix_len) => prefix_len.parse::<u8>().ok().filter(|len| *len <= max_prefix_len)
+                .ok_or_else(|| format!("Invalid trusted proxy prefix {value}"))?,
+            None => max_prefix_len,
+        };
+        Ok(Self { addr, prefix_len })
+    }
+}
+
+impl ConfigApi {
+    pub fn prepare(&mut self) -> Result<(), M3uFilterError> {
+        if self.web_root.is_empty() {
+            self.web_root = String::from("./web");
+        }
+        // the prefix is kept without trailing slash, the root is an empty prefix
+        let web_path = self.web_path.trim().trim_matches('/');
+        self.web_path = if web_path.is_empty() { String::new() } else { format!("/{web_path}") };
+        if ["/api", "/auth", "/t"].iter().any(|reserved| self.web_path == *reserved || self.web_path.starts_with(&format!("{reserved}/"))) {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "api web_path {} is reserved", self.web_path);
+        }
+        if let Some(cors) = &mut self.cors {
+            cors.prepare()?;
+        }
+        if let Some(tls) = &self.tls {
+            if tls.listen.is_empty() {
+                return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "api tls listen is empty");
+            }
+            if tls.cert.trim().is_empty() || tls.key.trim().is_empty() {
+                return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "api tls cert and key are required");
+            }
+        }
+        if let Some(addr) = self.listen.iter().chain(self.tls.iter().flat_map(|tls| &tls.listen))
+            .find(|addr| addr.to_socket_addrs().is_err()) {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "Invalid api listen address {addr}");
+        }
+        if let Some(base_url) = &self.base_url {
+            if !Url::parse(base_url).is_ok_and(|url| matches!(url.scheme(), "http" | "https") && url.has_host()) {
+                return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "Invalid api base_url {base_url}");
+            }
+        }
+        self.t_trusted_proxies = self.trusted_proxies.iter().map(|proxy| TrustedProxy::from_str(proxy))
+            .collect::<Result<Vec<_>, _>>().map_err(|err| M3uFilterError::new(M3uFilterErrorKind::Info, err))?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TelegramMessagingConfig {
+    pub bot_token: String,
+    pub chat_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RestMessagingConfig {
+    pub url: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct WebhookConfig {
+    pub url: String,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// json body with the placeholders `{event}`, `{target}`, `{message}` and `{timestamp}`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    /// events which trigger the webhook, all events if empty
+    #[serde(default)]
+    pub events: Vec<WebhookEvent>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Default)]
+pub struct MessagingConfig {
+    #[serde(default)]
+    pub notify_on: Vec<MsgKind>,
+    #[serde(default)]
+    pub telegram: Option<TelegramMessagingConfig>,
+    #[serde(default)]
+    pub rest: Option<RestMessagingConfig>,
+    #[serde(default)]
+    pub webhooks: Vec<WebhookConfig>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Default)]
+pub struct VideoDownloadConfig {
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    #[serde(default)]
+    pub directory: Option<String>,
+    #[serde(default)]
+    pub organize_into_directories: bool,
+    #[serde(default)]
+    pub episode_pattern: Option<String>,
+    #[serde(default, skip_serializing, skip_deserializing)]
+    pub t_re_episode_pattern: Option<regex::Regex>,
+    #[serde(default, skip_serializing, skip_deserializing)]
+    pub t_re_filename: Option<regex::Regex>,
+    #[serde(default, skip_serializing, skip_deserializing)]
+    pub t_re_remove_filename_ending: Option<regex::Regex>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct StreamBufferConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_stream_buffer_size")]
+    pub size: usize,
+}
+
+impl Default for StreamBufferConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            size: default_stream_buffer_size(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Default)]
+pub struct ReverseProxyConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream_buffer: Option<StreamBufferConfig>,
+}
+
+impl ReverseProxyConfig {
+    pub fn prepare(&mut self) -> Result<(), M3uFilterError> {
+        if let Some(stream_buffer) = &self.stream_buffer {
+            if stream_buffer.enabled && stream_buffer.size == 0 {
+                return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "stream buffer size must be greater than 0");
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq, Eq, Default)]
+pub enum HealthCheckMethod {
+    #[serde(rename = "head")]
+    #[default]
+    Head,
+    #[serde(rename = "get")]
+    Get,
+    #[serde(rename = "ffprobe")]
+    Ffprobe,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct HealthCheckConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub method: HealthCheckMethod,
+    /// interval between two checks in seconds
+    #[serde(default = "default_health_check_interval")]
+    pub interval: u64,
+    /// timeout for a single probe in seconds
+    #[serde(default = "default_health_check_timeout")]
+    pub timeout: u64,
+    /// number of randomly chosen channels per target to check, all if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sample_size: Option<usize>,
+    /// path to the ffprobe executable
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ffprobe: Option<String>,
+}
+
+impl HealthCheckConfig {
+    pub fn prepare(&mut self) -> Result<(), M3uFilterError> {
+        if self.interval == 0 {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "health_check interval must be greater than 0");
+        }
+        if self.timeout == 0 {
+            self.timeout = default_health_check_timeout();
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct LogoNormalizeConfig {
+    #[serde(default = "default_logo_size")]
+    pub max_width: u32,
+    #[serde(default = "default_logo_size")]
+    pub max_height: u32,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct LogoPlaceholderConfig {
+    /// path to a ttf/otf font used to render the channel name
+    pub font: String,
+    #[serde(default = "default_logo_size")]
+    pub width: u32,
+    #[serde(default = "default_logo_size")]
+    pub height: u32,
+    #[serde(default = "default_logo_placeholder_background")]
+    pub background: String,
+    #[serde(default = "default_logo_placeholder_color")]
+    pub color: String,
+    #[serde(default, skip_serializing, skip_deserializing)]
+    pub t_background: [u8; 3],
+    #[serde(default, skip_serializing, skip_deserializing)]
+    pub t_color: [u8; 3],
+}
+
+fn parse_hex_color(color: &str) -> Option<[u8; 3]> {
+    let hex = color.trim().strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+    let value = u32::from_str_radix(hex, 16).ok()?;
+    let [_, r, g, b] = value.to_be_bytes();
+    Some([r, g, b])
+}
+
+impl LogoPlaceholderConfig {
+    pub fn prepare(&mut self) -> Result<(), M3uFilterError> {
+        if self.width == 0 || self.height == 0 {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "logo_cache placeholder width and height must be greater than 0");
+        }
+        if !PathBuf::from(&self.font).is_file() {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "logo_cache placeholder font not found: {}", &self.font);
+        }
+        self.t_background = parse_hex_color(&self.background).ok_or_else(|| create_m3u_filter_error!(M3uFilterErrorKind::Info, "invalid logo_cache placeholder background color: {}", &self.background))?;
+        self.t_color = parse_hex_color(&self.color).ok_or_else(|| create_m3u_filter_error!(M3uFilterErrorKind::Info, "invalid logo_cache placeholder color: {}", &self.color))?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct LogoCacheConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// max age in seconds clients are allowed to cache a logo
+    #[serde(default = "default_logo_cache_max_age")]
+    pub max_age: u64,
+    /// convert downloaded logos to png, downscaled to the max dimensions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub normalize: Option<LogoNormalizeConfig>,
+    /// generate logos with the channel name for channels without a logo
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub placeholder: Option<LogoPlaceholderConfig>,
+}
+
+impl LogoCacheConfig {
+    pub fn prepare(&mut self) -> Result<(), M3uFilterError> {
+        if let Some(normalize) = &self.normalize {
+            if normalize.max_width == 0 || normalize.max_height == 0 {
+                return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "logo_cache normalize max_width and max_height must be greater than 0");
+            }
+        }
+        if let Some(placeholder) = &mut self.placeholder {
+            placeholder.prepare()?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads the account info (`exp_date`, `max_connections`) of the xtream inputs.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct AccountCheckConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// interval between two checks in seconds
+    #[serde(default = "default_account_check_interval")]
+    pub interval: u64,
+    /// days before the expiry a notification is sent
+    #[serde(default = "default_account_expiry_notify_days")]
+    pub notify_days: u32,
+}
+
+impl AccountCheckConfig {
+    pub fn prepare(&mut self) -> Result<(), M3uFilterError> {
+        if self.interval == 0 {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "account_check interval must be greater than 0");
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ShortEpgConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// interval between two updates in seconds
+    #[serde(default = "default_short_epg_interval")]
+    pub interval: u64,
+    /// number of programmes requested per channel
+    #[serde(default = "default_short_epg_limit")]
+    pub limit: u32,
+}
+
+impl ShortEpgConfig {
+    pub fn prepare(&mut self) -> Result<(), M3uFilterError> {
+        if self.interval == 0 {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "short_epg interval must be greater than 0");
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TmdbConfig {
+    #[serde(default = "default_as_true")]
+    pub enabled: bool,
+    pub api_key: String,
+    /// language of plot and title, e.g. `de-DE`
+    #[serde(default = "default_tmdb_language")]
+    pub language: String,
+}
+
+impl TmdbConfig {
+    pub fn prepare(&mut self, resolve_var: bool) -> Result<(), M3uFilterError> {
+        if resolve_var {
+            self.api_key = config_reader::resolve_env_var(&self.api_key);
+        }
+        if self.enabled && self.api_key.trim().is_empty() {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "tmdb api_key is required");
+        }
+        Ok(())
+    }
+}
+
+/// Api application of trakt.tv, the account is authorized with `--trakt-login`.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TraktConfig {
+    #[serde(default = "default_as_true")]
+    pub enabled: bool,
+    pub client_id: String,
+    pub client_secret: String,
+}
+
+impl TraktConfig {
+    pub fn prepare(&mut self, resolve_var: bool) -> Result<(), M3uFilterError> {
+        if resolve_var {
+            self.client_id = config_reader::resolve_env_var(&self.client_id);
+            self.client_secret = config_reader::resolve_env_var(&self.client_secret);
+        }
+        if self.enabled && (self.client_id.trim().is_empty() || self.client_secret.trim().is_empty()) {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "trakt client_id and client_secret are required");
+        }
+        Ok(())
+    }
+}
+
+/// Account of the Schedules Direct json api, the guide is merged into the epg of the targets.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SchedulesDirectConfig {
+    #[serde(default = "default_as_true")]
+    pub enabled: bool,
+    pub username: String,
+    pub password: String,
+    /// lineup ids like `USA-NY67791-X`, all lineups of the account if empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub lineups: Vec<String>,
+    /// number of days of the guide
+    #[serde(default = "default_schedules_direct_days")]
+    pub days: u8,
+    /// seconds the downloaded guide is reused
+    #[serde(default = "default_schedules_direct_interval")]
+    pub interval: u64,
+}
+
+impl SchedulesDirectConfig {
+    pub fn prepare(&mut self, resolve_var: bool) -> Result<(), M3uFilterError> {
+        if resolve_var {
+            self.username = config_reader::resolve_env_var(&self.username);
+            self.password = config_reader::resolve_env_var(&self.password);
+        }
+        if self.enabled && (self.username.trim().is_empty() || self.password.is_empty()) {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "schedules_direct username and password are required");
+        }
+        if !(1..=21).contains(&self.days) {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "schedules_direct days must be between 1 and 21");
+        }
+        Ok(())
+    }
+}
+
+/// Database of canonical channel names and their aliases.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Default)]
+pub struct ChannelAliasesConfig {
+    /// yaml or csv file, relative to the config directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+}
+
+/// Per user history of the started movies and episodes for continue-watching rows.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct WatchHistoryConfig {
+    #[serde(default = "default_as_true")]
+    pub enabled: bool,
+    /// days an entry is kept after it was last watched, 0 keeps the entries
+    #[serde(default = "default_watch_history_retention_days")]
+    pub retention_days: u32,
+    /// max number of entries per user, the oldest entries are removed
+    #[serde(default = "default_watch_history_max_entries")]
+    pub max_entries: usize,
+    /// users whose history is not recorded
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude_users: Vec<String>,
+}
+
+impl WatchHistoryConfig {
+    pub fn prepare(&self) -> Result<(), M3uFilterError> {
+        if self.max_entries == 0 {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "watch_history max_entries must be greater than 0");
+        }
+        Ok(())
+    }
+
+    /// `true` if the history of the user is recorded.
+    pub fn is_recorded(&self, username: &str) -> bool {
+        self.enabled && !self.exclude_users.iter().any(|user| user == username)
+    }
+}
+
+/// In-memory cache of the stored items requested by the stream urls.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ItemCacheConfig {
+    #[serde(default = "default_as_true")]
+    pub enabled: bool,
+    /// max number of cached items, the least recently used items are removed
+    #[serde(default = "default_item_cache_max_entries")]
+    pub max_entries: usize,
+    /// max size of the cached items in bytes, 0 for no limit
+    #[serde(default)]
+    pub max_bytes: usize,
+}
+
+impl ItemCacheConfig {
+    pub fn prepare(&self) -> Result<(), M3uFilterError> {
+        if self.enabled && self.max_entries == 0 {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "item_cache max_entries must be greater than 0");
+        }
+        Ok(())
+    }
+}
+
+/// Size of the cpu set of `sched_setaffinity`.
+const MAX_PROCESSING_CPUS: usize = 1024;
+
+/// Priority and cpus of the processing, which runs on its own thread apart from the api server.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ProcessingConfig {
+    /// nice value of the processing threads, from 0 (api priority) to 19 (lowest)
+    #[serde(default = "default_processing_nice")]
+    pub nice: i32,
+    /// cpus the processing threads are bound to, all cpus if empty
+    #[serde(default)]
+    pub cpus: Vec<usize>,
+    /// streams m3u playlists from disk through the processing to the m3u outputs
+    #[serde(default)]
+    pub low_memory: bool,
+    /// max number of items kept in memory in `low_memory` mode
+    #[serde(default = "default_processing_chunk_size")]
+    pub chunk_size: usize,
+}
+
+impl Default for ProcessingConfig {
+    fn default() -> Self {
+        Self { nice: default_processing_nice(), cpus: vec![], low_memory: false, chunk_size: default_processing_chunk_size() }
+    }
+}
+
+impl ProcessingConfig {
+    pub fn prepare(&self) -> Result<(), M3uFilterError> {
+        if !(0..=19).contains(&self.nice) {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "processing nice must be between 0 and 19");
+        }
+        if let Some(cpu) = self.cpus.iter().find(|&&cpu| cpu >= MAX_PROCESSING_CPUS) {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "processing cpu {cpu} must be lower than {MAX_PROCESSING_CPUS}");
+        }
+        if self.low_memory && self.chunk_size == 0 {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "processing chunk_size must be greater than 0");
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Copy, Clone, serde::Serialize, serde::Deserialize, PartialEq, Eq)]
+pub enum PublisherType {
+    #[serde(rename = "sftp")]
+    Sftp,
+    #[serde(rename = "webdav")]
+    Webdav,
+    #[serde(rename = "s3")]
+    S3,
+}
+
+/// A remote destination the outputs of a target are uploaded to after each refresh.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PublisherConfig {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub publisher_type: PublisherType,
+    /// `sftp://host:port`, the webdav base url or the s3 endpoint, e.g. `https://s3.eu-central-1.amazonaws.com`
+    pub url: String,
+    /// user for sftp and webdav, the access key for s3
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    /// password for webdav, the secret key for s3
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bucket: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    /// private key file for sftp
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identity_file: Option<String>,
+}
+
+impl PublisherConfig {
+    pub fn prepare(&mut self, resolve_var: bool) -> Result<(), M3uFilterError> {
+        self.name = self.name.trim().to_string();
+        self.url = self.url.trim().trim_end_matches('/').to_string();
+        if resolve_var {
+            self.url = config_reader::resolve_env_var(&self.url);
+            self.username = self.username.as_ref().map(|username| config_reader::resolve_env_var(username));
+            self.password = self.password.as_ref().map(|password| config_reader::resolve_env_var(password));
+        }
+        if self.name.is_empty() || self.url.is_empty() {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "publisher name and url are required");
+        }
+        match self.publisher_type {
+            PublisherType::Sftp => {
+                if self.username.is_none() || !self.url.starts_with("sftp://") {
+                    return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "sftp publisher {} requires username and url sftp://host:port", self.name);
                 }
             }
-            InputType::Xtream => {
-                if self.username.is_none() || self.password.is_none() {
-                    return Err(M3uFilterError::new(M3uFilterErrorKind::Info, "for input type xtream: username and password are mandatory".to_string()));
+            PublisherType::S3 => {
+                if self.username.is_none() || self.password.is_none() || self.bucket.as_ref().is_none_or(|bucket| bucket.trim().is_empty()) {
+                    return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "s3 publisher {} requires bucket, username (access key) and password (secret key)", self.name);
                 }
             }
+            PublisherType::Webdav => {}
         }
-        if let Some(persist_path) = &self.persist {
-            if persist_path.trim().is_empty() {
-                self.persist = None;
-            }
-        }
+        Ok(())
+    }
+}
+
+/// Uploads the outputs of the target with the publisher `publisher` into the remote directory `path`.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TargetPublishConfig {
+    pub publisher: String,
+    #[serde(default)]
+    pub path: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RateLimitConfig {
+    #[serde(default = "default_as_true")]
+    pub enabled: bool,
+    /// length of the rate limit window in seconds
+    #[serde(default = "default_rate_limit_period")]
+    pub period: u64,
+    /// max requests per client ip and period, 0 disables the limit
+    #[serde(default = "default_rate_limit_requests")]
+    pub ip_limit: u32,
+    /// max requests per user and period, 0 disables the limit
+    #[serde(default = "default_rate_limit_requests")]
+    pub user_limit: u32,
+    /// failed logins per client ip and period until the ip is banned, 0 disables the ban
+    #[serde(default = "default_rate_limit_max_failed_logins")]
+    pub max_failed_logins: u32,
+    /// ban duration in seconds
+    #[serde(default = "default_rate_limit_ban_duration")]
+    pub ban_duration: u64,
+    /// client ips which are never limited or banned
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub whitelist: Vec<String>,
+}
 
+impl RateLimitConfig {
+    pub fn prepare(&mut self) -> Result<(), M3uFilterError> {
+        if self.period == 0 {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "rate_limit period must be greater than 0");
+        }
         Ok(())
     }
+}
 
-    pub fn get_user_info(&self) -> Option<InputUserInfo> {
-        if self.input_type == InputType::Xtream {
-            if self.username.is_some() || self.password.is_some() {
-                return Some(InputUserInfo {
-                    base_url: self.url.clone(),
-                    username: self.username.as_ref().unwrap().to_owned(),
-                    password: self.password.as_ref().unwrap().to_owned(),
-                });
-            }
-        } else if let Ok(url) = Url::parse(&self.url) {
-            let base_url = url.origin().ascii_serialization();
-            let mut username = None;
-            let mut password = None;
-            for (key, value) in url.query_pairs() {
-                if key.eq("username") {
-                    username = Some(value.into_owned());
-                } else if key.eq("password") {
-                    password = Some(value.into_owned());
-                }
-            }
-            if username.is_some() || password.is_some() {
-                return Some(InputUserInfo {
-                    base_url,
-                    username: username.as_ref().unwrap().to_owned(),
-                    password: password.as_ref().unwrap().to_owned(),
-                });
-            }
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct AccessLogConfig {
+    #[serde(default = "default_as_true")]
+    pub enabled: bool,
+    /// log file, relative paths are resolved against the `working_dir`
+    #[serde(default = "default_access_log_path")]
+    pub path: String,
+    /// max size of the log file in bytes before it is rotated
+    #[serde(default = "default_access_log_max_size")]
+    pub max_size: u64,
+    /// number of rotated files to keep
+    #[serde(default = "default_access_log_max_files")]
+    pub max_files: u32,
+}
+
+impl AccessLogConfig {
+    pub fn prepare(&mut self) -> Result<(), M3uFilterError> {
+        if self.path.trim().is_empty() {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "access_log path is empty");
         }
-        None
+        if self.max_size == 0 {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "access_log max_size must be greater than 0");
+        }
+        Ok(())
     }
 }
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Default)]
-pub struct ConfigApi {
-    pub host: String,
-    pub port: u16,
-    #[serde(default)]
-    pub web_root: String,
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct WatchdogConfig {
+    /// seconds a refresh may run before the systemd watchdog keep-alive stops
+    #[serde(default = "default_watchdog_refresh_timeout")]
+    pub refresh_timeout: u64,
 }
 
-impl ConfigApi {
-    pub fn prepare(&mut self) {
-        if self.web_root.is_empty() {
-            self.web_root = String::from("./web");
+impl WatchdogConfig {
+    pub fn prepare(&mut self) -> Result<(), M3uFilterError> {
+        if self.refresh_timeout == 0 {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "watchdog refresh_timeout must be greater than 0");
         }
+        Ok(())
     }
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
-pub struct TelegramMessagingConfig {
-    pub bot_token: String,
-    pub chat_ids: Vec<String>,
+pub struct ReadinessConfig {
+    /// seconds since the last successful refresh until `/readyz` reports not ready, 0 disables the check
+    #[serde(default = "default_readiness_refresh_sla")]
+    pub refresh_sla: u64,
 }
 
+/// Records every programme of the target whose title matches, the channels can be restricted by name.
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
-pub struct RestMessagingConfig {
-    pub url: String,
+pub struct RecordingRule {
+    pub name: String,
+    pub target: String,
+    /// regular expression for the channel name, all channels with epg if omitted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel: Option<String>,
+    /// regular expression for the programme title
+    pub title: String,
+    #[serde(skip_serializing, skip_deserializing)]
+    pub t_re_channel: Option<regex::Regex>,
+    #[serde(skip_serializing, skip_deserializing)]
+    pub t_re_title: Option<regex::Regex>,
 }
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Default)]
-pub struct MessagingConfig {
-    #[serde(default)]
-    pub notify_on: Vec<MsgKind>,
-    #[serde(default)]
-    pub telegram: Option<TelegramMessagingConfig>,
-    #[serde(default)]
-    pub rest: Option<RestMessagingConfig>,
+impl RecordingRule {
+    pub fn prepare(&mut self) -> Result<(), M3uFilterError> {
+        if self.name.trim().is_empty() || self.target.trim().is_empty() {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "recording rule name and target are required");
+        }
+        let compile = |pattern: &str| regex::Regex::new(pattern)
+            .map_err(|_| M3uFilterError::new(M3uFilterErrorKind::Info, format!("cant parse regex: {pattern}")));
+        self.t_re_title = Some(compile(&self.title)?);
+        self.t_re_channel = self.channel.as_deref().map(compile).transpose()?;
+        Ok(())
+    }
+
+    pub fn matches_channel(&self, name: &str) -> bool {
+        self.t_re_channel.as_ref().is_none_or(|re| re.is_match(name))
+    }
+
+    pub fn matches_title(&self, title: &str) -> bool {
+        self.t_re_title.as_ref().is_some_and(|re| re.is_match(title))
+    }
 }
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Default)]
-pub struct VideoDownloadConfig {
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum LogFormat {
+    #[default]
+    #[serde(rename = "text")]
+    Text,
+    /// one json object per line with `timestamp`, `level`, `target` and `message`
+    #[serde(rename = "json")]
+    Json,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct LogConfig {
+    /// the `--log-level` argument and `M3U_FILTER_LOG` take precedence
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub level: Option<String>,
+    /// level per module like `m3u_filter::processing: debug`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub modules: BTreeMap<String, String>,
     #[serde(default)]
-    pub headers: HashMap<String, String>,
+    pub format: LogFormat,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RecordingConfig {
+    /// directory of the recorded `.ts` files, relative paths are resolved against the `working_dir`
+    #[serde(default = "default_recording_directory")]
+    pub directory: String,
+    /// finished recordings older than this are deleted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_age_days: Option<u32>,
+    /// max size of all recordings in MB, the oldest finished recordings are deleted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<u64>,
+    /// seconds the recording starts earlier
     #[serde(default)]
-    pub directory: Option<String>,
+    pub padding_before: u32,
+    /// seconds the recording stops later
     #[serde(default)]
-    pub organize_into_directories: bool,
+    pub padding_after: u32,
+    /// the rules are evaluated against the stored epg of the target
     #[serde(default)]
-    pub episode_pattern: Option<String>,
-    #[serde(default, skip_serializing, skip_deserializing)]
-    pub t_re_episode_pattern: Option<regex::Regex>,
-    #[serde(default, skip_serializing, skip_deserializing)]
-    pub t_re_filename: Option<regex::Regex>,
-    #[serde(default, skip_serializing, skip_deserializing)]
-    pub t_re_remove_filename_ending: Option<regex::Regex>,
+    pub rules: Vec<RecordingRule>,
+}
+
+impl RecordingConfig {
+    pub fn prepare(&mut self) -> Result<(), M3uFilterError> {
+        if self.directory.trim().is_empty() {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "recording directory is required");
+        }
+        if self.max_age_days == Some(0) || self.max_size == Some(0) {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "recording max_age_days and max_size must be greater than 0");
+        }
+        let mut rule_names = HashSet::new();
+        for rule in &mut self.rules {
+            rule.prepare()?;
+            if !rule_names.insert(rule.name.clone()) {
+                return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "recording rule names should be unique: {}", rule.name);
+            }
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Default)]
@@ -720,6 +2428,8 @@ impl VideoConfig {
 pub struct ConfigDto {
     #[serde(default)]
     pub threads: u8,
+    #[serde(default)]
+    pub target_threads: u8,
     pub api: ConfigApi,
     #[serde(default)]
     pub working_dir: String,
@@ -731,6 +2441,46 @@ pub struct ConfigDto {
     pub schedule: Option<String>,
     #[serde(default)]
     pub messaging: Option<MessagingConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reverse_proxy: Option<ReverseProxyConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub health_check: Option<HealthCheckConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logo_cache: Option<LogoCacheConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub short_epg: Option<ShortEpgConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tmdb: Option<TmdbConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trakt: Option<TraktConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedules_direct: Option<SchedulesDirectConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel_aliases: Option<ChannelAliasesConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub watch_history: Option<WatchHistoryConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub publishers: Option<Vec<PublisherConfig>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account_check: Option<AccountCheckConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limit: Option<RateLimitConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub access_log: Option<AccessLogConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub watchdog: Option<WatchdogConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub readiness: Option<ReadinessConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<ProxyConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dns: Option<DnsConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recording: Option<RecordingConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log: Option<LogConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub processing: Option<ProcessingConfig>,
 }
 
 impl ConfigDto {
@@ -755,13 +2505,29 @@ impl ConfigDto {
     }
 }
 
+/// Role of admin users and api tokens, users and tokens without a role are admins.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct WebAuthRole {
+    pub role: Role,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub users: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tokens: Vec<String>,
+}
+
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct WebAuthConfig {
     #[serde(default = "default_as_true")]
     pub enabled: bool,
+    #[serde(default)]
     pub issuer: String,
+    #[serde(default)]
     pub secret: String,
     pub userfile: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tokens: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub roles: Vec<WebAuthRole>,
     #[serde(skip_serializing, skip_deserializing)]
     pub t_users: Option<Vec<UserCredential>>,
 }
@@ -771,10 +2537,15 @@ impl WebAuthConfig {
         if resolve_var {
             self.issuer = config_reader::resolve_env_var(&self.issuer);
             self.secret = config_reader::resolve_env_var(&self.secret);
+            self.tokens = self.tokens.iter().map(|token| config_reader::resolve_env_var(token)).collect();
+            for web_auth_role in &mut self.roles {
+                web_auth_role.tokens = web_auth_role.tokens.iter().map(|token| config_reader::resolve_env_var(token)).collect();
+            }
             if let Some(file) = &self.userfile {
                 self.userfile = Some(config_reader::resolve_env_var(file));
             }
         }
+        let userfile_configured = self.userfile.is_some();
         let userfile_name = self.userfile.as_ref().map_or_else(|| file_utils::get_default_user_file_path(config_path), std::borrow::ToOwned::to_owned);
         self.userfile = Some(userfile_name.clone());
 
@@ -782,6 +2553,10 @@ impl WebAuthConfig {
         if !file_utils::path_exists(&userfile_path) {
             userfile_path = PathBuf::from(config_path).join(&userfile_name);
             if !file_utils::path_exists(&userfile_path) {
+                // api tokens can be used without admin users
+                if !userfile_configured && (!self.tokens.is_empty() || self.roles.iter().any(|web_auth_role| !web_auth_role.tokens.is_empty())) {
+                    return Ok(());
+                }
                 return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "Could not find userfile {}", &userfile_name);
             }
         }
@@ -807,6 +2582,29 @@ impl WebAuthConfig {
         Ok(())
     }
 
+    /// Jwt tokens are only issued if a secret is configured.
+    pub fn is_jwt_enabled(&self) -> bool {
+        !self.secret.is_empty()
+    }
+
+    /// The role of a valid api token, the tokens of `tokens` are admin tokens.
+    pub fn get_api_token_role(&self, token: &str) -> Option<Role> {
+        // blake3 hashes are compared in constant time
+        let token_hash = blake3::hash(token.as_bytes());
+        let matches = |tokens: &[String]| tokens.iter().any(|api_token| !api_token.is_empty() && blake3::hash(api_token.as_bytes()) == token_hash);
+        if matches(&self.tokens) {
+            return Some(Role::Admin);
+        }
+        self.roles.iter().find(|web_auth_role| matches(&web_auth_role.tokens)).map(|web_auth_role| web_auth_role.role)
+    }
+
+    /// The role of a user of the userfile, users without a configured role are admins.
+    pub fn get_user_role(&self, username: &str) -> Role {
+        self.roles.iter()
+            .find(|web_auth_role| web_auth_role.users.iter().any(|user| user.eq_ignore_ascii_case(username)))
+            .map_or(Role::Admin, |web_auth_role| web_auth_role.role)
+    }
+
     pub fn get_user_password(&self, username: &str) -> Option<&str> {
         if let Some(users) = &self.t_users {
             for credential in users {
@@ -823,6 +2621,8 @@ impl WebAuthConfig {
 pub struct Config {
     #[serde(default)]
     pub threads: u8,
+    #[serde(default)]
+    pub target_threads: u8,
     pub api: ConfigApi,
     pub sources: Vec<ConfigSource>,
     pub working_dir: String,
@@ -842,6 +2642,48 @@ pub struct Config {
     pub web_auth: Option<WebAuthConfig>,
     #[serde(default)]
     pub messaging: Option<MessagingConfig>,
+    #[serde(default)]
+    pub reverse_proxy: Option<ReverseProxyConfig>,
+    #[serde(default)]
+    pub health_check: Option<HealthCheckConfig>,
+    #[serde(default)]
+    pub logo_cache: Option<LogoCacheConfig>,
+    #[serde(default)]
+    pub short_epg: Option<ShortEpgConfig>,
+    #[serde(default)]
+    pub tmdb: Option<TmdbConfig>,
+    #[serde(default)]
+    pub trakt: Option<TraktConfig>,
+    #[serde(default)]
+    pub schedules_direct: Option<SchedulesDirectConfig>,
+    #[serde(default)]
+    pub channel_aliases: Option<ChannelAliasesConfig>,
+    #[serde(default)]
+    pub watch_history: Option<WatchHistoryConfig>,
+    #[serde(default)]
+    pub publishers: Option<Vec<PublisherConfig>>,
+    #[serde(default)]
+    pub account_check: Option<AccountCheckConfig>,
+    #[serde(default)]
+    pub rate_limit: Option<RateLimitConfig>,
+    #[serde(default)]
+    pub access_log: Option<AccessLogConfig>,
+    #[serde(default)]
+    pub watchdog: Option<WatchdogConfig>,
+    #[serde(default)]
+    pub readiness: Option<ReadinessConfig>,
+    #[serde(default)]
+    pub proxy: Option<ProxyConfig>,
+    #[serde(default)]
+    pub dns: Option<DnsConfig>,
+    #[serde(default)]
+    pub recording: Option<RecordingConfig>,
+    #[serde(default)]
+    pub log: Option<LogConfig>,
+    #[serde(default)]
+    pub item_cache: Option<ItemCacheConfig>,
+    #[serde(default)]
+    pub processing: Option<ProcessingConfig>,
     #[serde(skip_serializing, skip_deserializing)]
     pub t_api_proxy: Arc<RwLock<Option<ApiProxyConfig>>>,
     #[serde(skip_serializing, skip_deserializing)]
@@ -854,9 +2696,54 @@ pub struct Config {
     pub t_api_proxy_file_path: String,
     #[serde(skip)]
     pub file_locks: Arc<FileLockManager>,
+    #[serde(skip)]
+    pub t_item_cache: Arc<ItemCache>,
+    /// client with the global proxy and dns settings for the provider requests without own input http settings
+    #[serde(skip)]
+    pub t_http_client: Option<reqwest::Client>,
 }
 
 impl Config {
+    /// The max number of items kept in memory if the processing runs in `low_memory` mode.
+    pub fn get_low_memory_chunk_size(&self) -> Option<usize> {
+        self.processing.as_ref().filter(|processing| processing.low_memory).map(|processing| processing.chunk_size)
+    }
+
+    /// The `low_memory` mode streams the m3u playlists from disk to the outputs,
+    /// the features which need the whole playlist in memory can't be used.
+    fn check_low_memory(&self) -> Result<(), M3uFilterError> {
+        if self.get_low_memory_chunk_size().is_none() {
+            return Ok(());
+        }
+        if self.tmdb.is_some() || self.logo_cache.is_some() {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "tmdb and logo_cache are not supported in processing low_memory mode");
+        }
+        for source in &self.sources {
+            if let Some(input) = source.inputs.iter().find(|input| !matches!(input.input_type, InputType::M3u | InputType::Directory)) {
+                return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "{} input {} is not supported in processing low_memory mode",
+                    input.input_type, input.name.as_deref().unwrap_or(&input.url));
+            }
+            for target in &source.targets {
+                let unsupported = [
+                    (target.output.iter().any(|output| output.target != TargetType::M3u), "output other than m3u"),
+                    (target.output.iter().any(|output| output.split_groups), "split_groups"),
+                    (target.watch.is_some(), "watch"),
+                    (target.snapshots > 0, "snapshots"),
+                    (target.incremental, "incremental"),
+                    (target.post_process.is_some(), "post_process"),
+                    (target.wasm_plugins.is_some(), "wasm_plugins"),
+                    (target.budget.is_some(), "budget"),
+                    (target.tagging.is_some(), "tagging"),
+                    (target.trakt.is_some(), "trakt"),
+                ];
+                if let Some((_, feature)) = unsupported.iter().find(|(used, _)| *used) {
+                    return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "{feature} of target {} is not supported in processing low_memory mode", target.name);
+                }
+            }
+        }
+        Ok(())
+    }
+
     pub fn set_api_proxy(&mut self, api_proxy: Option<ApiProxyConfig>) {
         self.t_api_proxy = Arc::new(RwLock::new(api_proxy));
     }
@@ -886,12 +2773,12 @@ impl Config {
         None
     }
 
-    pub fn get_target_for_user(&self, username: &str, password: &str) -> Option<(ProxyUserCredentials, &ConfigTarget)> {
-        self.t_api_proxy.read().unwrap().as_ref().and_then(|api_proxy| self.intern_get_target_for_user(api_proxy.get_target_name(username, password)))
+    pub fn get_target_for_user(&self, username: &str, password: &str, tenant: Option<&str>) -> Option<(ProxyUserCredentials, &ConfigTarget)> {
+        self.t_api_proxy.read().unwrap().as_ref().and_then(|api_proxy| self.intern_get_target_for_user(api_proxy.get_target_name(username, password, tenant)))
     }
 
-    pub fn get_target_for_user_by_token(&self, token: &str) -> Option<(ProxyUserCredentials, &ConfigTarget)> {
-        self.t_api_proxy.read().unwrap().as_ref().and_then(|api_proxy| self.intern_get_target_for_user(api_proxy.get_target_name_by_token(token)))
+    pub fn get_target_for_user_by_token(&self, token: &str, tenant: Option<&str>) -> Option<(ProxyUserCredentials, &ConfigTarget)> {
+        self.t_api_proxy.read().unwrap().as_ref().and_then(|api_proxy| self.intern_get_target_for_user(api_proxy.get_target_name_by_token(token, tenant)))
     }
 
     pub fn get_input_by_id(&self, input_id: u16) -> Option<&ConfigInput> {
@@ -938,15 +2825,74 @@ impl Config {
                 Err(err) => { error!("Could not create backup dir {} {}", self.backup_dir.as_ref().unwrap(), err) }
             }
         }
-        self.api.prepare();
+        self.api.prepare()?;
         self.prepare_api_web_root(resolve_var);
+        if let Some(reverse_proxy) = &mut self.reverse_proxy {
+            reverse_proxy.prepare()?;
+        }
+        if let Some(health_check) = &mut self.health_check {
+            health_check.prepare()?;
+        }
+        if let Some(logo_cache) = &mut self.logo_cache {
+            logo_cache.prepare()?;
+        }
+        if let Some(short_epg) = &mut self.short_epg {
+            short_epg.prepare()?;
+        }
+        if let Some(account_check) = &mut self.account_check {
+            account_check.prepare()?;
+        }
+        if let Some(tmdb) = &mut self.tmdb {
+            tmdb.prepare(resolve_var)?;
+        }
+        if let Some(trakt) = &mut self.trakt {
+            trakt.prepare(resolve_var)?;
+        }
+        if let Some(schedules_direct) = &mut self.schedules_direct {
+            schedules_direct.prepare(resolve_var)?;
+        }
+        let mut publisher_names = HashSet::new();
+        for publisher in self.publishers.iter_mut().flatten() {
+            publisher.prepare(resolve_var)?;
+            if !publisher_names.insert(publisher.name.clone()) {
+                return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "publisher names should be unique: {}", publisher.name);
+            }
+        }
+        if let Some(rate_limit) = &mut self.rate_limit {
+            rate_limit.prepare()?;
+        }
+        if let Some(access_log) = &mut self.access_log {
+            access_log.prepare()?;
+        }
+        if let Some(watch_history) = &self.watch_history {
+            watch_history.prepare()?;
+        }
+        if let Some(item_cache) = &self.item_cache {
+            item_cache.prepare()?;
+        }
+        self.t_item_cache = Arc::new(ItemCache::new(self.item_cache.as_ref()));
+        if let Some(processing) = &self.processing {
+            processing.prepare()?;
+        }
+        if let Some(watchdog) = &mut self.watchdog {
+            watchdog.prepare()?;
+        }
+        if let Some(proxy) = &mut self.proxy {
+            proxy.prepare()?;
+        }
+        if let Some(dns) = &mut self.dns {
+            dns.prepare()?;
+        }
+        if let Some(recording) = &mut self.recording {
+            recording.prepare()?;
+        }
         if let Some(templates) = &mut self.templates {
             match prepare_templates(templates) {
                 Ok(tmplts) => {
                     self.templates = Some(tmplts);
                 }
                 Err(err) => {
-                    return Err(err);
+                    return Err(err.into());
                 }
             }
         };
@@ -975,9 +2921,19 @@ impl Config {
                     _ => target.prepare(target_index, None)
                 };
                 prepare_result?;
+                target.load_filter_files(&self.t_config_path)?;
+                if let Some(publish) = target.publish.iter().flatten().find(|publish| !publisher_names.contains(&publish.publisher)) {
+                    return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "unknown publisher {} for target {}", publish.publisher, target.name);
+                }
                 target_index += 1;
             }
         }
+        self.check_low_memory()?;
+        self.prepare_http_clients()?;
+        if let Some(rule) = self.recording.iter().flat_map(|recording| &recording.rules)
+            .find(|rule| !self.sources.iter().flat_map(|source| &source.targets).any(|target| target.name == rule.target)) {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "unknown target {} for recording rule {}", rule.target, rule.name);
+        }
 
         match &mut self.video {
             None => {
@@ -995,10 +2951,6 @@ impl Config {
             }
         };
 
-        if !self.web_ui_enabled {
-            self.web_auth = None;
-        }
-
         if let Some(web_auth) = &mut self.web_auth {
             if web_auth.enabled {
                 web_auth.prepare(&self.t_config_path, resolve_var)?;
@@ -1010,6 +2962,23 @@ impl Config {
         Ok(())
     }
 
+    /// Creates the http clients for the inputs, inputs without own http settings share the client of the global proxy and dns settings.
+    fn prepare_http_clients(&mut self) -> Result<(), M3uFilterError> {
+        if self.proxy.is_some() || self.dns.is_some() {
+            let client = request_utils::create_http_client(None, self.proxy.as_ref(), self.dns.as_ref())
+                .map_err(|err| M3uFilterError::new(M3uFilterErrorKind::Info, format!("Invalid proxy or dns settings: {err}")))?;
+            self.t_http_client = Some(client);
+        }
+        for input in self.sources.iter_mut().flat_map(|source| source.inputs.iter_mut()) {
+            input.t_http_client = match &input.http {
+                None => self.t_http_client.clone(),
+                Some(http) => Some(request_utils::create_http_client(Some(http), self.proxy.as_ref(), self.dns.as_ref())
+                    .map_err(|err| M3uFilterError::new(M3uFilterErrorKind::Info, format!("Invalid http settings for input {}: {err}", request_utils::mask_sensitive_info(&input.url))))?),
+            };
+        }
+        Ok(())
+    }
+
     fn prepare_api_web_root(&mut self, resolve_var: bool) {
         if !self.api.web_root.is_empty() {
             let web_root = if resolve_var { config_reader::resolve_env_var(&self.api.web_root) } else { self.api.web_root.clone() };
@@ -1080,11 +3049,139 @@ pub fn validate_targets(target_args: Option<&Vec<String>>, sources: &Vec<ConfigS
         enabled,
         inputs,
         targets,
+        only_inputs: false,
+        force_refresh: false,
+        dry_run: false,
+        clusters: None,
+        progress: None,
     })
 }
 
+/// Restricts the processing to the inputs with the given names.
+/// If no target was selected, the enabled targets of their sources are processed.
+pub fn validate_inputs(input_args: Option<&Vec<String>>, sources: &[ConfigSource], process_targets: &mut ProcessTargets) -> Result<(), M3uFilterError> {
+    let Some(user_inputs) = input_args else {
+        return Ok(());
+    };
+    let mut inputs = vec![];
+    let mut targets = vec![];
+    let mut missing_inputs = vec![];
+    for user_input in user_inputs {
+        let mut found = false;
+        for source in sources {
+            for input in source.inputs.iter().filter(|input| input.name.as_ref().is_some_and(|name| name.eq_ignore_ascii_case(user_input))) {
+                found = true;
+                inputs.push(input.id);
+                targets.extend(source.targets.iter().filter(|target| target.enabled).map(|target| target.id));
+            }
+        }
+        if !found {
+            missing_inputs.push(user_input.to_lowercase());
+        }
+    }
+    if !missing_inputs.is_empty() {
+        return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "No input found for {}", missing_inputs.join(", "));
+    }
+    if !process_targets.enabled {
+        process_targets.targets = targets;
+    }
+    process_targets.enabled = true;
+    process_targets.inputs = inputs;
+    process_targets.only_inputs = true;
+    Ok(())
+}
+
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct HealthcheckConfig {
     pub api: ConfigApi,
-}
\ No newline at end of file
+}
+
+/// The `log` section of the config file, it is read before the config to initialize the logger.
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+pub struct LoggingConfig {
+    #[serde(default)]
+    pub log: Option<LogConfig>,
+}
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveTime;
+
+    use crate::auth::role::Role;
+    use crate::model::config::{BlackoutWindow, ConfigTarget, ProcessingConfig, WebAuthConfig};
+
+    fn window(from: &str, to: &str) -> BlackoutWindow {
+        let mut window = BlackoutWindow {
+            groups: vec!["(?i)adult".to_string()],
+            channels: vec![],
+            from: from.to_string(),
+            to: to.to_string(),
+            t_from: NaiveTime::MIN,
+            t_to: NaiveTime::MIN,
+            t_groups: vec![],
+            t_channels: vec![],
+        };
+        window.prepare("test").unwrap();
+        window
+    }
+
+    #[test]
+    fn test_blackout_window() {
+        let time = |value: &str| NaiveTime::parse_from_str(value, "%H:%M").unwrap();
+        let day = window("06:00", "22:00");
+        assert!(day.is_active(time("06:00")));
+        assert!(day.is_active(time("21:59")));
+        assert!(!day.is_active(time("22:00")));
+        assert!(!day.is_active(time("03:00")));
+        let night = window("23:00", "01:00");
+        assert!(night.is_active(time("23:30")));
+        assert!(night.is_active(time("00:30")));
+        assert!(!night.is_active(time("12:00")));
+        assert!(day.matches("Adult Movies", "Channel"));
+        assert!(!day.matches("News", "Adult"));
+    }
+
+    fn prepare_outputs(outputs: &str) -> Result<(), String> {
+        let mut target = ConfigTarget { name: "tv".to_string(), filter: "Group ~ \".*\"".to_string(), output: serde_yaml::from_str(outputs).unwrap(), ..ConfigTarget::default() };
+        target.prepare(1, None).map_err(|err| err.message)
+    }
+
+    #[test]
+    fn test_target_outputs() {
+        assert_eq!(prepare_outputs("[{type: m3u, filename: tv.m3u}, {type: xtream}, {type: strm, filename: strm}, {type: report, filename: report.json}]"), Ok(()));
+        assert!(prepare_outputs("[{type: m3u}, {type: m3u}]").unwrap_err().contains("same type m3u"));
+        assert!(prepare_outputs("[{type: xtream}, {type: xtream}]").unwrap_err().contains("same type xtream"));
+        assert!(prepare_outputs("[{type: strm, filename: a}, {type: strm, filename: b}]").unwrap_err().contains("same type strm"));
+        assert!(prepare_outputs("[{type: m3u, filename: tv}, {type: strm, filename: ' tv'}]").unwrap_err().contains("same filename"));
+        assert!(prepare_outputs("[{type: strm}]").unwrap_err().contains("filename is required"));
+        assert!(prepare_outputs("[]").unwrap_err().contains("Missing output"));
+    }
+
+    #[test]
+    fn test_processing_config() {
+        let processing: ProcessingConfig = serde_yaml::from_str("cpus: [0, 1]").unwrap();
+        assert_eq!(processing.nice, 10);
+        assert!(processing.prepare().is_ok());
+        assert!(ProcessingConfig { nice: 20, ..ProcessingConfig::default() }.prepare().is_err());
+        assert!(ProcessingConfig { cpus: vec![4096], ..ProcessingConfig::default() }.prepare().is_err());
+    }
+
+    #[test]
+    fn test_web_auth_roles() {
+        let web_auth: WebAuthConfig = serde_yaml::from_str(r"
+tokens: [admin-token]
+roles:
+  - role: viewer
+    users: [guest]
+    tokens: [viewer-token]
+  - role: operator
+    tokens: [operator-token]
+").unwrap();
+        assert_eq!(web_auth.get_api_token_role("admin-token"), Some(Role::Admin));
+        assert_eq!(web_auth.get_api_token_role("viewer-token"), Some(Role::Viewer));
+        assert_eq!(web_auth.get_api_token_role("operator-token"), Some(Role::Operator));
+        assert_eq!(web_auth.get_api_token_role("unknown"), None);
+        assert_eq!(web_auth.get_user_role("Guest"), Role::Viewer);
+        assert_eq!(web_auth.get_user_role("admin"), Role::Admin);
+    }
+}
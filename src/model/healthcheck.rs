@@ -3,4 +3,19 @@ pub struct Healthcheck {
     pub status: String,
     pub version: String,
     pub time: String,
-}
\ No newline at end of file
+}
+
+/// Result of one dependency check of `/readyz`.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ReadinessCheck {
+    pub ok: bool,
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Readiness {
+    pub status: String,
+    pub config: ReadinessCheck,
+    pub storage: ReadinessCheck,
+    pub refresh: ReadinessCheck,
+}
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde_json::Value;
+
+use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorKind};
+use crate::utils::csv_utils::parse_csv;
+
+/// A channel of the lineup, matched by `tvg-id` or by name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LineupEntry {
+    pub id: Option<String>,
+    pub name: Option<String>,
+    pub chno: Option<String>,
+    pub group: Option<String>,
+}
+
+/// Canonical channel numbers and groups maintained in a csv or json file.
+#[derive(Debug, Clone, Default)]
+pub struct Lineup {
+    entries: Vec<LineupEntry>,
+    by_id: HashMap<String, usize>,
+    by_name: HashMap<String, usize>,
+}
+
+fn normalize_name(name: &str) -> String {
+    name.trim().to_lowercase()
+}
+
+fn non_empty(value: Option<&String>) -> Option<String> {
+    value.map(|text| text.trim()).filter(|text| !text.is_empty()).map(ToString::to_string)
+}
+
+impl LineupEntry {
+    /// The columns `tvg-id` (or `id`), `name`, `chno` and `group`, unknown columns are ignored.
+    fn from_row(row: &HashMap<String, String>) -> Self {
+        Self {
+            id: non_empty(row.get("tvg-id").or_else(|| row.get("id"))),
+            name: non_empty(row.get("name")),
+            chno: non_empty(row.get("chno")),
+            group: non_empty(row.get("group")),
+        }
+    }
+}
+
+/// A list of objects, numbers are accepted as channel numbers.
+fn parse_json(content: &str) -> Result<Vec<HashMap<String, String>>, String> {
+    let rows: Vec<serde_json::Map<String, Value>> = serde_json::from_str(content).map_err(|err| err.to_string())?;
+    Ok(rows.into_iter().map(|row| row.into_iter().filter_map(|(key, value)| {
+        let text = match value {
+            Value::String(text) => text,
+            Value::Number(number) => number.to_string(),
+            _ => return None,
+        };
+        Some((key.to_lowercase(), text))
+    }).collect()).collect())
+}
+
+impl Lineup {
+    pub fn new(entries: Vec<LineupEntry>) -> Self {
+        let mut by_id = HashMap::new();
+        let mut by_name = HashMap::new();
+        for (index, entry) in entries.iter().enumerate() {
+            if let Some(id) = &entry.id {
+                by_id.entry(id.clone()).or_insert(index);
+            }
+            if let Some(name) = &entry.name {
+                by_name.entry(normalize_name(name)).or_insert(index);
+            }
+        }
+        Self { entries, by_id, by_name }
+    }
+
+    /// Reads a `.json` file or a csv file.
+    pub fn from_file(path: &Path) -> Result<Self, M3uFilterError> {
+        let error = |message: String| M3uFilterError::new(M3uFilterErrorKind::Info, format!("cant read lineup file {}: {message}", path.display()));
+        let content = std::fs::read_to_string(path).map_err(|err| error(err.to_string()))?;
+        let is_json = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+        let rows = if is_json { parse_json(&content).map_err(error)? } else { parse_csv(&content) };
+        Ok(Self::new(rows.iter().map(LineupEntry::from_row).collect()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// The entry with the `tvg-id` of the channel, the entry with the channel name otherwise (case-insensitive).
+    pub fn get(&self, epg_channel_id: Option<&str>, name: &str) -> Option<&LineupEntry> {
+        epg_channel_id.and_then(|id| self.by_id.get(id))
+            .or_else(|| self.by_name.get(&normalize_name(name)))
+            .map(|&index| &self.entries[index])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::lineup::{parse_json, Lineup, LineupEntry};
+    use crate::utils::csv_utils::parse_csv;
+
+    #[test]
+    fn test_lineup_get() {
+        let csv = "\u{feff}name;tvg-id;chno;group\n\"News; World\";news.us;1;News\nSport;;\"2\";\n";
+        let lineup = Lineup::new(parse_csv(csv).iter().map(LineupEntry::from_row).collect());
+        assert_eq!(lineup.len(), 2);
+        let news = lineup.get(Some("news.us"), "CNN").unwrap();
+        assert_eq!((news.chno.as_deref(), news.group.as_deref()), (Some("1"), Some("News")));
+        assert_eq!(lineup.get(None, "news; world"), Some(news));
+        let sport = lineup.get(Some("sport.us"), " SPORT ").unwrap();
+        assert_eq!((sport.chno.as_deref(), sport.group.as_deref()), (Some("2"), None));
+        assert!(lineup.get(None, "Movies").is_none());
+
+        let json = r#"[{"tvg-id": "news.us", "chno": 101}, {"name": "Sport", "chno": "102", "group": "Sport"}]"#;
+        let lineup = Lineup::new(parse_json(json).unwrap().iter().map(LineupEntry::from_row).collect());
+        assert_eq!(lineup.get(Some("news.us"), "").and_then(|entry| entry.chno.as_deref()), Some("101"));
+        assert_eq!(lineup.get(None, "sport").and_then(|entry| entry.group.as_deref()), Some("Sport"));
+    }
+}
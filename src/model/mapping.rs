@@ -1,35 +1,20 @@
-use std::borrow::Cow;
-use std::cell::RefCell;
-use std::collections::HashMap;
 use std::fmt::Display;
-use std::rc::Rc;
+use std::path::Path;
 use std::str::FromStr;
 use std::sync::{Arc};
 use std::sync::atomic::AtomicU32;
 use enum_iterator::Sequence;
-use log::{debug, error, trace};
+use log::debug;
 use regex::Regex;
 
-use crate::filter::{apply_templates_to_pattern, get_filter, prepare_templates, Filter, PatternTemplate, RegexWithCaptures, ValueProcessor};
+pub use m3u_filter_core::mapper::{Mapper, MappingTag};
+use m3u_filter_core::filter::apply_templates_to_pattern;
+use crate::filter::{get_filter, prepare_templates, Filter, PatternTemplate};
 use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorKind};
-use crate::model::config::{ItemField, AFFIX_FIELDS, COUNTER_FIELDS, MAPPER_ATTRIBUTE_FIELDS};
-use crate::model::playlist::{FieldAccessor, PlaylistItem};
-use crate::utils::string_utils::Capitalize;
+use crate::model::lineup::Lineup;
+use crate::model::config::COUNTER_FIELDS;
 use crate::{create_m3u_filter_error_result, handle_m3u_filter_error_result, valid_property};
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Default)]
-pub struct MappingTag {
-    pub name: String,
-    pub captures: Vec<String>,
-    #[serde(default)]
-    pub concat: String,
-    #[serde(default)]
-    pub prefix: String,
-    #[serde(default)]
-    pub suffix: String,
-}
-
-
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Sequence, PartialEq, Eq)]
 pub enum CounterModifier {
     #[serde(rename = "assign")]
@@ -99,333 +84,50 @@ pub struct MappingCounter {
     pub value: Arc<AtomicU32>,
 }
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Sequence, PartialEq, Eq)]
-pub enum TransformModifier {
-    #[serde(rename = "lowercase")]
-    Lowercase,
-    #[serde(rename = "uppercase")]
-    Uppercase,
-    #[serde(rename = "capitalize")]
-    Capitalize,
-}
-
-impl Default for TransformModifier {
-    fn default() -> Self {
-        Self::Lowercase
-    }
-}
-
-impl TransformModifier {
-    const LOWERCASE: &'static str = "lowercase";
-    const UPPERCASE: &'static str = "uppercase";
-    const CAPITALIZE: &'static str = "capitalize";
-}
-
-impl Display for TransformModifier {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", match self {
-            Self::Lowercase => Self::LOWERCASE,
-            Self::Uppercase => Self::UPPERCASE,
-            Self::Capitalize => Self::CAPITALIZE,
-        })
-    }
-}
-
-impl FromStr for TransformModifier {
-    type Err = M3uFilterError;
-
-    fn from_str(s: &str) -> Result<Self, M3uFilterError> {
-        if s.eq("lowercase") {
-            Ok(Self::Lowercase)
-        } else if s.eq("uppercase") {
-            Ok(Self::Uppercase)
-        } else if s.eq("capitalize") {
-            Ok(Self::Capitalize)
-        } else {
-            create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "Unknown TransformModifier: {}", s)
-        }
-    }
+fn compile_group_regex(pattern: &str, templates: Option<&Vec<PatternTemplate>>) -> Result<Regex, M3uFilterError> {
+    let new_pattern = templates.map_or_else(|| pattern.to_string(), |template_list| apply_templates_to_pattern(pattern, template_list));
+    Regex::new(&new_pattern).map_err(|_| M3uFilterError::new(M3uFilterErrorKind::Info, format!("cant parse regex: {new_pattern}")))
 }
 
+/// Moves the channels of all groups matching one of the `groups` regular expressions into the group `name`.
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
-pub struct MapperTransform {
-    pub field: String,
-    pub modifier: TransformModifier,
-    pub pattern: Option<String>,
+pub struct GroupMerge {
+    pub groups: Vec<String>,
+    pub name: String,
     #[serde(skip_serializing, skip_deserializing)]
-    pub t_pattern: Option<Regex>,
+    pub t_groups: Vec<Regex>,
 }
 
-impl MapperTransform {
+impl GroupMerge {
     pub fn prepare(&mut self, templates: Option<&Vec<PatternTemplate>>) -> Result<(), M3uFilterError> {
-        match &self.pattern {
-            None => self.t_pattern = None,
-            Some(pattern) => {
-                let mut new_pattern = pattern.to_string();
-                match templates {
-                    None => {}
-                    Some(template_list) => {
-                        new_pattern = apply_templates_to_pattern(pattern, template_list);
-                    }
-                }
-                let re = Regex::new(&new_pattern);
-                if re.is_err() {
-                    return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "cant parse regex: {}", new_pattern);
-                }
-                self.t_pattern = Some(re.unwrap());
-            }
-        }
+        self.t_groups = self.groups.iter().map(|group| compile_group_regex(group, templates)).collect::<Result<Vec<Regex>, M3uFilterError>>()?;
         Ok(())
     }
 }
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Default)]
-pub struct Mapper {
-    pub filter: Option<String>,
+/// Channels whose name matches `pattern` are moved into the group `name`,
+/// `name` can contain the captures of the pattern like `$1` or `${league}`.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct GroupSplitRule {
     pub pattern: String,
-    #[serde(default)]
-    attributes: HashMap<String, String>,
-    #[serde(default)]
-    suffix: HashMap<String, String>,
-    #[serde(default)]
-    prefix: HashMap<String, String>,
-    #[serde(default)]
-    assignments: HashMap<String, String>,
-    #[serde(default)]
-    transform: Option<Vec<MapperTransform>>,
-    #[serde(skip_serializing, skip_deserializing)]
-    pub t_filter: Option<Filter>,
-    #[serde(skip_serializing, skip_deserializing)]
-    pub t_pattern: Option<Filter>,
-    #[serde(skip_serializing, skip_deserializing)]
-    t_tags: Vec<MappingTag>,
-    #[serde(skip_serializing, skip_deserializing)]
-    t_tagre: Option<Regex>,
+    pub name: String,
     #[serde(skip_serializing, skip_deserializing)]
-    t_attre: Option<Regex>,
-}
-
-impl Mapper {
-    pub fn prepare(&mut self, templates: Option<&Vec<PatternTemplate>>, tags: Option<&Vec<MappingTag>>) -> Result<(), M3uFilterError> {
-        for key in self.attributes.keys() {
-            if !valid_property!(key.as_str(), MAPPER_ATTRIBUTE_FIELDS) {
-                return Err(M3uFilterError::new(M3uFilterErrorKind::Info, format!("Invalid mapper attribute field {key}")));
-            }
-        }
-        for key in self.suffix.keys() {
-            if !valid_property!(key.as_str(), AFFIX_FIELDS) {
-                return Err(M3uFilterError::new(M3uFilterErrorKind::Info, format!("Invalid mapper suffix field {key}")));
-            }
-        }
-        for key in self.prefix.keys() {
-            if !valid_property!(key.as_str(), AFFIX_FIELDS) {
-                return Err(M3uFilterError::new(M3uFilterErrorKind::Info, format!("Invalid mapper prefix field {key}")));
-            }
-        }
-        for (key, value) in &self.assignments {
-            if !valid_property!(key.as_str(), MAPPER_ATTRIBUTE_FIELDS) {
-                return Err(M3uFilterError::new(M3uFilterErrorKind::Info, format!("Invalid mapper assignment field {key}")));
-            }
-            if !valid_property!(value.as_str(), MAPPER_ATTRIBUTE_FIELDS) {
-                return Err(M3uFilterError::new(M3uFilterErrorKind::Info, format!("Invalid mapper assignment field {value}")));
-            }
-        }
-
-        match &mut self.transform {
-            None => {}
-            Some(transforms) => {
-                for t in transforms {
-                    let field = t.field.as_str();
-                    if !valid_property!(field, AFFIX_FIELDS) {
-                        return Err(M3uFilterError::new(M3uFilterErrorKind::Info, format!("Invalid mapper transform field {field}")));
-                    }
-                    t.prepare(templates)?;
-                }
-            }
-        }
-
-        match get_filter(&self.pattern, templates) {
-            Ok(pattern) => {
-                self.t_pattern = Some(pattern);
-                match &self.filter {
-                    Some(flt) => {
-                        match get_filter(flt, templates) {
-                            Ok(filter) => self.t_filter = Some(filter),
-                            Err(err) => return Err(err),
-                        }
-                    }
-                    _ => self.t_filter = None
-                }
-                self.t_tags = tags.map_or_else(std::vec::Vec::new, std::clone::Clone::clone);
-                self.t_tagre = Some(Regex::new("<tag:(.*?)>").unwrap());
-                self.t_attre = Some(Regex::new("<(.*?)>").unwrap());
-                Ok(())
-            }
-            Err(err) => Err(err)
-        }
-    }
-}
-
-pub struct MappingValueProcessor<'a> {
-    pub pli: RefCell<&'a PlaylistItem>,
-    pub mapper: &'a Mapper,
+    pub t_pattern: Option<Regex>,
 }
 
-impl MappingValueProcessor<'_> {
-    fn get_property(&self, key: &str) -> Option<Rc<String>> {
-        self.pli.borrow().header.borrow().get_field(key)
-    }
-
-    fn set_property(&self, key: &str, value: &str) {
-        if !self.pli.borrow().header.borrow_mut().set_field(key, value) {
-            error!("Cant set unknown field {} to {}", key, value);
-        }
-        trace!("Property {} set to {}", key, value);
-    }
-
-    fn apply_attributes(&self, captured_names: &HashMap<&str, &str>) {
-        let mapper = self.mapper;
-        let attr_re = &mapper.t_attre.as_ref().unwrap();
-        let attributes = &mapper.attributes;
-        for (key, value) in attributes {
-            if value.contains('<') { // possible replacement
-                let replaced = attr_re.replace_all(value, |captures: &regex::Captures| {
-                    let capture_name = &captures[1];
-                    (*captured_names.get(&capture_name).unwrap_or(&&captures[0])).to_string()
-                });
-                self.set_property(key, &replaced);
-            } else {
-                self.set_property(key, value);
-            }
-        }
-    }
-
-    fn apply_tags(&self, value: &String, captures: &HashMap<&str, &str>) -> Option<String> {
-        let mut new_value = String::from(value);
-        let tag_captures = self.mapper.t_tagre.as_ref().unwrap().captures_iter(value)
-            .filter(|caps| caps.len() > 1)
-            .filter_map(|caps| caps.get(1))
-            .map(|caps| caps.as_str())
-            .collect::<Vec<&str>>();
-
-        for tag_capture in tag_captures {
-            for mapping_tag in &self.mapper.t_tags {
-                if mapping_tag.name.eq(tag_capture) {
-                    // we have the right tag, now get all captured values
-                    let mut captured_tag_values: Vec<&str> = Vec::new();
-                    for cap in &mapping_tag.captures {
-                        if let Some(cap_value) = captures.get(cap.as_str()) {
-                            captured_tag_values.push(cap_value);
-                        } else {
-                            debug!("Cant find any tag match for {}", tag_capture);
-                            return None;
-                        }
-                    }
-                    if !captured_tag_values.is_empty() {
-                        let captured_text = captured_tag_values.join(&mapping_tag.concat);
-                        let replacement = if captured_text.trim().is_empty() {
-                            // nothing found so replace tag with empty string
-                            String::new()
-                        } else {
-                            // Now we have all our captured values, lets create the tag
-                            format!("{}{captured_text}{}", &mapping_tag.prefix, &mapping_tag.suffix)
-                        };
-                        new_value = new_value.replace(format!("<tag:{}>", mapping_tag.name).as_str(), replacement.as_str());
-                    }
-                }
-            }
-        }
-        Some(new_value)
-    }
-
-    fn apply_suffix(&self, captures: &HashMap<&str, &str>) {
-        let mapper = self.mapper;
-        let suffix = &mapper.suffix;
-
-        for (key, value) in suffix {
-            if let Some(suffix) = self.apply_tags(value, captures) {
-                if let Some(old_value) = self.get_property(key) {
-                    let new_value = format!("{}{}", &old_value, suffix);
-                    self.set_property(key, &new_value);
-                }
-            }
-        }
-    }
-
-    fn apply_prefix(&self, captures: &HashMap<&str, &str>) {
-        let mapper = self.mapper;
-        let prefix = &mapper.prefix;
-        for (key, value) in prefix {
-            if let Some(prefix) = self.apply_tags(value, captures) {
-                if let Some(old_value) = self.get_property(key) {
-                    let new_value = format!("{}{}", prefix, &old_value);
-                    self.set_property(key, &new_value);
-                }
-            }
-        }
-    }
-
-    fn apply_assignments(&self) {
-        let mapper = self.mapper;
-        let assignments = &mapper.assignments;
-        for (key, value) in assignments {
-            if let Some(prop_value) = self.get_property(value) {
-                self.set_property(key, &prop_value);
-            }
-        }
-    }
-
-    fn apply_transform_modifier(modifier: &TransformModifier, value: &str) -> String {
-        match modifier {
-            TransformModifier::Uppercase => value.to_uppercase(),
-            TransformModifier::Lowercase => value.to_lowercase(),
-            TransformModifier::Capitalize => value.capitalize(),
-        }
-    }
-
-    fn apply_transform(&self) {
-        let mapper = self.mapper;
-        match &mapper.transform {
-            None => {}
-            Some(transform_list) => {
-                for transform in transform_list {
-                    if let Some(prop_value) = self.get_property(&transform.field) {
-                        let value = transform.t_pattern.as_ref().map_or_else(|| Cow::from(Self::apply_transform_modifier(&transform.modifier, prop_value.as_str())), |regex| regex.replace_all(&prop_value, |caps: &regex::Captures| {
-                            Self::apply_transform_modifier(&transform.modifier, &caps[0])
-                        }));
-                        self.set_property(&transform.field, &value);
-                    }
-                }
-            }
-        }
-    }
+/// Splits the group `group` by the channel names, channels without matching rule stay in the group.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct GroupSplit {
+    pub group: String,
+    pub rules: Vec<GroupSplitRule>,
 }
 
-impl ValueProcessor for MappingValueProcessor<'_> {
-    fn process<'a>(&mut self, _: &ItemField, value: &str, rewc: &RegexWithCaptures) -> bool {
-        let mut captured_values = HashMap::new();
-        if !rewc.captures.is_empty() {
-            rewc.re.captures_iter(value)
-                .filter(|caps| caps.len() > 1)
-                .for_each(|captures|
-                    for capture_name in &rewc.captures {
-                        let match_opt = captures.name(capture_name.as_str());
-                        let capture_value = if match_opt.is_some() {
-                            match_opt.map_or("", |m| m.as_str())
-                        } else {
-                            ""
-                        };
-                        debug!("match {}: {}", capture_name, capture_value);
-                        captured_values.insert(capture_name.as_str(), capture_value);
-                    }
-                );
+impl GroupSplit {
+    pub fn prepare(&mut self, templates: Option<&Vec<PatternTemplate>>) -> Result<(), M3uFilterError> {
+        for rule in &mut self.rules {
+            rule.t_pattern = Some(compile_group_regex(&rule.pattern, templates)?);
         }
-        MappingValueProcessor::<'_>::apply_attributes(self, &captured_values);
-        MappingValueProcessor::<'_>::apply_suffix(self, &captured_values);
-        MappingValueProcessor::<'_>::apply_prefix(self, &captured_values);
-        MappingValueProcessor::<'_>::apply_assignments(self);
-        MappingValueProcessor::<'_>::apply_transform(self);
-        true
+        Ok(())
     }
 }
 
@@ -434,10 +136,20 @@ pub struct Mapping {
     pub id: String,
     #[serde(default)]
     pub match_as_ascii: bool,
+    #[serde(default)]
     pub mapper: Vec<Mapper>,
     pub counter: Option<Vec<MappingCounterDefinition>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group_merge: Option<Vec<GroupMerge>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group_split: Option<Vec<GroupSplit>>,
+    /// csv or json file with the channel numbers and groups of the channels
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lineup: Option<String>,
     #[serde(skip_serializing, skip_deserializing)]
     pub t_counter: Option<Vec<MappingCounter>>,
+    #[serde(skip_serializing, skip_deserializing)]
+    pub t_lineup: Option<Arc<Lineup>>,
 
 }
 
@@ -448,6 +160,14 @@ impl Mapping {
             handle_m3u_filter_error_result!(M3uFilterErrorKind::Info, mapper.prepare(templates, tags));
         }
 
+        for group_merge in self.group_merge.iter_mut().flatten() {
+            group_merge.prepare(templates)?;
+        }
+
+        for group_split in self.group_split.iter_mut().flatten() {
+            group_split.prepare(templates)?;
+        }
+
         if let Some(counter_def_list) = &self.counter {
             let mut counters = vec![];
             for def in counter_def_list {
@@ -472,6 +192,49 @@ impl Mapping {
 
         Ok(())
     }
+
+    /// Reads the lineup file, a relative path is resolved against the directory of the mapping file.
+    pub fn prepare_lineup(&mut self, base_dir: &Path) -> Result<(), M3uFilterError> {
+        if let Some(file) = &self.lineup {
+            let lineup = Lineup::from_file(&base_dir.join(file))?;
+            debug!("Lineup of mapping {} has {} channels", self.id, lineup.len());
+            self.t_lineup = Some(Arc::new(lineup));
+        }
+        Ok(())
+    }
+
+    /// Reads the files of the `IN_FILE` comparisons of the mappers and counters, relative to the directory of the mapping file.
+    pub fn load_filter_files(&self, base_dir: &Path) -> Result<(), M3uFilterError> {
+        for filter in self.mapper.iter().flat_map(Mapper::filters) {
+            filter.load_files(base_dir)?;
+        }
+        for counter in self.t_counter.iter().flatten() {
+            counter.filter.load_files(base_dir)?;
+        }
+        Ok(())
+    }
+
+    pub fn has_group_operations(&self) -> bool {
+        self.group_merge.as_ref().is_some_and(|merges| !merges.is_empty())
+            || self.group_split.as_ref().is_some_and(|splits| !splits.is_empty())
+    }
+
+    /// Returns the new group of a channel, merges are applied before splits.
+    pub fn map_group(&self, group: &str, channel_name: &str) -> Option<String> {
+        let merged = self.group_merge.iter().flatten()
+            .find(|merge| merge.t_groups.iter().any(|re| re.is_match(group)))
+            .map(|merge| merge.name.clone());
+        let current = merged.as_deref().unwrap_or(group);
+        let split = self.group_split.iter().flatten()
+            .filter(|split| split.group == current)
+            .flat_map(|split| &split.rules)
+            .find_map(|rule| rule.t_pattern.as_ref().and_then(|re| re.captures(channel_name)).map(|caps| {
+                let mut name = String::new();
+                caps.expand(&rule.name, &mut name);
+                name
+            }));
+        split.or(merged)
+    }
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -482,19 +245,16 @@ pub struct MappingDefinition {
 }
 
 impl MappingDefinition {
-    pub fn prepare(&mut self) -> Result<(), M3uFilterError> {
+    pub fn prepare(&mut self, base_dir: &Path) -> Result<(), M3uFilterError> {
         if let Some(templates) = &mut self.templates {
-            match prepare_templates(templates) {
-                Ok(tmplts) => {
-                    self.templates = Some(tmplts);
-                }
-                Err(err) => return Err(err),
-            }
+            self.templates = Some(prepare_templates(templates)?);
         };
         for mapping in &mut self.mapping {
             let template_list = self.templates.as_ref();
             let tag_list = self.tags.as_ref();
             handle_m3u_filter_error_result!(M3uFilterErrorKind::Info, mapping.prepare(template_list, tag_list));
+            mapping.prepare_lineup(base_dir)?;
+            mapping.load_filter_files(base_dir)?;
         }
         Ok(())
     }
@@ -506,8 +266,9 @@ pub struct Mappings {
 }
 
 impl Mappings {
-    pub fn prepare(&mut self) -> Result<(), M3uFilterError> {
-        self.mappings.prepare()
+    /// `base_dir` is the directory of the mapping file.
+    pub fn prepare(&mut self, base_dir: &Path) -> Result<(), M3uFilterError> {
+        self.mappings.prepare(base_dir)
     }
 
     pub fn get_mapping(&self, mapping_id: &String) -> Option<Mapping> {
@@ -519,3 +280,27 @@ impl Mappings {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::model::mapping::Mapping;
+
+    #[test]
+    fn test_map_group() {
+        let mut mapping: Mapping = serde_yaml::from_str(r#"
+id: groups
+group_merge:
+  - groups: ['^DE Sport.*', '^Sky Sport']
+    name: Sport
+group_split:
+  - group: Sport
+    rules:
+      - pattern: '^(?P<league>NFL|NBA) '
+        name: 'Sport ${league}'
+"#).unwrap();
+        mapping.prepare(None, None).unwrap();
+        assert_eq!(mapping.map_group("DE Sport HD", "Eurosport"), Some("Sport".to_string()));
+        assert_eq!(mapping.map_group("Sky Sport", "NBA Lakers"), Some("Sport NBA".to_string()));
+        assert_eq!(mapping.map_group("News", "NFL Live"), None);
+    }
+}
@@ -1,6 +1,7 @@
 pub mod config;
 pub mod playlist;
 pub mod mapping;
+pub mod lineup;
 pub mod api_proxy;
 pub mod stats;
 pub mod xmltv;
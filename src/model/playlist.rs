@@ -9,7 +9,8 @@ use serde_json::Value;
 use crate::model::config::{ConfigInput, ConfigTargetOptions};
 use crate::model::xmltv::TVGuide;
 use crate::model::xtream::{xtream_playlistitem_to_document, XtreamMappingOptions};
-use crate::processing::m3u_parser::extract_id_from_url;
+pub use m3u_filter_core::field::FieldAccessor;
+use m3u_filter_core::m3u::{extract_id_from_url, write_extinf};
 use crate::repository::storage::hash_string;
 
 // https://de.wikipedia.org/wiki/M3U
@@ -82,6 +83,7 @@ pub enum PlaylistItemType {
     Catchup = 6,
     LiveUnknown = 7, // No Provider id
     LiveHls = 8, // m3u8 entry
+    LiveDirect = 9, // udp, rtp or rtsp entry
 }
 
 impl From<XtreamCluster> for PlaylistItemType {
@@ -106,7 +108,7 @@ impl PlaylistItemType {
 impl Display for PlaylistItemType {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", match self {
-            Self::Live | Self::LiveHls | Self::LiveUnknown => Self::LIVE,
+            Self::Live | Self::LiveHls | Self::LiveUnknown | Self::LiveDirect => Self::LIVE,
             Self::Video => Self::VIDEO,
             Self::Series => Self::SERIES,
             Self::SeriesInfo => Self::SERIES_INFO,
@@ -116,11 +118,6 @@ impl Display for PlaylistItemType {
     }
 }
 
-pub trait FieldAccessor {
-    fn get_field(&self, field: &str) -> Option<Rc<String>>;
-    fn set_field(&mut self, field: &str, value: &str) -> bool;
-}
-
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct PlaylistItemHeader {
     pub uuid: Rc<[u8; 32]>, // calculated
@@ -138,6 +135,8 @@ pub struct PlaylistItemHeader {
     pub rec: Rc<String>,
     pub url: Rc<String>,
     pub epg_channel_id: Option<Rc<String>>,
+    #[serde(default)]
+    pub epg_timeshift: Rc<String>,
     pub xtream_cluster: XtreamCluster,
     pub additional_properties: Option<Value>,
     #[serde(default, skip_serializing, skip_deserializing)]
@@ -148,6 +147,11 @@ pub struct PlaylistItemHeader {
     pub category_id: u32,
     #[serde(default)]
     pub input_id: u16,
+    /// inferred by the `tagging` of the target
+    #[serde(default)]
+    pub country: Rc<String>,
+    #[serde(default)]
+    pub language: Rc<String>,
 }
 
 impl PlaylistItemHeader {
@@ -175,16 +179,6 @@ impl PlaylistItemHeader {
     }
 }
 
-macro_rules! to_m3u_non_empty_fields {
-    ($header:expr, $line:expr, $(($prop:ident, $field:expr)),*;) => {
-        $(
-           if !$header.$prop.is_empty() {
-                $line = format!("{} {}=\"{}\"", $line, $field, $header.$prop);
-            }
-         )*
-    };
-}
-
 
 macro_rules! generate_field_accessor_impl_for_playlist_item_header {
     ($($prop:ident),*;) => {
@@ -219,7 +213,7 @@ macro_rules! generate_field_accessor_impl_for_playlist_item_header {
     }
 }
 
-generate_field_accessor_impl_for_playlist_item_header!(id, /*virtual_id,*/ name, chno, logo, logo_small, group, title, parent_code, audio_track, time_shift, rec, url;);
+generate_field_accessor_impl_for_playlist_item_header!(id, /*virtual_id,*/ name, chno, logo, logo_small, group, title, parent_code, audio_track, time_shift, rec, url, epg_timeshift, country, language;);
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct M3uPlaylistItem {
@@ -241,26 +235,92 @@ pub struct M3uPlaylistItem {
     pub item_type: PlaylistItemType,
 }
 
+/// The EXTINF attributes of a m3u playlist item.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum M3uAttribute {
+    TvgId,
+    TvgName,
+    GroupTitle,
+    TvgLogo,
+    TvgLogoSmall,
+    TvgChno,
+    ParentCode,
+    AudioTrack,
+    Timeshift,
+    TvgRec,
+}
+
+impl M3uAttribute {
+    /// The attributes in the default order of the m3u output.
+    pub const ALL: [Self; 10] = [Self::TvgId, Self::TvgName, Self::GroupTitle, Self::TvgLogo, Self::TvgLogoSmall,
+        Self::TvgChno, Self::ParentCode, Self::AudioTrack, Self::Timeshift, Self::TvgRec];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::TvgId => "tvg-id",
+            Self::TvgName => "tvg-name",
+            Self::GroupTitle => "group-title",
+            Self::TvgLogo => "tvg-logo",
+            Self::TvgLogoSmall => "tvg-logo-small",
+            Self::TvgChno => "tvg-chno",
+            Self::ParentCode => "parent-code",
+            Self::AudioTrack => "audio-track",
+            Self::Timeshift => "timeshift",
+            Self::TvgRec => "tvg-rec",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|attribute| attribute.name() == name)
+    }
+
+    /// `tvg-id`, `tvg-name` and `group-title` are written even if they are empty.
+    const fn is_required(self) -> bool {
+        matches!(self, Self::TvgId | Self::TvgName | Self::GroupTitle)
+    }
+
+    const fn is_logo(self) -> bool {
+        matches!(self, Self::TvgLogo | Self::TvgLogoSmall)
+    }
+
+    fn value(self, item: &M3uPlaylistItem) -> &str {
+        match self {
+            Self::TvgId => item.epg_channel_id.as_ref().map_or("", |o| o.as_str()),
+            Self::TvgName => &item.name,
+            Self::GroupTitle => &item.group,
+            Self::TvgLogo => &item.logo,
+            Self::TvgLogoSmall => &item.logo_small,
+            Self::TvgChno => &item.chno,
+            Self::ParentCode => &item.parent_code,
+            Self::AudioTrack => &item.audio_track,
+            Self::Timeshift => &item.time_shift,
+            Self::TvgRec => &item.rec,
+        }
+    }
+}
+
 impl M3uPlaylistItem {
-    pub fn to_m3u(&self, target_options: Option<&ConfigTargetOptions>, url: Option<&str>) -> String {
+    pub fn to_m3u<'a>(&'a self, target_options: Option<&'a ConfigTargetOptions>, url: Option<&str>) -> String {
         let options = target_options.as_ref();
         let ignore_logo = options.is_some_and(|o| o.ignore_logo);
-        let mut line = format!("#EXTINF:-1 tvg-id=\"{}\" tvg-name=\"{}\" group-title=\"{}\"",
-                               self.epg_channel_id.as_ref().map_or("", |o| o.as_ref()),
-                               self.name, self.group);
-
-        if !ignore_logo {
-            to_m3u_non_empty_fields!(self, line, (logo, "tvg-logo"), (logo_small, "tvg-logo-small"););
+        let attributes_cfg = options.and_then(|o| o.m3u_attributes.as_ref());
+        let mut line_attributes: Vec<(&str, &str)> = vec![];
+        let mut write_attribute = |attribute: M3uAttribute, name: &'a str| {
+            let value = attribute.value(self);
+            if (attribute.is_required() || !value.is_empty()) && !(ignore_logo && attribute.is_logo()) {
+                line_attributes.push((name, value));
+            }
+        };
+        match attributes_cfg {
+            Some(attributes) => attributes.t_attributes.iter().for_each(|(attribute, name)| write_attribute(*attribute, name)),
+            None => M3uAttribute::ALL.into_iter().for_each(|attribute| write_attribute(attribute, attribute.name())),
+        }
+        if let Some(attributes) = attributes_cfg {
+            line_attributes.extend(attributes.custom.iter().map(|(name, value)| (name.as_str(), value.as_str())));
         }
 
-        to_m3u_non_empty_fields!(self, line,
-            (chno, "tvg-chno"),
-            (parent_code, "parent-code"),
-            (audio_track, "audio-track"),
-            (time_shift, "timeshift"),
-            (rec, "tvg-rec"););
-
-        format!("{},{}\n{}", line, self.title, url.unwrap_or_else(|| self.url.as_str()))
+        let url_suffix = options.and_then(|o| o.t_m3u_url_suffix.as_deref()).unwrap_or_default();
+        write_extinf(&line_attributes, &self.title, &format!("{}{url_suffix}", url.unwrap_or_else(|| self.url.as_str())))
     }
 }
 
@@ -357,4 +417,52 @@ impl PlaylistGroup {
     pub fn on_load(&mut self) {
         self.channels.iter().for_each(|pl| pl.header.borrow_mut().gen_uuid());
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::collections::{BTreeMap, HashMap};
+    use std::rc::Rc;
+
+    use crate::model::config::{ConfigTargetOptions, M3uAttributePreset, M3uAttributesConfig};
+    use crate::model::playlist::{PlaylistItem, PlaylistItemHeader};
+
+    fn options(attributes: M3uAttributesConfig) -> ConfigTargetOptions {
+        let mut attributes = attributes;
+        attributes.prepare("test").unwrap();
+        ConfigTargetOptions { m3u_attributes: Some(attributes), ..ConfigTargetOptions::default() }
+    }
+
+    #[test]
+    fn test_to_m3u_attributes() {
+        let header = PlaylistItemHeader {
+            name: Rc::new("News".to_string()), title: Rc::new("News".to_string()), group: Rc::new("TV".to_string()),
+            chno: Rc::new("7".to_string()), time_shift: Rc::new("2".to_string()), url: Rc::new("http://localhost/1".to_string()),
+            ..PlaylistItemHeader::default()
+        };
+        let item = PlaylistItem { header: RefCell::new(header) }.to_m3u();
+        assert_eq!(item.to_m3u(None, None),
+                   "#EXTINF:-1 tvg-id=\"\" tvg-name=\"News\" group-title=\"TV\" tvg-chno=\"7\" timeshift=\"2\",News\nhttp://localhost/1");
+
+        let kodi = options(M3uAttributesConfig { preset: M3uAttributePreset::Kodi, ..M3uAttributesConfig::default() });
+        assert_eq!(item.to_m3u(Some(&kodi), None),
+                   "#EXTINF:-1 tvg-id=\"\" tvg-name=\"News\" tvg-chno=\"7\" group-title=\"TV\" tvg-shift=\"2\",News\nhttp://localhost/1");
+
+        let custom = options(M3uAttributesConfig {
+            include: vec!["group-title".to_string(), "tvg-name".to_string(), "tvg-chno".to_string()],
+            exclude: vec!["tvg-chno".to_string()],
+            rename: HashMap::from([("tvg-name".to_string(), "name".to_string())]),
+            custom: BTreeMap::from([("catchup".to_string(), "default".to_string())]),
+            ..M3uAttributesConfig::default()
+        });
+        assert_eq!(item.to_m3u(Some(&custom), None),
+                   "#EXTINF:-1 group-title=\"TV\" name=\"News\" catchup=\"default\",News\nhttp://localhost/1");
+
+        let mut url_headers = ConfigTargetOptions {
+            m3u_url_headers: BTreeMap::from([("User-Agent".to_string(), "VLC/3.0 LibVLC".to_string()), ("Referer".to_string(), "http://tv/".to_string())]),
+            ..ConfigTargetOptions::default()
+        };
+        url_headers.prepare("test").unwrap();
+        assert!(item.to_m3u(Some(&url_headers), None).ends_with("\nhttp://localhost/1|Referer=http%3A%2F%2Ftv%2F&User-Agent=VLC%2F3.0%20LibVLC"));
+    }
+}
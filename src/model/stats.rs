@@ -1,5 +1,6 @@
 use std::fmt::Display;
 use serde::{Serialize, Serializer};
+use crate::m3u_filter_error::M3uFilterErrorInfo;
 use crate::model::config::InputType;
 
 pub fn format_elapsed_time(seconds: u64) -> String {
@@ -48,4 +49,40 @@ impl Display for InputStats {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         serde_json::to_string(&self).map_or(Err(std::fmt::Error), |json_str| write!(f, "{json_str}"))
     }
-}
\ No newline at end of file
+}
+/// The result of a target, `errors` are the errors of processing and writing.
+#[derive(Debug, Clone, Serialize)]
+pub struct TargetStats {
+    pub name: String,
+    #[serde(flatten)]
+    pub stats: PlaylistStats,
+    pub errors: Vec<String>,
+    pub secs: u64,
+}
+
+/// Exit code in cli mode if an input could not be downloaded.
+pub const EXIT_CODE_DOWNLOAD_FAILURE: i32 = 2;
+/// Exit code in cli mode if a target could not be processed or written.
+pub const EXIT_CODE_WRITE_FAILURE: i32 = 3;
+
+/// Machine-readable result of a processing run.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProcessingSummary {
+    pub inputs: Vec<InputStats>,
+    pub targets: Vec<TargetStats>,
+    pub errors: Vec<M3uFilterErrorInfo>,
+    pub secs: u64,
+}
+
+impl ProcessingSummary {
+    /// `0` on success, a write failure outweighs a download failure.
+    pub fn exit_code(&self) -> i32 {
+        if self.targets.iter().any(|target| !target.errors.is_empty()) {
+            EXIT_CODE_WRITE_FAILURE
+        } else if self.inputs.iter().any(|input| input.error_count > 0) {
+            EXIT_CODE_DOWNLOAD_FAILURE
+        } else {
+            0
+        }
+    }
+}
@@ -10,6 +10,8 @@ pub const EPG_TAG_PROGRAMME: &str = "programme";
 pub const EPG_TAG_CHANNEL: &str = "channel";
 pub const EPG_ATTRIB_ID: &str = "id";
 pub const EPG_ATTRIB_CHANNEL: &str = "channel";
+pub const EPG_TAG_ICON: &str = "icon";
+pub const EPG_ATTRIB_SRC: &str = "src";
 
 // https://github.com/XMLTV/xmltv/blob/master/xmltv.dtd
 
@@ -22,6 +24,17 @@ pub struct XmlTag {
 }
 
 impl XmlTag {
+    pub fn new(name: &str, value: Option<String>, attributes: Vec<(&str, String)>, children: Option<Vec<Rc<XmlTag>>>) -> Self {
+        Self {
+            name: name.to_string(),
+            value,
+            attributes: if attributes.is_empty() { None } else {
+                Some(Rc::new(attributes.into_iter().map(|(key, value)| (key.to_string(), value)).collect::<HashMap<String, String>>()))
+            },
+            children,
+        }
+    }
+
     pub fn get_attribute_value(&self, attr_name: &str) -> Option<&String> {
         self.attributes.as_ref().and_then(|attr| attr.get(attr_name))
     }
@@ -51,13 +64,21 @@ pub struct Epg {
 
 impl Epg {
     pub fn write_to<W: std::io::Write>(&self, writer: &mut Writer<W>) -> Result<(), quick_xml::Error> {
+        self.write_mapped_to(writer, |_| None)
+    }
+
+    /// Writes the epg, `map` can replace a child tag of `tv` in the output.
+    pub fn write_mapped_to<W: std::io::Write, F: Fn(&XmlTag) -> Option<XmlTag>>(&self, writer: &mut Writer<W>, map: F) -> Result<(), quick_xml::Error> {
         let mut elem = BytesStart::new("tv");
         if let Some(attribs) = self.attributes.as_ref() {
             attribs.iter().for_each(|(k, v)| elem.push_attribute((k.as_str(), v.as_str())));
         }
         writer.write_event(Event::Start(elem))?;
         for child in &self.children {
-            child.write_to(writer)?;
+            match map(child) {
+                Some(mapped) => mapped.write_to(writer)?,
+                None => child.write_to(writer)?,
+            }
         }
         Ok(writer.write_event(Event::End(BytesEnd::new("tv")))?)
     }
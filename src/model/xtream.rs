@@ -8,6 +8,8 @@ use serde_json::{Map, Value};
 
 use crate::model::config::ConfigTargetOptions;
 use crate::model::playlist::{PlaylistItem, XtreamCluster, XtreamPlaylistItem};
+use crate::processing::logo_cache::LogoRewrite;
+use crate::processing::token_refresh::TokenRewrite;
 
 const LIVE_STREAM_FIELDS: &[&str] = &[];
 
@@ -364,6 +366,8 @@ impl XtreamSeriesInfoEpisode {
         add_str_property_if_exists!(result, self.info.releasedate, "release_date");
         add_str_property_if_exists!(result, self.title, "title");
         add_i64_property_if_exists!(result, self.season, "season");
+        add_i64_property_if_exists!(result, self.episode_num, "episode_num");
+        add_str_property_if_exists!(result, series_info.info.name, "series_name");
         add_str_property_if_exists!(result, series_info.info.youtube_trailer, "youtube_trailer");
         if result.is_empty() { None } else { Some(Value::Object(result)) }
     }
@@ -373,6 +377,8 @@ pub struct XtreamMappingOptions {
     pub skip_live_direct_source: bool,
     pub skip_video_direct_source: bool,
     pub skip_series_direct_source: bool,
+    pub logo_rewrite: Option<LogoRewrite>,
+    pub token_rewrite: Option<TokenRewrite>,
 }
 
 impl XtreamMappingOptions {
@@ -384,6 +390,8 @@ impl XtreamMappingOptions {
             skip_live_direct_source,
             skip_video_direct_source,
             skip_series_direct_source,
+            logo_rewrite: None,
+            token_rewrite: None,
         }
     }
 }
@@ -479,6 +487,29 @@ pub fn xtream_playlistitem_to_document(pli: &XtreamPlaylistItem, options: &Xtrea
         }
     }
 
+    if !pli.parent_code.is_empty() {
+        document.insert("is_adult".to_string(), Value::String(String::from("1")));
+    }
+
+    if let Some(token_rewrite) = &options.token_rewrite {
+        if let Some(url) = document.get("direct_source").and_then(Value::as_str).and_then(|url| token_rewrite.rewrite(pli.input_id, url)) {
+            document.insert("direct_source".to_string(), Value::String(url));
+        }
+    }
+
+    if let Some(logo_rewrite) = &options.logo_rewrite {
+        for field in ["stream_icon", "thumbnail", "cover"] {
+            if let Some(logo) = document.get(field).and_then(Value::as_str).and_then(|logo| logo_rewrite.rewrite(logo)) {
+                document.insert(field.to_string(), Value::String(logo));
+            }
+        }
+        if pli.logo.is_empty() {
+            if let Some(placeholder) = logo_rewrite.placeholder(&pli.name) {
+                document.insert("stream_icon".to_string(), Value::String(placeholder));
+            }
+        }
+    }
+
     match pli.xtream_cluster {
         XtreamCluster::Live => {
             append_mandatory_fields(&mut document, LIVE_STREAM_FIELDS);
@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, RwLock};
+use std::time::Duration;
+
+use log::{debug, info};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::messaging::{send_message, MsgKind};
+use crate::model::config::{AccountCheckConfig, Config, ConfigInput, InputType};
+use crate::utils::request_utils;
+use crate::utils::request_utils::mask_sensitive_info;
+
+const SECONDS_PER_DAY: i64 = 86_400;
+
+/// The account of a xtream input, as reported by the provider.
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountStatus {
+    pub input_id: u16,
+    pub input_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exp_date: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_connections: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub active_connections: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub checked_at: i64,
+    #[serde(skip)]
+    notified_at: Option<i64>,
+}
+
+/// Last known account status per input id
+static ACCOUNT_STATUS: LazyLock<RwLock<HashMap<u16, AccountStatus>>> = LazyLock::new(|| RwLock::new(HashMap::new()));
+
+/// Returns the last known account status of all checked inputs.
+pub fn get_account_status_list() -> Vec<AccountStatus> {
+    let mut result: Vec<AccountStatus> = ACCOUNT_STATUS.read().unwrap().values().cloned().collect();
+    result.sort_by_key(|status| status.input_id);
+    result
+}
+
+/// Xtream panels deliver numbers as strings, numbers or null.
+fn get_number(value: Option<&Value>) -> Option<i64> {
+    match value? {
+        Value::Number(number) => number.as_i64(),
+        Value::String(text) => text.trim().parse::<i64>().ok(),
+        _ => None,
+    }
+}
+
+fn get_count(value: Option<&Value>) -> Option<u32> {
+    get_number(value).and_then(|number| u32::try_from(number).ok())
+}
+
+fn parse_user_info(input: &ConfigInput, content: &Value, now: i64) -> AccountStatus {
+    let user_info = content.get("user_info");
+    AccountStatus {
+        input_id: input.id,
+        input_name: input.name.clone(),
+        status: user_info.and_then(|info| info.get("status")).and_then(Value::as_str).map(ToString::to_string),
+        exp_date: get_number(user_info.and_then(|info| info.get("exp_date"))).filter(|exp_date| *exp_date > 0),
+        max_connections: get_count(user_info.and_then(|info| info.get("max_connections"))),
+        active_connections: get_count(user_info.and_then(|info| info.get("active_cons"))),
+        error: if user_info.is_none() { Some("Missing user_info".to_string()) } else { None },
+        checked_at: now,
+        notified_at: None,
+    }
+}
+
+/// Returns the days until the account expires if a notification is due, at most one notification per day is sent.
+fn get_expiry_notification_days(status: &AccountStatus, notify_days: u32, now: i64) -> Option<i64> {
+    let exp_date = status.exp_date?;
+    let days_left = (exp_date - now).div_euclid(SECONDS_PER_DAY);
+    if days_left > i64::from(notify_days) || status.notified_at.is_some_and(|notified_at| now - notified_at < SECONDS_PER_DAY) {
+        return None;
+    }
+    Some(days_left)
+}
+
+async fn check_account(input: &ConfigInput, now: i64) -> AccountStatus {
+    let Some(user_info) = input.get_user_info() else {
+        return AccountStatus { input_id: input.id, input_name: input.name.clone(), status: None, exp_date: None, max_connections: None,
+            active_connections: None, error: Some("Missing credentials".to_string()), checked_at: now, notified_at: None };
+    };
+    let url = format!("{}/player_api.php?username={}&password={}", &user_info.base_url, &user_info.username, &user_info.password);
+    match request_utils::get_input_json_content(input, &url, None).await {
+        Ok(content) => parse_user_info(input, &content, now),
+        Err(err) => {
+            debug!("Failed to read account info {}: {}", mask_sensitive_info(&url), mask_sensitive_info(&err.to_string()));
+            AccountStatus { input_id: input.id, input_name: input.name.clone(), status: None, exp_date: None, max_connections: None,
+                active_connections: None, error: Some(mask_sensitive_info(&err.to_string())), checked_at: now, notified_at: None }
+        }
+    }
+}
+
+async fn check_accounts(cfg: &Config, account_cfg: &AccountCheckConfig) {
+    let inputs = cfg.sources.iter().flat_map(|source| &source.inputs)
+        .filter(|input| input.enabled && input.input_type == InputType::Xtream);
+    for input in inputs {
+        let now = chrono::Utc::now().timestamp();
+        let mut status = check_account(input, now).await;
+        status.notified_at = ACCOUNT_STATUS.read().unwrap().get(&input.id).and_then(|old| old.notified_at);
+        if let Some(days_left) = get_expiry_notification_days(&status, account_cfg.notify_days, now) {
+            let name = input.name.as_deref().unwrap_or(&input.url);
+            let msg = if days_left < 0 {
+                format!("Account of input {name} is expired")
+            } else {
+                format!("Account of input {name} expires in {days_left} days")
+            };
+            info!("{msg}");
+            send_message(&MsgKind::Expiry, cfg.messaging.as_ref(), &msg);
+            status.notified_at = Some(now);
+        }
+        ACCOUNT_STATUS.write().unwrap().insert(input.id, status);
+    }
+}
+
+/// Periodically reads `exp_date` and `max_connections` of the xtream inputs
+/// and notifies `notify_days` before an account expires.
+pub fn start_account_checker(cfg: Arc<Config>) {
+    if let Some(account_cfg) = cfg.account_check.as_ref().filter(|account_check| account_check.enabled).cloned() {
+        actix_rt::spawn(async move {
+            loop {
+                check_accounts(&cfg, &account_cfg).await;
+                actix_rt::time::sleep(Duration::from_secs(account_cfg.interval)).await;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::config::ConfigInput;
+    use crate::processing::account_check::{get_expiry_notification_days, parse_user_info};
+
+    #[test]
+    fn test_account_expiry() {
+        let input = ConfigInput { id: 1, ..ConfigInput::default() };
+        let now = 1_700_000_000;
+        let content = serde_json::json!({"user_info": {"status": "Active", "exp_date": (now + 3 * 86_400 + 100).to_string(), "max_connections": "2", "active_cons": 1}});
+        let mut status = parse_user_info(&input, &content, now);
+        assert_eq!(status.status.as_deref(), Some("Active"));
+        assert_eq!(status.max_connections, Some(2));
+        assert_eq!(status.active_connections, Some(1));
+        assert_eq!(get_expiry_notification_days(&status, 7, now), Some(3));
+        assert_eq!(get_expiry_notification_days(&status, 2, now), None);
+        status.notified_at = Some(now - 3600);
+        assert_eq!(get_expiry_notification_days(&status, 7, now), None);
+
+        let unlimited = parse_user_info(&input, &serde_json::json!({"user_info": {"exp_date": null}}), now);
+        assert_eq!(unlimited.exp_date, None);
+        assert_eq!(get_expiry_notification_days(&unlimited, 7, now), None);
+    }
+}
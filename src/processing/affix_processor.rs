@@ -1,11 +1,12 @@
-use crate::model::config::{ConfigInput, InputAffix, AFFIX_FIELDS};
+use m3u_filter_core::mapper::AFFIX_FIELDS;
+use crate::model::config::{ConfigInput, InputAffix};
 use crate::model::playlist::{FetchedPlaylist, FieldAccessor, PlaylistItem};
 use crate::valid_property;
 use log::{debug, log_enabled, Level};
 
-type AffixProcessor<'a> = Box<dyn Fn(&mut PlaylistItem) + 'a>;
+pub type AffixProcessor<'a> = Box<dyn Fn(&mut PlaylistItem) + 'a>;
 
-fn create_affix_processor(affix: &InputAffix, is_prefix: bool) -> AffixProcessor {
+fn create_affix_processor(affix: &InputAffix, is_prefix: bool) -> AffixProcessor<'_> {
     Box::new(move |channel: &mut PlaylistItem| {
         let header = &mut channel.header.borrow_mut();
         let value = header.get_field(affix.field.as_str()).map_or_else(|| String::from(&affix.value), |field_value| if is_prefix {
@@ -20,7 +21,7 @@ fn create_affix_processor(affix: &InputAffix, is_prefix: bool) -> AffixProcessor
     })
 }
 
-fn validate_and_create_affix_processor(affix: Option<&InputAffix>, is_prefix: bool) -> Option<AffixProcessor> {
+fn validate_and_create_affix_processor(affix: Option<&InputAffix>, is_prefix: bool) -> Option<AffixProcessor<'_>> {
     if let Some(affix_def) = affix {
         if (valid_property!(&affix_def.field.as_str(), AFFIX_FIELDS) && !affix_def.value.is_empty()) {
             return Some(create_affix_processor(affix_def, is_prefix));
@@ -29,7 +30,7 @@ fn validate_and_create_affix_processor(affix: Option<&InputAffix>, is_prefix: bo
     None
 }
 
-fn get_affix_processor(input: &ConfigInput) -> Option<AffixProcessor> {
+pub fn get_affix_processor(input: &ConfigInput) -> Option<AffixProcessor<'_>> {
     if input.suffix.is_some() || input.prefix.is_some() {
         let processors: Vec<AffixProcessor> = vec![
             validate_and_create_affix_processor(input.prefix.as_ref(), true),
@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use log::debug;
+
+use crate::model::config::{Config, ConfigTarget, TargetChannelAliases};
+use crate::model::playlist::{PlaylistGroup, PlaylistItemHeader, XtreamCluster};
+use crate::repository::alias_repository::{alias_load, AliasDatabase};
+
+/// Renames the channel and sets its epg channel id as configured.
+/// Returns the canonical name and the quality rank of the channel name if the channel has an alias.
+pub fn alias_channel(db: &AliasDatabase, options: &TargetChannelAliases, header: &mut PlaylistItemHeader) -> Option<(String, usize)> {
+    let entry = db.get(&header.name)?;
+    let rank = AliasDatabase::quality_rank(entry, &header.name);
+    if options.epg && header.epg_channel_id.as_ref().is_none_or(|id| id.is_empty()) {
+        header.epg_channel_id = entry.epg_channel_id.as_ref().map(|id| Rc::new(id.clone()));
+    }
+    if options.rename {
+        header.name = Rc::new(entry.name.clone());
+        header.title = Rc::clone(&header.name);
+    }
+    Some((entry.name.clone(), rank))
+}
+
+fn alias_playlist(db: &AliasDatabase, options: &TargetChannelAliases, mut playlist: Vec<PlaylistGroup>) -> Vec<PlaylistGroup> {
+    // best channel of each canonical name: (rank, group index, channel index)
+    let mut best: HashMap<String, (usize, usize, usize)> = HashMap::new();
+    let mut duplicates = vec![];
+    for (group_index, group) in playlist.iter().enumerate().filter(|(_, group)| group.xtream_cluster == XtreamCluster::Live) {
+        for (channel_index, channel) in group.channels.iter().enumerate() {
+            let Some((name, rank)) = alias_channel(db, options, &mut channel.header.borrow_mut()) else { continue };
+            if options.dedup {
+                match best.get(&name) {
+                    Some(&(best_rank, _, _)) if best_rank <= rank => duplicates.push((group_index, channel_index)),
+                    Some(&(_, best_group, best_channel)) => {
+                        duplicates.push((best_group, best_channel));
+                        best.insert(name, (rank, group_index, channel_index));
+                    }
+                    None => { best.insert(name, (rank, group_index, channel_index)); }
+                }
+            }
+        }
+    }
+    if duplicates.is_empty() {
+        return playlist;
+    }
+    debug!("Removing {} duplicate channels", duplicates.len());
+    // remove from the end to keep the indices valid
+    duplicates.sort_unstable();
+    for (group_index, channel_index) in duplicates.into_iter().rev() {
+        playlist[group_index].channels.remove(channel_index);
+    }
+    playlist.retain(|group| !group.channels.is_empty());
+    playlist
+}
+
+/// Renames the live channels of the target to their canonical names, sets missing epg channel ids
+/// and removes the duplicates as configured in the `channel_aliases` of the target.
+pub fn apply_channel_aliases(cfg: &Config, target: &ConfigTarget, playlist: Vec<PlaylistGroup>) -> Vec<PlaylistGroup> {
+    let Some(options) = &target.channel_aliases else {
+        return playlist;
+    };
+    let db = alias_load(cfg);
+    if db.entries().is_empty() {
+        return playlist;
+    }
+    alias_playlist(&db, options, playlist)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::model::config::TargetChannelAliases;
+    use crate::model::playlist::{PlaylistGroup, PlaylistItem, PlaylistItemHeader, XtreamCluster};
+    use crate::processing::channel_alias::alias_playlist;
+    use crate::repository::alias_repository::{AliasDatabase, ChannelAlias};
+
+    fn item(name: &str) -> PlaylistItem {
+        PlaylistItem { header: RefCell::new(PlaylistItemHeader { name: Rc::new(name.to_string()), xtream_cluster: XtreamCluster::Live, ..PlaylistItemHeader::default() }) }
+    }
+
+    #[test]
+    fn test_alias_playlist() {
+        let db = AliasDatabase::new(vec![ChannelAlias { name: "Das Erste".to_string(), aliases: vec!["ARD".to_string()], epg_channel_id: Some("daserste.de".to_string()), ..ChannelAlias::default() }]);
+        let group = |id: u32, channels: Vec<PlaylistItem>| PlaylistGroup { id, title: Rc::new(format!("G{id}")), channels, xtream_cluster: XtreamCluster::Live };
+        let playlist = vec![group(1, vec![item("ARD SD"), item("Arte")]), group(2, vec![item("Das Erste HD")])];
+        let options = TargetChannelAliases { rename: true, epg: true, dedup: true };
+        let result = alias_playlist(&db, &options, playlist);
+        let names: Vec<Vec<String>> = result.iter().map(|group| group.channels.iter().map(|channel| channel.header.borrow().name.to_string()).collect()).collect();
+        assert_eq!(names, vec![vec!["Arte".to_string()], vec!["Das Erste".to_string()]]);
+        assert_eq!(result[1].channels[0].header.borrow().epg_channel_id.as_deref().map(String::as_str), Some("daserste.de"));
+    }
+}
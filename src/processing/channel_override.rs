@@ -0,0 +1,148 @@
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use log::debug;
+
+use crate::model::config::{Config, ConfigTarget};
+use crate::model::playlist::{PlaylistGroup, PlaylistItemHeader};
+use crate::processing::post_process::regroup_items;
+use crate::repository::override_repository::{override_load, ChannelOverride, ChannelOverrides};
+use crate::repository::quarantine_repository::{quarantine_load, ChannelQuarantines};
+use crate::repository::storage::hex_encode;
+
+/// Returns `true` if the group of the item changed.
+fn apply_override(header: &mut PlaylistItemHeader, channel_override: &ChannelOverride) -> bool {
+    if let Some(name) = &channel_override.name {
+        header.name = Rc::new(name.clone());
+        header.title = Rc::clone(&header.name);
+    }
+    if let Some(logo) = &channel_override.logo {
+        header.logo = Rc::new(logo.clone());
+    }
+    if let Some(epg_channel_id) = &channel_override.epg_channel_id {
+        header.epg_channel_id = Some(Rc::new(epg_channel_id.clone()));
+    }
+    match &channel_override.group {
+        Some(group) if group.as_str() != header.group.as_str() => {
+            header.group = Rc::new(group.clone());
+            true
+        }
+        _ => false,
+    }
+}
+
+fn override_playlist(overrides: &ChannelOverrides, playlist: Vec<PlaylistGroup>) -> Vec<PlaylistGroup> {
+    let mut hidden = HashSet::new();
+    let mut regroup = false;
+    for channel in playlist.iter().flat_map(|group| &group.channels) {
+        let mut header = channel.header.borrow_mut();
+        let uuid = **header.get_uuid();
+        let Some(channel_override) = overrides.get(&hex_encode(&uuid)) else { continue };
+        if channel_override.hidden {
+            hidden.insert(uuid);
+            regroup = true;
+        } else {
+            regroup |= apply_override(&mut header, channel_override);
+        }
+    }
+    if !regroup {
+        return playlist;
+    }
+    let items: Vec<PlaylistItemHeader> = playlist.iter().flat_map(|group| &group.channels)
+        .map(|channel| channel.header.borrow().clone())
+        .filter(|header| !hidden.contains(&**header.get_uuid()))
+        .collect();
+    regroup_items(items.into_iter(), &playlist)
+}
+
+/// Applies the override of a single channel. Returns `None` if the channel is hidden,
+/// otherwise if the override moved the channel to another group.
+pub fn override_channel(overrides: &ChannelOverrides, header: &mut PlaylistItemHeader) -> Option<bool> {
+    let uuid = hex_encode(header.get_uuid().as_ref());
+    match overrides.get(&uuid) {
+        Some(channel_override) if channel_override.hidden => None,
+        Some(channel_override) => Some(apply_override(header, channel_override)),
+        None => Some(false),
+    }
+}
+
+/// Applies the manual channel overrides of the target, they survive the refreshes because they are keyed by the channel uuid.
+pub fn apply_channel_overrides(cfg: &Config, target: &ConfigTarget, playlist: Vec<PlaylistGroup>) -> Vec<PlaylistGroup> {
+    let overrides = override_load(cfg, &target.name);
+    if overrides.is_empty() {
+        return playlist;
+    }
+    debug!("Applying {} channel overrides to target {}", overrides.len(), target.name);
+    override_playlist(&overrides, playlist)
+}
+
+fn quarantine_playlist(quarantine: &ChannelQuarantines, playlist: Vec<PlaylistGroup>) -> Vec<PlaylistGroup> {
+    playlist.into_iter().filter_map(|mut group| {
+        group.channels.retain(|channel| !quarantine.contains_key(&hex_encode(channel.header.borrow().get_uuid().as_ref())));
+        (!group.channels.is_empty()).then_some(group)
+    }).collect()
+}
+
+/// Removes the quarantined channels of the target, they are part of the outputs again with the first refresh after the quarantine expired.
+pub fn apply_channel_quarantine(cfg: &Config, target: &ConfigTarget, playlist: Vec<PlaylistGroup>) -> Vec<PlaylistGroup> {
+    let quarantine = quarantine_load(cfg, &target.name, chrono::Utc::now().timestamp());
+    if quarantine.is_empty() {
+        return playlist;
+    }
+    debug!("Skipping {} quarantined channels of target {}", quarantine.len(), target.name);
+    quarantine_playlist(&quarantine, playlist)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::model::playlist::{PlaylistGroup, PlaylistItem, PlaylistItemHeader, XtreamCluster};
+    use crate::processing::channel_override::{override_playlist, quarantine_playlist};
+    use crate::repository::override_repository::{ChannelOverride, ChannelOverrides};
+    use crate::repository::quarantine_repository::{ChannelQuarantine, ChannelQuarantines};
+    use crate::repository::storage::hex_encode;
+
+    fn item(name: &str) -> PlaylistItem {
+        let mut header = PlaylistItemHeader { name: Rc::new(name.to_string()), group: Rc::new("News".to_string()), url: Rc::new(format!("http://localhost/{name}")), ..PlaylistItemHeader::default() };
+        header.gen_uuid();
+        PlaylistItem { header: RefCell::new(header) }
+    }
+
+    #[test]
+    fn test_override_playlist() {
+        let playlist = vec![PlaylistGroup { id: 1, title: Rc::new("News".to_string()), channels: vec![item("CNN"), item("BBC"), item("Sky")], xtream_cluster: XtreamCluster::Live }];
+        let uuid = |index: usize| hex_encode(playlist[0].channels[index].header.borrow().get_uuid().as_ref());
+        let mut overrides = ChannelOverrides::new();
+        overrides.insert(uuid(0), ChannelOverride { name: Some("CNN HD".to_string()), epg_channel_id: Some("cnn.us".to_string()), ..ChannelOverride::default() });
+        overrides.insert(uuid(1), ChannelOverride { hidden: true, ..ChannelOverride::default() });
+        overrides.insert(uuid(2), ChannelOverride { group: Some("UK".to_string()), ..ChannelOverride::default() });
+
+        let result = override_playlist(&overrides, playlist);
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].id, 1);
+        assert_eq!(result[0].channels.len(), 1);
+        let header = result[0].channels[0].header.borrow();
+        assert_eq!(header.name.as_str(), "CNN HD");
+        assert_eq!(header.epg_channel_id.as_deref().map(String::as_str), Some("cnn.us"));
+        assert_eq!(result[1].title.as_str(), "UK");
+    }
+
+    #[test]
+    fn test_quarantine_playlist() {
+        let playlist = vec![
+            PlaylistGroup { id: 1, title: Rc::new("News".to_string()), channels: vec![item("CNN"), item("BBC")], xtream_cluster: XtreamCluster::Live },
+            PlaylistGroup { id: 2, title: Rc::new("Sports".to_string()), channels: vec![item("ESPN")], xtream_cluster: XtreamCluster::Live },
+        ];
+        let uuid = |group: usize, index: usize| hex_encode(playlist[group].channels[index].header.borrow().get_uuid().as_ref());
+        let mut quarantine = ChannelQuarantines::new();
+        quarantine.insert(uuid(0, 0), ChannelQuarantine { until: i64::MAX, reason: Some("broken".to_string()) });
+        quarantine.insert(uuid(1, 0), ChannelQuarantine { until: i64::MAX, reason: None });
+
+        let result = quarantine_playlist(&quarantine, playlist);
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].channels.len(), 1);
+        assert_eq!(result[0].channels[0].header.borrow().name.as_str(), "BBC");
+    }
+}
@@ -0,0 +1,143 @@
+use std::cmp::Ordering;
+use std::rc::Rc;
+
+use unidecode::unidecode;
+
+use crate::model::config::{ConfigTarget, ItemField, SortCollation};
+use crate::model::playlist::PlaylistGroup;
+
+/// The compared form of the value, folded for a locale and without leading article.
+fn collation_key(collation: &SortCollation, value: &str) -> String {
+    let folded = if collation.locale.is_some() { unidecode(value).to_lowercase() } else { value.to_string() };
+    for article in &collation.t_articles {
+        let has_article = folded.get(..article.len()).is_some_and(|prefix| prefix.eq_ignore_ascii_case(article));
+        if has_article {
+            let rest = &folded[article.len()..];
+            // `l'` is followed by the word, the other articles by a whitespace
+            let rest = if article.ends_with('\'') { rest } else if rest.starts_with(char::is_whitespace) { rest.trim_start() } else { continue };
+            if !rest.is_empty() {
+                return rest.to_string();
+            }
+        }
+    }
+    folded
+}
+
+/// Compares the digit runs by their number value.
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut chars_a, mut chars_b) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        match (chars_a.peek().copied(), chars_b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(char_a), Some(char_b)) if char_a.is_ascii_digit() && char_b.is_ascii_digit() => {
+                let mut number_a = String::new();
+                while let Some(digit) = chars_a.next_if(char::is_ascii_digit) {
+                    number_a.push(digit);
+                }
+                let mut number_b = String::new();
+                while let Some(digit) = chars_b.next_if(char::is_ascii_digit) {
+                    number_b.push(digit);
+                }
+                let (number_a, number_b) = (number_a.trim_start_matches('0'), number_b.trim_start_matches('0'));
+                let ordering = number_a.len().cmp(&number_b.len()).then_with(|| number_a.cmp(number_b));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(char_a), Some(char_b)) => {
+                if char_a != char_b {
+                    return char_a.cmp(&char_b);
+                }
+                chars_a.next();
+                chars_b.next();
+            }
+        }
+    }
+}
+
+/// Compares the values with the collation, equal keys are ordered by the original value.
+pub fn collate(collation: &SortCollation, a: &str, b: &str) -> Ordering {
+    let (key_a, key_b) = (collation_key(collation, a), collation_key(collation, b));
+    let ordering = if collation.numeric { natural_cmp(&key_a, &key_b) } else { key_a.cmp(&key_b) };
+    ordering.then_with(|| a.cmp(b))
+}
+
+fn transliterate(value: &Rc<String>) -> Option<Rc<String>> {
+    if value.is_ascii() {
+        None
+    } else {
+        Some(Rc::new(unidecode(value).trim().to_string()))
+    }
+}
+
+/// Converts the configured fields of the channels and the group titles to latin letters.
+pub fn transliterate_playlist(target: &ConfigTarget, playlist: &mut [PlaylistGroup]) {
+    let Some(fields) = target.transliterate.as_ref().filter(|fields| !fields.is_empty()) else {
+        return;
+    };
+    let group = fields.iter().any(|field| matches!(field, ItemField::Group));
+    let name = fields.iter().any(|field| matches!(field, ItemField::Name));
+    let title = fields.iter().any(|field| matches!(field, ItemField::Title));
+    for playlist_group in playlist {
+        if group {
+            if let Some(value) = transliterate(&playlist_group.title) {
+                playlist_group.title = value;
+            }
+        }
+        for channel in &playlist_group.channels {
+            let mut header = channel.header.borrow_mut();
+            if group {
+                if let Some(value) = transliterate(&header.group) {
+                    header.group = value;
+                }
+            }
+            if name {
+                if let Some(value) = transliterate(&header.name) {
+                    header.name = value;
+                }
+            }
+            if title {
+                if let Some(value) = transliterate(&header.title) {
+                    header.title = value;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::cmp::Ordering;
+    use std::rc::Rc;
+
+    use crate::model::config::{ConfigSort, ConfigTarget, ItemField, SortCollation};
+    use crate::model::playlist::{PlaylistGroup, PlaylistItem, PlaylistItemHeader, XtreamCluster};
+    use crate::processing::collation::{collate, transliterate_playlist};
+
+    #[test]
+    fn test_collate() {
+        let mut sort = ConfigSort { collation: Some(SortCollation { locale: Some("en".to_string()), numeric: true, ignore_articles: true, ..SortCollation::default() }), ..ConfigSort::default() };
+        sort.prepare().unwrap();
+        let collation = sort.collation.as_ref().unwrap();
+        let mut names = vec!["The Wire", "Channel 10", "channel 2", "Ärzte", "Theater", "Zorro"];
+        names.sort_by(|a, b| collate(collation, a, b));
+        assert_eq!(names, vec!["Ärzte", "channel 2", "Channel 10", "Theater", "The Wire", "Zorro"]);
+        assert_eq!(collate(&SortCollation::default(), "b", "B"), Ordering::Greater);
+    }
+
+    #[test]
+    fn test_transliterate_playlist() {
+        let target = ConfigTarget { transliterate: Some(vec![ItemField::Name, ItemField::Group]), ..ConfigTarget::default() };
+        let header = PlaylistItemHeader { name: Rc::new("Первый канал".to_string()), title: Rc::new("Первый".to_string()), group: Rc::new("Россия".to_string()), ..PlaylistItemHeader::default() };
+        let mut playlist = vec![PlaylistGroup { id: 1, title: Rc::new("Россия".to_string()), channels: vec![PlaylistItem { header: RefCell::new(header) }], xtream_cluster: XtreamCluster::Live }];
+        transliterate_playlist(&target, &mut playlist);
+        assert_eq!(playlist[0].title.as_str(), "Rossiia");
+        let header = playlist[0].channels[0].header.borrow();
+        assert_eq!(header.name.as_str(), "Pervyi kanal");
+        assert_eq!(header.group.as_str(), "Rossiia");
+        assert_eq!(header.title.as_str(), "Первый");
+    }
+}
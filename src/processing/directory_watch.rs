@@ -0,0 +1,136 @@
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, SystemTime};
+
+use log::{debug, error, info};
+use notify_debouncer_mini::notify::RecursiveMode;
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult};
+
+use crate::model::config::{Config, ConfigInput, InputType};
+use crate::processing::input_source::{get_input_directory, list_m3u_files};
+use crate::processing::job_queue::JobQueue;
+use crate::repository::job_repository::JobTrigger;
+
+/// Seconds a watched directory has to be unchanged before its playlists are processed,
+/// a playlist which is still written by another tool is not processed.
+const DIRECTORY_WATCH_DEBOUNCE_SECS: u64 = 2;
+
+type DirectorySignature = Vec<(PathBuf, u64, Option<SystemTime>)>;
+
+struct WatchedDirectory {
+    path: PathBuf,
+    input: ConfigInput,
+    targets: Vec<String>,
+    signature: DirectorySignature,
+}
+
+/// The playlists of the directory with their size and modification time.
+fn get_directory_signature(path: &Path, input: &ConfigInput) -> DirectorySignature {
+    list_m3u_files(path, input).unwrap_or_default().into_iter().map(|file| {
+        let metadata = std::fs::metadata(&file).ok();
+        let size = metadata.as_ref().map_or(0, std::fs::Metadata::len);
+        let modified = metadata.and_then(|meta| meta.modified().ok());
+        (file, size, modified)
+    }).collect()
+}
+
+impl WatchedDirectory {
+    /// `true` if the playlists of the directory changed since the last call,
+    /// events for files which are not read by the input are ignored.
+    fn refresh(&mut self) -> bool {
+        let signature = get_directory_signature(&self.path, &self.input);
+        if signature == self.signature {
+            return false;
+        }
+        self.signature = signature;
+        true
+    }
+}
+
+/// Queues a processing of the enabled targets of the source when a playlist of a `directory` input
+/// is added, changed or removed. Changes are processed once the directory is unchanged for
+/// `DIRECTORY_WATCH_DEBOUNCE_SECS` seconds.
+pub fn start_directory_watcher(cfg: &Config, jobs: &Arc<JobQueue>) {
+    let mut watched: Vec<WatchedDirectory> = vec![];
+    for source in &cfg.sources {
+        let targets: Vec<String> = source.targets.iter().filter(|target| target.enabled).map(|target| target.name.clone()).collect();
+        if targets.is_empty() {
+            continue;
+        }
+        for input in source.inputs.iter().filter(|input| input.enabled && input.input_type == InputType::Directory) {
+            if let Some(path) = get_input_directory(cfg, input) {
+                let signature = get_directory_signature(&path, input);
+                watched.push(WatchedDirectory { path, input: input.clone(), targets: targets.clone(), signature });
+            }
+        }
+    }
+    if watched.is_empty() {
+        return;
+    }
+
+    let (tx, rx) = mpsc::channel::<DebounceEventResult>();
+    let mut debouncer = match new_debouncer(Duration::from_secs(DIRECTORY_WATCH_DEBOUNCE_SECS), tx) {
+        Ok(debouncer) => debouncer,
+        Err(err) => {
+            error!("Failed to create directory watcher: {err}");
+            return;
+        }
+    };
+    watched.retain(|directory| match debouncer.watcher().watch(&directory.path, RecursiveMode::NonRecursive) {
+        Ok(()) => {
+            info!("Watching directory {}", directory.path.display());
+            true
+        }
+        Err(err) => {
+            error!("Failed to watch directory {}: {err}", directory.path.display());
+            false
+        }
+    });
+    if watched.is_empty() {
+        return;
+    }
+
+    let jobs = Arc::clone(jobs);
+    std::thread::spawn(move || {
+        // the debouncer stops watching when it is dropped
+        let _debouncer = debouncer;
+        for result in rx {
+            match result {
+                Ok(events) => {
+                    for directory in &mut watched {
+                        let affected = events.iter().any(|event| event.path.starts_with(&directory.path));
+                        if affected && directory.refresh() {
+                            debug!("Directory {} changed", directory.path.display());
+                            jobs.enqueue(JobTrigger::Watch, Some(directory.targets.clone()), None);
+                        }
+                    }
+                }
+                Err(err) => error!("Directory watcher failed: {err}"),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::config::ConfigInput;
+    use crate::processing::directory_watch::{get_directory_signature, WatchedDirectory};
+    use crate::utils::test_utils::create_temp_dir;
+
+    #[test]
+    fn test_directory_watch_refresh() {
+        let temp_dir = create_temp_dir("directory_watch");
+        let dir = temp_dir.path();
+        let input = ConfigInput::default();
+        let signature = get_directory_signature(dir, &input);
+        let mut watched = WatchedDirectory { path: dir.to_path_buf(), input, targets: vec![], signature };
+        assert!(!watched.refresh());
+        std::fs::write(dir.join("ignored.txt"), "text").unwrap();
+        assert!(!watched.refresh());
+        std::fs::write(dir.join("a.m3u"), "#EXTM3U\n").unwrap();
+        assert!(watched.refresh());
+        assert!(!watched.refresh());
+        std::fs::remove_file(dir.join("a.m3u")).unwrap();
+        assert!(watched.refresh());
+    }
+}
@@ -0,0 +1,139 @@
+use std::io::Write;
+use std::path::PathBuf;
+
+use log::{debug, error};
+
+use crate::filter::Filter;
+use crate::model::config::{Config, ConfigTarget};
+use crate::model::playlist::{PlaylistGroup, XtreamCluster};
+use crate::processing::post_process::{item_from_json, item_to_json, regroup_items};
+use crate::repository::storage::{get_target_storage_path, hex_encode};
+use crate::utils::file_utils;
+
+const INCREMENTAL_DIR: &str = "incremental";
+
+/// Hash of the downloaded playlist of an input.
+pub fn input_content_hash(playlist: &[PlaylistGroup]) -> [u8; 32] {
+    let mut hasher = blake3::Hasher::new();
+    for group in playlist {
+        hasher.update(group.title.as_bytes());
+        for channel in &group.channels {
+            if let Ok(content) = item_to_json(&channel.header.borrow()) {
+                hasher.update(&content);
+            }
+        }
+    }
+    hasher.finalize().into()
+}
+
+/// The cache key changes with the input content and with the target definition, its mappings, the templates and the filter files.
+fn get_cache_key(cfg: &Config, target: &ConfigTarget, input_hash: &[u8; 32]) -> String {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(input_hash);
+    for content in [serde_json::to_vec(target), serde_json::to_vec(&target.t_mapping), serde_json::to_vec(&cfg.templates)] {
+        hasher.update(&content.unwrap_or_default());
+    }
+    // the target is processed again when a filter file changed
+    for file in target.t_filter.iter().flat_map(Filter::get_files) {
+        hasher.update(&file.content_hash());
+    }
+    hex_encode(hasher.finalize().as_bytes())
+}
+
+fn get_cache_path(cfg: &Config, target: &ConfigTarget, input_id: u16) -> Option<PathBuf> {
+    get_target_storage_path(cfg, &target.name).map(|path| path.join(INCREMENTAL_DIR).join(format!("input_{input_id}.jsonl")))
+}
+
+/// Returns the processed playlist of the previous run if neither the input nor the target changed.
+pub fn incremental_load(cfg: &Config, target: &ConfigTarget, input_id: u16, input_hash: &[u8; 32]) -> Option<Vec<PlaylistGroup>> {
+    let path = get_cache_path(cfg, target, input_id)?;
+    let content = std::fs::read_to_string(&path).ok()?;
+    let mut lines = content.lines();
+    if lines.next()? != get_cache_key(cfg, target, input_hash) {
+        return None;
+    }
+    let mut items = vec![];
+    for line in lines {
+        match item_from_json(line.as_bytes()) {
+            Ok(item) => items.push(item),
+            Err(err) => {
+                error!("Invalid incremental cache {path:?}: {err}");
+                return None;
+            }
+        }
+    }
+    debug!("Reusing processed input {input_id} for target {}", target.name);
+    Some(regroup_items(items.into_iter(), &[]))
+}
+
+/// Returns the processed items of the previous run which are not part of the refreshed clusters, the content hash is not checked.
+/// Used by cluster refreshes to keep the other clusters of the input.
+pub fn incremental_load_other_clusters(cfg: &Config, target: &ConfigTarget, input_id: u16, clusters: &[XtreamCluster]) -> Option<Vec<PlaylistGroup>> {
+    let path = get_cache_path(cfg, target, input_id)?;
+    let content = std::fs::read_to_string(&path).ok()?;
+    let mut items = vec![];
+    for line in content.lines().skip(1) {
+        match item_from_json(line.as_bytes()) {
+            Ok(item) => {
+                if !clusters.contains(&item.xtream_cluster) {
+                    items.push(item);
+                }
+            }
+            Err(err) => {
+                error!("Invalid incremental cache {path:?}: {err}");
+                return None;
+            }
+        }
+    }
+    Some(regroup_items(items.into_iter(), &[]))
+}
+
+/// Stores the processed playlist of the input for the next run.
+pub fn incremental_store(cfg: &Config, target: &ConfigTarget, input_id: u16, input_hash: &[u8; 32], playlist: &[PlaylistGroup]) {
+    let Some(path) = get_cache_path(cfg, target, input_id) else { return };
+    let result = path.parent().map_or(Ok(()), std::fs::create_dir_all).and_then(|()| {
+        file_utils::write_atomic(&path, |writer| {
+            writer.write_all(get_cache_key(cfg, target, input_hash).as_bytes())?;
+            writer.write_all(b"\n")?;
+            for channel in playlist.iter().flat_map(|group| &group.channels) {
+                writer.write_all(&item_to_json(&channel.header.borrow())?)?;
+                writer.write_all(b"\n")?;
+            }
+            Ok(())
+        })
+    });
+    if let Err(err) = result {
+        error!("Failed to write incremental cache {path:?}: {err}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::model::config::{Config, ConfigTarget};
+    use crate::model::playlist::{PlaylistGroup, PlaylistItem, PlaylistItemHeader, PlaylistItemType, XtreamCluster};
+    use crate::processing::incremental::{incremental_load, incremental_store, input_content_hash};
+    use crate::utils::test_utils::create_temp_dir;
+
+    #[test]
+    fn test_incremental_cache() {
+        let temp_dir = create_temp_dir("incremental");
+        let working_dir = temp_dir.path();
+        let cfg = Config { working_dir: working_dir.to_str().unwrap().to_string(), ..Config::default() };
+        let mut target = ConfigTarget { name: "tv".to_string(), filter: "Group ~ \"DE\"".to_string(), ..ConfigTarget::default() };
+        let header = PlaylistItemHeader { name: Rc::new("News".to_string()), group: Rc::new("DE".to_string()), item_type: PlaylistItemType::Video, ..PlaylistItemHeader::default() };
+        let playlist = vec![PlaylistGroup { id: 7, title: Rc::new("DE".to_string()), channels: vec![PlaylistItem { header: RefCell::new(header) }], xtream_cluster: XtreamCluster::Video }];
+        let hash = input_content_hash(&playlist);
+
+        incremental_store(&cfg, &target, 1, &hash, &playlist);
+        let cached = incremental_load(&cfg, &target, 1, &hash).unwrap();
+        assert_eq!(cached.len(), 1);
+        assert_eq!(cached[0].channels[0].header.borrow().item_type, PlaylistItemType::Video);
+        assert!(incremental_load(&cfg, &target, 1, &[0; 32]).is_none());
+        assert!(incremental_load(&cfg, &target, 2, &hash).is_none());
+        target.filter = "Group ~ \"FR\"".to_string();
+        assert!(incremental_load(&cfg, &target, 1, &hash).is_none());
+    }
+}
@@ -0,0 +1,249 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, RwLock};
+
+use futures::future::LocalBoxFuture;
+use futures::FutureExt;
+use log::warn;
+use serde::Serialize;
+
+use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorCategory, M3uFilterErrorKind};
+use crate::model::config::{Config, ConfigInput, InputType, InputUserInfo};
+use crate::model::playlist::{PlaylistGroup, XtreamCluster};
+use crate::processing::{m3u_parser, stalker};
+use crate::utils::download::{self, prepare_file_path};
+use crate::utils::file_utils;
+use crate::utils::multi_file_reader::{expand_globs, MultiFileReader};
+use crate::utils::request_utils::mask_sensitive_info;
+
+pub type InputResult = (Vec<PlaylistGroup>, Vec<M3uFilterError>);
+
+/// What a source needs from the processing to fetch an input.
+pub struct InputContext<'a> {
+    pub cfg: &'a Config,
+    /// only the listed clusters are downloaded, all clusters if `None`
+    pub clusters: Option<&'a [XtreamCluster]>,
+}
+
+/// Acquires the playlist of an input.
+/// The http requests of all sources are retried as configured in the `http` settings of the input,
+/// the downloaded content is written to the `persist` file of the input.
+pub trait InputSource {
+    fn fetch<'a>(&'a self, ctx: &'a InputContext<'a>, input: &'a ConfigInput) -> LocalBoxFuture<'a, InputResult>;
+}
+
+/// M3u playlist from an url or a local file.
+struct M3uSource;
+
+impl InputSource for M3uSource {
+    fn fetch<'a>(&'a self, ctx: &'a InputContext<'a>, input: &'a ConfigInput) -> LocalBoxFuture<'a, InputResult> {
+        download::get_m3u_playlist(ctx.cfg, input, &ctx.cfg.working_dir).boxed_local()
+    }
+}
+
+/// The playlists of a local directory, merged in the order of the `files` patterns of the input
+/// or all `.m3u` and `.m3u8` files in the order of their file names.
+struct DirectorySource {
+    path: PathBuf,
+}
+
+fn is_m3u_file(path: &Path) -> bool {
+    path.is_file() && path.extension().and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("m3u") || ext.eq_ignore_ascii_case("m3u8"))
+}
+
+/// The files matching the `files` patterns of the input, the `.m3u` and `.m3u8` files of the directory sorted by name otherwise.
+pub fn list_m3u_files(path: &Path, input: &ConfigInput) -> std::io::Result<Vec<PathBuf>> {
+    if let Some(patterns) = &input.files {
+        return Ok(expand_globs(path, patterns));
+    }
+    let mut files: Vec<PathBuf> = fs::read_dir(path)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|file| is_m3u_file(file))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Unreadable files are skipped with a warning.
+fn read_m3u_directory(path: &Path, input: &ConfigInput) -> Result<String, M3uFilterError> {
+    let read_error = |err: std::io::Error| {
+        let message = format!("Failed to read directory {}", path.to_str().unwrap_or("?"));
+        M3uFilterError::from_source(M3uFilterErrorKind::Notify, M3uFilterErrorCategory::Download, err, message)
+    };
+    let files = list_m3u_files(path, input).map_err(read_error)?;
+    if files.is_empty() {
+        return Err(M3uFilterError::new(M3uFilterErrorKind::Notify, format!("No m3u files in directory {}", path.to_str().unwrap_or("?")))
+            .with_category(M3uFilterErrorCategory::Download));
+    }
+    let mut content = vec![];
+    MultiFileReader::new_skip_unreadable(&files).read_to_end(&mut content).map_err(read_error)?;
+    Ok(String::from_utf8_lossy(&content).into_owned())
+}
+
+impl InputSource for DirectorySource {
+    fn fetch<'a>(&'a self, ctx: &'a InputContext<'a>, input: &'a ConfigInput) -> LocalBoxFuture<'a, InputResult> {
+        async move {
+            match read_m3u_directory(&self.path, input) {
+                Ok(content) => {
+                    file_utils::persist_file(prepare_file_path(input.persist.as_ref(), &ctx.cfg.working_dir, ""), &content);
+                    (m3u_parser::parse_m3u(ctx.cfg, input, content.lines()), vec![])
+                }
+                Err(err) => (vec![], vec![err]),
+            }
+        }.boxed_local()
+    }
+}
+
+struct XtreamSource;
+
+impl InputSource for XtreamSource {
+    fn fetch<'a>(&'a self, ctx: &'a InputContext<'a>, input: &'a ConfigInput) -> LocalBoxFuture<'a, InputResult> {
+        download::get_xtream_playlist(input, &ctx.cfg.working_dir, ctx.clusters).boxed_local()
+    }
+}
+
+/// Live channels of a stalker (ministra) portal.
+struct StalkerSource;
+
+impl InputSource for StalkerSource {
+    fn fetch<'a>(&'a self, ctx: &'a InputContext<'a>, input: &'a ConfigInput) -> LocalBoxFuture<'a, InputResult> {
+        async move {
+            if ctx.clusters.is_some_and(|clusters| !clusters.contains(&XtreamCluster::Live)) {
+                return (vec![], vec![]);
+            }
+            match stalker::get_stalker_playlist(input, &ctx.cfg.working_dir).await {
+                Ok(groups) => (groups, vec![]),
+                Err(err) => (vec![], vec![err]),
+            }
+        }.boxed_local()
+    }
+}
+
+/// The local path of the input url, relative paths are resolved against the working dir.
+pub fn get_input_directory(cfg: &Config, input: &ConfigInput) -> Option<PathBuf> {
+    file_utils::get_file_path(&cfg.working_dir, Some(PathBuf::from(&input.url)))
+}
+
+/// The source of an input, only `directory` inputs read all playlists of a local directory.
+pub fn get_input_source(cfg: &Config, input: &ConfigInput) -> Box<dyn InputSource> {
+    match input.input_type {
+        InputType::M3u => Box::new(M3uSource),
+        InputType::Xtream => Box::new(XtreamSource),
+        InputType::Stalker => Box::new(StalkerSource),
+        InputType::Directory => Box::new(DirectorySource { path: get_input_directory(cfg, input).unwrap_or_default() }),
+    }
+}
+
+/// The source the last playlist of an input was fetched from.
+#[derive(Debug, Clone, Serialize)]
+pub struct InputSourceStatus {
+    pub input_id: u16,
+    pub input_name: Option<String>,
+    /// the masked url of the used source, `None` if all sources failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// index of the used entry of the `failover` list, `None` for the primary url
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failover: Option<usize>,
+    /// the errors of the sources tried before
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+    pub fetched_at: i64,
+}
+
+/// Last fetched source per input id
+static INPUT_SOURCE_STATUS: LazyLock<RwLock<HashMap<u16, InputSourceStatus>>> = LazyLock::new(|| RwLock::new(HashMap::new()));
+
+/// Returns the source of the last fetched playlist of all inputs.
+pub fn get_input_source_status_list() -> Vec<InputSourceStatus> {
+    let mut result: Vec<InputSourceStatus> = INPUT_SOURCE_STATUS.read().unwrap().values().cloned().collect();
+    result.sort_by_key(|status| status.input_id);
+    result
+}
+
+fn get_active_failover(input: &ConfigInput) -> Option<usize> {
+    input.failover.as_ref()?;
+    INPUT_SOURCE_STATUS.read().ok()?.get(&input.id).and_then(|status| status.failover)
+}
+
+/// The user info of the source the last playlist was fetched from, stream and info urls have to use the same account.
+pub fn get_active_user_info(input: &ConfigInput) -> Option<InputUserInfo> {
+    match get_active_failover(input).and_then(|index| input.get_failover_input(index)) {
+        Some(alternate) => alternate.get_user_info(),
+        None => input.get_user_info(),
+    }
+}
+
+fn set_input_source_status(input: &ConfigInput, url: Option<&str>, failover: Option<usize>, errors: &[M3uFilterError]) {
+    let status = InputSourceStatus {
+        input_id: input.id,
+        input_name: input.name.clone(),
+        url: url.map(mask_sensitive_info),
+        failover,
+        errors: errors.iter().map(|err| mask_sensitive_info(&err.to_string())).collect(),
+        fetched_at: chrono::Utc::now().timestamp(),
+    };
+    if let Ok(mut status_list) = INPUT_SOURCE_STATUS.write() {
+        status_list.insert(input.id, status);
+    }
+}
+
+/// Fetches the playlist of the input. If the download fails or the playlist is empty,
+/// the `failover` urls of the input are tried in order, the first non-empty playlist is taken.
+/// The errors of the failed sources are only returned if no source delivered a playlist.
+pub async fn fetch_input(ctx: &InputContext<'_>, input: &ConfigInput) -> InputResult {
+    let (groups, errors) = get_input_source(ctx.cfg, input).fetch(ctx, input).await;
+    if !groups.is_empty() || input.failover.is_none() {
+        set_input_source_status(input, (!groups.is_empty()).then_some(input.url.as_str()), None, &errors);
+        return (groups, errors);
+    }
+    let input_name = input.name.as_deref().unwrap_or_default();
+    let mut failed_errors = errors;
+    let failover_count = input.failover.as_ref().map_or(0, Vec::len);
+    for index in 0..failover_count {
+        let Some(alternate) = input.get_failover_input(index) else { continue };
+        warn!("Input {input_name} failed, trying failover {}", mask_sensitive_info(&alternate.url));
+        let (groups, errors) = get_input_source(ctx.cfg, &alternate).fetch(ctx, &alternate).await;
+        if !groups.is_empty() {
+            set_input_source_status(input, Some(&alternate.url), Some(index), &failed_errors);
+            return (groups, errors);
+        }
+        failed_errors.extend(errors);
+    }
+    set_input_source_status(input, None, None, &failed_errors);
+    (vec![], failed_errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::config::{ConfigInput, ConfigInputFailover, InputType};
+    use crate::processing::input_source::{get_active_user_info, set_input_source_status};
+
+    #[test]
+    fn test_failover_input() {
+        let mut input = ConfigInput {
+            id: 4711,
+            input_type: InputType::Xtream,
+            url: "http://primary.tv".to_string(),
+            username: Some("user".to_string()),
+            password: Some("secret".to_string()),
+            failover: Some(vec![
+                ConfigInputFailover { url: " http://backup.tv ".to_string(), username: None, password: None },
+                ConfigInputFailover { url: "http://other.tv".to_string(), username: Some("other".to_string()), password: Some(String::new()) },
+            ]),
+            ..ConfigInput::default()
+        };
+        assert!(input.prepare(4711).is_ok());
+        let alternate = input.get_failover_input(1).unwrap();
+        assert_eq!((alternate.url.as_str(), alternate.username.as_deref(), alternate.password.as_deref()), ("http://other.tv", Some("other"), Some("secret")));
+        assert!(input.get_failover_input(2).is_none());
+
+        assert_eq!(get_active_user_info(&input).unwrap().base_url, "http://primary.tv");
+        set_input_source_status(&input, Some("http://backup.tv"), Some(0), &[]);
+        assert_eq!(get_active_user_info(&input).unwrap().base_url, "http://backup.tv");
+    }
+}
+
@@ -0,0 +1,360 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use actix_rt::System;
+use log::{error, info};
+use serde_json::json;
+use tokio::sync::{broadcast, Notify};
+
+use crate::model::config::{validate_targets, Config, ProcessTargets};
+use crate::model::playlist::XtreamCluster;
+use crate::processing::playlist_processor::exec_processing;
+use crate::repository::job_repository::{get_jobs_file_path, job_load, job_save, Job, JobStages, JobStatus, JobTrigger};
+use crate::utils::thread_limits::apply_processing_limits;
+
+/// Maximum number of finished jobs kept in the history.
+const JOB_HISTORY_SIZE: usize = 50;
+/// Maximum number of log lines of a job, older lines are dropped.
+const JOB_LOG_SIZE: usize = 1000;
+/// Number of events buffered for each subscriber, a slow subscriber misses the older events.
+const JOB_EVENTS_CAPACITY: usize = 256;
+const PROCESSING_THREAD_NAME: &str = "processing";
+
+#[derive(Debug, Copy, Clone)]
+pub enum JobStage {
+    Download = 0,
+    Parse = 1,
+    Filter = 2,
+    Write = 3,
+}
+
+/// Changes of the jobs, sent to the subscribers of `/api/v1/events`.
+#[derive(Debug, Clone)]
+pub enum JobEvent {
+    /// a job was queued, started or finished
+    Job(Job),
+    Progress { id: String, progress: JobStages },
+    Log { id: String, line: String },
+}
+
+impl JobEvent {
+    /// The event in the server-sent events format.
+    pub fn to_sse(&self) -> String {
+        let (name, data) = match self {
+            Self::Job(job) => ("job", serde_json::to_value(job).unwrap_or_default()),
+            Self::Progress { id, progress } => ("progress", json!({"id": id, "progress": progress})),
+            Self::Log { id, line } => ("log", json!({"id": id, "line": line})),
+        };
+        format!("event: {name}\ndata: {data}\n\n")
+    }
+}
+
+/// Progress, log and cancel flag of the running job, shared with the processing.
+/// `download` and `parse` advance per input, `filter` and `write` per target.
+pub struct JobProgress {
+    id: String,
+    events: broadcast::Sender<JobEvent>,
+    inputs: AtomicUsize,
+    targets: AtomicUsize,
+    done: [AtomicUsize; 4],
+    log: Mutex<Vec<String>>,
+    cancelled: AtomicBool,
+}
+
+impl JobProgress {
+    pub fn new(id: &str, events: broadcast::Sender<JobEvent>) -> Self {
+        Self {
+            id: id.to_string(),
+            events,
+            inputs: AtomicUsize::default(),
+            targets: AtomicUsize::default(),
+            done: Default::default(),
+            log: Mutex::default(),
+            cancelled: AtomicBool::default(),
+        }
+    }
+
+    fn send(&self, event: JobEvent) {
+        // without subscribers the event is dropped
+        let _ = self.events.send(event);
+    }
+
+    pub fn set_totals(&self, inputs: usize, targets: usize) {
+        self.inputs.store(inputs, Ordering::Relaxed);
+        self.targets.store(targets, Ordering::Relaxed);
+    }
+
+    pub fn advance(&self, stage: JobStage) {
+        self.done[stage as usize].fetch_add(1, Ordering::Relaxed);
+        self.send(JobEvent::Progress { id: self.id.clone(), progress: self.stages() });
+    }
+
+    pub fn log(&self, message: &str) {
+        let line = format!("{} {message}", chrono::Local::now().format("%Y-%m-%d %H:%M:%S"));
+        if let Ok(mut log) = self.log.lock() {
+            if log.len() >= JOB_LOG_SIZE {
+                log.remove(0);
+            }
+            log.push(line.clone());
+        }
+        self.send(JobEvent::Log { id: self.id.clone(), line });
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    fn percent(&self, stage: JobStage) -> u8 {
+        let total = match stage {
+            JobStage::Download | JobStage::Parse => self.inputs.load(Ordering::Relaxed),
+            JobStage::Filter | JobStage::Write => self.targets.load(Ordering::Relaxed),
+        };
+        if total == 0 {
+            return 0;
+        }
+        let done = self.done[stage as usize].load(Ordering::Relaxed).min(total);
+        u8::try_from(done * 100 / total).unwrap_or(100)
+    }
+
+    fn stages(&self) -> JobStages {
+        JobStages {
+            download: self.percent(JobStage::Download),
+            parse: self.percent(JobStage::Parse),
+            filter: self.percent(JobStage::Filter),
+            write: self.percent(JobStage::Write),
+        }
+    }
+
+    fn lines(&self) -> Vec<String> {
+        self.log.lock().map(|log| log.clone()).unwrap_or_default()
+    }
+}
+
+/// Processing runs are queued as jobs and executed one after the other.
+/// The jobs are persisted in the working directory, queued jobs are continued after a restart.
+pub struct JobQueue {
+    cfg: Arc<Config>,
+    targets: Arc<ProcessTargets>,
+    path: PathBuf,
+    jobs: Mutex<Vec<Job>>,
+    running: Mutex<Option<(String, Arc<JobProgress>)>>,
+    notify: Notify,
+    events: broadcast::Sender<JobEvent>,
+}
+
+impl JobQueue {
+    pub fn new(cfg: Arc<Config>, targets: Arc<ProcessTargets>) -> Self {
+        let path = get_jobs_file_path(&cfg);
+        let mut jobs = job_load(&path);
+        for job in jobs.iter_mut().filter(|job| job.status == JobStatus::Running) {
+            job.status = JobStatus::Failed;
+            job.finished = Some(chrono::Utc::now().timestamp());
+            job.error = Some("Interrupted".to_string());
+        }
+        let (events, _) = broadcast::channel(JOB_EVENTS_CAPACITY);
+        Self { cfg, targets, path, jobs: Mutex::new(jobs), running: Mutex::new(None), notify: Notify::new(), events }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<JobEvent> {
+        self.events.subscribe()
+    }
+
+    fn send(&self, job: &Job) {
+        let _ = self.events.send(JobEvent::Job(self.with_progress(job.clone())));
+    }
+
+    /// Drops the oldest finished jobs exceeding the history size and persists the jobs.
+    fn save(&self, jobs: &mut Vec<Job>) {
+        let finished = jobs.iter().filter(|job| job.status.is_finished()).count();
+        let mut remove = finished.saturating_sub(JOB_HISTORY_SIZE);
+        jobs.retain(|job| {
+            if remove > 0 && job.status.is_finished() {
+                remove -= 1;
+                return false;
+            }
+            true
+        });
+        if let Err(err) = job_save(&self.path, jobs) {
+            error!("{err}");
+        }
+    }
+
+    fn update<F: FnOnce(&mut Job)>(&self, id: &str, change: F) {
+        if let Ok(mut jobs) = self.jobs.lock() {
+            if let Some(job) = jobs.iter_mut().find(|job| job.id == id) {
+                change(job);
+                self.send(job);
+            }
+            self.save(&mut jobs);
+        }
+    }
+
+    pub fn enqueue(&self, trigger: JobTrigger, targets: Option<Vec<String>>, clusters: Option<Vec<XtreamCluster>>) -> Job {
+        let job = Job {
+            id: format!("{:016x}", rand::random::<u64>()),
+            trigger,
+            targets,
+            clusters,
+            status: JobStatus::Queued,
+            created: chrono::Utc::now().timestamp(),
+            started: None,
+            finished: None,
+            progress: JobStages::default(),
+            log: vec![],
+            error: None,
+            errors: vec![],
+        };
+        if let Ok(mut jobs) = self.jobs.lock() {
+            jobs.push(job.clone());
+            self.save(&mut jobs);
+        }
+        self.send(&job);
+        self.notify.notify_one();
+        job
+    }
+
+    /// The running job contains the current progress and log.
+    fn with_progress(&self, mut job: Job) -> Job {
+        if let Some((_, progress)) = self.running.lock().ok().as_ref().and_then(|running| running.as_ref()).filter(|(id, _)| *id == job.id) {
+            job.progress = progress.stages();
+            job.log = progress.lines();
+        }
+        job
+    }
+
+    pub fn list(&self) -> Vec<Job> {
+        let jobs = self.jobs.lock().map(|jobs| jobs.clone()).unwrap_or_default();
+        jobs.into_iter().map(|job| self.with_progress(job)).collect()
+    }
+
+    pub fn get(&self, id: &str) -> Option<Job> {
+        let job = self.jobs.lock().ok()?.iter().find(|job| job.id == id).cloned()?;
+        Some(self.with_progress(job))
+    }
+
+    /// Unix timestamp of the last completed refresh.
+    pub fn last_completed(&self) -> Option<i64> {
+        self.jobs.lock().ok()?.iter().filter(|job| job.status == JobStatus::Completed).filter_map(|job| job.finished).max()
+    }
+
+    /// A queued job is cancelled immediately, a running job stops before the next input or target.
+    pub fn cancel(&self, id: &str) {
+        if let Some((_, progress)) = self.running.lock().ok().as_ref().and_then(|running| running.as_ref()).filter(|(running_id, _)| running_id == id) {
+            progress.cancel();
+            progress.log("Cancel requested");
+            return;
+        }
+        self.update(id, |job| {
+            if job.status == JobStatus::Queued {
+                job.status = JobStatus::Cancelled;
+                job.finished = Some(chrono::Utc::now().timestamp());
+            }
+        });
+    }
+
+    fn take_next(&self) -> Option<Job> {
+        let mut jobs = self.jobs.lock().ok()?;
+        let job = jobs.iter_mut().find(|job| job.status == JobStatus::Queued)?;
+        job.status = JobStatus::Running;
+        job.started = Some(chrono::Utc::now().timestamp());
+        let job = job.clone();
+        self.save(&mut jobs);
+        self.send(&job);
+        Some(job)
+    }
+
+    fn get_process_targets(&self, job: &Job) -> Result<ProcessTargets, String> {
+        let mut targets = match job.targets.as_ref() {
+            None => Ok(ProcessTargets::clone(&self.targets)),
+            Some(names) => validate_targets(Some(names).filter(|names| !names.is_empty()), &self.cfg.sources),
+        }.map_err(|err| err.message)?;
+        targets.clusters.clone_from(&job.clusters);
+        targets.force_refresh |= job.trigger == JobTrigger::Integrity;
+        Ok(targets)
+    }
+
+    async fn run(&self, job: Job) {
+        info!("Processing job {}", job.id);
+        let progress = Arc::new(JobProgress::new(&job.id, self.events.clone()));
+        if let Ok(mut running) = self.running.lock() {
+            *running = Some((job.id.clone(), Arc::clone(&progress)));
+        }
+        let mut errors = vec![];
+        let (status, error) = match self.get_process_targets(&job) {
+            Ok(mut targets) => {
+                targets.progress = Some(Arc::clone(&progress));
+                let summary = exec_processing(Arc::clone(&self.cfg), Arc::new(targets)).await;
+                let exit_code = summary.exit_code();
+                errors = summary.errors;
+                if progress.is_cancelled() {
+                    (JobStatus::Cancelled, None)
+                } else if exit_code == 0 {
+                    (JobStatus::Completed, None)
+                } else {
+                    (JobStatus::Failed, errors.first().map(|err| err.message.clone()))
+                }
+            }
+            Err(err) => {
+                progress.log(&err);
+                (JobStatus::Failed, Some(err))
+            }
+        };
+        if let Ok(mut running) = self.running.lock() {
+            *running = None;
+        }
+        self.update(&job.id, |job| {
+            job.status = status;
+            job.finished = Some(chrono::Utc::now().timestamp());
+            job.progress = progress.stages();
+            job.log = progress.lines();
+            job.error = error;
+            job.errors = errors;
+        });
+    }
+}
+
+/// Runs the jobs on their own thread and runtime, a refresh doesn't slow down the api server.
+/// The processing thread and the threads it starts have the priority and cpus of the `processing` config.
+pub fn start_job_worker(queue: &Arc<JobQueue>) {
+    let queue = Arc::clone(queue);
+    let spawned = thread::Builder::new().name(PROCESSING_THREAD_NAME.to_string()).spawn(move || {
+        apply_processing_limits(&queue.cfg.processing.clone().unwrap_or_default());
+        System::new().block_on(async move {
+            loop {
+                match queue.take_next() {
+                    Some(job) => queue.run(job).await,
+                    None => queue.notify.notified().await,
+                }
+            }
+        });
+    });
+    if let Err(err) = spawned {
+        error!("Failed to start processing thread: {err}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::processing::job_queue::{JobProgress, JobStage};
+
+    #[test]
+    fn test_job_progress() {
+        let (events, _) = tokio::sync::broadcast::channel(16);
+        let progress = JobProgress::new("test", events);
+        progress.set_totals(4, 2);
+        progress.advance(JobStage::Download);
+        progress.advance(JobStage::Download);
+        progress.advance(JobStage::Parse);
+        progress.advance(JobStage::Write);
+        progress.advance(JobStage::Write);
+        progress.advance(JobStage::Write);
+        let stages = progress.stages();
+        assert_eq!((stages.download, stages.parse, stages.filter, stages.write), (50, 25, 0, 100));
+    }
+}
@@ -0,0 +1,343 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::Duration;
+
+use ab_glyph::{point, Font, FontVec, Glyph, PxScale, ScaleFont};
+use futures::StreamExt;
+use image::{DynamicImage, ImageFormat, Rgba, RgbaImage};
+use log::{debug, error, info, log_enabled, Level};
+
+use crate::api::api_utils::get_default_base_url;
+use crate::model::config::{Config, ConfigInput, LogoNormalizeConfig, LogoPlaceholderConfig};
+use crate::model::playlist::{M3uPlaylistItem, PlaylistGroup};
+use crate::repository::storage::hash_string_as_hex;
+use crate::utils::file_utils;
+use crate::utils::request_utils::{get_http_client, get_request_headers, mask_sensitive_info};
+
+const LOGO_CACHE_DIR: &str = "logos";
+const LOGO_DOWNLOAD_CONCURRENCY: usize = 8;
+const LOGO_DOWNLOAD_TIMEOUT_SECS: u64 = 30;
+/// Text height relative to the placeholder height
+const PLACEHOLDER_TEXT_HEIGHT: f32 = 0.3;
+/// Text width relative to the placeholder width
+const PLACEHOLDER_TEXT_WIDTH: f32 = 0.9;
+
+fn get_logo_cache_dir(cfg: &Config) -> PathBuf {
+    PathBuf::from(&cfg.working_dir).join(LOGO_CACHE_DIR)
+}
+
+/// The logo id is the hash of the provider url.
+pub fn get_logo_id(url: &str) -> String {
+    hash_string_as_hex(url)
+}
+
+/// The placeholder id is the hash of the channel name.
+fn get_placeholder_id(name: &str) -> String {
+    hash_string_as_hex(&format!("placeholder:{name}"))
+}
+
+fn is_logo_url(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+/// Returns the path of a cached logo, `None` if the id is invalid or the logo is not cached.
+pub fn get_cached_logo_path(cfg: &Config, logo_id: &str) -> Option<PathBuf> {
+    if logo_id.is_empty() || !logo_id.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let path = get_logo_cache_dir(cfg).join(logo_id);
+    if path.is_file() { Some(path) } else { None }
+}
+
+/// Returns the cached logo of a channel, the placeholder for a channel without logo.
+/// `None` if the logo cache is disabled or the logo is not cached.
+pub fn get_cached_channel_logo_path(cfg: &Config, logo: &str, name: &str) -> Option<PathBuf> {
+    let logo_cache = cfg.logo_cache.as_ref().filter(|logo_cache| logo_cache.enabled)?;
+    let logo_id = if logo.is_empty() {
+        if logo_cache.placeholder.is_none() || name.trim().is_empty() {
+            return None;
+        }
+        get_placeholder_id(name.trim())
+    } else if is_logo_url(logo) {
+        get_logo_id(logo)
+    } else {
+        return None;
+    };
+    get_cached_logo_path(cfg, &logo_id)
+}
+
+/// The file extension of the logo for its detected image type, `None` if the type is unknown.
+pub fn get_logo_extension(content: &[u8]) -> Option<&'static str> {
+    match get_logo_mime_type(content).subtype().as_str() {
+        "png" => Some("png"),
+        "jpeg" => Some("jpg"),
+        "gif" => Some("gif"),
+        "webp" => Some("webp"),
+        "svg" => Some("svg"),
+        _ => None,
+    }
+}
+
+/// Detects the image type from the first bytes of the logo.
+pub fn get_logo_mime_type(content: &[u8]) -> mime::Mime {
+    if content.starts_with(b"\x89PNG") {
+        mime::IMAGE_PNG
+    } else if content.starts_with(b"\xFF\xD8\xFF") {
+        mime::IMAGE_JPEG
+    } else if content.starts_with(b"GIF8") {
+        mime::IMAGE_GIF
+    } else if content.len() > 12 && content.starts_with(b"RIFF") && &content[8..12] == b"WEBP" {
+        "image/webp".parse().unwrap()
+    } else if content[..content.len().min(512)].windows(4).any(|w| w == b"<svg") {
+        mime::IMAGE_SVG
+    } else {
+        mime::APPLICATION_OCTET_STREAM
+    }
+}
+
+/// Rewrites provider logo urls to the `/logo/{id}` endpoint of the given server.
+/// Only logos which are already cached are rewritten, all others keep the provider url.
+pub struct LogoRewrite {
+    cache_dir: PathBuf,
+    base_url: String,
+    placeholder: bool,
+}
+
+impl LogoRewrite {
+    pub fn new(cfg: &Config, base_url: &str) -> Option<Self> {
+        cfg.logo_cache.as_ref().filter(|logo_cache| logo_cache.enabled).map(|logo_cache| Self {
+            cache_dir: get_logo_cache_dir(cfg),
+            base_url: base_url.to_string(),
+            placeholder: logo_cache.placeholder.is_some(),
+        })
+    }
+
+    /// The rewrite for the written m3u and epg files, the logos are served by the `default` server.
+    pub fn for_files(cfg: &Config) -> Option<Self> {
+        Self::new(cfg, &get_default_base_url(cfg)?)
+    }
+
+    fn get_logo_url(&self, logo_id: &str) -> Option<String> {
+        if self.cache_dir.join(logo_id).is_file() {
+            Some(format!("{}/logo/{logo_id}", self.base_url))
+        } else {
+            None
+        }
+    }
+
+    pub fn rewrite(&self, logo: &str) -> Option<String> {
+        if is_logo_url(logo) {
+            return self.get_logo_url(&get_logo_id(logo));
+        }
+        None
+    }
+
+    /// Returns the placeholder url for a channel without logo.
+    pub fn placeholder(&self, name: &str) -> Option<String> {
+        if self.placeholder && !name.trim().is_empty() {
+            return self.get_logo_url(&get_placeholder_id(name.trim()));
+        }
+        None
+    }
+
+    /// Rewrites `tvg-logo` and `tvg-logo-small` of the item, a channel without logo gets the placeholder.
+    pub fn rewrite_m3u_item(&self, m3u_pli: &mut M3uPlaylistItem) {
+        let logo = if m3u_pli.logo.is_empty() { self.placeholder(&m3u_pli.name) } else { self.rewrite(&m3u_pli.logo) };
+        if let Some(logo) = logo {
+            m3u_pli.logo = Rc::new(logo);
+        }
+        if let Some(logo_small) = self.rewrite(&m3u_pli.logo_small) {
+            m3u_pli.logo_small = Rc::new(logo_small);
+        }
+    }
+}
+
+/// Converts the logo to png, images larger than the max dimensions are downscaled.
+/// Returns `None` if the image format is not supported, e.g. svg.
+fn normalize_logo(content: &[u8], normalize: &LogoNormalizeConfig) -> Option<Vec<u8>> {
+    let image = image::load_from_memory(content).ok()?;
+    let image = if image.width() > normalize.max_width || image.height() > normalize.max_height {
+        image.thumbnail(normalize.max_width, normalize.max_height)
+    } else {
+        image
+    };
+    let mut result = Vec::new();
+    image.write_to(&mut Cursor::new(&mut result), ImageFormat::Png).ok()?;
+    Some(result)
+}
+
+fn layout_text(font: &FontVec, scale: PxScale, text: &str, x: f32, y: f32) -> (Vec<Glyph>, f32) {
+    let scaled_font = font.as_scaled(scale);
+    let mut glyphs = Vec::new();
+    let mut caret = x;
+    let mut last_glyph = None;
+    for c in text.chars() {
+        let glyph_id = scaled_font.glyph_id(c);
+        if let Some(last) = last_glyph {
+            caret += scaled_font.kern(last, glyph_id);
+        }
+        glyphs.push(glyph_id.with_scale_and_position(scale, point(caret, y + scaled_font.ascent())));
+        caret += scaled_font.h_advance(glyph_id);
+        last_glyph = Some(glyph_id);
+    }
+    (glyphs, caret - x)
+}
+
+fn blend(background: u8, color: u8, coverage: f32) -> u8 {
+    // coverage is in 0.0..=1.0, the result is always in the u8 range
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let value = (f32::from(background) + (f32::from(color) - f32::from(background)) * coverage).round() as u8;
+    value
+}
+
+/// Renders the channel name centered on a plain background.
+#[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+fn create_placeholder(font: &FontVec, placeholder: &LogoPlaceholderConfig, text: &str) -> Option<Vec<u8>> {
+    let width = placeholder.width as f32;
+    let height = placeholder.height as f32;
+    let [br, bg, bb] = placeholder.t_background;
+    let [cr, cg, cb] = placeholder.t_color;
+    let mut image = RgbaImage::from_pixel(placeholder.width, placeholder.height, Rgba([br, bg, bb, 255]));
+
+    // the text width grows linear with the scale, shrink long names to fit
+    let mut scale = height * PLACEHOLDER_TEXT_HEIGHT;
+    let (_, text_width) = layout_text(font, PxScale::from(scale), text, 0.0, 0.0);
+    if text_width > width * PLACEHOLDER_TEXT_WIDTH {
+        scale *= width * PLACEHOLDER_TEXT_WIDTH / text_width;
+    }
+    let text_height = font.as_scaled(PxScale::from(scale)).height();
+    let (_, text_width) = layout_text(font, PxScale::from(scale), text, 0.0, 0.0);
+    let (glyphs, _) = layout_text(font, PxScale::from(scale), text, (width - text_width) / 2.0, (height - text_height) / 2.0);
+
+    for glyph in glyphs {
+        if let Some(outlined) = font.outline_glyph(glyph) {
+            let bounds = outlined.px_bounds();
+            outlined.draw(|x, y, coverage| {
+                let px = bounds.min.x as i32 + x as i32;
+                let py = bounds.min.y as i32 + y as i32;
+                if let (Ok(px), Ok(py)) = (u32::try_from(px), u32::try_from(py)) {
+                    if px < placeholder.width && py < placeholder.height {
+                        let coverage = coverage.clamp(0.0, 1.0);
+                        let pixel = image.get_pixel_mut(px, py);
+                        pixel.0 = [blend(pixel.0[0], cr, coverage), blend(pixel.0[1], cg, coverage), blend(pixel.0[2], cb, coverage), 255];
+                    }
+                }
+            });
+        }
+    }
+
+    let mut result = Vec::new();
+    DynamicImage::ImageRgba8(image).write_to(&mut Cursor::new(&mut result), ImageFormat::Png).ok()?;
+    Some(result)
+}
+
+fn write_logo(path: &Path, content: &[u8]) -> bool {
+    if let Err(err) = file_utils::write_atomic(path, |writer| writer.write_all(content)) {
+        error!("Failed to write logo {}: {err}", path.to_str().unwrap_or("?"));
+        return false;
+    }
+    true
+}
+
+fn create_placeholders(placeholder: &LogoPlaceholderConfig, names: HashSet<(String, PathBuf)>) {
+    let font = match std::fs::read(&placeholder.font).map_err(|err| err.to_string())
+        .and_then(|data| FontVec::try_from_vec(data).map_err(|err| err.to_string())) {
+        Ok(font) => font,
+        Err(err) => {
+            error!("Failed to load placeholder font {}: {err}", &placeholder.font);
+            return;
+        }
+    };
+    let total = names.len();
+    let created = names.into_iter()
+        .filter(|(name, path)| create_placeholder(&font, placeholder, name).is_some_and(|content| write_logo(path, &content)))
+        .count();
+    info!("Logo placeholders created {created} of {total}");
+}
+
+/// The logo is downloaded with the client and the headers of the input of the channel.
+async fn download_logo(cfg: &Config, input: Option<&ConfigInput>, url: &str, path: &Path, normalize: Option<&LogoNormalizeConfig>) -> bool {
+    let request = get_http_client(cfg, input).get(url)
+        .headers(get_request_headers(input.map(|i| &i.headers), None))
+        .timeout(Duration::from_secs(LOGO_DOWNLOAD_TIMEOUT_SECS));
+    match request.send().await {
+        Ok(response) if response.status().is_success() => {
+            match response.bytes().await {
+                Ok(content) if !content.is_empty() => {
+                    match normalize.and_then(|normalize_cfg| normalize_logo(&content, normalize_cfg)) {
+                        Some(normalized) => write_logo(path, &normalized),
+                        None => write_logo(path, &content),
+                    }
+                }
+                _ => false,
+            }
+        }
+        Ok(response) => {
+            if log_enabled!(Level::Debug) {
+                debug!("Failed to download logo {}: {}", mask_sensitive_info(url), response.status());
+            }
+            false
+        }
+        Err(err) => {
+            if log_enabled!(Level::Debug) {
+                debug!("Failed to download logo {}: {err}", mask_sensitive_info(url));
+            }
+            false
+        }
+    }
+}
+
+/// Downloads all logos of the playlist which are not cached yet
+/// and creates the placeholders for channels without logo.
+pub async fn cache_logos(cfg: &Config, playlist: &[PlaylistGroup]) {
+    let Some(logo_cache) = cfg.logo_cache.as_ref().filter(|logo_cache| logo_cache.enabled) else {
+        return;
+    };
+    let cache_dir = get_logo_cache_dir(cfg);
+    if let Err(err) = std::fs::create_dir_all(&cache_dir) {
+        error!("Failed to create logo cache directory {}: {err}", cache_dir.to_str().unwrap_or("?"));
+        return;
+    }
+
+    // logo url with the cache path and the input of the first channel
+    let mut logos = HashMap::new();
+    let mut placeholders = HashSet::new();
+    for channel in playlist.iter().flat_map(|group| &group.channels) {
+        let header = channel.header.borrow();
+        for logo in [&header.logo, &header.logo_small] {
+            if is_logo_url(logo) {
+                let path = cache_dir.join(get_logo_id(logo));
+                if !path.exists() {
+                    logos.entry(logo.to_string()).or_insert((path, header.input_id));
+                }
+            }
+        }
+        let name = header.name.trim();
+        if logo_cache.placeholder.is_some() && header.logo.is_empty() && !name.is_empty() {
+            let path = cache_dir.join(get_placeholder_id(name));
+            if !path.exists() {
+                placeholders.insert((name.to_string(), path));
+            }
+        }
+    }
+    if let Some(placeholder) = logo_cache.placeholder.as_ref().filter(|_| !placeholders.is_empty()) {
+        create_placeholders(placeholder, placeholders);
+    }
+    if logos.is_empty() {
+        return;
+    }
+
+    let total = logos.len();
+    let downloaded = futures::stream::iter(logos)
+        .map(|(url, (path, input_id))| {
+            let input = cfg.get_input_by_id(input_id);
+            let normalize = logo_cache.normalize.as_ref();
+            async move { download_logo(cfg, input, &url, &path, normalize).await }
+        })
+        .buffer_unordered(LOGO_DOWNLOAD_CONCURRENCY)
+        .filter(|success| futures::future::ready(*success))
+        .count().await;
+    info!("Logo cache updated, downloaded {downloaded} of {total} logos");
+}
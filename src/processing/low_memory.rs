@@ -0,0 +1,690 @@
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::time::Instant;
+
+use log::{debug, error, info, log_enabled, Level};
+use serde::{Deserialize, Serialize};
+use unidecode::unidecode;
+
+use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorCategory, M3uFilterErrorKind};
+use crate::messaging::{send_webhook, WebhookEvent};
+use crate::model::config::{Config, ConfigInput, ConfigTarget, InputType, ProcessTargets, TargetType};
+use crate::model::playlist::{PlaylistGroup, PlaylistItem, PlaylistItemHeader, XtreamCluster};
+use crate::model::stats::{InputStats, PlaylistStats, TargetStats};
+use crate::model::xmltv::TVGuide;
+use crate::processing::affix_processor::get_affix_processor;
+use crate::processing::channel_alias::alias_channel;
+use crate::processing::channel_override::override_channel;
+use crate::processing::collation::transliterate_playlist;
+use crate::processing::input_source::{get_input_directory, list_m3u_files};
+use crate::processing::job_queue::JobStage;
+use crate::processing::m3u_parser::{consume_m3u, write_parse_report, M3uParseReport};
+use crate::processing::playlist_processor::{compare_channel_values, compare_group_titles, create_input_stat, finish_target, get_channel_sort_value,
+                                            get_processing_pipe, get_sequence_position, is_input_enabled, is_target_enabled, map_playlist_counter,
+                                            mark_adult_groups, with_category};
+use crate::processing::post_process::{item_from_json, item_to_json};
+use crate::processing::publisher::publish_target;
+use crate::processing::schedules_direct::merge_schedules_direct_epg;
+use crate::processing::tvheadend::tvheadend_update_network;
+use crate::processing::xmltv_parser::{apply_epg_timeshift, assign_channel_epg_timeshift, flatten_tvguide, EpgTimeshift};
+use crate::repository::alias_repository::alias_load;
+use crate::repository::epg_repository::epg_write;
+use crate::repository::m3u_repository::M3uPlaylistWriter;
+use crate::repository::manifest_repository::manifest_write;
+use crate::repository::override_repository::override_load;
+use crate::repository::persist_repository::persist_cleanup;
+use crate::repository::playlist_repository::assign_virtual_id;
+use crate::repository::search_repository::{get_search_index_path, search_entry_write};
+use crate::repository::storage::{ensure_target_storage_path, get_input_storage_path, get_target_id_mapping_file};
+use crate::repository::target_id_mapping::TargetIdMapping;
+use crate::repository::url_index::UrlIndex;
+use crate::utils::compressed_file_reader::CompressedFileReader;
+use crate::utils::download::{self, prepare_file_path};
+use crate::utils::external_sort::{ExternalSorter, SortedItems};
+use crate::utils::multi_file_reader::MultiFileReader;
+use crate::utils::request_utils::{self, mask_sensitive_info};
+
+/// The playlist of a m3u input downloaded in `low_memory` mode.
+const FILE_M3U_DOWNLOAD: &str = "playlist.m3u";
+
+static STAGE_FILE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Position of a channel in the playlist before sorting: input, group of the parsed playlist and channel.
+type Position = (u32, u32, u64);
+
+/// The playlist of an input on disk, it is read line by line for every target.
+enum InputFile {
+    /// a downloaded or local playlist, a temporary download is removed when dropped
+    File { path: PathBuf, temporary: bool },
+    /// the playlists of a directory input in their merge order
+    Directory(Vec<PathBuf>),
+}
+
+impl InputFile {
+    fn lines(&self) -> std::io::Result<impl Iterator<Item=String>> {
+        let reader: Box<dyn Read> = match self {
+            Self::File { path, .. } => Box::new(CompressedFileReader::new(path)?),
+            Self::Directory(files) => Box::new(MultiFileReader::new_skip_unreadable(files)),
+        };
+        Ok(BufReader::new(reader).split(b'\n').map_while(Result::ok)
+            .map(|line| String::from_utf8_lossy(&line).trim_end_matches('\r').to_string()))
+    }
+}
+
+impl Drop for InputFile {
+    fn drop(&mut self) {
+        if let Self::File { path, temporary: true } = self {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// A fetched input of the source.
+struct StreamedInput<'a> {
+    input: &'a ConfigInput,
+    file: InputFile,
+    epg: Option<TVGuide>,
+}
+
+fn download_error(message: String) -> M3uFilterError {
+    M3uFilterError::new(M3uFilterErrorKind::Notify, message).with_category(M3uFilterErrorCategory::Download)
+}
+
+fn accepts_item(input: &ConfigInput, header: &PlaylistItemHeader, clusters: Option<&[XtreamCluster]>) -> bool {
+    input.accepts_group(&header.group) && clusters.is_none_or(|clusters| clusters.contains(&header.xtream_cluster))
+}
+
+/// Downloads the playlist of a m3u input to disk, a directory input is read from its files.
+async fn fetch_input_file(cfg: &Config, input: &ConfigInput) -> Result<InputFile, M3uFilterError> {
+    let persist_file_path = prepare_file_path(input.persist.as_ref(), &cfg.working_dir, "");
+    if input.input_type == InputType::Directory {
+        let path = get_input_directory(cfg, input).unwrap_or_default();
+        let files = list_m3u_files(&path, input)
+            .map_err(|err| download_error(format!("Failed to read directory {}: {err}", path.to_str().unwrap_or("?"))))?;
+        if files.is_empty() {
+            return Err(download_error(format!("No m3u files in directory {}", path.to_str().unwrap_or("?"))));
+        }
+        if let Some(persist_path) = persist_file_path {
+            let result = File::create(&persist_path).and_then(|mut file| std::io::copy(&mut MultiFileReader::new_skip_unreadable(&files), &mut file));
+            if let Err(err) = result {
+                error!("cant persist to: {}  => {err}", persist_path.to_str().unwrap_or("?"));
+            }
+        }
+        return Ok(InputFile::Directory(files));
+    }
+    let download_path = match &persist_file_path {
+        Some(path) => path.clone(),
+        None => get_input_storage_path(input, &cfg.working_dir).map(|path| path.join(FILE_M3U_DOWNLOAD))
+            .map_err(|err| download_error(format!("Failed to create input storage: {err}")))?,
+    };
+    let path = request_utils::get_input_text_content_as_file(input, &cfg.working_dir, &input.url, Some(download_path.clone())).await?;
+    Ok(InputFile::File { temporary: persist_file_path.is_none() && path == download_path, path })
+}
+
+/// Reads the playlist once for the stats and the url index of the input, returns the group and channel count.
+fn scan_input(cfg: &Config, input: &ConfigInput, file: &InputFile, clusters: Option<&[XtreamCluster]>) -> std::io::Result<(usize, usize)> {
+    let mut report = M3uParseReport::default();
+    let mut groups = HashSet::new();
+    let mut channel_count = 0;
+    let mut url_index = UrlIndex::default();
+    consume_m3u(cfg, input, file.lines()?, &mut report, |item| {
+        let mut header = item.header.borrow_mut();
+        if accepts_item(input, &header, clusters) {
+            header.gen_uuid();
+            url_index.add(&mut header);
+            groups.insert(Rc::clone(&header.group));
+            channel_count += 1;
+        }
+    });
+    write_parse_report(cfg, input, &report);
+    if channel_count > 0 {
+        if let Err(err) = url_index.store(cfg, input) {
+            error!("{err}");
+        }
+    }
+    Ok((groups.len(), channel_count))
+}
+
+/// Fetches the playlist of the input to disk, `None` if the download fails or the playlist is empty.
+async fn fetch_streamed_input<'a>(cfg: &Config, input: &'a ConfigInput, clusters: Option<&[XtreamCluster]>)
+                                  -> (Option<(StreamedInput<'a>, usize, usize)>, Vec<M3uFilterError>) {
+    let file = match fetch_input_file(cfg, input).await {
+        Ok(file) => file,
+        Err(err) => return (None, vec![err]),
+    };
+    match scan_input(cfg, input, &file, clusters) {
+        Ok((group_count, channel_count)) if channel_count > 0 => (Some((StreamedInput { input, file, epg: None }, group_count, channel_count)), vec![]),
+        Ok(_) => (None, vec![]),
+        Err(err) => (None, vec![download_error(format!("Failed to read playlist of input {}: {err}", input.name.as_deref().unwrap_or_default()))]),
+    }
+}
+
+/// Processes a source in `low_memory` mode. The playlists of the inputs stay on disk and are read line by line for every target,
+/// the processed channels are sorted in chunks of `chunk_size` on disk and written to the m3u output while they are merged.
+pub(super) async fn process_source_low_memory(cfg: &Config, source_idx: usize, user_targets: &ProcessTargets, chunk_size: usize)
+                                              -> (Vec<InputStats>, Vec<TargetStats>, Vec<M3uFilterError>) {
+    let source = cfg.sources.get(source_idx).unwrap();
+    let mut errors = vec![];
+    let mut stats = HashMap::<u16, InputStats>::new();
+    let mut target_stats = vec![];
+    let mut inputs = vec![];
+    let enabled_inputs = source.inputs.iter().filter(|item| item.enabled).count();
+    let targets: Vec<&ConfigTarget> = source.targets.iter().filter(|target| is_target_enabled(target, user_targets)).collect();
+    for target in &targets {
+        send_webhook(WebhookEvent::RefreshStart, cfg.messaging.as_ref(), &target.name, "");
+    }
+    for input in source.inputs.iter().filter(|input| is_input_enabled(enabled_inputs, input.enabled, input.id, user_targets)) {
+        if user_targets.is_cancelled() {
+            break;
+        }
+        let start_time = Instant::now();
+        let (fetched, error_list) = fetch_streamed_input(cfg, input, user_targets.clusters.as_deref()).await;
+        let (tvguide, tvguide_errors) = if error_list.is_empty() {
+            download::get_xmltv(cfg, input, &cfg.working_dir).await
+        } else {
+            (None, vec![])
+        };
+        user_targets.advance(JobStage::Download);
+        persist_cleanup(cfg, input);
+        let mut error_count = error_list.len();
+        let input_name = input.name.as_ref().map_or_else(|| mask_sensitive_info(input.url.as_str()), ToString::to_string);
+        errors.extend(error_list.into_iter().chain(tvguide_errors).map(|err| err.with_context(&input_name)));
+        let (group_count, channel_count) = match fetched {
+            Some((mut streamed, group_count, channel_count)) => {
+                streamed.epg = tvguide;
+                inputs.push(streamed);
+                (group_count, channel_count)
+            }
+            None => {
+                info!("source is empty {}", input.url);
+                errors.push(M3uFilterError::new(M3uFilterErrorKind::Notify, format!("source is empty {input_name}"))
+                    .with_category(M3uFilterErrorCategory::Download).with_context(&input_name));
+                error_count += 1;
+                (0, 0)
+            }
+        };
+        user_targets.advance(JobStage::Parse);
+        user_targets.job_log(&format!("Input {input_name}: {group_count} groups, {channel_count} channels, {error_count} errors"));
+        stats.insert(input.id, create_input_stat(group_count, channel_count, error_count, input.input_type.clone(), &input_name, start_time.elapsed().as_secs()));
+    }
+    if user_targets.is_cancelled() {
+        return (stats.into_values().collect(), target_stats, errors);
+    }
+    if inputs.is_empty() {
+        errors.push(M3uFilterError::new(M3uFilterErrorKind::Notify, format!("Source at {source_idx} is empty")).with_category(M3uFilterErrorCategory::Download));
+        for target in targets {
+            send_webhook(WebhookEvent::RefreshFailure, cfg.messaging.as_ref(), &target.name, "Source is empty");
+        }
+    } else {
+        for target in targets {
+            if user_targets.is_cancelled() {
+                break;
+            }
+            let start_time = Instant::now();
+            let result = stream_playlist(cfg, target, &inputs, user_targets, chunk_size, &mut stats, &mut errors).await;
+            target_stats.push(finish_target(cfg, target, result, start_time, user_targets, &mut errors));
+        }
+    }
+    (stats.into_values().collect(), target_stats, errors)
+}
+
+/// A channel after the processing of the channel, one json line per channel in the stage file.
+#[derive(Serialize, Deserialize)]
+struct StagedChannel {
+    position: Position,
+    /// index into `StagedPlaylist::groups`
+    group: usize,
+    /// canonical name of the channel alias if duplicates are removed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    alias: Option<String>,
+    /// the json encoded channel
+    item: String,
+}
+
+struct StagedGroup {
+    title: Rc<String>,
+    xtream_cluster: XtreamCluster,
+    /// position of the first channel, the groups are merged in this order
+    first: Position,
+}
+
+#[derive(Default)]
+struct InputEpg {
+    channel_ids: HashSet<Rc<String>>,
+    timeshifts: Vec<EpgTimeshift>,
+}
+
+/// The processed channels of all inputs of a target in the order of the inputs, the stage file is removed when dropped.
+struct StagedPlaylist {
+    path: PathBuf,
+    groups: Vec<StagedGroup>,
+    /// the kept channel of each canonical name of the channel aliases: group and position
+    aliases: HashMap<String, (usize, Position)>,
+    channel_count: usize,
+    /// processed stats per input id
+    input_stats: Vec<(u16, PlaylistStats)>,
+    /// per input
+    epg: Vec<InputEpg>,
+}
+
+impl Drop for StagedPlaylist {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Applies the processing of the target channel by channel and writes the channels to the stage file.
+/// Only one channel is kept in memory, the pipe of the target runs on a playlist with a single channel.
+/// The overrides are applied before the channels are sorted.
+fn stage_playlist(cfg: &Config, target: &ConfigTarget, inputs: &[StreamedInput], dir: &Path) -> std::io::Result<StagedPlaylist> {
+    let counter = STAGE_FILE_COUNTER.fetch_add(1, AtomicOrdering::Relaxed);
+    let mut staged = StagedPlaylist {
+        path: dir.join(format!("low_memory_{}_{counter}.jsonl", std::process::id())),
+        groups: vec![],
+        aliases: HashMap::new(),
+        channel_count: 0,
+        input_stats: vec![],
+        epg: vec![],
+    };
+    let mut writer = BufWriter::new(File::create(&staged.path)?);
+    let pipe = get_processing_pipe(target);
+    let alias_db = target.channel_aliases.as_ref().map(|_| alias_load(cfg)).filter(|db| !db.entries().is_empty());
+    let overrides = override_load(cfg, &target.name);
+    let mut group_index: HashMap<(Rc<String>, XtreamCluster), usize> = HashMap::new();
+    // channels with the best quality rank of each canonical name: rank and group with position
+    let mut alias_candidates: HashMap<String, (usize, Vec<(usize, Position)>)> = HashMap::new();
+
+    for (input_index, streamed) in inputs.iter().enumerate() {
+        let input_index = u32::try_from(input_index).unwrap_or(u32::MAX);
+        let affix_processor = get_affix_processor(streamed.input);
+        let mut parsed_groups: HashMap<Rc<String>, u32> = HashMap::new();
+        let mut processed_groups = HashSet::new();
+        let mut processed_channels = 0;
+        let mut epg = InputEpg::default();
+        let mut channel_index = 0;
+        let mut result = Ok(());
+        consume_m3u(cfg, streamed.input, streamed.file.lines()?, &mut M3uParseReport::default(), |item| {
+            if result.is_err() || !accepts_item(streamed.input, &item.header.borrow(), None) {
+                return;
+            }
+            let (title, xtream_cluster) = {
+                let mut header = item.header.borrow_mut();
+                header.gen_uuid();
+                (Rc::clone(&header.group), header.xtream_cluster)
+            };
+            let next_group = u32::try_from(parsed_groups.len()).unwrap_or(u32::MAX);
+            let parsed_group = *parsed_groups.entry(Rc::clone(&title)).or_insert(next_group);
+            let position = (input_index, parsed_group, channel_index);
+            channel_index += 1;
+
+            let mut playlist = vec![PlaylistGroup { id: parsed_group + 1, title, channels: vec![item], xtream_cluster }];
+            for f in &pipe {
+                if let Some(groups) = f(&mut playlist, target) {
+                    playlist = groups;
+                }
+            }
+            let Some(mut group) = playlist.into_iter().find(|group| !group.channels.is_empty()) else { return };
+            processed_groups.insert(Rc::clone(&group.title));
+            processed_channels += 1;
+            if let Some(affix_processor) = &affix_processor {
+                group.channels.iter_mut().for_each(affix_processor);
+            }
+            for timeshift in assign_channel_epg_timeshift(std::slice::from_ref(&group)) {
+                epg.channel_ids.insert(Rc::clone(&timeshift.epg_channel_id));
+                if !epg.timeshifts.iter().any(|shift| shift.shifted_epg_channel_id == timeshift.shifted_epg_channel_id) {
+                    epg.timeshifts.push(timeshift);
+                }
+            }
+            let alias = {
+                let mut header = group.channels[0].header.borrow_mut();
+                if let Some(epg_channel_id) = &header.epg_channel_id {
+                    epg.channel_ids.insert(Rc::clone(epg_channel_id));
+                }
+                match (&alias_db, &target.channel_aliases) {
+                    (Some(db), Some(options)) if group.xtream_cluster == XtreamCluster::Live =>
+                        alias_channel(db, options, &mut header).filter(|_| options.dedup),
+                    _ => None,
+                }
+            };
+            transliterate_playlist(target, std::slice::from_mut(&mut group));
+            {
+                let mut header = group.channels[0].header.borrow_mut();
+                match override_channel(&overrides, &mut header) {
+                    None => return,
+                    Some(true) => group.title = Rc::clone(&header.group),
+                    Some(false) => {}
+                }
+            }
+
+            let key = (Rc::clone(&group.title), group.xtream_cluster);
+            let group_idx = match group_index.get(&key) {
+                Some(&idx) => {
+                    let staged_group = &mut staged.groups[idx];
+                    staged_group.first = staged_group.first.min(position);
+                    idx
+                }
+                None => {
+                    staged.groups.push(StagedGroup { title: Rc::clone(&group.title), xtream_cluster: group.xtream_cluster, first: position });
+                    group_index.insert(key, staged.groups.len() - 1);
+                    staged.groups.len() - 1
+                }
+            };
+            if let Some((name, rank)) = &alias {
+                let candidate = alias_candidates.entry(name.clone()).or_insert_with(|| (*rank, vec![]));
+                if *rank < candidate.0 {
+                    *candidate = (*rank, vec![]);
+                }
+                if *rank == candidate.0 {
+                    candidate.1.push((group_idx, position));
+                }
+            }
+            result = item_to_json(&group.channels[0].header.borrow())
+                .map_err(std::io::Error::from)
+                .and_then(|item| String::from_utf8(item).map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err)))
+                .and_then(|item| {
+                    let channel = StagedChannel { position, group: group_idx, alias: alias.map(|(name, _)| name), item };
+                    serde_json::to_writer(&mut writer, &channel)?;
+                    writer.write_all(b"\n")
+                });
+            staged.channel_count += 1;
+        });
+        result?;
+        staged.input_stats.push((streamed.input.id, PlaylistStats { group_count: processed_groups.len(), channel_count: processed_channels }));
+        staged.epg.push(epg);
+    }
+    writer.flush()?;
+
+    // the first of the channels with the best rank in the merged playlist is kept
+    for (name, (_, candidates)) in alias_candidates {
+        if let Some(best) = candidates.into_iter().min_by_key(|(group, position)| (staged.groups[*group].first, *position)) {
+            staged.aliases.insert(name, best);
+        }
+    }
+    Ok(staged)
+}
+
+/// A staged channel with its sort keys.
+#[derive(Serialize, Deserialize)]
+struct SortEntry {
+    group_rank: usize,
+    /// sequence position and value of the channel sorts matching the group, the last sort first
+    keys: Vec<(Option<usize>, String)>,
+    position: Position,
+    group: usize,
+    item: String,
+}
+
+/// The result of the sorted channels.
+struct StreamResult {
+    group_count: usize,
+    channel_count: usize,
+    /// max number of channels kept in memory
+    peak_items: usize,
+}
+
+/// Sorts the staged channels on disk and visits them in the order of the sorted playlist. Duplicate channel aliases are removed,
+/// the counters and the adult groups are applied. The visited group has the channel as its only entry.
+fn stream_sorted<F>(target: &ConfigTarget, staged: &StagedPlaylist, chunk_size: usize, dir: &Path, mut visit: F) -> std::io::Result<StreamResult>
+where
+    F: FnMut(&PlaylistGroup) -> std::io::Result<()>,
+{
+    let sort = target.sort.as_ref();
+    let match_as_ascii = sort.is_some_and(|sort| sort.match_as_ascii);
+    let collation = sort.and_then(|sort| sort.collation.as_ref());
+    let channel_sorts = sort.and_then(|sort| sort.channels.as_ref()).map_or(&[][..], Vec::as_slice);
+
+    // the groups are merged in the order of their first channel, the group sort is stable
+    let mut group_order: Vec<usize> = (0..staged.groups.len()).collect();
+    group_order.sort_by_key(|&group| staged.groups[group].first);
+    if let Some(group_sort) = sort.and_then(|sort| sort.groups.as_ref()) {
+        group_order.sort_by(|&a, &b| compare_group_titles(&staged.groups[a].title, &staged.groups[b].title, group_sort, match_as_ascii, collation));
+    }
+    let mut group_ranks = vec![0; staged.groups.len()];
+    for (rank, &group) in group_order.iter().enumerate() {
+        group_ranks[group] = rank;
+    }
+    // the channel sorts are applied one after the other, the last sort decides first
+    let group_sorts: Vec<Vec<usize>> = staged.groups.iter().map(|group| {
+        let title = if match_as_ascii { Rc::new(unidecode(&group.title)) } else { Rc::clone(&group.title) };
+        (0..channel_sorts.len()).rev().filter(|&idx| channel_sorts[idx].re.as_ref().is_some_and(|re| re.is_match(&title))).collect()
+    }).collect();
+
+    let compare = |a: &SortEntry, b: &SortEntry| {
+        a.group_rank.cmp(&b.group_rank).then_with(|| {
+            group_sorts[a.group].iter().zip(a.keys.iter().zip(&b.keys))
+                .map(|(&idx, (key_a, key_b))| compare_channel_values((key_a.0, &key_a.1), (key_b.0, &key_b.1), &channel_sorts[idx], collation))
+                .find(|ordering| *ordering != Ordering::Equal)
+                .unwrap_or(Ordering::Equal)
+        }).then_with(|| a.position.cmp(&b.position))
+    };
+    let mut sorter = ExternalSorter::new(dir, chunk_size, compare);
+    let mut peak_items = 0;
+    let file = File::open(&staged.path)?;
+    for line in BufReader::new(file).lines() {
+        let channel: StagedChannel = serde_json::from_str(&line?)?;
+        if channel.alias.as_ref().is_some_and(|name| staged.aliases.get(name) != Some(&(channel.group, channel.position))) {
+            continue;
+        }
+        let item = PlaylistItem { header: RefCell::new(item_from_json(channel.item.as_bytes())?) };
+        let keys = group_sorts[channel.group].iter().map(|&idx| {
+            let value = get_channel_sort_value(&item, &channel_sorts[idx], match_as_ascii);
+            (get_sequence_position(&channel_sorts[idx], &value), value.to_string())
+        }).collect();
+        sorter.push(SortEntry { group_rank: group_ranks[channel.group], keys, position: channel.position, group: channel.group, item: channel.item })?;
+        peak_items = peak_items.max(sorter.buffered());
+    }
+
+    let mut sorted: SortedItems<SortEntry, _> = sorter.finish()?;
+    let mut result = StreamResult { group_count: 0, channel_count: 0, peak_items };
+    let mut last_group = None;
+    while let Some(entry) = sorted.next() {
+        result.peak_items = result.peak_items.max(sorted.buffered() + 1);
+        let entry = entry?;
+        let staged_group = &staged.groups[entry.group];
+        let group = PlaylistGroup {
+            id: u32::try_from(entry.group_rank + 1).unwrap_or(u32::MAX),
+            title: Rc::clone(&staged_group.title),
+            channels: vec![PlaylistItem { header: RefCell::new(item_from_json(entry.item.as_bytes())?) }],
+            xtream_cluster: staged_group.xtream_cluster,
+        };
+        map_playlist_counter(target, std::slice::from_ref(&group));
+        mark_adult_groups(target, std::slice::from_ref(&group));
+        if last_group != Some(entry.group) {
+            last_group = Some(entry.group);
+            result.group_count += 1;
+        }
+        result.channel_count += 1;
+        visit(&group)?;
+    }
+    Ok(result)
+}
+
+fn io_error(target: &ConfigTarget, err: &std::io::Error) -> Vec<M3uFilterError> {
+    vec![M3uFilterError::new(M3uFilterErrorKind::Info, format!("Failed to process playlist of target {}: {err}", target.name))]
+}
+
+/// Streams the channels of the inputs through the processing of the target into the m3u output.
+async fn stream_playlist(cfg: &Config, target: &ConfigTarget, inputs: &[StreamedInput<'_>], user_targets: &ProcessTargets, chunk_size: usize,
+                         stats: &mut HashMap<u16, InputStats>, errors: &mut Vec<M3uFilterError>) -> Result<PlaylistStats, Vec<M3uFilterError>> {
+    if user_targets.clusters.is_some() {
+        return Err(vec![M3uFilterError::new(M3uFilterErrorKind::Info,
+            format!("Cluster refresh of target {} is not supported in processing low_memory mode", target.name))]);
+    }
+    // edited filter files are used without restart, the previous entries are kept if a file can't be read
+    if let Err(err) = target.load_filter_files(&cfg.t_config_path) {
+        error!("{err}");
+    }
+    let dir = PathBuf::from(&cfg.working_dir);
+    let staged = stage_playlist(cfg, target, inputs, &dir).map_err(|err| io_error(target, &err))?;
+    for (input_id, processed_stats) in &staged.input_stats {
+        if let Some(stat) = stats.get_mut(input_id) {
+            stat.processed_stats = processed_stats.clone();
+        }
+    }
+    user_targets.advance(JobStage::Filter);
+
+    let mut new_epg = vec![];
+    let mut target_epg_channel_ids = HashSet::new();
+    for (streamed, input_epg) in inputs.iter().zip(&staged.epg) {
+        target_epg_channel_ids.extend(input_epg.channel_ids.iter().cloned());
+        if let Some(tv_guide) = streamed.epg.as_ref().filter(|_| !input_epg.channel_ids.is_empty()) {
+            if let Some(mut epg) = tv_guide.filter(&input_epg.channel_ids) {
+                apply_epg_timeshift(&mut epg, &input_epg.timeshifts);
+                new_epg.push(epg);
+            }
+        }
+    }
+    merge_schedules_direct_epg(cfg, target_epg_channel_ids, &mut new_epg, errors).await;
+
+    if staged.channel_count == 0 {
+        info!("Playlist is empty: {}", &target.name);
+        return Ok(PlaylistStats { group_count: 0, channel_count: 0 });
+    }
+    if user_targets.dry_run {
+        let mut group_counts: Vec<(XtreamCluster, Rc<String>, usize)> = vec![];
+        let result = stream_sorted(target, &staged, chunk_size, &dir, |group| {
+            match group_counts.last_mut() {
+                Some((_, title, count)) if Rc::ptr_eq(title, &group.title) => *count += 1,
+                _ => group_counts.push((group.xtream_cluster, Rc::clone(&group.title), 1)),
+            }
+            Ok(())
+        }).map_err(|err| io_error(target, &err))?;
+        eprintln!("Target {}: {} groups, {} channels", target.name, result.group_count, result.channel_count);
+        for (xtream_cluster, title, count) in group_counts {
+            eprintln!("  [{}] {title}: {count}", xtream_cluster.as_str());
+        }
+        return Ok(PlaylistStats { group_count: result.group_count, channel_count: result.channel_count });
+    }
+
+    let playlist_stats = write_playlist(cfg, target, &staged, chunk_size, flatten_tvguide(&new_epg).as_ref())?;
+    publish_target(cfg, target).await.map_err(|errors| with_category(errors, M3uFilterErrorCategory::Persistence))?;
+    tvheadend_update_network(target).await.map_err(|err| vec![err.with_category(M3uFilterErrorCategory::Api)])?;
+    Ok(playlist_stats)
+}
+
+/// Writes the sorted channels to the m3u output and the search index of the target. The files are replaced together,
+/// the previous files are kept on errors.
+fn write_playlist(cfg: &Config, target: &ConfigTarget, staged: &StagedPlaylist, chunk_size: usize, epg: Option<&crate::model::xmltv::Epg>)
+                  -> Result<PlaylistStats, Vec<M3uFilterError>> {
+    let persistence_error = |message: String| vec![M3uFilterError::new(M3uFilterErrorKind::Info, message).with_category(M3uFilterErrorCategory::Persistence)];
+    let target_path = ensure_target_storage_path(cfg, &target.name).map_err(|err| vec![err])?;
+    let target_id_mapping_file = get_target_id_mapping_file(&target_path);
+    let _file_lock = cfg.file_locks.write_lock(&target_id_mapping_file).map_err(|err| persistence_error(err.to_string()))?;
+    let mut target_id_mapping = TargetIdMapping::new(&target_id_mapping_file);
+    let udpxy_url = target.options.as_ref().and_then(|options| options.udpxy_url.as_deref());
+
+    let mut transaction = cfg.file_locks.begin_transaction(&target_path)
+        .map_err(|err| persistence_error(format!("Failed to start transaction for target {}: {err}", target.name)))?;
+    let mut m3u_writer = M3uPlaylistWriter::new(target, cfg, &target_path, &mut transaction).map_err(|err| vec![err])?;
+    let search_path = get_search_index_path(cfg, &target.name).map(|path| transaction.stage(&path));
+    let mut search_writer = match &search_path {
+        Some(path) => Some(File::create(path).map(BufWriter::new)
+            .map_err(|err| persistence_error(format!("Failed to write search index {}: {err}", path.to_str().unwrap_or("?"))))?),
+        None => None,
+    };
+    let mut write_error = None;
+    let result = stream_sorted(target, staged, chunk_size, Path::new(&cfg.working_dir), |group| {
+        let channel = &group.channels[0];
+        assign_virtual_id(&mut channel.header.borrow_mut(), udpxy_url, &mut target_id_mapping);
+        if let Err(err) = m3u_writer.write(channel) {
+            write_error = Some(err);
+            return Err(std::io::Error::other("m3u output"));
+        }
+        match &mut search_writer {
+            Some(writer) => search_entry_write(writer, &channel.header.borrow()),
+            None => Ok(()),
+        }
+    });
+    let result = match (result, write_error) {
+        (_, Some(err)) => return Err(vec![err]),
+        (Err(err), None) => return Err(io_error(target, &err)),
+        (Ok(result), None) => result,
+    };
+    if log_enabled!(Level::Debug) {
+        debug!("Target {} was written with at most {} channels in memory", target.name, result.peak_items);
+    }
+    m3u_writer.finish().map_err(|err| vec![err])?;
+    if let Some(mut writer) = search_writer {
+        writer.flush().map_err(|err| persistence_error(format!("Failed to write search index: {err}")))?;
+    }
+    if let Some(output) = target.output.iter().find(|output| output.target == TargetType::M3u) {
+        epg_write(target, cfg, &target_path, epg, output, &mut transaction).map_err(|err| vec![err])?;
+    }
+    // on errors the transaction is dropped and the files of the previous refresh are kept
+    target_id_mapping.persist().map_err(|err| persistence_error(err.to_string()))?;
+    transaction.commit().map_err(|err| persistence_error(format!("Failed to commit files of target {}: {err}", target.name)))?;
+    cfg.t_item_cache.invalidate(&target_path);
+    manifest_write(cfg, &target.name).map_err(|err| vec![err])?;
+    Ok(PlaylistStats { group_count: result.group_count, channel_count: result.channel_count })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    use crate::model::config::{Config, ConfigInput, ConfigSort, ConfigSortChannel, ConfigSortGroup, ConfigTarget, FilenameSanitize, InputType, ItemField,
+                               SortOrder, TargetOutput, TargetType, VideoConfig};
+    use crate::processing::low_memory::{stage_playlist, stream_sorted, InputFile, StreamedInput};
+    use crate::processing::m3u_parser::parse_m3u;
+    use crate::processing::playlist_processor::process_target_playlist;
+    use crate::utils::test_utils::create_temp_dir;
+
+    #[test]
+    fn test_low_memory_stream() {
+        const CHANNELS: usize = 300;
+        const CHUNK_SIZE: usize = 16;
+        let temp_dir = create_temp_dir("low_memory");
+        let dir = temp_dir.path();
+        let playlist_path = dir.join("input.m3u");
+        let mut file = std::fs::File::create(&playlist_path).unwrap();
+        writeln!(file, "#EXTM3U").unwrap();
+        for idx in 0..CHANNELS {
+            writeln!(file, "#EXTINF:-1 group-title=\"Group {}\",Channel {}\nhttp://host/live/{idx}.ts", (idx * 7) % 11, (idx * 13) % 97).unwrap();
+        }
+        drop(file);
+
+        let cfg = Config { working_dir: dir.to_str().unwrap().to_string(), video: Some(VideoConfig::default()), ..Config::default() };
+        let input = ConfigInput { input_type: InputType::M3u, url: playlist_path.to_str().unwrap().to_string(), ..ConfigInput::default() };
+        let mut target = ConfigTarget {
+            name: "tv".to_string(),
+            filter: "NOT (Group ~ \"Group 3\")".to_string(),
+            output: vec![TargetOutput { target: TargetType::M3u, filename: None, template: None, sanitize: FilenameSanitize::default(), split_groups: false }],
+            sort: Some(ConfigSort {
+                groups: Some(ConfigSortGroup { order: SortOrder::Desc }),
+                channels: Some(vec![ConfigSortChannel { field: ItemField::Name, group_pattern: "Group 1.*".to_string(), order: SortOrder::Asc, sequence: None, re: None }]),
+                ..ConfigSort::default()
+            }),
+            ..ConfigTarget::default()
+        };
+        target.prepare(1, None).unwrap();
+
+        let content = std::fs::read_to_string(&playlist_path).unwrap();
+        let mut playlist = parse_m3u(&cfg, &input, content.lines());
+        playlist.iter_mut().for_each(crate::model::playlist::PlaylistGroup::on_load);
+        let expected: Vec<(String, String)> = process_target_playlist(&target, playlist).iter()
+            .flat_map(|group| group.channels.iter().map(|channel| (group.title.to_string(), channel.header.borrow().name.to_string())))
+            .collect();
+
+        let inputs = [StreamedInput { input: &input, file: InputFile::File { path: PathBuf::from(&playlist_path), temporary: false }, epg: None }];
+        let staged = stage_playlist(&cfg, &target, &inputs, dir).unwrap();
+        let mut streamed = vec![];
+        let result = stream_sorted(&target, &staged, CHUNK_SIZE, dir, |group| {
+            streamed.push((group.title.to_string(), group.channels[0].header.borrow().name.to_string()));
+            Ok(())
+        }).unwrap();
+        assert_eq!(streamed, expected);
+        assert_eq!(result.channel_count, expected.len());
+        assert!(expected.len() > 2 * CHUNK_SIZE);
+        // a chunk in memory while sorting, one channel per chunk file while merging
+        assert!(result.peak_items <= CHUNK_SIZE.max(CHANNELS.div_ceil(CHUNK_SIZE) + 1), "{} channels in memory", result.peak_items);
+        drop(staged);
+        drop(inputs);
+    }
+}
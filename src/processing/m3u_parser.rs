@@ -1,77 +1,19 @@
 use std::borrow::BorrowMut;
 use std::cell::RefCell;
+use std::path::PathBuf;
 use std::rc::Rc;
 
-use crate::model::config::{Config, ConfigInput};
-use crate::model::playlist::{PlaylistGroup, PlaylistItem, PlaylistItemHeader, PlaylistItemType, XtreamCluster};
-use crate::utils::string_utils;
-
-#[inline]
-fn token_value(it: &mut std::str::Chars) -> String {
-    // Use .find() to skip until the first double quote (") character.
-    if it.any(|ch| ch == '"') {
-        // If a quote is found, call get_value to extract the value.
-        return get_value(it);
-    }
-    // If no double quote is found, return an empty string.
-    String::new()
-}
-
-fn get_value(it: &mut std::str::Chars) -> String {
-    let mut result = String::with_capacity(128);
-    for oc in it.by_ref() {
-        if oc == '"' {
-            break;
-        }
-        result.push(oc);
-    }
-    result.shrink_to_fit();
-    result
-}
-
-fn token_till(it: &mut std::str::Chars, stop_char: char, start_with_alpha: bool) -> Option<String> {
-    let mut result = String::with_capacity(128);
-    let mut skip_non_alpha = start_with_alpha;
-
-    for ch in it.by_ref() {
-        if ch == stop_char {
-            break;
-        }
-        if result.is_empty() && ch.is_whitespace() {
-            continue;
-        }
+use log::{error, warn};
+pub use m3u_filter_core::m3u::M3uParseReport;
+use m3u_filter_core::m3u::{consume_m3u_lines, extract_id_from_url, get_title_group, parse_extinf};
 
-        if skip_non_alpha {
-            if ch.is_alphabetic() {
-                skip_non_alpha = false;
-            } else {
-                continue;
-            }
-        }
-        result.push(ch);
-    }
-
-    if result.is_empty() {
-        None
-    } else {
-        result.shrink_to_fit();
-        Some(result)
-    }
-}
+use crate::model::config::{Config, ConfigInput, M3uStrictness};
+use crate::model::playlist::{PlaylistGroup, PlaylistItem, PlaylistItemHeader, PlaylistItemType, XtreamCluster};
+use crate::utils::file_utils;
+use crate::utils::request_utils::mask_sensitive_info;
+use crate::utils::string_utils::StringInterner;
 
-#[inline]
-fn skip_digit(it: &mut std::str::Chars) -> Option<char> {
-    loop {
-        match it.next() {
-            Some(c) => {
-                if !(c == '-' || c == '+' || c.is_ascii_digit()) {
-                    return Some(c);
-                }
-            }
-            None => return None,
-        }
-    }
-}
+const M3U_REPORT_FILE_PREFIX: &str = "m3u_report_";
 
 fn create_empty_playlistitem_header(input_id: u16, url: &str) -> PlaylistItemHeader {
     PlaylistItemHeader {
@@ -93,36 +35,24 @@ macro_rules! process_header_fields {
     };
 }
 
-fn process_header(input: &ConfigInput, video_suffixes: &[&str], content: &str, url: &str) -> PlaylistItemHeader {
+fn process_header(input: &ConfigInput, video_suffixes: &[&str], interner: &mut StringInterner, content: &str, url: &str) -> PlaylistItemHeader {
     let mut plih = create_empty_playlistitem_header(input.id, url);
-    let mut it = content.chars();
-    let line_token = token_till(&mut it, ':', false);
-    if line_token.as_deref() == Some("#EXTINF") {
-        let mut c = skip_digit(&mut it);
-        loop {
-            if c.is_none() {
-                break;
+    if let Some(extinf) = parse_extinf(content) {
+        plih.title = Rc::new(extinf.title);
+        for (token, value) in extinf.attributes {
+            match token.as_str() {
+                "group-title" => plih.group = interner.intern(value),
+                "tvg-logo" => plih.logo = interner.intern(value),
+                token => process_header_fields!(plih, token,
+                    (id, "tvg-id"),
+                    (name, "tvg-name"),
+                    (chno, "tvg-chno"),
+                    (parent_code, "parent-code"),
+                    (audio_track, "audio-track"),
+                    (logo_small, "tvg-logo-small"),
+                    (time_shift, "timeshift"),
+                    (rec, "tvg-rec"); value),
             }
-            if c.unwrap() == ',' {
-                plih.title = Rc::new(get_value(&mut it));
-            } else {
-                let token = token_till(&mut it, '=', true);
-                if let Some(t) = token {
-                    let value = token_value(&mut it);
-                    process_header_fields!(plih, t.to_lowercase().as_str(),
-                        (id, "tvg-id"),
-                        (group, "group-title"),
-                        (name, "tvg-name"),
-                        (chno, "tvg-chno"),
-                        (parent_code, "parent-code"),
-                        (audio_track, "audio-track"),
-                        (logo, "tvg-logo"),
-                        (logo_small, "tvg-logo-small"),
-                        (time_shift, "timeshift"),
-                        (rec, "tvg-rec"); value);
-                }
-            }
-            c = it.next();
         }
         if plih.id.is_empty() {
             if let Some(chanid) = extract_id_from_url(url) {
@@ -154,62 +84,48 @@ fn process_header(input: &ConfigInput, video_suffixes: &[&str], content: &str, u
     plih
 }
 
-pub fn extract_id_from_url(url: &str) -> Option<String> {
-    if let Some(filename) = url.split('/').last() {
-        return filename.rfind('.').map_or_else(|| Some(filename.to_string()), |index| Some(filename[..index].to_string()));
-    }
-    None
-}
-
-pub fn consume_m3u<'a, I, F: FnMut(PlaylistItem)>(cfg: &Config, input: &ConfigInput, lines: I, mut visit: F)
+/// Parses the playlist, with `M3uStrictness::Repair` or `M3uStrictness::Strict` the malformed `#EXTINF` lines
+/// are repaired or skipped and added to the report.
+pub fn consume_m3u<I, S, F: FnMut(PlaylistItem)>(cfg: &Config, input: &ConfigInput, lines: I, report: &mut M3uParseReport, mut visit: F)
 where
-    I: Iterator<Item=&'a str>,
+    I: Iterator<Item=S>,
+    S: AsRef<str>,
 {
-    let mut header: Option<String> = None;
-    let mut group: Option<String> = None;
+    let mut interner = StringInterner::default();
+    let strictness = input.options.as_ref().map_or(M3uStrictness::Lenient, |options| options.m3u_strictness);
 
     let video_suffixes = cfg.video.as_ref().unwrap().extensions.iter().map(String::as_str).collect::<Vec<&str>>();
-    for line in lines {
-        if line.starts_with("#EXTINF") {
-            header = Some(String::from(line));
-            continue;
-        }
-        if line.starts_with("#EXTGRP") {
-            group = Some(String::from(&line[8..]));
-            continue;
-        }
-        if line.starts_with('#') {
-            continue;
-        }
-        if let Some(header_value) = header {
-            let item = PlaylistItem { header: RefCell::new(process_header(input, &video_suffixes, &header_value, line)) };
-            let mut header = item.header.borrow_mut();
-            if header.group.is_empty() {
-                if let Some(group_value) = group {
-                    header.group = Rc::new(group_value);
-                } else {
-                    let current_title = header.title.clone();
-                    header.group = Rc::new(string_utils::get_title_group(current_title.as_str()));
-                }
+    consume_m3u_lines(lines, strictness, report, |header_value, group, line| {
+        let item = PlaylistItem { header: RefCell::new(process_header(input, &video_suffixes, &mut interner, header_value, line)) };
+        let mut header = item.header.borrow_mut();
+        if header.group.is_empty() {
+            if let Some(group_value) = group {
+                header.group = interner.intern(group_value.to_string());
+            } else {
+                let current_title = header.title.clone();
+                header.group = interner.intern(get_title_group(current_title.as_str()));
             }
-            drop(header);
-            visit(item);
         }
-        header = None;
-        group = None;
-    }
+        drop(header);
+        visit(item);
+    });
 }
 
-pub fn parse_m3u<'a, I>(cfg: &Config, input: &ConfigInput, lines: I) -> Vec<PlaylistGroup>
+pub fn parse_m3u<I, S>(cfg: &Config, input: &ConfigInput, lines: I) -> Vec<PlaylistGroup>
 where
-    I: Iterator<Item=&'a str>,
+    I: Iterator<Item=S>,
+    S: AsRef<str>,
 {
     let mut sort_order: Vec<Vec<PlaylistItem>> = vec![];
     let mut sort_order_idx: usize = 0;
     let mut group_map: std::collections::HashMap<Rc<String>, usize> = std::collections::HashMap::new();
-    consume_m3u(cfg, input, lines, |item| {
+    let mut report = M3uParseReport::default();
+    consume_m3u(cfg, input, lines, &mut report, |item| {
         // keep the original sort order for groups and group the playlist items
         let key = Rc::clone(&item.header.borrow().group);
+        if !input.accepts_group(&key) {
+            return;
+        }
         match group_map.entry(key) {
             std::collections::hash_map::Entry::Vacant(v) => {
                 v.insert(sort_order_idx);
@@ -230,5 +146,26 @@ where
         grp_id += 1;
         PlaylistGroup { id: grp_id, xtream_cluster: cluster, title: Rc::clone(&group_title), channels }
     }).collect();
+    write_parse_report(cfg, input, &report);
     result
 }
+
+fn get_report_path(cfg: &Config, input: &ConfigInput) -> PathBuf {
+    let name = input.name.as_deref().map_or_else(|| input.id.to_string(), file_utils::sanitize_filename);
+    PathBuf::from(&cfg.working_dir).join(format!("{M3U_REPORT_FILE_PREFIX}{name}.json"))
+}
+
+/// Writes the report of the repaired and skipped lines to the working dir, the report of the previous run is replaced.
+pub fn write_parse_report(cfg: &Config, input: &ConfigInput, report: &M3uParseReport) {
+    if input.options.as_ref().is_none_or(|options| options.m3u_strictness == M3uStrictness::Lenient) {
+        return;
+    }
+    let path = get_report_path(cfg, input);
+    if !report.repaired.is_empty() || !report.skipped.is_empty() {
+        let input_name = input.name.as_ref().map_or_else(|| mask_sensitive_info(&input.url), ToString::to_string);
+        warn!("Input {input_name}: {} malformed EXTINF lines repaired, {} skipped, see {}", report.repaired.len(), report.skipped.len(), path.display());
+    }
+    if let Err(err) = file_utils::write_atomic(&path, |writer| serde_json::to_writer_pretty(writer, report).map_err(std::io::Error::from)) {
+        error!("Failed to write m3u report {}: {err}", path.display());
+    }
+}
@@ -2,6 +2,32 @@ pub mod m3u_parser;
 pub mod xtream_parser;
 pub mod playlist_processor;
 pub mod xmltv_parser;
+pub mod stream_health;
+pub mod logo_cache;
+pub mod short_epg;
+pub mod account_check;
+pub mod recorder;
+pub mod job_queue;
+pub mod input_source;
+pub mod directory_watch;
+pub mod trakt;
+pub mod token_refresh;
+mod tmdb;
+mod schedules_direct;
+mod tvheadend;
+mod publisher;
+mod post_process;
+mod incremental;
+mod channel_override;
+mod channel_alias;
+mod tagging;
+mod collation;
+#[cfg(feature = "wasm")]
+mod wasm_plugin;
 mod playlist_watch;
 mod xtream_processor;
-mod affix_processor;
\ No newline at end of file
+mod affix_processor;
+mod size_budget;
+mod shrink_protection;
+mod stalker;
+mod low_memory;
@@ -4,6 +4,7 @@ use core::cmp::Ordering;
 use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
 use std::sync::{Arc, Mutex};
 use std::thread;
 
@@ -14,20 +15,43 @@ use std::time::Instant;
 use unidecode::unidecode;
 
 use crate::filter::{get_field_value, set_field_value, MockValueProcessor, ValueProvider};
-use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorKind};
-use crate::messaging::{send_message, MsgKind};
-use crate::model::config::{ConfigSortChannel, ConfigSortGroup, ConfigTarget, InputType,
+use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorCategory, M3uFilterErrorInfo, M3uFilterErrorKind};
+use crate::messaging::{send_message, send_webhook, MsgKind, WebhookEvent};
+use crate::model::config::{ConfigInput, ConfigSortChannel, ConfigSortGroup, ConfigTarget, InputType, SortCollation,
                            ItemField, ProcessTargets, ProcessingOrder, SortOrder::{Asc, Desc}};
-use crate::model::mapping::{CounterModifier, Mapping, MappingValueProcessor};
+use crate::model::mapping::{CounterModifier, Mapping};
 use crate::model::playlist::{FetchedPlaylist, FieldAccessor, PlaylistGroup, PlaylistItem, XtreamCluster};
-use crate::model::stats::{InputStats, PlaylistStats};
+use crate::model::xmltv::TVGuide;
+use crate::model::stats::{InputStats, PlaylistStats, ProcessingSummary, TargetStats};
 use crate::processing::affix_processor::apply_affixes;
+use crate::processing::channel_alias::apply_channel_aliases;
+use crate::processing::channel_override::{apply_channel_overrides, apply_channel_quarantine};
+use crate::processing::collation::{collate, transliterate_playlist};
+use crate::processing::input_source::{fetch_input, InputContext};
+use crate::processing::job_queue::JobStage;
+use crate::processing::low_memory;
 use crate::processing::playlist_watch::process_group_watch;
-use crate::processing::xmltv_parser::flatten_tvguide;
-use crate::processing::xtream_processor::playlist_resolve_series;
+use crate::processing::xmltv_parser::{apply_epg_timeshift, assign_channel_epg_timeshift, flatten_tvguide};
+use crate::processing::incremental::{incremental_load, incremental_load_other_clusters, incremental_store, input_content_hash};
+use crate::processing::post_process::{item_from_json, item_to_json, post_process_playlist};
+use crate::processing::schedules_direct::merge_schedules_direct_epg;
+use crate::processing::shrink_protection::{check_shrink_protection, store_playlist_size};
+use crate::processing::size_budget::apply_size_budget;
+use crate::processing::tagging::{prefix_tagged_groups, tag_playlist};
+use crate::processing::tmdb::tmdb_enrich_playlist;
+use crate::processing::trakt::trakt_apply_lists;
+#[cfg(feature = "wasm")]
+use crate::processing::wasm_plugin;
+use crate::processing::publisher::publish_target;
+use crate::processing::tvheadend::tvheadend_update_network;
+use crate::processing::xtream_processor::{playlist_resolve_series, playlist_resolve_source_series};
+use crate::processing::{logo_cache, stream_health};
+use crate::repository::persist_repository::persist_cleanup;
 use crate::repository::playlist_repository::persist_playlist;
+use crate::repository::url_index::url_index_write;
 use crate::utils::default_utils::default_as_default;
 use crate::utils::download;
+use crate::utils::sd_notify;
 use crate::{get_errors_notify_message, model::config, Config};
 use crate::utils::request_utils::mask_sensitive_info;
 
@@ -56,62 +80,65 @@ fn filter_playlist(playlist: &mut [PlaylistGroup], target: &ConfigTarget) -> Opt
     Some(new_playlist)
 }
 
-fn playlistgroup_comparator(a: &PlaylistGroup, b: &PlaylistGroup, group_sort: &ConfigSortGroup, match_as_ascii: bool) -> Ordering {
-    let value_a = if match_as_ascii { Rc::new(unidecode(&a.title)) } else { Rc::clone(&a.title) };
-    let value_b = if match_as_ascii { Rc::new(unidecode(&b.title)) } else { Rc::clone(&b.title) };
-    let ordering = value_a.partial_cmp(&value_b).unwrap();
+fn compare_values(a: &str, b: &str, collation: Option<&SortCollation>) -> Ordering {
+    collation.map_or_else(|| a.cmp(b), |collation| collate(collation, a, b))
+}
+
+fn playlistgroup_comparator(a: &PlaylistGroup, b: &PlaylistGroup, group_sort: &ConfigSortGroup, match_as_ascii: bool, collation: Option<&SortCollation>) -> Ordering {
+    compare_group_titles(&a.title, &b.title, group_sort, match_as_ascii, collation)
+}
+
+pub(super) fn compare_group_titles(a: &Rc<String>, b: &Rc<String>, group_sort: &ConfigSortGroup, match_as_ascii: bool, collation: Option<&SortCollation>) -> Ordering {
+    let value_a = if match_as_ascii { Rc::new(unidecode(a)) } else { Rc::clone(a) };
+    let value_b = if match_as_ascii { Rc::new(unidecode(b)) } else { Rc::clone(b) };
+    let ordering = compare_values(&value_a, &value_b, collation);
     match group_sort.order {
         Asc => ordering,
         Desc => ordering.reverse()
     }
 }
 
-fn playlistitem_comparator(a: &PlaylistItem, b: &PlaylistItem, channel_sort: &ConfigSortChannel, match_as_ascii: bool) -> Ordering {
-    let raw_value_a = get_field_value(a, &channel_sort.field);
-    let raw_value_b = get_field_value(b, &channel_sort.field);
-    let value_a = if match_as_ascii { Rc::new(unidecode(&raw_value_a)) } else { raw_value_a };
-    let value_b = if match_as_ascii { Rc::new(unidecode(&raw_value_b)) } else { raw_value_b };
-    channel_sort.sequence.as_ref().map_or_else(|| {
-        let ordering = value_a.partial_cmp(&value_b).unwrap();
-        match channel_sort.order {
-            Asc => ordering,
-            Desc => ordering.reverse()
-        }
-    }, |custom_order| {
-        // Check indices in the custom order vector
-        let index_a = custom_order.iter().position(|s| s == value_a.as_ref());
-        let index_b = custom_order.iter().position(|s| s == value_b.as_ref());
-
-        match (index_a, index_b) {
-            (Some(idx_a), Some(idx_b)) => {
-                // Both items found in custom order, compare indices
-                idx_a.cmp(&idx_b)
-            }
-            (Some(_), None) => {
-                // Only 'a' found in custom order, it comes first
-                Ordering::Less
-            }
-            (None, Some(_)) => {
-                // Only 'b' found in custom order, it comes first
-                Ordering::Greater
-            }
-            (None, None) => {
-                // Neither found, fall back to default ordering
-                let ordering = value_a.partial_cmp(&value_b).unwrap();
-                match channel_sort.order {
-                    Asc => ordering,
-                    Desc => ordering.reverse(),
-                }
+fn playlistitem_comparator(a: &PlaylistItem, b: &PlaylistItem, channel_sort: &ConfigSortChannel, match_as_ascii: bool, collation: Option<&SortCollation>) -> Ordering {
+    let value_a = get_channel_sort_value(a, channel_sort, match_as_ascii);
+    let value_b = get_channel_sort_value(b, channel_sort, match_as_ascii);
+    compare_channel_values((get_sequence_position(channel_sort, &value_a), &value_a), (get_sequence_position(channel_sort, &value_b), &value_b), channel_sort, collation)
+}
+
+pub(super) fn get_channel_sort_value(item: &PlaylistItem, channel_sort: &ConfigSortChannel, match_as_ascii: bool) -> Rc<String> {
+    let raw_value = get_field_value(item, &channel_sort.field);
+    if match_as_ascii { Rc::new(unidecode(&raw_value)) } else { raw_value }
+}
+
+pub(super) fn get_sequence_position(channel_sort: &ConfigSortChannel, value: &str) -> Option<usize> {
+    channel_sort.sequence.as_ref().and_then(|custom_order| custom_order.iter().position(|s| s == value))
+}
+
+/// Compares the sort values with their position in the custom order.
+pub(super) fn compare_channel_values(a: (Option<usize>, &str), b: (Option<usize>, &str), channel_sort: &ConfigSortChannel, collation: Option<&SortCollation>) -> Ordering {
+    match (a.0, b.0) {
+        // Both items found in custom order, compare indices
+        (Some(idx_a), Some(idx_b)) => idx_a.cmp(&idx_b),
+        // Only 'a' found in custom order, it comes first
+        (Some(_), None) => Ordering::Less,
+        // Only 'b' found in custom order, it comes first
+        (None, Some(_)) => Ordering::Greater,
+        // Neither found, fall back to default ordering
+        (None, None) => {
+            let ordering = compare_values(a.1, b.1, collation);
+            match channel_sort.order {
+                Asc => ordering,
+                Desc => ordering.reverse(),
             }
         }
-    })
+    }
 }
 
 fn sort_playlist(target: &ConfigTarget, new_playlist: &mut [PlaylistGroup]) {
     if let Some(sort) = &target.sort {
         let match_as_ascii = sort.match_as_ascii;
+        let collation = sort.collation.as_ref();
         if let Some(group_sort) = &sort.groups {
-            new_playlist.sort_by(|a, b| playlistgroup_comparator(a, b, group_sort, match_as_ascii));
+            new_playlist.sort_by(|a, b| playlistgroup_comparator(a, b, group_sort, match_as_ascii, collation));
         }
         if let Some(channel_sorts) = &sort.channels {
             for channel_sort in channel_sorts {
@@ -119,7 +146,7 @@ fn sort_playlist(target: &ConfigTarget, new_playlist: &mut [PlaylistGroup]) {
                 for group in new_playlist.iter_mut() {
                     let group_title = if match_as_ascii { Rc::new(unidecode(&group.title)) } else { Rc::clone(&group.title) };
                     if regexp.is_match(group_title.as_str()) {
-                        group.channels.sort_by(|chan1, chan2| playlistitem_comparator(chan1, chan2, channel_sort, match_as_ascii));
+                        group.channels.sort_by(|chan1, chan2| playlistitem_comparator(chan1, chan2, channel_sort, match_as_ascii, collation));
                     }
                 }
             }
@@ -172,35 +199,33 @@ fn rename_playlist(playlist: &mut [PlaylistGroup], target: &ConfigTarget) -> Opt
     }
 }
 
-macro_rules! apply_pattern {
-    ($pattern:expr, $provider:expr, $processor:expr) => {{
-            if let Some(ptrn) = $pattern {
-               ptrn.filter($provider, $processor);
-            };
-    }};
-}
-
 fn map_channel(channel: PlaylistItem, mapping: &Mapping) -> PlaylistItem {
     if !mapping.mapper.is_empty() {
         let header = channel.header.borrow();
         let channel_name = if mapping.match_as_ascii { Rc::new(unidecode(&header.name)) } else { header.name.clone() };
         if mapping.match_as_ascii && log_enabled!(Level::Trace) { trace!("Decoded {} for matching to {}", &header.name, &channel_name); };
         drop(header);
-        let ref_chan = RefCell::new(&channel);
-        let provider = ValueProvider { pli: ref_chan.clone() };
-        let mut mock_processor = MockValueProcessor {};
+        let provider = ValueProvider { pli: RefCell::new(&channel) };
         for m in &mapping.mapper {
-            let mut processor = MappingValueProcessor { pli: ref_chan.clone(), mapper: m };
-            match &m.t_filter {
-                Some(filter) => {
-                    if filter.filter(&provider, &mut mock_processor) {
-                        apply_pattern!(&m.t_pattern, &provider, &mut processor);
-                    }
-                }
-                _ => {
-                    apply_pattern!(&m.t_pattern, &provider, &mut processor);
-                }
-            };
+            m.apply(&provider, &channel.header);
+        }
+    }
+    if mapping.has_group_operations() {
+        let mut header = channel.header.borrow_mut();
+        if let Some(group) = mapping.map_group(&header.group, &header.name) {
+            header.group = Rc::new(group);
+        }
+    }
+    if let Some(lineup) = &mapping.t_lineup {
+        let mut header = channel.header.borrow_mut();
+        let entry = lineup.get(header.epg_channel_id.as_deref().map(String::as_str), &header.name).cloned();
+        if let Some(entry) = entry {
+            if let Some(chno) = entry.chno {
+                header.chno = Rc::new(chno);
+            }
+            if let Some(group) = entry.group {
+                header.group = Rc::new(group);
+            }
         }
     }
     channel
@@ -211,7 +236,7 @@ fn map_playlist(playlist: &mut [PlaylistGroup], target: &ConfigTarget) -> Option
         let new_playlist: Vec<PlaylistGroup> = playlist.iter().map(|playlist_group| {
             let mut grp = playlist_group.clone();
             let mappings = target.t_mapping.as_ref().unwrap();
-            mappings.iter().filter(|&mapping| !mapping.mapper.is_empty()).for_each(|mapping|
+            mappings.iter().filter(|&mapping| !mapping.mapper.is_empty() || mapping.has_group_operations() || mapping.t_lineup.is_some()).for_each(|mapping|
                 grp.channels = grp.channels.drain(..).map(|chan| map_channel(chan, mapping)).collect());
             grp
         }).collect();
@@ -243,7 +268,21 @@ fn map_playlist(playlist: &mut [PlaylistGroup], target: &ConfigTarget) -> Option
     }
 }
 
-fn map_playlist_counter(target: &ConfigTarget, playlist: &[PlaylistGroup]) {
+/// Sets the `parent_code` of the channels in the `adult_groups` of the target.
+pub(super) fn mark_adult_groups(target: &ConfigTarget, playlist: &[PlaylistGroup]) {
+    if target.t_adult_groups.is_some() {
+        for plg in playlist.iter().filter(|plg| target.is_adult_group(&plg.title)) {
+            for channel in &plg.channels {
+                let mut header = channel.header.borrow_mut();
+                if header.parent_code.is_empty() {
+                    header.parent_code = Rc::new(String::from("1"));
+                }
+            }
+        }
+    }
+}
+
+pub(super) fn map_playlist_counter(target: &ConfigTarget, playlist: &[PlaylistGroup]) {
     if target.t_mapping.is_some() {
         let mut mock_processor = MockValueProcessor {};
         let mappings = target.t_mapping.as_ref().unwrap();
@@ -279,50 +318,73 @@ fn map_playlist_counter(target: &ConfigTarget, playlist: &[PlaylistGroup]) {
 // If no input is enabled but the user set the target as command line argument,
 // we force the input to be enabled.
 // If there are enabled input, then only these are used.
-fn is_input_enabled(enabled_inputs: usize, input_enabled: bool, input_id: u16, user_targets: &ProcessTargets) -> bool {
-    if enabled_inputs == 0 {
+pub(super) fn is_input_enabled(enabled_inputs: usize, input_enabled: bool, input_id: u16, user_targets: &ProcessTargets) -> bool {
+    if enabled_inputs == 0 || user_targets.only_inputs {
         return user_targets.enabled && user_targets.has_input(input_id);
     }
     input_enabled
 }
 
-fn is_target_enabled(target: &ConfigTarget, user_targets: &ProcessTargets) -> bool {
+pub(super) fn is_target_enabled(target: &ConfigTarget, user_targets: &ProcessTargets) -> bool {
     (!user_targets.enabled && target.enabled) || (user_targets.enabled && user_targets.has_target(target.id))
 }
 
-async fn process_source(cfg: Arc<Config>, source_idx: usize, user_targets: Arc<ProcessTargets>) -> (Vec<InputStats>, Vec<M3uFilterError>) {
+async fn process_source(cfg: Arc<Config>, source_idx: usize, user_targets: Arc<ProcessTargets>) -> (Vec<InputStats>, Vec<TargetStats>, Vec<M3uFilterError>) {
+    if let Some(chunk_size) = cfg.get_low_memory_chunk_size() {
+        return low_memory::process_source_low_memory(&cfg, source_idx, &user_targets, chunk_size).await;
+    }
     let source = cfg.sources.get(source_idx).unwrap();
     let mut errors = vec![];
     let mut stats = HashMap::<u16, InputStats>::new();
+    let mut target_stats = vec![];
     let mut source_playlists = Vec::new();
+    let mut input_hashes = HashMap::<u16, [u8; 32]>::new();
+    let incremental = source.targets.iter().any(|target| target.incremental);
     let enabled_inputs = source.inputs.iter().filter(|item| item.enabled).count();
+    for target in source.targets.iter().filter(|target| is_target_enabled(target, &user_targets)) {
+        send_webhook(WebhookEvent::RefreshStart, cfg.messaging.as_ref(), &target.name, "");
+    }
     // Downlod the sources
     for input in &source.inputs {
         let start_time = Instant::now();
         let input_id = input.id;
         if is_input_enabled(enabled_inputs, input.enabled, input_id, &user_targets) {
-            let (mut playlistgroups, mut error_list) = match input.input_type {
-                InputType::M3u => download::get_m3u_playlist(&cfg, input, &cfg.working_dir).await,
-                InputType::Xtream => download::get_xtream_playlist(input, &cfg.working_dir).await,
-            };
+            if user_targets.is_cancelled() {
+                break;
+            }
+            let ctx = InputContext { cfg: &cfg, clusters: user_targets.clusters.as_deref() };
+            let (mut playlistgroups, error_list) = fetch_input(&ctx, input).await;
+            if let Some(clusters) = &user_targets.clusters {
+                playlistgroups.retain(|group| clusters.contains(&group.xtream_cluster));
+            }
             // @TODO optmization dont hold tv_guide in memory, persist raw and  later use sax parser to extract.
-            let (tvguide, mut tvguide_errors) = if error_list.is_empty() {
+            let (tvguide, tvguide_errors) = if error_list.is_empty() {
                 download::get_xmltv(&cfg, input, &cfg.working_dir).await
             } else {
                 (None, vec![])
             };
-            errors.append(&mut error_list);
-            errors.append(&mut tvguide_errors);
+            user_targets.advance(JobStage::Download);
+            persist_cleanup(&cfg, input);
+            let mut error_count = error_list.len();
             let input_name = input.name.as_ref().map_or_else(|| mask_sensitive_info(input.url.as_str()), std::string::ToString::to_string);
+            errors.extend(error_list.into_iter().chain(tvguide_errors).map(|err| err.with_context(&input_name)));
             let group_count = playlistgroups.len();
             let channel_count = playlistgroups.iter()
                 .map(|group| group.channels.len())
                 .sum();
             if playlistgroups.is_empty() {
                 info!("source is empty {}", input.url);
-                errors.push(M3uFilterError::new(M3uFilterErrorKind::Notify, format!("source is empty {input_name}")));
+                errors.push(M3uFilterError::new(M3uFilterErrorKind::Notify, format!("source is empty {input_name}"))
+                    .with_category(M3uFilterErrorCategory::Download).with_context(&input_name));
+                error_count += 1;
             } else {
                 playlistgroups.iter_mut().for_each(PlaylistGroup::on_load);
+                if let Err(err) = url_index_write(&cfg, input, &playlistgroups) {
+                    error!("{err}");
+                }
+                if incremental {
+                    input_hashes.insert(input_id, input_content_hash(&playlistgroups));
+                }
                 source_playlists.push(
                     FetchedPlaylist {
                         input,
@@ -331,33 +393,215 @@ async fn process_source(cfg: Arc<Config>, source_idx: usize, user_targets: Arc<P
                     }
                 );
             }
+            user_targets.advance(JobStage::Parse);
+            user_targets.job_log(&format!("Input {input_name}: {group_count} groups, {channel_count} channels, {error_count} errors"));
             let elapsed = start_time.elapsed().as_secs();
-            stats.insert(input_id, create_input_stat(group_count, channel_count, error_list.len(),
+            stats.insert(input_id, create_input_stat(group_count, channel_count, error_count,
                                                      input.input_type.clone(), &input_name, elapsed));
         }
     }
+    if user_targets.is_cancelled() {
+        return (stats.into_values().collect(), target_stats, errors);
+    }
     if source_playlists.is_empty() {
         if log_enabled!(Level::Debug) {
             debug!("Source at index {source_idx} is empty");
         }
-        errors.push(M3uFilterError::new(M3uFilterErrorKind::Notify, format!("Source at {source_idx} is empty")));
+        errors.push(M3uFilterError::new(M3uFilterErrorKind::Notify, format!("Source at {source_idx} is empty")).with_category(M3uFilterErrorCategory::Download));
+        for target in source.targets.iter().filter(|target| is_target_enabled(target, &user_targets)) {
+            send_webhook(WebhookEvent::RefreshFailure, cfg.messaging.as_ref(), &target.name, "Source is empty");
+        }
     } else {
         if log_enabled!(Level::Debug) {
             debug!("Source has {} groups", source_playlists.iter().map(|fpl| fpl.playlistgroups.len()).sum::<usize>());
         }
-        for target in &source.targets {
-            if is_target_enabled(target, &user_targets) {
-                match process_playlist(&mut source_playlists, target, &cfg, &mut stats, &mut errors).await {
-                    Ok(()) => {}
-                    Err(mut err) => errors.append(&mut err)
+        let targets: Vec<&ConfigTarget> = source.targets.iter().filter(|target| is_target_enabled(target, &user_targets)).collect();
+        let thread_num = usize::from(cfg.target_threads);
+        if thread_num > 1 && targets.len() > 1 {
+            if log_enabled!(Level::Debug) {
+                debug!("Using {} threads for {} targets", thread_num.min(targets.len()), targets.len());
+            }
+            for fpl in &mut source_playlists {
+                playlist_resolve_source_series(&targets, &mut errors, fpl).await;
+            }
+            let (snapshot, dropped) = SourceSnapshot::new(&source_playlists);
+            if dropped > 0 {
+                errors.push(M3uFilterError::new(M3uFilterErrorKind::Notify, format!("{dropped} channels of source {source_idx} couldn't be copied for the parallel targets")));
+            }
+            for result in process_targets_parallel(&cfg, &targets, &snapshot, &input_hashes, &user_targets, &stats, thread_num) {
+                for (input_id, input_stats) in result.input_stats {
+                    if let Some(stat) = stats.get_mut(&input_id) {
+                        stat.processed_stats = input_stats.processed_stats;
+                    }
                 }
+                errors.extend(result.errors);
+                target_stats.push(result.target_stats);
             }
+        } else {
+            for target in targets {
+                if user_targets.is_cancelled() {
+                    break;
+                }
+                target_stats.push(process_source_target(&cfg, target, &mut source_playlists, &input_hashes, &user_targets, &mut stats, &mut errors).await);
+            }
+        }
+    }
+    (stats.into_values().collect(), target_stats, errors)
+}
+
+/// Processes and writes one target of a source, the errors of the target don't stop the other targets.
+async fn process_source_target(cfg: &Config, target: &ConfigTarget,
+                               source_playlists: &mut [FetchedPlaylist<'_>],
+                               input_hashes: &HashMap<u16, [u8; 32]>,
+                               user_targets: &ProcessTargets,
+                               stats: &mut HashMap<u16, InputStats>,
+                               errors: &mut Vec<M3uFilterError>) -> TargetStats {
+    let start_time = Instant::now();
+    let result = process_playlist(source_playlists, input_hashes, target, cfg, user_targets, stats, errors).await;
+    finish_target(cfg, target, result, start_time, user_targets, errors)
+}
+
+/// Sends the webhook of the processed target and logs the result to the job.
+pub(super) fn finish_target(cfg: &Config, target: &ConfigTarget, result: Result<PlaylistStats, Vec<M3uFilterError>>, start_time: Instant,
+                            user_targets: &ProcessTargets, errors: &mut Vec<M3uFilterError>) -> TargetStats {
+    let (playlist_stats, target_errors) = match result {
+        Ok(playlist_stats) => {
+            send_webhook(WebhookEvent::RefreshSuccess, cfg.messaging.as_ref(), &target.name, "");
+            (playlist_stats, vec![])
+        }
+        Err(err) => {
+            let messages: Vec<String> = err.iter().map(|e| e.message.clone()).collect();
+            send_webhook(WebhookEvent::RefreshFailure, cfg.messaging.as_ref(), &target.name, &messages.join("\n"));
+            errors.extend(err.into_iter().map(|e| e.with_context(&target.name)));
+            (PlaylistStats { group_count: 0, channel_count: 0 }, messages)
         }
+    };
+    user_targets.advance(JobStage::Write);
+    if target_errors.is_empty() {
+        user_targets.job_log(&format!("Target {}: {} groups, {} channels", target.name, playlist_stats.group_count, playlist_stats.channel_count));
+    } else {
+        user_targets.job_log(&format!("Target {} failed: {}", target.name, target_errors.join(", ")));
     }
-    (stats.into_values().collect(), errors)
+    TargetStats { name: target.name.clone(), stats: playlist_stats, errors: target_errors, secs: start_time.elapsed().as_secs() }
+}
+
+/// The fetched playlists of a source in a form which can be shared between threads,
+/// the playlists itself can't be shared because their values are reference counted.
+struct SourceSnapshot<'a> {
+    inputs: Vec<InputSnapshot<'a>>,
+}
+
+struct InputSnapshot<'a> {
+    input: &'a ConfigInput,
+    /// group id, title, cluster and the json encoded channels
+    groups: Vec<(u32, String, XtreamCluster, Vec<ChannelSnapshot>)>,
+    epg: Option<TVGuide>,
 }
 
-fn create_input_stat(group_count: usize, channel_count: usize, error_count: usize, input_type: InputType, input_name: &str, secs_took: u64) -> InputStats {
+/// The json of a channel skips the `series_fetched` flag, the resolved series are not fetched again.
+struct ChannelSnapshot {
+    content: Vec<u8>,
+    series_fetched: bool,
+}
+
+impl<'a> SourceSnapshot<'a> {
+    /// Channels which can't be encoded are logged and counted as `dropped`.
+    fn new(playlists: &[FetchedPlaylist<'a>]) -> (Self, usize) {
+        let mut dropped = 0;
+        let inputs = playlists.iter().map(|fpl| InputSnapshot {
+            input: fpl.input,
+            groups: fpl.playlistgroups.iter().map(|group| (group.id, group.title.to_string(), group.xtream_cluster,
+                                                          group.channels.iter().filter_map(|channel| {
+                                                              let header = channel.header.borrow();
+                                                              match item_to_json(&header) {
+                                                                  Ok(content) => Some(ChannelSnapshot { content, series_fetched: header.series_fetched }),
+                                                                  Err(err) => {
+                                                                      error!("Failed to copy channel {} for the parallel targets: {err}", header.name);
+                                                                      dropped += 1;
+                                                                      None
+                                                                  }
+                                                              }
+                                                          }).collect())).collect(),
+            epg: fpl.epg.clone(),
+        }).collect();
+        (Self { inputs }, dropped)
+    }
+
+    /// Every target gets its own copy of the playlists, channels which can't be decoded are logged and counted as `dropped`.
+    fn restore(&self) -> (Vec<FetchedPlaylist<'a>>, usize) {
+        let mut dropped = 0;
+        let playlists = self.inputs.iter().map(|input| FetchedPlaylist {
+            input: input.input,
+            playlistgroups: input.groups.iter().map(|(id, title, xtream_cluster, channels)| PlaylistGroup {
+                id: *id,
+                title: Rc::new(title.clone()),
+                channels: channels.iter().filter_map(|channel| {
+                    match item_from_json(&channel.content) {
+                        Ok(mut header) => {
+                            header.series_fetched = channel.series_fetched;
+                            Some(PlaylistItem { header: RefCell::new(header) })
+                        }
+                        Err(err) => {
+                            error!("Failed to restore a channel of group {title} for the parallel targets: {err}");
+                            dropped += 1;
+                            None
+                        }
+                    }
+                }).collect(),
+                xtream_cluster: *xtream_cluster,
+            }).collect(),
+            epg: input.epg.clone(),
+        }).collect();
+        (playlists, dropped)
+    }
+}
+
+struct TargetResult {
+    index: usize,
+    target_stats: TargetStats,
+    input_stats: HashMap<u16, InputStats>,
+    errors: Vec<M3uFilterError>,
+}
+
+/// Processes the targets of a source with `thread_num` threads, every thread takes the next target until all are processed.
+/// A target which fails is reported as failed, the other targets are processed.
+/// The results are returned in the order of the targets.
+fn process_targets_parallel(cfg: &Config, targets: &[&ConfigTarget], snapshot: &SourceSnapshot,
+                            input_hashes: &HashMap<u16, [u8; 32]>,
+                            user_targets: &ProcessTargets,
+                            stats: &HashMap<u16, InputStats>,
+                            thread_num: usize) -> Vec<TargetResult> {
+    let next_target = AtomicUsize::new(0);
+    let results = Mutex::new(Vec::<TargetResult>::with_capacity(targets.len()));
+    thread::scope(|scope| {
+        for _ in 0..thread_num.min(targets.len()) {
+            scope.spawn(|| {
+                loop {
+                    let index = next_target.fetch_add(1, AtomicOrdering::Relaxed);
+                    let Some(target) = targets.get(index) else { break };
+                    if user_targets.is_cancelled() {
+                        break;
+                    }
+                    let mut input_stats = stats.clone();
+                    let mut errors = vec![];
+                    let (mut playlists, dropped) = snapshot.restore();
+                    if dropped > 0 {
+                        errors.push(M3uFilterError::new(M3uFilterErrorKind::Notify, format!("{dropped} channels couldn't be restored for the target")).with_context(&target.name));
+                    }
+                    let target_stats = System::new().block_on(process_source_target(cfg, target, &mut playlists, input_hashes, user_targets, &mut input_stats, &mut errors));
+                    if let Ok(mut results) = results.lock() {
+                        results.push(TargetResult { index, target_stats, input_stats, errors });
+                    }
+                }
+            });
+        }
+    });
+    let mut results = results.into_inner().unwrap_or_default();
+    results.sort_by_key(|result| result.index);
+    results
+}
+
+pub(super) fn create_input_stat(group_count: usize, channel_count: usize, error_count: usize, input_type: InputType, input_name: &str, secs_took: u64) -> InputStats {
     InputStats {
         name: input_name.to_string(),
         input_type,
@@ -374,7 +618,7 @@ fn create_input_stat(group_count: usize, channel_count: usize, error_count: usiz
     }
 }
 
-async fn process_sources(config: Arc<Config>, user_targets: Arc<ProcessTargets>) -> (Vec<InputStats>, Vec<M3uFilterError>) {
+async fn process_sources(config: Arc<Config>, user_targets: Arc<ProcessTargets>) -> (Vec<InputStats>, Vec<TargetStats>, Vec<M3uFilterError>) {
     let mut handle_list = vec![];
     let thread_num = config.threads;
     let process_parallel = thread_num > 1 && config.sources.len() > 1;
@@ -383,39 +627,60 @@ async fn process_sources(config: Arc<Config>, user_targets: Arc<ProcessTargets>)
     }
     let errors = Arc::new(Mutex::<Vec<M3uFilterError>>::new(vec![]));
     let stats = Arc::new(Mutex::<Vec<InputStats>>::new(vec![]));
+    let target_stats = Arc::new(Mutex::<Vec<TargetStats>>::new(vec![]));
     for (index, _) in config.sources.iter().enumerate() {
         let shared_errors = errors.clone();
         let shared_stats = stats.clone();
+        let shared_target_stats = target_stats.clone();
         let cfg = config.clone();
         let usr_trgts = user_targets.clone();
         if process_parallel {
             let handles = &mut handle_list;
             let process = move || {
-                let (mut res_stats, mut res_errors) = System::new().block_on(async {
+                let (mut res_stats, mut res_target_stats, mut res_errors) = System::new().block_on(async {
                     process_source(cfg, index, usr_trgts).await
                 });
                 shared_errors.lock().unwrap().append(&mut res_errors);
                 shared_stats.lock().unwrap().append(&mut res_stats);
+                shared_target_stats.lock().unwrap().append(&mut res_target_stats);
             };
             handles.push(thread::spawn(process));
             if handles.len() >= thread_num as usize {
                 handles.drain(..).for_each(|handle| { let _ = handle.join(); });
             }
         } else {
-            let (mut res_stats, mut res_errors) = process_source(cfg, index, usr_trgts).await;
+            let (mut res_stats, mut res_target_stats, mut res_errors) = process_source(cfg, index, usr_trgts).await;
             shared_errors.lock().unwrap().append(&mut res_errors);
             shared_stats.lock().unwrap().append(&mut res_stats);
+            shared_target_stats.lock().unwrap().append(&mut res_target_stats);
         }
     }
     for handle in handle_list {
         let _ = handle.join();
     }
-    (Arc::try_unwrap(stats).unwrap().into_inner().unwrap(), Arc::try_unwrap(errors).unwrap().into_inner().unwrap())
+    (Arc::try_unwrap(stats).unwrap().into_inner().unwrap(),
+     Arc::try_unwrap(target_stats).unwrap().into_inner().unwrap(),
+     Arc::try_unwrap(errors).unwrap().into_inner().unwrap())
+}
+
+/// Applies the filter, rename and map of the target in its processing order, then the sorting and the counters.
+/// Groups with the same name are merged.
+#[cfg(test)]
+pub fn process_target_playlist(target: &ConfigTarget, mut playlist: Vec<PlaylistGroup>) -> Vec<PlaylistGroup> {
+    for f in get_processing_pipe(target) {
+        if let Some(groups) = f(&mut playlist, target) {
+            playlist = groups;
+        }
+    }
+    let mut playlist = flatten_groups(playlist);
+    sort_playlist(target, &mut playlist);
+    map_playlist_counter(target, &playlist);
+    playlist
 }
 
 pub type ProcessingPipe = Vec<fn(playlist: &mut [PlaylistGroup], target: &ConfigTarget) -> Option<Vec<PlaylistGroup>>>;
 
-fn get_processing_pipe(target: &ConfigTarget) -> ProcessingPipe {
+pub(super) fn get_processing_pipe(target: &ConfigTarget) -> ProcessingPipe {
     match &target.processing_order {
         ProcessingOrder::Frm => vec![filter_playlist, rename_playlist, map_playlist],
         ProcessingOrder::Fmr => vec![filter_playlist, map_playlist, rename_playlist],
@@ -433,11 +698,18 @@ fn execute_pipe<'a>(target: &ConfigTarget, pipe: &ProcessingPipe, fpl: &FetchedP
         playlistgroups: fpl.playlistgroups.clone(), // we need to clone, because of multiple target definitions, we cant change the initial playlist.
         epg: fpl.epg.clone(),
     };
+    // the tags are set before the pipe to be usable in filters
+    if let Some(tagging) = &target.tagging {
+        tag_playlist(tagging, &new_fpl.playlistgroups);
+    }
     for f in pipe {
         if let Some(groups) = f(&mut new_fpl.playlistgroups, target) {
             new_fpl.playlistgroups = groups;
         }
     }
+    if let Some(tagging) = &target.tagging {
+        new_fpl.playlistgroups = prefix_tagged_groups(tagging, new_fpl.playlistgroups);
+    }
     new_fpl
 }
 
@@ -464,19 +736,44 @@ fn flatten_groups(playlistgroups: Vec<PlaylistGroup>) -> Vec<PlaylistGroup> {
 }
 
 async fn process_playlist(playlists: &mut [FetchedPlaylist<'_>],
+                              input_hashes: &HashMap<u16, [u8; 32]>,
                               target: &ConfigTarget,
                               cfg: &Config,
+                              user_targets: &ProcessTargets,
                               stats: &mut HashMap<u16, InputStats>,
-                              errors: &mut Vec<M3uFilterError>) -> Result<(), Vec<M3uFilterError>> {
+                              errors: &mut Vec<M3uFilterError>) -> Result<PlaylistStats, Vec<M3uFilterError>> {
     let pipe = get_processing_pipe(target);
     if log_enabled!(Level::Debug) {
         debug!("Processing order is {}", &target.processing_order);
     }
+    // edited filter files are used without restart, the previous entries are kept if a file can't be read
+    if let Err(err) = target.load_filter_files(&cfg.t_config_path) {
+        error!("{err}");
+    }
 
     let mut new_fetched_playlists: Vec<FetchedPlaylist> = vec![];
     for fpl in playlists.iter_mut() {
-        let mut new_fpl = execute_pipe(target, &pipe, fpl);
-        playlist_resolve_series(target, errors, &pipe, fpl, &mut new_fpl).await;
+        let input_hash = input_hashes.get(&fpl.input.id).filter(|_| target.incremental);
+        // a cluster refresh has only the refreshed clusters of the input, the cache can't be used
+        let cached = input_hash.filter(|_| !user_targets.force_refresh && user_targets.clusters.is_none())
+            .and_then(|hash| incremental_load(cfg, target, fpl.input.id, hash));
+        let new_fpl = if let Some(playlistgroups) = cached {
+            FetchedPlaylist { input: fpl.input, playlistgroups, epg: fpl.epg.clone() }
+        } else {
+            let mut new_fpl = execute_pipe(target, &pipe, fpl);
+            playlist_resolve_series(target, errors, &pipe, fpl, &mut new_fpl).await;
+            if let Some(clusters) = &user_targets.clusters {
+                match incremental_load_other_clusters(cfg, target, fpl.input.id, clusters) {
+                    Some(mut other_clusters) => new_fpl.playlistgroups.append(&mut other_clusters),
+                    None => return Err(vec![M3uFilterError::new(M3uFilterErrorKind::Info,
+                        format!("Cluster refresh of target {} needs the incremental cache of a full refresh", target.name))]),
+                }
+            }
+            if let Some(hash) = input_hash {
+                incremental_store(cfg, target, fpl.input.id, hash, &new_fpl.playlistgroups);
+            }
+            new_fpl
+        };
         // stats
         let input_stats = stats.get_mut(&new_fpl.input.id);
         if let Some(stat) = input_stats {
@@ -487,24 +784,30 @@ async fn process_playlist(playlists: &mut [FetchedPlaylist<'_>],
         }
         new_fetched_playlists.push(new_fpl);
     }
+    user_targets.advance(JobStage::Filter);
 
     apply_affixes(&mut new_fetched_playlists);
 
     let mut new_playlist = vec![];
     let mut new_epg = vec![];
+    let mut target_epg_channel_ids = HashSet::new();
 
     // each fetched playlist can have its own epgl url.
     // we need to process each input epg.
     for mut fp in new_fetched_playlists {
+        let epg_timeshifts = assign_channel_epg_timeshift(&fp.playlistgroups);
         // collect all epg_channel ids
-        let epg_channel_ids: HashSet<_> = fp.playlistgroups.iter().flat_map(|g| &g.channels)
+        let mut epg_channel_ids: HashSet<_> = fp.playlistgroups.iter().flat_map(|g| &g.channels)
             .filter_map(|c| c.header.borrow().epg_channel_id.clone()).collect();
+        epg_channel_ids.extend(epg_timeshifts.iter().map(|timeshift| Rc::clone(&timeshift.epg_channel_id)));
 
         new_playlist.append(&mut fp.playlistgroups);
+        target_epg_channel_ids.extend(epg_channel_ids.iter().cloned());
         if !epg_channel_ids.is_empty() {
             if let Some(tv_guide) = fp.epg {
                 debug!("found epg information for {}", &target.name);
-                if let Some(epg) = tv_guide.filter(&epg_channel_ids) {
+                if let Some(mut epg) = tv_guide.filter(&epg_channel_ids) {
+                    apply_epg_timeshift(&mut epg, &epg_timeshifts);
                     new_epg.push(epg);
                 }
             }
@@ -512,16 +815,66 @@ async fn process_playlist(playlists: &mut [FetchedPlaylist<'_>],
             debug!("channel ids are empty");
         }
     }
+    merge_schedules_direct_epg(cfg, target_epg_channel_ids, &mut new_epg, errors).await;
 
     if new_playlist.is_empty() {
         info!("Playlist is empty: {}", &target.name);
-        Ok(())
+        if !user_targets.dry_run {
+            check_shrink_protection(cfg, target, 0, user_targets.force_refresh).map_err(|err| vec![err])?;
+        }
+        Ok(PlaylistStats { group_count: 0, channel_count: 0 })
     } else {
-        let mut flat_new_playlist = flatten_groups(new_playlist);
+        let mut flat_new_playlist = apply_channel_aliases(cfg, target, flatten_groups(new_playlist));
+        transliterate_playlist(target, &mut flat_new_playlist);
         sort_playlist(target, &mut flat_new_playlist);
         map_playlist_counter(target, &flat_new_playlist);
+        let flat_new_playlist = enrich_playlist(cfg, target, flat_new_playlist, user_targets.dry_run).await?;
+        let flat_new_playlist = apply_channel_quarantine(cfg, target, flat_new_playlist);
+        let flat_new_playlist = apply_channel_overrides(cfg, target, flat_new_playlist);
+        let mut flat_new_playlist = apply_size_budget(target, flat_new_playlist);
+        mark_adult_groups(target, &flat_new_playlist);
+        let playlist_stats = PlaylistStats {
+            group_count: flat_new_playlist.len(),
+            channel_count: flat_new_playlist.iter().map(|group| group.channels.len()).sum(),
+        };
+        if user_targets.dry_run {
+            print_channel_counts(target, &flat_new_playlist);
+            return Ok(playlist_stats);
+        }
+        check_shrink_protection(cfg, target, playlist_stats.channel_count, user_targets.force_refresh).map_err(|err| vec![err])?;
         process_watch(target, cfg, &flat_new_playlist);
+        logo_cache::cache_logos(cfg, &flat_new_playlist).await;
         persist_playlist(&mut flat_new_playlist, flatten_tvguide(&new_epg).as_ref(), target, cfg)
+            .map_err(|errors| with_category(errors, M3uFilterErrorCategory::Persistence))?;
+        store_playlist_size(cfg, target, playlist_stats.channel_count);
+        publish_target(cfg, target).await.map_err(|errors| with_category(errors, M3uFilterErrorCategory::Persistence))?;
+        tvheadend_update_network(target).await.map_err(|err| vec![err.with_category(M3uFilterErrorCategory::Api)])?;
+        Ok(playlist_stats)
+    }
+}
+
+/// The tmdb lookups, the wasm plugins and the post process command.
+/// They are skipped for a dry run, which only prints the channel counts.
+async fn enrich_playlist(cfg: &Config, target: &ConfigTarget, playlist: Vec<PlaylistGroup>, dry_run: bool) -> Result<Vec<PlaylistGroup>, Vec<M3uFilterError>> {
+    if dry_run {
+        return Ok(playlist);
+    }
+    tmdb_enrich_playlist(cfg, &playlist).await;
+    let playlist = trakt_apply_lists(cfg, target, playlist).await;
+    #[cfg(feature = "wasm")]
+    let playlist = wasm_plugin::wasm_transform_playlist(target, playlist).map_err(|err| vec![err])?;
+    post_process_playlist(target, playlist).await.map_err(|err| vec![err])
+}
+
+pub(super) fn with_category(errors: Vec<M3uFilterError>, category: M3uFilterErrorCategory) -> Vec<M3uFilterError> {
+    errors.into_iter().map(|err| err.with_category(category)).collect()
+}
+
+fn print_channel_counts(target: &ConfigTarget, playlist: &[PlaylistGroup]) {
+    let channel_count: usize = playlist.iter().map(|group| group.channels.len()).sum();
+    eprintln!("Target {}: {} groups, {channel_count} channels", target.name, playlist.len());
+    for group in playlist {
+        eprintln!("  [{}] {}: {}", group.xtream_cluster.as_str(), group.title, group.channels.len());
     }
 }
 
@@ -540,8 +893,26 @@ fn process_watch(target: &ConfigTarget, cfg: &Config, new_playlist: &Vec<Playlis
     }
 }
 
-pub async fn exec_processing(cfg: Arc<Config>, targets: Arc<ProcessTargets>) {
-    let (stats, errors) = process_sources(cfg.clone(), targets.clone()).await;
+/// Number of inputs and targets a processing run includes.
+fn count_enabled(cfg: &Config, user_targets: &ProcessTargets) -> (usize, usize) {
+    cfg.sources.iter().fold((0, 0), |(inputs, targets), source| {
+        let enabled_inputs = source.inputs.iter().filter(|input| input.enabled).count();
+        (inputs + source.inputs.iter().filter(|input| is_input_enabled(enabled_inputs, input.enabled, input.id, user_targets)).count(),
+         targets + source.targets.iter().filter(|target| is_target_enabled(target, user_targets)).count())
+    })
+}
+
+pub async fn exec_processing(cfg: Arc<Config>, targets: Arc<ProcessTargets>) -> ProcessingSummary {
+    let start_time = Instant::now();
+    if let Some(progress) = &targets.progress {
+        let (inputs, target_count) = count_enabled(&cfg, &targets);
+        progress.set_totals(inputs, target_count);
+        progress.log(&format!("Processing {inputs} inputs and {target_count} targets"));
+    }
+    let refresh_id = sd_notify::refresh_started();
+    stream_health::load_stream_health(&cfg);
+    let (stats, target_stats, errors) = process_sources(cfg.clone(), targets.clone()).await;
+    sd_notify::refresh_finished(refresh_id);
     let stats_msg = format!("{{\"stats\": {}}}", stats.iter().map(std::string::ToString::to_string).collect::<Vec<String>>().join("\n"));
     // print stats
     info!("{}", stats_msg);
@@ -550,10 +921,116 @@ pub async fn exec_processing(cfg: Arc<Config>, targets: Arc<ProcessTargets>) {
     // log errors
     for err in &errors {
         error!("{}", err.message);
+        targets.job_log(&err.message);
+    }
+    if targets.is_cancelled() {
+        targets.job_log("Cancelled");
     }
     // send errors
     if let Some(message) = get_errors_notify_message!(errors, 255) {
         let error_msg = format!("{{\"errors\": \"{}\"}}", message.as_str());
         send_message(&MsgKind::Error, cfg.messaging.as_ref(), error_msg.as_str());
     }
-}
\ No newline at end of file
+    ProcessingSummary {
+        inputs: stats,
+        targets: target_stats,
+        errors: errors.iter().map(M3uFilterErrorInfo::from).collect(),
+        secs: start_time.elapsed().as_secs(),
+    }
+}
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::model::config::{Config, ConfigInput, ConfigTarget, ConfigTargetOptions, InputType, PostProcessConfig, TargetOutput, TargetType};
+    use crate::model::playlist::{FetchedPlaylist, PlaylistGroup, PlaylistItem, PlaylistItemHeader, PlaylistItemType, XtreamCluster};
+    use crate::processing::playlist_processor::{enrich_playlist, SourceSnapshot};
+    use crate::processing::xtream_processor::playlist_resolve_source_series;
+    use crate::utils::download::get_xtream_playlist_series;
+    use crate::utils::test_utils::create_temp_dir;
+
+    const SERIES_INFO: &str = r#"{
+        "seasons": [],
+        "info": {"name": "Show", "cover": "", "plot": "", "cast": "", "director": "", "genre": "", "releaseDate": "", "last_modified": "",
+                 "rating": "", "rating_5based": 0.0, "backdrop_path": [], "youtube_trailer": "", "episode_run_time": "", "category_id": "1"},
+        "episodes": {"1": [{"id": "11", "episode_num": 1, "title": "Show S01E01", "container_extension": "mkv", "custom_sid": "", "added": "", "season": 1, "direct_source": "",
+                            "info": {"tmdb_id": 0, "releasedate": "", "plot": "", "duration_secs": 0, "duration": "", "movie_image": "", "video": {}, "audio": {}, "bitrate": 0, "rating": 0.0, "season": 1}}]}
+    }"#;
+
+    #[test]
+    fn test_source_snapshot() {
+        let input = ConfigInput::default();
+        let header = PlaylistItemHeader { name: Rc::new("Movie".to_string()), url: Rc::new("http://host/movie/1.mkv".to_string()),
+            item_type: PlaylistItemType::Video, xtream_cluster: XtreamCluster::Video, ..PlaylistItemHeader::default() };
+        let playlists = vec![FetchedPlaylist { input: &input, epg: None, playlistgroups: vec![PlaylistGroup {
+            id: 3, title: Rc::new("Movies".to_string()), xtream_cluster: XtreamCluster::Video,
+            channels: vec![PlaylistItem { header: RefCell::new(header) }],
+        }] }];
+        let (snapshot, dropped) = SourceSnapshot::new(&playlists);
+        assert_eq!(dropped, 0);
+        let (restored, dropped) = snapshot.restore();
+        assert_eq!(dropped, 0);
+        let group = &restored[0].playlistgroups[0];
+        assert_eq!((group.id, group.title.as_str(), group.xtream_cluster), (3, "Movies", XtreamCluster::Video));
+        let header = group.channels[0].header.borrow();
+        assert_eq!((header.name.as_str(), header.item_type), ("Movie", PlaylistItemType::Video));
+        assert_eq!(header.url.as_str(), "http://host/movie/1.mkv");
+    }
+
+    #[actix_rt::test]
+    async fn test_parallel_targets_fetch_series_once() {
+        let temp_dir = create_temp_dir("series_once");
+        let dir = temp_dir.path();
+        let series_file = dir.join("series_info.json");
+        std::fs::write(&series_file, SERIES_INFO).unwrap();
+        let input = ConfigInput { input_type: InputType::Xtream, url: "http://provider".to_string(), ..ConfigInput::default() };
+        let header = PlaylistItemHeader { name: Rc::new("Show".to_string()), group: Rc::new("Shows".to_string()),
+            url: Rc::new(format!("file://{}", series_file.display())), item_type: PlaylistItemType::SeriesInfo,
+            xtream_cluster: XtreamCluster::Series, ..PlaylistItemHeader::default() };
+        let mut playlists = vec![FetchedPlaylist { input: &input, epg: None, playlistgroups: vec![PlaylistGroup {
+            id: 1, title: Rc::new("Shows".to_string()), xtream_cluster: XtreamCluster::Series,
+            channels: vec![PlaylistItem { header: RefCell::new(header) }],
+        }] }];
+        let target = ConfigTarget {
+            name: "tv".to_string(),
+            options: Some(ConfigTargetOptions { xtream_resolve_series: true, ..ConfigTargetOptions::default() }),
+            output: vec![TargetOutput { target: TargetType::M3u, filename: None, template: None, sanitize: Default::default(), split_groups: false }],
+            ..ConfigTarget::default()
+        };
+        let targets = vec![&target, &target];
+        let mut errors = vec![];
+        playlist_resolve_source_series(&targets, &mut errors, &mut playlists[0]).await;
+        assert!(errors.is_empty());
+
+        // fetching again would fail without the file
+        std::fs::remove_file(&series_file).unwrap();
+        let (snapshot, _) = SourceSnapshot::new(&playlists);
+        for _ in &targets {
+            let (mut restored, _) = snapshot.restore();
+            assert!(get_xtream_playlist_series(&mut restored[0], &mut errors, 0).await.is_empty());
+            let episodes: Vec<String> = restored[0].playlistgroups.iter().flat_map(|group| &group.channels)
+                .filter(|channel| channel.header.borrow().item_type == PlaylistItemType::Series)
+                .map(|channel| channel.header.borrow().title.to_string()).collect();
+            assert_eq!(episodes, vec!["Show S01E01".to_string()]);
+        }
+        assert!(errors.is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn test_dry_run_skips_post_process() {
+        let temp_dir = create_temp_dir("post_process");
+        let marker = temp_dir.path().join("marker");
+        let target = ConfigTarget {
+            name: "tv".to_string(),
+            post_process: Some(PostProcessConfig { command: "sh".to_string(), args: vec!["-c".to_string(), format!("touch {} && cat", marker.display())], timeout: 10 }),
+            ..ConfigTarget::default()
+        };
+        let playlist = vec![PlaylistGroup { id: 1, title: Rc::new("News".to_string()), channels: vec![], xtream_cluster: XtreamCluster::Live }];
+        let cfg = Config::default();
+        assert_eq!(enrich_playlist(&cfg, &target, playlist.clone(), true).await.unwrap().len(), 1);
+        assert!(!marker.exists());
+        assert!(enrich_playlist(&cfg, &target, playlist, false).await.is_ok());
+        assert!(marker.exists());
+    }
+}
@@ -1,7 +1,7 @@
 use std::collections::BTreeSet;
 use std::path::{Path};
 use log::{error, info};
-use crate::messaging::{MsgKind, send_message};
+use crate::messaging::{MsgKind, send_message, send_webhook, WebhookEvent};
 use crate::model::config::Config;
 use crate::model::playlist::PlaylistGroup;
 use crate::utils::file_utils;
@@ -72,6 +72,7 @@ fn handle_watch_notification(cfg: &Config, added: &BTreeSet<String>, removed: &B
         let msg = format!("Changes {}/{}\n{}", target_name, group_name, message.join(""));
         info!("{}", &msg);
         send_message(&MsgKind::Watch, cfg.messaging.as_ref(), &msg);
+        send_webhook(WebhookEvent::Watch, cfg.messaging.as_ref(), target_name, &msg);
     }
 }
 
@@ -0,0 +1,166 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::rc::Rc;
+use std::time::Duration;
+
+use log::{debug, error};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::create_m3u_filter_error_result;
+use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorKind};
+use crate::model::config::{ConfigTarget, PostProcessConfig};
+use crate::model::playlist::{PlaylistGroup, PlaylistItem, PlaylistItemHeader, PlaylistItemType, XtreamCluster};
+
+/// One line of the post process input and output.
+#[derive(Serialize, Deserialize)]
+struct PostProcessItem {
+    item_type: PlaylistItemType,
+    #[serde(flatten)]
+    header: PlaylistItemHeader,
+}
+
+/// Serializes the header with its item type, the item type is skipped by the header itself.
+pub(super) fn item_to_json(header: &PlaylistItemHeader) -> Result<Vec<u8>, serde_json::Error> {
+    serde_json::to_vec(&PostProcessItem { item_type: header.item_type, header: header.clone() })
+}
+
+pub(super) fn item_from_json(content: &[u8]) -> Result<PlaylistItemHeader, serde_json::Error> {
+    let PostProcessItem { item_type, mut header } = serde_json::from_slice::<PostProcessItem>(content)?;
+    header.item_type = item_type;
+    header.gen_uuid();
+    Ok(header)
+}
+
+fn to_json_lines(playlist: &[PlaylistGroup]) -> Result<Vec<u8>, serde_json::Error> {
+    let mut content = vec![];
+    for channel in playlist.iter().flat_map(|group| &group.channels) {
+        content.append(&mut item_to_json(&channel.header.borrow())?);
+        content.push(b'\n');
+    }
+    Ok(content)
+}
+
+/// Groups the items by group title and cluster in order of their first occurrence.
+/// Existing group ids are kept.
+pub(super) fn regroup_items<I: Iterator<Item=PlaylistItemHeader>>(items: I, playlist: &[PlaylistGroup]) -> Vec<PlaylistGroup> {
+    let mut group_ids: HashMap<(Rc<String>, XtreamCluster), u32> = playlist.iter()
+        .map(|group| ((Rc::clone(&group.title), group.xtream_cluster), group.id)).collect();
+    let mut next_id = playlist.iter().map(|group| group.id).max().unwrap_or(0);
+    let mut group_index: HashMap<(Rc<String>, XtreamCluster), usize> = HashMap::new();
+    let mut result: Vec<PlaylistGroup> = vec![];
+    for header in items {
+        let key = (Rc::clone(&header.group), header.xtream_cluster);
+        let index = *group_index.entry(key.clone()).or_insert_with(|| {
+            let id = *group_ids.entry(key.clone()).or_insert_with(|| {
+                next_id += 1;
+                next_id
+            });
+            result.push(PlaylistGroup { id, title: Rc::clone(&key.0), channels: vec![], xtream_cluster: key.1 });
+            result.len() - 1
+        });
+        result[index].channels.push(PlaylistItem { header: RefCell::new(header) });
+    }
+    result
+}
+
+fn from_json_lines(content: &str, playlist: &[PlaylistGroup]) -> Vec<PlaylistGroup> {
+    let items = content.lines().filter(|line| !line.trim().is_empty()).filter_map(|line| {
+        item_from_json(line.as_bytes()).map_err(|err| error!("Post process returned invalid item: {err}")).ok()
+    });
+    regroup_items(items, playlist)
+}
+
+async fn run_command(post_process: &PostProcessConfig, input: Vec<u8>) -> std::io::Result<std::process::Output> {
+    // the child is killed when the command times out and the future is dropped
+    let mut child = Command::new(&post_process.command)
+        .args(&post_process.args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+    // write while the output is read, otherwise a full stdout pipe blocks the command
+    let stdin = child.stdin.take();
+    let writer = async move {
+        match stdin {
+            // the command gets the end of the input when stdin is dropped
+            Some(mut stdin) => stdin.write_all(&input).await,
+            None => Ok(()),
+        }
+    };
+    let (written, output) = futures::future::join(writer, child.wait_with_output()).await;
+    let output = output?;
+    written?;
+    Ok(output)
+}
+
+/// Pipes the playlist items as json lines through the `post_process` command of the target.
+pub async fn post_process_playlist(target: &ConfigTarget, playlist: Vec<PlaylistGroup>) -> Result<Vec<PlaylistGroup>, M3uFilterError> {
+    let Some(post_process) = target.post_process.as_ref() else {
+        return Ok(playlist);
+    };
+    let input = match to_json_lines(&playlist) {
+        Ok(input) => input,
+        Err(err) => return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "Failed to serialize playlist for post process of target {}: {err}", target.name),
+    };
+    match tokio::time::timeout(Duration::from_secs(post_process.timeout), run_command(post_process, input)).await {
+        Ok(Ok(output)) if output.status.success() => {
+            let result = from_json_lines(&String::from_utf8_lossy(&output.stdout), &playlist);
+            debug!("Post process of target {} returned {} groups", target.name, result.len());
+            Ok(result)
+        }
+        Ok(Ok(output)) => create_m3u_filter_error_result!(M3uFilterErrorKind::Notify, "Post process of target {} failed with {}: {}",
+            target.name, output.status, String::from_utf8_lossy(&output.stderr).trim()),
+        Ok(Err(err)) => create_m3u_filter_error_result!(M3uFilterErrorKind::Notify, "Post process of target {} failed: {err}", target.name),
+        Err(_) => create_m3u_filter_error_result!(M3uFilterErrorKind::Notify, "Post process of target {} timed out after {} seconds", target.name, post_process.timeout),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use std::time::Instant;
+
+    use crate::model::config::{ConfigTarget, PostProcessConfig};
+    use crate::model::playlist::{PlaylistGroup, PlaylistItem, PlaylistItemHeader, PlaylistItemType, XtreamCluster};
+    use crate::processing::post_process::{from_json_lines, post_process_playlist, to_json_lines};
+
+    #[test]
+    fn test_json_lines() {
+        let header = PlaylistItemHeader {
+            name: Rc::new("News".to_string()),
+            group: Rc::new("DE".to_string()),
+            url: Rc::new("http://localhost/1.m3u8".to_string()),
+            item_type: PlaylistItemType::LiveHls,
+            ..PlaylistItemHeader::default()
+        };
+        let playlist = vec![PlaylistGroup { id: 3, title: Rc::new("DE".to_string()), channels: vec![PlaylistItem { header: RefCell::new(header) }], xtream_cluster: XtreamCluster::Live }];
+        let content = String::from_utf8(to_json_lines(&playlist).unwrap()).unwrap();
+        let modified = format!("{}\n{}", content.trim(), content.trim().replace("\"DE\"", "\"FR\""));
+        let result = from_json_lines(&modified, &playlist);
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].id, 3);
+        assert_eq!(result[1].id, 4);
+        assert_eq!(result[1].title.as_str(), "FR");
+        assert_eq!(result[1].channels[0].header.borrow().item_type, PlaylistItemType::LiveHls);
+    }
+
+    #[actix_rt::test]
+    async fn test_post_process_timeout() {
+        let target = ConfigTarget {
+            name: "tv".to_string(),
+            post_process: Some(PostProcessConfig { command: "sh".to_string(), args: vec!["-c".to_string(), "exec sleep 60".to_string()], timeout: 1 }),
+            ..ConfigTarget::default()
+        };
+        let playlist = vec![PlaylistGroup { id: 1, title: Rc::new("News".to_string()), channels: vec![], xtream_cluster: XtreamCluster::Live }];
+        let started = Instant::now();
+        let err = post_process_playlist(&target, playlist).await.unwrap_err();
+        assert!(err.message.contains("timed out"), "{}", err.message);
+        assert!(started.elapsed().as_secs() < 10);
+    }
+}
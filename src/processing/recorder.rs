@@ -0,0 +1,380 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures::StreamExt;
+use log::{error, info};
+use url::Url;
+
+use crate::api::xmltv_api::get_target_epg_path;
+use crate::api::xtream_epg::read_programmes;
+use crate::model::config::{Config, RecordingConfig};
+use crate::model::playlist::XtreamCluster;
+use crate::repository::recording_repository::{get_recording_directory, recording_file_name, recording_load, recording_save, Recording, RecordingStatus};
+use crate::repository::search_repository::{search_index_find, search_index_for_each, SearchEntry};
+use crate::utils::request_utils;
+
+/// Interval in seconds the scheduled recordings are checked.
+const RECORDER_INTERVAL_SECS: u64 = 5;
+/// Interval in seconds the recording rules are evaluated against the epg.
+const RECORDER_RULES_INTERVAL_SECS: u64 = 900;
+/// Delay in seconds before the stream is reopened after the provider closed it.
+const RECORDER_RECONNECT_SECS: u64 = 3;
+
+/// Schedules and captures the recordings, the list is persisted in the recording directory.
+pub struct Recorder {
+    cfg: Arc<Config>,
+    directory: Option<PathBuf>,
+    recordings: Mutex<Vec<Recording>>,
+    active: Mutex<HashMap<String, Arc<AtomicBool>>>,
+}
+
+impl Recorder {
+    pub fn new(cfg: Arc<Config>) -> Self {
+        let directory = cfg.recording.as_ref().map(|recording_cfg| get_recording_directory(&cfg, recording_cfg));
+        let mut recordings = directory.as_ref().map(|directory| recording_load(directory)).unwrap_or_default();
+        let now = chrono::Utc::now().timestamp();
+        // recordings interrupted by a restart are continued, the stream is appended to the file
+        for recording in recordings.iter_mut().filter(|recording| recording.status == RecordingStatus::Recording) {
+            if recording.stop > now {
+                recording.status = RecordingStatus::Scheduled;
+            } else {
+                recording.status = RecordingStatus::Failed;
+                recording.error = Some("Interrupted".to_string());
+            }
+        }
+        Self { cfg, directory, recordings: Mutex::new(recordings), active: Mutex::new(HashMap::new()) }
+    }
+
+    pub fn get_config(&self) -> Option<&RecordingConfig> {
+        self.cfg.recording.as_ref()
+    }
+
+    pub fn list(&self) -> Vec<Recording> {
+        self.recordings.lock().map(|recordings| recordings.clone()).unwrap_or_default()
+    }
+
+    fn save(&self, recordings: &[Recording]) {
+        if let Some(directory) = &self.directory {
+            if let Err(err) = recording_save(directory, recordings) {
+                error!("{err}");
+            }
+        }
+    }
+
+    fn update<F: FnOnce(&mut Recording)>(&self, id: &str, change: F) {
+        if let Ok(mut recordings) = self.recordings.lock() {
+            if let Some(recording) = recordings.iter_mut().find(|recording| recording.id == id) {
+                change(recording);
+            }
+            self.save(&recordings);
+        }
+    }
+
+    pub fn schedule(&self, recording: Recording) -> Recording {
+        if let Ok(mut recordings) = self.recordings.lock() {
+            recordings.push(recording.clone());
+            self.save(&recordings);
+        }
+        recording
+    }
+
+    /// Cancels a running recording and deletes the recording with its file.
+    /// Recordings of a rule are kept as cancelled until the programme is over.
+    pub fn remove(&self, id: &str) -> bool {
+        if let Some(cancel) = self.active.lock().ok().and_then(|active| active.get(id).cloned()) {
+            cancel.store(true, Ordering::Relaxed);
+        }
+        let Ok(mut recordings) = self.recordings.lock() else {
+            return false;
+        };
+        let Some(index) = recordings.iter().position(|recording| recording.id == id) else {
+            return false;
+        };
+        let now = chrono::Utc::now().timestamp();
+        if recordings[index].rule.is_some() && recordings[index].stop > now {
+            self.delete_file(&recordings[index]);
+            let recording = &mut recordings[index];
+            recording.status = RecordingStatus::Cancelled;
+            recording.file = None;
+            recording.size = 0;
+        } else {
+            let recording = recordings.remove(index);
+            self.delete_file(&recording);
+        }
+        self.save(&recordings);
+        true
+    }
+
+    pub fn get_file_path(&self, id: &str) -> Option<PathBuf> {
+        let recordings = self.recordings.lock().ok()?;
+        let file = recordings.iter().find(|recording| recording.id == id)?.file.as_ref()?;
+        self.directory.as_ref().map(|directory| directory.join(file)).filter(|path| path.is_file())
+    }
+
+    fn delete_file(&self, recording: &Recording) {
+        if let Some(path) = self.directory.as_ref().zip(recording.file.as_ref()).map(|(directory, file)| directory.join(file)) {
+            if path.exists() {
+                if let Err(err) = std::fs::remove_file(&path) {
+                    error!("Failed to delete recording {}: {err}", path.to_str().unwrap_or("?"));
+                }
+            }
+        }
+    }
+
+    /// Deletes the finished recordings exceeding `max_age_days` or `max_size`, the oldest first.
+    fn apply_retention(&self, recording_cfg: &RecordingConfig) {
+        let Ok(mut recordings) = self.recordings.lock() else {
+            return;
+        };
+        let now = chrono::Utc::now().timestamp();
+        let max_age = recording_cfg.max_age_days.map(|days| i64::from(days) * 86_400);
+        let mut total_size: u64 = recordings.iter().map(|recording| recording.size).sum();
+        let max_size = recording_cfg.max_size.map(|max_size| max_size * 1_048_576);
+        let mut finished: Vec<(i64, String)> = recordings.iter().filter(|recording| recording.status.is_finished())
+            .map(|recording| (recording.stop, recording.id.clone())).collect();
+        finished.sort();
+        let mut expired = vec![];
+        for (stop, id) in finished {
+            let cancelled = recordings.iter().any(|recording| recording.id == id && recording.status == RecordingStatus::Cancelled && stop < now);
+            let too_old = cancelled || max_age.is_some_and(|max_age| now - stop > max_age);
+            let too_big = max_size.is_some_and(|max_size| total_size > max_size);
+            if !(too_old || too_big) {
+                continue;
+            }
+            if let Some(recording) = recordings.iter().find(|recording| recording.id == id) {
+                total_size = total_size.saturating_sub(recording.size);
+            }
+            expired.push(id);
+        }
+        if expired.is_empty() {
+            return;
+        }
+        recordings.retain(|recording| {
+            let keep = !expired.contains(&recording.id);
+            if !keep {
+                info!("Deleting recording {} by retention", recording.title);
+                self.delete_file(recording);
+            }
+            keep
+        });
+        self.save(&recordings);
+    }
+
+    /// Schedules the programmes matching the rules, programmes which are already scheduled are skipped.
+    fn apply_rules(&self, recording_cfg: &RecordingConfig) {
+        let now = chrono::Utc::now().timestamp();
+        let mut scheduled = vec![];
+        for rule in &recording_cfg.rules {
+            let Some(target) = self.cfg.sources.iter().flat_map(|source| &source.targets).find(|target| target.name == rule.target) else {
+                continue;
+            };
+            let mut channels: HashMap<String, Vec<SearchEntry>> = HashMap::new();
+            let result = search_index_for_each(&self.cfg, &target.name, |entry| {
+                if entry.xtream_cluster == XtreamCluster::Live && !entry.epg_channel_id.is_empty() && rule.matches_channel(&entry.name) {
+                    channels.entry(entry.epg_channel_id.clone()).or_default().push(entry);
+                }
+                Ok(())
+            });
+            if let Err(err) = result {
+                error!("Recording rule {}: {err}", rule.name);
+                continue;
+            }
+            let Some(epg_path) = get_target_epg_path(&self.cfg, target).filter(|_| !channels.is_empty()) else {
+                continue;
+            };
+            let epg_channel_ids = channels.keys().cloned().collect::<HashSet<_>>();
+            for (epg_channel_id, programme) in read_programmes(&epg_path, &epg_channel_ids) {
+                if programme.stop <= now || !rule.matches_title(&programme.title) {
+                    continue;
+                }
+                for entry in channels.get(&epg_channel_id).into_iter().flatten() {
+                    scheduled.push(Recording {
+                        id: format!("{:016x}", rand::random::<u64>()),
+                        target: target.name.clone(),
+                        virtual_id: entry.virtual_id,
+                        name: entry.name.clone(),
+                        title: programme.title.clone(),
+                        start: programme.start,
+                        stop: programme.stop,
+                        status: RecordingStatus::Scheduled,
+                        file: None,
+                        size: 0,
+                        error: None,
+                        rule: Some(rule.name.clone()),
+                    });
+                }
+            }
+        }
+        let Ok(mut recordings) = self.recordings.lock() else {
+            return;
+        };
+        let count = recordings.len();
+        for recording in scheduled {
+            let exists = recordings.iter().any(|rec| rec.target == recording.target && rec.virtual_id == recording.virtual_id && rec.start == recording.start);
+            if !exists {
+                info!("Recording rule {} scheduled {}", recording.rule.as_deref().unwrap_or_default(), recording.title);
+                recordings.push(recording);
+            }
+        }
+        if recordings.len() != count {
+            self.save(&recordings);
+        }
+    }
+
+    /// Marks the due recordings as running and returns them.
+    fn take_due(&self, recording_cfg: &RecordingConfig) -> Vec<Recording> {
+        let now = chrono::Utc::now().timestamp();
+        let Ok(mut recordings) = self.recordings.lock() else {
+            return vec![];
+        };
+        let mut due = vec![];
+        let mut changed = false;
+        for recording in recordings.iter_mut().filter(|recording| recording.status == RecordingStatus::Scheduled) {
+            if recording.stop + i64::from(recording_cfg.padding_after) <= now {
+                recording.status = RecordingStatus::Failed;
+                recording.error = Some("Missed".to_string());
+                changed = true;
+            } else if recording.start - i64::from(recording_cfg.padding_before) <= now {
+                recording.status = RecordingStatus::Recording;
+                if recording.file.is_none() {
+                    recording.file = Some(recording_file_name(recording));
+                }
+                due.push(recording.clone());
+            }
+        }
+        if changed || !due.is_empty() {
+            self.save(&recordings);
+        }
+        due
+    }
+}
+
+async fn open_stream(cfg: &Config, recording: &Recording) -> Result<reqwest::Response, String> {
+    let entry = search_index_find(cfg, &recording.target, recording.virtual_id)
+        .map_err(|err| err.to_string())?
+        .ok_or_else(|| format!("Channel {} not found in target {}", recording.virtual_id, recording.target))?;
+    if request_utils::is_direct_stream_url(&entry.url) {
+        return Err(format!("Channel {} is an udp, rtp or rtsp stream, only http streams can be recorded", recording.virtual_id));
+    }
+    let url = Url::parse(&entry.url).map_err(|err| format!("Url is malformed {err}"))?;
+    let input = cfg.get_input_by_id(entry.input_id);
+    let request = request_utils::get_client_request(input, &url, None);
+    let response = request_utils::send_with_retries(input, request).await
+        .map_err(|err| format!("Failed to open stream {}", request_utils::mask_sensitive_info(err.to_string().as_str())))?;
+    if response.status().is_success() {
+        Ok(response)
+    } else {
+        Err(format!("Failed to open stream, status {}", response.status()))
+    }
+}
+
+/// Writes the stream to the file until the stop time, the stream is reopened if the provider closes it.
+async fn capture(recorder: &Recorder, recording: &Recording, path: &PathBuf, stop: i64, cancel: &AtomicBool) -> Result<u64, String> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path).map_err(|err| format!("Failed to create file {}: {err}", path.to_str().unwrap_or("?")))?;
+    let mut size: u64 = file.metadata().map(|meta| meta.len()).unwrap_or(0);
+    let remaining = || u64::try_from(stop - chrono::Utc::now().timestamp()).unwrap_or(0);
+    let mut last_error = None;
+    while remaining() > 0 && !cancel.load(Ordering::Relaxed) {
+        match open_stream(&recorder.cfg, recording).await {
+            Ok(response) => {
+                let mut stream = response.bytes_stream();
+                loop {
+                    if cancel.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    // a stalled stream must not block the stop of the recording
+                    match actix_rt::time::timeout(Duration::from_secs(remaining()), stream.next()).await {
+                        Ok(Some(Ok(chunk))) => {
+                            file.write_all(&chunk).map_err(|err| format!("Failed to write recording: {err}"))?;
+                            size += chunk.len() as u64;
+                        }
+                        Ok(Some(Err(err))) => {
+                            last_error = Some(format!("Stream failed: {err}"));
+                            break;
+                        }
+                        Ok(None) | Err(_) => break,
+                    }
+                }
+                recorder.update(&recording.id, |rec| rec.size = size);
+            }
+            Err(err) => {
+                error!("Recording {}: {err}", recording.title);
+                last_error = Some(err);
+            }
+        }
+        if remaining() > 0 && !cancel.load(Ordering::Relaxed) {
+            actix_rt::time::sleep(Duration::from_secs(RECORDER_RECONNECT_SECS.min(remaining()))).await;
+        }
+    }
+    file.flush().map_err(|err| format!("Failed to write recording: {err}"))?;
+    match last_error {
+        Some(err) if size == 0 => Err(err),
+        _ => Ok(size),
+    }
+}
+
+async fn run_recording(recorder: Arc<Recorder>, recording: Recording, recording_cfg: RecordingConfig) {
+    let (Some(directory), Some(file)) = (recorder.directory.clone(), recording.file.clone()) else {
+        return;
+    };
+    let cancel = Arc::new(AtomicBool::new(false));
+    if let Ok(mut active) = recorder.active.lock() {
+        active.insert(recording.id.clone(), Arc::clone(&cancel));
+    }
+    info!("Recording started {}", recording.title);
+    let result = match std::fs::create_dir_all(&directory) {
+        Ok(()) => capture(&recorder, &recording, &directory.join(file), recording.stop + i64::from(recording_cfg.padding_after), &cancel).await,
+        Err(err) => Err(format!("Failed to create recording directory: {err}")),
+    };
+    if let Ok(mut active) = recorder.active.lock() {
+        active.remove(&recording.id);
+    }
+    match result {
+        Ok(size) => {
+            info!("Recording finished {}, filesize: {}MB", recording.title, request_utils::bytes_to_megabytes(size));
+            recorder.update(&recording.id, |rec| {
+                rec.status = RecordingStatus::Completed;
+                rec.size = size;
+            });
+        }
+        Err(err) => {
+            error!("Recording failed {}: {err}", recording.title);
+            recorder.update(&recording.id, |rec| {
+                rec.status = RecordingStatus::Failed;
+                rec.error = Some(err);
+            });
+        }
+    }
+    recorder.apply_retention(&recording_cfg);
+}
+
+pub fn start_recorder(recorder: &Arc<Recorder>) {
+    let Some(recording_cfg) = recorder.get_config().cloned() else {
+        return;
+    };
+    let recorder = Arc::clone(recorder);
+    actix_rt::spawn(async move {
+        recorder.apply_retention(&recording_cfg);
+        let mut rules_applied: Option<Instant> = None;
+        loop {
+            if !recording_cfg.rules.is_empty() && rules_applied.is_none_or(|applied| applied.elapsed().as_secs() >= RECORDER_RULES_INTERVAL_SECS) {
+                rules_applied = Some(Instant::now());
+                let rule_recorder = Arc::clone(&recorder);
+                let rule_cfg = recording_cfg.clone();
+                // reading the epg is blocking
+                if let Err(err) = actix_rt::task::spawn_blocking(move || rule_recorder.apply_rules(&rule_cfg)).await {
+                    error!("Failed to apply recording rules: {err}");
+                }
+            }
+            for recording in recorder.take_due(&recording_cfg) {
+                actix_rt::spawn(run_recording(Arc::clone(&recorder), recording, recording_cfg.clone()));
+            }
+            actix_rt::time::sleep(Duration::from_secs(RECORDER_INTERVAL_SECS)).await;
+        }
+    });
+}
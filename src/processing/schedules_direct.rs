@@ -0,0 +1,283 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::{Duration, SystemTime};
+
+use log::{debug, info};
+use serde_json::{json, Value};
+use sha1::{Digest, Sha1};
+
+use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorCategory, M3uFilterErrorKind};
+use crate::model::config::{Config, SchedulesDirectConfig};
+use crate::model::xmltv::{Epg, TVGuide, XmlTag, EPG_ATTRIB_CHANNEL, EPG_ATTRIB_ID, EPG_TAG_CHANNEL, EPG_TAG_PROGRAMME};
+use crate::repository::epg_repository::epg_write_xml_file;
+
+const SD_API_URL: &str = "https://json.schedulesdirect.org/20141201";
+const FILE_SCHEDULES_DIRECT: &str = "schedules_direct.xml";
+/// Maximum number of stations or programs of one request.
+const SD_REQUEST_LIMIT: usize = 5000;
+const SD_TIMEOUT_SECS: u64 = 120;
+const EPG_DATE_FORMAT: &str = "%Y%m%d%H%M%S %z";
+
+/// A station of a lineup, the xmltv channel id is `<station_id>.schedulesdirect.org`.
+#[derive(Debug, Clone)]
+struct SdStation {
+    id: String,
+    name: String,
+    callsign: String,
+    channel: String,
+    logo: Option<String>,
+}
+
+fn sd_error(message: String) -> M3uFilterError {
+    M3uFilterError::new(M3uFilterErrorKind::Notify, message).with_category(M3uFilterErrorCategory::Download)
+}
+
+fn get_guide_path(cfg: &Config) -> PathBuf {
+    PathBuf::from(&cfg.working_dir).join(FILE_SCHEDULES_DIRECT)
+}
+
+fn get_channel_id(station_id: &str) -> String {
+    format!("{station_id}.schedulesdirect.org")
+}
+
+fn get_str(value: &Value, field: &str) -> Option<String> {
+    value.get(field).and_then(Value::as_str).map(str::trim).filter(|text| !text.is_empty()).map(ToString::to_string)
+}
+
+async fn sd_request(client: &reqwest::Client, token: Option<&str>, path: &str, body: Option<Value>) -> Result<Value, M3uFilterError> {
+    let url = format!("{SD_API_URL}/{path}");
+    let mut request = match &body {
+        Some(body) => client.post(&url).json(body),
+        None => client.get(&url),
+    };
+    if let Some(token) = token {
+        request = request.header("token", token);
+    }
+    let response = request.send().await.map_err(|err| {
+        M3uFilterError::from_source(M3uFilterErrorKind::Notify, M3uFilterErrorCategory::Download, err, format!("Schedules Direct request {path} failed"))
+    })?;
+    let status = response.status();
+    let content = response.json::<Value>().await.map_err(|err| {
+        M3uFilterError::from_source(M3uFilterErrorKind::Notify, M3uFilterErrorCategory::Parse, err, format!("Failed to parse Schedules Direct response {path}"))
+    })?;
+    // errors are reported with a `code` other than 0 and a `message`
+    let code = content.get("code").and_then(Value::as_i64).unwrap_or(0);
+    if !status.is_success() || code != 0 {
+        let message = get_str(&content, "message").unwrap_or_else(|| status.to_string());
+        return Err(sd_error(format!("Schedules Direct request {path} failed: {message}")));
+    }
+    Ok(content)
+}
+
+async fn get_token(client: &reqwest::Client, sd: &SchedulesDirectConfig) -> Result<String, M3uFilterError> {
+    // the api expects the lower case sha1 hex digest of the password
+    let password: String = Sha1::digest(sd.password.as_bytes()).iter().map(|b| format!("{b:02x}")).collect();
+    let content = sd_request(client, None, "token", Some(json!({"username": sd.username, "password": password}))).await?;
+    get_str(&content, "token").ok_or_else(|| sd_error("Schedules Direct login failed".to_string()))
+}
+
+async fn get_lineups(client: &reqwest::Client, token: &str, sd: &SchedulesDirectConfig) -> Result<Vec<String>, M3uFilterError> {
+    if !sd.lineups.is_empty() {
+        return Ok(sd.lineups.clone());
+    }
+    let content = sd_request(client, Some(token), "lineups", None).await?;
+    Ok(content.get("lineups").and_then(Value::as_array).into_iter().flatten()
+        .filter_map(|lineup| get_str(lineup, "lineup")).collect())
+}
+
+/// The stations of a lineup with their channel number.
+fn parse_lineup(content: &Value) -> Vec<SdStation> {
+    let channels: HashMap<String, String> = content.get("map").and_then(Value::as_array).into_iter().flatten()
+        .filter_map(|entry| Some((get_str(entry, "stationID")?, get_str(entry, "channel").unwrap_or_default())))
+        .collect();
+    content.get("stations").and_then(Value::as_array).into_iter().flatten().filter_map(|station| {
+        let id = get_str(station, "stationID")?;
+        Some(SdStation {
+            name: get_str(station, "name").unwrap_or_default(),
+            callsign: get_str(station, "callsign").unwrap_or_default(),
+            channel: channels.get(&id).cloned().unwrap_or_default(),
+            logo: station.get("logo").and_then(|logo| get_str(logo, "URL")),
+            id,
+        })
+    }).collect()
+}
+
+fn format_time(date_time: chrono::DateTime<chrono::Utc>) -> String {
+    date_time.format(EPG_DATE_FORMAT).to_string()
+}
+
+fn text_tag(name: &str, value: String) -> Rc<XmlTag> {
+    Rc::new(XmlTag::new(name, Some(value), vec![], None))
+}
+
+fn create_channel(station: &SdStation) -> XmlTag {
+    let mut children = vec![];
+    for name in [&station.callsign, &station.name, &station.channel] {
+        if !name.is_empty() {
+            children.push(text_tag("display-name", name.clone()));
+        }
+    }
+    if let Some(logo) = &station.logo {
+        children.push(Rc::new(XmlTag::new("icon", None, vec![("src", logo.clone())], None)));
+    }
+    XmlTag::new(EPG_TAG_CHANNEL, None, vec![(EPG_ATTRIB_ID, get_channel_id(&station.id))], Some(children))
+}
+
+fn create_programme(channel_id: &str, airing: &Value, program: &Value) -> Option<XmlTag> {
+    let start = chrono::DateTime::parse_from_rfc3339(&get_str(airing, "airDateTime")?).ok()?.with_timezone(&chrono::Utc);
+    let stop = start + chrono::Duration::seconds(airing.get("duration").and_then(Value::as_i64)?);
+    let title = program.get("titles").and_then(Value::as_array)?.first().and_then(|title| get_str(title, "title120"))?;
+    let mut children = vec![text_tag("title", title)];
+    if let Some(episode_title) = get_str(program, "episodeTitle150") {
+        children.push(text_tag("sub-title", episode_title));
+    }
+    let descriptions = program.get("descriptions");
+    let description = ["description1000", "description100"].iter()
+        .find_map(|kind| descriptions.and_then(|desc| desc.get(kind)).and_then(Value::as_array).and_then(|list| list.first()))
+        .and_then(|desc| get_str(desc, "description"));
+    if let Some(description) = description {
+        children.push(text_tag("desc", description));
+    }
+    for genre in program.get("genres").and_then(Value::as_array).into_iter().flatten().filter_map(Value::as_str) {
+        children.push(text_tag("category", genre.to_string()));
+    }
+    Some(XmlTag::new(EPG_TAG_PROGRAMME, None,
+                     vec![("start", format_time(start)), ("stop", format_time(stop)), (EPG_ATTRIB_CHANNEL, channel_id.to_string())],
+                     Some(children)))
+}
+
+/// Converts the stations, their schedules and the programs into xmltv.
+fn create_epg(stations: &[SdStation], schedules: &[Value], programs: &HashMap<String, Value>) -> Epg {
+    let mut children: Vec<XmlTag> = stations.iter().map(create_channel).collect();
+    for schedule in schedules {
+        let Some(channel_id) = get_str(schedule, "stationID").map(|id| get_channel_id(&id)) else {
+            continue;
+        };
+        for airing in schedule.get("programs").and_then(Value::as_array).into_iter().flatten() {
+            let program = get_str(airing, "programID").and_then(|id| programs.get(&id));
+            if let Some(programme) = program.and_then(|program| create_programme(&channel_id, airing, program)) {
+                children.push(programme);
+            }
+        }
+    }
+    Epg {
+        attributes: Some(Rc::new(HashMap::from([("generator-info-name".to_string(), "m3u-filter".to_string())]))),
+        children,
+    }
+}
+
+async fn download_epg(sd: &SchedulesDirectConfig) -> Result<Epg, M3uFilterError> {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(SD_TIMEOUT_SECS))
+        .user_agent(format!("m3u-filter/{}", env!("CARGO_PKG_VERSION")))
+        .build()
+        .map_err(|err| sd_error(format!("Failed to create client for Schedules Direct: {err}")))?;
+    let token = get_token(&client, sd).await?;
+    let mut stations: Vec<SdStation> = vec![];
+    for lineup in get_lineups(&client, &token, sd).await? {
+        let content = sd_request(&client, Some(&token), &format!("lineups/{lineup}"), None).await?;
+        for station in parse_lineup(&content) {
+            if !stations.iter().any(|known| known.id == station.id) {
+                stations.push(station);
+            }
+        }
+    }
+    let today = chrono::Utc::now().date_naive();
+    let dates: Vec<String> = (0..i64::from(sd.days)).map(|day| (today + chrono::Duration::days(day)).format("%Y-%m-%d").to_string()).collect();
+    let mut schedules: Vec<Value> = vec![];
+    for chunk in stations.chunks(SD_REQUEST_LIMIT) {
+        let body: Vec<Value> = chunk.iter().map(|station| json!({"stationID": station.id, "date": dates})).collect();
+        if let Value::Array(mut list) = sd_request(&client, Some(&token), "schedules", Some(Value::Array(body))).await? {
+            schedules.append(&mut list);
+        }
+    }
+    let program_ids: Vec<String> = schedules.iter()
+        .flat_map(|schedule| schedule.get("programs").and_then(Value::as_array).into_iter().flatten())
+        .filter_map(|airing| get_str(airing, "programID"))
+        .collect::<HashSet<String>>().into_iter().collect();
+    let mut programs: HashMap<String, Value> = HashMap::new();
+    for chunk in program_ids.chunks(SD_REQUEST_LIMIT) {
+        if let Value::Array(list) = sd_request(&client, Some(&token), "programs", Some(json!(chunk))).await? {
+            programs.extend(list.into_iter().filter_map(|program| Some((get_str(&program, "programID")?, program))));
+        }
+    }
+    info!("Schedules Direct guide downloaded, {} stations, {} programs", stations.len(), programs.len());
+    Ok(create_epg(&stations, &schedules, &programs))
+}
+
+/// The guide of the Schedules Direct account, downloaded again when it is older than the `interval`.
+async fn get_guide(cfg: &Config, sd: &SchedulesDirectConfig) -> Result<TVGuide, M3uFilterError> {
+    let path = get_guide_path(cfg);
+    let age = std::fs::metadata(&path).and_then(|meta| meta.modified()).ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok());
+    if age.is_some_and(|age| age.as_secs() < sd.interval) {
+        debug!("Using cached Schedules Direct guide {}", path.display());
+    } else {
+        let epg = download_epg(sd).await?;
+        epg_write_xml_file(&epg, &path, None)?;
+    }
+    Ok(TVGuide { file: path })
+}
+
+/// Adds the Schedules Direct programmes of the channels which are not part of the guides of the inputs.
+pub async fn merge_schedules_direct_epg(cfg: &Config, mut channel_ids: HashSet<Rc<String>>, epgs: &mut Vec<Epg>, errors: &mut Vec<M3uFilterError>) {
+    let Some(sd) = cfg.schedules_direct.as_ref().filter(|sd| sd.enabled) else {
+        return;
+    };
+    for epg in epgs.iter() {
+        for channel in epg.children.iter().filter(|tag| tag.name == EPG_TAG_CHANNEL) {
+            if let Some(id) = channel.get_attribute_value(EPG_ATTRIB_ID) {
+                channel_ids.remove(id);
+            }
+        }
+    }
+    if channel_ids.is_empty() {
+        return;
+    }
+    match get_guide(cfg, sd).await {
+        Ok(guide) => {
+            if let Some(epg) = guide.filter(&channel_ids) {
+                epgs.push(epg);
+            }
+        }
+        Err(err) => errors.push(err.with_context("schedules_direct")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+
+    use crate::processing::schedules_direct::{create_epg, parse_lineup};
+
+    #[test]
+    fn test_create_epg() {
+        let lineup = json!({
+            "map": [{"stationID": "10021", "channel": "002"}],
+            "stations": [{"stationID": "10021", "name": "AMC Network", "callsign": "AMC", "logo": {"URL": "https://sd/amc.png"}}]
+        });
+        let stations = parse_lineup(&lineup);
+        assert_eq!((stations[0].id.as_str(), stations[0].channel.as_str()), ("10021", "002"));
+        let schedules = vec![json!({"stationID": "10021", "programs": [
+            {"programID": "EP1", "airDateTime": "2024-01-01T05:00:00Z", "duration": 1800},
+            {"programID": "EP2", "airDateTime": "2024-01-01T05:30:00Z", "duration": 1800}
+        ]})];
+        let programs = HashMap::from([("EP1".to_string(), json!({
+            "programID": "EP1", "titles": [{"title120": "News"}], "episodeTitle150": "Morning",
+            "descriptions": {"description100": [{"descriptionLanguage": "en", "description": "Short"}]}, "genres": ["News"]
+        }))]);
+        let epg = create_epg(&stations, &schedules, &programs);
+        assert_eq!(epg.children.len(), 2);
+        let channel = &epg.children[0];
+        assert_eq!(channel.get_attribute_value("id").unwrap(), "10021.schedulesdirect.org");
+        assert_eq!(channel.children.as_ref().unwrap().len(), 4);
+        let programme = &epg.children[1];
+        assert_eq!(programme.get_attribute_value("start").unwrap(), "20240101050000 +0000");
+        assert_eq!(programme.get_attribute_value("stop").unwrap(), "20240101053000 +0000");
+        let values: Vec<&str> = programme.children.as_ref().unwrap().iter().filter_map(|tag| tag.value.as_deref()).collect();
+        assert_eq!(values, vec!["News", "Morning", "Short", "News"]);
+    }
+}
@@ -0,0 +1,175 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use log::{debug, error, info, log_enabled, Level};
+use serde_json::Value;
+
+use crate::model::config::{Config, ConfigInput, ConfigTarget, ShortEpgConfig};
+use crate::model::xmltv::{Epg, XmlTag, EPG_ATTRIB_CHANNEL, EPG_ATTRIB_ID, EPG_TAG_CHANNEL, EPG_TAG_PROGRAMME};
+use crate::processing::input_source;
+use crate::repository::epg_repository::epg_write_file;
+use crate::repository::playlist_repository::{load_target_live_channels, LiveChannel};
+use crate::repository::storage::get_target_storage_path;
+use crate::utils::request_utils;
+use crate::utils::request_utils::mask_sensitive_info;
+
+const FILE_SHORT_EPG: &str = "short_epg.xml";
+const ACTION_GET_SHORT_EPG: &str = "get_short_epg";
+const EPG_DATE_FORMAT: &str = "%Y%m%d%H%M%S %z";
+
+/// Returns the path of the generated epg of a target, `None` if there is none.
+pub fn get_short_epg_file_path(cfg: &Config, target: &ConfigTarget) -> Option<PathBuf> {
+    get_target_storage_path(cfg, &target.name)
+        .map(|target_path| target_path.join(FILE_SHORT_EPG))
+        .filter(|path| path.is_file())
+}
+
+/// Only inputs with xtream credentials and without a xmltv url are polled.
+fn get_short_epg_url(input: &ConfigInput, provider_id: &str, limit: u32) -> Option<String> {
+    if input.epg_url.is_some() {
+        return None;
+    }
+    input_source::get_active_user_info(input).map(|user_info| format!("{}/player_api.php?username={}&password={}&action={ACTION_GET_SHORT_EPG}&stream_id={provider_id}&limit={limit}",
+                                                  &user_info.base_url, &user_info.username, &user_info.password))
+}
+
+/// Short epg titles and descriptions are base64 encoded.
+fn decode_text(value: Option<&Value>) -> Option<String> {
+    let text = value.and_then(Value::as_str)?;
+    let decoded = STANDARD.decode(text).ok().and_then(|bytes| String::from_utf8(bytes).ok());
+    Some(decoded.unwrap_or_else(|| text.to_string())).filter(|text| !text.is_empty())
+}
+
+fn get_timestamp(value: Option<&Value>) -> Option<i64> {
+    match value? {
+        Value::Number(number) => number.as_i64(),
+        Value::String(text) => text.parse::<i64>().ok(),
+        _ => None,
+    }
+}
+
+fn format_timestamp(timestamp: i64) -> Option<String> {
+    chrono::DateTime::from_timestamp(timestamp, 0).map(|date_time| date_time.format(EPG_DATE_FORMAT).to_string())
+}
+
+fn create_programmes(epg_channel_id: &str, content: &str) -> Vec<XmlTag> {
+    let Ok(doc) = serde_json::from_str::<Value>(content) else {
+        return vec![];
+    };
+    let Some(listings) = doc.get("epg_listings").and_then(Value::as_array) else {
+        return vec![];
+    };
+    listings.iter().filter_map(|listing| {
+        let start = format_timestamp(get_timestamp(listing.get("start_timestamp"))?)?;
+        let stop = format_timestamp(get_timestamp(listing.get("stop_timestamp"))?)?;
+        let title = decode_text(listing.get("title"))?;
+        let mut children = vec![Rc::new(XmlTag::new("title", Some(title), vec![], None))];
+        if let Some(desc) = decode_text(listing.get("description")) {
+            children.push(Rc::new(XmlTag::new("desc", Some(desc), vec![], None)));
+        }
+        Some(XmlTag::new(EPG_TAG_PROGRAMME, None,
+                     vec![("start", start), ("stop", stop), (EPG_ATTRIB_CHANNEL, epg_channel_id.to_string())],
+                     Some(children)))
+    }).collect()
+}
+
+async fn create_short_epg(cfg: &Config, short_epg_cfg: &ShortEpgConfig, channels: Vec<LiveChannel>) -> Option<Epg> {
+    let mut channel_tags = vec![];
+    let mut programme_tags = vec![];
+    let mut processed = HashSet::new();
+    for channel in channels {
+        let Some(epg_channel_id) = channel.epg_channel_id.filter(|id| !id.is_empty()) else {
+            continue;
+        };
+        if processed.contains(&epg_channel_id) {
+            continue;
+        }
+        let Some(input) = cfg.get_input_by_id(channel.input_id) else {
+            continue;
+        };
+        let Some(url) = get_short_epg_url(input, &channel.provider_id, short_epg_cfg.limit) else {
+            continue;
+        };
+        processed.insert(epg_channel_id.clone());
+        match request_utils::download_text_content(input, &url, None).await {
+            Ok(content) => {
+                let mut programmes = create_programmes(&epg_channel_id, &content);
+                if !programmes.is_empty() {
+                    let display_name = Rc::new(XmlTag::new("display-name", Some(channel.name), vec![], None));
+                    channel_tags.push(XmlTag::new(EPG_TAG_CHANNEL, None, vec![(EPG_ATTRIB_ID, epg_channel_id)], Some(vec![display_name])));
+                    programme_tags.append(&mut programmes);
+                }
+            }
+            Err(err) => {
+                if log_enabled!(Level::Debug) {
+                    debug!("Failed to download short epg {}: {}", mask_sensitive_info(&url), mask_sensitive_info(&err.to_string()));
+                }
+            }
+        }
+    }
+    if channel_tags.is_empty() {
+        return None;
+    }
+    channel_tags.append(&mut programme_tags);
+    Some(Epg {
+        attributes: Some(Rc::new(HashMap::from([("generator-info-name".to_string(), "m3u-filter".to_string())]))),
+        children: channel_tags,
+    })
+}
+
+async fn update_short_epg(cfg: &Config, short_epg_cfg: &ShortEpgConfig) {
+    for target in cfg.sources.iter().flat_map(|source| &source.targets).filter(|target| target.enabled) {
+        let channels = load_target_live_channels(cfg, target);
+        if channels.is_empty() {
+            continue;
+        }
+        if let Some(epg) = create_short_epg(cfg, short_epg_cfg, channels).await {
+            if let Some(target_path) = get_target_storage_path(cfg, &target.name) {
+                let path = target_path.join(FILE_SHORT_EPG);
+                match epg_write_file(target, cfg, &epg, &path) {
+                    Ok(()) => info!("Short epg for target {} updated", target.name),
+                    Err(err) => error!("{err}"),
+                }
+            }
+        }
+    }
+}
+
+/// Polls the short epg of all live channels of xtream inputs without xmltv url
+/// and writes a combined xmltv file for each target.
+pub fn start_short_epg_generator(cfg: Arc<Config>) {
+    if let Some(short_epg_cfg) = cfg.short_epg.as_ref().filter(|short_epg| short_epg.enabled).cloned() {
+        actix_rt::spawn(async move {
+            loop {
+                update_short_epg(&cfg, &short_epg_cfg).await;
+                actix_rt::time::sleep(Duration::from_secs(short_epg_cfg.interval)).await;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::processing::short_epg::create_programmes;
+
+    #[test]
+    fn test_create_programmes() {
+        let content = r#"{"epg_listings":[
+            {"id":"1","title":"TmV3cw==","description":"V2VhdGhlcg==","start_timestamp":"1700000000","stop_timestamp":"1700003600"},
+            {"id":"2","title":"","start_timestamp":"1700003600","stop_timestamp":"1700007200"}
+        ]}"#;
+        let programmes = create_programmes("news.de", content);
+        assert_eq!(programmes.len(), 1);
+        let programme = &programmes[0];
+        assert_eq!(programme.get_attribute_value("start").unwrap(), "20231114221320 +0000");
+        assert_eq!(programme.get_attribute_value("channel").unwrap(), "news.de");
+        let children = programme.children.as_ref().unwrap();
+        assert_eq!(children[0].value.as_deref(), Some("News"));
+        assert_eq!(children[1].value.as_deref(), Some("Weather"));
+    }
+}
@@ -0,0 +1,80 @@
+use std::io::Write;
+use std::path::PathBuf;
+
+use log::{error, warn};
+
+use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorCategory, M3uFilterErrorKind};
+use crate::model::config::{Config, ConfigTarget};
+use crate::repository::storage::get_target_storage_path;
+use crate::utils::file_utils;
+
+const FILE_PLAYLIST_SIZE: &str = "playlist_size.txt";
+
+fn get_size_path(cfg: &Config, target: &ConfigTarget) -> Option<PathBuf> {
+    get_target_storage_path(cfg, &target.name).map(|path| path.join(FILE_PLAYLIST_SIZE))
+}
+
+/// The channel count of the last written output of the target.
+fn load_playlist_size(cfg: &Config, target: &ConfigTarget) -> Option<usize> {
+    std::fs::read_to_string(get_size_path(cfg, target)?).ok()?.trim().parse().ok()
+}
+
+fn is_shrunk(previous: usize, current: usize, min_percent: u8) -> bool {
+    current.saturating_mul(100) < previous.saturating_mul(usize::from(min_percent))
+}
+
+/// Fails if the target has a `shrink_protection` and the new playlist has less than `min_percent`
+/// of the channels of the last written output. The outputs are not written then and the previous ones are still served.
+/// A forced refresh accepts the new playlist.
+pub fn check_shrink_protection(cfg: &Config, target: &ConfigTarget, channel_count: usize, force: bool) -> Result<(), M3uFilterError> {
+    let Some(protection) = target.shrink_protection.as_ref() else { return Ok(()) };
+    let Some(previous) = load_playlist_size(cfg, target) else { return Ok(()) };
+    if !is_shrunk(previous, channel_count, protection.min_percent) {
+        return Ok(());
+    }
+    if force {
+        warn!("Playlist of target {} shrank from {previous} to {channel_count} channels, accepted by forced refresh", target.name);
+        return Ok(());
+    }
+    Err(M3uFilterError::new(M3uFilterErrorKind::Notify,
+                            format!("Playlist of target {} shrank from {previous} to {channel_count} channels, the previous output is kept", target.name))
+        .with_category(M3uFilterErrorCategory::Processing))
+}
+
+/// Remembers the channel count of the written output for the next check.
+pub fn store_playlist_size(cfg: &Config, target: &ConfigTarget, channel_count: usize) {
+    if target.shrink_protection.is_none() {
+        return;
+    }
+    let Some(path) = get_size_path(cfg, target) else { return };
+    let result = path.parent().map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|()| file_utils::write_atomic(&path, |writer| writer.write_all(channel_count.to_string().as_bytes())));
+    if let Err(err) = result {
+        error!("Failed to write playlist size {path:?}: {err}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::config::{Config, ConfigTarget, ShrinkProtectionConfig};
+    use crate::processing::shrink_protection::{check_shrink_protection, is_shrunk, store_playlist_size};
+    use crate::utils::test_utils::create_temp_dir;
+
+    #[test]
+    fn test_shrink_protection() {
+        assert!(is_shrunk(1000, 499, 50));
+        assert!(!is_shrunk(1000, 500, 50));
+        assert!(!is_shrunk(0, 0, 50));
+
+        let temp_dir = create_temp_dir("shrink_protection");
+        let dir = temp_dir.path();
+        let cfg = Config { working_dir: dir.to_string_lossy().to_string(), ..Config::default() };
+        let target = ConfigTarget { name: "tv".to_string(), shrink_protection: Some(ShrinkProtectionConfig { min_percent: 50 }), ..ConfigTarget::default() };
+        // without a previous output every size is accepted
+        assert!(check_shrink_protection(&cfg, &target, 0, false).is_ok());
+        store_playlist_size(&cfg, &target, 1000);
+        assert!(check_shrink_protection(&cfg, &target, 600, false).is_ok());
+        assert!(check_shrink_protection(&cfg, &target, 0, false).is_err());
+        assert!(check_shrink_protection(&cfg, &target, 0, true).is_ok());
+    }
+}
@@ -0,0 +1,84 @@
+use log::{info, warn};
+
+use crate::model::config::{ConfigTarget, SizeBudgetConfig};
+use crate::model::playlist::{PlaylistGroup, PlaylistItemType};
+
+/// Index of the first matching `priority` expression, groups without a match come last.
+fn group_priority(budget: &SizeBudgetConfig, group: &str) -> usize {
+    budget.t_priority.iter().position(|re| re.is_match(group)).unwrap_or(budget.t_priority.len())
+}
+
+/// Number of channels of each group which fit into the budget.
+/// The groups are filled in priority order, groups with the same priority in playlist order.
+fn get_kept_counts(budget: &SizeBudgetConfig, playlist: &[PlaylistGroup], item_size: impl Fn(usize, usize) -> usize) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..playlist.len()).collect();
+    order.sort_by_key(|&idx| group_priority(budget, &playlist[idx].title));
+    let mut kept = vec![0; playlist.len()];
+    let mut channels = 0;
+    let mut bytes = 0;
+    'groups: for group_idx in order {
+        for item_idx in 0..playlist[group_idx].channels.len() {
+            let size = if budget.max_bytes > 0 { item_size(group_idx, item_idx) } else { 0 };
+            if (budget.max_channels > 0 && channels + 1 > budget.max_channels) || (budget.max_bytes > 0 && bytes + size > budget.max_bytes) {
+                break 'groups;
+            }
+            channels += 1;
+            bytes += size;
+            kept[group_idx] += 1;
+        }
+    }
+    kept
+}
+
+/// Truncates the playlist to the `budget` of the target, the dropped channels are logged.
+/// The size of a channel is the size of its entry in the m3u file, series info items are not counted.
+pub fn apply_size_budget(target: &ConfigTarget, playlist: Vec<PlaylistGroup>) -> Vec<PlaylistGroup> {
+    let Some(budget) = target.budget.as_ref() else { return playlist };
+    let kept = get_kept_counts(budget, &playlist, |group_idx, item_idx| {
+        let item = &playlist[group_idx].channels[item_idx];
+        if item.header.borrow().item_type == PlaylistItemType::SeriesInfo {
+            0
+        } else {
+            item.to_m3u().to_m3u(target.options.as_ref(), None).len() + 1
+        }
+    });
+    let total: usize = playlist.iter().map(|group| group.channels.len()).sum();
+    let kept_total: usize = kept.iter().sum();
+    if kept_total == total {
+        return playlist;
+    }
+    warn!("Target {} exceeds the size budget, dropped {} of {total} channels", target.name, total - kept_total);
+    playlist.into_iter().zip(kept).filter_map(|(mut group, count)| {
+        if count < group.channels.len() {
+            info!("Size budget of target {}: dropped {} channels of group {}", target.name, group.channels.len() - count, group.title);
+            group.channels.truncate(count);
+        }
+        (!group.channels.is_empty()).then_some(group)
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::model::config::SizeBudgetConfig;
+    use crate::model::playlist::{PlaylistGroup, PlaylistItem, PlaylistItemHeader, XtreamCluster};
+    use crate::processing::size_budget::get_kept_counts;
+
+    fn group(title: &str, count: usize) -> PlaylistGroup {
+        PlaylistGroup { id: 0, title: Rc::new(title.to_string()), channels: (0..count).map(|_| PlaylistItem { header: RefCell::new(PlaylistItemHeader::default()) }).collect(), xtream_cluster: XtreamCluster::Live }
+    }
+
+    #[test]
+    fn test_size_budget() {
+        let playlist = vec![group("Movies", 3), group("News", 2), group("Sport", 4)];
+        let mut budget = SizeBudgetConfig { max_channels: 5, priority: vec!["News".to_string(), "Sport".to_string()], ..SizeBudgetConfig::default() };
+        budget.prepare("test").unwrap();
+        assert_eq!(get_kept_counts(&budget, &playlist, |_, _| 0), vec![0, 2, 3]);
+
+        let mut budget = SizeBudgetConfig { max_bytes: 250, ..SizeBudgetConfig::default() };
+        budget.prepare("test").unwrap();
+        assert_eq!(get_kept_counts(&budget, &playlist, |_, _| 100), vec![2, 0, 0]);
+    }
+}
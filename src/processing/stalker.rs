@@ -0,0 +1,163 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::Duration;
+
+use log::debug;
+use serde_json::Value;
+use url::Url;
+
+use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorCategory, M3uFilterErrorKind};
+use crate::model::config::ConfigInput;
+use crate::model::playlist::{PlaylistGroup, PlaylistItem, PlaylistItemHeader, PlaylistItemType, XtreamCluster};
+use crate::utils::download::prepare_file_path;
+use crate::utils::file_utils;
+use crate::utils::request_utils::{get_client_request, mask_sensitive_info, send_with_retries};
+use crate::utils::string_utils::StringInterner;
+
+const STALKER_USER_AGENT: &str = "Mozilla/5.0 (QtEmbedded; U; Linux; C) AppleWebKit/533.3 (KHTML, like Gecko) MAG200 stbapp ver: 2 rev: 250 Safari/533.3";
+const STALKER_X_USER_AGENT: &str = "Model: MAG250; Link: WiFi";
+
+fn download_error(message: String) -> M3uFilterError {
+    M3uFilterError::new(M3uFilterErrorKind::Notify, message).with_category(M3uFilterErrorCategory::Download)
+}
+
+/// The api endpoint of the portal, `http://host/stalker_portal/c/` is served by `http://host/stalker_portal/server/load.php`.
+fn get_portal_api_url(url: &str) -> String {
+    let url = url.trim().trim_end_matches('/');
+    if url.ends_with(".php") {
+        return url.to_string();
+    }
+    format!("{}/server/load.php", url.strip_suffix("/c").unwrap_or(url))
+}
+
+/// Ids and numbers are strings or numbers depending on the portal.
+fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.trim().to_string(),
+        Value::Number(number) => number.to_string(),
+        _ => String::new(),
+    }
+}
+
+async fn portal_request(input: &ConfigInput, api_url: &str, token: Option<&str>, query: &str) -> Result<Value, M3uFilterError> {
+    let url = Url::parse(&format!("{api_url}?{query}&JsHttpRequest=1-xml"))
+        .map_err(|err| download_error(format!("Invalid stalker portal url {}: {err}", mask_sensitive_info(api_url))))?;
+    let cookie = format!("mac={}; stb_lang=en; timezone=UTC", input.mac.as_deref().unwrap_or_default());
+    let authorization = token.map(|token| format!("Bearer {token}"));
+    let mut headers = HashMap::from([
+        ("User-Agent", STALKER_USER_AGENT.as_bytes()),
+        ("X-User-Agent", STALKER_X_USER_AGENT.as_bytes()),
+        ("Cookie", cookie.as_bytes()),
+    ]);
+    if let Some(authorization) = &authorization {
+        headers.insert("Authorization", authorization.as_bytes());
+    }
+    let mut request = get_client_request(Some(input), &url, Some(&headers));
+    if let Some(timeout) = input.http.as_ref().and_then(|http| http.timeout) {
+        request = request.timeout(Duration::from_secs(timeout));
+    }
+    let response = send_with_retries(Some(input), request).await.map_err(|err| {
+        let source = mask_sensitive_info(&err.to_string());
+        M3uFilterError::from_source(M3uFilterErrorKind::Notify, M3uFilterErrorCategory::Download, source, "Stalker portal request failed".to_string())
+    })?;
+    if !response.status().is_success() {
+        return Err(download_error(format!("Stalker portal request failed with status {}", response.status())));
+    }
+    match response.json::<Value>().await {
+        Ok(mut content) => Ok(content.get_mut("js").map(Value::take).unwrap_or_default()),
+        Err(err) => {
+            let message = format!("Failed to parse stalker portal response {err}");
+            Err(M3uFilterError::from_source(M3uFilterErrorKind::Notify, M3uFilterErrorCategory::Parse, err, message))
+        }
+    }
+}
+
+/// Groups the channels by their genre in the order of the genres.
+fn parse_stalker_channels(input: &ConfigInput, genres: &Value, channels: &Value) -> Vec<PlaylistGroup> {
+    let mut interner = StringInterner::default();
+    let mut group_idx: HashMap<String, usize> = HashMap::new();
+    let mut groups: Vec<PlaylistGroup> = vec![];
+    for genre in genres.as_array().into_iter().flatten() {
+        let title = genre.get("title").and_then(Value::as_str).unwrap_or_default().trim();
+        if !title.is_empty() && input.accepts_group(title) {
+            group_idx.insert(value_to_string(&genre["id"]), groups.len());
+            groups.push(PlaylistGroup { id: 0, title: Rc::new(title.to_string()), channels: vec![], xtream_cluster: XtreamCluster::Live });
+        }
+    }
+    for channel in channels.get("data").and_then(Value::as_array).into_iter().flatten() {
+        // the cmd is prefixed with the player, e.g. `ffmpeg http://...`
+        let url = channel.get("cmd").and_then(Value::as_str).and_then(|cmd| cmd.split_whitespace().last()).unwrap_or_default();
+        let Some(idx) = group_idx.get(&value_to_string(&channel["tv_genre_id"])) else {
+            continue;
+        };
+        if !url.contains("://") {
+            continue;
+        }
+        let group = &mut groups[*idx];
+        let name = Rc::new(value_to_string(&channel["name"]));
+        let epg_channel_id = value_to_string(&channel["xmltv_id"]);
+        group.channels.push(PlaylistItem {
+            header: RefCell::new(PlaylistItemHeader {
+                id: Rc::new(value_to_string(&channel["id"])),
+                name: Rc::clone(&name),
+                title: name,
+                chno: Rc::new(value_to_string(&channel["number"])),
+                logo: interner.intern(value_to_string(&channel["logo"])),
+                group: Rc::clone(&group.title),
+                url: Rc::new(url.to_string()),
+                epg_channel_id: (!epg_channel_id.is_empty()).then(|| Rc::new(epg_channel_id)),
+                item_type: PlaylistItemType::Live,
+                xtream_cluster: XtreamCluster::Live,
+                input_id: input.id,
+                ..Default::default()
+            })
+        });
+    }
+    groups.retain(|group| !group.channels.is_empty());
+    for (grp_id, group) in (1_u32..).zip(groups.iter_mut()) {
+        group.id = grp_id;
+    }
+    groups
+}
+
+/// Downloads the live channels of a stalker portal. The portal is authenticated with the `mac` of the input.
+pub async fn get_stalker_playlist(input: &ConfigInput, working_dir: &str) -> Result<Vec<PlaylistGroup>, M3uFilterError> {
+    let api_url = get_portal_api_url(&input.url);
+    let handshake = portal_request(input, &api_url, None, "type=stb&action=handshake&token=").await?;
+    let token = handshake.get("token").and_then(Value::as_str).filter(|token| !token.is_empty())
+        .ok_or_else(|| download_error("Stalker portal handshake failed".to_string()))?;
+    portal_request(input, &api_url, Some(token), "type=stb&action=get_profile").await?;
+    let genres = portal_request(input, &api_url, Some(token), "type=itv&action=get_genres").await?;
+    let channels = portal_request(input, &api_url, Some(token), "type=itv&action=get_all_channels").await?;
+    debug!("Stalker portal {} has {} channels", mask_sensitive_info(&api_url),
+        channels.get("data").and_then(Value::as_array).map_or(0, Vec::len));
+    file_utils::persist_file(prepare_file_path(input.persist.as_ref(), working_dir, "stalker_"), &channels.to_string());
+    Ok(parse_stalker_channels(input, &genres, &channels))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::model::config::ConfigInput;
+    use crate::processing::stalker::{get_portal_api_url, parse_stalker_channels};
+
+    #[test]
+    fn test_parse_stalker_channels() {
+        assert_eq!(get_portal_api_url("http://portal.tv/stalker_portal/c/"), "http://portal.tv/stalker_portal/server/load.php");
+        assert_eq!(get_portal_api_url("http://portal.tv/portal.php"), "http://portal.tv/portal.php");
+        let genres = json!([{"id": "1", "title": "News"}, {"id": 2, "title": "Sports"}, {"id": "3", "title": "Empty"}]);
+        let channels = json!({"data": [
+            {"id": 10, "name": "Sport 1", "number": "5", "cmd": "ffmpeg http://portal.tv/play/10", "tv_genre_id": "2", "xmltv_id": "sport1"},
+            {"id": "11", "name": "News 1", "number": 1, "cmd": "http://portal.tv/play/11", "tv_genre_id": 1},
+            {"id": "12", "name": "Temporary", "cmd": "ffrt /ch/12", "tv_genre_id": "1"}
+        ]});
+        let groups = parse_stalker_channels(&ConfigInput::default(), &genres, &channels);
+        let titles: Vec<(u32, &str, usize)> = groups.iter().map(|group| (group.id, group.title.as_str(), group.channels.len())).collect();
+        assert_eq!(titles, vec![(1, "News", 1), (2, "Sports", 1)]);
+        let header = groups[1].channels[0].header.borrow();
+        assert_eq!((header.url.as_str(), header.chno.as_str()), ("http://portal.tv/play/10", "5"));
+        assert_eq!(header.epg_channel_id.as_deref().map(String::as_str), Some("sport1"));
+    }
+}
@@ -0,0 +1,292 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::{Arc, LazyLock, RwLock};
+use std::time::Duration;
+
+use log::{debug, error, info, log_enabled, Level};
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+pub use m3u_filter_core::field::StreamStatus;
+use crate::model::config::{Config, ConfigInput, HealthCheckConfig, HealthCheckMethod};
+use crate::repository::playlist_repository::load_target_live_channels;
+use crate::utils::json_utils::json_write_documents_to_file;
+use crate::utils::request_utils::{get_http_client, get_request_headers, is_direct_stream_url, mask_sensitive_info};
+
+const FILE_STREAM_HEALTH: &str = "stream_health.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamHealth {
+    pub target: String,
+    pub name: String,
+    pub url: String,
+    pub status: StreamStatus,
+    pub checked_at: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bitrate: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub video_codec: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio_codec: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolution: Option<String>,
+}
+
+/// Last known health per provider url
+static STREAM_HEALTH: LazyLock<RwLock<HashMap<String, StreamHealth>>> = LazyLock::new(|| RwLock::new(HashMap::new()));
+
+fn get_stream_health_file_path(cfg: &Config) -> PathBuf {
+    PathBuf::from(&cfg.working_dir).join(FILE_STREAM_HEALTH)
+}
+
+/// Returns the last known status of the stream, `None` if the stream was not checked.
+pub fn get_stream_status(url: &str) -> Option<StreamStatus> {
+    STREAM_HEALTH.read().unwrap().get(url).map(|health| health.status)
+}
+
+/// Returns the health of all checked streams, provider urls are masked.
+pub fn get_stream_health_list() -> Vec<StreamHealth> {
+    let mut result: Vec<StreamHealth> = STREAM_HEALTH.read().unwrap().values().map(|health| {
+        let mut item = health.clone();
+        item.url = mask_sensitive_info(&item.url);
+        item
+    }).collect();
+    result.sort_by(|a, b| a.target.cmp(&b.target).then_with(|| a.name.cmp(&b.name)));
+    result
+}
+
+/// Loads the persisted results of the last health check.
+pub fn load_stream_health(cfg: &Config) {
+    if cfg.health_check.is_none() {
+        return;
+    }
+    let path = get_stream_health_file_path(cfg);
+    if let Ok(content) = std::fs::read_to_string(&path) {
+        match serde_json::from_str::<Vec<StreamHealth>>(&content) {
+            Ok(list) => {
+                let mut health_map = STREAM_HEALTH.write().unwrap();
+                health_map.clear();
+                list.into_iter().for_each(|health| { health_map.insert(health.url.clone(), health); });
+            }
+            Err(err) => error!("Failed to read stream health {}: {err}", path.to_str().unwrap_or("?")),
+        }
+    }
+}
+
+/// Drops the results of the streams which are no longer in the playlists of the targets.
+fn retain_stream_health(urls: &HashSet<String>) {
+    STREAM_HEALTH.write().unwrap().retain(|url, _| urls.contains(url));
+}
+
+fn save_stream_health(cfg: &Config) {
+    let path = get_stream_health_file_path(cfg);
+    let list: Vec<StreamHealth> = STREAM_HEALTH.read().unwrap().values().cloned().collect();
+    if let Err(err) = json_write_documents_to_file(&path, &list) {
+        error!("Failed to write stream health {}: {err}", path.to_str().unwrap_or("?"));
+    }
+}
+
+/// The probe uses the client and the headers of the input, the provider may only be reachable through its proxy.
+async fn probe_http(cfg: &Config, input: Option<&ConfigInput>, url: &str, health_cfg: &HealthCheckConfig) -> bool {
+    let client = get_http_client(cfg, input);
+    let headers = get_request_headers(input.map(|i| &i.headers), None);
+    let timeout = Duration::from_secs(health_cfg.timeout);
+    if health_cfg.method == HealthCheckMethod::Head {
+        if let Ok(response) = client.head(url).headers(headers.clone()).timeout(timeout).send().await {
+            if response.status().is_success() {
+                return true;
+            }
+        }
+        // some providers do not support head requests, try a short get
+    }
+    match client.get(url).headers(headers).timeout(timeout).send().await {
+        Ok(mut response) => {
+            if response.status().is_success() {
+                // the first chunk is enough, the stream itself never ends
+                return response.chunk().await.is_ok_and(|chunk| chunk.is_some_and(|bytes| !bytes.is_empty()));
+            }
+            false
+        }
+        Err(_) => false
+    }
+}
+
+fn get_json_str(value: &Value, key: &str) -> Option<String> {
+    value.get(key).and_then(Value::as_str).map(ToString::to_string)
+}
+
+fn probe_ffprobe(ffprobe: &str, url: &str, timeout: u64, health: &mut StreamHealth) -> bool {
+    let output = Command::new(ffprobe)
+        .args(["-v", "quiet", "-print_format", "json", "-show_format", "-show_streams"])
+        .args(["-rw_timeout", (timeout * 1_000_000).to_string().as_str()])
+        .arg(url)
+        .output();
+    match output {
+        Ok(result) if result.status.success() => {
+            if let Ok(probe) = serde_json::from_slice::<Value>(&result.stdout) {
+                health.bitrate = probe.get("format").and_then(|format| get_json_str(format, "bit_rate")).and_then(|rate| rate.parse::<u64>().ok());
+                if let Some(streams) = probe.get("streams").and_then(Value::as_array) {
+                    for stream in streams {
+                        match stream.get("codec_type").and_then(Value::as_str) {
+                            Some("video") if health.video_codec.is_none() => {
+                                health.video_codec = get_json_str(stream, "codec_name");
+                                if let (Some(width), Some(height)) = (stream.get("width").and_then(Value::as_u64), stream.get("height").and_then(Value::as_u64)) {
+                                    health.resolution = Some(format!("{width}x{height}"));
+                                }
+                            }
+                            Some("audio") if health.audio_codec.is_none() => {
+                                health.audio_codec = get_json_str(stream, "codec_name");
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                return true;
+            }
+            false
+        }
+        Ok(_) => false,
+        Err(err) => {
+            error!("Failed to execute ffprobe {ffprobe}: {err}");
+            false
+        }
+    }
+}
+
+async fn check_stream(cfg: &Config, input: Option<&ConfigInput>, health_cfg: &HealthCheckConfig, target: &str, name: String, url: String) -> StreamHealth {
+    let mut health = StreamHealth {
+        target: target.to_string(),
+        name,
+        url,
+        status: StreamStatus::Dead,
+        checked_at: chrono::Local::now().timestamp(),
+        bitrate: None,
+        video_codec: None,
+        audio_codec: None,
+        resolution: None,
+    };
+    let alive = match health_cfg.method {
+        HealthCheckMethod::Head | HealthCheckMethod::Get => probe_http(cfg, input, &health.url, health_cfg).await,
+        HealthCheckMethod::Ffprobe => {
+            let ffprobe = health_cfg.ffprobe.as_deref().unwrap_or("ffprobe");
+            let url = health.url.clone();
+            probe_ffprobe(ffprobe, &url, health_cfg.timeout, &mut health)
+        }
+    };
+    if alive {
+        health.status = StreamStatus::Alive;
+    }
+    if log_enabled!(Level::Debug) {
+        debug!("Stream health {} {}: {}", health.name, mask_sensitive_info(&health.url), health.status);
+    }
+    health
+}
+
+async fn check_streams(cfg: &Config, health_cfg: &HealthCheckConfig) {
+    let mut checked = 0;
+    let mut dead = 0;
+    // the results of the channels which are not sampled in this run are kept
+    let mut current_urls = HashSet::new();
+    for target in cfg.sources.iter().flat_map(|source| &source.targets).filter(|target| target.enabled) {
+        let mut channels = load_target_live_channels(cfg, target);
+        current_urls.extend(channels.iter().map(|channel| channel.url.clone()));
+        if let Some(sample_size) = health_cfg.sample_size {
+            channels.shuffle(&mut rand::thread_rng());
+            channels.truncate(sample_size);
+        }
+        for channel in channels {
+            // udp, rtp and rtsp streams can only be probed by ffprobe
+            if health_cfg.method != HealthCheckMethod::Ffprobe && is_direct_stream_url(&channel.url) {
+                continue;
+            }
+            let input = cfg.get_input_by_id(channel.input_id);
+            let health = check_stream(cfg, input, health_cfg, &target.name, channel.name, channel.url).await;
+            checked += 1;
+            if health.status == StreamStatus::Dead {
+                dead += 1;
+            }
+            STREAM_HEALTH.write().unwrap().insert(health.url.clone(), health);
+        }
+    }
+    retain_stream_health(&current_urls);
+    save_stream_health(cfg);
+    info!("Stream health check finished, checked {checked} streams, {dead} dead");
+}
+
+/// Starts the background health check, the checks run in a separate thread
+/// because ffprobe is blocking.
+pub fn start_health_checker(cfg: Arc<Config>) {
+    if let Some(health_cfg) = cfg.health_check.as_ref().filter(|hc| hc.enabled).cloned() {
+        load_stream_health(&cfg);
+        std::thread::spawn(move || {
+            let system = actix_rt::System::new();
+            loop {
+                system.block_on(check_streams(&cfg, &health_cfg));
+                std::thread::sleep(Duration::from_secs(health_cfg.interval));
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    use crate::model::config::{Config, ConfigInput, HealthCheckConfig, HealthCheckMethod, ProxyConfig};
+    use crate::processing::stream_health::{get_stream_status, probe_http, retain_stream_health, StreamHealth, StreamStatus, STREAM_HEALTH};
+    use crate::utils::request_utils::create_http_client;
+
+    #[test]
+    fn test_retain_stream_health() {
+        for url in ["http://health.test/1", "http://health.test/2"] {
+            STREAM_HEALTH.write().unwrap().insert(url.to_string(), StreamHealth {
+                target: "tv".to_string(),
+                name: url.to_string(),
+                url: url.to_string(),
+                status: StreamStatus::Dead,
+                checked_at: 0,
+                bitrate: None,
+                video_codec: None,
+                audio_codec: None,
+                resolution: None,
+            });
+        }
+        let mut current_urls: HashSet<String> = STREAM_HEALTH.read().unwrap().keys().filter(|url| !url.starts_with("http://health.test/")).cloned().collect();
+        current_urls.insert("http://health.test/1".to_string());
+        retain_stream_health(&current_urls);
+        assert_eq!(get_stream_status("http://health.test/1"), Some(StreamStatus::Dead));
+        // removed channels are dropped
+        assert_eq!(get_stream_status("http://health.test/2"), None);
+    }
+
+    #[actix_rt::test]
+    async fn test_probe_through_input_proxy() {
+        // the proxy answers only requests with the header of the input
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy_url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().take(2).flatten() {
+                let mut buffer = [0u8; 4096];
+                let size = stream.read(&mut buffer).unwrap_or(0);
+                let request = String::from_utf8_lossy(&buffer[..size]).to_lowercase();
+                let response = if request.contains("x-provider: m3u") { "HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\ndata" } else { "HTTP/1.1 403 Forbidden\r\nContent-Length: 0\r\n\r\n" };
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+        let proxy = ProxyConfig { url: proxy_url, ..ProxyConfig::default() };
+        let mut input = ConfigInput { t_http_client: Some(create_http_client(None, Some(&proxy), None).unwrap()), ..ConfigInput::default() };
+        let cfg = Config::default();
+        let health_cfg = HealthCheckConfig { enabled: true, method: HealthCheckMethod::Get, interval: 60, timeout: 5, sample_size: None, ffprobe: None };
+        // the host is only reachable through the proxy
+        let url = "http://provider.invalid/live/1.ts";
+        assert!(!probe_http(&cfg, Some(&input), url, &health_cfg).await);
+        input.headers.insert("X-Provider".to_string(), "m3u".to_string());
+        assert!(probe_http(&cfg, Some(&input), url, &health_cfg).await);
+        assert!(!probe_http(&cfg, None, url, &health_cfg).await);
+    }
+}
@@ -0,0 +1,95 @@
+use std::rc::Rc;
+
+use crate::model::config::{TagPattern, TaggingConfig};
+use crate::model::playlist::{PlaylistGroup, PlaylistItemHeader};
+use crate::processing::post_process::regroup_items;
+
+/// The tag of the first matching pattern in upper case.
+fn infer_tag(patterns: &[TagPattern], header: &PlaylistItemHeader) -> Option<String> {
+    patterns.iter().find_map(|pattern| {
+        let value = if pattern.field == "group" { &header.group } else { &header.name };
+        let captures = pattern.t_pattern.as_ref()?.captures(value)?;
+        pattern.value.clone()
+            .or_else(|| captures.name("tag").map(|tag| tag.as_str().to_string()))
+            .map(|tag| tag.trim().to_uppercase())
+            .filter(|tag| !tag.is_empty())
+    })
+}
+
+/// Sets the `country` and `language` of the channels, existing tags are kept.
+pub fn tag_playlist(tagging: &TaggingConfig, playlist: &[PlaylistGroup]) {
+    for channel in playlist.iter().flat_map(|group| &group.channels) {
+        let mut header = channel.header.borrow_mut();
+        if header.country.is_empty() {
+            if let Some(country) = infer_tag(tagging.country.as_deref().unwrap_or_default(), &header) {
+                header.country = Rc::new(country);
+            }
+        }
+        if header.language.is_empty() {
+            if let Some(language) = infer_tag(&tagging.language, &header) {
+                header.language = Rc::new(language);
+            }
+        }
+    }
+}
+
+/// The prefix with the tags of the channel, `None` if a tag of the prefix is missing.
+fn format_group_prefix(prefix: &str, header: &PlaylistItemHeader) -> Option<String> {
+    if (prefix.contains("{country}") && header.country.is_empty()) || (prefix.contains("{language}") && header.language.is_empty()) {
+        return None;
+    }
+    Some(prefix.replace("{country}", &header.country).replace("{language}", &header.language))
+}
+
+/// Prefixes the groups of the tagged channels with the `group_prefix`, channels with different tags are split into separate groups.
+pub fn prefix_tagged_groups(tagging: &TaggingConfig, playlist: Vec<PlaylistGroup>) -> Vec<PlaylistGroup> {
+    let Some(prefix) = &tagging.group_prefix else {
+        return playlist;
+    };
+    let items: Vec<PlaylistItemHeader> = playlist.iter().flat_map(|group| &group.channels).map(|channel| {
+        let mut header = channel.header.borrow().clone();
+        if let Some(group_prefix) = format_group_prefix(prefix, &header) {
+            header.group = Rc::new(format!("{group_prefix}{}", header.group));
+        }
+        header
+    }).collect();
+    regroup_items(items.into_iter(), &playlist)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::model::config::TaggingConfig;
+    use crate::model::playlist::{PlaylistGroup, PlaylistItem, PlaylistItemHeader, XtreamCluster};
+    use crate::processing::tagging::{prefix_tagged_groups, tag_playlist};
+
+    fn item(name: &str, group: &str) -> PlaylistItem {
+        PlaylistItem { header: RefCell::new(PlaylistItemHeader { name: Rc::new(name.to_string()), group: Rc::new(group.to_string()), ..PlaylistItemHeader::default() }) }
+    }
+
+    #[test]
+    fn test_tag_playlist() {
+        let mut tagging: TaggingConfig = serde_yaml::from_str(r#"
+language:
+  - pattern: '\[(?P<tag>[a-z]{2})\]$'
+  - field: group
+    pattern: 'English'
+    value: en
+group_prefix: '{country} | '
+"#).unwrap();
+        tagging.prepare("test").unwrap();
+        let playlist = vec![PlaylistGroup { id: 1, title: Rc::new("News".to_string()), xtream_cluster: XtreamCluster::Live,
+            channels: vec![item("DE: ZDF [de]", "News"), item("CNN", "News"), item("BBC", "UK - English")] }];
+        tag_playlist(&tagging, &playlist);
+        let tags: Vec<(String, String)> = playlist[0].channels.iter().map(|channel| {
+            let header = channel.header.borrow();
+            (header.country.to_string(), header.language.to_string())
+        }).collect();
+        assert_eq!(tags, vec![("DE".to_string(), "DE".to_string()), (String::new(), String::new()), ("UK".to_string(), "EN".to_string())]);
+
+        let groups: Vec<String> = prefix_tagged_groups(&tagging, playlist).iter().map(|group| group.title.to_string()).collect();
+        assert_eq!(groups, vec!["DE | News", "News", "UK | UK - English"]);
+    }
+}
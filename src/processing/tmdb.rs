@@ -0,0 +1,250 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::Duration;
+
+use futures::StreamExt;
+use log::{debug, error, info};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::model::config::{Config, TmdbConfig};
+use crate::model::playlist::{PlaylistGroup, PlaylistItemHeader, PlaylistItemType};
+use crate::repository::library_repository::split_title_year;
+use crate::utils::file_utils;
+use crate::utils::request_utils::mask_sensitive_info;
+
+const TMDB_API_URL: &str = "https://api.themoviedb.org/3";
+const TMDB_IMAGE_URL: &str = "https://image.tmdb.org/t/p/w500";
+const TMDB_CACHE_FILE: &str = "tmdb_cache.json";
+const TMDB_CONCURRENCY: usize = 4;
+const TMDB_TIMEOUT_SECS: u64 = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum TmdbKind {
+    Movie,
+    Tv,
+}
+
+impl TmdbKind {
+    const fn path(self) -> &'static str {
+        match self {
+            Self::Movie => "movie",
+            Self::Tv => "tv",
+        }
+    }
+
+    const fn year_param(self) -> &'static str {
+        match self {
+            Self::Movie => "year",
+            Self::Tv => "first_air_date_year",
+        }
+    }
+
+    const fn date_field(self) -> &'static str {
+        match self {
+            Self::Movie => "release_date",
+            Self::Tv => "first_air_date",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TmdbInfo {
+    tmdb_id: u64,
+    poster: Option<String>,
+    plot: Option<String>,
+    year: Option<String>,
+    rating: Option<f64>,
+}
+
+/// Lookups by kind, title and year. Titles without a match are cached as `None` to avoid repeated lookups.
+type TmdbCache = HashMap<String, Option<TmdbInfo>>;
+
+fn get_cache_path(cfg: &Config) -> PathBuf {
+    PathBuf::from(&cfg.working_dir).join(TMDB_CACHE_FILE)
+}
+
+fn load_cache(path: &Path) -> TmdbCache {
+    std::fs::read(path).ok()
+        .and_then(|content| serde_json::from_slice(&content).map_err(|err| error!("Failed to read tmdb cache: {err}")).ok())
+        .unwrap_or_default()
+}
+
+fn save_cache(path: &Path, cache: &TmdbCache) {
+    if let Err(err) = file_utils::write_atomic(path, |writer| serde_json::to_writer(writer, cache).map_err(std::io::Error::from)) {
+        error!("Failed to write tmdb cache: {err}");
+    }
+}
+
+fn cache_key(kind: TmdbKind, title: &str, year: Option<&str>) -> String {
+    format!("{}:{}:{}", kind.path(), title.trim().to_lowercase(), year.unwrap_or_default())
+}
+
+fn non_empty_str(value: Option<&Value>) -> Option<String> {
+    value.and_then(Value::as_str).map(str::trim).filter(|text| !text.is_empty()).map(ToString::to_string)
+}
+
+/// Takes the best match of a search response.
+fn parse_search_result(kind: TmdbKind, content: &Value) -> Option<TmdbInfo> {
+    let result = content.get("results")?.as_array()?.first()?;
+    Some(TmdbInfo {
+        tmdb_id: result.get("id")?.as_u64()?,
+        poster: non_empty_str(result.get("poster_path")).map(|path| format!("{TMDB_IMAGE_URL}{path}")),
+        plot: non_empty_str(result.get("overview")),
+        year: non_empty_str(result.get(kind.date_field())).and_then(|date| date.get(0..4).map(ToString::to_string)),
+        rating: result.get("vote_average").and_then(Value::as_f64),
+    })
+}
+
+/// `Err` for failed requests, they are not cached and retried with the next run.
+async fn search(client: &reqwest::Client, tmdb: &TmdbConfig, kind: TmdbKind, title: &str, year: Option<&str>) -> Result<Option<TmdbInfo>, String> {
+    let mut params = vec![("api_key", tmdb.api_key.as_str()), ("query", title), ("language", tmdb.language.as_str())];
+    if let Some(year) = year {
+        params.push((kind.year_param(), year));
+    }
+    let url = format!("{TMDB_API_URL}/search/{}", kind.path());
+    match client.get(&url).query(&params).send().await {
+        Ok(response) if response.status().is_success() => {
+            let content = response.json::<Value>().await.map_err(|err| err.to_string())?;
+            Ok(parse_search_result(kind, &content))
+        }
+        Ok(response) => Err(format!("status {}", response.status())),
+        Err(err) => Err(mask_sensitive_info(&err.without_url().to_string())),
+    }
+}
+
+/// Fills the missing properties of the item, provider values are kept.
+fn apply_info(header: &mut PlaylistItemHeader, info: &TmdbInfo) {
+    let mut props = match header.additional_properties.take() {
+        Some(Value::Object(props)) => props,
+        _ => Map::new(),
+    };
+    let mut insert = |name: &str, value: Option<Value>| {
+        if let Some(value) = value {
+            let missing = props.get(name).is_none_or(|current| current.is_null() || current.as_str().is_some_and(str::is_empty));
+            if missing {
+                props.insert(name.to_string(), value);
+            }
+        }
+    };
+    insert("tmdb_id", Some(Value::from(info.tmdb_id)));
+    insert("cover", info.poster.clone().map(Value::String));
+    insert("plot", info.plot.clone().map(Value::String));
+    insert("year", info.year.clone().map(Value::String));
+    insert("rating", info.rating.map(|rating| Value::String(format!("{rating:.1}"))));
+    if header.logo.is_empty() {
+        if let Some(poster) = &info.poster {
+            header.logo = Rc::new(poster.clone());
+        }
+    }
+    header.additional_properties = Some(Value::Object(props));
+}
+
+fn get_kind(item_type: PlaylistItemType) -> Option<TmdbKind> {
+    match item_type {
+        PlaylistItemType::Video => Some(TmdbKind::Movie),
+        PlaylistItemType::SeriesInfo => Some(TmdbKind::Tv),
+        _ => None,
+    }
+}
+
+/// Looks up the movies and series of the playlist at TMDB and fills in
+/// poster, plot, year, rating and `tmdb_id`. Lookups are cached in the working directory.
+pub async fn tmdb_enrich_playlist(cfg: &Config, playlist: &[PlaylistGroup]) {
+    let Some(tmdb) = cfg.tmdb.as_ref().filter(|tmdb| tmdb.enabled) else {
+        return;
+    };
+    let cache_path = get_cache_path(cfg);
+    let mut cache = load_cache(&cache_path);
+
+    let mut lookups = HashMap::new();
+    for channel in playlist.iter().flat_map(|group| &group.channels) {
+        let header = channel.header.borrow();
+        if let Some(kind) = get_kind(header.item_type) {
+            let (title, year) = split_title_year(&header);
+            let key = cache_key(kind, &title, year.as_deref());
+            if !cache.contains_key(&key) {
+                lookups.insert(key, (kind, title, year));
+            }
+        }
+    }
+
+    if !lookups.is_empty() {
+        let client = match reqwest::Client::builder().timeout(Duration::from_secs(TMDB_TIMEOUT_SECS)).build() {
+            Ok(client) => client,
+            Err(err) => {
+                error!("Failed to create client for tmdb: {err}");
+                return;
+            }
+        };
+        let total = lookups.len();
+        let results: Vec<(String, Result<Option<TmdbInfo>, String>)> = futures::stream::iter(lookups)
+            .map(|(key, (kind, title, year))| {
+                let client = &client;
+                async move {
+                    let result = search(client, tmdb, kind, &title, year.as_deref()).await;
+                    (key, result)
+                }
+            })
+            .buffer_unordered(TMDB_CONCURRENCY)
+            .collect().await;
+        let mut found = 0;
+        for (key, result) in results {
+            match result {
+                Ok(info) => {
+                    found += usize::from(info.is_some());
+                    cache.insert(key, info);
+                }
+                Err(err) => debug!("Tmdb lookup {key} failed: {err}"),
+            }
+        }
+        info!("Tmdb lookups finished, found {found} of {total} titles");
+        save_cache(&cache_path, &cache);
+    }
+
+    for channel in playlist.iter().flat_map(|group| &group.channels) {
+        let mut header = channel.header.borrow_mut();
+        if let Some(kind) = get_kind(header.item_type) {
+            let (title, year) = split_title_year(&header);
+            if let Some(Some(info)) = cache.get(&cache_key(kind, &title, year.as_deref())) {
+                apply_info(&mut header, info);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use serde_json::json;
+
+    use crate::model::playlist::PlaylistItemHeader;
+    use crate::processing::tmdb::{apply_info, parse_search_result, TmdbKind};
+
+    #[test]
+    fn test_parse_and_apply() {
+        let response = json!({"page": 1, "results": [
+            {"id": 603, "title": "The Matrix", "overview": "A hacker", "poster_path": "/matrix.jpg", "release_date": "1999-03-30", "vote_average": 8.2},
+            {"id": 604, "title": "The Matrix Reloaded"}
+        ]});
+        let info = parse_search_result(TmdbKind::Movie, &response).unwrap();
+        assert_eq!(info.tmdb_id, 603);
+        assert_eq!(info.year.as_deref(), Some("1999"));
+        assert!(parse_search_result(TmdbKind::Movie, &json!({"results": []})).is_none());
+
+        let mut header = PlaylistItemHeader {
+            title: Rc::new("The Matrix".to_string()),
+            additional_properties: Some(json!({"plot": "provider plot", "year": ""})),
+            ..PlaylistItemHeader::default()
+        };
+        apply_info(&mut header, &info);
+        let props = header.additional_properties.unwrap();
+        assert_eq!(props["plot"], "provider plot");
+        assert_eq!(props["year"], "1999");
+        assert_eq!(props["tmdb_id"], 603);
+        assert_eq!(props["rating"], "8.2");
+        assert_eq!(header.logo.as_str(), "https://image.tmdb.org/t/p/w500/matrix.jpg");
+    }
+}
@@ -0,0 +1,303 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, RwLock};
+use std::time::Duration;
+
+use log::{debug, error};
+use serde_json::Value;
+use url::Url;
+
+use crate::model::config::{Config, ConfigInput, ConfigInputTokenRefresh, TargetType};
+use crate::repository::m3u_repository::{get_m3u_text_path, m3u_get_group_dir};
+use crate::utils::{file_utils, request_utils};
+use crate::utils::json_utils::json_write_documents_to_file;
+use crate::utils::request_utils::mask_sensitive_info;
+
+const FILE_INPUT_TOKENS: &str = "input_tokens.json";
+
+/// Current token per input id
+static INPUT_TOKENS: LazyLock<RwLock<HashMap<u16, String>>> = LazyLock::new(|| RwLock::new(HashMap::new()));
+
+fn get_input_tokens_file_path(cfg: &Config) -> PathBuf {
+    PathBuf::from(&cfg.working_dir).join(FILE_INPUT_TOKENS)
+}
+
+/// The key of the persisted token, the ids of the inputs change with the config.
+fn get_input_token_key(input: &ConfigInput) -> String {
+    input.name.clone().unwrap_or_else(|| input.id.to_string())
+}
+
+fn get_token_refresh_inputs(cfg: &Config) -> impl Iterator<Item=&ConfigInput> {
+    cfg.sources.iter().flat_map(|source| &source.inputs).filter(|input| input.token_refresh.is_some())
+}
+
+/// Loads the persisted tokens, the streams work with the last renewed token until the first renewal.
+fn load_input_tokens(cfg: &Config) {
+    let path = get_input_tokens_file_path(cfg);
+    let Ok(content) = std::fs::read_to_string(&path) else { return };
+    match serde_json::from_str::<HashMap<String, String>>(&content) {
+        Ok(persisted) => {
+            let mut tokens = INPUT_TOKENS.write().unwrap();
+            for input in get_token_refresh_inputs(cfg) {
+                if let Some(token) = persisted.get(&get_input_token_key(input)) {
+                    tokens.insert(input.id, token.clone());
+                }
+            }
+        }
+        Err(err) => error!("Failed to read input tokens {}: {err}", path.to_str().unwrap_or("?")),
+    }
+}
+
+fn save_input_tokens(cfg: &Config) {
+    let path = get_input_tokens_file_path(cfg);
+    let persisted: HashMap<String, String> = {
+        let tokens = INPUT_TOKENS.read().unwrap();
+        get_token_refresh_inputs(cfg)
+            .filter_map(|input| tokens.get(&input.id).map(|token| (get_input_token_key(input), token.clone())))
+            .collect()
+    };
+    if let Err(err) = json_write_documents_to_file(&path, &persisted) {
+        error!("Failed to write input tokens {}: {err}", path.to_str().unwrap_or("?"));
+    }
+}
+
+/// The value of the query parameter `param` of the url.
+fn get_url_token(url: &str, param: &str) -> Option<String> {
+    Url::parse(url).ok()?.query_pairs().find(|(key, _)| key == param).map(|(_, value)| value.into_owned()).filter(|token| !token.is_empty())
+}
+
+/// The token of the first stream url of the m3u playlist which has the token parameter.
+fn get_playlist_token(content: &str, param: &str) -> Option<String> {
+    content.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .find_map(|url| get_url_token(url, param))
+}
+
+fn get_endpoint_token(content: &str, json_field: Option<&str>) -> Option<String> {
+    let token = match json_field {
+        Some(field) => match serde_json::from_str::<Value>(content).ok()?.get(field)? {
+            Value::String(token) => token.clone(),
+            Value::Number(token) => token.to_string(),
+            _ => return None,
+        },
+        None => content.trim().to_string(),
+    };
+    (!token.is_empty()).then_some(token)
+}
+
+async fn fetch_token(input: &ConfigInput, token_refresh: &ConfigInputTokenRefresh) -> Result<String, String> {
+    let url = token_refresh.url.as_deref().unwrap_or(&input.url);
+    let content = request_utils::download_text_content(input, url, None).await
+        .map_err(|err| mask_sensitive_info(&err.to_string()))?;
+    let token = if token_refresh.url.is_some() {
+        get_endpoint_token(&content, token_refresh.json_field.as_deref())
+    } else {
+        get_playlist_token(&content, &token_refresh.param)
+    };
+    token.ok_or_else(|| format!("No token in response of {}", mask_sensitive_info(url)))
+}
+
+/// Replaces the token of the stream url with the last renewed token of the input.
+/// Urls without the token parameter and inputs without `token_refresh` are returned unchanged.
+pub fn rewrite_token_url<'a>(input: Option<&ConfigInput>, url: &'a str) -> Cow<'a, str> {
+    let Some((input, token_refresh)) = input.and_then(|input| input.token_refresh.as_ref().map(|token_refresh| (input, token_refresh))) else {
+        return Cow::Borrowed(url);
+    };
+    let Some(token) = INPUT_TOKENS.read().ok().and_then(|tokens| tokens.get(&input.id).cloned()) else {
+        return Cow::Borrowed(url);
+    };
+    replace_url_token(url, &token_refresh.param, &token).map_or(Cow::Borrowed(url), Cow::Owned)
+}
+
+fn replace_url_token(url: &str, param: &str, token: &str) -> Option<String> {
+    let mut parsed = Url::parse(url).ok()?;
+    let pairs: Vec<(String, String)> = parsed.query_pairs().into_owned().collect();
+    if !pairs.iter().any(|(key, _)| key == param) {
+        return None;
+    }
+    parsed.query_pairs_mut().clear().extend_pairs(pairs.iter().map(|(key, value)| (key, if key == param { token } else { value })));
+    Some(parsed.to_string())
+}
+
+/// Replaces the tokens of the stream urls with the last renewed tokens of their inputs,
+/// for the outputs which hand the provider urls to the clients.
+pub struct TokenRewrite {
+    /// token parameter and current token by input id
+    tokens: HashMap<u16, (String, String)>,
+}
+
+impl TokenRewrite {
+    /// `None` if no input has a renewed token.
+    pub fn new(cfg: &Config) -> Option<Self> {
+        let current = INPUT_TOKENS.read().ok()?;
+        let tokens: HashMap<u16, (String, String)> = get_token_refresh_inputs(cfg)
+            .filter_map(|input| Some((input.id, (input.token_refresh.as_ref()?.param.clone(), current.get(&input.id)?.clone()))))
+            .collect();
+        (!tokens.is_empty()).then_some(Self { tokens })
+    }
+
+    /// The url with the current token of the input, `None` if the url is unchanged.
+    pub fn rewrite(&self, input_id: u16, url: &str) -> Option<String> {
+        let (param, token) = self.tokens.get(&input_id)?;
+        replace_url_token(url, param, token).filter(|rewritten| rewritten != url)
+    }
+}
+
+/// Replaces `old_token` with `token` in the stream urls of the file. Returns true if the file was changed.
+fn rewrite_file_tokens(path: &Path, param: &str, old_token: &str, token: &str) -> std::io::Result<bool> {
+    let content = std::fs::read_to_string(path)?;
+    let mut changed = false;
+    let lines: Vec<Cow<str>> = content.split('\n').map(|line| {
+        if !line.starts_with('#') && get_url_token(line.trim(), param).is_some_and(|current| current == old_token) {
+            if let Some(rewritten) = replace_url_token(line.trim(), param, token) {
+                changed = true;
+                return Cow::Owned(rewritten);
+            }
+        }
+        Cow::Borrowed(line)
+    }).collect();
+    if changed {
+        file_utils::write_atomic(path, |writer| writer.write_all(lines.join("\n").as_bytes()))?;
+    }
+    Ok(changed)
+}
+
+/// The files of the directory and its subdirectories with the extension.
+fn collect_files(dir: &Path, extension: &str, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else { return };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_files(&path, extension, files);
+        } else if path.extension().is_some_and(|ext| ext == extension) {
+            files.push(path);
+        }
+    }
+}
+
+/// The strm, library and plain m3u files of the targets which are fed by the input.
+fn get_input_output_files(cfg: &Config, input: &ConfigInput) -> Vec<PathBuf> {
+    let mut files = vec![];
+    let targets = cfg.sources.iter()
+        .filter(|source| source.inputs.iter().any(|source_input| source_input.id == input.id))
+        .flat_map(|source| &source.targets);
+    for target in targets {
+        for output in &target.output {
+            match output.target {
+                TargetType::Strm | TargetType::Library => {
+                    if let Some(dir) = output.get_filename(&target.name).and_then(|name| file_utils::get_file_path(&cfg.working_dir, Some(PathBuf::from(name)))) {
+                        collect_files(&dir, "strm", &mut files);
+                    }
+                }
+                TargetType::M3u => {
+                    if let Some(path) = get_m3u_text_path(target, cfg) {
+                        collect_files(&m3u_get_group_dir(&path), "m3u", &mut files);
+                        files.push(path);
+                    }
+                }
+                TargetType::Xtream | TargetType::Report => {}
+            }
+        }
+    }
+    files
+}
+
+/// The written output files keep the token of the last processing run, the urls with the replaced token are updated.
+fn rewrite_output_files(cfg: &Config, input: &ConfigInput, param: &str, old_token: &str, token: &str) {
+    let mut count = 0;
+    for path in get_input_output_files(cfg, input) {
+        match rewrite_file_tokens(&path, param, old_token, token) {
+            Ok(changed) => count += usize::from(changed),
+            Err(err) => error!("Failed to renew token in {}: {err}", path.to_str().unwrap_or("?")),
+        }
+    }
+    debug!("Renewed token in {count} files of input {}", input.name.as_deref().unwrap_or_default());
+}
+
+/// Renews the tokens of the inputs with `token_refresh` in their `interval`.
+/// The stored playlists keep the provider urls, the token is replaced in the outputs.
+/// The renewed tokens are persisted and used after a restart until the next renewal.
+pub fn start_token_refresh(cfg: &Arc<Config>) {
+    load_input_tokens(cfg);
+    let inputs = get_token_refresh_inputs(cfg).filter(|input| input.enabled);
+    for input in inputs {
+        let input = input.clone();
+        let cfg = Arc::clone(cfg);
+        actix_rt::spawn(async move {
+            let Some(token_refresh) = input.token_refresh.as_ref() else { return };
+            loop {
+                match fetch_token(&input, token_refresh).await {
+                    Ok(token) => {
+                        debug!("Renewed token of input {}", input.name.as_deref().unwrap_or_default());
+                        let old_token = INPUT_TOKENS.write().ok().and_then(|mut tokens| tokens.insert(input.id, token.clone()));
+                        if old_token.as_ref() != Some(&token) {
+                            save_input_tokens(&cfg);
+                            if let Some(old_token) = old_token {
+                                let (cfg, input, param) = (Arc::clone(&cfg), input.clone(), token_refresh.param.clone());
+                                let _ = actix_rt::task::spawn_blocking(move || rewrite_output_files(&cfg, &input, &param, &old_token, &token)).await;
+                            }
+                        }
+                    }
+                    Err(err) => error!("Failed to renew token of input {}: {err}", input.name.as_deref().unwrap_or_default()),
+                }
+                actix_rt::time::sleep(Duration::from_secs(token_refresh.interval)).await;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::config::{Config, ConfigInput, ConfigInputTokenRefresh, ConfigSource, ConfigTarget, TargetOutput, TargetType};
+    use crate::processing::token_refresh::{get_endpoint_token, get_playlist_token, load_input_tokens, replace_url_token, rewrite_output_files, save_input_tokens, TokenRewrite, INPUT_TOKENS};
+    use crate::utils::test_utils::create_temp_dir;
+
+    #[test]
+    fn test_token_refresh() {
+        let playlist = "#EXTM3U\n#EXTINF:-1,News\nhttp://cdn.tv/live/1.m3u8?id=1&token=abc\n";
+        assert_eq!(get_playlist_token(playlist, "token").as_deref(), Some("abc"));
+        assert_eq!(get_playlist_token(playlist, "auth"), None);
+        assert_eq!(get_endpoint_token(" xyz\n", None).as_deref(), Some("xyz"));
+        assert_eq!(get_endpoint_token(r#"{"token": "xyz", "expires": 3600}"#, Some("token")).as_deref(), Some("xyz"));
+        assert_eq!(get_endpoint_token(r#"{"expires": 3600}"#, Some("token")), None);
+        assert_eq!(replace_url_token("http://cdn.tv/live/1.m3u8?id=1&token=abc", "token", "x+y").as_deref(),
+                   Some("http://cdn.tv/live/1.m3u8?id=1&token=x%2By"));
+        assert_eq!(replace_url_token("http://cdn.tv/live/1.m3u8?id=1", "token", "xyz"), None);
+    }
+
+    #[test]
+    fn test_token_rewrite() {
+        let temp_dir = create_temp_dir("token_refresh");
+        let working_dir = temp_dir.path();
+        std::fs::create_dir_all(working_dir.join("strm/News")).unwrap();
+        let token_refresh = ConfigInputTokenRefresh { param: "token".to_string(), url: None, json_field: None, interval: 3600 };
+        let input = ConfigInput { id: 4711, name: Some("cdn".to_string()), token_refresh: Some(token_refresh), ..ConfigInput::default() };
+        let output = TargetOutput { target: TargetType::Strm, filename: Some("strm".to_string()), template: None, sanitize: Default::default(), split_groups: false };
+        let target = ConfigTarget { name: "tv".to_string(), output: vec![output], ..ConfigTarget::default() };
+        let cfg = Config {
+            working_dir: working_dir.to_str().unwrap().to_string(),
+            sources: vec![ConfigSource { inputs: vec![input.clone()], targets: vec![target] }],
+            ..Config::default()
+        };
+
+        INPUT_TOKENS.write().unwrap().insert(input.id, "new".to_string());
+        let token_rewrite = TokenRewrite::new(&cfg).unwrap();
+        assert_eq!(token_rewrite.rewrite(input.id, "http://cdn.tv/live/1.m3u8?token=old").as_deref(), Some("http://cdn.tv/live/1.m3u8?token=new"));
+        assert_eq!(token_rewrite.rewrite(input.id, "http://cdn.tv/live/1.m3u8?token=new"), None);
+        assert_eq!(token_rewrite.rewrite(1, "http://cdn.tv/live/1.m3u8?token=old"), None);
+
+        let strm_file = working_dir.join("strm/News/News.strm");
+        std::fs::write(&strm_file, "http://cdn.tv/live/1.m3u8?token=old").unwrap();
+        rewrite_output_files(&cfg, &input, "token", "old", "new");
+        assert_eq!(std::fs::read_to_string(&strm_file).unwrap(), "http://cdn.tv/live/1.m3u8?token=new");
+        rewrite_output_files(&cfg, &input, "token", "other", "next");
+        assert_eq!(std::fs::read_to_string(&strm_file).unwrap(), "http://cdn.tv/live/1.m3u8?token=new");
+
+        save_input_tokens(&cfg);
+        INPUT_TOKENS.write().unwrap().remove(&input.id);
+        load_input_tokens(&cfg);
+        assert_eq!(INPUT_TOKENS.read().unwrap().get(&input.id).map(String::as_str), Some("new"));
+        INPUT_TOKENS.write().unwrap().remove(&input.id);
+    }
+}
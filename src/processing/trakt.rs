@@ -0,0 +1,339 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use chrono::Local;
+use log::{debug, error, info};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use unidecode::unidecode;
+
+use crate::create_m3u_filter_error;
+use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorKind};
+use crate::model::config::{Config, ConfigTarget, TargetTrakt, TraktConfig};
+use crate::model::playlist::{PlaylistGroup, PlaylistItemHeader, PlaylistItemType};
+use crate::repository::library_repository::split_title_year;
+use crate::utils::file_utils;
+use crate::utils::request_utils::mask_sensitive_info;
+
+const TRAKT_API_URL: &str = "https://api.trakt.tv";
+const TRAKT_TOKEN_FILE: &str = "trakt_token.json";
+const TRAKT_TIMEOUT_SECS: u64 = 30;
+const TRAKT_REDIRECT_URI: &str = "urn:ietf:wg:oauth:2.0:oob";
+/// The token is refreshed when it expires within this time.
+const TRAKT_REFRESH_BEFORE_SECS: i64 = 86_400;
+const TRAKT_PROPERTY_LISTS: &str = "trakt_lists";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TraktToken {
+    access_token: String,
+    refresh_token: String,
+    expires_at: i64,
+}
+
+impl TraktToken {
+    /// From the token response, `expires_in` is given in seconds.
+    fn from_response(content: &Value) -> Option<Self> {
+        let created_at = content.get("created_at").and_then(Value::as_i64).unwrap_or_else(|| Local::now().timestamp());
+        Some(Self {
+            access_token: content.get("access_token")?.as_str()?.to_string(),
+            refresh_token: content.get("refresh_token")?.as_str()?.to_string(),
+            expires_at: created_at + content.get("expires_in").and_then(Value::as_i64).unwrap_or_default(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum TraktKind {
+    Movie,
+    Show,
+}
+
+impl TraktKind {
+    const fn from_item_type(item_type: PlaylistItemType) -> Option<Self> {
+        match item_type {
+            PlaylistItemType::Video => Some(Self::Movie),
+            PlaylistItemType::SeriesInfo => Some(Self::Show),
+            _ => None,
+        }
+    }
+}
+
+/// The movies and shows of a list, matched by tmdb id or by title and year.
+#[derive(Debug, Default)]
+struct TraktList {
+    count: usize,
+    tmdb_ids: HashSet<(TraktKind, u64)>,
+    titles: HashSet<(TraktKind, String, Option<String>)>,
+}
+
+fn normalize_title(title: &str) -> String {
+    unidecode(title).to_lowercase().split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|token| !token.is_empty()).collect::<Vec<_>>().join(" ")
+}
+
+impl TraktList {
+    /// The items of a list or watchlist response, seasons and episodes are ignored.
+    fn from_items(items: &[Value]) -> Self {
+        let mut list = Self::default();
+        for item in items {
+            let (kind, media) = match item.get("type").and_then(Value::as_str) {
+                Some("movie") => (TraktKind::Movie, item.get("movie")),
+                Some("show") => (TraktKind::Show, item.get("show")),
+                _ => continue,
+            };
+            let Some(media) = media else { continue };
+            list.count += 1;
+            if let Some(tmdb_id) = media.get("ids").and_then(|ids| ids.get("tmdb")).and_then(Value::as_u64) {
+                list.tmdb_ids.insert((kind, tmdb_id));
+            }
+            if let Some(title) = media.get("title").and_then(Value::as_str) {
+                let title = normalize_title(title);
+                let year = media.get("year").and_then(Value::as_u64).map(|year| year.to_string());
+                list.titles.insert((kind, title.clone(), None));
+                if year.is_some() {
+                    list.titles.insert((kind, title, year));
+                }
+            }
+        }
+        list
+    }
+
+    fn contains(&self, kind: TraktKind, header: &PlaylistItemHeader) -> bool {
+        let tmdb_id = header.additional_properties.as_ref()
+            .and_then(|props| props.get("tmdb_id").or_else(|| props.get("tmdb")))
+            .and_then(|value| value.as_u64().or_else(|| value.as_str().and_then(|text| text.trim().parse().ok())));
+        if let Some(tmdb_id) = tmdb_id.filter(|id| *id > 0) {
+            if self.tmdb_ids.contains(&(kind, tmdb_id)) {
+                return true;
+            }
+        }
+        let (title, year) = split_title_year(header);
+        self.titles.contains(&(kind, normalize_title(&title), year))
+    }
+}
+
+fn get_token_path(cfg: &Config) -> PathBuf {
+    PathBuf::from(&cfg.working_dir).join(TRAKT_TOKEN_FILE)
+}
+
+fn load_token(path: &Path) -> Option<TraktToken> {
+    std::fs::read(path).ok()
+        .and_then(|content| serde_json::from_slice(&content).map_err(|err| error!("Failed to read trakt token: {err}")).ok())
+}
+
+fn save_token(path: &Path, token: &TraktToken) -> Result<(), M3uFilterError> {
+    file_utils::write_atomic(path, |writer| serde_json::to_writer(writer, token).map_err(std::io::Error::from))
+        .map_err(|err| create_m3u_filter_error!(M3uFilterErrorKind::Info, "Failed to write trakt token: {err}"))
+}
+
+fn create_client(trakt: &TraktConfig) -> Result<reqwest::Client, M3uFilterError> {
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert("trakt-api-version", reqwest::header::HeaderValue::from_static("2"));
+    headers.insert("trakt-api-key", reqwest::header::HeaderValue::from_str(trakt.client_id.trim())
+        .map_err(|err| create_m3u_filter_error!(M3uFilterErrorKind::Info, "Invalid trakt client_id: {err}"))?);
+    reqwest::Client::builder().timeout(Duration::from_secs(TRAKT_TIMEOUT_SECS)).default_headers(headers).build()
+        .map_err(|err| create_m3u_filter_error!(M3uFilterErrorKind::Info, "Failed to create client for trakt: {err}"))
+}
+
+async fn post_json(client: &reqwest::Client, path: &str, body: &Value) -> Result<(reqwest::StatusCode, Value), String> {
+    let response = client.post(format!("{TRAKT_API_URL}{path}")).json(body).send().await
+        .map_err(|err| mask_sensitive_info(&err.without_url().to_string()))?;
+    let status = response.status();
+    Ok((status, response.json::<Value>().await.unwrap_or(Value::Null)))
+}
+
+/// Authorizes the account with the OAuth device flow, the user enters the shown code at trakt.tv.
+/// The token is stored in the working directory and refreshed when it expires.
+pub async fn trakt_device_login(cfg: &Config) -> Result<(), M3uFilterError> {
+    let trakt = cfg.trakt.as_ref().ok_or_else(|| create_m3u_filter_error!(M3uFilterErrorKind::Info, "trakt is not configured"))?;
+    let client = create_client(trakt)?;
+    let (status, code) = post_json(&client, "/oauth/device/code", &json!({"client_id": trakt.client_id.trim()})).await
+        .map_err(|err| create_m3u_filter_error!(M3uFilterErrorKind::Info, "Failed to request trakt device code: {err}"))?;
+    let (Some(device_code), Some(user_code), Some(verification_url)) = (
+        code.get("device_code").and_then(Value::as_str),
+        code.get("user_code").and_then(Value::as_str),
+        code.get("verification_url").and_then(Value::as_str)) else {
+        return Err(create_m3u_filter_error!(M3uFilterErrorKind::Info, "Failed to request trakt device code: status {status}"));
+    };
+    println!("Open {verification_url} and enter the code {user_code}");
+    let interval = code.get("interval").and_then(Value::as_u64).unwrap_or(5).max(1);
+    let expires_at = Local::now().timestamp() + code.get("expires_in").and_then(Value::as_i64).unwrap_or(600);
+    let body = json!({"code": device_code, "client_id": trakt.client_id.trim(), "client_secret": trakt.client_secret.trim()});
+    while Local::now().timestamp() < expires_at {
+        tokio::time::sleep(Duration::from_secs(interval)).await;
+        match post_json(&client, "/oauth/device/token", &body).await {
+            Ok((status, content)) if status.is_success() => {
+                let token = TraktToken::from_response(&content).ok_or_else(|| create_m3u_filter_error!(M3uFilterErrorKind::Info, "Invalid trakt token response"))?;
+                save_token(&get_token_path(cfg), &token)?;
+                info!("Trakt account authorized");
+                return Ok(());
+            }
+            // 400 pending, 429 polling too fast
+            Ok((status, _)) if status.as_u16() == 400 || status.as_u16() == 429 => {}
+            Ok((status, _)) => return Err(create_m3u_filter_error!(M3uFilterErrorKind::Info, "Trakt authorization failed: status {status}")),
+            Err(err) => debug!("Trakt token request failed: {err}"),
+        }
+    }
+    Err(create_m3u_filter_error!(M3uFilterErrorKind::Info, "Trakt authorization code expired"))
+}
+
+/// The access token of the authorized account, refreshed if it expires soon.
+async fn get_access_token(cfg: &Config, trakt: &TraktConfig, client: &reqwest::Client) -> Option<String> {
+    let path = get_token_path(cfg);
+    let token = load_token(&path)?;
+    if token.expires_at - Local::now().timestamp() > TRAKT_REFRESH_BEFORE_SECS {
+        return Some(token.access_token);
+    }
+    let body = json!({
+        "refresh_token": token.refresh_token,
+        "client_id": trakt.client_id.trim(),
+        "client_secret": trakt.client_secret.trim(),
+        "redirect_uri": TRAKT_REDIRECT_URI,
+        "grant_type": "refresh_token",
+    });
+    match post_json(client, "/oauth/token", &body).await {
+        Ok((status, content)) if status.is_success() => {
+            let refreshed = TraktToken::from_response(&content)?;
+            if let Err(err) = save_token(&path, &refreshed) {
+                error!("{err}");
+            }
+            Some(refreshed.access_token)
+        }
+        Ok((status, _)) => {
+            error!("Failed to refresh trakt token: status {status}, authorize again with --trakt-login");
+            None
+        }
+        Err(err) => {
+            error!("Failed to refresh trakt token: {err}");
+            None
+        }
+    }
+}
+
+/// `watchlist` of the authorized user, `user/watchlist` or `user/list-slug`.
+fn get_list_path(list: &str) -> String {
+    match list.trim().split_once('/') {
+        None => format!("/users/me/{}", list.trim()),
+        Some((user, "watchlist")) => format!("/users/{}/watchlist", user.trim()),
+        Some((user, slug)) => format!("/users/{}/lists/{}/items", user.trim(), slug.trim()),
+    }
+}
+
+async fn fetch_list(client: &reqwest::Client, access_token: Option<&str>, list: &str) -> Result<TraktList, String> {
+    let mut request = client.get(format!("{TRAKT_API_URL}{}", get_list_path(list)));
+    if let Some(access_token) = access_token {
+        request = request.bearer_auth(access_token);
+    }
+    match request.send().await {
+        Ok(response) if response.status().is_success() => {
+            let items = response.json::<Vec<Value>>().await.map_err(|err| err.to_string())?;
+            Ok(TraktList::from_items(&items))
+        }
+        Ok(response) => Err(format!("status {}", response.status())),
+        Err(err) => Err(mask_sensitive_info(&err.without_url().to_string())),
+    }
+}
+
+/// Tags the movies and series with the names of the lists containing them, and removes the others when filtering.
+fn apply_lists(options: &TargetTrakt, lists: &HashMap<String, TraktList>, mut playlist: Vec<PlaylistGroup>) -> Vec<PlaylistGroup> {
+    for group in &mut playlist {
+        group.channels.retain(|channel| {
+            let mut header = channel.header.borrow_mut();
+            let Some(kind) = TraktKind::from_item_type(header.item_type) else {
+                return true;
+            };
+            let matched: Vec<Value> = options.lists.iter()
+                .filter(|name| lists.get(*name).is_some_and(|list| list.contains(kind, &header)))
+                .map(|name| Value::String(name.clone()))
+                .collect();
+            if matched.is_empty() {
+                return !options.filter;
+            }
+            if options.tag {
+                let mut props = match header.additional_properties.take() {
+                    Some(Value::Object(props)) => props,
+                    _ => Map::new(),
+                };
+                props.insert(TRAKT_PROPERTY_LISTS.to_string(), Value::Array(matched));
+                header.additional_properties = Some(Value::Object(props));
+            }
+            true
+        });
+    }
+    playlist.retain(|group| !group.channels.is_empty());
+    playlist
+}
+
+/// Applies the trakt lists of the target to the movies and series of the playlist.
+/// The playlist is not filtered if a list can not be fetched.
+pub async fn trakt_apply_lists(cfg: &Config, target: &ConfigTarget, playlist: Vec<PlaylistGroup>) -> Vec<PlaylistGroup> {
+    let (Some(trakt), Some(options)) = (cfg.trakt.as_ref().filter(|trakt| trakt.enabled), target.trakt.as_ref()) else {
+        return playlist;
+    };
+    let client = match create_client(trakt) {
+        Ok(client) => client,
+        Err(err) => {
+            error!("{err}");
+            return playlist;
+        }
+    };
+    let access_token = get_access_token(cfg, trakt, &client).await;
+    let mut lists = HashMap::new();
+    for name in options.lists.iter().filter(|name| !name.trim().is_empty()) {
+        match fetch_list(&client, access_token.as_deref(), name).await {
+            Ok(list) => {
+                debug!("Trakt list {name} has {} movies and shows", list.count);
+                lists.insert(name.clone(), list);
+            }
+            Err(err) => {
+                error!("Failed to fetch trakt list {name} for target {}: {err}", target.name);
+                return playlist;
+            }
+        }
+    }
+    apply_lists(options, &lists, playlist)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::rc::Rc;
+
+    use serde_json::json;
+
+    use crate::model::config::TargetTrakt;
+    use crate::model::playlist::{PlaylistGroup, PlaylistItem, PlaylistItemHeader, PlaylistItemType, XtreamCluster};
+    use crate::processing::trakt::{apply_lists, get_list_path, TraktList};
+
+    fn item(title: &str, item_type: PlaylistItemType, props: Option<serde_json::Value>) -> PlaylistItem {
+        PlaylistItem { header: RefCell::new(PlaylistItemHeader { title: Rc::new(title.to_string()), item_type, additional_properties: props, ..PlaylistItemHeader::default() }) }
+    }
+
+    #[test]
+    fn test_apply_lists() {
+        assert_eq!(get_list_path("watchlist"), "/users/me/watchlist");
+        assert_eq!(get_list_path("sean/watchlist"), "/users/sean/watchlist");
+        assert_eq!(get_list_path("sean/best-movies"), "/users/sean/lists/best-movies/items");
+
+        let list = TraktList::from_items(json!([
+            {"type": "movie", "movie": {"title": "The Matrix", "year": 1999, "ids": {"tmdb": 603}}},
+            {"type": "show", "show": {"title": "Dark", "year": 2017, "ids": {"tmdb": 70523}}},
+            {"type": "episode", "episode": {"title": "Pilot"}}
+        ]).as_array().unwrap());
+        let lists = HashMap::from([("watchlist".to_string(), list)]);
+        let playlist = vec![PlaylistGroup { id: 1, title: Rc::new("VOD".to_string()), xtream_cluster: XtreamCluster::Video, channels: vec![
+            item("Matrix", PlaylistItemType::Video, Some(json!({"tmdb_id": 603}))),
+            item("Dark (2017)", PlaylistItemType::SeriesInfo, None),
+            item("The Matrix (2003)", PlaylistItemType::Video, None),
+            item("News", PlaylistItemType::Live, None),
+        ]}];
+        let options = TargetTrakt { lists: vec!["watchlist".to_string()], filter: true, tag: true };
+        let result = apply_lists(&options, &lists, playlist);
+        let titles: Vec<String> = result[0].channels.iter().map(|channel| channel.header.borrow().title.to_string()).collect();
+        assert_eq!(titles, vec!["Matrix", "Dark (2017)", "News"]);
+        let header = result[0].channels[1].header.borrow();
+        assert_eq!(header.additional_properties.as_ref().unwrap()["trakt_lists"], json!(["watchlist"]));
+    }
+}
@@ -0,0 +1,82 @@
+use log::{debug, info};
+use serde_json::{json, Value};
+
+use crate::create_m3u_filter_error_result;
+use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorKind};
+use crate::model::config::{ConfigTarget, TvheadendConfig};
+use crate::utils::request_utils::mask_sensitive_info;
+
+const NETWORK_CLASS_IPTV_AUTO: &str = "iptv_auto_network";
+
+fn post_form(tvh: &TvheadendConfig, path: &str, params: &[(&str, String)]) -> reqwest::RequestBuilder {
+    let request = reqwest::Client::new().post(format!("{}{path}", tvh.url)).form(params);
+    match &tvh.username {
+        Some(username) => request.basic_auth(username, tvh.password.as_ref()),
+        None => request,
+    }
+}
+
+async fn send(tvh: &TvheadendConfig, path: &str, params: &[(&str, String)]) -> Result<Value, M3uFilterError> {
+    match post_form(tvh, path, params).send().await {
+        Ok(response) if response.status().is_success() => Ok(response.json::<Value>().await.unwrap_or(Value::Null)),
+        Ok(response) => create_m3u_filter_error_result!(M3uFilterErrorKind::Notify, "Tvheadend request {path} failed with status {}", response.status()),
+        Err(err) => create_m3u_filter_error_result!(M3uFilterErrorKind::Notify, "Tvheadend request {path} failed: {}", mask_sensitive_info(&err.to_string())),
+    }
+}
+
+/// Returns the uuid of the network with the given name from the network grid.
+fn find_network_uuid(grid: &Value, network: &str) -> Option<String> {
+    grid.get("entries")?.as_array()?.iter()
+        .find(|entry| entry.get("networkname").and_then(Value::as_str) == Some(network))
+        .and_then(|entry| entry.get("uuid").and_then(Value::as_str))
+        .map(ToString::to_string)
+}
+
+/// Creates or updates the `IPTV Automatic Network` of the target and triggers a rescan.
+/// Tvheadend reads the channel numbers (`tvg-chno`) and epg ids (`tvg-id`) from the m3u playlist.
+pub async fn tvheadend_update_network(target: &ConfigTarget) -> Result<(), M3uFilterError> {
+    let Some(tvh) = target.tvheadend.as_ref() else {
+        return Ok(());
+    };
+    let grid = send(tvh, "/api/mpegts/network/grid", &[("limit", "1000".to_string())]).await?;
+    let conf = json!({
+        "networkname": tvh.network,
+        "url": tvh.playlist_url,
+        "max_streams": tvh.max_streams,
+    });
+    let uuid = if let Some(uuid) = find_network_uuid(&grid, &tvh.network) {
+        let mut node = conf;
+        node["uuid"] = Value::String(uuid.clone());
+        send(tvh, "/api/idnode/save", &[("node", node.to_string())]).await?;
+        debug!("Tvheadend network {} updated for target {}", tvh.network, target.name);
+        uuid
+    } else {
+        let created = send(tvh, "/api/mpegts/network/create",
+                           &[("class", NETWORK_CLASS_IPTV_AUTO.to_string()), ("conf", conf.to_string())]).await?;
+        let Some(uuid) = created.get("uuid").and_then(Value::as_str).map(ToString::to_string) else {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Notify, "Tvheadend network {} could not be created", tvh.network);
+        };
+        info!("Tvheadend network {} created for target {}", tvh.network, target.name);
+        uuid
+    };
+    if tvh.scan {
+        send(tvh, "/api/mpegts/network/scan", &[("uuid", uuid)]).await?;
+        info!("Tvheadend network {} scan started for target {}", tvh.network, target.name);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::processing::tvheadend::find_network_uuid;
+
+    #[test]
+    fn test_find_network_uuid() {
+        let grid = serde_json::json!({"entries": [
+            {"uuid": "a1", "networkname": "DVB-C"},
+            {"uuid": "b2", "networkname": "m3u-filter"}
+        ], "total": 2});
+        assert_eq!(find_network_uuid(&grid, "m3u-filter").as_deref(), Some("b2"));
+        assert_eq!(find_network_uuid(&grid, "IPTV"), None);
+    }
+}
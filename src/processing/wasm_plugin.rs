@@ -0,0 +1,180 @@
+use log::{debug, error};
+use wasmi::{Config as WasmConfig, Engine, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder, TypedFunc};
+
+use crate::create_m3u_filter_error_result;
+use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorKind};
+use crate::model::config::{ConfigTarget, WasmPluginConfig};
+use crate::model::playlist::{PlaylistGroup, PlaylistItemHeader};
+use crate::processing::post_process::{item_from_json, item_to_json, regroup_items};
+
+/// A loaded plugin module. The module exports `memory`, `alloc(len: i32) -> i32`
+/// and `transform(ptr: i32, len: i32) -> i64`. `transform` receives the item as json and
+/// returns the pointer (high 32 bits) and length (low 32 bits) of the transformed item,
+/// a length of 0 drops the item.
+struct WasmPlugin {
+    store: Store<StoreLimits>,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    transform: TypedFunc<(i32, i32), i64>,
+    fuel: u64,
+}
+
+impl WasmPlugin {
+    fn load(config: &WasmPluginConfig) -> Result<Self, String> {
+        let bytes = std::fs::read(&config.path).map_err(|err| err.to_string())?;
+        let mut wasm_config = WasmConfig::default();
+        wasm_config.consume_fuel(true);
+        let engine = Engine::new(&wasm_config);
+        let module = Module::new(&engine, &bytes).map_err(|err| err.to_string())?;
+        let max_memory = usize::try_from(config.max_memory).unwrap_or(usize::MAX).saturating_mul(1024 * 1024);
+        let mut store = Store::new(&engine, StoreLimitsBuilder::new().memory_size(max_memory).build());
+        store.limiter(|limits| limits);
+        store.set_fuel(config.fuel).map_err(|err| err.to_string())?;
+        let instance = Linker::<StoreLimits>::new(&engine)
+            .instantiate(&mut store, &module)
+            .and_then(|pre| pre.start(&mut store))
+            .map_err(|err| err.to_string())?;
+        let memory = instance.get_memory(&store, "memory").ok_or("missing export memory")?;
+        let alloc = instance.get_typed_func::<i32, i32>(&store, "alloc").map_err(|err| err.to_string())?;
+        let transform = instance.get_typed_func::<(i32, i32), i64>(&store, "transform").map_err(|err| err.to_string())?;
+        Ok(Self { store, memory, alloc, transform, fuel: config.fuel })
+    }
+
+    /// Each call gets the full fuel budget, an exhausted budget aborts the call.
+    fn call(&mut self, input: &[u8]) -> Result<Option<Vec<u8>>, String> {
+        self.store.set_fuel(self.fuel).map_err(|err| err.to_string())?;
+        let len = i32::try_from(input.len()).map_err(|err| err.to_string())?;
+        let ptr = self.alloc.call(&mut self.store, len).map_err(|err| err.to_string())?;
+        let offset = usize::try_from(ptr).map_err(|err| err.to_string())?;
+        self.memory.write(&mut self.store, offset, input).map_err(|err| err.to_string())?;
+        let result = self.transform.call(&mut self.store, (ptr, len)).map_err(|err| err.to_string())?;
+        let result = u64::from_ne_bytes(result.to_ne_bytes());
+        let out_len = usize::try_from(result & 0xFFFF_FFFF).map_err(|err| err.to_string())?;
+        if out_len == 0 {
+            return Ok(None);
+        }
+        let out_offset = usize::try_from(result >> 32).map_err(|err| err.to_string())?;
+        if out_offset.checked_add(out_len).is_none_or(|end| end > self.memory.data(&self.store).len()) {
+            return Err(format!("Output range {out_offset}+{out_len} exceeds the plugin memory"));
+        }
+        let mut output = vec![0; out_len];
+        self.memory.read(&self.store, out_offset, &mut output).map_err(|err| err.to_string())?;
+        Ok(Some(output))
+    }
+}
+
+/// A failed call (trap, exhausted fuel, invalid output) keeps the item unchanged, only a plugin
+/// which can't be loaded fails the target.
+fn transform_items(config: &WasmPluginConfig, items: Vec<PlaylistItemHeader>) -> Result<Vec<PlaylistItemHeader>, String> {
+    let mut plugin = WasmPlugin::load(config)?;
+    let mut result = Vec::with_capacity(items.len());
+    for item in items {
+        let input = item_to_json(&item).map_err(|err| err.to_string())?;
+        match plugin.call(&input) {
+            Ok(Some(output)) => match item_from_json(&output) {
+                Ok(header) => result.push(header),
+                Err(err) => {
+                    error!("Wasm plugin {} returned invalid item, item {} is kept: {err}", config.path, item.name);
+                    result.push(item);
+                }
+            },
+            Ok(None) => {}
+            Err(err) => {
+                error!("Wasm plugin {} failed, item {} is kept: {err}", config.path, item.name);
+                result.push(item);
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Transforms each playlist item with the `wasm_plugins` of the target, in the configured order.
+pub fn wasm_transform_playlist(target: &ConfigTarget, playlist: Vec<PlaylistGroup>) -> Result<Vec<PlaylistGroup>, M3uFilterError> {
+    let Some(plugins) = target.wasm_plugins.as_ref().filter(|plugins| !plugins.is_empty()) else {
+        return Ok(playlist);
+    };
+    let mut items: Vec<PlaylistItemHeader> = playlist.iter().flat_map(|group| &group.channels).map(|channel| channel.header.borrow().clone()).collect();
+    for plugin in plugins {
+        items = match transform_items(plugin, items) {
+            Ok(items) => items,
+            Err(err) => return create_m3u_filter_error_result!(M3uFilterErrorKind::Notify, "Wasm plugin {} of target {} failed: {err}", plugin.path, target.name),
+        };
+        debug!("Wasm plugin {} of target {} returned {} items", plugin.path, target.name, items.len());
+    }
+    Ok(regroup_items(items.into_iter(), &playlist))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::rc::Rc;
+
+    use crate::model::config::WasmPluginConfig;
+    use crate::model::playlist::PlaylistItemHeader;
+    use crate::processing::post_process::item_to_json;
+    use crate::processing::wasm_plugin::transform_items;
+    use crate::utils::test_utils::create_temp_dir;
+
+    // keeps items with a json longer than LIMIT bytes, drops the others
+    const PLUGIN: &str = r#"(module
+        (memory (export "memory") 1)
+        (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+        (func (export "transform") (param $ptr i32) (param $len i32) (result i64)
+            (if (result i64) (i32.gt_u (local.get $len) (i32.const LIMIT))
+                (then (i64.or (i64.shl (i64.extend_i32_u (local.get $ptr)) (i64.const 32)) (i64.extend_i32_u (local.get $len))))
+                (else (i64.const 0)))))"#;
+
+    // traps for items with a json up to LIMIT bytes, replaces the others with the OUTPUT item
+    const TRAP_PLUGIN: &str = r#"(module
+        (memory (export "memory") 1)
+        (data (i32.const 2048) "OUTPUT")
+        (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+        (func (export "transform") (param $ptr i32) (param $len i32) (result i64)
+            (if (i32.le_u (local.get $len) (i32.const LIMIT)) (then unreachable))
+            (i64.or (i64.shl (i64.const 2048) (i64.const 32)) (i64.const OUTPUT_LEN))))"#;
+
+    const LOOP_PLUGIN: &str = r#"(module
+        (memory (export "memory") 1)
+        (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+        (func (export "transform") (param i32 i32) (result i64) (loop (br 0)) (i64.const 0)))"#;
+
+    const OVERSIZED_PLUGIN: &str = r#"(module
+        (memory (export "memory") 1)
+        (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+        (func (export "transform") (param i32 i32) (result i64) (i64.const 0x0000_0400_FFFF_FFFF)))"#;
+
+    fn write_plugin(dir: &Path, name: &str, content: &str) -> WasmPluginConfig {
+        let path = dir.join(format!("{name}.wasm"));
+        std::fs::write(&path, wat::parse_str(content).unwrap()).unwrap();
+        WasmPluginConfig { path: path.to_str().unwrap().to_string(), fuel: 10_000, max_memory: 1 }
+    }
+
+    #[test]
+    fn test_transform_items() {
+        let temp_dir = create_temp_dir("wasm_plugin");
+        let item = |name: &str| PlaylistItemHeader { name: Rc::new(name.to_string()), ..PlaylistItemHeader::default() };
+        let limit = item_to_json(&item("News")).unwrap().len();
+        let plugin = write_plugin(temp_dir.path(), "plugin", &PLUGIN.replace("LIMIT", &limit.to_string()));
+        let items = transform_items(&plugin, vec![item("News"), item(&"Sport".repeat(20))]).unwrap();
+        assert_eq!(items.len(), 1);
+        assert!(items[0].name.starts_with("Sport"));
+
+        let output = item_to_json(&item("Transformed")).unwrap();
+        let plugin = write_plugin(temp_dir.path(), "trap_plugin", &TRAP_PLUGIN
+            .replace("OUTPUT_LEN", &output.len().to_string())
+            .replace("OUTPUT", &output.iter().map(|byte| format!("\\{byte:02x}")).collect::<String>())
+            .replace("LIMIT", &limit.to_string()));
+        let items = transform_items(&plugin, vec![item("Sport1"), item("News"), item("Sport2")]).unwrap();
+        let names: Vec<&str> = items.iter().map(|item| item.name.as_str()).collect();
+        assert_eq!(names, vec!["Transformed", "News", "Transformed"]);
+
+        let plugin = write_plugin(temp_dir.path(), "loop_plugin", LOOP_PLUGIN);
+        let items = transform_items(&plugin, vec![item("News"), item("Sport")]).unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[1].name.as_str(), "Sport");
+
+        let plugin = write_plugin(temp_dir.path(), "oversized_plugin", OVERSIZED_PLUGIN);
+        let items = transform_items(&plugin, vec![item("News")]).unwrap();
+        assert_eq!(items[0].name.as_str(), "News");
+    }
+}
@@ -1,9 +1,12 @@
 use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
 
+use chrono::{NaiveDateTime, TimeDelta};
 use quick_xml::events::Event;
 use quick_xml::Reader;
 
+use crate::model::playlist::PlaylistGroup;
+
 use crate::model::xmltv::{Epg, EPG_ATTRIB_CHANNEL, EPG_ATTRIB_ID, EPG_TAG_TV, EPG_TAG_CHANNEL, EPG_TAG_PROGRAMME, TVGuide, XmlTag};
 use crate::utils::compressed_file_reader::CompressedFileReader;
 
@@ -128,6 +131,134 @@ where
     }
 }
 
+pub fn time_correct(date_time: &str, correction: &TimeDelta) -> String {
+    // Split the dateTime string into date and time parts
+    let date_time_split: Vec<&str> = date_time.split(' ').collect();
+    if date_time_split.len() != 2 {
+        return date_time.to_string();
+    }
+
+    // Parse the datetime string
+    NaiveDateTime::parse_from_str(date_time_split[0], "%Y%m%d%H%M%S").map_or_else(|_| date_time.to_string(), |native_dt| {
+            let corrected_dt = native_dt + *correction;
+            // Format the corrected datetime back to string
+            let formatted_dt = corrected_dt.format("%Y%m%d%H%M%S").to_string();
+            let result = format!("{} {}", formatted_dt, date_time_split[1]);
+            result
+        })
+}
+
+pub fn parse_timeshift(time_shift: Option<&String>) -> Option<i32> {
+    time_shift.and_then(|offset| {
+            let sign_factor = if offset.starts_with('-') { -1 } else { 1 };
+            let offset = offset.trim_start_matches(&['-', '+'][..]); // Remove the sign for parsing
+
+            let total_minutes = if offset.contains(':') {
+                // Handle the case with hours and minutes (e.g., "-2:30", "1:45", "+0:15", ":30")
+                let parts: Vec<&str> = offset.split(':').collect();
+
+                let hours: i32 = if parts[0].is_empty() {
+                    0 // Treat empty hour part as 0 hours
+                } else {
+                    parts[0].parse().unwrap_or(0)
+                };
+
+                let minutes: i32 = if parts.len() > 1 {
+                    parts[1].parse().unwrap_or(0)
+                } else {
+                    0
+                };
+
+                // Convert hours to minutes and add the minute part
+                hours * 60 + minutes
+            } else {
+                // Handle single number case (e.g., "2" or "+2")
+                let num: i32 = offset.parse().unwrap_or(0);
+                num * 60
+            };
+
+            if total_minutes > 0 {
+                Some(sign_factor * total_minutes)
+            } else {
+                None
+            }
+        })
+}
+
+
+/// A channel whose programmes are copied with shifted times to a new channel id.
+pub struct EpgTimeshift {
+    pub epg_channel_id: Rc<String>,
+    pub shifted_epg_channel_id: Rc<String>,
+    pub minutes: i32,
+}
+
+/// Assigns a new epg channel id to all channels with an `epg_timeshift`.
+/// Returns the timeshifts which have to be applied to the epg.
+pub fn assign_channel_epg_timeshift(playlist: &[PlaylistGroup]) -> Vec<EpgTimeshift> {
+    let mut result: Vec<EpgTimeshift> = vec![];
+    for channel in playlist.iter().flat_map(|group| &group.channels) {
+        let mut header = channel.header.borrow_mut();
+        if header.epg_timeshift.is_empty() {
+            continue;
+        }
+        let Some(epg_channel_id) = header.epg_channel_id.clone() else {
+            continue;
+        };
+        if let Some(minutes) = parse_timeshift(Some(&header.epg_timeshift)) {
+            let shifted_epg_channel_id = Rc::new(format!("{epg_channel_id}_{minutes:+}"));
+            if !result.iter().any(|shift| shift.shifted_epg_channel_id == shifted_epg_channel_id) {
+                result.push(EpgTimeshift {
+                    epg_channel_id,
+                    shifted_epg_channel_id: Rc::clone(&shifted_epg_channel_id),
+                    minutes,
+                });
+            }
+            header.epg_channel_id = Some(shifted_epg_channel_id);
+        }
+    }
+    result
+}
+
+fn copy_tag_with_attributes(tag: &XmlTag, attributes: &[(&str, String)]) -> XmlTag {
+    let mut tag_attributes = tag.attributes.as_ref().map_or_else(HashMap::new, |attribs| attribs.as_ref().clone());
+    for (key, value) in attributes {
+        tag_attributes.insert((*key).to_string(), value.clone());
+    }
+    XmlTag {
+        name: tag.name.clone(),
+        value: tag.value.clone(),
+        attributes: Some(Rc::new(tag_attributes)),
+        children: tag.children.clone(),
+    }
+}
+
+/// Adds a copy of the channel and its programmes with the shifted id and times for each timeshift.
+pub fn apply_epg_timeshift(epg: &mut Epg, timeshifts: &[EpgTimeshift]) {
+    let mut shifted_tags = vec![];
+    for timeshift in timeshifts {
+        let duration = TimeDelta::minutes(i64::from(timeshift.minutes));
+        for tag in &epg.children {
+            match tag.name.as_str() {
+                EPG_TAG_CHANNEL if tag.get_attribute_value(EPG_ATTRIB_ID) == Some(&timeshift.epg_channel_id) => {
+                    shifted_tags.push(copy_tag_with_attributes(tag, &[(EPG_ATTRIB_ID, timeshift.shifted_epg_channel_id.to_string())]));
+                }
+                EPG_TAG_PROGRAMME if tag.get_attribute_value(EPG_ATTRIB_CHANNEL) == Some(&timeshift.epg_channel_id) => {
+                    let mut attributes = vec![(EPG_ATTRIB_CHANNEL, timeshift.shifted_epg_channel_id.to_string())];
+                    for attrib in ["start", "stop"] {
+                        if let Some(value) = tag.get_attribute_value(attrib) {
+                            attributes.push((attrib, time_correct(value, &duration)));
+                        }
+                    }
+                    shifted_tags.push(copy_tag_with_attributes(tag, &attributes));
+                }
+                _ => {}
+            }
+        }
+    }
+    epg.children.append(&mut shifted_tags);
+}
+
 pub fn flatten_tvguide(tv_guides: &[Epg]) -> Option<Epg> {
     if tv_guides.is_empty() {
         None
@@ -173,7 +304,8 @@ mod tests {
     use std::path::PathBuf;
     use std::rc::Rc;
 
-    use crate::model::xmltv::{TVGuide};
+    use crate::model::xmltv::{Epg, TVGuide, XmlTag};
+    use crate::processing::xmltv_parser::{apply_epg_timeshift, EpgTimeshift};
 
     #[test]
     fn parse_test() -> io::Result<()> {
@@ -193,4 +325,33 @@ mod tests {
 
         Ok(())
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn timeshift_test() {
+        let create_tag = |name: &str, attributes: &[(&str, &str)]| XmlTag {
+            name: name.to_string(),
+            value: None,
+            attributes: Some(Rc::new(attributes.iter().map(|(k, v)| ((*k).to_string(), (*v).to_string())).collect())),
+            children: None,
+        };
+        let mut epg = Epg {
+            attributes: None,
+            children: vec![
+                create_tag("channel", &[("id", "channel.1")]),
+                create_tag("programme", &[("channel", "channel.1"), ("start", "20240101100000 +0000"), ("stop", "20240101110000 +0000")]),
+            ],
+        };
+        let timeshifts = vec![EpgTimeshift {
+            epg_channel_id: Rc::new("channel.1".to_string()),
+            shifted_epg_channel_id: Rc::new("channel.1_+60".to_string()),
+            minutes: 60,
+        }];
+        apply_epg_timeshift(&mut epg, &timeshifts);
+        assert_eq!(epg.children.len(), 4);
+        assert_eq!(epg.children[2].get_attribute_value("id").unwrap(), "channel.1_+60");
+        let programme = &epg.children[3];
+        assert_eq!(programme.get_attribute_value("channel").unwrap(), "channel.1_+60");
+        assert_eq!(programme.get_attribute_value("start").unwrap(), "20240101110000 +0000");
+        assert_eq!(programme.get_attribute_value("stop").unwrap(), "20240101120000 +0000");
+    }
+}
@@ -4,17 +4,18 @@ use std::rc::Rc;
 
 use serde_json::Value;
 
-use crate::create_m3u_filter_error_result;
-use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorKind};
+use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorCategory, M3uFilterErrorKind};
 use crate::model::config::ConfigInput;
 use crate::model::playlist::{PlaylistGroup, PlaylistItem, PlaylistItemHeader, PlaylistItemType, XtreamCluster};
 use crate::model::xtream::{XtreamCategory, XtreamSeriesInfo, XtreamSeriesInfoEpisode, XtreamStream};
+use crate::utils::string_utils::StringInterner;
 
 fn map_to_xtream_category(categories: &Value) -> Result<Vec<XtreamCategory>, M3uFilterError> {
     match serde_json::from_value::<Vec<XtreamCategory>>(categories.to_owned()) {
         Ok(xtream_categories) => Ok(xtream_categories),
         Err(err) => {
-            create_m3u_filter_error_result!(M3uFilterErrorKind::Notify, "Failed to process categories {}", &err)
+            let message = format!("Failed to process categories {err}");
+            Err(M3uFilterError::from_source(M3uFilterErrorKind::Notify, M3uFilterErrorCategory::Parse, err, message))
         }
     }
 }
@@ -23,7 +24,8 @@ fn map_to_xtream_streams(xtream_cluster: XtreamCluster, streams: &Value) -> Resu
     match serde_json::from_value::<Vec<XtreamStream>>(streams.to_owned()) {
         Ok(stream_list) => Ok(stream_list),
         Err(err) => {
-            create_m3u_filter_error_result!(M3uFilterErrorKind::Notify, "Failed to map to xtream streams {:?}: {}", xtream_cluster, &err)
+            let message = format!("Failed to map to xtream streams {xtream_cluster:?}: {err}");
+            Err(M3uFilterError::from_source(M3uFilterErrorKind::Notify, M3uFilterErrorCategory::Parse, err, message))
         }
     }
 }
@@ -45,13 +47,15 @@ pub fn parse_xtream_series_info(info: &Value, group_title: &str, input: &ConfigI
 
     match serde_json::from_value::<XtreamSeriesInfo>(info.to_owned()) {
         Ok(series_info) => {
+            let group = Rc::new(group_title.to_string());
+            let mut interner = StringInterner::default();
             let result: Vec<PlaylistItem> = series_info.episodes.values().flatten().map(|episode|
                 PlaylistItem {
                     header: RefCell::new(PlaylistItemHeader {
                         id: Rc::new(episode.id.to_string()),
                         name: Rc::new(episode.title.clone()),
-                        logo: Rc::new(episode.info.movie_image.clone()),
-                        group: Rc::new(group_title.to_string()),
+                        logo: interner.intern(episode.info.movie_image.clone()),
+                        group: Rc::clone(&group),
                         title: Rc::new(episode.title.clone()),
                         url: create_xtream_series_info_url(url, username, password, episode),
                         item_type: PlaylistItemType::Series,
@@ -65,7 +69,8 @@ pub fn parse_xtream_series_info(info: &Value, group_title: &str, input: &ConfigI
             if result.is_empty() { Ok(None) } else { Ok(Some(result)) }
         }
         Err(err) => {
-            create_m3u_filter_error_result!(M3uFilterErrorKind::Notify, "Failed to process series info {}", &err)
+            let message = format!("Failed to process series info {err}");
+            Err(M3uFilterError::from_source(M3uFilterErrorKind::Notify, M3uFilterErrorCategory::Parse, err, message))
         }
     }
 }
@@ -87,6 +92,36 @@ fn create_xtream_url(xtream_cluster: XtreamCluster, url: &str, username: &str, p
         Rc::clone(&stream.direct_source)
     }
 }
+/// The `user_info` of the xtream account, returned by `player_api.php` without action.
+#[derive(Debug, Default)]
+pub struct XtreamAccountInfo {
+    pub auth: bool,
+    pub status: String,
+    pub exp_date: Option<i64>,
+    pub max_connections: Option<u32>,
+    pub active_cons: Option<u32>,
+}
+
+/// Providers send numbers as json numbers or strings.
+fn get_account_number(value: Option<&Value>) -> Option<i64> {
+    match value? {
+        Value::Number(number) => number.as_i64(),
+        Value::String(text) => text.trim().parse::<i64>().ok(),
+        _ => None,
+    }
+}
+
+pub fn parse_xtream_account_info(content: &Value) -> Option<XtreamAccountInfo> {
+    let user_info = content.get("user_info")?.as_object()?;
+    Some(XtreamAccountInfo {
+        auth: get_account_number(user_info.get("auth")).is_some_and(|auth| auth != 0),
+        status: user_info.get("status").and_then(Value::as_str).unwrap_or_default().to_string(),
+        exp_date: get_account_number(user_info.get("exp_date")).filter(|exp_date| *exp_date > 0),
+        max_connections: get_account_number(user_info.get("max_connections")).and_then(|value| u32::try_from(value).ok()),
+        active_cons: get_account_number(user_info.get("active_cons")).and_then(|value| u32::try_from(value).ok()),
+    })
+}
+
 pub fn parse_xtream(input: &ConfigInput,
                            xtream_cluster: XtreamCluster,
                            categories: &Value,
@@ -105,6 +140,7 @@ pub fn parse_xtream(input: &ConfigInput,
                             (Rc::clone(&category.category_id), RefCell::new(category))
                         ).collect();
 
+                    let mut interner = StringInterner::default();
                     for stream in xtream_streams {
                         if let Some(group) = group_map.get(&stream.category_id) {
                             let mut grp = group.borrow_mut();
@@ -113,7 +149,7 @@ pub fn parse_xtream(input: &ConfigInput,
                                 header: RefCell::new(PlaylistItemHeader {
                                     id: Rc::new(stream.get_stream_id().to_string()),
                                     name: Rc::clone(&stream.name),
-                                    logo: Rc::clone(&stream.stream_icon),
+                                    logo: interner.intern_rc(&stream.stream_icon),
                                     group: Rc::clone(category_name),
                                     title: Rc::clone(&stream.name),
                                     url: create_xtream_url(xtream_cluster, url, username, password, &stream),
@@ -148,3 +184,24 @@ pub fn parse_xtream(input: &ConfigInput,
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use crate::processing::xtream_parser::parse_xtream_account_info;
+
+    #[test]
+    fn test_parse_xtream_account_info() {
+        let content = serde_json::json!({"user_info": {"auth": 1, "status": "Active", "exp_date": "1700000000", "max_connections": "2", "active_cons": 0}});
+        let info = parse_xtream_account_info(&content).unwrap();
+        assert!(info.auth);
+        assert_eq!(info.status, "Active");
+        assert_eq!(info.exp_date, Some(1_700_000_000));
+        assert_eq!(info.max_connections, Some(2));
+        assert_eq!(info.active_cons, Some(0));
+        let content = serde_json::json!({"user_info": {"auth": 0, "exp_date": null}});
+        let info = parse_xtream_account_info(&content).unwrap();
+        assert!(!info.auth);
+        assert_eq!(info.exp_date, None);
+        assert!(parse_xtream_account_info(&serde_json::json!([])).is_none());
+    }
+}
@@ -1,21 +1,33 @@
 use crate::m3u_filter_error::M3uFilterError;
-use crate::model::config::{ConfigTarget, InputType, TargetType};
+use crate::model::config::{ConfigTarget, InputType};
 use crate::model::playlist::FetchedPlaylist;
 use crate::processing::playlist_processor::ProcessingPipe;
 use crate::utils::download;
 
+/// The delay between the series info requests, `None` if the target doesn't resolve the series of the input.
+fn get_resolve_series_delay(target: &ConfigTarget, fpl: &FetchedPlaylist<'_>) -> Option<u16> {
+    target.options.as_ref()
+        .filter(|options| options.xtream_resolve_series && fpl.input.input_type == InputType::Xtream && target.has_series_episode_output())
+        .map(|options| options.xtream_resolve_series_delay)
+}
+
+/// Resolves the series of the input once for the targets which are processed in parallel.
+/// The targets get a copy of the playlist with the episodes and the series marked as fetched.
+pub async fn playlist_resolve_source_series(targets: &[&ConfigTarget], errors: &mut Vec<M3uFilterError>, fpl: &mut FetchedPlaylist<'_>) {
+    let Some(resolve_series_delay) = targets.iter().find_map(|target| get_resolve_series_delay(target, fpl)) else {
+        return;
+    };
+    let series_playlist = download::get_xtream_playlist_series(fpl, errors, resolve_series_delay).await;
+    for plg in &series_playlist {
+        fpl.update_playlist(plg);
+    }
+}
+
 pub async fn playlist_resolve_series(target: &ConfigTarget, errors: &mut Vec<M3uFilterError>,
                                      pipe: &ProcessingPipe,
                                      fpl: &mut FetchedPlaylist<'_>,
                                      new_fpl: &mut FetchedPlaylist<'_>) {
-    let (resolve_series, resolve_series_delay) =
-        if let Some(options) = &target.options {
-            (options.xtream_resolve_series && fpl.input.input_type == InputType::Xtream && target.has_output(&TargetType::M3u),
-             options.xtream_resolve_series_delay)
-        } else {
-            (false, 0)
-        };
-    if resolve_series {
+    if let Some(resolve_series_delay) = get_resolve_series_delay(target, fpl) {
         let mut series_playlist = download::get_xtream_playlist_series(fpl, errors, resolve_series_delay).await;
         // original content saved into original list
         for plg in &series_playlist {
@@ -0,0 +1,237 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use log::error;
+use serde::{Deserialize, Serialize};
+use unidecode::unidecode;
+
+use crate::create_m3u_filter_error_result;
+use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorKind};
+use crate::model::config::Config;
+use crate::utils::csv_utils::parse_csv;
+use crate::utils::file_utils;
+use crate::utils::json_utils::json_write_documents_to_file;
+
+/// Aliases added through the api.
+const FILE_CHANNEL_ALIASES: &str = "channel_aliases.json";
+
+/// Quality tags of channel names, best first.
+pub const DEFAULT_QUALITY_TAGS: &[&str] = &["uhd", "4k", "fhd", "hd", "sd"];
+
+/// A canonical channel name with the names the providers use for the channel.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ChannelAlias {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+    /// quality tags of the channel names best first, like `FHD`, `HD`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub quality: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub epg_channel_id: Option<String>,
+}
+
+impl ChannelAlias {
+    /// The columns `name`, `aliases` and `quality` separated by `|`, `country` and `epg_channel_id`.
+    fn from_row(row: &HashMap<String, String>) -> Self {
+        let list = |column: &str| row.get(column).map(|value| value.split('|').map(str::trim)
+            .filter(|text| !text.is_empty()).map(ToString::to_string).collect()).unwrap_or_default();
+        let text = |column: &str| row.get(column).map(|value| value.trim().to_string()).filter(|value| !value.is_empty());
+        Self {
+            name: text("name").unwrap_or_default(),
+            aliases: list("aliases"),
+            country: text("country"),
+            quality: list("quality"),
+            epg_channel_id: text("epg_channel_id"),
+        }
+    }
+
+    /// Adds the aliases and quality tags of `other`, the other fields are replaced when set.
+    fn merge(&mut self, other: Self) {
+        for alias in other.aliases {
+            if !self.aliases.contains(&alias) {
+                self.aliases.push(alias);
+            }
+        }
+        for quality in other.quality {
+            if !self.quality.iter().any(|tag| tag.eq_ignore_ascii_case(&quality)) {
+                self.quality.push(quality);
+            }
+        }
+        if other.country.is_some() {
+            self.country = other.country;
+        }
+        if other.epg_channel_id.is_some() {
+            self.epg_channel_id = other.epg_channel_id;
+        }
+    }
+}
+
+/// Looks up channels by their normalized name.
+/// The names are compared in lower case ascii without punctuation, quality tags and a leading country code.
+#[derive(Debug, Default)]
+pub struct AliasDatabase {
+    entries: Vec<ChannelAlias>,
+    index: HashMap<String, usize>,
+    quality_tags: HashSet<String>,
+    countries: HashSet<String>,
+}
+
+impl AliasDatabase {
+    /// Entries with the same canonical name are merged.
+    pub fn new(aliases: Vec<ChannelAlias>) -> Self {
+        let mut db = Self {
+            quality_tags: DEFAULT_QUALITY_TAGS.iter().map(ToString::to_string).collect(),
+            ..Self::default()
+        };
+        let mut entries: Vec<ChannelAlias> = vec![];
+        for alias in aliases.into_iter().filter(|alias| !alias.name.trim().is_empty()) {
+            db.quality_tags.extend(alias.quality.iter().map(|tag| tag.to_lowercase()));
+            db.countries.extend(alias.country.iter().map(|country| country.to_lowercase()));
+            match entries.iter_mut().find(|entry| entry.name.eq_ignore_ascii_case(&alias.name)) {
+                Some(entry) => entry.merge(alias),
+                None => entries.push(alias),
+            }
+        }
+        for (position, entry) in entries.iter().enumerate() {
+            for name in std::iter::once(&entry.name).chain(&entry.aliases) {
+                db.index.entry(db.normalize(name)).or_insert(position);
+            }
+        }
+        db.entries = entries;
+        db
+    }
+
+    pub fn entries(&self) -> &[ChannelAlias] {
+        &self.entries
+    }
+
+    fn tokens(name: &str) -> Vec<String> {
+        unidecode(name).to_lowercase().split(|c: char| !c.is_ascii_alphanumeric())
+            .filter(|token| !token.is_empty()).map(ToString::to_string).collect()
+    }
+
+    pub fn normalize(&self, name: &str) -> String {
+        let mut tokens: Vec<String> = Self::tokens(name).into_iter().filter(|token| !self.quality_tags.contains(token)).collect();
+        if tokens.len() > 1 && self.countries.contains(&tokens[0]) {
+            tokens.remove(0);
+        }
+        tokens.join(" ")
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ChannelAlias> {
+        self.index.get(&self.normalize(name)).map(|&position| &self.entries[position])
+    }
+
+    /// The position of the quality tag of the name in the quality tags of the entry, lower is better.
+    /// Names without a known quality tag rank last.
+    pub fn quality_rank(entry: &ChannelAlias, name: &str) -> usize {
+        let tokens = Self::tokens(name);
+        let ranking: Vec<String> = if entry.quality.is_empty() {
+            DEFAULT_QUALITY_TAGS.iter().map(ToString::to_string).collect()
+        } else {
+            entry.quality.iter().map(|tag| tag.to_lowercase()).collect()
+        };
+        ranking.iter().position(|tag| tokens.contains(tag)).unwrap_or(ranking.len())
+    }
+
+    /// The entry of the name and the entries whose name or aliases contain the query.
+    pub fn search(&self, query: &str) -> Vec<&ChannelAlias> {
+        let query = self.normalize(query);
+        let exact = self.index.get(&query).copied();
+        let mut result: Vec<&ChannelAlias> = exact.iter().map(|&position| &self.entries[position]).collect();
+        result.extend(self.entries.iter().enumerate()
+            .filter(|(position, entry)| Some(*position) != exact
+                && std::iter::once(&entry.name).chain(&entry.aliases).any(|name| self.normalize(name).contains(&query)))
+            .map(|(_, entry)| entry));
+        result
+    }
+}
+
+fn read_alias_file(path: &Path) -> Result<Vec<ChannelAlias>, String> {
+    let content = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("csv")) {
+        Ok(parse_csv(&content).iter().map(ChannelAlias::from_row).collect())
+    } else {
+        serde_yaml::from_str(&content).map_err(|err| err.to_string())
+    }
+}
+
+fn get_aliases_path(cfg: &Config) -> PathBuf {
+    PathBuf::from(&cfg.working_dir).join(FILE_CHANNEL_ALIASES)
+}
+
+fn read_added_aliases(path: &Path) -> Vec<ChannelAlias> {
+    let Ok(file) = File::open(path) else {
+        return vec![];
+    };
+    serde_json::from_reader(BufReader::new(file)).unwrap_or_else(|err| {
+        error!("Failed to read channel aliases {}: {err}", path.to_str().unwrap_or("?"));
+        vec![]
+    })
+}
+
+/// The aliases of the configured file and the aliases added through the api.
+pub fn alias_load(cfg: &Config) -> AliasDatabase {
+    let mut aliases = vec![];
+    let file = cfg.channel_aliases.as_ref().and_then(|aliases_cfg| aliases_cfg.file.as_ref());
+    if let Some(path) = file_utils::get_file_path(&cfg.t_config_path, file.map(PathBuf::from)) {
+        match read_alias_file(&path) {
+            Ok(mut entries) => aliases.append(&mut entries),
+            Err(err) => error!("Failed to read channel aliases {}: {err}", path.to_str().unwrap_or("?")),
+        }
+    }
+    let path = get_aliases_path(cfg);
+    if path.exists() {
+        if let Ok(_file_lock) = cfg.file_locks.read_lock(&path) {
+            aliases.append(&mut read_added_aliases(&path));
+        }
+    }
+    AliasDatabase::new(aliases)
+}
+
+/// Adds an alias entry, it is merged with the entries of the same canonical name.
+pub fn alias_add(cfg: &Config, alias: ChannelAlias) -> Result<(), M3uFilterError> {
+    if alias.name.trim().is_empty() {
+        return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "Channel alias needs a name");
+    }
+    let path = get_aliases_path(cfg);
+    let _file_lock = cfg.file_locks.write_lock(&path).map_err(|err| M3uFilterError::new(M3uFilterErrorKind::Info, format!("{err}")))?;
+    let mut aliases = read_added_aliases(&path);
+    match aliases.iter_mut().find(|entry| entry.name.eq_ignore_ascii_case(&alias.name)) {
+        Some(entry) => entry.merge(alias),
+        None => aliases.push(alias),
+    }
+    if let Err(err) = json_write_documents_to_file(&path, &aliases) {
+        return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "Failed to write channel aliases {}: {err}", path.to_str().unwrap_or("?"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::repository::alias_repository::{AliasDatabase, ChannelAlias};
+
+    #[test]
+    fn test_alias_database() {
+        let db = AliasDatabase::new(vec![
+            ChannelAlias { name: "Das Erste".to_string(), aliases: vec!["ARD".to_string()], country: Some("DE".to_string()), ..ChannelAlias::default() },
+            ChannelAlias { name: "ZDF".to_string(), quality: vec!["RAW".to_string(), "FHD".to_string()], ..ChannelAlias::default() },
+            ChannelAlias { name: "das erste".to_string(), aliases: vec!["Erstes".to_string()], epg_channel_id: Some("daserste.de".to_string()), ..ChannelAlias::default() },
+        ]);
+        assert_eq!(db.entries().len(), 2);
+        let ard = db.get("DE: ARD HD").unwrap();
+        assert_eq!((ard.name.as_str(), ard.epg_channel_id.as_deref()), ("Das Erste", Some("daserste.de")));
+        assert_eq!(db.get("Erstes").map(|entry| entry.name.as_str()), Some("Das Erste"));
+        assert_eq!(db.get("ZDF RAW").map(|entry| entry.name.as_str()), Some("ZDF"));
+        assert!(db.get("Arte").is_none());
+        let zdf = db.get("ZDF").unwrap();
+        assert!(AliasDatabase::quality_rank(zdf, "ZDF RAW") < AliasDatabase::quality_rank(zdf, "ZDF FHD"));
+        assert!(AliasDatabase::quality_rank(ard, "ARD FHD") < AliasDatabase::quality_rank(ard, "ARD"));
+        assert_eq!(db.search("erst").iter().map(|entry| entry.name.as_str()).collect::<Vec<_>>(), vec!["Das Erste"]);
+    }
+}
@@ -0,0 +1,200 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::auth::role::Role;
+use crate::create_m3u_filter_error_result;
+use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorKind};
+use crate::model::config::Config;
+use crate::utils::request_utils::mask_sensitive_info;
+
+/// Config changes made through the api, one json document per line. The file is only appended.
+const FILE_AUDIT_LOG: &str = "audit_log.jsonl";
+
+/// Values of these fields are masked, the audit log only shows that they changed.
+const MASKED_FIELDS: &[&str] = &["password", "secret", "token", "tokens", "api_key", "client_secret"];
+const MASKED_VALUE: &str = "***";
+
+/// The changed config file or data managed through the api.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditSection {
+    /// `config.yml`
+    Config,
+    /// the users of `api-proxy.yml`
+    ApiProxyUser,
+    /// the servers of `api-proxy.yml`
+    ApiProxyServer,
+    /// the channel overrides of a target
+    ChannelOverride,
+    /// the quarantined channels of a target
+    ChannelQuarantine,
+    /// the channel aliases added through the api
+    ChannelAlias,
+    /// the banned client ips
+    Ban,
+    /// the scheduled recordings
+    Recording,
+}
+
+/// A changed value, `path` is like `sources[0].targets[1].name`. A missing value is `null`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditChange {
+    pub path: String,
+    pub old: Value,
+    pub new: Value,
+}
+
+/// A config change, `timestamp` is a unix timestamp.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub timestamp: i64,
+    /// the user, `token:<hash>` for api tokens or `anonymous` without `web_auth`
+    pub user: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<Role>,
+    pub client_ip: String,
+    pub section: AuditSection,
+    pub changes: Vec<AuditChange>,
+}
+
+/// Filter of the audit log query, `from` and `to` are unix timestamps.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AuditQuery {
+    pub user: Option<String>,
+    pub section: Option<AuditSection>,
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+    pub limit: Option<usize>,
+}
+
+impl AuditQuery {
+    fn matches(&self, entry: &AuditEntry) -> bool {
+        self.user.as_ref().is_none_or(|user| entry.user.eq_ignore_ascii_case(user))
+            && self.section.is_none_or(|section| entry.section == section)
+            && self.from.is_none_or(|from| entry.timestamp >= from)
+            && self.to.is_none_or(|to| entry.timestamp <= to)
+    }
+}
+
+fn get_audit_log_path(cfg: &Config) -> PathBuf {
+    PathBuf::from(&cfg.working_dir).join(FILE_AUDIT_LOG)
+}
+
+fn mask(value: &Value) -> Value {
+    if value.is_null() { Value::Null } else { Value::String(MASKED_VALUE.to_string()) }
+}
+
+/// Credentials in the query of urls like `?username=..&password=..` are masked.
+fn mask_query(value: &Value) -> Value {
+    match value {
+        Value::String(text) => Value::String(mask_sensitive_info(text)),
+        _ => value.clone(),
+    }
+}
+
+fn child_path(path: &str, key: &str) -> String {
+    if path.is_empty() { key.to_string() } else { format!("{path}.{key}") }
+}
+
+/// An added or removed object or list is compared with an empty one, the secret fields inside stay masked.
+fn collect_changes(path: &str, masked: bool, old: &Value, new: &Value, changes: &mut Vec<AuditChange>) {
+    match (old, new) {
+        (Value::Null, Value::Object(_)) => collect_changes(path, masked, &Value::Object(Map::new()), new, changes),
+        (Value::Object(_), Value::Null) => collect_changes(path, masked, old, &Value::Object(Map::new()), changes),
+        (Value::Null, Value::Array(_)) => collect_changes(path, masked, &Value::Array(vec![]), new, changes),
+        (Value::Array(_), Value::Null) => collect_changes(path, masked, old, &Value::Array(vec![]), changes),
+        (Value::Object(old_map), Value::Object(new_map)) => {
+            for (key, old_value) in old_map {
+                let masked = masked || MASKED_FIELDS.contains(&key.as_str());
+                collect_changes(&child_path(path, key), masked, old_value, new_map.get(key).unwrap_or(&Value::Null), changes);
+            }
+            for (key, new_value) in new_map.iter().filter(|(key, _)| !old_map.contains_key(*key)) {
+                let masked = masked || MASKED_FIELDS.contains(&key.as_str());
+                collect_changes(&child_path(path, key), masked, &Value::Null, new_value, changes);
+            }
+        }
+        (Value::Array(old_list), Value::Array(new_list)) => {
+            for index in 0..old_list.len().max(new_list.len()) {
+                collect_changes(&format!("{path}[{index}]"), masked,
+                                old_list.get(index).unwrap_or(&Value::Null), new_list.get(index).unwrap_or(&Value::Null), changes);
+            }
+        }
+        _ if old != new => {
+            let (old, new) = if masked { (mask(old), mask(new)) } else { (mask_query(old), mask_query(new)) };
+            changes.push(AuditChange { path: path.to_string(), old, new });
+        }
+        _ => {}
+    }
+}
+
+/// The changed leaf values between two json documents. Values of secret fields and credentials in urls are masked.
+pub fn audit_diff(old: &Value, new: &Value) -> Vec<AuditChange> {
+    let mut changes = vec![];
+    collect_changes("", false, old, new, &mut changes);
+    changes
+}
+
+/// Appends the entry to the audit log, entries without changes are not written.
+pub fn audit_log_append(cfg: &Config, entry: &AuditEntry) -> Result<(), M3uFilterError> {
+    if entry.changes.is_empty() {
+        return Ok(());
+    }
+    let path = get_audit_log_path(cfg);
+    let _file_lock = cfg.file_locks.write_lock(&path).map_err(|err| M3uFilterError::new(M3uFilterErrorKind::Info, format!("{err}")))?;
+    let line = serde_json::to_string(entry).map_err(|err| M3uFilterError::new(M3uFilterErrorKind::Info, format!("{err}")))?;
+    let result = OpenOptions::new().create(true).append(true).open(&path)
+        .and_then(|mut file| writeln!(file, "{line}"));
+    if let Err(err) = result {
+        return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "Failed to write audit log {}: {err}", path.to_str().unwrap_or("?"));
+    }
+    Ok(())
+}
+
+/// The matching entries of the audit log, the latest first.
+pub fn audit_log_query(cfg: &Config, query: &AuditQuery) -> Vec<AuditEntry> {
+    let path = get_audit_log_path(cfg);
+    let Ok(_file_lock) = cfg.file_locks.read_lock(&path) else {
+        return vec![];
+    };
+    let Ok(file) = File::open(&path) else {
+        return vec![];
+    };
+    let mut entries: Vec<AuditEntry> = BufReader::new(file).lines().map_while(Result::ok)
+        .filter_map(|line| serde_json::from_str(&line).ok())
+        .filter(|entry| query.matches(entry))
+        .collect();
+    entries.reverse();
+    if let Some(limit) = query.limit {
+        entries.truncate(limit);
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::repository::audit_repository::{audit_diff, AuditChange};
+
+    #[test]
+    fn test_audit_diff() {
+        let old = json!({"api": {"port": 8901}, "users": [{"username": "a", "password": "old"}], "schedule": "0 0 * * *",
+            "url": "http://provider/get.php?username=a&password=old&type=m3u"});
+        let new = json!({"api": {"port": 8902}, "users": [{"username": "a", "password": "new"}, {"username": "b", "password": "x"}],
+            "url": "http://provider/get.php?username=a&password=new&type=m3u"});
+        let change = |path: &str, old, new| AuditChange { path: path.to_string(), old, new };
+        assert_eq!(audit_diff(&old, &new), vec![
+            change("api.port", json!(8901), json!(8902)),
+            change("schedule", json!("0 0 * * *"), json!(null)),
+            change("url", json!("http://provider/get.php?username=***&password=***&type=m3u"), json!("http://provider/get.php?username=***&password=***&type=m3u")),
+            change("users[0].password", json!("***"), json!("***")),
+            change("users[1].password", json!(null), json!("***")),
+            change("users[1].username", json!(null), json!("b")),
+        ]);
+        assert!(audit_diff(&old, &old).is_empty());
+    }
+}
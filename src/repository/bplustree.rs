@@ -9,6 +9,8 @@ use flate2::Compression;
 use log::error;
 use serde::{Deserialize, Serialize};
 
+use crate::utils::file_utils;
+
 const BINCODE_OVERHEAD: usize = 4;
 const BLOCK_SIZE: usize = 4096;
 const POINTER_SIZE: usize = size_of::<Option<u64>>();
@@ -402,12 +404,15 @@ where
 
     pub fn store(&mut self, filepath: &Path) -> io::Result<u64> {
         if self.dirty {
-            let mut file = BufWriter::new(OpenOptions::new().write(true).create(true).truncate(true).open(filepath)?);
+            let temp_path = file_utils::get_temp_path(filepath);
+            let mut file = BufWriter::new(OpenOptions::new().write(true).create(true).truncate(true).open(&temp_path)?);
             let mut buffer = vec![0u8; BLOCK_SIZE];
-            let result = self.root.serialize_to_block(&mut file, &mut buffer, 0u64);
+            let result = self.root.serialize_to_block(&mut file, &mut buffer, 0u64)?;
             file.flush()?;
+            drop(file);
+            file_utils::rename_atomic(&temp_path, filepath)?;
             self.dirty = false;
-            result
+            Ok(result)
         } else {
             Ok(0)
         }
@@ -1,48 +1,73 @@
-use std::fs::File;
 use std::io::{Cursor, Write};
 use std::path::{Path};
+use std::rc::Rc;
 use log::{debug, log_enabled, Level};
 use quick_xml::{Writer};
 use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorKind};
 use crate::model::config::{Config, ConfigTarget, TargetOutput};
 use crate::model::config::TargetType;
-use crate::model::xmltv::{Epg};
+use crate::model::xmltv::{Epg, XmlTag, EPG_ATTRIB_SRC, EPG_TAG_CHANNEL, EPG_TAG_ICON};
+use crate::processing::logo_cache::LogoRewrite;
 use crate::repository::m3u_repository::{m3u_get_epg_file_path};
 use crate::repository::xtream_repository::{xtream_get_epg_file_path, xtream_get_storage_path};
+use crate::utils::file_lock_manager::FileTransaction;
+use crate::utils::file_utils;
 
-fn epg_write_file(target: &ConfigTarget, epg: &Epg, path: &Path) -> Result<(), M3uFilterError> {
+/// Returns the channel tag with the `<icon>` urls rewritten to the logo cache, `None` if nothing is rewritten.
+fn epg_rewrite_channel_icons(tag: &XmlTag, logo_rewrite: &LogoRewrite) -> Option<XmlTag> {
+    if tag.name != EPG_TAG_CHANNEL {
+        return None;
+    }
+    let children = tag.children.as_ref()?;
+    let mut rewritten = false;
+    let children = children.iter().map(|child| {
+        let logo = (child.name == EPG_TAG_ICON).then(|| child.get_attribute_value(EPG_ATTRIB_SRC)).flatten().and_then(|src| logo_rewrite.rewrite(src));
+        match (logo, child.attributes.as_ref()) {
+            (Some(logo), Some(attributes)) => {
+                rewritten = true;
+                let mut attributes = attributes.as_ref().clone();
+                attributes.insert(EPG_ATTRIB_SRC.to_string(), logo);
+                Rc::new(XmlTag { attributes: Some(Rc::new(attributes)), ..child.as_ref().clone() })
+            }
+            _ => Rc::clone(child),
+        }
+    }).collect();
+    rewritten.then(|| XmlTag { children: Some(children), ..tag.clone() })
+}
+
+/// Writes the epg as xmltv file, the channel icons are rewritten with the `logo_rewrite`.
+pub fn epg_write_xml_file(epg: &Epg, path: &Path, logo_rewrite: Option<&LogoRewrite>) -> Result<(), M3uFilterError> {
     let mut writer = Writer::new(Cursor::new(vec![]));
-    match epg.write_to(&mut writer) {
+    let written = match logo_rewrite {
+        Some(logo_rewrite) => epg.write_mapped_to(&mut writer, |tag| epg_rewrite_channel_icons(tag, logo_rewrite)),
+        None => epg.write_to(&mut writer),
+    };
+    match written {
         Ok(()) => {
             let result = writer.into_inner().into_inner();
-            match File::create(path) {
-                Ok(mut epg_file) => {
-                    match epg_file.write_all("<?xml version=\"1.0\" encoding=\"utf-8\" ?><!DOCTYPE tv SYSTEM \"xmltv.dtd\">".as_bytes()) {
-                        Ok(()) => {}
-                        Err(err) => return Err(M3uFilterError::new(
-                            M3uFilterErrorKind::Notify, format!("failed to write epg: {} - {}", path.to_str().unwrap_or("?"), err))),
-                    }
-                    match epg_file.write_all(&result) {
-                        Ok(()) => {
-                            if log_enabled!(Level::Debug) {
-                                debug!("Epg for target {} written to {}", target.name, path.to_str().unwrap_or("?"));
-                            }
-                        }
-                        Err(err) => return Err(M3uFilterError::new(
-                            M3uFilterErrorKind::Notify, format!("failed to write epg: {} - {}", path.to_str().unwrap_or("?"), err))),
-                    }
-                }
-                Err(err) => return Err(M3uFilterError::new(
+            match file_utils::write_atomic(path, |epg_file| {
+                epg_file.write_all("<?xml version=\"1.0\" encoding=\"utf-8\" ?><!DOCTYPE tv SYSTEM \"xmltv.dtd\">".as_bytes())?;
+                epg_file.write_all(&result)
+            }) {
+                Ok(()) => Ok(()),
+                Err(err) => Err(M3uFilterError::new(
                     M3uFilterErrorKind::Notify, format!("failed to write epg: {} - {}", path.to_str().unwrap_or("?"), err))),
             }
         }
-        Err(err) => return Err(M3uFilterError::new(
+        Err(err) => Err(M3uFilterError::new(
             M3uFilterErrorKind::Notify, format!("failed to write epg: {} - {}", path.to_str().unwrap_or("?"), err))),
     }
+}
+
+pub fn epg_write_file(target: &ConfigTarget, cfg: &Config, epg: &Epg, path: &Path) -> Result<(), M3uFilterError> {
+    epg_write_xml_file(epg, path, LogoRewrite::for_files(cfg).as_ref())?;
+    if log_enabled!(Level::Debug) {
+        debug!("Epg for target {} written to {}", target.name, path.to_str().unwrap_or("?"));
+    }
     Ok(())
 }
 
-pub fn epg_write(target: &ConfigTarget, cfg: &Config, target_path: &Path, epg: Option<&Epg>, output: &TargetOutput) -> Result<(), M3uFilterError> {
+pub fn epg_write(target: &ConfigTarget, cfg: &Config, target_path: &Path, epg: Option<&Epg>, output: &TargetOutput, transaction: &mut FileTransaction) -> Result<(), M3uFilterError> {
     if let Some(epg_data) = epg {
         match &output.target {
             TargetType::M3u => {
@@ -55,7 +80,7 @@ pub fn epg_write(target: &ConfigTarget, cfg: &Config, target_path: &Path, epg: O
                 if log_enabled!(Level::Debug) {
                     debug!("writing m3u epg to {}", path.to_str().unwrap_or("?"));
                 }
-                epg_write_file(target, epg_data, &path)?;
+                epg_write_file(target, cfg, epg_data, &transaction.stage(&path))?;
             }
             TargetType::Xtream => {
                 match xtream_get_storage_path(cfg, &target.name) {
@@ -64,14 +89,14 @@ pub fn epg_write(target: &ConfigTarget, cfg: &Config, target_path: &Path, epg: O
                         if log_enabled!(Level::Debug) {
                             debug!("writing xtream epg to {}", epg_path.to_str().unwrap_or("?"));
                         }
-                        epg_write_file(target, epg_data, &epg_path)?;
+                        epg_write_file(target, cfg, epg_data, &transaction.stage(&epg_path))?;
                     }
                     None => return Err(M3uFilterError::new(
                         M3uFilterErrorKind::Notify,
                         format!("failed to serialize epg for target: {}, storage path not found", target.name))),
                 }
             }
-            TargetType::Strm => {}
+            TargetType::Strm | TargetType::Report | TargetType::Library => {}
         }
     }
     Ok(())
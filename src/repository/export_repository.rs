@@ -0,0 +1,162 @@
+use std::io::Write;
+use std::str::FromStr;
+
+use serde_json::{Map, Value};
+
+use crate::create_m3u_filter_error_result;
+use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorKind};
+use crate::model::config::Config;
+use crate::repository::search_repository::{search_index_for_each, SearchEntry};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ExportFormat {
+    Json,
+    Csv,
+}
+
+impl ExportFormat {
+    pub const fn content_type(self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::Csv => "text/csv",
+        }
+    }
+
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Csv => "csv",
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = M3uFilterError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "csv" => Ok(Self::Csv),
+            _ => create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "Unknown export format {value}, supported are json and csv"),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ExportColumn {
+    Name,
+    Group,
+    Url,
+    EpgId,
+    Type,
+}
+
+impl ExportColumn {
+    pub const ALL: [Self; 5] = [Self::Name, Self::Group, Self::Url, Self::EpgId, Self::Type];
+
+    const fn header(self) -> &'static str {
+        match self {
+            Self::Name => "name",
+            Self::Group => "group",
+            Self::Url => "url",
+            Self::EpgId => "epg_id",
+            Self::Type => "type",
+        }
+    }
+
+    fn value(self, entry: &SearchEntry) -> String {
+        match self {
+            Self::Name => entry.name.clone(),
+            Self::Group => entry.group.clone(),
+            Self::Url => entry.url.clone(),
+            Self::EpgId => entry.epg_channel_id.clone(),
+            Self::Type => entry.item_type.to_string(),
+        }
+    }
+
+    /// Parses a comma separated column list like `name,group,url`, all columns if empty.
+    pub fn parse_list(value: &str) -> Result<Vec<Self>, M3uFilterError> {
+        let columns = value.split(',').map(str::trim).filter(|column| !column.is_empty())
+            .map(|column| Self::ALL.into_iter().find(|col| col.header().eq_ignore_ascii_case(column))
+                .ok_or_else(|| M3uFilterError::new(M3uFilterErrorKind::Info, format!("Unknown export column {column}"))))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(if columns.is_empty() { Self::ALL.to_vec() } else { columns })
+    }
+}
+
+fn csv_escape(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn write_entry<W: Write>(writer: &mut W, format: ExportFormat, columns: &[ExportColumn], entry: &SearchEntry, first: bool) -> std::io::Result<()> {
+    match format {
+        ExportFormat::Csv => {
+            let line = columns.iter().map(|column| csv_escape(&column.value(entry))).collect::<Vec<_>>().join(",");
+            writeln!(writer, "{line}")
+        }
+        ExportFormat::Json => {
+            let doc: Map<String, Value> = columns.iter().map(|column| (column.header().to_string(), Value::String(column.value(entry)))).collect();
+            if !first {
+                writer.write_all(b",")?;
+            }
+            serde_json::to_writer(&mut *writer, &doc).map_err(std::io::Error::other)
+        }
+    }
+}
+
+/// Writes the processed playlist of the target with the selected columns, `false` if the target was not processed yet.
+pub fn playlist_export<W: Write>(cfg: &Config, target_name: &str, format: ExportFormat, columns: &[ExportColumn], writer: &mut W) -> Result<bool, M3uFilterError> {
+    let to_error = |err: std::io::Error| M3uFilterError::new(M3uFilterErrorKind::Info, format!("Failed to export playlist {target_name}: {err}"));
+    match format {
+        ExportFormat::Csv => writeln!(writer, "{}", columns.iter().map(|column| column.header()).collect::<Vec<_>>().join(",")),
+        ExportFormat::Json => writer.write_all(b"["),
+    }.map_err(to_error)?;
+    let mut first = true;
+    let exists = search_index_for_each(cfg, target_name, |entry| {
+        write_entry(writer, format, columns, &entry, first).map_err(to_error)?;
+        first = false;
+        Ok(())
+    })?;
+    if format == ExportFormat::Json {
+        writer.write_all(b"]").map_err(to_error)?;
+    }
+    writer.flush().map_err(to_error)?;
+    Ok(exists)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::playlist::{PlaylistItemType, XtreamCluster};
+    use crate::repository::export_repository::{write_entry, ExportColumn, ExportFormat};
+    use crate::repository::search_repository::SearchEntry;
+
+    #[test]
+    fn test_export_entry() {
+        let entry = SearchEntry {
+            virtual_id: 1,
+            uuid: String::new(),
+            xtream_cluster: XtreamCluster::Live,
+            item_type: PlaylistItemType::Live,
+            name: "News, \"HD\"".to_string(),
+            title: String::new(),
+            group: "News".to_string(),
+            logo: String::new(),
+            url: "http://localhost/1.ts".to_string(),
+            epg_channel_id: "news.us".to_string(),
+            input_id: 1,
+        };
+        let columns = ExportColumn::parse_list("name, group,epg_id,type").unwrap();
+        let mut csv = vec![];
+        write_entry(&mut csv, ExportFormat::Csv, &columns, &entry, true).unwrap();
+        assert_eq!(String::from_utf8(csv).unwrap(), "\"News, \"\"HD\"\"\",News,news.us,live\n");
+        let mut json = vec![];
+        write_entry(&mut json, ExportFormat::Json, &[ExportColumn::Name, ExportColumn::Url], &entry, false).unwrap();
+        assert_eq!(String::from_utf8(json).unwrap(), r#",{"name":"News, \"HD\"","url":"http://localhost/1.ts"}"#);
+        assert_eq!(ExportColumn::parse_list("").unwrap().len(), 5);
+        assert!(ExportColumn::parse_list("name,logo").is_err());
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use log::error;
+
+use crate::create_m3u_filter_error_result;
+use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorKind};
+use crate::model::config::Config;
+use crate::repository::bplustree::BPlusTreeQuery;
+use crate::repository::storage::{ensure_target_storage_path, get_target_id_mapping_file, hex_decode_hash, hex_encode};
+use crate::repository::target_id_mapping::{TargetIdMapping, VirtualIdRecord};
+use crate::utils::json_utils::json_write_documents_to_file;
+
+const FILE_FAVORITES: &str = "favorites.json";
+
+/// The name of the generated category with the favorites of a user.
+pub const FAVORITES_GROUP: &str = "Favorites";
+/// The xtream category id of the favorites, the ids of the regular categories are counted from 1.
+pub const FAVORITES_CATEGORY_ID: u32 = 999_999;
+
+/// Favorite channel uuids (hex encoded hash of the url) by username, in the order they were added.
+pub type Favorites = BTreeMap<String, Vec<String>>;
+
+fn get_favorites_path(cfg: &Config, target_name: &str) -> Result<PathBuf, M3uFilterError> {
+    ensure_target_storage_path(cfg, target_name).map(|path| path.join(FILE_FAVORITES))
+}
+
+fn read_favorites(path: &Path) -> Favorites {
+    let Ok(file) = File::open(path) else {
+        return Favorites::new();
+    };
+    serde_json::from_reader(BufReader::new(file)).unwrap_or_else(|err| {
+        error!("Failed to read favorites {}: {err}", path.to_str().unwrap_or("?"));
+        Favorites::new()
+    })
+}
+
+/// Adds the uuid to the end of the favorites of the user or removes it, returns `true` if the favorites changed.
+fn toggle_favorite(favorites: &mut Favorites, username: &str, uuid: &str, favorite: bool) -> bool {
+    let user_favorites = favorites.entry(username.to_string()).or_default();
+    let position = user_favorites.iter().position(|item| item == uuid);
+    let changed = match (position, favorite) {
+        (None, true) => {
+            user_favorites.push(uuid.to_string());
+            true
+        }
+        (Some(index), false) => {
+            user_favorites.remove(index);
+            true
+        }
+        _ => false,
+    };
+    if user_favorites.is_empty() {
+        favorites.remove(username);
+    }
+    changed
+}
+
+/// The favorite channel uuids of the user.
+pub fn favorites_load(cfg: &Config, target_name: &str, username: &str) -> Vec<String> {
+    let Ok(path) = get_favorites_path(cfg, target_name) else {
+        return vec![];
+    };
+    if !path.exists() {
+        return vec![];
+    }
+    match cfg.file_locks.read_lock(&path) {
+        Ok(_file_lock) => read_favorites(&path).remove(username).unwrap_or_default(),
+        Err(_) => vec![],
+    }
+}
+
+/// Marks the channel with the virtual id as favorite of the user or removes the mark.
+/// The channel is stored with its uuid, so the favorite survives a playlist update.
+pub fn favorites_update(cfg: &Config, target_name: &str, username: &str, virtual_id: u32, favorite: bool) -> Result<(), M3uFilterError> {
+    let target_path = ensure_target_storage_path(cfg, target_name)?;
+    let uuid = {
+        let target_id_mapping_file = get_target_id_mapping_file(&target_path);
+        let _file_lock = cfg.file_locks.read_lock(&target_id_mapping_file).map_err(|err| M3uFilterError::new(M3uFilterErrorKind::Info, format!("{err}")))?;
+        let mut target_id_mapping = BPlusTreeQuery::<u32, VirtualIdRecord>::try_new(&target_id_mapping_file)
+            .map_err(|err| M3uFilterError::new(M3uFilterErrorKind::Info, format!("Could not load id mapping for target {target_name} err:{err}")))?;
+        match target_id_mapping.query(&virtual_id) {
+            Some(record) => hex_encode(&record.uuid),
+            None => return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "Could not find channel {virtual_id} for target {target_name}"),
+        }
+    };
+    let path = target_path.join(FILE_FAVORITES);
+    let _file_lock = cfg.file_locks.write_lock(&path).map_err(|err| M3uFilterError::new(M3uFilterErrorKind::Info, format!("{err}")))?;
+    let mut favorites = read_favorites(&path);
+    if toggle_favorite(&mut favorites, username, &uuid, favorite) {
+        if let Err(err) = json_write_documents_to_file(&path, &favorites) {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "Failed to write favorites {}: {err}", path.to_str().unwrap_or("?"));
+        }
+    }
+    Ok(())
+}
+
+/// The current virtual ids of the favorites of the user, channels which are not known anymore are skipped.
+pub fn favorites_get_virtual_ids(cfg: &Config, target_name: &str, username: &str) -> Vec<u32> {
+    let favorites = favorites_load(cfg, target_name, username);
+    if favorites.is_empty() {
+        return vec![];
+    }
+    let Ok(target_path) = ensure_target_storage_path(cfg, target_name) else {
+        return vec![];
+    };
+    let target_id_mapping_file = get_target_id_mapping_file(&target_path);
+    let Ok(_file_lock) = cfg.file_locks.read_lock(&target_id_mapping_file) else {
+        return vec![];
+    };
+    let target_id_mapping = TargetIdMapping::new(&target_id_mapping_file);
+    favorites.iter()
+        .filter_map(|uuid| hex_decode_hash(uuid))
+        .filter_map(|uuid| target_id_mapping.get_virtual_id(&uuid))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::repository::favorites_repository::{toggle_favorite, Favorites};
+
+    #[test]
+    fn test_toggle_favorite() {
+        let mut favorites = Favorites::new();
+        assert!(toggle_favorite(&mut favorites, "alice", "A1", true));
+        assert!(toggle_favorite(&mut favorites, "alice", "B2", true));
+        assert!(!toggle_favorite(&mut favorites, "alice", "A1", true));
+        assert!(toggle_favorite(&mut favorites, "bob", "B2", true));
+        assert_eq!(favorites.get("alice"), Some(&vec!["A1".to_string(), "B2".to_string()]));
+        assert!(toggle_favorite(&mut favorites, "bob", "B2", false));
+        assert!(!toggle_favorite(&mut favorites, "bob", "B2", false));
+        assert!(!favorites.contains_key("bob"));
+    }
+}
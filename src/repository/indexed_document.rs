@@ -67,30 +67,37 @@ impl IndexedDocument {
  */
 pub(in crate::repository) struct IndexedDocumentWriter {
     main_path: PathBuf,
+    /// `None` in append mode, the records are appended to the main file.
+    temp_path: Option<PathBuf>,
     index_path: PathBuf,
     main_file: File,
     main_offset: OffsetPointer,
+    /// The records before this offset are referenced by the stored index and are not overwritten.
+    stored_offset: OffsetPointer,
     index_tree: BPlusTree<u32, OffsetPointer>,
     dirty: bool,
     fragmented: bool,
 }
 
 impl IndexedDocumentWriter {
+    /// A new document is written to a temp file which replaces the main file on store.
+    /// In append mode the records are only appended to the existing main file, changed records are appended too.
+    /// The records of the current index stay untouched, readers see the new records once the index is replaced on store.
     fn new_with_mode(main_path: PathBuf, index_path: PathBuf, append: bool) -> Result<Self, Error> {
         let append_mode = append && main_path.exists();
-        let mut main_file = if append_mode {
-            OpenOptions::new()
+        let temp_path = (!append_mode).then(|| file_utils::get_temp_path(&main_path));
+        let mut main_file = match &temp_path {
+            None => OpenOptions::new()
                 .read(true)
                 .write(true)
                 .truncate(false)
-                .open(&main_path)
-        } else {
-            OpenOptions::new()
+                .open(&main_path),
+            Some(path) => OpenOptions::new()
                 .read(true)
                 .write(true)
                 .create(true)
                 .truncate(true)
-                .open(&main_path)
+                .open(path),
         }?;
 
         // Retrieve file size and convert to `u32` for `main_offset`, if possible
@@ -119,11 +126,13 @@ impl IndexedDocumentWriter {
 
         Ok(Self {
             main_path,
+            temp_path,
             index_path,
             main_file,
             main_offset,
+            stored_offset: if append_mode { main_offset } else { 0 },
             index_tree,
-            dirty: false,
+            dirty: true,
             fragmented,
         })
     }
@@ -140,6 +149,10 @@ impl IndexedDocumentWriter {
         if self.dirty {
             self.dirty = false;
             self.main_file.flush()?;
+            // swap the content file first, then the index
+            if let Some(temp_path) = self.temp_path.as_ref().filter(|path| path.exists()) {
+                file_utils::rename_atomic(temp_path, &self.main_path)?;
+            }
             self.index_tree.store(&self.index_path).map(|_| ())
         } else {
             Ok(())
@@ -164,7 +177,8 @@ impl IndexedDocumentWriter {
                 }
             }
 
-            if encoded_bytes.len() > size {
+            // in append mode the records of the stored index are not overwritten
+            if encoded_bytes.len() > size || offset < self.stored_offset {
                 // does not fit we need to append, file is fragmented
                 if !self.fragmented {
                     self.fragmented = true;
@@ -418,11 +432,8 @@ impl IndexedDocumentGarbageCollector {
             self.index_tree.store(&gc_index_path)?;
         }
 
-        let _ = std::fs::remove_file(&self.main_path);
-        let _ = std::fs::remove_file(&self.index_path);
-
-        std::fs::rename(&gc_main_path, &self.main_path)?;
-        std::fs::rename(&gc_index_path, &self.index_path)?;
+        file_utils::rename_atomic(&gc_main_path, &self.main_path)?;
+        file_utils::rename_atomic(&gc_index_path, &self.index_path)?;
 
         Ok(())
     }
@@ -436,6 +447,8 @@ mod tests {
     use serde::{Deserialize, Serialize};
 
     use crate::repository::indexed_document::{IndexedDocumentGarbageCollector, IndexedDocumentReader, IndexedDocumentWriter};
+    use crate::utils::file_utils;
+    use crate::utils::test_utils::create_temp_dir;
 
     // Example usage with a simple struct
     #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
@@ -450,6 +463,8 @@ mod tests {
         let index_path = PathBuf::from("/tmp/main.iw.idx");
         {
             let mut idw = IndexedDocumentWriter::new(main_path.clone(), index_path.clone())?;
+            // until stored, the writer works on the temp file
+            let temp_path = file_utils::get_temp_path(&main_path);
 
             for i in 0u32..=500 {
                 idw.write_doc(i, &Record {
@@ -458,7 +473,7 @@ mod tests {
                 })?;
             }
 
-            let size_main_file_1 = std::fs::metadata(&main_path)?.len();
+            let size_main_file_1 = std::fs::metadata(&temp_path)?.len();
 
             // update same block, file size should not increase
             for i in 0u32..=500 {
@@ -468,7 +483,7 @@ mod tests {
                 })?;
             }
 
-            let size_main_file_2 = std::fs::metadata(&main_path)?.len();
+            let size_main_file_2 = std::fs::metadata(&temp_path)?.len();
             assert_eq!(size_main_file_1, size_main_file_2, "Failed, the filesize should be the same");
 
             // fragmentation
@@ -479,7 +494,7 @@ mod tests {
                 })?;
             }
 
-            let size_main_file_3 = std::fs::metadata(&main_path)?.len();
+            let size_main_file_3 = std::fs::metadata(&temp_path)?.len();
             assert!(size_main_file_1 < size_main_file_3, "Failed, the filesize should be greater");
 
             idw.store()?;
@@ -506,4 +521,34 @@ mod tests {
 
         Ok(())
     }
-}
\ No newline at end of file
+    #[test]
+    fn append_test() -> io::Result<()> {
+        let temp_dir = create_temp_dir("indexed_append");
+        let dir = temp_dir.path();
+        let main_path = dir.join("main.iw");
+        let index_path = dir.join("main.iw.idx");
+        let record = |i: u32, data: &str| Record { id: i, data: data.to_string() };
+        {
+            let mut idw = IndexedDocumentWriter::new(main_path.clone(), index_path.clone())?;
+            for i in 1u32..=10 {
+                idw.write_doc(i, &record(i, "old"))?;
+            }
+            idw.store()?;
+        }
+        let size_before = std::fs::metadata(&main_path)?.len();
+        {
+            let mut idw = IndexedDocumentWriter::new_append(main_path.clone(), index_path.clone())?;
+            idw.write_doc(5, &record(5, "new"))?;
+            idw.write_doc(11, &record(11, "added"))?;
+            // the main file is not copied, the stored index still reads the old record
+            assert!(!file_utils::get_temp_path(&main_path).exists());
+            assert_eq!(IndexedDocumentReader::<Record>::read_indexed_item(&main_path, &index_path, 5)?.data, "old");
+            idw.store()?;
+        }
+        assert!(std::fs::metadata(&main_path)?.len() > size_before);
+        assert_eq!(IndexedDocumentReader::<Record>::read_indexed_item(&main_path, &index_path, 5)?.data, "new");
+        assert_eq!(IndexedDocumentReader::<Record>::read_indexed_item(&main_path, &index_path, 11)?.data, "added");
+        assert_eq!(IndexedDocumentReader::<Record>::read_indexed_item(&main_path, &index_path, 1)?.data, "old");
+        Ok(())
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::model::config::ItemCacheConfig;
+use crate::model::playlist::XtreamCluster;
+
+/// A stored item, `scope` is the document file of m3u items or the xtream storage of xtream items.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ItemCacheKey {
+    scope: PathBuf,
+    virtual_id: u32,
+    cluster: Option<XtreamCluster>,
+}
+
+impl ItemCacheKey {
+    pub fn new(scope: &Path, virtual_id: u32, cluster: Option<XtreamCluster>) -> Self {
+        Self { scope: scope.to_path_buf(), virtual_id, cluster }
+    }
+}
+
+#[derive(Debug, Default)]
+struct LruEntries {
+    /// last use and the bincode encoded item
+    items: HashMap<ItemCacheKey, (u64, Vec<u8>)>,
+    /// keys by last use, the first key is evicted first
+    order: BTreeMap<u64, ItemCacheKey>,
+    tick: u64,
+    bytes: usize,
+}
+
+impl LruEntries {
+    fn touch(&mut self, key: &ItemCacheKey) -> Option<&[u8]> {
+        self.tick += 1;
+        let tick = self.tick;
+        let (last_use, content) = self.items.get_mut(key)?;
+        self.order.remove(last_use);
+        *last_use = tick;
+        self.order.insert(tick, key.clone());
+        Some(content)
+    }
+
+    fn insert(&mut self, key: ItemCacheKey, content: Vec<u8>) {
+        self.tick += 1;
+        self.bytes += content.len();
+        self.order.insert(self.tick, key.clone());
+        if let Some((last_use, old_content)) = self.items.insert(key, (self.tick, content)) {
+            self.order.remove(&last_use);
+            self.bytes -= old_content.len();
+        }
+    }
+
+    fn evict(&mut self, max_entries: usize, max_bytes: usize) {
+        while self.items.len() > max_entries || (max_bytes > 0 && self.bytes > max_bytes) {
+            let Some((_, key)) = self.order.pop_first() else { break };
+            if let Some((_, content)) = self.items.remove(&key) {
+                self.bytes -= content.len();
+            }
+        }
+    }
+
+    fn retain<F: Fn(&ItemCacheKey) -> bool>(&mut self, keep: F) {
+        self.items.retain(|key, _| keep(key));
+        self.order.retain(|_, key| keep(key));
+        self.bytes = self.items.values().map(|(_, content)| content.len()).sum();
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ItemCacheStats {
+    pub enabled: bool,
+    pub entries: usize,
+    pub bytes: usize,
+    pub max_entries: usize,
+    pub max_bytes: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub hit_ratio: f64,
+}
+
+/// Least recently used stored items for the stream requests, shared by all workers.
+/// The items of a target are removed when the target is refreshed.
+#[derive(Debug, Default)]
+pub struct ItemCache {
+    max_entries: usize,
+    max_bytes: usize,
+    entries: Mutex<LruEntries>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl ItemCache {
+    pub fn new(cfg: Option<&ItemCacheConfig>) -> Self {
+        let (max_entries, max_bytes) = cfg.filter(|cfg| cfg.enabled).map_or((0, 0), |cfg| (cfg.max_entries, cfg.max_bytes));
+        Self { max_entries, max_bytes, ..Self::default() }
+    }
+
+    pub const fn is_enabled(&self) -> bool {
+        self.max_entries > 0
+    }
+
+    pub fn get<T: DeserializeOwned>(&self, key: &ItemCacheKey) -> Option<T> {
+        if !self.is_enabled() {
+            return None;
+        }
+        let item = self.entries.lock().ok()?.touch(key).and_then(|content| bincode::deserialize::<T>(content).ok());
+        let counter = if item.is_some() { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        item
+    }
+
+    pub fn put<T: Serialize>(&self, key: ItemCacheKey, item: &T) {
+        if !self.is_enabled() {
+            return;
+        }
+        if let (Ok(content), Ok(mut entries)) = (bincode::serialize(item), self.entries.lock()) {
+            entries.insert(key, content);
+            entries.evict(self.max_entries, self.max_bytes);
+        }
+    }
+
+    /// Removes the items stored below the path, called when the files of a target are replaced.
+    pub fn invalidate(&self, path: &Path) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.retain(|key| !key.scope.starts_with(path));
+        }
+    }
+
+    pub fn get_stats(&self) -> ItemCacheStats {
+        let (entries, bytes) = self.entries.lock().map_or((0, 0), |entries| (entries.items.len(), entries.bytes));
+        let hits = self.hits.load(Ordering::Relaxed);
+        let misses = self.misses.load(Ordering::Relaxed);
+        #[allow(clippy::cast_precision_loss)]
+        let hit_ratio = if hits + misses == 0 { 0.0 } else { hits as f64 / (hits + misses) as f64 };
+        ItemCacheStats { enabled: self.is_enabled(), entries, bytes, max_entries: self.max_entries, max_bytes: self.max_bytes, hits, misses, hit_ratio }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::model::config::ItemCacheConfig;
+    use crate::repository::item_cache::{ItemCache, ItemCacheKey};
+
+    #[test]
+    fn test_item_cache() {
+        let cache = ItemCache::new(Some(&ItemCacheConfig { enabled: true, max_entries: 2, max_bytes: 0 }));
+        let key = |target: &str, virtual_id| ItemCacheKey::new(&Path::new("/data").join(target).join("m3u.db"), virtual_id, None);
+        cache.put(key("tv", 1), &"one".to_string());
+        cache.put(key("tv", 2), &"two".to_string());
+        assert_eq!(cache.get::<String>(&key("tv", 1)).as_deref(), Some("one"));
+        // the least recently used item is evicted
+        cache.put(key("radio", 3), &"three".to_string());
+        assert!(cache.get::<String>(&key("tv", 2)).is_none());
+        assert_eq!(cache.get::<String>(&key("radio", 3)).as_deref(), Some("three"));
+
+        cache.invalidate(Path::new("/data/tv"));
+        assert!(cache.get::<String>(&key("tv", 1)).is_none());
+        let stats = cache.get_stats();
+        assert_eq!((stats.entries, stats.hits, stats.misses), (1, 2, 2));
+
+        let disabled = ItemCache::new(None);
+        disabled.put(key("tv", 1), &"one".to_string());
+        assert!(disabled.get::<String>(&key("tv", 1)).is_none());
+    }
+}
@@ -0,0 +1,100 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use log::error;
+use serde::{Deserialize, Serialize};
+
+use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorCategory, M3uFilterErrorInfo, M3uFilterErrorKind};
+use crate::model::config::Config;
+use crate::model::playlist::XtreamCluster;
+use crate::utils::json_utils::json_write_documents_to_file;
+
+const FILE_JOBS: &str = "jobs.json";
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    pub const fn is_finished(self) -> bool {
+        matches!(self, Self::Completed | Self::Failed | Self::Cancelled)
+    }
+}
+
+/// What created the job.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobTrigger {
+    Api,
+    Schedule,
+    Boot,
+    /// a file of a watched directory input changed
+    Watch,
+    /// corrupted playlist files were found at startup, the target is refreshed from the provider
+    Integrity,
+}
+
+/// Progress in percent of each processing stage.
+#[derive(Debug, Copy, Clone, Default, Serialize, Deserialize)]
+pub struct JobStages {
+    pub download: u8,
+    pub parse: u8,
+    pub filter: u8,
+    pub write: u8,
+}
+
+/// A processing run, `created`, `started` and `finished` are unix timestamps.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub id: String,
+    pub trigger: JobTrigger,
+    /// target names, `None` processes the targets given at startup
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub targets: Option<Vec<String>>,
+    /// only the listed clusters are refreshed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clusters: Option<Vec<XtreamCluster>>,
+    pub status: JobStatus,
+    pub created: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub started: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finished: Option<i64>,
+    #[serde(default)]
+    pub progress: JobStages,
+    #[serde(default)]
+    pub log: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// the errors of the processing
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<M3uFilterErrorInfo>,
+}
+
+pub fn get_jobs_file_path(cfg: &Config) -> PathBuf {
+    PathBuf::from(&cfg.working_dir).join(FILE_JOBS)
+}
+
+pub fn job_load(path: &Path) -> Vec<Job> {
+    let Ok(file) = File::open(path) else {
+        return vec![];
+    };
+    serde_json::from_reader(BufReader::new(file)).unwrap_or_else(|err| {
+        error!("Failed to read jobs {}: {err}", path.to_str().unwrap_or("?"));
+        vec![]
+    })
+}
+
+pub fn job_save(path: &Path, jobs: &[Job]) -> Result<(), M3uFilterError> {
+    json_write_documents_to_file(path, jobs).map_err(|err| {
+        let message = format!("Failed to save jobs: {err}");
+        M3uFilterError::from_source(M3uFilterErrorKind::Info, M3uFilterErrorCategory::Persistence, err, message)
+    })
+}
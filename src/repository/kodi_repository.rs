@@ -1,13 +1,17 @@
-use std::fs::File;
+use std::collections::HashSet;
 use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::sync::LazyLock;
 use chrono::Datelike;
 use log::error;
 use crate::create_m3u_filter_error_result;
 use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorKind};
-use crate::model::config::{Config, ConfigTarget};
-use crate::model::playlist::PlaylistGroup;
+use crate::model::config::{Config, ConfigTarget, TargetOutput};
+use crate::model::playlist::{PlaylistGroup, PlaylistItemHeader};
+use crate::processing::logo_cache::{get_cached_channel_logo_path, get_logo_extension};
+use crate::processing::token_refresh::TokenRewrite;
 use crate::utils::file_utils;
+use crate::utils::filename_template::{get_quality, render_file_path, sanitize_filename};
 
 struct KodiStyle {
     year: regex::Regex,
@@ -16,11 +20,7 @@ struct KodiStyle {
     whitespace: regex::Regex,
 }
 
-fn sanitize_for_filename(text: &str, underscore_whitespace: bool) -> String {
-    text.chars().filter(|c| c.is_alphanumeric() || c.is_whitespace())
-        .map(|c| if underscore_whitespace { if c.is_whitespace() { '_' } else { c } } else { c })
-        .collect::<String>()
-}
+const DEFAULT_STRM_TEMPLATE: &str = "{group}/{title}";
 
 fn kodi_style_rename_year(name: &String, style: &KodiStyle) -> (String, Option<String>) {
     let current_date = chrono::Utc::now();
@@ -76,50 +76,180 @@ static KODY_STYLE: LazyLock<KodiStyle> = LazyLock::new(|| KodiStyle {
 });
 
 
-pub fn kodi_write_strm_playlist(target: &ConfigTarget, cfg: &Config, new_playlist: &[PlaylistGroup], filename: Option<&String>) -> Result<(), M3uFilterError> {
+/// The local artwork next to a strm file, `<name>-thumb.<ext>`.
+const KODI_THUMB_SUFFIX: &str = "-thumb";
+
+fn is_kodi_output_file(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "strm")
+        || path.file_stem().is_some_and(|stem| stem.to_string_lossy().ends_with(KODI_THUMB_SUFFIX))
+}
+
+/// Removes all `.strm` and thumb files which are not part of the current playlist and prunes empty directories.
+/// Returns true if the directory is empty afterward.
+pub(super) fn kodi_cleanup_strm_dir(dir: &Path, written: &HashSet<PathBuf>) -> bool {
+    let Ok(entries) = std::fs::read_dir(dir) else { return false };
+    let mut empty = true;
+    for entry in entries.flatten() {
+        let entry_path = entry.path();
+        if entry_path.is_dir() {
+            if kodi_cleanup_strm_dir(&entry_path, written) {
+                if let Err(err) = std::fs::remove_dir(&entry_path) {
+                    error!("cant remove directory: {:?} {err}", &entry_path);
+                    empty = false;
+                }
+            } else {
+                empty = false;
+            }
+        } else if is_kodi_output_file(&entry_path) && !written.contains(&entry_path) {
+            if let Err(err) = std::fs::remove_file(&entry_path) {
+                error!("cant remove file: {:?} {err}", &entry_path);
+                empty = false;
+            }
+        } else {
+            empty = false;
+        }
+    }
+    empty
+}
+
+/// Only writes the file if the content has changed, keeps the timestamps of unchanged files for library scans.
+pub(super) fn kodi_write_strm_file(file_path: &Path, url: &str) -> std::io::Result<()> {
+    if let Ok(content) = std::fs::read(file_path) {
+        if content == url.as_bytes() {
+            return Ok(());
+        }
+    }
+    file_utils::write_atomic(file_path, |strm_file| strm_file.write_all(url.as_bytes()))
+}
+
+/// Copies the cached logo of the channel as local artwork next to the strm file, Kodi doesn't read logo urls from strm files.
+/// Returns the path of the thumb file, `None` if the logo is not cached.
+fn kodi_write_thumb_file(cfg: &Config, strm_path: &Path, header: &PlaylistItemHeader) -> std::io::Result<Option<PathBuf>> {
+    let Some(logo_path) = get_cached_channel_logo_path(cfg, &header.logo, &header.name) else {
+        return Ok(None);
+    };
+    let content = std::fs::read(logo_path)?;
+    let Some(extension) = get_logo_extension(&content) else {
+        return Ok(None);
+    };
+    let stem = strm_path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+    let thumb_path = strm_path.with_file_name(format!("{stem}{KODI_THUMB_SUFFIX}.{extension}"));
+    if std::fs::read(&thumb_path).is_ok_and(|existing| existing == content) {
+        return Ok(Some(thumb_path));
+    }
+    file_utils::write_atomic(&thumb_path, |thumb_file| thumb_file.write_all(&content))?;
+    Ok(Some(thumb_path))
+}
+
+/// The path of the strm file relative to the strm directory, rendered from the output `template`.
+fn kodi_strm_file_path(target: &ConfigTarget, output: &TargetOutput, header: &PlaylistItemHeader) -> String {
+    let underscore_whitespace = target.options.as_ref().is_some_and(|o| o.underscore_whitespace);
+    let kodi_style = target.options.as_ref().is_some_and(|o| o.kodi_style);
+    let sanitize = |text: &str| sanitize_filename(text, output.sanitize, underscore_whitespace);
+    let template = output.template.as_deref().unwrap_or(DEFAULT_STRM_TEMPLATE);
+    let relative_path = render_file_path(template.strip_suffix(".strm").unwrap_or(template), |token| match token {
+        "target" => Some(sanitize(&target.name)),
+        "group" => Some(sanitize(&header.group)),
+        "name" => Some(sanitize(&header.name)),
+        "title" => {
+            let title = sanitize(&header.title);
+            Some(if kodi_style { kodi_style_rename(&title, &KODY_STYLE) } else { title })
+        }
+        "quality" => Some(get_quality(&header.name)),
+        "chno" => Some(sanitize(&header.chno)),
+        "date" => Some(chrono::Local::now().format("%Y-%m-%d").to_string()),
+        _ => None,
+    });
+    format!("{relative_path}.strm")
+}
+
+pub fn kodi_write_strm_playlist(target: &ConfigTarget, cfg: &Config, new_playlist: &[PlaylistGroup], output: &TargetOutput) -> Result<(), M3uFilterError> {
     if !new_playlist.is_empty() {
-        if filename.is_none() {
+        let Some(filename) = output.get_filename(&target.name) else {
             return Err(M3uFilterError::new(M3uFilterErrorKind::Notify, "write strm playlist failed: ".to_string()));
-        }
-        let underscore_whitespace = target.options.as_ref().is_some_and(|o| o.underscore_whitespace);
+        };
         let cleanup = target.options.as_ref().is_some_and(|o| o.cleanup);
-        let kodi_style = target.options.as_ref().is_some_and(|o| o.kodi_style);
 
-        if let Some(path) = file_utils::get_file_path(&cfg.working_dir, Some(std::path::PathBuf::from(&filename.as_ref().unwrap()))) {
-            if cleanup {
-                let _ = std::fs::remove_dir_all(&path);
-            }
+        if let Some(path) = file_utils::get_file_path(&cfg.working_dir, Some(PathBuf::from(&filename))) {
             if let Err(e) = std::fs::create_dir_all(&path) {
                 error!("cant create directory: {:?}", &path);
                 return create_m3u_filter_error_result!(M3uFilterErrorKind::Notify, "failed to write strm playlist: {}", e);
             };
+            let mut written = HashSet::new();
+            let token_rewrite = TokenRewrite::new(cfg);
             for pg in new_playlist {
                 for pli in &pg.channels {
                     let header = &pli.header.borrow();
-                    let dir_path = path.join(sanitize_for_filename(&header.group, underscore_whitespace));
-                    if let Err(e) = std::fs::create_dir_all(&dir_path) {
-                        error!("cant create directory: {:?}", &path);
-                        return create_m3u_filter_error_result!(M3uFilterErrorKind::Notify, "failed to write strm playlist: {}", e);
-                    };
-                    let mut kodi_file_name = sanitize_for_filename(&header.title, underscore_whitespace);
-                    if kodi_style {
-                        kodi_file_name = kodi_style_rename(&kodi_file_name, &KODY_STYLE);
+                    let file_path = path.join(kodi_strm_file_path(target, output, header));
+                    if let Some(dir_path) = file_path.parent() {
+                        if let Err(e) = std::fs::create_dir_all(dir_path) {
+                            error!("cant create directory: {:?}", &dir_path);
+                            return create_m3u_filter_error_result!(M3uFilterErrorKind::Notify, "failed to write strm playlist: {}", e);
+                        };
                     }
-                    let file_path = dir_path.join(format!("{kodi_file_name}.strm"));
-                    match File::create(&file_path) {
-                        Ok(mut strm_file) => {
-                            match file_utils::check_write(&strm_file.write_all(header.url.as_bytes())) {
-                                Ok(()) => (),
-                                Err(e) => return create_m3u_filter_error_result!(M3uFilterErrorKind::Notify, "failed to write strm playlist: {}", e),
-                            }
-                        }
-                        Err(err) => {
-                            return create_m3u_filter_error_result!(M3uFilterErrorKind::Notify, "failed to write strm playlist: {}", err);
-                        }
+                    let url = token_rewrite.as_ref().and_then(|token_rewrite| token_rewrite.rewrite(header.input_id, &header.url));
+                    if let Err(err) = kodi_write_strm_file(&file_path, url.as_deref().unwrap_or(&header.url)) {
+                        return create_m3u_filter_error_result!(M3uFilterErrorKind::Notify, "failed to write strm playlist: {}", err);
                     }
+                    match kodi_write_thumb_file(cfg, &file_path, header) {
+                        Ok(Some(thumb_path)) => { written.insert(thumb_path); }
+                        Ok(None) => {}
+                        Err(err) => error!("cant write thumb for {:?}: {err}", &file_path),
+                    }
+                    written.insert(file_path);
                 }
             }
+            if cleanup {
+                kodi_cleanup_strm_dir(&path, &written);
+            }
         }
     }
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::time::{Duration, SystemTime};
+
+    use crate::model::config::{Config, ConfigTarget, ConfigTargetOptions, TargetOutput, TargetType};
+    use crate::model::playlist::{PlaylistGroup, PlaylistItem, PlaylistItemHeader, XtreamCluster};
+    use crate::repository::kodi_repository::kodi_write_strm_playlist;
+    use crate::utils::test_utils::create_temp_dir;
+
+    fn group(title: &str, names: &[&str]) -> PlaylistGroup {
+        let channels = names.iter().map(|name| PlaylistItem {
+            header: RefCell::new(PlaylistItemHeader {
+                title: Rc::new((*name).to_string()),
+                group: Rc::new(title.to_string()),
+                url: Rc::new(format!("http://localhost/{name}")),
+                ..PlaylistItemHeader::default()
+            })
+        }).collect();
+        PlaylistGroup { id: 1, title: Rc::new(title.to_string()), channels, xtream_cluster: XtreamCluster::Video }
+    }
+
+    #[test]
+    fn test_strm_sync() {
+        let temp_dir = create_temp_dir("kodi");
+        let cfg = Config { working_dir: temp_dir.path().to_string_lossy().to_string(), ..Config::default() };
+        let target = ConfigTarget { name: "kodi".to_string(), options: Some(ConfigTargetOptions { cleanup: true, ..ConfigTargetOptions::default() }), ..ConfigTarget::default() };
+        let output = TargetOutput { target: TargetType::Strm, filename: Some("strm".to_string()), template: None, sanitize: Default::default(), split_groups: false };
+        let strm_dir = temp_dir.path().join("strm");
+
+        kodi_write_strm_playlist(&target, &cfg, &[group("Drama", &["Movie A", "Movie B"]), group("Comedy", &["Movie C"])], &output).unwrap();
+        let unchanged = strm_dir.join("Drama/Movie A.strm");
+        assert_eq!(std::fs::read_to_string(&unchanged).unwrap(), "http://localhost/Movie A");
+        assert!(strm_dir.join("Comedy/Movie C.strm").exists());
+        let modified = SystemTime::now() - Duration::from_secs(3600);
+        std::fs::File::options().write(true).open(&unchanged).unwrap().set_modified(modified).unwrap();
+
+        kodi_write_strm_playlist(&target, &cfg, &[group("Drama", &["Movie A"])], &output).unwrap();
+        // removed channels are deleted, the empty group directory is pruned
+        assert!(!strm_dir.join("Drama/Movie B.strm").exists());
+        assert!(!strm_dir.join("Comedy").exists());
+        // unchanged files are not rewritten and keep their timestamp for the library scan
+        assert_eq!(std::fs::metadata(&unchanged).unwrap().modified().unwrap(), modified);
+    }
+}
@@ -0,0 +1,202 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+use log::{error, warn};
+use regex::Regex;
+use serde_json::Value;
+
+use crate::create_m3u_filter_error_result;
+use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorKind};
+use crate::model::config::{Config, ConfigTarget};
+use crate::model::playlist::{PlaylistGroup, PlaylistItemHeader, PlaylistItemType};
+use crate::processing::token_refresh::TokenRewrite;
+use crate::repository::kodi_repository::{kodi_cleanup_strm_dir, kodi_write_strm_file};
+use crate::utils::file_utils;
+
+const LIBRARY_MOVIES: &str = "Movies";
+const LIBRARY_SHOWS: &str = "Shows";
+
+static TITLE_YEAR: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(.*?)[\s._-]*[(\[]?((?:19|20)\d{2})[)\]]?\s*$").unwrap());
+static SEASON_EPISODE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)S(\d{1,2})\s*E(\d{1,3})").unwrap());
+static WHITESPACE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\s+").unwrap());
+
+/// Removes the characters which are not allowed in file names on common filesystems.
+fn sanitize_path_name(text: &str) -> String {
+    let name: String = text.chars().filter(|c| !c.is_control() && !matches!(c, '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*')).collect();
+    WHITESPACE.replace_all(name.trim(), " ").trim_end_matches('.').trim().to_string()
+}
+
+fn get_property(header: &PlaylistItemHeader, name: &str) -> Option<String> {
+    match header.additional_properties.as_ref()?.get(name)? {
+        Value::String(value) if !value.trim().is_empty() => Some(value.trim().to_string()),
+        Value::Number(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+/// Splits a trailing year from the title, the year property is used if the title has none.
+pub fn split_title_year(header: &PlaylistItemHeader) -> (String, Option<String>) {
+    let title = if header.title.is_empty() { header.name.as_str() } else { header.title.as_str() };
+    if let Some(caps) = TITLE_YEAR.captures(title) {
+        if !caps[1].trim().is_empty() {
+            return (caps[1].to_string(), Some(caps[2].to_string()));
+        }
+    }
+    let year = get_property(header, "year")
+        .or_else(|| get_property(header, "release_date"))
+        .and_then(|date| date.get(0..4).filter(|year| year.chars().all(|c| c.is_ascii_digit())).map(ToString::to_string));
+    (title.to_string(), year)
+}
+
+/// `Movies/<Title (Year)>/<Title>.strm`
+fn movie_path(header: &PlaylistItemHeader) -> Option<PathBuf> {
+    let (title, year) = split_title_year(header);
+    let title = sanitize_path_name(&title);
+    if title.is_empty() {
+        return None;
+    }
+    let folder = year.map_or_else(|| title.clone(), |year| format!("{title} ({year})"));
+    Some(PathBuf::from(LIBRARY_MOVIES).join(folder).join(format!("{title}.strm")))
+}
+
+/// `Shows/<Title>/Season <X>/<Title> S<XX>E<YY>.strm`
+fn episode_path(header: &PlaylistItemHeader) -> Option<PathBuf> {
+    let caps = SEASON_EPISODE.captures(&header.title);
+    let season = get_property(header, "season").and_then(|season| season.parse::<u32>().ok())
+        .or_else(|| caps.as_ref().and_then(|caps| caps[1].parse::<u32>().ok()));
+    let episode = get_property(header, "episode_num").and_then(|episode| episode.parse::<u32>().ok())
+        .or_else(|| caps.as_ref().and_then(|caps| caps[2].parse::<u32>().ok()));
+    let show = sanitize_path_name(&get_property(header, "series_name").unwrap_or_else(|| header.group.to_string()));
+    if show.is_empty() {
+        return None;
+    }
+    let season = season.unwrap_or(1);
+    let file_name = match episode {
+        Some(episode) => format!("{show} S{season:02}E{episode:02}"),
+        None => sanitize_path_name(&header.title),
+    };
+    if file_name.is_empty() {
+        return None;
+    }
+    Some(PathBuf::from(LIBRARY_SHOWS).join(&show).join(format!("Season {season}")).join(format!("{file_name}.strm")))
+}
+
+/// Items with the same path are written as versions `<name> - <n>.strm`, which Jellyfin groups as one item.
+fn unique_path(used: &mut HashMap<PathBuf, usize>, path: PathBuf) -> PathBuf {
+    let count = used.entry(path.clone()).or_insert(0);
+    *count += 1;
+    if *count == 1 {
+        return path;
+    }
+    let stem = path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+    path.with_file_name(format!("{stem} - {count}.strm"))
+}
+
+fn library_item_paths(playlist: &[PlaylistGroup], token_rewrite: Option<&TokenRewrite>) -> Vec<(PathBuf, String)> {
+    let mut used = HashMap::new();
+    let mut result = vec![];
+    for pli in playlist.iter().flat_map(|pg| &pg.channels) {
+        let header = pli.header.borrow();
+        let path = match header.item_type {
+            PlaylistItemType::Video => movie_path(&header),
+            PlaylistItemType::Series => episode_path(&header),
+            _ => continue,
+        };
+        match path {
+            Some(path) => {
+                let url = token_rewrite.and_then(|token_rewrite| token_rewrite.rewrite(header.input_id, &header.url));
+                result.push((unique_path(&mut used, path), url.unwrap_or_else(|| header.url.to_string())));
+            }
+            None => warn!("Library item without title skipped: {}", header.url),
+        }
+    }
+    result
+}
+
+fn write_library_file(root: &Path, relative_path: &Path, url: &str) -> std::io::Result<PathBuf> {
+    let file_path = root.join(relative_path);
+    if let Some(dir_path) = file_path.parent() {
+        std::fs::create_dir_all(dir_path)?;
+    }
+    kodi_write_strm_file(&file_path, url)?;
+    Ok(file_path)
+}
+
+/// Writes the movies and series episodes of the playlist in the folder structure Jellyfin expects.
+pub fn library_write_playlist(target: &ConfigTarget, cfg: &Config, new_playlist: &[PlaylistGroup], filename: Option<&String>) -> Result<(), M3uFilterError> {
+    if new_playlist.is_empty() {
+        return Ok(());
+    }
+    let Some(path) = filename.and_then(|name| file_utils::get_file_path(&cfg.working_dir, Some(PathBuf::from(name)))) else {
+        return create_m3u_filter_error_result!(M3uFilterErrorKind::Notify, "write library failed for target {}: missing directory", target.name);
+    };
+    let mut written = HashSet::new();
+    for (relative_path, url) in library_item_paths(new_playlist, TokenRewrite::new(cfg).as_ref()) {
+        match write_library_file(&path, &relative_path, &url) {
+            Ok(file_path) => {
+                written.insert(file_path);
+            }
+            Err(err) => {
+                error!("cant write library file: {:?}", &relative_path);
+                return create_m3u_filter_error_result!(M3uFilterErrorKind::Notify, "failed to write library: {}", err);
+            }
+        }
+    }
+    if target.options.as_ref().is_some_and(|o| o.cleanup) {
+        for dir in [LIBRARY_MOVIES, LIBRARY_SHOWS] {
+            kodi_cleanup_strm_dir(&path.join(dir), &written);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::path::PathBuf;
+    use std::rc::Rc;
+
+    use serde_json::json;
+
+    use crate::model::playlist::{PlaylistGroup, PlaylistItem, PlaylistItemHeader, PlaylistItemType, XtreamCluster};
+    use crate::repository::library_repository::library_item_paths;
+
+    fn item(title: &str, item_type: PlaylistItemType, props: Option<serde_json::Value>) -> PlaylistItem {
+        PlaylistItem {
+            header: RefCell::new(PlaylistItemHeader {
+                title: Rc::new(title.to_string()),
+                group: Rc::new("Drama".to_string()),
+                url: Rc::new(format!("http://localhost/{title}")),
+                item_type,
+                additional_properties: props,
+                ..PlaylistItemHeader::default()
+            })
+        }
+    }
+
+    #[test]
+    fn test_library_item_paths() {
+        let playlist = vec![PlaylistGroup {
+            id: 1,
+            title: Rc::new("Drama".to_string()),
+            channels: vec![
+                item("The Movie (2019)", PlaylistItemType::Video, None),
+                item("The Movie: Part 2", PlaylistItemType::Video, Some(json!({"year": "2021"}))),
+                item("The Movie 2019", PlaylistItemType::Video, None),
+                item("Pilot", PlaylistItemType::Series, Some(json!({"season": 1, "episode_num": 1, "series_name": "The Show"}))),
+                item("The Show S02E05", PlaylistItemType::Series, None),
+                item("News", PlaylistItemType::Live, None),
+            ],
+            xtream_cluster: XtreamCluster::Video,
+        }];
+        let paths: Vec<PathBuf> = library_item_paths(&playlist, None).into_iter().map(|(path, _)| path).collect();
+        assert_eq!(paths, vec![
+            PathBuf::from("Movies/The Movie (2019)/The Movie.strm"),
+            PathBuf::from("Movies/The Movie Part 2 (2021)/The Movie Part 2.strm"),
+            PathBuf::from("Movies/The Movie (2019)/The Movie - 2.strm"),
+            PathBuf::from("Shows/The Show/Season 1/The Show S01E01.strm"),
+            PathBuf::from("Shows/Drama/Season 2/Drama S02E05.strm"),
+        ]);
+    }
+}
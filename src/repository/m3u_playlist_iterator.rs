@@ -1,8 +1,13 @@
-use crate::api::api_utils::get_user_server_info;
+use std::rc::Rc;
+
+use crate::api::api_utils::{get_tenant_path, get_user_server_info};
 use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorKind};
 use crate::model::api_proxy::{ProxyType, ProxyUserCredentials};
-use crate::model::config::{Config, ConfigTarget, ConfigTargetOptions};
+use crate::model::config::{Blackout, Config, ConfigTarget, ConfigTargetOptions};
 use crate::model::playlist::{M3uPlaylistItem, PlaylistItemType};
+use crate::processing::logo_cache::LogoRewrite;
+use crate::processing::token_refresh::TokenRewrite;
+use crate::repository::favorites_repository::{favorites_get_virtual_ids, FAVORITES_GROUP};
 use crate::repository::indexed_document::IndexedDocumentReader;
 use crate::repository::m3u_repository::m3u_get_file_paths;
 use crate::repository::storage::ensure_target_storage_path;
@@ -10,6 +15,7 @@ use crate::utils::file_lock_manager::FileReadGuard;
 
 pub struct M3uPlaylistIterator {
     reader: IndexedDocumentReader<M3uPlaylistItem>,
+    favorites: std::vec::IntoIter<M3uPlaylistItem>,
     base_url: String,
     username: String,
     password: String,
@@ -17,6 +23,10 @@ pub struct M3uPlaylistIterator {
     mask_redirect_url: bool,
     include_type_in_url: bool,
     proxy_type: ProxyType,
+    logo_rewrite: Option<LogoRewrite>,
+    token_rewrite: Option<TokenRewrite>,
+    hide_adult: bool,
+    blackout: Blackout,
     _file_lock: FileReadGuard,
     started: bool,
 }
@@ -26,6 +36,7 @@ impl M3uPlaylistIterator {
         cfg: &Config,
         target: &ConfigTarget,
         user: &ProxyUserCredentials,
+        tenant: Option<&str>,
     ) -> Result<Self, M3uFilterError> {
         let target_path = ensure_target_storage_path(cfg, target.name.as_str())?;
         let (m3u_path, idx_path) = m3u_get_file_paths(&target_path);
@@ -45,20 +56,39 @@ impl M3uPlaylistIterator {
                 )
             })?;
 
+        let hide_adult = target.hide_adult_content(user);
+        let blackout = target.get_blackout();
+        let favorites_group = Rc::new(FAVORITES_GROUP.to_string());
+        let favorites: Vec<M3uPlaylistItem> = favorites_get_virtual_ids(cfg, &target.name, &user.username).into_iter()
+            .filter_map(|virtual_id| IndexedDocumentReader::<M3uPlaylistItem>::read_indexed_item(&m3u_path, &idx_path, virtual_id).ok())
+            .filter(|m3u_pli| (!hide_adult || m3u_pli.parent_code.is_empty()) && !blackout.is_hidden(&m3u_pli.group, &m3u_pli.name))
+            .map(|mut m3u_pli| {
+                m3u_pli.group = Rc::clone(&favorites_group);
+                m3u_pli
+            })
+            .collect();
+
         let target_options = target.options.as_ref();
         let include_type_in_url = target_options.is_some_and( |opts| opts.m3u_include_type_in_url);
         let mask_redirect_url = target_options.is_some_and(|opts| opts.m3u_mask_redirect_url);
 
         let server_info = get_user_server_info(cfg, user);
+        let base_url = server_info.get_base_url();
+        let logo_rewrite = LogoRewrite::new(cfg, &base_url);
         Ok(Self {
             reader,
-            base_url: server_info.get_base_url(),
+            favorites: favorites.into_iter(),
+            base_url: format!("{base_url}{}", get_tenant_path(tenant)),
             username: user.username.to_string(),
             password: user.password.to_string(),
             target_options: target.options.clone(),
             include_type_in_url,
             mask_redirect_url,
             proxy_type: user.proxy.clone(),
+            logo_rewrite,
+            token_rewrite: TokenRewrite::new(cfg),
+            hide_adult,
+            blackout,
             _file_lock: file_lock, // Save lock inside struct
             started: false,
         })
@@ -70,7 +100,8 @@ impl M3uPlaylistIterator {
                 PlaylistItemType::Live
                 | PlaylistItemType::Catchup
                 | PlaylistItemType::LiveUnknown
-                | PlaylistItemType::LiveHls => "live",
+                | PlaylistItemType::LiveHls
+                | PlaylistItemType::LiveDirect => "live",
                 PlaylistItemType::Video => "movie",
                 PlaylistItemType::Series
                 | PlaylistItemType::SeriesInfo
@@ -88,6 +119,32 @@ impl M3uPlaylistIterator {
             )
         }
     }
+
+    fn to_m3u_line(&self, mut m3u_pli: M3uPlaylistItem) -> String {
+        if let Some(logo_rewrite) = &self.logo_rewrite {
+            logo_rewrite.rewrite_m3u_item(&mut m3u_pli);
+        }
+        let provider_url = || self.token_rewrite.as_ref().and_then(|token_rewrite| token_rewrite.rewrite(m3u_pli.input_id, &m3u_pli.url));
+        let stream_url = match m3u_pli.item_type {
+            PlaylistItemType::LiveHls | PlaylistItemType::LiveDirect => provider_url(),
+            _ => match &self.proxy_type {
+                ProxyType::Reverse => Some(self.get_stream_url(
+                    &m3u_pli,
+                    self.include_type_in_url,
+                )),
+                ProxyType::Redirect => if self.mask_redirect_url {
+                    Some(self.get_stream_url(
+                        &m3u_pli,
+                        self.include_type_in_url,
+                    ))
+                } else {
+                    provider_url()
+                }
+            }
+        };
+        let target_options = self.target_options.as_ref();
+        m3u_pli.to_m3u(target_options, stream_url.as_deref())
+    }
 }
 
 impl Iterator for M3uPlaylistIterator {
@@ -99,27 +156,14 @@ impl Iterator for M3uPlaylistIterator {
             return Some("#EXTM3U".to_string());
         }
 
+        if let Some(m3u_pli) = self.favorites.next() {
+            return Some(self.to_m3u_line(m3u_pli));
+        }
+
         // TODO hls and unknown reverse proxy
-        self.reader.next().map(|m3u_pli| {
-            let stream_url = match m3u_pli.item_type {
-                PlaylistItemType::LiveHls => None,
-                _ => match &self.proxy_type {
-                    ProxyType::Reverse => Some(self.get_stream_url(
-                        &m3u_pli,
-                        self.include_type_in_url,
-                    )),
-                    ProxyType::Redirect => if self.mask_redirect_url {
-                        Some(self.get_stream_url(
-                            &m3u_pli,
-                            self.include_type_in_url,
-                        ))
-                    } else {
-                        None
-                    }
-                }
-            };
-            let target_options = self.target_options.as_ref();
-            m3u_pli.to_m3u(target_options, stream_url.as_deref())
-        })
+        let hide_adult = self.hide_adult;
+        let blackout = &self.blackout;
+        self.reader.find(|m3u_pli| (!hide_adult || m3u_pli.parent_code.is_empty())
+            && !blackout.is_hidden(&m3u_pli.group, &m3u_pli.name)).map(|m3u_pli| self.to_m3u_line(m3u_pli))
     }
 }
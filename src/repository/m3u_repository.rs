@@ -1,19 +1,29 @@
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::{BufWriter, Error, ErrorKind, Write};
 use std::path::{Path, PathBuf};
-use log::error;
+use std::time::{SystemTime, UNIX_EPOCH};
+use log::{debug, error};
 
+use crate::api::api_utils::get_user_server_info;
 use crate::create_m3u_filter_error;
 use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorKind};
 use crate::model::api_proxy::{ProxyUserCredentials};
-use crate::model::config::{Config, ConfigTarget};
+use crate::model::config::{Blackout, Config, ConfigTarget, TargetOutput};
 use crate::model::playlist::{M3uPlaylistItem, PlaylistGroup, PlaylistItem, PlaylistItemType};
+use crate::processing::logo_cache::LogoRewrite;
+use crate::processing::token_refresh::TokenRewrite;
+use crate::repository::favorites_repository::favorites_load;
+use crate::repository::item_cache::ItemCacheKey;
 use crate::repository::indexed_document::{IndexedDocumentReader, IndexedDocumentWriter};
 use crate::repository::m3u_playlist_iterator::M3uPlaylistIterator;
-use crate::repository::storage::{FILE_SUFFIX_DB, FILE_SUFFIX_INDEX};
+use crate::repository::storage::{ensure_target_storage_path, get_target_storage_path, hash_string, hex_encode, FILE_SUFFIX_DB, FILE_SUFFIX_INDEX};
+use crate::utils::file_lock_manager::FileTransaction;
 use crate::utils::file_utils;
+use crate::utils::filename_template::sanitize_filename;
 
 const FILE_M3U: &str = "m3u";
+const M3U_RANGE_DIR: &str = "m3u_range";
 macro_rules! cant_write_result {
     ($path:expr, $err:expr) => {
         create_m3u_filter_error!(M3uFilterErrorKind::Notify, "failed to write m3u playlist: {} - {}", $path.to_str().unwrap() ,$err)
@@ -31,49 +41,189 @@ pub fn m3u_get_epg_file_path(target_path: &Path) -> PathBuf {
     file_utils::add_prefix_to_filename(&path, "epg_", Some("xml"))
 }
 
-fn persist_m3u_playlist_as_text(target: &ConfigTarget, cfg: &Config, m3u_playlist: &Vec<M3uPlaylistItem>) {
-    if let Some(filename) = target.get_m3u_filename() {
-        if let Some(m3u_filename) = file_utils::get_file_path(&cfg.working_dir, Some(PathBuf::from(filename))) {
-            match File::create(&m3u_filename) {
-                Ok(file) => {
-                    let mut buf_writer = BufWriter::new(file);
-                    let _ = buf_writer.write(b"#EXTM3U\n");
-                    for m3u in m3u_playlist {
-                        let _ = buf_writer.write(m3u.to_m3u(target.options.as_ref(), None).as_bytes());
-                        let _ = buf_writer.write(b"\n");
-                    }
-                }
-                Err(_) => {
-                    error!("Can't write m3u plain playlist {}", &m3u_filename.to_str().unwrap());
+/// The directory of the group playlists next to the index file, `tv.m3u` -> `tv/`.
+pub fn m3u_get_group_dir(m3u_filename: &Path) -> PathBuf {
+    m3u_filename.with_extension("")
+}
+
+/// The m3u line of the item with the token and logo urls rewritten.
+fn to_m3u_line(m3u: &M3uPlaylistItem, target: &ConfigTarget, token_rewrite: Option<&TokenRewrite>, logo_rewrite: Option<&LogoRewrite>) -> String {
+    let url = token_rewrite.and_then(|token_rewrite| token_rewrite.rewrite(m3u.input_id, &m3u.url));
+    match logo_rewrite {
+        Some(logo_rewrite) => {
+            let mut m3u = m3u.clone();
+            logo_rewrite.rewrite_m3u_item(&mut m3u);
+            m3u.to_m3u(target.options.as_ref(), url.as_deref())
+        }
+        None => m3u.to_m3u(target.options.as_ref(), url.as_deref()),
+    }
+}
+
+fn write_m3u_file<'a>(path: &Path, target: &ConfigTarget, token_rewrite: Option<&TokenRewrite>, logo_rewrite: Option<&LogoRewrite>, items: impl Iterator<Item=&'a M3uPlaylistItem>) -> std::io::Result<()> {
+    file_utils::write_atomic(path, |buf_writer| {
+        buf_writer.write_all(b"#EXTM3U\n")?;
+        for m3u in items {
+            buf_writer.write_all(to_m3u_line(m3u, target, token_rewrite, logo_rewrite).as_bytes())?;
+            buf_writer.write_all(b"\n")?;
+        }
+        Ok(())
+    })
+}
+
+/// Writes one playlist per group into the group directory and an index playlist with the relative paths of the group playlists.
+/// Group playlists which are no longer part of the playlist are deleted.
+fn write_m3u_group_files(m3u_filename: &Path, target: &ConfigTarget, output: &TargetOutput, token_rewrite: Option<&TokenRewrite>, logo_rewrite: Option<&LogoRewrite>, m3u_playlist: &[&M3uPlaylistItem]) -> std::io::Result<()> {
+    let group_dir = m3u_get_group_dir(m3u_filename);
+    let dir_name = group_dir.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    std::fs::create_dir_all(&group_dir)?;
+    let underscore_whitespace = target.options.as_ref().is_some_and(|o| o.underscore_whitespace);
+
+    let mut groups: Vec<(&str, Vec<&M3uPlaylistItem>)> = vec![];
+    let mut group_index = HashMap::new();
+    for m3u in m3u_playlist {
+        let idx = *group_index.entry(m3u.group.as_str()).or_insert_with(|| {
+            groups.push((m3u.group.as_str(), vec![]));
+            groups.len() - 1
+        });
+        groups[idx].1.push(m3u);
+    }
+
+    let mut written = HashSet::new();
+    let mut index = vec![];
+    for (group, items) in groups {
+        let base_name = sanitize_filename(group, output.sanitize, underscore_whitespace);
+        let base_name = if base_name.trim().is_empty() { "group".to_string() } else { base_name.trim().to_string() };
+        let mut file_name = format!("{base_name}.m3u");
+        let mut counter = 2;
+        while written.contains(&file_name) {
+            file_name = format!("{base_name} {counter}.m3u");
+            counter += 1;
+        }
+        write_m3u_file(&group_dir.join(&file_name), target, token_rewrite, logo_rewrite, items.into_iter())?;
+        index.push((group, format!("{dir_name}/{file_name}")));
+        written.insert(file_name);
+    }
+
+    if let Ok(entries) = std::fs::read_dir(&group_dir) {
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.ends_with(".m3u") && !written.contains(&name) {
+                if let Err(err) = std::fs::remove_file(entry.path()) {
+                    error!("cant remove file: {:?} {err}", entry.path());
                 }
             }
         }
     }
+
+    file_utils::write_atomic(m3u_filename, |buf_writer| {
+        buf_writer.write_all(b"#EXTM3U\n")?;
+        for (group, path) in &index {
+            buf_writer.write_all(format!("#EXTINF:-1,{group}\n{path}\n").as_bytes())?;
+        }
+        Ok(())
+    })
 }
 
-pub fn m3u_write_playlist(target: &ConfigTarget, cfg: &Config, target_path: &Path, new_playlist: &[PlaylistGroup]) -> Result<(), M3uFilterError> {
+/// The path of the plain playlist file of the m3u output, `None` if the output has no filename.
+pub fn get_m3u_text_path(target: &ConfigTarget, cfg: &Config) -> Option<PathBuf> {
+    let filename = target.get_m3u_output()?.get_filename(&target.name)?;
+    file_utils::get_file_path(&cfg.working_dir, Some(PathBuf::from(filename)))
+}
+
+fn persist_m3u_playlist_as_text(target: &ConfigTarget, cfg: &Config, m3u_playlist: &[M3uPlaylistItem]) {
+    let Some(output) = target.get_m3u_output() else { return };
+    if let Some(m3u_filename) = get_m3u_text_path(target, cfg) {
+        let blackout = target.get_blackout();
+        let items: Vec<&M3uPlaylistItem> = m3u_playlist.iter().filter(|m3u| !blackout.is_hidden(&m3u.group, &m3u.name)).collect();
+        let token_rewrite = TokenRewrite::new(cfg);
+        let logo_rewrite = LogoRewrite::for_files(cfg);
+        let result = if output.split_groups {
+            write_m3u_group_files(&m3u_filename, target, output, token_rewrite.as_ref(), logo_rewrite.as_ref(), &items)
+        } else {
+            write_m3u_file(&m3u_filename, target, token_rewrite.as_ref(), logo_rewrite.as_ref(), items.into_iter())
+        };
+        if let Err(err) = result {
+            error!("Can't write m3u plain playlist {}: {err}", &m3u_filename.to_str().unwrap());
+        }
+    }
+}
+
+/// Writes the m3u output of a target channel by channel into the staged files of the transaction,
+/// for playlists which are not kept in memory. The group files of `split_groups` are not written.
+pub struct M3uPlaylistWriter<'a> {
+    target: &'a ConfigTarget,
+    m3u_path: PathBuf,
+    documents: IndexedDocumentWriter,
+    /// the plain playlist file of the output
+    text: Option<(PathBuf, BufWriter<File>)>,
+    blackout: Blackout,
+    token_rewrite: Option<TokenRewrite>,
+    logo_rewrite: Option<LogoRewrite>,
+}
+
+impl<'a> M3uPlaylistWriter<'a> {
+    pub fn new(target: &'a ConfigTarget, cfg: &Config, target_path: &Path, transaction: &mut FileTransaction) -> Result<Self, M3uFilterError> {
+        let (m3u_path, idx_path) = m3u_get_file_paths(target_path);
+        let (m3u_path, idx_path) = (transaction.stage(&m3u_path), transaction.stage(&idx_path));
+        let documents = IndexedDocumentWriter::new(m3u_path.clone(), idx_path).map_err(|err| cant_write_result!(&m3u_path, err))?;
+        let text = match get_m3u_text_path(target, cfg) {
+            Some(path) => {
+                let text_path = transaction.stage(&path);
+                let mut writer = File::create(&text_path).map(BufWriter::new).map_err(|err| cant_write_result!(&text_path, err))?;
+                writer.write_all(b"#EXTM3U\n").map_err(|err| cant_write_result!(&text_path, err))?;
+                Some((text_path, writer))
+            }
+            None => None,
+        };
+        Ok(Self { target, m3u_path, documents, text, blackout: target.get_blackout(), token_rewrite: TokenRewrite::new(cfg), logo_rewrite: LogoRewrite::for_files(cfg) })
+    }
+
+    /// The virtual id of the channel has to be assigned.
+    pub fn write(&mut self, item: &PlaylistItem) -> Result<(), M3uFilterError> {
+        if item.header.borrow().item_type == PlaylistItemType::SeriesInfo {
+            return Ok(());
+        }
+        let m3u = item.to_m3u();
+        if let Some((text_path, writer)) = &mut self.text {
+            if !self.blackout.is_hidden(&m3u.group, &m3u.name) {
+                writer.write_all(to_m3u_line(&m3u, self.target, self.token_rewrite.as_ref(), self.logo_rewrite.as_ref()).as_bytes())
+                    .and_then(|()| writer.write_all(b"\n"))
+                    .map_err(|err| cant_write_result!(text_path, err))?;
+            }
+        }
+        self.documents.write_doc(m3u.virtual_id, &m3u).map_err(|err| cant_write_result!(&self.m3u_path, err))
+    }
+
+    pub fn finish(mut self) -> Result<(), M3uFilterError> {
+        self.documents.store().map_err(|err| cant_write_result!(&self.m3u_path, err))?;
+        if let Some((text_path, mut writer)) = self.text.take() {
+            writer.flush().map_err(|err| cant_write_result!(&text_path, err))?;
+        }
+        Ok(())
+    }
+}
+
+pub fn m3u_write_playlist(target: &ConfigTarget, cfg: &Config, target_path: &Path, new_playlist: &[PlaylistGroup], transaction: &mut FileTransaction) -> Result<(), M3uFilterError> {
     if !new_playlist.is_empty() {
         let (m3u_path, idx_path) = m3u_get_file_paths(target_path);
+        let (m3u_path, idx_path) = (transaction.stage(&m3u_path), transaction.stage(&idx_path));
         let m3u_playlist = new_playlist.iter()
             .flat_map(|pg| &pg.channels)
             .filter(|&pli| pli.header.borrow().item_type != PlaylistItemType::SeriesInfo)
             .map(PlaylistItem::to_m3u).collect::<Vec<M3uPlaylistItem>>();
 
         persist_m3u_playlist_as_text(target, cfg, &m3u_playlist);
-        {
-            let _file_lock = cfg.file_locks.write_lock(&m3u_path).map_err(|err| M3uFilterError::new(M3uFilterErrorKind::Info, format!("{err}")))?;
-            match IndexedDocumentWriter::new(m3u_path.clone(), idx_path) {
-                Ok(mut writer) => {
-                    for m3u in m3u_playlist {
-                        match writer.write_doc(m3u.virtual_id, &m3u) {
-                            Ok(()) => {}
-                            Err(err) => return Err(cant_write_result!(&m3u_path, err))
-                        }
+        match IndexedDocumentWriter::new(m3u_path.clone(), idx_path) {
+            Ok(mut writer) => {
+                for m3u in m3u_playlist {
+                    match writer.write_doc(m3u.virtual_id, &m3u) {
+                        Ok(()) => {}
+                        Err(err) => return Err(cant_write_result!(&m3u_path, err))
                     }
-                    writer.store().map_err(|err| cant_write_result!(&m3u_path, err))?;
                 }
-                Err(err) => return Err(cant_write_result!(&m3u_path, err))
+                writer.store().map_err(|err| cant_write_result!(&m3u_path, err))?;
             }
+            Err(err) => return Err(cant_write_result!(&m3u_path, err))
         }
     }
     Ok(())
@@ -83,17 +233,208 @@ pub fn m3u_load_rewrite_playlist(
     cfg: &Config,
     target: &ConfigTarget,
     user: &ProxyUserCredentials,
+    tenant: Option<&str>,
 ) -> Result<Box<dyn Iterator<Item = String>>, M3uFilterError> {
-    Ok(Box::new(M3uPlaylistIterator::new(cfg, target, user)?))
+    Ok(Box::new(M3uPlaylistIterator::new(cfg, target, user, tenant)?))
+}
+
+/// Returns the `ETag` and the modification time of the playlist a user gets.
+/// The version changes with the stored playlist and with the user, server and target settings and the favorites which change the content.
+/// The modification time is `None` if the blackout windows vary the content,
+/// the date of the stored playlist is no validator for it.
+pub fn m3u_get_playlist_version(
+    cfg: &Config,
+    target: &ConfigTarget,
+    user: &ProxyUserCredentials,
+    tenant: Option<&str>,
+) -> Option<(String, Option<SystemTime>)> {
+    let (m3u_path, _) = m3u_get_file_paths(&get_target_storage_path(cfg, &target.name)?);
+    let metadata = std::fs::metadata(m3u_path).ok()?;
+    let modified = metadata.modified().ok()?;
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&metadata.len().to_le_bytes());
+    hasher.update(&modified.duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos().to_le_bytes());
+    for value in [user.username.as_str(), user.password.as_str(), tenant.unwrap_or_default(), get_user_server_info(cfg, user).get_base_url().as_str()] {
+        hasher.update(value.as_bytes());
+        hasher.update(&[0]);
+    }
+    hasher.update(&[u8::from(target.hide_adult_content(user))]);
+    hasher.update(target.get_blackout().key().as_bytes());
+    for uuid in favorites_load(cfg, &target.name, &user.username) {
+        hasher.update(uuid.as_bytes());
+    }
+    for content in [serde_json::to_vec(&user.proxy), serde_json::to_vec(&target.options), serde_json::to_vec(&cfg.logo_cache)] {
+        hasher.update(&content.unwrap_or_default());
+    }
+    let varies = target.blackout.as_ref().is_some_and(|windows| !windows.is_empty());
+    Some((hex_encode(&hasher.finalize().as_bytes()[..16]), (!varies).then_some(modified)))
+}
+
+fn get_playlist_file_prefix(username: &str) -> String {
+    format!("{}_", hex_encode(&hash_string(username)[..8]))
+}
+
+/// Writes the playlist of the user into a file, ranges can only be served from the complete content.
+/// The file of the previous version of the user is deleted.
+pub fn m3u_get_playlist_file(
+    cfg: &Config,
+    target: &ConfigTarget,
+    user: &ProxyUserCredentials,
+    tenant: Option<&str>,
+    version: &str,
+) -> Result<PathBuf, M3uFilterError> {
+    let dir = ensure_target_storage_path(cfg, &target.name)?.join(M3U_RANGE_DIR);
+    let user_prefix = get_playlist_file_prefix(&user.username);
+    let path = dir.join(format!("{user_prefix}{version}.m3u"));
+    if path.exists() {
+        return Ok(path);
+    }
+    std::fs::create_dir_all(&dir).map_err(|err| cant_write_result!(&dir, err))?;
+    if let Ok(entries) = std::fs::read_dir(&dir) {
+        for entry in entries.filter_map(Result::ok) {
+            if entry.file_name().to_string_lossy().starts_with(&user_prefix) {
+                let _ = std::fs::remove_file(entry.path());
+            }
+        }
+    }
+    let lines = M3uPlaylistIterator::new(cfg, target, user, tenant)?;
+    file_utils::write_atomic(&path, |writer| {
+        for line in lines {
+            writer.write_all(line.as_bytes())?;
+            writer.write_all(b"\n")?;
+        }
+        Ok(())
+    }).map_err(|err| cant_write_result!(&path, err))?;
+    debug!("m3u playlist for range requests written to {}", path.to_str().unwrap_or("?"));
+    Ok(path)
 }
 
+/// Deletes the range request playlists of the users which were removed from the targets.
+pub fn m3u_cleanup_playlist_files(cfg: &Config) {
+    let api_proxy = cfg.t_api_proxy.read().unwrap();
+    let Some(api_proxy) = api_proxy.as_ref() else {
+        return;
+    };
+    for target in cfg.sources.iter().flat_map(|source| &source.targets) {
+        let Some(dir) = get_target_storage_path(cfg, &target.name).map(|path| path.join(M3U_RANGE_DIR)) else {
+            continue;
+        };
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        let user_prefixes: Vec<String> = api_proxy.user.iter()
+            .filter(|target_user| target_user.target.eq_ignore_ascii_case(&target.name))
+            .flat_map(|target_user| &target_user.credentials)
+            .map(|credentials| get_playlist_file_prefix(&credentials.username))
+            .collect();
+        for entry in entries.filter_map(Result::ok) {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            if !user_prefixes.iter().any(|prefix| file_name.starts_with(prefix)) {
+                debug!("Deleting m3u playlist {file_name} of removed user");
+                let _ = std::fs::remove_file(entry.path());
+            }
+        }
+    }
+}
 
 pub fn m3u_get_item_for_stream_id(cfg: &Config, stream_id: u32, m3u_path: &Path, idx_path: &Path) -> Result<M3uPlaylistItem, Error> {
     if stream_id < 1 {
         return Err(Error::new(ErrorKind::Other, "id should start with 1"));
     }
-    {
+    let cache_key = ItemCacheKey::new(m3u_path, stream_id, None);
+    if let Some(item) = cfg.t_item_cache.get(&cache_key) {
+        return Ok(item);
+    }
+    let item = {
         let _file_lock = cfg.file_locks.read_lock(m3u_path)?;
-        IndexedDocumentReader::<M3uPlaylistItem>::read_indexed_item(m3u_path, idx_path, stream_id)
+        IndexedDocumentReader::<M3uPlaylistItem>::read_indexed_item(m3u_path, idx_path, stream_id)?
+    };
+    cfg.t_item_cache.put(cache_key, &item);
+    Ok(item)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::model::api_proxy::{ApiProxyConfig, ProxyUserCredentials};
+    use crate::model::config::{BlackoutWindow, Config, ConfigSource, ConfigTarget};
+    use crate::model::playlist::{PlaylistItem, PlaylistItemHeader};
+    use crate::processing::logo_cache::get_logo_id;
+    use crate::repository::m3u_repository::{m3u_cleanup_playlist_files, m3u_get_file_paths, m3u_get_playlist_version, M3U_RANGE_DIR};
+    use crate::repository::storage::ensure_target_storage_path;
+    use crate::utils::test_utils::create_temp_dir;
+
+    #[test]
+    fn test_playlist_version() {
+        let temp_dir = create_temp_dir("playlist_version");
+        let working_dir = temp_dir.path();
+        let mut cfg = Config { working_dir: working_dir.to_str().unwrap().to_string(), ..Config::default() };
+        let api_proxy: ApiProxyConfig = serde_yaml::from_str("server: [{name: default, protocol: http, host: localhost, timezone: UTC, message: ''}]\nuser: []").unwrap();
+        cfg.set_api_proxy(Some(api_proxy));
+        let mut target = ConfigTarget { name: "tv".to_string(), ..ConfigTarget::default() };
+        let user: ProxyUserCredentials = serde_yaml::from_str("{username: u, password: p}").unwrap();
+        let target_path = ensure_target_storage_path(&cfg, &target.name).unwrap();
+        let (m3u_path, _) = m3u_get_file_paths(&target_path);
+        assert!(m3u_get_playlist_version(&cfg, &target, &user, None).is_none());
+
+        std::fs::write(&m3u_path, "content").unwrap();
+        let (etag, modified) = m3u_get_playlist_version(&cfg, &target, &user, None).unwrap();
+        assert!(modified.is_some());
+        assert_ne!(m3u_get_playlist_version(&cfg, &target, &user, Some("tenant")).unwrap().0, etag);
+        std::fs::write(&m3u_path, "changed content").unwrap();
+        let (changed_etag, _) = m3u_get_playlist_version(&cfg, &target, &user, None).unwrap();
+        assert_ne!(changed_etag, etag);
+
+        // blackout windows vary the content, the date of the playlist is no validator
+        let window: BlackoutWindow = serde_yaml::from_str("{groups: [News], from: '00:00', to: '00:00'}").unwrap();
+        target.blackout = Some(vec![window]);
+        assert!(m3u_get_playlist_version(&cfg, &target, &user, None).unwrap().1.is_none());
+    }
+
+    #[test]
+    fn test_cleanup_playlist_files() {
+        let temp_dir = create_temp_dir("playlist_cleanup");
+        let working_dir = temp_dir.path();
+        let mut cfg = Config { working_dir: working_dir.to_str().unwrap().to_string(), ..Config::default() };
+        let api_proxy: ApiProxyConfig = serde_yaml::from_str("server: [{name: default, protocol: http, host: localhost, timezone: UTC, message: ''}]
+user: [{target: tv, credentials: [{username: kept, password: p}]}]").unwrap();
+        cfg.set_api_proxy(Some(api_proxy));
+        cfg.sources = vec![ConfigSource { targets: vec![ConfigTarget { name: "tv".to_string(), ..ConfigTarget::default() }], inputs: vec![] }];
+        let dir = ensure_target_storage_path(&cfg, "tv").unwrap().join(M3U_RANGE_DIR);
+        std::fs::create_dir_all(&dir).unwrap();
+        let kept = dir.join(format!("{}1.m3u", super::get_playlist_file_prefix("kept")));
+        let removed = dir.join(format!("{}1.m3u", super::get_playlist_file_prefix("removed")));
+        std::fs::write(&kept, "kept").unwrap();
+        std::fs::write(&removed, "removed").unwrap();
+        m3u_cleanup_playlist_files(&cfg);
+        assert!(kept.exists());
+        assert!(!removed.exists());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_write_m3u_file_rewrites_logos() {
+        let temp_dir = create_temp_dir("m3u_logos");
+        let working_dir = temp_dir.path();
+        let mut cfg = Config { working_dir: working_dir.to_str().unwrap().to_string(), ..Config::default() };
+        let api_proxy: ApiProxyConfig = serde_yaml::from_str("server: [{name: default, protocol: http, host: localhost, timezone: UTC, message: ''}]\nuser: []").unwrap();
+        cfg.set_api_proxy(Some(api_proxy));
+        cfg.api.base_url = Some("https://tv.example.com/m3u".to_string());
+        cfg.logo_cache = Some(serde_yaml::from_str("{enabled: true}").unwrap());
+        let target = ConfigTarget { name: "tv".to_string(), output: vec![serde_yaml::from_str("{type: m3u, filename: tv.m3u}").unwrap()], ..ConfigTarget::default() };
+        let item = |name: &str, logo: &str| PlaylistItem { header: RefCell::new(PlaylistItemHeader {
+            name: Rc::new(name.to_string()), title: Rc::new(name.to_string()), group: Rc::new("TV".to_string()),
+            logo: Rc::new(logo.to_string()), url: Rc::new(format!("http://provider/{name}")), ..PlaylistItemHeader::default()
+        }) }.to_m3u();
+        let cached = "http://provider/logo/news.png";
+        std::fs::create_dir_all(working_dir.join("logos")).unwrap();
+        std::fs::write(working_dir.join("logos").join(get_logo_id(cached)), "png").unwrap();
+
+        super::persist_m3u_playlist_as_text(&target, &cfg, &[item("News", cached), item("Sport", "http://provider/logo/sport.png")]);
+        let content = std::fs::read_to_string(working_dir.join("tv.m3u")).unwrap();
+        assert!(content.contains(&format!("tvg-logo=\"https://tv.example.com:443/m3u/logo/{}\"", get_logo_id(cached))));
+        // logos which are not cached keep the provider url
+        assert!(content.contains("tvg-logo=\"http://provider/logo/sport.png\""));
+    }
+}
@@ -0,0 +1,140 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorKind};
+use crate::model::config::Config;
+use crate::model::playlist::XtreamCluster;
+use crate::repository::m3u_repository::m3u_get_file_paths;
+use crate::repository::storage::{get_target_storage_path, hex_encode};
+use crate::repository::xtream_repository::{xtream_get_file_paths, xtream_get_storage_path};
+use crate::utils::file_utils;
+
+const FILE_MANIFEST: &str = "manifest.json";
+
+/// Checksums of the playlist files of a target, written after the playlist was persisted.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Manifest {
+    created: i64,
+    /// blake3 hash by path relative to the target storage
+    files: BTreeMap<String, String>,
+}
+
+/// The m3u and xtream documents and indices, the files which are changed by api requests are not covered.
+fn get_manifest_files(cfg: &Config, target_name: &str, target_path: &Path) -> Vec<PathBuf> {
+    let (m3u_path, m3u_idx_path) = m3u_get_file_paths(target_path);
+    let mut files = vec![m3u_path, m3u_idx_path];
+    if let Some(xtream_path) = xtream_get_storage_path(cfg, target_name) {
+        for cluster in [XtreamCluster::Live, XtreamCluster::Video, XtreamCluster::Series] {
+            let (db_path, idx_path) = xtream_get_file_paths(&xtream_path, cluster);
+            files.push(db_path);
+            files.push(idx_path);
+        }
+    }
+    files
+}
+
+fn file_hash(cfg: &Config, path: &Path) -> std::io::Result<String> {
+    let _file_lock = cfg.file_locks.read_lock(path)?;
+    let mut hasher = blake3::Hasher::new();
+    std::io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hex_encode(hasher.finalize().as_bytes()))
+}
+
+fn read_manifest(path: &Path) -> Option<Manifest> {
+    let file = File::open(path).ok()?;
+    serde_json::from_reader(BufReader::new(file)).map_err(|err| error!("Failed to read manifest {}: {err}", path.to_str().unwrap_or("?"))).ok()
+}
+
+/// Writes the checksums of the existing playlist files of the target.
+pub fn manifest_write(cfg: &Config, target_name: &str) -> Result<(), M3uFilterError> {
+    let Some(target_path) = get_target_storage_path(cfg, target_name) else {
+        return Ok(());
+    };
+    let mut manifest = Manifest { created: chrono::Utc::now().timestamp(), files: BTreeMap::new() };
+    for path in get_manifest_files(cfg, target_name, &target_path).into_iter().filter(|path| path.exists()) {
+        let hash = file_hash(cfg, &path).map_err(|err| M3uFilterError::new(M3uFilterErrorKind::Info, format!("Failed to hash {}: {err}", path.to_str().unwrap_or("?"))))?;
+        if let Ok(relative_path) = path.strip_prefix(&target_path) {
+            manifest.files.insert(relative_path.to_string_lossy().to_string(), hash);
+        }
+    }
+    file_utils::write_atomic(&target_path.join(FILE_MANIFEST), |writer| {
+        writer.write_all(&serde_json::to_vec_pretty(&manifest)?)
+    }).map_err(|err| M3uFilterError::new(M3uFilterErrorKind::Info, format!("Failed to write manifest for target {target_name}: {err}")))
+}
+
+/// Returns the files of the target which are missing or don't match their checksum, empty if the target has no manifest.
+pub fn manifest_verify(cfg: &Config, target_name: &str) -> Vec<PathBuf> {
+    let Some(target_path) = get_target_storage_path(cfg, target_name) else {
+        return vec![];
+    };
+    let Some(manifest) = read_manifest(&target_path.join(FILE_MANIFEST)) else {
+        return vec![];
+    };
+    manifest.files.iter()
+        .map(|(file, hash)| (target_path.join(file), hash))
+        .filter(|(path, hash)| file_hash(cfg, path).map_or(true, |file_hash| file_hash != **hash))
+        .map(|(path, _)| path)
+        .collect()
+}
+
+/// Completes interrupted transactions and verifies the playlist files of all enabled targets, the corrupted files are removed,
+/// they are not served until the target is refreshed. Returns the names of the targets to refresh.
+pub fn manifest_verify_targets(cfg: &Config) -> Vec<String> {
+    let mut corrupted_targets = vec![];
+    for target in cfg.sources.iter().flat_map(|source| &source.targets).filter(|target| target.enabled) {
+        if let Some(target_path) = get_target_storage_path(cfg, &target.name).filter(|path| path.exists()) {
+            if let Err(err) = cfg.file_locks.recover_transaction(&target_path) {
+                error!("Failed to complete interrupted transaction of target {}: {err}", target.name);
+            }
+        }
+        let corrupted_files = manifest_verify(cfg, &target.name);
+        if corrupted_files.is_empty() {
+            continue;
+        }
+        for path in &corrupted_files {
+            warn!("Corrupted file {} of target {}", path.to_str().unwrap_or("?"), target.name);
+            if let Err(err) = std::fs::remove_file(path) {
+                if err.kind() != std::io::ErrorKind::NotFound {
+                    error!("Failed to remove corrupted file {}: {err}", path.to_str().unwrap_or("?"));
+                }
+            }
+        }
+        corrupted_targets.push(target.name.clone());
+    }
+    if !corrupted_targets.is_empty() {
+        info!("Refreshing targets with corrupted files: {}", corrupted_targets.join(", "));
+    }
+    corrupted_targets
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::config::Config;
+    use crate::repository::m3u_repository::m3u_get_file_paths;
+    use crate::repository::manifest_repository::{manifest_verify, manifest_write};
+    use crate::repository::storage::ensure_target_storage_path;
+    use crate::utils::test_utils::create_temp_dir;
+
+    #[test]
+    fn test_manifest_verify() {
+        let temp_dir = create_temp_dir("manifest");
+        let working_dir = temp_dir.path();
+        let cfg = Config { working_dir: working_dir.to_str().unwrap().to_string(), ..Config::default() };
+        let target_path = ensure_target_storage_path(&cfg, "tv").unwrap();
+        let (m3u_path, idx_path) = m3u_get_file_paths(&target_path);
+        std::fs::write(&m3u_path, b"documents").unwrap();
+        std::fs::write(&idx_path, b"index").unwrap();
+
+        assert!(manifest_verify(&cfg, "tv").is_empty());
+        manifest_write(&cfg, "tv").unwrap();
+        assert!(manifest_verify(&cfg, "tv").is_empty());
+        std::fs::write(&m3u_path, b"garbage").unwrap();
+        std::fs::remove_file(&idx_path).unwrap();
+        assert_eq!(manifest_verify(&cfg, "tv"), vec![m3u_path, idx_path]);
+    }
+}
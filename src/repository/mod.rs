@@ -3,6 +3,24 @@ pub mod m3u_repository;
 pub mod xtream_repository;
 pub mod epg_repository;
 pub mod kodi_repository;
+pub mod library_repository;
+pub mod report_repository;
+pub mod snapshot_repository;
+pub mod persist_repository;
+pub mod override_repository;
+pub mod quarantine_repository;
+pub mod favorites_repository;
+pub mod watch_history_repository;
+pub mod alias_repository;
+pub mod search_repository;
+pub mod export_repository;
+pub mod recording_repository;
+pub mod job_repository;
+pub mod audit_repository;
+pub mod item_cache;
+pub mod manifest_repository;
+pub mod url_index;
+pub mod title_index;
 pub mod storage;
 
 mod indexed_document;
@@ -0,0 +1,75 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use log::error;
+use serde::{Deserialize, Serialize};
+
+use crate::create_m3u_filter_error_result;
+use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorKind};
+use crate::model::config::Config;
+use crate::repository::storage::ensure_target_storage_path;
+use crate::utils::json_utils::json_write_documents_to_file;
+
+const FILE_CHANNEL_OVERRIDES: &str = "channel_overrides.json";
+
+/// Manual corrections of a channel, applied after all other processing steps.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChannelOverride {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logo: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub epg_channel_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    #[serde(default)]
+    pub hidden: bool,
+}
+
+/// Overrides by channel uuid (hex encoded hash of the url).
+pub type ChannelOverrides = BTreeMap<String, ChannelOverride>;
+
+fn get_overrides_path(cfg: &Config, target_name: &str) -> Result<PathBuf, M3uFilterError> {
+    ensure_target_storage_path(cfg, target_name).map(|path| path.join(FILE_CHANNEL_OVERRIDES))
+}
+
+fn read_overrides(path: &Path) -> ChannelOverrides {
+    let Ok(file) = File::open(path) else {
+        return ChannelOverrides::new();
+    };
+    serde_json::from_reader(BufReader::new(file)).unwrap_or_else(|err| {
+        error!("Failed to read channel overrides {}: {err}", path.to_str().unwrap_or("?"));
+        ChannelOverrides::new()
+    })
+}
+
+pub fn override_load(cfg: &Config, target_name: &str) -> ChannelOverrides {
+    let Ok(path) = get_overrides_path(cfg, target_name) else {
+        return ChannelOverrides::new();
+    };
+    if !path.exists() {
+        return ChannelOverrides::new();
+    }
+    match cfg.file_locks.read_lock(&path) {
+        Ok(_file_lock) => read_overrides(&path),
+        Err(_) => ChannelOverrides::new(),
+    }
+}
+
+/// Sets the override of the channel, `None` removes it.
+pub fn override_update(cfg: &Config, target_name: &str, uuid: &str, channel_override: Option<ChannelOverride>) -> Result<(), M3uFilterError> {
+    let path = get_overrides_path(cfg, target_name)?;
+    let _file_lock = cfg.file_locks.write_lock(&path).map_err(|err| M3uFilterError::new(M3uFilterErrorKind::Info, format!("{err}")))?;
+    let mut overrides = read_overrides(&path);
+    match channel_override {
+        Some(value) => overrides.insert(uuid.to_string(), value),
+        None => overrides.remove(uuid),
+    };
+    if let Err(err) = json_write_documents_to_file(&path, &overrides) {
+        return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "Failed to write channel overrides {}: {err}", path.to_str().unwrap_or("?"));
+    }
+    Ok(())
+}
@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use chrono::{Local, NaiveDateTime, TimeZone};
+use log::{debug, error};
+use serde::Serialize;
+
+use crate::model::config::{Config, ConfigInput, PersistRetention};
+use crate::utils::file_utils;
+
+const PERSIST_PLACEHOLDER: &str = "{}";
+/// The timestamp `prepare_persist_path` puts into the file name.
+const PERSIST_TIMESTAMP_FORMAT: &str = "%Y%m%d_%H%M%S";
+const PERSIST_TIMESTAMP_LEN: usize = 15;
+
+/// A persisted download of an input.
+#[derive(Debug, Clone, Serialize)]
+pub struct PersistedFile {
+    pub name: String,
+    pub size: u64,
+    pub created_at: i64,
+    #[serde(skip)]
+    download: String,
+}
+
+/// The directory and the file name parts before and after `{}` of the `persist` setting.
+fn get_persist_pattern(cfg: &Config, input: &ConfigInput) -> Option<(PathBuf, String, String)> {
+    let persist = input.persist.as_ref()?;
+    let path = file_utils::get_file_path(&cfg.working_dir, Some(PathBuf::from(persist)))?;
+    let file_name = path.file_name()?.to_str()?;
+    let (prefix, suffix) = file_name.split_once(PERSIST_PLACEHOLDER)?;
+    Some((path.parent()?.to_path_buf(), prefix.to_string(), suffix.to_string()))
+}
+
+/// Splits `<download><timestamp>` into the download (e.g. `get_live_streams_`) and the unix timestamp.
+fn parse_persisted_name(middle: &str) -> Option<(String, i64)> {
+    let split = middle.len().checked_sub(PERSIST_TIMESTAMP_LEN)?;
+    let (download, timestamp) = (middle.get(..split)?, middle.get(split..)?);
+    let created_at = NaiveDateTime::parse_from_str(timestamp, PERSIST_TIMESTAMP_FORMAT).ok()?;
+    let created_at = Local.from_local_datetime(&created_at).earliest()?;
+    Some((download.to_string(), created_at.timestamp()))
+}
+
+fn list_persisted_files(dir: &Path, prefix: &str, suffix: &str) -> Vec<PersistedFile> {
+    let mut result: Vec<PersistedFile> = std::fs::read_dir(dir).map(|entries| {
+        entries.filter_map(Result::ok).filter_map(|entry| {
+            let name = entry.file_name().to_str()?.to_string();
+            let middle = name.strip_prefix(prefix)?.strip_suffix(suffix)?;
+            let (download, created_at) = parse_persisted_name(middle)?;
+            let size = entry.metadata().ok().filter(std::fs::Metadata::is_file)?.len();
+            Some(PersistedFile { name, size, created_at, download })
+        }).collect()
+    }).unwrap_or_default();
+    result.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.name.cmp(&b.name)));
+    result
+}
+
+/// Returns the persisted files of the input, oldest first.
+pub fn persist_list(cfg: &Config, input: &ConfigInput) -> Vec<PersistedFile> {
+    get_persist_pattern(cfg, input).map_or_else(Vec::new, |(dir, prefix, suffix)| list_persisted_files(&dir, &prefix, &suffix))
+}
+
+/// Returns the path of a persisted file of the input, only listed files are accepted.
+pub fn persist_file_path(cfg: &Config, input: &ConfigInput, name: &str) -> Option<PathBuf> {
+    let (dir, prefix, suffix) = get_persist_pattern(cfg, input)?;
+    list_persisted_files(&dir, &prefix, &suffix).into_iter().find(|file| file.name == name).map(|file| dir.join(file.name))
+}
+
+/// Returns the files which are outside the retention, the files are grouped by download.
+fn get_expired_files(files: Vec<PersistedFile>, retention: &PersistRetention, now: i64) -> Vec<PersistedFile> {
+    let mut downloads: HashMap<String, Vec<PersistedFile>> = HashMap::new();
+    for file in files {
+        downloads.entry(file.download.clone()).or_default().push(file);
+    }
+    let min_created_at = retention.max_age_days.map(|days| now - i64::from(days) * 86_400);
+    let mut result = vec![];
+    for mut files in downloads.into_values() {
+        // the newest file is always kept
+        files.pop();
+        let keep_last = retention.keep_last.unwrap_or(usize::MAX).saturating_sub(1);
+        let keep_from = files.len().saturating_sub(keep_last);
+        for (index, file) in files.into_iter().enumerate() {
+            if index < keep_from || min_created_at.is_some_and(|min| file.created_at < min) {
+                result.push(file);
+            }
+        }
+    }
+    result
+}
+
+/// Deletes the persisted files of the input which are outside `persist_retention`.
+pub fn persist_cleanup(cfg: &Config, input: &ConfigInput) {
+    let Some(retention) = input.persist_retention.as_ref() else { return };
+    let Some((dir, prefix, suffix)) = get_persist_pattern(cfg, input) else { return };
+    let files = list_persisted_files(&dir, &prefix, &suffix);
+    for file in get_expired_files(files, retention, Local::now().timestamp()) {
+        let path = dir.join(&file.name);
+        match std::fs::remove_file(&path) {
+            Ok(()) => debug!("Deleted persisted file {}", path.to_str().unwrap_or("?")),
+            Err(err) => error!("Failed to delete persisted file {}: {err}", path.to_str().unwrap_or("?")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::config::PersistRetention;
+    use crate::repository::persist_repository::{get_expired_files, parse_persisted_name, PersistedFile};
+
+    fn file(name: &str) -> PersistedFile {
+        let (download, created_at) = parse_persisted_name(name).unwrap();
+        PersistedFile { name: name.to_string(), size: 0, created_at, download }
+    }
+
+    #[test]
+    fn test_expired_files() {
+        assert!(parse_persisted_name("get_live_streams_2024").is_none());
+        let files = vec![
+            file("get_live_streams_20240101_120000"),
+            file("get_vod_streams_20240101_120000"),
+            file("get_live_streams_20240102_120000"),
+            file("get_live_streams_20240103_120000"),
+        ];
+        let now = files[3].created_at;
+        let names = |retention: PersistRetention| {
+            let mut names: Vec<String> = get_expired_files(files.clone(), &retention, now).into_iter().map(|file| file.name).collect();
+            names.sort();
+            names
+        };
+        assert_eq!(names(PersistRetention { keep_last: Some(2), max_age_days: None }), vec!["get_live_streams_20240101_120000"]);
+        assert_eq!(names(PersistRetention { keep_last: None, max_age_days: Some(1) }), vec!["get_live_streams_20240101_120000"]);
+        assert_eq!(names(PersistRetention { keep_last: Some(0), max_age_days: None }),
+                   vec!["get_live_streams_20240101_120000", "get_live_streams_20240102_120000"]);
+        assert!(names(PersistRetention::default()).is_empty());
+    }
+}
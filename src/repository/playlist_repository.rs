@@ -1,14 +1,39 @@
+use std::rc::Rc;
+
 use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorKind};
 use crate::model::config::{Config, ConfigTarget, TargetType};
 use crate::model::playlist::PlaylistItemType::LiveUnknown;
-use crate::model::playlist::{PlaylistGroup, PlaylistItemType};
+use crate::model::playlist::{M3uPlaylistItem, PlaylistGroup, PlaylistItemHeader, PlaylistItemType, XtreamCluster, XtreamPlaylistItem};
 use crate::model::xmltv::Epg;
 use crate::repository::epg_repository::epg_write;
+use crate::repository::indexed_document::IndexedDocumentReader;
 use crate::repository::kodi_repository::kodi_write_strm_playlist;
-use crate::repository::m3u_repository::m3u_write_playlist;
-use crate::repository::storage::{ensure_target_storage_path, get_target_id_mapping_file};
+use crate::repository::library_repository::library_write_playlist;
+use crate::repository::m3u_repository::{m3u_get_file_paths, m3u_write_playlist};
+use crate::repository::manifest_repository::manifest_write;
+use crate::repository::report_repository::report_write_playlist;
+use crate::repository::search_repository::search_index_write;
+use crate::repository::snapshot_repository::snapshot_write;
+use crate::repository::storage::{ensure_target_storage_path, get_target_id_mapping_file, get_target_storage_path};
 use crate::repository::target_id_mapping::TargetIdMapping;
-use crate::repository::xtream_repository::xtream_write_playlist;
+use crate::repository::xtream_repository::{xtream_get_file_paths, xtream_get_storage_path, xtream_write_playlist};
+use crate::utils::request_utils::{get_udpxy_url, is_direct_stream_url};
+
+/// Assigns the virtual id of the channel, the url is rewritten for the `udpxy_url` of the target.
+pub fn assign_virtual_id(header: &mut PlaylistItemHeader, udpxy_url: Option<&str>, target_id_mapping: &mut TargetIdMapping) {
+    if let Some(relay_url) = udpxy_url.and_then(|udpxy| get_udpxy_url(udpxy, &header.url)) {
+        header.url = Rc::new(relay_url);
+    }
+    let provider_id = header.get_provider_id().unwrap_or_default();
+    if is_direct_stream_url(&header.url) {
+        header.item_type = PlaylistItemType::LiveDirect;
+    } else if provider_id == 0 {
+        header.item_type = if header.url.ends_with(".m3u8") { PlaylistItemType::LiveHls } else { LiveUnknown };
+    }
+    let uuid = header.get_uuid();
+    let item_type = header.item_type;
+    header.virtual_id = target_id_mapping.insert_entry(**uuid, provider_id, item_type, 0);
+}
 
 pub fn persist_playlist(playlist: &mut [PlaylistGroup], epg: Option<&Epg>,
                         target: &ConfigTarget, cfg: &Config) -> Result<(), Vec<M3uFilterError>> {
@@ -29,25 +54,31 @@ pub fn persist_playlist(playlist: &mut [PlaylistGroup], epg: Option<&Epg>,
 
     let mut target_id_mapping = TargetIdMapping::new(&target_id_mapping_file);
 
+    let udpxy_url = target.options.as_ref().and_then(|options| options.udpxy_url.as_deref());
+
     // Virtual IDs assignment
     for group in playlist.iter_mut() {
         for channel in &group.channels {
-            let mut header = channel.header.borrow_mut();
-            let provider_id = header.get_provider_id().unwrap_or_default();
-            if provider_id == 0 {
-                header.item_type = if header.url.ends_with(".m3u8") { PlaylistItemType::LiveHls } else { LiveUnknown };
-            }
-            let uuid = header.get_uuid();
-            let item_type = header.item_type;
-            header.virtual_id = target_id_mapping.insert_entry(**uuid, provider_id, item_type, 0);
+            assign_virtual_id(&mut channel.header.borrow_mut(), udpxy_url, &mut target_id_mapping);
         }
     }
 
+    // the playlist, index and epg files of all outputs are replaced together
+    let mut transaction = match cfg.file_locks.begin_transaction(&target_path) {
+        Ok(transaction) => transaction,
+        Err(err) => {
+            errors.push(M3uFilterError::new(M3uFilterErrorKind::Info, format!("Failed to start transaction for target {}: {err}", target.name)));
+            return Err(errors);
+        }
+    };
+
     for output in &target.output {
         let result = match output.target {
-            TargetType::M3u => m3u_write_playlist(target, cfg, &target_path, playlist),
-            TargetType::Xtream => xtream_write_playlist(target, cfg, playlist),
-            TargetType::Strm => kodi_write_strm_playlist(target, cfg, playlist, output.filename.as_ref()),
+            TargetType::M3u => m3u_write_playlist(target, cfg, &target_path, playlist, &mut transaction),
+            TargetType::Xtream => xtream_write_playlist(target, cfg, playlist, &mut transaction),
+            TargetType::Strm => kodi_write_strm_playlist(target, cfg, playlist, output),
+            TargetType::Report => report_write_playlist(target, cfg, playlist, output.get_filename(&target.name).as_ref()),
+            TargetType::Library => library_write_playlist(target, cfg, playlist, output.get_filename(&target.name).as_ref()),
         };
 
         if let Err(err) = result {
@@ -57,7 +88,7 @@ pub fn persist_playlist(playlist: &mut [PlaylistGroup], epg: Option<&Epg>,
                 errors.push(M3uFilterError::new(M3uFilterErrorKind::Info, err.to_string()));
             }
             if !playlist.is_empty() {
-                if let Err(err) = epg_write(target, cfg, &target_path, epg, output) {
+                if let Err(err) = epg_write(target, cfg, &target_path, epg, output, &mut transaction) {
                     errors.push(err);
                 }
             }
@@ -68,5 +99,74 @@ pub fn persist_playlist(playlist: &mut [PlaylistGroup], epg: Option<&Epg>,
         errors.push(M3uFilterError::new(M3uFilterErrorKind::Info, err.to_string()));
     }
 
+    // on errors the transaction is dropped and the files of the previous refresh are kept
+    if errors.is_empty() {
+        if let Err(err) = transaction.commit() {
+            errors.push(M3uFilterError::new(M3uFilterErrorKind::Info, format!("Failed to commit files of target {}: {err}", target.name)));
+        }
+        cfg.t_item_cache.invalidate(&target_path);
+    }
+
+    if let Err(err) = snapshot_write(cfg, target, playlist) {
+        errors.push(err);
+    }
+
+    if let Err(err) = search_index_write(cfg, target, playlist) {
+        errors.push(err);
+    }
+
+    // the old manifest is kept on errors, the files are refreshed at the next start
+    if errors.is_empty() {
+        if let Err(err) = manifest_write(cfg, &target.name) {
+            errors.push(err);
+        }
+    }
+
     if errors.is_empty() { Ok(()) } else { Err(errors) }
 }
+
+/// A persisted live channel of a target.
+pub struct LiveChannel {
+    pub name: String,
+    pub url: String,
+    pub provider_id: String,
+    pub epg_channel_id: Option<String>,
+    pub input_id: u16,
+}
+
+/// Returns the persisted live channels of a target.
+pub fn load_target_live_channels(cfg: &Config, target: &ConfigTarget) -> Vec<LiveChannel> {
+    if target.has_output(&TargetType::Xtream) {
+        if let Some(storage_path) = xtream_get_storage_path(cfg, &target.name) {
+            let (xtream_path, idx_path) = xtream_get_file_paths(&storage_path, XtreamCluster::Live);
+            if let Ok(_file_lock) = cfg.file_locks.read_lock(&xtream_path) {
+                if let Ok(reader) = IndexedDocumentReader::<XtreamPlaylistItem>::new(&xtream_path, &idx_path) {
+                    return reader.map(|item| LiveChannel {
+                        name: item.name.to_string(),
+                        url: item.url.to_string(),
+                        provider_id: item.provider_id.to_string(),
+                        epg_channel_id: item.epg_channel_id.as_ref().map(ToString::to_string),
+                        input_id: item.input_id,
+                    }).collect();
+                }
+            }
+        }
+    } else if target.has_output(&TargetType::M3u) {
+        if let Some(target_path) = get_target_storage_path(cfg, &target.name) {
+            let (m3u_path, idx_path) = m3u_get_file_paths(&target_path);
+            if let Ok(_file_lock) = cfg.file_locks.read_lock(&m3u_path) {
+                if let Ok(reader) = IndexedDocumentReader::<M3uPlaylistItem>::new(&m3u_path, &idx_path) {
+                    return reader.filter(|item| item.item_type == PlaylistItemType::Live)
+                        .map(|item| LiveChannel {
+                            name: item.name.to_string(),
+                            url: item.url.to_string(),
+                            provider_id: item.provider_id.to_string(),
+                            epg_channel_id: item.epg_channel_id.as_ref().map(ToString::to_string),
+                            input_id: item.input_id,
+                        }).collect();
+                }
+            }
+        }
+    }
+    vec![]
+}
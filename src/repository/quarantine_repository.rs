@@ -0,0 +1,72 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use log::error;
+use serde::{Deserialize, Serialize};
+
+use crate::create_m3u_filter_error_result;
+use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorKind};
+use crate::model::config::Config;
+use crate::repository::storage::ensure_target_storage_path;
+use crate::utils::json_utils::json_write_documents_to_file;
+
+const FILE_CHANNEL_QUARANTINE: &str = "channel_quarantine.json";
+
+/// A channel which is removed from the outputs until `until` (unix timestamp).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChannelQuarantine {
+    pub until: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// Quarantined channels by channel uuid (hex encoded hash of the url).
+pub type ChannelQuarantines = BTreeMap<String, ChannelQuarantine>;
+
+fn get_quarantine_path(cfg: &Config, target_name: &str) -> Result<PathBuf, M3uFilterError> {
+    ensure_target_storage_path(cfg, target_name).map(|path| path.join(FILE_CHANNEL_QUARANTINE))
+}
+
+/// Reads the quarantined channels, expired entries are dropped.
+fn read_quarantine(path: &Path, now: i64) -> ChannelQuarantines {
+    let Ok(file) = File::open(path) else {
+        return ChannelQuarantines::new();
+    };
+    let mut quarantine: ChannelQuarantines = serde_json::from_reader(BufReader::new(file)).unwrap_or_else(|err| {
+        error!("Failed to read channel quarantine {}: {err}", path.to_str().unwrap_or("?"));
+        ChannelQuarantines::new()
+    });
+    quarantine.retain(|_, entry| entry.until > now);
+    quarantine
+}
+
+/// Returns the channels of the target which are quarantined at `now`.
+pub fn quarantine_load(cfg: &Config, target_name: &str, now: i64) -> ChannelQuarantines {
+    let Ok(path) = get_quarantine_path(cfg, target_name) else {
+        return ChannelQuarantines::new();
+    };
+    if !path.exists() {
+        return ChannelQuarantines::new();
+    }
+    match cfg.file_locks.read_lock(&path) {
+        Ok(_file_lock) => read_quarantine(&path, now),
+        Err(_) => ChannelQuarantines::new(),
+    }
+}
+
+/// Quarantines the channel, `None` releases it. Expired entries are removed from the file.
+pub fn quarantine_update(cfg: &Config, target_name: &str, uuid: &str, quarantine: Option<ChannelQuarantine>, now: i64) -> Result<(), M3uFilterError> {
+    let path = get_quarantine_path(cfg, target_name)?;
+    let _file_lock = cfg.file_locks.write_lock(&path).map_err(|err| M3uFilterError::new(M3uFilterErrorKind::Info, format!("{err}")))?;
+    let mut quarantines = read_quarantine(&path, now);
+    match quarantine {
+        Some(value) => quarantines.insert(uuid.to_string(), value),
+        None => quarantines.remove(uuid),
+    };
+    if let Err(err) = json_write_documents_to_file(&path, &quarantines) {
+        return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "Failed to write channel quarantine {}: {err}", path.to_str().unwrap_or("?"));
+    }
+    Ok(())
+}
@@ -0,0 +1,112 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use log::error;
+use serde::{Deserialize, Serialize};
+
+use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorKind};
+use crate::model::config::{Config, RecordingConfig};
+use crate::utils::file_utils;
+use crate::utils::json_utils::json_write_documents_to_file;
+
+const FILE_RECORDINGS: &str = "recordings.json";
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RecordingStatus {
+    Scheduled,
+    Recording,
+    Completed,
+    Failed,
+    /// a deleted recording of a rule, kept until the programme is over that the rule does not create it again
+    Cancelled,
+}
+
+impl RecordingStatus {
+    pub const fn is_finished(self) -> bool {
+        matches!(self, Self::Completed | Self::Failed | Self::Cancelled)
+    }
+}
+
+/// A recording of a channel, `start` and `stop` are unix timestamps without padding.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Recording {
+    pub id: String,
+    pub target: String,
+    pub virtual_id: u32,
+    /// channel name
+    pub name: String,
+    /// programme title, the channel name for manual recordings
+    pub title: String,
+    pub start: i64,
+    pub stop: i64,
+    pub status: RecordingStatus,
+    /// file name in the recording directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    #[serde(default)]
+    pub size: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// the recording rule which created the recording
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule: Option<String>,
+}
+
+pub fn get_recording_directory(cfg: &Config, recording_cfg: &RecordingConfig) -> PathBuf {
+    file_utils::get_file_path(&cfg.working_dir, Some(PathBuf::from(&recording_cfg.directory)))
+        .unwrap_or_else(|| PathBuf::from(&recording_cfg.directory))
+}
+
+fn get_recordings_path(directory: &Path) -> PathBuf {
+    directory.join(FILE_RECORDINGS)
+}
+
+pub fn recording_load(directory: &Path) -> Vec<Recording> {
+    let path = get_recordings_path(directory);
+    let Ok(file) = File::open(&path) else {
+        return vec![];
+    };
+    serde_json::from_reader(BufReader::new(file)).unwrap_or_else(|err| {
+        error!("Failed to read recordings {}: {err}", path.to_str().unwrap_or("?"));
+        vec![]
+    })
+}
+
+pub fn recording_save(directory: &Path, recordings: &[Recording]) -> Result<(), M3uFilterError> {
+    std::fs::create_dir_all(directory)
+        .and_then(|()| json_write_documents_to_file(&get_recordings_path(directory), recordings))
+        .map_err(|err| M3uFilterError::new(M3uFilterErrorKind::Info, format!("Failed to save recordings: {err}")))
+}
+
+/// `News_20241016_2015_<id>.ts`, only alphanumeric characters of the title are kept.
+pub fn recording_file_name(recording: &Recording) -> String {
+    let title: String = recording.title.chars().map(|c| if c.is_alphanumeric() { c } else { '_' }).collect();
+    let date = chrono::DateTime::from_timestamp(recording.start, 0).map(|date| date.format("%Y%m%d_%H%M").to_string()).unwrap_or_default();
+    format!("{}_{date}_{}.ts", title.trim_matches('_'), recording.id)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::repository::recording_repository::{recording_file_name, Recording, RecordingStatus};
+
+    #[test]
+    fn test_recording_file_name() {
+        let recording = Recording {
+            id: "00ff".to_string(),
+            target: "iptv".to_string(),
+            virtual_id: 1,
+            name: "News HD".to_string(),
+            title: "Evening News: Weather/Sport".to_string(),
+            start: 1_729_108_800,
+            stop: 1_729_112_400,
+            status: RecordingStatus::Scheduled,
+            file: None,
+            size: 0,
+            error: None,
+            rule: None,
+        };
+        assert_eq!(recording_file_name(&recording), "Evening_News__Weather_Sport_20241016_2000_00ff.ts");
+    }
+}
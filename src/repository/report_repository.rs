@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use serde::Serialize;
+use url::Url;
+
+use crate::create_m3u_filter_error_result;
+use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorKind};
+use crate::model::config::{Config, ConfigTarget};
+use crate::model::playlist::{PlaylistGroup, PlaylistItemType};
+use crate::processing::stream_health::{get_stream_status, StreamStatus};
+use crate::utils::file_utils;
+use crate::utils::json_utils::json_write_documents_to_file;
+use crate::utils::request_utils::mask_sensitive_info;
+
+const VALID_URL_SCHEMES: &[&str] = &["http", "https", "rtmp", "rtmps", "rtsp", "udp", "rtp"];
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+pub enum ReportIssueKind {
+    #[serde(rename = "invalid_url")]
+    InvalidUrl,
+    #[serde(rename = "duplicate_id")]
+    DuplicateId,
+    #[serde(rename = "dead_stream")]
+    DeadStream,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReportIssue {
+    pub kind: ReportIssueKind,
+    pub group: String,
+    pub name: String,
+    pub id: String,
+    pub url: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlaylistReport {
+    pub target: String,
+    pub created_at: i64,
+    pub total: usize,
+    pub issues: Vec<ReportIssue>,
+}
+
+fn is_valid_url(url: &str) -> bool {
+    Url::parse(url).is_ok_and(|parsed| VALID_URL_SCHEMES.contains(&parsed.scheme()) && parsed.has_host())
+}
+
+/// Collects all items which failed the validity checks.
+pub fn create_report(target: &ConfigTarget, playlist: &[PlaylistGroup]) -> PlaylistReport {
+    let mut issues = vec![];
+    let mut total = 0;
+    let mut provider_ids: HashMap<(PlaylistItemType, String), usize> = HashMap::new();
+    for group in playlist {
+        for channel in &group.channels {
+            let header = channel.header.borrow();
+            if header.item_type == PlaylistItemType::SeriesInfo {
+                continue;
+            }
+            total += 1;
+            let mut add_issue = |kind: ReportIssueKind| issues.push(ReportIssue {
+                kind,
+                group: header.group.to_string(),
+                name: header.name.to_string(),
+                id: header.id.to_string(),
+                url: mask_sensitive_info(&header.url),
+            });
+            if !is_valid_url(&header.url) {
+                add_issue(ReportIssueKind::InvalidUrl);
+            }
+            if !header.id.is_empty() {
+                let count = provider_ids.entry((header.item_type, header.id.to_string())).or_insert(0);
+                *count += 1;
+                if *count > 1 {
+                    add_issue(ReportIssueKind::DuplicateId);
+                }
+            }
+            if get_stream_status(&header.url) == Some(StreamStatus::Dead) {
+                add_issue(ReportIssueKind::DeadStream);
+            }
+        }
+    }
+    PlaylistReport {
+        target: target.name.clone(),
+        created_at: chrono::Local::now().timestamp(),
+        total,
+        issues,
+    }
+}
+
+pub fn report_write_playlist(target: &ConfigTarget, cfg: &Config, playlist: &[PlaylistGroup], filename: Option<&String>) -> Result<(), M3uFilterError> {
+    let Some(report_filename) = filename else {
+        return create_m3u_filter_error_result!(M3uFilterErrorKind::Notify, "write report failed, no filename set for target {}", target.name);
+    };
+    let report = create_report(target, playlist);
+    if let Some(path) = file_utils::get_file_path(&cfg.working_dir, Some(PathBuf::from(report_filename))) {
+        if let Err(err) = json_write_documents_to_file(&path, &report) {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Notify, "failed to write report: {} - {}", path.to_str().unwrap_or("?"), err);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,213 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::create_m3u_filter_error_result;
+use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorKind};
+use crate::model::config::{Config, ConfigTarget};
+use crate::model::playlist::{PlaylistGroup, PlaylistItemHeader, PlaylistItemType, XtreamCluster};
+use crate::repository::storage::{get_target_storage_path, hex_encode};
+use crate::utils::file_utils;
+
+const FILE_SEARCH_INDEX: &str = "search_index.jsonl";
+const SEARCH_DEFAULT_PAGE_SIZE: usize = 50;
+const SEARCH_MAX_PAGE_SIZE: usize = 500;
+
+/// A channel of the processed playlist, one json line per channel.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchEntry {
+    pub virtual_id: u32,
+    pub uuid: String,
+    pub xtream_cluster: XtreamCluster,
+    pub item_type: PlaylistItemType,
+    pub name: String,
+    pub title: String,
+    pub group: String,
+    pub logo: String,
+    #[serde(default)]
+    pub url: String,
+    #[serde(default)]
+    pub epg_channel_id: String,
+    #[serde(default)]
+    pub input_id: u16,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct SearchQuery {
+    #[serde(default)]
+    pub q: String,
+    /// `live`, `video` or `series`
+    #[serde(default, rename = "type")]
+    pub cluster: Option<String>,
+    #[serde(default)]
+    pub group: Option<String>,
+    /// `q` is a regular expression
+    #[serde(default)]
+    pub regex: bool,
+    /// starts with 1
+    #[serde(default)]
+    pub page: Option<usize>,
+    #[serde(default)]
+    pub page_size: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchResult {
+    pub total: usize,
+    pub page: usize,
+    pub page_size: usize,
+    pub items: Vec<SearchEntry>,
+}
+
+pub fn get_search_index_path(cfg: &Config, target_name: &str) -> Option<PathBuf> {
+    get_target_storage_path(cfg, target_name).map(|path| path.join(FILE_SEARCH_INDEX))
+}
+
+/// Writes the json line of the channel, the virtual id has to be assigned.
+pub fn search_entry_write<W: Write>(writer: &mut W, header: &PlaylistItemHeader) -> std::io::Result<()> {
+    let entry = SearchEntry {
+        virtual_id: header.virtual_id,
+        uuid: hex_encode(header.get_uuid().as_ref()),
+        xtream_cluster: header.xtream_cluster,
+        item_type: header.item_type,
+        name: header.name.to_string(),
+        title: header.title.to_string(),
+        group: header.group.to_string(),
+        logo: header.logo.to_string(),
+        url: header.url.to_string(),
+        epg_channel_id: header.epg_channel_id.as_ref().map(ToString::to_string).unwrap_or_default(),
+        input_id: header.input_id,
+    };
+    serde_json::to_writer(&mut *writer, &entry).map_err(std::io::Error::other)?;
+    writer.write_all(b"\n")
+}
+
+/// Writes the search index of the processed playlist, the virtual ids have to be assigned.
+pub fn search_index_write(cfg: &Config, target: &ConfigTarget, playlist: &[PlaylistGroup]) -> Result<(), M3uFilterError> {
+    let Some(path) = get_search_index_path(cfg, &target.name) else {
+        return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "Failed to get search index path for target {}", &target.name);
+    };
+    let _file_lock = cfg.file_locks.write_lock(&path).map_err(|err| M3uFilterError::new(M3uFilterErrorKind::Info, format!("{err}")))?;
+    let result = file_utils::write_atomic(&path, |writer| {
+        for channel in playlist.iter().flat_map(|group| &group.channels) {
+            search_entry_write(writer, &channel.header.borrow())?;
+        }
+        Ok(())
+    });
+    if let Err(err) = result {
+        return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "Failed to write search index {}: {err}", path.to_str().unwrap_or("?"));
+    }
+    Ok(())
+}
+
+enum TextMatcher {
+    Contains(String),
+    Regex(regex::Regex),
+}
+
+impl TextMatcher {
+    fn new(query: &SearchQuery) -> Result<Self, M3uFilterError> {
+        if query.regex {
+            regex::RegexBuilder::new(&query.q).case_insensitive(true).build().map(Self::Regex)
+                .map_err(|err| M3uFilterError::new(M3uFilterErrorKind::Info, format!("Invalid search expression: {err}")))
+        } else {
+            Ok(Self::Contains(query.q.trim().to_lowercase()))
+        }
+    }
+
+    fn is_match(&self, entry: &SearchEntry) -> bool {
+        let fields = [&entry.name, &entry.title, &entry.group];
+        match self {
+            Self::Contains(text) => text.is_empty() || fields.iter().any(|field| field.to_lowercase().contains(text.as_str())),
+            Self::Regex(re) => fields.iter().any(|field| re.is_match(field)),
+        }
+    }
+}
+
+fn matches_filter(query: &SearchQuery, entry: &SearchEntry) -> bool {
+    query.cluster.as_ref().is_none_or(|cluster| entry.xtream_cluster.as_str().eq_ignore_ascii_case(cluster.trim()))
+        && query.group.as_ref().is_none_or(|group| entry.group.eq_ignore_ascii_case(group.trim()))
+}
+
+/// Calls the visitor for each entry of the stored playlist, `false` if the target has no search index.
+pub fn search_index_for_each<F>(cfg: &Config, target_name: &str, mut visit: F) -> Result<bool, M3uFilterError>
+where
+    F: FnMut(SearchEntry) -> Result<(), M3uFilterError>,
+{
+    let Some(path) = get_search_index_path(cfg, target_name).filter(|path| path.exists()) else {
+        return Ok(false);
+    };
+    let _file_lock = cfg.file_locks.read_lock(&path).map_err(|err| M3uFilterError::new(M3uFilterErrorKind::Info, format!("{err}")))?;
+    let file = File::open(&path).map_err(|err| M3uFilterError::new(M3uFilterErrorKind::Info, format!("Failed to open search index: {err}")))?;
+    for line in BufReader::new(file).lines().map_while(Result::ok) {
+        if let Ok(entry) = serde_json::from_str::<SearchEntry>(&line) {
+            visit(entry)?;
+        }
+    }
+    Ok(true)
+}
+
+/// Finds the channel with the virtual id in the search index of the target.
+pub fn search_index_find(cfg: &Config, target_name: &str, virtual_id: u32) -> Result<Option<SearchEntry>, M3uFilterError> {
+    let mut result = None;
+    search_index_for_each(cfg, target_name, |entry| {
+        if result.is_none() && entry.virtual_id == virtual_id {
+            result = Some(entry);
+        }
+        Ok(())
+    })?;
+    Ok(result)
+}
+
+/// Searches name, title and group of the stored playlist. The index is read line by line,
+/// only the entries of the requested page are kept in memory.
+pub fn search_query(cfg: &Config, target_name: &str, query: &SearchQuery) -> Result<SearchResult, M3uFilterError> {
+    let matcher = TextMatcher::new(query)?;
+    let page = query.page.unwrap_or(1).max(1);
+    let page_size = query.page_size.unwrap_or(SEARCH_DEFAULT_PAGE_SIZE).clamp(1, SEARCH_MAX_PAGE_SIZE);
+    let mut result = SearchResult { total: 0, page, page_size, items: vec![] };
+    let first = (page - 1).saturating_mul(page_size);
+    search_index_for_each(cfg, target_name, |entry| {
+        if matches_filter(query, &entry) && matcher.is_match(&entry) {
+            if result.total >= first && result.items.len() < page_size {
+                result.items.push(entry);
+            }
+            result.total += 1;
+        }
+        Ok(())
+    })?;
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::playlist::{PlaylistItemType, XtreamCluster};
+    use crate::repository::search_repository::{matches_filter, SearchEntry, SearchQuery, TextMatcher};
+
+    #[test]
+    fn test_search_match() {
+        let entry = SearchEntry {
+            virtual_id: 1,
+            uuid: String::new(),
+            xtream_cluster: XtreamCluster::Video,
+            item_type: PlaylistItemType::Video,
+            name: "The Matrix".to_string(),
+            title: "The Matrix".to_string(),
+            group: "Action".to_string(),
+            logo: String::new(),
+            url: String::new(),
+            epg_channel_id: String::new(),
+            input_id: 1,
+        };
+        let query = |q: &str, regex: bool| SearchQuery { q: q.to_string(), regex, ..SearchQuery::default() };
+        assert!(TextMatcher::new(&query("matrix", false)).unwrap().is_match(&entry));
+        assert!(TextMatcher::new(&query("action", false)).unwrap().is_match(&entry));
+        assert!(!TextMatcher::new(&query("drama", false)).unwrap().is_match(&entry));
+        assert!(TextMatcher::new(&query("^the m.*x$", true)).unwrap().is_match(&entry));
+        assert!(TextMatcher::new(&query("(", true)).is_err());
+        assert!(matches_filter(&SearchQuery { cluster: Some("video".to_string()), ..SearchQuery::default() }, &entry));
+        assert!(!matches_filter(&SearchQuery { group: Some("Drama".to_string()), ..SearchQuery::default() }, &entry));
+    }
+}
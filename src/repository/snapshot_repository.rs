@@ -0,0 +1,191 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use log::error;
+use serde::{Deserialize, Serialize};
+
+use crate::create_m3u_filter_error_result;
+use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorKind};
+use crate::model::config::{Config, ConfigTarget};
+use crate::model::playlist::{PlaylistGroup, PlaylistItemType};
+use crate::repository::storage::{get_target_storage_path, hex_encode};
+use crate::utils::json_utils::json_write_documents_to_file;
+
+const SNAPSHOT_DIR: &str = "snapshots";
+const SNAPSHOT_EXTENSION: &str = "json";
+
+/// A channel of a processed playlist, identified by the hash of its url.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotChannel {
+    pub id: String,
+    pub name: String,
+    pub group: String,
+}
+
+/// The channels of one processing run.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PlaylistSnapshot {
+    /// Unix timestamp in milliseconds, increased if a snapshot with the same timestamp exists.
+    /// Snapshots of older versions use the timestamp in seconds of their file name.
+    #[serde(default)]
+    pub id: i64,
+    pub created_at: i64,
+    pub channels: Vec<SnapshotChannel>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RenamedChannel {
+    pub id: String,
+    pub old_name: String,
+    pub new_name: String,
+    pub old_group: String,
+    pub new_group: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlaylistDiff {
+    pub from: i64,
+    pub to: i64,
+    pub added: Vec<SnapshotChannel>,
+    pub removed: Vec<SnapshotChannel>,
+    pub renamed: Vec<RenamedChannel>,
+}
+
+fn get_snapshot_dir(cfg: &Config, target_name: &str) -> Option<PathBuf> {
+    get_target_storage_path(cfg, target_name).map(|path| path.join(SNAPSHOT_DIR))
+}
+
+fn get_snapshot_path(snapshot_dir: &Path, id: i64) -> PathBuf {
+    snapshot_dir.join(format!("{id}.{SNAPSHOT_EXTENSION}"))
+}
+
+fn list_snapshots(snapshot_dir: &Path) -> Vec<i64> {
+    let mut result: Vec<i64> = std::fs::read_dir(snapshot_dir).map(|entries| {
+        entries.filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == SNAPSHOT_EXTENSION))
+            .filter_map(|path| path.file_stem().and_then(|stem| stem.to_str()).and_then(|stem| stem.parse::<i64>().ok()))
+            .collect()
+    }).unwrap_or_default();
+    result.sort_unstable();
+    result
+}
+
+/// Returns the ids of the stored snapshots, oldest first.
+pub fn snapshot_list(cfg: &Config, target_name: &str) -> Vec<i64> {
+    get_snapshot_dir(cfg, target_name).map_or_else(Vec::new, |snapshot_dir| list_snapshots(&snapshot_dir))
+}
+
+pub fn snapshot_load(cfg: &Config, target_name: &str, id: i64) -> Option<PlaylistSnapshot> {
+    let path = get_snapshot_path(&get_snapshot_dir(cfg, target_name)?, id);
+    let file = File::open(&path).ok()?;
+    match serde_json::from_reader::<_, PlaylistSnapshot>(BufReader::new(file)) {
+        Ok(snapshot) => Some(PlaylistSnapshot { id, ..snapshot }),
+        Err(err) => {
+            error!("Failed to read snapshot {}: {err}", path.to_str().unwrap_or("?"));
+            None
+        }
+    }
+}
+
+/// The ids are strictly increasing, even for several runs within one millisecond.
+fn next_snapshot_id(snapshots: &[i64], now_millis: i64) -> i64 {
+    snapshots.last().map_or(now_millis, |last| now_millis.max(last + 1))
+}
+
+fn create_snapshot(id: i64, playlist: &[PlaylistGroup]) -> PlaylistSnapshot {
+    let channels = playlist.iter().flat_map(|group| &group.channels).filter_map(|channel| {
+        let header = channel.header.borrow();
+        if header.item_type == PlaylistItemType::SeriesInfo {
+            return None;
+        }
+        Some(SnapshotChannel {
+            id: hex_encode(header.get_uuid().as_ref()),
+            name: header.name.to_string(),
+            group: header.group.to_string(),
+        })
+    }).collect();
+    PlaylistSnapshot { id, created_at: chrono::Local::now().timestamp(), channels }
+}
+
+/// Stores the channels of the processed playlist and keeps the last `target.snapshots` snapshots.
+pub fn snapshot_write(cfg: &Config, target: &ConfigTarget, playlist: &[PlaylistGroup]) -> Result<(), M3uFilterError> {
+    if target.snapshots == 0 {
+        return Ok(());
+    }
+    let Some(snapshot_dir) = get_snapshot_dir(cfg, &target.name) else {
+        return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "Failed to get snapshot directory for target {}", &target.name);
+    };
+    if let Err(err) = std::fs::create_dir_all(&snapshot_dir) {
+        return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "Failed to create snapshot directory {}: {err}", snapshot_dir.to_str().unwrap_or("?"));
+    }
+    let id = next_snapshot_id(&list_snapshots(&snapshot_dir), chrono::Local::now().timestamp_millis());
+    let snapshot = create_snapshot(id, playlist);
+    let path = get_snapshot_path(&snapshot_dir, snapshot.id);
+    if let Err(err) = json_write_documents_to_file(&path, &snapshot) {
+        return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "Failed to write snapshot {}: {err}", path.to_str().unwrap_or("?"));
+    }
+    let snapshots = list_snapshots(&snapshot_dir);
+    let obsolete = snapshots.len().saturating_sub(target.snapshots);
+    for id in &snapshots[..obsolete] {
+        let _ = std::fs::remove_file(get_snapshot_path(&snapshot_dir, *id));
+    }
+    Ok(())
+}
+
+/// Compares two snapshots, channels with the same id but a different name or group are renamed.
+pub fn snapshot_diff(from: &PlaylistSnapshot, to: &PlaylistSnapshot) -> PlaylistDiff {
+    let from_channels: HashMap<&str, &SnapshotChannel> = from.channels.iter().map(|channel| (channel.id.as_str(), channel)).collect();
+    let to_channels: HashMap<&str, &SnapshotChannel> = to.channels.iter().map(|channel| (channel.id.as_str(), channel)).collect();
+    let mut added = vec![];
+    let mut renamed = vec![];
+    for channel in &to.channels {
+        match from_channels.get(channel.id.as_str()) {
+            None => added.push(channel.clone()),
+            Some(old) if old.name != channel.name || old.group != channel.group => renamed.push(RenamedChannel {
+                id: channel.id.clone(),
+                old_name: old.name.clone(),
+                new_name: channel.name.clone(),
+                old_group: old.group.clone(),
+                new_group: channel.group.clone(),
+            }),
+            Some(_) => {}
+        }
+    }
+    let removed = from.channels.iter().filter(|channel| !to_channels.contains_key(channel.id.as_str())).cloned().collect();
+    PlaylistDiff { from: from.id, to: to.id, added, removed, renamed }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::repository::snapshot_repository::{next_snapshot_id, snapshot_diff, PlaylistSnapshot, SnapshotChannel};
+
+    fn channel(id: &str, name: &str, group: &str) -> SnapshotChannel {
+        SnapshotChannel { id: id.to_string(), name: name.to_string(), group: group.to_string() }
+    }
+
+    #[test]
+    fn test_snapshot_diff() {
+        let from = PlaylistSnapshot { id: 1, created_at: 1, channels: vec![channel("a", "News", "DE"), channel("b", "Sport", "DE"), channel("c", "Kids", "DE")] };
+        let to = PlaylistSnapshot { id: 2, created_at: 2, channels: vec![channel("a", "News", "DE"), channel("b", "Sport HD", "DE"), channel("d", "Music", "DE")] };
+        let diff = snapshot_diff(&from, &to);
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.added[0].name, "Music");
+        assert_eq!(diff.removed.len(), 1);
+        assert_eq!(diff.removed[0].name, "Kids");
+        assert_eq!(diff.renamed.len(), 1);
+        assert_eq!(diff.renamed[0].new_name, "Sport HD");
+    }
+
+    #[test]
+    fn test_next_snapshot_id() {
+        assert_eq!(next_snapshot_id(&[], 1_700_000_000_000), 1_700_000_000_000);
+        // snapshots of older versions have ids in seconds
+        assert_eq!(next_snapshot_id(&[1_700_000_000], 1_700_000_000_000), 1_700_000_000_000);
+        // runs within the same millisecond get distinct ids
+        assert_eq!(next_snapshot_id(&[1_700_000_000_000], 1_700_000_000_000), 1_700_000_000_001);
+        assert_eq!(next_snapshot_id(&[1_700_000_000_001], 1_700_000_000_000), 1_700_000_000_002);
+    }
+}
@@ -14,18 +14,31 @@ pub fn hash_string(url: &str) -> [u8; 32] {
     hash.into() // convert to hash array
 }
 
-fn hex_encode(bytes: &[u8]) -> String {
+pub fn hex_encode(bytes: &[u8]) -> String {
     bytes.iter().fold(String::new(), |mut output, b| {
         let _ = write!(output, "{b:02X}");
         output
     })
 }
 
+/// Decodes a hash encoded with `hex_encode`.
+pub fn hex_decode_hash(text: &str) -> Option<[u8; 32]> {
+    let text = text.trim();
+    if text.len() != 64 || !text.is_ascii() {
+        return None;
+    }
+    let mut hash = [0u8; 32];
+    for (idx, byte) in hash.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&text[idx * 2..idx * 2 + 2], 16).ok()?;
+    }
+    Some(hash)
+}
+
 pub fn hash_string_as_hex(url: &str) -> String {
     hex_encode(&hash_string(url))
 }
 
-pub(in crate::repository) fn get_target_id_mapping_file(target_path: &Path) -> PathBuf {
+pub fn get_target_id_mapping_file(target_path: &Path) -> PathBuf {
     target_path.join(PathBuf::from(FILE_ID_MAPPING))
 }
 
@@ -84,6 +84,10 @@ impl TargetIdMapping {
         }
     }
 
+    pub fn get_virtual_id(&self, uuid: &[u8; 32]) -> Option<u32> {
+        self.by_uuid.get(uuid).copied()
+    }
+
     pub fn persist(&mut self) -> Result<(), Error> {
         if self.dirty {
             self.by_virtual_id.store(&self.path)?;
@@ -0,0 +1,148 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorKind};
+use crate::model::config::Config;
+use crate::model::playlist::{PlaylistItem, XtreamCluster};
+use crate::repository::storage::{FILE_SUFFIX_DB, FILE_SUFFIX_INDEX};
+use crate::utils::file_utils;
+
+const FILE_TITLE_INDEX: &str = "title_index";
+
+/// A token of the dictionary and the position of its posting list (sorted virtual ids) in the db file.
+#[derive(Serialize, Deserialize)]
+struct TitleIndexTerm {
+    token: String,
+    offset: u64,
+    size: u32,
+}
+
+fn get_title_index_paths(storage_path: &Path, cluster: XtreamCluster) -> (PathBuf, PathBuf) {
+    let name = format!("{FILE_TITLE_INDEX}_{}", cluster.as_str().to_lowercase());
+    (storage_path.join(format!("{name}.{FILE_SUFFIX_DB}")), storage_path.join(format!("{name}.{FILE_SUFFIX_INDEX}")))
+}
+
+/// Lowercase alphanumeric words of the text.
+fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric()).filter(|token| !token.is_empty()).map(str::to_lowercase).collect()
+}
+
+fn to_io_error(err: bincode::Error) -> std::io::Error {
+    std::io::Error::other(err.to_string())
+}
+
+/// Writes the inverted index of the item titles for the cluster into the xtream storage.
+pub fn title_index_write(cfg: &Config, storage_path: &Path, cluster: XtreamCluster, items: &[PlaylistItem]) -> Result<(), M3uFilterError> {
+    let mut postings: BTreeMap<String, Vec<u32>> = BTreeMap::new();
+    for item in items {
+        let header = item.header.borrow();
+        for token in tokenize(&header.name).into_iter().chain(tokenize(&header.title)) {
+            let ids = postings.entry(token).or_default();
+            if ids.last() != Some(&header.virtual_id) {
+                ids.push(header.virtual_id);
+            }
+        }
+    }
+    let (db_path, idx_path) = get_title_index_paths(storage_path, cluster);
+    let _file_lock = cfg.file_locks.write_lock(&db_path).map_err(|err| M3uFilterError::new(M3uFilterErrorKind::Info, format!("{err}")))?;
+    let mut terms = Vec::with_capacity(postings.len());
+    file_utils::write_atomic(&db_path, |writer| {
+        let mut offset = 0u64;
+        for (token, mut ids) in postings {
+            ids.sort_unstable();
+            ids.dedup();
+            let encoded = bincode::serialize(&ids).map_err(to_io_error)?;
+            writer.write_all(&encoded)?;
+            let size = u32::try_from(encoded.len()).map_err(std::io::Error::other)?;
+            terms.push(TitleIndexTerm { token, offset, size });
+            offset += u64::from(size);
+        }
+        Ok(())
+    }).and_then(|()| file_utils::write_atomic(&idx_path, |writer| bincode::serialize_into(writer, &terms).map_err(to_io_error)))
+        .map_err(|err| M3uFilterError::new(M3uFilterErrorKind::Info, format!("Could not write title index {db_path:?}: {err}")))
+}
+
+fn read_postings(file: &mut File, term: &TitleIndexTerm) -> std::io::Result<Vec<u32>> {
+    file.seek(SeekFrom::Start(term.offset))?;
+    let mut buffer = vec![0; term.size as usize];
+    file.read_exact(&mut buffer)?;
+    bincode::deserialize(&buffer).map_err(to_io_error)
+}
+
+fn intersect(left: &[u32], right: &[u32]) -> Vec<u32> {
+    left.iter().filter(|id| right.binary_search(id).is_ok()).copied().collect()
+}
+
+fn search_terms(terms: &[TitleIndexTerm], file: &mut File, query: &str) -> std::io::Result<Vec<u32>> {
+    let tokens = tokenize(query);
+    let Some((last, tokens)) = tokens.split_last() else {
+        return Ok(vec![]);
+    };
+    let mut result: Option<Vec<u32>> = None;
+    for token in tokens {
+        let ids = match terms.binary_search_by(|term| term.token.as_str().cmp(token)) {
+            Ok(index) => read_postings(file, &terms[index])?,
+            Err(_) => return Ok(vec![]),
+        };
+        result = Some(result.map_or(ids.clone(), |found| intersect(&found, &ids)));
+    }
+    // the last token is matched as prefix, the user may still be typing
+    let start = terms.partition_point(|term| term.token.as_str() < last.as_str());
+    let mut prefix_ids = vec![];
+    for term in terms[start..].iter().take_while(|term| term.token.starts_with(last.as_str())) {
+        prefix_ids.extend(read_postings(file, term)?);
+    }
+    prefix_ids.sort_unstable();
+    prefix_ids.dedup();
+    Ok(result.map_or(prefix_ids.clone(), |found| intersect(&found, &prefix_ids)))
+}
+
+/// Returns the virtual ids of the items whose name or title contains all words of the query.
+pub fn title_index_search(cfg: &Config, storage_path: &Path, cluster: XtreamCluster, query: &str) -> Result<Vec<u32>, M3uFilterError> {
+    let (db_path, idx_path) = get_title_index_paths(storage_path, cluster);
+    if !db_path.exists() || !idx_path.exists() {
+        return Ok(vec![]);
+    }
+    let _file_lock = cfg.file_locks.read_lock(&db_path).map_err(|err| M3uFilterError::new(M3uFilterErrorKind::Info, format!("{err}")))?;
+    File::open(&idx_path)
+        .and_then(|file| bincode::deserialize_from::<_, Vec<TitleIndexTerm>>(BufReader::new(file)).map_err(to_io_error))
+        .and_then(|terms| File::open(&db_path).and_then(|mut file| search_terms(&terms, &mut file, query)))
+        .map_err(|err| M3uFilterError::new(M3uFilterErrorKind::Info, format!("Could not read title index {db_path:?}: {err}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::model::config::Config;
+    use crate::model::playlist::{PlaylistItem, PlaylistItemHeader, XtreamCluster};
+    use crate::repository::title_index::{title_index_search, title_index_write, tokenize};
+    use crate::utils::test_utils::create_temp_dir;
+
+    #[test]
+    fn test_title_index() {
+        assert_eq!(tokenize("The Matrix (1999)"), vec!["the", "matrix", "1999"]);
+        let temp_dir = create_temp_dir("title_index");
+        let storage_path = temp_dir.path();
+        let items: Vec<PlaylistItem> = ["The Matrix", "The Matrix Reloaded", "Matilda", "Alien"].iter().enumerate().map(|(index, name)| {
+            let header = PlaylistItemHeader { name: Rc::new((*name).to_string()), virtual_id: u32::try_from(index).unwrap() + 1, ..PlaylistItemHeader::default() };
+            PlaylistItem { header: RefCell::new(header) }
+        }).collect();
+        let cfg = Config::default();
+        title_index_write(&cfg, storage_path, XtreamCluster::Video, &items).unwrap();
+
+        let search = |query: &str| title_index_search(&cfg, storage_path, XtreamCluster::Video, query).unwrap();
+        assert_eq!(search("matrix"), vec![1, 2]);
+        assert_eq!(search("mat"), vec![1, 2, 3]);
+        assert_eq!(search("the matrix rel"), vec![2]);
+        assert_eq!(search("ALIEN"), vec![4]);
+        assert!(search("predator").is_empty());
+        assert!(search("").is_empty());
+        assert!(title_index_search(&cfg, storage_path, XtreamCluster::Series, "matrix").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,130 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorKind};
+use crate::model::config::{Config, ConfigInput};
+use crate::model::playlist::{PlaylistGroup, PlaylistItemHeader, PlaylistItemType};
+use crate::repository::bplustree::{BPlusTree, BPlusTreeQuery};
+use crate::repository::storage::get_input_storage_path;
+
+const FILE_URL_INDEX: &str = "url_index.db";
+
+/// Indexed by the item uuid, which is the hash of the stream url.
+#[derive(Serialize, Deserialize, Clone)]
+struct UrlIndexRecord {
+    provider_id: u32,
+    item_type: PlaylistItemType,
+}
+
+/// An input which carries the stream.
+#[derive(Serialize, Clone)]
+pub struct UrlIndexEntry {
+    pub input_id: u16,
+    pub input_name: Option<String>,
+    pub provider_id: u32,
+    pub item_type: PlaylistItemType,
+}
+
+fn get_url_index_file(input: &ConfigInput, working_dir: &str) -> std::io::Result<PathBuf> {
+    get_input_storage_path(input, working_dir).map(|path| path.join(FILE_URL_INDEX))
+}
+
+/// The url index of an input, filled item by item.
+pub struct UrlIndex {
+    tree: BPlusTree<[u8; 32], UrlIndexRecord>,
+}
+
+impl Default for UrlIndex {
+    fn default() -> Self {
+        Self { tree: BPlusTree::new() }
+    }
+}
+
+impl UrlIndex {
+    pub fn add(&mut self, header: &mut PlaylistItemHeader) {
+        let record = UrlIndexRecord { provider_id: header.get_provider_id().unwrap_or_default(), item_type: header.item_type };
+        self.tree.insert(**header.get_uuid(), record);
+    }
+
+    /// Replaces the url index of the input.
+    pub fn store(&mut self, cfg: &Config, input: &ConfigInput) -> Result<(), M3uFilterError> {
+        let path = get_url_index_file(input, &cfg.working_dir)
+            .map_err(|err| M3uFilterError::new(M3uFilterErrorKind::Info, format!("Could not create url index for input {}: {err}", input.id)))?;
+        let _file_lock = cfg.file_locks.write_lock(&path).map_err(|err| M3uFilterError::new(M3uFilterErrorKind::Info, format!("{err}")))?;
+        self.tree.store(&path).map_err(|err| M3uFilterError::new(M3uFilterErrorKind::Info, format!("Could not write url index {path:?}: {err}")))?;
+        Ok(())
+    }
+}
+
+/// Replaces the url index of the input with the items of the downloaded playlist.
+pub fn url_index_write(cfg: &Config, input: &ConfigInput, playlist: &[PlaylistGroup]) -> Result<(), M3uFilterError> {
+    let mut url_index = UrlIndex::default();
+    for channel in playlist.iter().flat_map(|group| &group.channels) {
+        url_index.add(&mut channel.header.borrow_mut());
+    }
+    url_index.store(cfg, input)
+}
+
+/// Returns the inputs which carry the stream with the given uuid.
+pub fn url_index_find(cfg: &Config, uuid: &[u8; 32]) -> Vec<UrlIndexEntry> {
+    let mut result = vec![];
+    for input in cfg.sources.iter().flat_map(|source| &source.inputs) {
+        let Ok(path) = get_url_index_file(input, &cfg.working_dir) else { continue };
+        if !path.exists() {
+            continue;
+        }
+        let Ok(_file_lock) = cfg.file_locks.read_lock(&path) else { continue };
+        let record = BPlusTreeQuery::<[u8; 32], UrlIndexRecord>::try_new(&path).ok().and_then(|mut query| query.query(uuid));
+        if let Some(record) = record {
+            result.push(UrlIndexEntry {
+                input_id: input.id,
+                input_name: input.name.clone(),
+                provider_id: record.provider_id,
+                item_type: record.item_type,
+            });
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::model::config::{Config, ConfigInput, ConfigSource};
+    use crate::model::playlist::{PlaylistGroup, PlaylistItem, PlaylistItemHeader, PlaylistItemType, XtreamCluster};
+    use crate::repository::storage::hash_string;
+    use crate::repository::url_index::{url_index_find, url_index_write};
+    use crate::utils::test_utils::create_temp_dir;
+
+    fn playlist(urls: &[&str]) -> Vec<PlaylistGroup> {
+        let channels = urls.iter().map(|url| {
+            let mut header = PlaylistItemHeader { url: Rc::new((*url).to_string()), item_type: PlaylistItemType::Live, ..PlaylistItemHeader::default() };
+            header.gen_uuid();
+            PlaylistItem { header: RefCell::new(header) }
+        }).collect();
+        vec![PlaylistGroup { id: 1, title: Rc::new("DE".to_string()), channels, xtream_cluster: XtreamCluster::Live }]
+    }
+
+    #[test]
+    fn test_url_index() {
+        let temp_dir = create_temp_dir("url_index");
+        let working_dir = temp_dir.path();
+        let input = |id: u16| ConfigInput { id, name: Some(format!("provider{id}")), ..ConfigInput::default() };
+        let cfg = Config {
+            working_dir: working_dir.to_str().unwrap().to_string(),
+            sources: vec![ConfigSource { inputs: vec![input(1), input(2)], targets: vec![] }],
+            ..Config::default()
+        };
+        url_index_write(&cfg, &cfg.sources[0].inputs[0], &playlist(&["http://a/live/1.ts", "http://a/live/2.ts"])).unwrap();
+        url_index_write(&cfg, &cfg.sources[0].inputs[1], &playlist(&["http://a/live/2.ts"])).unwrap();
+
+        let inputs = url_index_find(&cfg, &hash_string("http://a/live/2.ts"));
+        assert_eq!(inputs.iter().map(|entry| entry.input_id).collect::<Vec<u16>>(), vec![1, 2]);
+        assert_eq!(inputs[0].provider_id, 2);
+        assert_eq!(url_index_find(&cfg, &hash_string("http://a/live/1.ts")).len(), 1);
+        assert!(url_index_find(&cfg, &hash_string("http://b/live/1.ts")).is_empty());
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use chrono::Local;
+use log::error;
+use serde::{Deserialize, Serialize};
+
+use crate::create_m3u_filter_error_result;
+use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorKind};
+use crate::model::config::{Config, WatchHistoryConfig};
+use crate::model::playlist::{XtreamCluster, XtreamPlaylistItem};
+use crate::repository::storage::ensure_target_storage_path;
+use crate::utils::json_utils::json_write_documents_to_file;
+
+const FILE_WATCH_HISTORY: &str = "watch_history.json";
+
+/// Requests of the same stream within this time are counted as one playback (seeking starts new requests).
+const WATCH_SESSION_SECS: i64 = 600;
+
+const SECONDS_PER_DAY: i64 = 86_400;
+
+/// A started movie or episode.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct WatchEntry {
+    pub stream_id: u32,
+    /// `movie` or `series`
+    pub stream_type: String,
+    /// the series of an episode
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub series_id: Option<u32>,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub logo: String,
+    pub play_count: u32,
+    pub first_watched: i64,
+    pub last_watched: i64,
+}
+
+/// The entries by username, the last watched first.
+type WatchHistory = BTreeMap<String, Vec<WatchEntry>>;
+
+fn get_watch_history_path(cfg: &Config, target_name: &str) -> Result<PathBuf, M3uFilterError> {
+    ensure_target_storage_path(cfg, target_name).map(|path| path.join(FILE_WATCH_HISTORY))
+}
+
+fn read_watch_history(path: &Path) -> WatchHistory {
+    let Ok(file) = File::open(path) else {
+        return WatchHistory::new();
+    };
+    serde_json::from_reader(BufReader::new(file)).unwrap_or_else(|err| {
+        error!("Failed to read watch history {}: {err}", path.to_str().unwrap_or("?"));
+        WatchHistory::new()
+    })
+}
+
+fn is_retained(settings: &WatchHistoryConfig, entry: &WatchEntry, now: i64) -> bool {
+    settings.retention_days == 0 || now - entry.last_watched <= i64::from(settings.retention_days) * SECONDS_PER_DAY
+}
+
+/// Moves the entry to the front, repeated requests within the watch session are not counted.
+fn add_entry(settings: &WatchHistoryConfig, entries: &mut Vec<WatchEntry>, mut entry: WatchEntry, now: i64) {
+    if let Some(index) = entries.iter().position(|existing| existing.stream_id == entry.stream_id) {
+        let existing = entries.remove(index);
+        entry.first_watched = existing.first_watched;
+        entry.play_count = if now - existing.last_watched < WATCH_SESSION_SECS { existing.play_count } else { existing.play_count + 1 };
+    }
+    entries.insert(0, entry);
+    entries.retain(|item| is_retained(settings, item, now));
+    entries.truncate(settings.max_entries);
+}
+
+/// Records the start of a movie or episode for the user, live streams are ignored.
+pub fn watch_history_record(cfg: &Config, target_name: &str, username: &str, virtual_id: u32, pli: &XtreamPlaylistItem) -> Result<(), M3uFilterError> {
+    let Some(settings) = cfg.watch_history.as_ref().filter(|settings| settings.is_recorded(username)) else {
+        return Ok(());
+    };
+    let (stream_type, series_id) = match pli.xtream_cluster {
+        XtreamCluster::Video => ("movie", None),
+        // the item of an episode is the series info
+        XtreamCluster::Series => ("series", Some(pli.virtual_id).filter(|series_id| *series_id != virtual_id)),
+        XtreamCluster::Live => return Ok(()),
+    };
+    let now = Local::now().timestamp();
+    let entry = WatchEntry {
+        stream_id: virtual_id,
+        stream_type: stream_type.to_string(),
+        series_id,
+        name: pli.title.to_string(),
+        logo: pli.logo.to_string(),
+        play_count: 1,
+        first_watched: now,
+        last_watched: now,
+    };
+    let path = get_watch_history_path(cfg, target_name)?;
+    let _file_lock = cfg.file_locks.write_lock(&path).map_err(|err| M3uFilterError::new(M3uFilterErrorKind::Info, format!("{err}")))?;
+    let mut history = read_watch_history(&path);
+    add_entry(settings, history.entry(username.to_string()).or_default(), entry, now);
+    history.retain(|_, entries| !entries.is_empty());
+    if let Err(err) = json_write_documents_to_file(&path, &history) {
+        return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "Failed to write watch history {}: {err}", path.to_str().unwrap_or("?"));
+    }
+    Ok(())
+}
+
+/// The retained entries of the user, the last watched first.
+pub fn watch_history_load(cfg: &Config, target_name: &str, username: &str) -> Vec<WatchEntry> {
+    let Some(settings) = &cfg.watch_history else {
+        return vec![];
+    };
+    let Ok(path) = get_watch_history_path(cfg, target_name) else {
+        return vec![];
+    };
+    if !path.exists() {
+        return vec![];
+    }
+    let Ok(_file_lock) = cfg.file_locks.read_lock(&path) else {
+        return vec![];
+    };
+    let now = Local::now().timestamp();
+    let mut entries = read_watch_history(&path).remove(username).unwrap_or_default();
+    entries.retain(|entry| is_retained(settings, entry, now));
+    entries
+}
+
+/// Deletes the history of the user.
+pub fn watch_history_clear(cfg: &Config, target_name: &str, username: &str) -> Result<(), M3uFilterError> {
+    let path = get_watch_history_path(cfg, target_name)?;
+    let _file_lock = cfg.file_locks.write_lock(&path).map_err(|err| M3uFilterError::new(M3uFilterErrorKind::Info, format!("{err}")))?;
+    let mut history = read_watch_history(&path);
+    if history.remove(username).is_some() {
+        if let Err(err) = json_write_documents_to_file(&path, &history) {
+            return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "Failed to write watch history {}: {err}", path.to_str().unwrap_or("?"));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::config::WatchHistoryConfig;
+    use crate::repository::watch_history_repository::{add_entry, WatchEntry, SECONDS_PER_DAY};
+
+    fn entry(stream_id: u32, now: i64) -> WatchEntry {
+        WatchEntry { stream_id, stream_type: "movie".to_string(), series_id: None, name: format!("Movie {stream_id}"),
+            logo: String::new(), play_count: 1, first_watched: now, last_watched: now }
+    }
+
+    #[test]
+    fn test_add_entry() {
+        let settings = WatchHistoryConfig { enabled: true, retention_days: 30, max_entries: 2, exclude_users: vec!["kid".to_string()] };
+        let now = 100 * SECONDS_PER_DAY;
+        let mut entries = vec![entry(1, now - 40 * SECONDS_PER_DAY)];
+        add_entry(&settings, &mut entries, entry(2, now), now);
+        // the entry of stream 1 is older than the retention
+        assert_eq!(entries.iter().map(|item| item.stream_id).collect::<Vec<_>>(), vec![2]);
+        add_entry(&settings, &mut entries, entry(3, now), now);
+        add_entry(&settings, &mut entries, entry(2, now + 60), now + 60);
+        add_entry(&settings, &mut entries, entry(2, now + 7200), now + 7200);
+        assert_eq!(entries.iter().map(|item| (item.stream_id, item.play_count)).collect::<Vec<_>>(), vec![(2, 2), (3, 1)]);
+        assert_eq!(entries[0].first_watched, now);
+        add_entry(&settings, &mut entries, entry(4, now + 7200), now + 7200);
+        assert_eq!(entries.iter().map(|item| item.stream_id).collect::<Vec<_>>(), vec![4, 2]);
+        assert!(!settings.is_recorded("kid"));
+    }
+}
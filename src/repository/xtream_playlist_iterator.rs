@@ -1,8 +1,12 @@
 use log::error;
+use crate::api::api_utils::get_user_server_info;
 use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorKind};
-use crate::model::config::{Config, ConfigTarget};
+use crate::model::api_proxy::ProxyUserCredentials;
+use crate::model::config::{Blackout, Config, ConfigTarget};
 use crate::model::playlist::{XtreamCluster, XtreamPlaylistItem};
 use crate::model::xtream::XtreamMappingOptions;
+use crate::processing::logo_cache::LogoRewrite;
+use crate::processing::token_refresh::TokenRewrite;
 use crate::repository::indexed_document::IndexedDocumentReader;
 use crate::repository::xtream_repository::{xtream_get_file_paths, xtream_get_storage_path};
 use crate::utils::file_lock_manager::FileReadGuard;
@@ -11,6 +15,8 @@ pub struct XtreamPlaylistIterator {
     reader: IndexedDocumentReader<XtreamPlaylistItem>,
     options: XtreamMappingOptions,
     category_id: u32,
+    hide_adult: bool,
+    blackout: Blackout,
     _file_lock: FileReadGuard,
 }
 
@@ -20,6 +26,7 @@ impl XtreamPlaylistIterator {
         config: &Config,
         target: &ConfigTarget,
         category_id: u32,
+        user: &ProxyUserCredentials,
     ) -> Result<Self, M3uFilterError> {
         if let Some(storage_path) = xtream_get_storage_path(config, target.name.as_str()) {
             let (xtream_path, idx_path) = xtream_get_file_paths(&storage_path, cluster);
@@ -30,12 +37,16 @@ impl XtreamPlaylistIterator {
             let reader = IndexedDocumentReader::<XtreamPlaylistItem>::new(&xtream_path, &idx_path)
                 .map_err(|err| M3uFilterError::new(M3uFilterErrorKind::Info, format!("Could not deserialize file {} - {}", &xtream_path.to_str().unwrap(), err)))?;
 
-            let options = XtreamMappingOptions::from_target_options(target.options.as_ref());
+            let mut options = XtreamMappingOptions::from_target_options(target.options.as_ref());
+            options.logo_rewrite = LogoRewrite::new(config, &get_user_server_info(config, user).get_base_url());
+            options.token_rewrite = TokenRewrite::new(config);
 
             Ok(Self {
                 reader,
                 options,
                 category_id,
+                hide_adult: target.hide_adult_content(user),
+                blackout: target.get_blackout(),
                 _file_lock: file_lock,
             })
         } else {
@@ -52,7 +63,9 @@ impl Iterator for XtreamPlaylistIterator {
             error!("Could not deserialize xtream item: {:?}", self.reader.get_path());
             return None;
         }
-        self.reader.find(|pli| self.category_id == 0 || pli.category_id == self.category_id)
+        self.reader.find(|pli| (self.category_id == 0 || pli.category_id == self.category_id)
+            && (!self.hide_adult || pli.parent_code.is_empty())
+            && !self.blackout.is_hidden(&pli.group, &pli.name))
             .map(|pli| pli.to_doc(&self.options).to_string())
     }
 }
\ No newline at end of file
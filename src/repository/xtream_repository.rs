@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
 use std::io::{BufReader, Error, ErrorKind};
 use std::path::{Path, PathBuf};
@@ -9,13 +9,22 @@ use serde_json::{json, Value};
 use crate::{create_m3u_filter_error, create_m3u_filter_error_result};
 use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorKind};
 use crate::model::config::{Config, ConfigTarget};
+use crate::model::api_proxy::ProxyUserCredentials;
 use crate::model::playlist::{PlaylistGroup, PlaylistItem, PlaylistItemType, XtreamCluster, XtreamPlaylistItem};
 use crate::model::xtream::XtreamMappingOptions;
+use crate::api::api_utils::get_user_server_info;
+use crate::processing::logo_cache::LogoRewrite;
+use crate::processing::token_refresh::TokenRewrite;
 use crate::repository::bplustree::{BPlusTreeQuery, BPlusTreeUpdate};
+use crate::repository::favorites_repository::{favorites_get_virtual_ids, FAVORITES_CATEGORY_ID, FAVORITES_GROUP};
+use crate::repository::item_cache::ItemCacheKey;
 use crate::repository::indexed_document::{IndexedDocumentGarbageCollector, IndexedDocumentReader, IndexedDocumentWriter};
 use crate::repository::storage::{FILE_SUFFIX_DB, FILE_SUFFIX_INDEX, get_target_id_mapping_file, get_target_storage_path, hash_string};
+use crate::repository::title_index::{title_index_search, title_index_write};
 use crate::repository::target_id_mapping::{TargetIdMapping, VirtualIdRecord};
 use crate::repository::xtream_playlist_iterator::XtreamPlaylistIterator;
+use crate::utils::file_lock_manager::FileTransaction;
+use crate::utils::file_utils;
 use crate::utils::json_utils::{json_iter_array, json_write_documents_to_file};
 
 pub static COL_CAT_LIVE: &str = "cat_live";
@@ -24,11 +33,13 @@ pub static COL_CAT_VOD: &str = "cat_vod";
 const FILE_SERIES_EPISODES: &str = "series_episodes";
 const FILE_SERIES: &str = "series";
 pub const FILE_EPG: &str = "epg.xml";
+const FILE_CATEGORY_IDS: &str = "category_ids.json";
 const PATH_XTREAM: &str = "xtream";
 const TAG_CATEGORY_ID: &str = "category_id";
 const TAG_CATEGORY_NAME: &str = "category_name";
 const TAG_DIRECT_SOURCE: &str = "direct_source";
 const TAG_PARENT_ID: &str = "parent_id";
+const XTREAM_SEARCH_LIMIT: usize = 200;
 
 macro_rules! cant_write_result {
     ($path:expr, $err:expr) => {
@@ -71,24 +82,22 @@ fn xtream_get_info_file_paths(storage_path: &Path, cluster: XtreamCluster) -> Op
     None
 }
 
-fn write_playlists_to_file(cfg: &Config, storage_path: &Path, collections: Vec<(XtreamCluster, &mut [PlaylistItem])>) -> Result<(), M3uFilterError> {
+fn write_playlists_to_file(storage_path: &Path, collections: Vec<(XtreamCluster, &mut [PlaylistItem])>, transaction: &mut FileTransaction) -> Result<(), M3uFilterError> {
     for (cluster, playlist) in collections {
         let (xtream_path, idx_path) = xtream_get_file_paths(storage_path, cluster);
-        {
-            let _file_lock = cfg.file_locks.write_lock(&xtream_path).map_err(|err| M3uFilterError::new(M3uFilterErrorKind::Info, format!("{err}")))?;
-            match IndexedDocumentWriter::new(xtream_path.clone(), idx_path) {
-                Ok(mut writer) => {
-                    for item in playlist {
-                        let xtream = item.to_xtream();
-                        match writer.write_doc(item.header.borrow().virtual_id, &xtream) {
-                            Ok(()) => {}
-                            Err(err) => return Err(cant_write_result!(&xtream_path, err))
-                        }
+        let (xtream_path, idx_path) = (transaction.stage(&xtream_path), transaction.stage(&idx_path));
+        match IndexedDocumentWriter::new(xtream_path.clone(), idx_path) {
+            Ok(mut writer) => {
+                for item in playlist {
+                    let xtream = item.to_xtream();
+                    match writer.write_doc(item.header.borrow().virtual_id, &xtream) {
+                        Ok(()) => {}
+                        Err(err) => return Err(cant_write_result!(&xtream_path, err))
                     }
-                    writer.store().map_err(|err| cant_write_result!(&xtream_path, err))?;
                 }
-                Err(err) => return Err(cant_write_result!(&xtream_path, err))
+                writer.store().map_err(|err| cant_write_result!(&xtream_path, err))?;
             }
+            Err(err) => return Err(cant_write_result!(&xtream_path, err))
         }
     }
     Ok(())
@@ -103,9 +112,9 @@ fn get_map_item_as_str(map: &serde_json::Map<String, Value>, key: &str) -> Optio
     None
 }
 
-fn load_old_category_ids(path: &Path) -> (u32, HashMap<String, u32>) {
+/// The category ids of the written categories, used when the target has no `category_ids.json`.
+fn load_old_category_ids(path: &Path) -> HashMap<String, u32> {
     let mut result: HashMap<String, u32> = HashMap::new();
-    let mut max_id: u32 = 0;
     for col_path in [
         get_collection_path(path, COL_CAT_LIVE),
         get_collection_path(path, COL_CAT_VOD),
@@ -119,7 +128,6 @@ fn load_old_category_ids(path: &Path) -> (u32, HashMap<String, u32>) {
                             if let Some(category_name) = get_map_item_as_str(item, TAG_CATEGORY_NAME) {
                                 if let Ok(cat_id) = category_id.parse::<u32>() {
                                     result.insert(category_name, cat_id);
-                                    max_id = max_id.max(cat_id);
                                 }
                             }
                         }
@@ -128,7 +136,24 @@ fn load_old_category_ids(path: &Path) -> (u32, HashMap<String, u32>) {
             }
         }
     }
-    (max_id, result)
+    result
+}
+
+/// The ids of all categories the target ever had, the id of a category which disappears is not reused.
+fn load_category_ids(path: &Path) -> HashMap<String, u32> {
+    let file_path = path.join(FILE_CATEGORY_IDS);
+    match std::fs::read_to_string(&file_path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|err| {
+            error!("Failed to read category ids {file_path:?}: {err}");
+            load_old_category_ids(path)
+        }),
+        Err(_) => load_old_category_ids(path),
+    }
+}
+
+fn write_category_ids(path: &Path, category_ids: &HashMap<String, u32>, transaction: &mut FileTransaction) -> std::io::Result<()> {
+    let sorted: BTreeMap<&String, &u32> = category_ids.iter().collect();
+    file_utils::write_atomic(&transaction.stage(&path.join(FILE_CATEGORY_IDS)), |writer| serde_json::to_writer(writer, &sorted).map_err(Error::from))
 }
 
 pub fn xtream_get_storage_path(cfg: &Config, target_name: &str) -> Option<PathBuf> {
@@ -164,7 +189,7 @@ fn xtream_garbage_collect(config: &Config, target_name: &str) -> std::io::Result
     Ok(())
 }
 
-pub fn xtream_write_playlist(target: &ConfigTarget, cfg: &Config, playlist: &mut [PlaylistGroup]) -> Result<(), M3uFilterError> {
+pub fn xtream_write_playlist(target: &ConfigTarget, cfg: &Config, playlist: &mut [PlaylistGroup], transaction: &mut FileTransaction) -> Result<(), M3uFilterError> {
     let path = ensure_xtream_storage_path(cfg, target.name.as_str())?;
     let mut errors = Vec::new();
     let mut cat_live_col = vec![];
@@ -174,16 +199,23 @@ pub fn xtream_write_playlist(target: &ConfigTarget, cfg: &Config, playlist: &mut
     let mut series_col = vec![];
     let mut vod_col = vec![];
 
-    // preserve category_ids
-    let (max_cat_id, existing_cat_ids) = load_old_category_ids(&path);
-    let mut cat_id_counter = max_cat_id;
-    for plg in playlist.iter_mut() {
+    // preserve category_ids, only new categories get a new id
+    let mut category_ids = load_category_ids(&path);
+    let mut cat_id_counter = category_ids.values().copied().max().unwrap_or(0);
+    let categories = target.options.as_ref().and_then(|options| options.xtream_categories.as_ref());
+    let mut group_indices: Vec<usize> = (0..playlist.len()).collect();
+    if let Some(categories) = categories {
+        group_indices.sort_by_key(|&idx| categories.get_position(&playlist[idx].title));
+    }
+    for idx in group_indices {
+        let plg = &mut playlist[idx];
         if !&plg.channels.is_empty() {
-            let cat_id = existing_cat_ids.get(plg.title.as_ref()).unwrap_or_else(|| {
+            let cat_id = *category_ids.entry(plg.title.to_string()).or_insert_with(|| {
                 cat_id_counter += 1;
-                &cat_id_counter
+                cat_id_counter
             });
-            plg.id = *cat_id;
+            plg.id = cat_id;
+            let category_name = categories.map_or(plg.title.as_str(), |categories| categories.get_name(&plg.title)).to_string();
 
             match &plg.xtream_cluster {
                 XtreamCluster::Live => &mut cat_live_col,
@@ -191,7 +223,7 @@ pub fn xtream_write_playlist(target: &ConfigTarget, cfg: &Config, playlist: &mut
                 XtreamCluster::Video => &mut cat_vod_col,
             }.push(json!({
               TAG_CATEGORY_ID: format!("{}", &cat_id),
-              TAG_CATEGORY_NAME: plg.title.clone(),
+              TAG_CATEGORY_NAME: category_name,
               TAG_PARENT_ID: 0
             }));
 
@@ -202,13 +234,13 @@ pub fn xtream_write_playlist(target: &ConfigTarget, cfg: &Config, playlist: &mut
                         // we skip resolved series, because this is only necessary when writing m3u files
                         None
                     },
-                    PlaylistItemType::LiveUnknown | PlaylistItemType::LiveHls => {
-                        header.category_id = *cat_id;
+                    PlaylistItemType::LiveUnknown | PlaylistItemType::LiveHls | PlaylistItemType::LiveDirect => {
+                        header.category_id = cat_id;
                         Some(&mut live_col)
                     },
                     _ => {
                         if header.get_provider_id().is_some() {
-                            header.category_id = *cat_id;
+                            header.category_id = cat_id;
                             Some(match header.xtream_cluster {
                                 XtreamCluster::Live => &mut live_col,
                                 XtreamCluster::Series => &mut series_col,
@@ -229,11 +261,15 @@ pub fn xtream_write_playlist(target: &ConfigTarget, cfg: &Config, playlist: &mut
         }
     }
 
+    if let Err(err) = write_category_ids(&path, &category_ids, transaction) {
+        errors.push(format!("Persisting category ids failed: {err}"));
+    }
+
     for (col_path, data) in [
         (get_collection_path(&path, COL_CAT_LIVE), &cat_live_col),
         (get_collection_path(&path, COL_CAT_VOD), &cat_vod_col),
         (get_collection_path(&path, COL_CAT_SERIES), &cat_series_col)] {
-        match json_write_documents_to_file(&col_path, data) {
+        match json_write_documents_to_file(&transaction.stage(&col_path), data) {
             Ok(()) => {}
             Err(err) => {
                 errors.push(format!("Persisting collection failed: {}: {}", &col_path.to_str().unwrap(), err));
@@ -241,16 +277,21 @@ pub fn xtream_write_playlist(target: &ConfigTarget, cfg: &Config, playlist: &mut
         }
     }
 
-    match write_playlists_to_file(cfg, &path, vec![
+    match write_playlists_to_file(&path, vec![
         (XtreamCluster::Live, &mut live_col),
         (XtreamCluster::Video, &mut vod_col),
-        (XtreamCluster::Series, &mut series_col)]) {
+        (XtreamCluster::Series, &mut series_col)], transaction) {
         Ok(()) => {
             if let Err(err) = xtream_garbage_collect(cfg, &target.name) {
                 if err.kind() != ErrorKind::NotFound {
                     errors.push(format!("Garbage collection failed:{err}"));
                 }
             }
+            for (cluster, items) in [(XtreamCluster::Video, &vod_col), (XtreamCluster::Series, &series_col)] {
+                if let Err(err) = title_index_write(cfg, &path, cluster, items) {
+                    errors.push(format!("Persisting title index failed:{err}"));
+                }
+            }
         }
         Err(err) => {
             errors.push(format!("Persisting collection failed:{err}"));
@@ -271,7 +312,7 @@ pub fn xtream_get_collection_path(cfg: &Config, target_name: &str, collection_na
             return Ok((Some(col_path), None));
         }
     }
-    Err(Error::new(ErrorKind::Other, format!("Cant find collection: {target_name}/{collection_name}")))
+    Err(Error::other(format!("Cant find collection: {target_name}/{collection_name}")))
 }
 
 fn xtream_read_item_for_stream_id(cfg: &Config, stream_id: u32, storage_path: &Path, cluster: XtreamCluster) -> Result<XtreamPlaylistItem, Error> {
@@ -293,7 +334,7 @@ fn xtream_read_series_item_for_stream_id(cfg: &Config, stream_id: u32, storage_p
 macro_rules! try_cluster {
     ($xtream_cluster:expr, $item_type:expr, $virtual_id:expr) => {
         $xtream_cluster.or_else(|| XtreamCluster::try_from($item_type).ok())
-            .ok_or_else(|| Error::new(ErrorKind::Other, format!("Could not determine cluster for xtream item with stream-id {}", $virtual_id)))
+            .ok_or_else(|| Error::other(format!("Could not determine cluster for xtream item with stream-id {}", $virtual_id)))
     };
 }
 
@@ -304,45 +345,116 @@ pub fn xtream_get_item_for_stream_id(
     xtream_cluster: Option<XtreamCluster>,
 ) -> Result<XtreamPlaylistItem, Error> {
     let target_path = get_target_storage_path(config, target.name.as_str())
-        .ok_or_else(|| Error::new(ErrorKind::Other, format!("Could not find path for target {}", &target.name)))?;
+        .ok_or_else(|| Error::other(format!("Could not find path for target {}", &target.name)))?;
     let storage_path = xtream_get_storage_path(config, target.name.as_str())
-        .ok_or_else(|| Error::new(ErrorKind::Other, format!("Could not find path for target {} xtream output", &target.name)))?;
-    {
-        let target_id_mapping_file = get_target_id_mapping_file(&target_path);
-        let _file_lock = config.file_locks.read_lock(&target_id_mapping_file)
-            .map_err(|err| Error::new(ErrorKind::Other, format!("Could not get lock for id mapping for target {} err:{err}", target.name)))?;
+        .ok_or_else(|| Error::other(format!("Could not find path for target {} xtream output", &target.name)))?;
+    let cache_key = ItemCacheKey::new(&storage_path, virtual_id, xtream_cluster);
+    if let Some(item) = config.t_item_cache.get(&cache_key) {
+        return Ok(item);
+    }
+    let item = xtream_read_item_for_virtual_id(virtual_id, config, target, xtream_cluster, &target_path, &storage_path)?;
+    config.t_item_cache.put(cache_key, &item);
+    Ok(item)
+}
 
-        let mut target_id_mapping = BPlusTreeQuery::<u32, VirtualIdRecord>::try_new(&target_id_mapping_file)
-            .map_err(|err| Error::new(ErrorKind::Other, format!("Could not load id mapping for target {} err:{err}", target.name)))?;
-
-        let mapping = target_id_mapping
-            .query(&virtual_id)
-            .ok_or_else(|| Error::new(ErrorKind::Other, format!("Could not find mapping for target {} and id {}", target.name, virtual_id)))?;
-
-        match mapping.item_type {
-            PlaylistItemType::SeriesInfo => xtream_read_series_item_for_stream_id(config, virtual_id, &storage_path),
-            PlaylistItemType::SeriesEpisode => {
-                let mut item = xtream_read_series_item_for_stream_id(config, mapping.parent_virtual_id, &storage_path)?;
-                item.provider_id = mapping.provider_id;
-                Ok(item)
-            }
-            PlaylistItemType::Catchup => {
-                let cluster = try_cluster!(xtream_cluster, mapping.item_type, virtual_id)?;
-                let mut item = xtream_read_item_for_stream_id(config, mapping.parent_virtual_id, &storage_path, cluster)?;
-                item.provider_id = mapping.provider_id;
-                Ok(item)
-            }
-            _ => {
-                let cluster = try_cluster!(xtream_cluster, mapping.item_type, virtual_id)?;
-                xtream_read_item_for_stream_id(config, virtual_id, &storage_path, cluster)
-            }
+fn xtream_read_item_for_virtual_id(
+    virtual_id: u32,
+    config: &Config,
+    target: &ConfigTarget,
+    xtream_cluster: Option<XtreamCluster>,
+    target_path: &Path,
+    storage_path: &Path,
+) -> Result<XtreamPlaylistItem, Error> {
+    let target_id_mapping_file = get_target_id_mapping_file(target_path);
+    let _file_lock = config.file_locks.read_lock(&target_id_mapping_file)
+        .map_err(|err| Error::other(format!("Could not get lock for id mapping for target {} err:{err}", target.name)))?;
+
+    let mut target_id_mapping = BPlusTreeQuery::<u32, VirtualIdRecord>::try_new(&target_id_mapping_file)
+        .map_err(|err| Error::other(format!("Could not load id mapping for target {} err:{err}", target.name)))?;
+
+    let mapping = target_id_mapping
+        .query(&virtual_id)
+        .ok_or_else(|| Error::other(format!("Could not find mapping for target {} and id {}", target.name, virtual_id)))?;
+
+    match mapping.item_type {
+        PlaylistItemType::SeriesInfo => xtream_read_series_item_for_stream_id(config, virtual_id, storage_path),
+        PlaylistItemType::SeriesEpisode => {
+            let mut item = xtream_read_series_item_for_stream_id(config, mapping.parent_virtual_id, storage_path)?;
+            item.provider_id = mapping.provider_id;
+            Ok(item)
+        }
+        PlaylistItemType::Catchup => {
+            let cluster = try_cluster!(xtream_cluster, mapping.item_type, virtual_id)?;
+            let mut item = xtream_read_item_for_stream_id(config, mapping.parent_virtual_id, storage_path, cluster)?;
+            item.provider_id = mapping.provider_id;
+            Ok(item)
+        }
+        _ => {
+            let cluster = try_cluster!(xtream_cluster, mapping.item_type, virtual_id)?;
+            xtream_read_item_for_stream_id(config, virtual_id, storage_path, cluster)
         }
     }
 }
 
 
-pub fn xtream_load_rewrite_playlist(cluster: XtreamCluster, config: &Config, target: &ConfigTarget, category_id: u32) -> Result<Box<dyn Iterator<Item=String>>, M3uFilterError> {
-    Ok(Box::new(XtreamPlaylistIterator::new(cluster, config, target, category_id)?))
+pub fn xtream_load_rewrite_playlist(cluster: XtreamCluster, config: &Config, target: &ConfigTarget, category_id: u32, user: &ProxyUserCredentials) -> Result<Box<dyn Iterator<Item=String>>, M3uFilterError> {
+    Ok(Box::new(XtreamPlaylistIterator::new(cluster, config, target, category_id, user)?))
+}
+
+/// Returns the items of the cluster whose name or title contains all words of the query.
+pub fn xtream_search_items(cluster: XtreamCluster, config: &Config, target: &ConfigTarget, query: &str, limit: usize) -> Result<Vec<XtreamPlaylistItem>, M3uFilterError> {
+    let storage_path = xtream_get_storage_path(config, target.name.as_str())
+        .ok_or_else(|| M3uFilterError::new(M3uFilterErrorKind::Info, format!("Failed to find xtream storage for target {}", &target.name)))?;
+    let virtual_ids = title_index_search(config, &storage_path, cluster, query)?;
+    let (xtream_path, idx_path) = xtream_get_file_paths(&storage_path, cluster);
+    let _file_lock = config.file_locks.read_lock(&xtream_path).map_err(|err| M3uFilterError::new(M3uFilterErrorKind::Info, format!("{err}")))?;
+    Ok(virtual_ids.into_iter().take(limit)
+        .filter_map(|virtual_id| IndexedDocumentReader::<XtreamPlaylistItem>::read_indexed_item(&xtream_path, &idx_path, virtual_id).ok())
+        .collect())
+}
+
+/// Same as `xtream_search_items`, but rewritten for the user like `xtream_load_rewrite_playlist`.
+pub fn xtream_search_rewrite_playlist(cluster: XtreamCluster, config: &Config, target: &ConfigTarget, query: &str, user: &ProxyUserCredentials) -> Result<Vec<Value>, M3uFilterError> {
+    let mut options = XtreamMappingOptions::from_target_options(target.options.as_ref());
+    options.logo_rewrite = LogoRewrite::new(config, &get_user_server_info(config, user).get_base_url());
+    options.token_rewrite = TokenRewrite::new(config);
+    let hide_adult = target.hide_adult_content(user);
+    let blackout = target.get_blackout();
+    Ok(xtream_search_items(cluster, config, target, query, XTREAM_SEARCH_LIMIT)?.iter()
+        .filter(|pli| (!hide_adult || pli.parent_code.is_empty()) && !blackout.is_hidden(&pli.group, &pli.name))
+        .map(|pli| pli.to_doc(&options))
+        .collect())
+}
+
+/// The live category of the favorites.
+pub fn xtream_favorites_category() -> Value {
+    json!({
+      TAG_CATEGORY_ID: format!("{FAVORITES_CATEGORY_ID}"),
+      TAG_CATEGORY_NAME: FAVORITES_GROUP,
+      TAG_PARENT_ID: 0
+    })
+}
+
+/// The live favorites of the user in the favorites category, rewritten like `xtream_load_rewrite_playlist`.
+pub fn xtream_load_favorites(config: &Config, target: &ConfigTarget, user: &ProxyUserCredentials) -> Result<Vec<Value>, M3uFilterError> {
+    let storage_path = xtream_get_storage_path(config, target.name.as_str())
+        .ok_or_else(|| M3uFilterError::new(M3uFilterErrorKind::Info, format!("Failed to find xtream storage for target {}", &target.name)))?;
+    let virtual_ids = favorites_get_virtual_ids(config, &target.name, &user.username);
+    let mut options = XtreamMappingOptions::from_target_options(target.options.as_ref());
+    options.logo_rewrite = LogoRewrite::new(config, &get_user_server_info(config, user).get_base_url());
+    options.token_rewrite = TokenRewrite::new(config);
+    let hide_adult = target.hide_adult_content(user);
+    let blackout = target.get_blackout();
+    let (xtream_path, idx_path) = xtream_get_file_paths(&storage_path, XtreamCluster::Live);
+    let _file_lock = config.file_locks.read_lock(&xtream_path).map_err(|err| M3uFilterError::new(M3uFilterErrorKind::Info, format!("{err}")))?;
+    Ok(virtual_ids.into_iter()
+        .filter_map(|virtual_id| IndexedDocumentReader::<XtreamPlaylistItem>::read_indexed_item(&xtream_path, &idx_path, virtual_id).ok())
+        .filter(|pli| (!hide_adult || pli.parent_code.is_empty()) && !blackout.is_hidden(&pli.group, &pli.name))
+        .map(|mut pli| {
+            pli.category_id = FAVORITES_CATEGORY_ID;
+            pli.to_doc(&options)
+        })
+        .collect())
 }
 
 pub fn xtream_write_series_info(config: &Config, target_name: &str,
@@ -357,7 +469,7 @@ pub fn xtream_write_series_info(config: &Config, target_name: &str,
         let mut writer = IndexedDocumentWriter::new_append(info_path, idx_path)?;
         writer
             .write_doc(series_info_id, content)
-            .map_err(|_| Error::new(ErrorKind::Other, format!("failed to write xtream series info for target {target_name}")))?;
+            .map_err(|_| Error::other(format!("failed to write xtream series info for target {target_name}")))?;
 
         writer.store()?;
     }
@@ -384,12 +496,12 @@ pub fn xtream_load_series_info(config: &Config, target_name: &str, series_id: u3
         let target_id_mapping_file = get_target_id_mapping_file(&target_path);
         let _file_lock = config.file_locks.read_lock(&target_id_mapping_file).map_err(|err| {
             error!("Could not lock id mapping for target {target_name}: {}", err);
-            Error::new(ErrorKind::Other, format!("ID mapping load error for target {target_name}"))
+            Error::other(format!("ID mapping load error for target {target_name}"))
         }).ok()?;
         let mut target_id_mapping = BPlusTreeQuery::<u32, VirtualIdRecord>::try_new(&target_id_mapping_file)
             .map_err(|err| {
                 error!("Could not load id mapping for target {target_name}: {}", err);
-                Error::new(ErrorKind::Other, format!("ID mapping load error for target {target_name}"))
+                Error::other(format!("ID mapping load error for target {target_name}"))
             }).ok()?;
 
         if let Some(id_record) = target_id_mapping.query(&series_id) {
@@ -405,7 +517,7 @@ pub fn xtream_load_series_info(config: &Config, target_name: &str, series_id: u3
         {
             let _file_lock = config.file_locks.read_lock(&info_path).map_err(|err| {
                 error!("Could not lock document {:?}: {}", info_path, err);
-                Error::new(ErrorKind::Other, format!("Document Reader error for target {target_name}"))
+                Error::other(format!("Document Reader error for target {target_name}"))
             }).ok()?;
             return match IndexedDocumentReader::<String>::read_indexed_item(&info_path, &idx_path, series_id) {
                 Ok(content) => Some(content),
@@ -426,19 +538,19 @@ pub fn write_and_get_xtream_series_info(
     content: &str,
 ) -> Result<String, Error> {
     let mut doc = serde_json::from_str::<Value>(content)
-        .map_err(|_| Error::new(ErrorKind::Other, "Failed to parse JSON content"))?;
+        .map_err(|_| Error::other("Failed to parse JSON content"))?;
 
     let target_path = get_target_storage_path(config, target.name.as_str())
-        .ok_or_else(|| Error::new(ErrorKind::Other, format!("Could not find path for target {}", target.name)))?;
+        .ok_or_else(|| Error::other(format!("Could not find path for target {}", target.name)))?;
 
     let episodes = doc.get_mut("episodes")
         .and_then(Value::as_object_mut)
-        .ok_or_else(|| Error::new(ErrorKind::Other, "No episodes found in content"))?;
+        .ok_or_else(|| Error::other("No episodes found in content"))?;
 
     {
         let target_id_mapping_file = get_target_id_mapping_file(&target_path);
         let _file_lock = config.file_locks.write_lock(&target_id_mapping_file)
-            .map_err(|err| Error::new(ErrorKind::Other, format!("Could not load id mapping for target {} err:{err}", target.name)))?;
+            .map_err(|err| Error::other(format!("Could not load id mapping for target {} err:{err}", target.name)))?;
         let mut target_id_mapping = TargetIdMapping::new(&target_id_mapping_file);
         let options = XtreamMappingOptions::from_target_options(target.options.as_ref());
 
@@ -458,8 +570,95 @@ pub fn write_and_get_xtream_series_info(
         drop(target_id_mapping);
     }
     let result = serde_json::to_string(&doc)
-        .map_err(|_| Error::new(ErrorKind::Other, "Failed to serialize updated series info"))?;
+        .map_err(|_| Error::other("Failed to serialize updated series info"))?;
     xtream_write_series_info(config, target.name.as_str(), pli_series_info.virtual_id, &result).ok();
 
     Ok(result)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::model::api_proxy::{ApiProxyConfig, ProxyUserCredentials};
+    use crate::model::config::{Config, ConfigTarget, ConfigTargetOptions, XtreamCategoriesConfig};
+    use crate::model::playlist::{PlaylistGroup, PlaylistItem, PlaylistItemHeader, PlaylistItemType, XtreamCluster};
+    use crate::repository::storage::{ensure_target_storage_path, get_target_id_mapping_file};
+    use crate::repository::target_id_mapping::TargetIdMapping;
+    use crate::repository::xtream_repository::{xtream_get_collection_path, xtream_get_item_for_stream_id, xtream_load_rewrite_playlist, xtream_write_playlist, COL_CAT_LIVE};
+    use crate::utils::test_utils::create_temp_dir;
+
+    fn live_group(title: &str, provider_id: u32) -> PlaylistGroup {
+        let header = PlaylistItemHeader { id: Rc::new(provider_id.to_string()), name: Rc::new(title.to_string()), group: Rc::new(title.to_string()),
+            url: Rc::new(format!("http://provider.tv/live/u/p/{provider_id}.ts")), item_type: PlaylistItemType::Live, ..PlaylistItemHeader::default() };
+        PlaylistGroup { id: 0, title: Rc::new(title.to_string()), channels: vec![PlaylistItem { header: RefCell::new(header) }], xtream_cluster: XtreamCluster::Live }
+    }
+
+    fn write_playlist(cfg: &Config, target: &ConfigTarget, playlist: &mut [PlaylistGroup]) {
+        let target_path = ensure_target_storage_path(cfg, &target.name).unwrap();
+        let mut transaction = cfg.file_locks.begin_transaction(&target_path).unwrap();
+        xtream_write_playlist(target, cfg, playlist, &mut transaction).unwrap();
+        transaction.commit().unwrap();
+    }
+
+    fn read_categories(cfg: &Config, target: &ConfigTarget) -> Vec<(String, String)> {
+        let (path, _) = xtream_get_collection_path(cfg, &target.name, COL_CAT_LIVE).unwrap();
+        let categories: Vec<serde_json::Value> = serde_json::from_str(&std::fs::read_to_string(path.unwrap()).unwrap()).unwrap();
+        categories.iter().map(|cat| (cat["category_id"].as_str().unwrap().to_string(), cat["category_name"].as_str().unwrap().to_string())).collect()
+    }
+
+    #[test]
+    fn test_category_ids() {
+        let temp_dir = create_temp_dir("categories");
+        let working_dir = temp_dir.path();
+        let cfg = Config { working_dir: working_dir.to_str().unwrap().to_string(), ..Config::default() };
+        let mut target = ConfigTarget { name: "tv".to_string(), ..ConfigTarget::default() };
+
+        write_playlist(&cfg, &target, &mut [live_group("News", 1), live_group("Sports", 2)]);
+        assert_eq!(read_categories(&cfg, &target), vec![("1".to_string(), "News".to_string()), ("2".to_string(), "Sports".to_string())]);
+
+        // a category which disappears keeps its id, new categories don't reuse it
+        write_playlist(&cfg, &target, &mut [live_group("Sports", 2), live_group("Kids", 3)]);
+        assert_eq!(read_categories(&cfg, &target), vec![("2".to_string(), "Sports".to_string()), ("3".to_string(), "Kids".to_string())]);
+
+        let categories = XtreamCategoriesConfig { order: vec!["Children".to_string()], rename: [("Kids".to_string(), "Children".to_string())].into_iter().collect() };
+        target.options = Some(ConfigTargetOptions { xtream_categories: Some(categories), ..ConfigTargetOptions::default() });
+        write_playlist(&cfg, &target, &mut [live_group("News", 1), live_group("Sports", 2), live_group("Kids", 3)]);
+        assert_eq!(read_categories(&cfg, &target), vec![("3".to_string(), "Children".to_string()), ("1".to_string(), "News".to_string()), ("2".to_string(), "Sports".to_string())]);
+    }
+
+    #[test]
+    fn test_adult_content() {
+        let temp_dir = create_temp_dir("adult");
+        let working_dir = temp_dir.path();
+        let mut cfg = Config { working_dir: working_dir.to_str().unwrap().to_string(), ..Config::default() };
+        let api_proxy: ApiProxyConfig = serde_yaml::from_str("server: [{name: default, protocol: http, host: localhost, timezone: UTC, message: ''}]\nuser: []").unwrap();
+        cfg.set_api_proxy(Some(api_proxy));
+        let target = ConfigTarget { name: "tv".to_string(), t_adult_groups: Some(vec![regex::Regex::new("(?i)adult").unwrap()]), ..ConfigTarget::default() };
+        let mut playlist = [live_group("News", 1), live_group("Adult", 2)];
+        playlist[1].channels[0].header.borrow_mut().parent_code = std::rc::Rc::new("1".to_string());
+        let target_path = ensure_target_storage_path(&cfg, &target.name).unwrap();
+        let mut target_id_mapping = TargetIdMapping::new(&get_target_id_mapping_file(&target_path));
+        for channel in playlist.iter().flat_map(|group| &group.channels) {
+            let mut header = channel.header.borrow_mut();
+            let provider_id = header.get_provider_id().unwrap_or_default();
+            let uuid = header.get_uuid();
+            let item_type = header.item_type;
+            header.virtual_id = target_id_mapping.insert_entry(**uuid, provider_id, item_type, 0);
+        }
+        target_id_mapping.persist().unwrap();
+        let adult_id = playlist[1].channels[0].header.borrow().virtual_id;
+        write_playlist(&cfg, &target, &mut playlist);
+
+        let mut user: ProxyUserCredentials = serde_yaml::from_str("{username: u, password: p, parental_pin: '1234'}").unwrap();
+        let listed = |user: &ProxyUserCredentials| xtream_load_rewrite_playlist(XtreamCluster::Live, &cfg, &target, 0, user).unwrap().count();
+        let adult_item = xtream_get_item_for_stream_id(adult_id, &cfg, &target, Some(XtreamCluster::Live)).unwrap();
+        assert_eq!(listed(&user), 1);
+        // the stream and info requests are refused with 403
+        assert!(target.is_adult_item_hidden(&user, &adult_item.parent_code));
+        user.t_adult_unlocked = true;
+        assert_eq!(listed(&user), 2);
+        assert!(!target.is_adult_item_hidden(&user, &adult_item.parent_code));
+    }
+}
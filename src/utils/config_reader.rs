@@ -1,18 +1,22 @@
 use std::env;
 use std::fs::File;
-use std::path::PathBuf;
+use std::io::Read;
+use std::path::{Path, PathBuf};
 use std::sync::LazyLock;
 use chrono::Local;
 use log::{debug, error, info, warn};
 use regex::Regex;
+use serde::de::DeserializeOwned;
 use serde::Serialize;
 
 use crate::{create_m3u_filter_error_result, handle_m3u_filter_error_result};
-use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorKind};
+use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorCategory, M3uFilterErrorKind};
 use crate::model::api_proxy::ApiProxyConfig;
-use crate::model::config::{Config, ConfigDto};
+use crate::model::config::{Config, ConfigDto, LogConfig, LoggingConfig};
 use crate::model::mapping::Mappings;
-use crate::utils::{file_utils, multi_file_reader};
+use crate::utils::{file_utils, multi_file_reader, yaml_utils};
+use crate::utils::config_schema;
+use crate::utils::config_schema::{find_unknown_fields, ConfigSchema};
 
 pub fn read_mappings(args_mapping: Option<String>, cfg: &mut Config) -> Result<(), M3uFilterError> {
     let mappings_file: String = args_mapping.unwrap_or_else(|| file_utils::get_default_mappings_path(cfg.t_config_path.as_str()));
@@ -25,7 +29,7 @@ pub fn read_mappings(args_mapping: Option<String>, cfg: &mut Config) -> Result<(
             }
             Ok(())
         }
-        Err(err) => Err(err),
+        Err(err) => Err(err.with_category(M3uFilterErrorCategory::Config)),
     }
 }
 
@@ -44,11 +48,16 @@ pub fn read_api_proxy_config(args_api_proxy_config: Option<String>, cfg: &mut Co
     }
 }
 
-pub fn read_config(config_path: &str, config_file: &str, sources_file: &str) -> Result<Config, M3uFilterError> {
+/// Without `resolve_var` the string values keep their `${VAR}` variables, the config is read for the web ui.
+pub fn read_config(config_path: &str, config_file: &str, sources_file: &str, resolve_var: bool) -> Result<Config, M3uFilterError> {
     let files = vec![std::path::PathBuf::from(config_file), std::path::PathBuf::from(sources_file)];
     match multi_file_reader::MultiFileReader::new(&files) {
-        Ok(file) => {
-            match serde_yaml::from_reader::<_, Config>(file) {
+        Ok(mut file) => {
+            let mut content = String::new();
+            if let Err(err) = file.read_to_string(&mut content) {
+                return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "cant read config file: {}", err);
+            }
+            match parse_config_document::<Config>(&content, Path::new(config_path), ConfigSchema::Config, resolve_var) {
                 Ok(mut result) => {
                     result.t_config_path = config_path.to_string();
                     result.t_config_file_path = config_file.to_string();
@@ -64,17 +73,48 @@ pub fn read_config(config_path: &str, config_file: &str, sources_file: &str) ->
             }
         }
         Err(err) => create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "{}", err)
+    }.map_err(|err: M3uFilterError| err.with_category(M3uFilterErrorCategory::Config))
+}
+
+/// Reads the `log` section of the config file, `None` if the file can't be read.
+pub fn read_log_config(config_file: &str) -> Option<LogConfig> {
+    let file = File::open(config_file).ok()?;
+    serde_yaml::from_reader::<_, LoggingConfig>(file).ok()?.log
+}
+
+/// Logs the fields which are ignored because they are unknown, misspelled or renamed.
+fn warn_unknown_fields(schema: ConfigSchema, document: &serde_yaml::Value) {
+    for message in find_unknown_fields(schema, document) {
+        warn!("{message}");
+    }
+}
+
+/// Parses the yaml config with `!include` resolved relative to `base_dir` and `${VAR}` interpolated in the values.
+fn parse_config_document<T: DeserializeOwned>(content: &str, base_dir: &Path, schema: ConfigSchema, resolve_var: bool) -> Result<T, String> {
+    let mut document = serde_yaml::from_str::<serde_yaml::Value>(content).map_err(|err| err.to_string())?;
+    let included = yaml_utils::resolve_includes(&mut document, base_dir, 0)?;
+    let interpolated = config_schema::interpolate_env_vars(schema, &mut document, resolve_var);
+    // the file is read again for the web ui, the warnings are logged only on startup
+    if resolve_var {
+        warn_unknown_fields(schema, &document);
+    }
+    if included || interpolated {
+        serde_yaml::from_value(document).map_err(|err| err.to_string())
+    } else {
+        // parsing the text keeps the line numbers in the error messages
+        serde_yaml::from_str(content).map_err(|err| err.to_string())
     }
 }
 
 pub fn read_mapping(mapping_file: &str) -> Result<Option<Mappings>, M3uFilterError> {
     let mapping_file = std::path::PathBuf::from(mapping_file);
-    if let Ok(file) = file_utils::open_file(&mapping_file) {
+    if let Ok(content) = std::fs::read_to_string(&mapping_file) {
         info!("Mapping file: {}", mapping_file.to_str().unwrap_or("?"));
-        let mapping: Result<Mappings, _> = serde_yaml::from_reader(file);
+        let base_dir = mapping_file.parent().unwrap_or_else(|| Path::new("."));
+        let mapping: Result<Mappings, _> = parse_config_document(&content, base_dir, ConfigSchema::Mapping, true);
         match mapping {
             Ok(mut result) => {
-                handle_m3u_filter_error_result!(M3uFilterErrorKind::Info, result.prepare());
+                handle_m3u_filter_error_result!(M3uFilterErrorKind::Info, result.prepare(base_dir));
                 return Ok(Some(result));
             },
             Err(err) => {
@@ -86,9 +126,11 @@ pub fn read_mapping(mapping_file: &str) -> Result<Option<Mappings>, M3uFilterErr
     Ok(None)
 }
 
+/// Without `resolve_var` the values keep their `${VAR}` and `${env:VAR}` variables, the config is read for the web ui.
 pub fn read_api_proxy(api_proxy_file: &str, resolve_var: bool) -> Option<ApiProxyConfig> {
-    file_utils::open_file(&std::path::PathBuf::from(api_proxy_file)).map_or(None, |file| {
-            let mapping: Result<ApiProxyConfig, _> = serde_yaml::from_reader(file);
+    std::fs::read_to_string(api_proxy_file).map_or(None, |content| {
+            let base_dir = Path::new(api_proxy_file).parent().unwrap_or_else(|| Path::new("."));
+            let mapping: Result<ApiProxyConfig, _> = parse_config_document(&content, base_dir, ConfigSchema::ApiProxy, resolve_var);
             match mapping {
                 Ok(mut result) => {
                     match result.prepare(resolve_var) {
@@ -109,13 +151,46 @@ pub fn read_api_proxy(api_proxy_file: &str, resolve_var: bool) -> Option<ApiProx
         })
 }
 
-fn write_config_file<T>(file_path: &str, backup_dir: &str, config: &T, default_name: &str) -> Result<(), M3uFilterError>
+/// The documents of a saved config file before and after the save, as compared by the audit log.
+/// Both sides are serialized the same way and keep their `${VAR}` variables and `!include`s,
+/// `old` is `Null` if the file did not exist.
+pub struct ConfigFileChange {
+    pub old: serde_yaml::Value,
+    pub new: serde_yaml::Value,
+}
+
+/// The raw document of the config file and the document with resolved `!include`s and typed `${VAR}`s.
+fn read_raw_config_file(path: &Path, schema: ConfigSchema) -> Option<(serde_yaml::Value, serde_yaml::Value)> {
+    let raw = std::fs::read_to_string(path).ok().and_then(|content| serde_yaml::from_str::<serde_yaml::Value>(&content).ok())?;
+    let mut resolved = raw.clone();
+    yaml_utils::resolve_includes(&mut resolved, path.parent().unwrap_or_else(|| Path::new(".")), 0).ok()?;
+    config_schema::interpolate_env_vars(schema, &mut resolved, false);
+    Some((raw, resolved))
+}
+
+/// The content of the config file as it would be written unchanged, for the audit of the saved changes.
+fn normalize_config_file<T: DeserializeOwned + Serialize>(raw: &serde_yaml::Value, resolved: &serde_yaml::Value) -> serde_yaml::Value {
+    serde_yaml::from_value::<T>(resolved.clone()).ok()
+        .and_then(|config| serde_yaml::to_value(config).ok())
+        .map_or_else(|| raw.clone(), |value| yaml_utils::preserve_raw_values(raw, resolved, value))
+}
+
+fn write_config_file<T>(file_path: &str, backup_dir: &str, config: &T, schema: ConfigSchema, default_name: &str) -> Result<ConfigFileChange, M3uFilterError>
     where
-        T: ?Sized + Serialize {
+        T: DeserializeOwned + Serialize {
     let path = PathBuf::from(file_path);
     let filename = path.file_name().map_or(default_name.to_string(), |f| f.to_string_lossy().to_string());
     let backup_path = PathBuf::from(backup_dir).join(format!("{}_{}", filename, Local::now().format("%Y%m%d_%H%M%S")));
 
+    // the unchanged values keep their `${VAR}` variables and `!include`s
+    let raw = read_raw_config_file(&path, schema);
+    let content = match serde_yaml::to_value(config) {
+        Ok(value) => match &raw {
+            Some((raw, resolved)) => yaml_utils::preserve_raw_values(raw, resolved, value),
+            None => value,
+        },
+        Err(err) => return create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "Could not serialize file {}: {}", &path.to_str().unwrap_or("?"), err),
+    };
 
     match std::fs::copy(&path, &backup_path) {
         Ok(_) => {}
@@ -124,19 +199,20 @@ fn write_config_file<T>(file_path: &str, backup_dir: &str, config: &T, default_n
     info!("Saving file to {}", &path.to_str().unwrap_or("?"));
     match File::create(&path) {
         Ok(f) => {
-            serde_yaml::to_writer(f, &config).unwrap();
-            Ok(())
+            serde_yaml::to_writer(f, &content).unwrap();
+            let old = raw.map(|(raw, resolved)| normalize_config_file::<T>(&raw, &resolved)).unwrap_or_default();
+            Ok(ConfigFileChange { old, new: content })
         }
         Err(err) => create_m3u_filter_error_result!(M3uFilterErrorKind::Info, "Could not write file {}: {}", &path.to_str().unwrap_or("?"), err)
     }
 }
 
-pub fn save_api_proxy(file_path: &str, backup_dir: &str, config: &ApiProxyConfig) -> Result<(), M3uFilterError> {
-    write_config_file(file_path, backup_dir, config, "api-proxy.yml")
+pub fn save_api_proxy(file_path: &str, backup_dir: &str, config: &ApiProxyConfig) -> Result<ConfigFileChange, M3uFilterError> {
+    write_config_file(file_path, backup_dir, config, ConfigSchema::ApiProxy, "api-proxy.yml")
 }
 
-pub fn save_main_config(file_path: &str, backup_dir: &str, config: &ConfigDto) -> Result<(), M3uFilterError> {
-    write_config_file(file_path, backup_dir, config, "config.yml")
+pub fn save_main_config(file_path: &str, backup_dir: &str, config: &ConfigDto) -> Result<ConfigFileChange, M3uFilterError> {
+    write_config_file(file_path, backup_dir, config, ConfigSchema::Config, "config.yml")
 }
 
 static ENV_REGEX: LazyLock<regex::Regex> = LazyLock::new(|| Regex::new(r"\$\{env:(?P<var>[a-zA-Z_][a-zA-Z0-9_]*)}").unwrap());
@@ -146,4 +222,28 @@ pub fn resolve_env_var(value: &str) -> String {
         let var_name = &caps["var"];
         env::var(var_name).unwrap_or_else(|_| format!("${{env:{var_name}}}"))
     }).to_string()
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use crate::repository::audit_repository::audit_diff;
+    use crate::utils::config_reader::{read_api_proxy, save_api_proxy};
+    use crate::utils::test_utils::create_temp_dir;
+
+    #[test]
+    fn test_save_config_change() {
+        std::env::set_var("M3U_FILTER_TEST_HOST", "tv.example.com");
+        let temp_dir = create_temp_dir("config_change");
+        let path = temp_dir.path().join("api-proxy.yml");
+        std::fs::write(&path, "server: [{name: default, protocol: http, host: '${M3U_FILTER_TEST_HOST}', http_port: '80', timezone: UTC, message: welcome}]
+user: [{target: tv, credentials: [{username: u1, password: p1}]}]\n").unwrap();
+        let mut api_proxy = read_api_proxy(path.to_str().unwrap(), false).unwrap();
+        api_proxy.user[0].credentials[0].username = "u2".to_string();
+        let change = save_api_proxy(path.to_str().unwrap(), temp_dir.path().to_str().unwrap(), &api_proxy).unwrap();
+        // both sides keep the variable, only the edited value is a change
+        assert_eq!(change.old["server"][0]["host"].as_str(), Some("${M3U_FILTER_TEST_HOST}"));
+        assert_eq!(change.new["server"][0]["host"].as_str(), Some("${M3U_FILTER_TEST_HOST}"));
+        let changes = audit_diff(&serde_json::to_value(&change.old).unwrap(), &serde_json::to_value(&change.new).unwrap());
+        assert_eq!(changes.len(), 1, "{changes:?}");
+        assert_eq!(changes[0].path, "user[0].credentials[0].username");
+    }
+}
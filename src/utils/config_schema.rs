@@ -0,0 +1,518 @@
+use serde::de::value::StrDeserializer;
+use serde::de::{DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess, VariantAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use serde_json::{json, Map, Value};
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use crate::model::api_proxy::ApiProxyConfig;
+use crate::model::config::Config;
+use crate::model::mapping::Mappings;
+use crate::utils::yaml_utils;
+
+const JSON_SCHEMA_DRAFT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+type SchemaError = serde::de::value::Error;
+
+thread_local! {
+    /// Aliases of struct fields found while recording: (struct name, alias) -> field name.
+    /// Serde lists the aliases as fields, visiting both fails with a duplicate field error.
+    static FIELD_ALIASES: RefCell<HashMap<(&'static str, &'static str), String>> = RefCell::new(HashMap::new());
+}
+
+/// A deserializer which records the json schema of the visited type instead of reading data.
+/// Every struct field, the first sequence element and the first enum variant are visited with a dummy value,
+/// this works for the config models which only use plain structs, sequences, maps and enums.
+struct SchemaRecorder<'a> {
+    schema: &'a mut Value,
+}
+
+impl<'a> SchemaRecorder<'a> {
+    fn new(schema: &'a mut Value) -> Self {
+        Self { schema }
+    }
+
+    fn record(&mut self, schema_type: &str) {
+        *self.schema = json!({ "type": schema_type });
+    }
+}
+
+macro_rules! record_number {
+    ($($method:ident, $visit:ident, $value:expr, $schema_type:literal);* $(;)?) => {
+        $(fn $method<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Self::Error> {
+            self.record($schema_type);
+            visitor.$visit($value)
+        })*
+    };
+}
+
+impl<'de> Deserializer<'de> for SchemaRecorder<'_> {
+    type Error = SchemaError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        *self.schema = json!({});
+        visitor.visit_unit()
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.record("boolean");
+        visitor.visit_bool(false)
+    }
+
+    record_number! {
+        deserialize_i8, visit_i8, 0, "integer";
+        deserialize_i16, visit_i16, 0, "integer";
+        deserialize_i32, visit_i32, 0, "integer";
+        deserialize_i64, visit_i64, 0, "integer";
+        deserialize_u8, visit_u8, 0, "integer";
+        deserialize_u16, visit_u16, 0, "integer";
+        deserialize_u32, visit_u32, 0, "integer";
+        deserialize_u64, visit_u64, 0, "integer";
+        deserialize_f32, visit_f32, 0.0, "number";
+        deserialize_f64, visit_f64, 0.0, "number";
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.record("string");
+        visitor.visit_char(' ')
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.record("string");
+        visitor.visit_str("")
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.record("string");
+        visitor.visit_bytes(&[])
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.record("null");
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        let mut items = Value::Null;
+        let result = visitor.visit_seq(SingleElementAccess { schema: Some(&mut items) })?;
+        *self.schema = json!({ "type": "array", "items": items });
+        Ok(result)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(self, _name: &'static str, _len: usize, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        let mut values = Value::Null;
+        let result = visitor.visit_map(SingleEntryAccess { key_schema: Value::Null, value_schema: Some(&mut values) })?;
+        *self.schema = json!({ "type": "object", "additionalProperties": values });
+        Ok(result)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(self, name: &'static str, fields: &'static [&'static str], visitor: V) -> Result<V::Value, Self::Error> {
+        let mut properties = Map::new();
+        let mut access = StructAccess { name, fields, index: 0, key_pending: false, properties: &mut properties };
+        let result = visitor.visit_map(&mut access).inspect_err(|err| {
+            let message = err.to_string();
+            // the visitor rejects the key before reading the value, errors of nested structs are ignored
+            if let Some(field) = message.strip_prefix("duplicate field `").and_then(|rest| rest.strip_suffix('`')).filter(|_| access.key_pending) {
+                if let Some(alias) = fields.get(access.index) {
+                    FIELD_ALIASES.with(|aliases| aliases.borrow_mut().insert((name, alias), field.to_string()));
+                }
+            }
+        })?;
+        FIELD_ALIASES.with(|aliases| {
+            for ((struct_name, alias), field) in aliases.borrow().iter() {
+                if *struct_name == name {
+                    if let Some(schema) = properties.get(field).cloned() {
+                        properties.insert((*alias).to_string(), schema);
+                    }
+                }
+            }
+        });
+        *self.schema = json!({ "type": "object", "properties": properties, "additionalProperties": false });
+        Ok(result)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(self, _name: &'static str, variants: &'static [&'static str], visitor: V) -> Result<V::Value, Self::Error> {
+        *self.schema = json!({ "enum": variants });
+        visitor.visit_enum(FirstVariantAccess { variant: variants.first().copied().unwrap_or_default() })
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_any(visitor)
+    }
+}
+
+struct SingleElementAccess<'a> {
+    schema: Option<&'a mut Value>,
+}
+
+impl<'de> SeqAccess<'de> for SingleElementAccess<'_> {
+    type Error = SchemaError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error> {
+        match self.schema.take() {
+            Some(schema) => seed.deserialize(SchemaRecorder::new(schema)).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+struct SingleEntryAccess<'a> {
+    key_schema: Value,
+    value_schema: Option<&'a mut Value>,
+}
+
+impl<'de> MapAccess<'de> for SingleEntryAccess<'_> {
+    type Error = SchemaError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error> {
+        if self.value_schema.is_none() {
+            return Ok(None);
+        }
+        seed.deserialize(SchemaRecorder::new(&mut self.key_schema)).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Self::Error> {
+        let schema = self.value_schema.take().ok_or_else(|| serde::de::Error::custom("map value without key"))?;
+        seed.deserialize(SchemaRecorder::new(schema))
+    }
+}
+
+struct StructAccess<'a> {
+    name: &'static str,
+    fields: &'static [&'static str],
+    index: usize,
+    key_pending: bool,
+    properties: &'a mut Map<String, Value>,
+}
+
+impl<'de> MapAccess<'de> for &mut StructAccess<'_> {
+    type Error = SchemaError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error> {
+        while self.fields.get(self.index).is_some_and(|field| FIELD_ALIASES.with(|aliases| aliases.borrow().contains_key(&(self.name, *field)))) {
+            self.index += 1;
+        }
+        self.key_pending = self.index < self.fields.len();
+        match self.fields.get(self.index) {
+            Some(field) => seed.deserialize::<StrDeserializer<SchemaError>>(field.into_deserializer()).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Self::Error> {
+        let field = self.fields[self.index];
+        self.index += 1;
+        self.key_pending = false;
+        let mut schema = Value::Null;
+        let result = seed.deserialize(SchemaRecorder::new(&mut schema))?;
+        self.properties.insert(field.to_string(), schema);
+        Ok(result)
+    }
+}
+
+struct FirstVariantAccess {
+    variant: &'static str,
+}
+
+impl<'de> EnumAccess<'de> for FirstVariantAccess {
+    type Error = SchemaError;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self::Variant), Self::Error> {
+        let value = seed.deserialize::<StrDeserializer<SchemaError>>(self.variant.into_deserializer())?;
+        Ok((value, self))
+    }
+}
+
+impl<'de> VariantAccess<'de> for FirstVariantAccess {
+    type Error = SchemaError;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Self::Error> {
+        seed.deserialize(SchemaRecorder::new(&mut Value::Null))
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, Self::Error> {
+        SchemaRecorder::new(&mut Value::Null).deserialize_tuple(len, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value, Self::Error> {
+        SchemaRecorder::new(&mut Value::Null).deserialize_struct("", fields, visitor)
+    }
+}
+
+fn record_schema<'de, T: Deserialize<'de>>() -> Result<Value, String> {
+    loop {
+        let alias_count = FIELD_ALIASES.with(|aliases| aliases.borrow().len());
+        let mut schema = Value::Null;
+        match T::deserialize(SchemaRecorder::new(&mut schema)) {
+            Ok(_) => return Ok(schema),
+            // retry with the newly found alias skipped
+            Err(_) if FIELD_ALIASES.with(|aliases| aliases.borrow().len()) > alias_count => {}
+            Err(err) => return Err(err.to_string()),
+        }
+    }
+}
+
+/// The config files which can be described by a schema.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ConfigSchema {
+    Config,
+    Source,
+    Mapping,
+    ApiProxy,
+}
+
+impl ConfigSchema {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().trim_end_matches(".yml") {
+            "config" => Some(Self::Config),
+            "source" => Some(Self::Source),
+            "mapping" => Some(Self::Mapping),
+            "api-proxy" | "api_proxy" => Some(Self::ApiProxy),
+            _ => None,
+        }
+    }
+
+    const fn file_name(self) -> &'static str {
+        match self {
+            Self::Config => "config.yml",
+            Self::Source => "source.yml",
+            Self::Mapping => "mapping.yml",
+            Self::ApiProxy => "api-proxy.yml",
+        }
+    }
+
+    /// The schema of the model the file is read into, `config.yml` and `source.yml` are read together into the `Config`.
+    fn model_schema(self) -> Result<Value, String> {
+        match self {
+            Self::Config | Self::Source => record_schema::<Config>(),
+            Self::Mapping => record_schema::<Mappings>(),
+            Self::ApiProxy => record_schema::<ApiProxyConfig>(),
+        }
+    }
+
+    /// `sources` is the only entry of the `source.yml`.
+    fn record(self) -> Result<Value, String> {
+        match self {
+            Self::Config | Self::Source => {
+                let mut schema = self.model_schema()?;
+                if let Some(properties) = schema.get_mut("properties").and_then(Value::as_object_mut) {
+                    let sources = properties.remove("sources").unwrap_or(Value::Null);
+                    if self == Self::Source {
+                        return Ok(json!({ "type": "object", "properties": { "sources": sources }, "additionalProperties": false }));
+                    }
+                }
+                Ok(schema)
+            }
+            Self::Mapping | Self::ApiProxy => self.model_schema(),
+        }
+    }
+
+    /// The json schema of the config file.
+    pub fn json_schema(self) -> Result<Value, String> {
+        let mut schema = self.record()?;
+        if let Some(doc) = schema.as_object_mut() {
+            doc.insert("$schema".to_string(), Value::String(JSON_SCHEMA_DRAFT.to_string()));
+            doc.insert("title".to_string(), Value::String(self.file_name().to_string()));
+        }
+        Ok(schema)
+    }
+}
+
+fn find_similar<'a>(key: &str, properties: &'a Map<String, Value>) -> Option<&'a str> {
+    properties.keys()
+        .map(|name| (name, strsim::jaro_winkler(key, name)))
+        .filter(|(_, similarity)| *similarity > 0.85)
+        .max_by(|(_, left), (_, right)| left.total_cmp(right))
+        .map(|(name, _)| name.as_str())
+}
+
+fn collect_unknown_fields(value: &serde_yaml::Value, schema: &Value, path: &str, warnings: &mut Vec<String>) {
+    match value {
+        serde_yaml::Value::Mapping(mapping) => {
+            let properties = schema.get("properties").and_then(Value::as_object);
+            let additional = schema.get("additionalProperties").filter(|additional| additional.is_object());
+            for (key, child) in mapping {
+                let Some(key) = key.as_str() else { continue };
+                let child_path = if path.is_empty() { key.to_string() } else { format!("{path}.{key}") };
+                if let Some(child_schema) = properties.and_then(|props| props.get(key)).or(additional) {
+                    collect_unknown_fields(child, child_schema, &child_path, warnings);
+                } else if let Some(properties) = properties {
+                    match find_similar(key, properties) {
+                        Some(similar) => warnings.push(format!("Unknown config field `{child_path}`, did you mean `{similar}`?")),
+                        None => warnings.push(format!("Unknown config field `{child_path}`")),
+                    }
+                }
+            }
+        }
+        serde_yaml::Value::Sequence(sequence) => {
+            if let Some(items) = schema.get("items") {
+                for (index, child) in sequence.iter().enumerate() {
+                    collect_unknown_fields(child, items, &format!("{path}[{index}]"), warnings);
+                }
+            }
+        }
+        serde_yaml::Value::Tagged(tagged) => collect_unknown_fields(&tagged.value, schema, path, warnings),
+        _ => {}
+    }
+}
+
+/// Returns a message for each field of the yaml document which is ignored by the config model,
+/// with a suggestion for misspelled or renamed fields.
+pub fn find_unknown_fields(schema: ConfigSchema, document: &serde_yaml::Value) -> Vec<String> {
+    let mut warnings = vec![];
+    if let Ok(schema) = schema.model_schema() {
+        collect_unknown_fields(document, &schema, "", &mut warnings);
+    }
+    warnings
+}
+
+fn interpolate_values(value: &mut serde_yaml::Value, schema: Option<&Value>, strings: bool) -> bool {
+    match value {
+        serde_yaml::Value::Mapping(mapping) => {
+            let properties = schema.and_then(|schema| schema.get("properties"));
+            let additional = schema.and_then(|schema| schema.get("additionalProperties")).filter(|additional| additional.is_object());
+            let mut changed = false;
+            for (key, child) in mapping.iter_mut() {
+                let child_schema = key.as_str().and_then(|key| properties.and_then(|props| props.get(key))).or(additional);
+                changed |= interpolate_values(child, child_schema, strings);
+            }
+            changed
+        }
+        serde_yaml::Value::Sequence(sequence) => {
+            let items = schema.and_then(|schema| schema.get("items"));
+            let mut changed = false;
+            for child in sequence.iter_mut() {
+                changed |= interpolate_values(child, items, strings);
+            }
+            changed
+        }
+        serde_yaml::Value::Tagged(tagged) => interpolate_values(&mut tagged.value, schema, strings),
+        serde_yaml::Value::String(text) => {
+            let typed = schema.and_then(|schema| schema.get("type")).and_then(Value::as_str)
+                .is_some_and(|schema_type| matches!(schema_type, "integer" | "number" | "boolean"));
+            if !strings && !typed {
+                return false;
+            }
+            let resolved = yaml_utils::interpolate_env_vars(text);
+            if resolved == *text {
+                return false;
+            }
+            // the variable of a number or boolean field is read like an unquoted value
+            match serde_yaml::from_str::<serde_yaml::Value>(&resolved) {
+                Ok(typed_value @ (serde_yaml::Value::Number(_) | serde_yaml::Value::Bool(_))) if typed => *value = typed_value,
+                _ => *text = resolved,
+            }
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Replaces `${VAR}` in the values of the parsed yaml document, the keys are not changed.
+/// Without `strings` only the values of number and boolean fields are replaced, the string values keep
+/// their variables, the web ui shows and saves the config like it is written.
+/// The templates of the `mapping.yml` use `${...}` themselves, it is not interpolated.
+/// Returns `true` if a value was replaced.
+pub fn interpolate_env_vars(schema: ConfigSchema, document: &mut serde_yaml::Value, strings: bool) -> bool {
+    if schema == ConfigSchema::Mapping {
+        return false;
+    }
+    let model_schema = schema.model_schema().ok();
+    interpolate_values(document, model_schema.as_ref(), strings)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::config_schema::{find_unknown_fields, interpolate_env_vars, ConfigSchema};
+
+    #[test]
+    fn test_config_schema() {
+        for schema in [ConfigSchema::Config, ConfigSchema::Source, ConfigSchema::Mapping, ConfigSchema::ApiProxy] {
+            assert!(schema.json_schema().is_ok_and(|value| value["properties"].is_object()), "{schema:?}");
+        }
+        let schema = ConfigSchema::Config.json_schema().unwrap();
+        assert_eq!(schema["properties"]["api"]["properties"]["port"]["type"], "integer");
+        assert!(schema["properties"].get("sources").is_none());
+
+        let document: serde_yaml::Value = serde_yaml::from_str("
+working_dir: ./data
+updte_on_boot: true
+api: {host: localhost, port: 8901, web_rot: ./web}
+sources:
+  - inputs: [{url: http://localhost/get.php, fancy: 1}]
+").unwrap();
+        let warnings = find_unknown_fields(ConfigSchema::Config, &document);
+        assert_eq!(warnings, vec![
+            "Unknown config field `updte_on_boot`, did you mean `update_on_boot`?".to_string(),
+            "Unknown config field `api.web_rot`, did you mean `web_root`?".to_string(),
+            "Unknown config field `sources[0].inputs[0].fancy`".to_string(),
+        ]);
+        let mapping: serde_yaml::Value = serde_yaml::from_str("mappings: {templates: [], mapping: []}").unwrap();
+        assert_eq!(find_unknown_fields(ConfigSchema::Mapping, &mapping), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_interpolate_env_vars() {
+        std::env::set_var("M3U_FILTER_TEST_SCHEMA_PORT", "8901");
+        std::env::set_var("M3U_FILTER_TEST_SCHEMA_PASSWORD", "a: 'b\"\n- c");
+        let content = "
+api: {host: localhost, port: '${M3U_FILTER_TEST_SCHEMA_PORT}', web_root: ./web}
+sources:
+  - inputs: [{url: http://localhost/get.php, password: '${M3U_FILTER_TEST_SCHEMA_PASSWORD}', username: '$${M3U_FILTER_TEST_SCHEMA_PORT}'}]
+";
+        let mut document: serde_yaml::Value = serde_yaml::from_str(content).unwrap();
+        assert!(interpolate_env_vars(ConfigSchema::Config, &mut document, true));
+        assert_eq!(document["api"]["port"].as_u64(), Some(8901));
+        let input = &document["sources"][0]["inputs"][0];
+        // the value is not parsed as yaml
+        assert_eq!(input["password"].as_str(), Some("a: 'b\"\n- c"));
+        assert_eq!(input["username"].as_str(), Some("${M3U_FILTER_TEST_SCHEMA_PORT}"));
+
+        // the web ui gets the string values with their variables
+        let mut document: serde_yaml::Value = serde_yaml::from_str(content).unwrap();
+        assert!(interpolate_env_vars(ConfigSchema::Config, &mut document, false));
+        assert_eq!(document["api"]["port"].as_u64(), Some(8901));
+        assert_eq!(document["sources"][0]["inputs"][0]["password"].as_str(), Some("${M3U_FILTER_TEST_SCHEMA_PASSWORD}"));
+
+        let mut mapping: serde_yaml::Value = serde_yaml::from_str("mappings: {templates: [{name: league, value: '${M3U_FILTER_TEST_SCHEMA_PORT}'}]}").unwrap();
+        assert!(!interpolate_env_vars(ConfigSchema::Mapping, &mut mapping, true));
+        assert_eq!(mapping["mappings"]["templates"][0]["value"].as_str(), Some("${M3U_FILTER_TEST_SCHEMA_PORT}"));
+    }
+}
@@ -0,0 +1,34 @@
+use std::collections::HashMap;
+
+/// Splits a csv line, fields can be quoted with `"` and a quote is escaped by doubling it.
+fn split_csv_line(line: &str, separator: char) -> Vec<String> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            _ if c == separator && !quoted => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+/// The rows by lower case column name, the first line has the column names, the separator is `,` or `;`.
+pub fn parse_csv(content: &str) -> Vec<HashMap<String, String>> {
+    let mut lines = content.lines().filter(|line| !line.trim().is_empty());
+    let Some(header) = lines.next() else {
+        return vec![];
+    };
+    let separator = if header.contains(';') && !header.contains(',') { ';' } else { ',' };
+    let columns: Vec<String> = split_csv_line(header.trim_start_matches('\u{feff}'), separator).iter()
+        .map(|column| column.trim().to_lowercase()).collect();
+    lines.map(|line| columns.iter().cloned().zip(split_csv_line(line, separator)).collect()).collect()
+}
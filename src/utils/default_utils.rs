@@ -3,3 +3,83 @@ pub const fn default_as_true() -> bool { true }
 pub fn default_as_default() -> String { String::from("default") }
 
 pub const fn default_as_two_u16() -> u16 { 2 }
+
+pub const fn default_stream_buffer_size() -> usize { 1024 }
+
+pub const fn default_health_check_interval() -> u64 { 3600 }
+
+pub const fn default_health_check_timeout() -> u64 { 10 }
+
+pub const fn default_logo_cache_max_age() -> u64 { 604_800 }
+
+pub const fn default_logo_size() -> u32 { 256 }
+
+pub fn default_logo_placeholder_background() -> String { String::from("#202020") }
+
+pub fn default_logo_placeholder_color() -> String { String::from("#FFFFFF") }
+
+pub const fn default_short_epg_interval() -> u64 { 21_600 }
+
+pub const fn default_short_epg_limit() -> u32 { 50 }
+
+pub const fn default_account_check_interval() -> u64 { 43_200 }
+
+pub const fn default_account_expiry_notify_days() -> u32 { 7 }
+
+pub const fn default_rate_limit_period() -> u64 { 60 }
+
+pub const fn default_rate_limit_requests() -> u32 { 120 }
+
+pub const fn default_rate_limit_max_failed_logins() -> u32 { 10 }
+
+pub const fn default_rate_limit_ban_duration() -> u64 { 900 }
+
+pub fn default_access_log_path() -> String { String::from("access.log") }
+
+pub const fn default_access_log_max_size() -> u64 { 10_485_760 }
+
+pub const fn default_access_log_max_files() -> u32 { 5 }
+
+pub const fn default_watchdog_refresh_timeout() -> u64 { 7_200 }
+
+pub const fn default_post_process_timeout() -> u64 { 300 }
+
+pub const fn default_readiness_refresh_sla() -> u64 { 86_400 }
+
+pub fn default_cors_methods() -> Vec<String> { ["GET", "POST", "PUT", "DELETE", "OPTIONS", "HEAD"].iter().map(ToString::to_string).collect() }
+
+pub const fn default_cors_max_age() -> usize { 3_600 }
+
+pub fn default_recording_directory() -> String { String::from("recordings") }
+
+pub const fn default_tvheadend_max_streams() -> u16 { 1 }
+
+pub const fn default_wasm_plugin_fuel() -> u64 { 1_000_000 }
+
+pub const fn default_wasm_plugin_max_memory() -> u32 { 64 }
+
+pub fn default_tmdb_language() -> String { String::from("en-US") }
+
+pub const fn default_schedules_direct_days() -> u8 { 7 }
+
+pub const fn default_schedules_direct_interval() -> u64 { 43_200 }
+
+pub fn default_tag_pattern_field() -> String { String::from("name") }
+
+pub const fn default_watch_history_retention_days() -> u32 { 90 }
+
+pub const fn default_watch_history_max_entries() -> usize { 50 }
+
+pub const fn default_item_cache_max_entries() -> usize { 10_000 }
+
+pub const fn default_processing_nice() -> i32 { 10 }
+
+pub const fn default_processing_chunk_size() -> usize { 100_000 }
+
+pub const fn default_http_backoff() -> u64 { 1_000 }
+
+pub const fn default_http_backoff_max() -> u64 { 60_000 }
+
+pub const fn default_shrink_protection_min_percent() -> u8 { 50 }
+
+pub const fn default_token_refresh_interval() -> u64 { 3_600 }
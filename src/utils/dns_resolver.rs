@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use log::debug;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use serde_json::Value;
+
+const DNS_TYPE_A: u64 = 1;
+const DNS_TYPE_AAAA: u64 = 28;
+const DOH_TIMEOUT_SECS: u64 = 10;
+const MIN_TTL_SECS: u64 = 30;
+
+type DnsCache = HashMap<String, (Instant, Vec<IpAddr>)>;
+
+/// Resolves hostnames with a DNS-over-HTTPS server in the json format (`application/dns-json`),
+/// like `https://1.1.1.1/dns-query` or `https://dns.google/resolve`. The answers are cached for their ttl.
+#[derive(Debug, Clone)]
+pub struct DohResolver {
+    url: String,
+    client: reqwest::Client,
+    cache: Arc<Mutex<DnsCache>>,
+}
+
+impl DohResolver {
+    pub fn new(url: &str) -> Result<Self, reqwest::Error> {
+        // the doh server itself is resolved with the system resolver
+        let client = reqwest::Client::builder().timeout(Duration::from_secs(DOH_TIMEOUT_SECS)).build()?;
+        Ok(Self { url: url.to_string(), client, cache: Arc::new(Mutex::new(HashMap::new())) })
+    }
+
+    fn get_cached(&self, name: &str) -> Option<Vec<IpAddr>> {
+        let cache = self.cache.lock().ok()?;
+        cache.get(name).filter(|(valid_until, _)| *valid_until > Instant::now()).map(|(_, addrs)| addrs.clone())
+    }
+
+    async fn query(&self, name: &str, record_type: u64) -> Result<(Vec<IpAddr>, u64), String> {
+        let response = self.client.get(&self.url)
+            .query(&[("name", name), ("type", record_type.to_string().as_str())])
+            .header(reqwest::header::ACCEPT, "application/dns-json")
+            .send().await.map_err(|err| format!("DoH request failed: {err}"))?;
+        if !response.status().is_success() {
+            return Err(format!("DoH request failed with status {}", response.status()));
+        }
+        let answer: Value = response.json().await.map_err(|err| format!("DoH response is invalid: {err}"))?;
+        Ok(parse_doh_answer(&answer, record_type))
+    }
+
+    async fn lookup(&self, name: &str) -> Result<Vec<IpAddr>, String> {
+        if let Some(addrs) = self.get_cached(name) {
+            return Ok(addrs);
+        }
+        let (mut addrs, mut ttl) = self.query(name, DNS_TYPE_A).await?;
+        if addrs.is_empty() {
+            (addrs, ttl) = self.query(name, DNS_TYPE_AAAA).await?;
+        }
+        if addrs.is_empty() {
+            return Err(format!("DoH could not resolve {name}"));
+        }
+        debug!("DoH resolved {name} to {addrs:?}");
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.insert(name.to_string(), (Instant::now() + Duration::from_secs(ttl.max(MIN_TTL_SECS)), addrs.clone()));
+        }
+        Ok(addrs)
+    }
+}
+
+impl Resolve for DohResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let resolver = self.clone();
+        Box::pin(async move {
+            let addrs = resolver.lookup(name.as_str()).await?;
+            // the port is set by the client
+            let addrs: Addrs = Box::new(addrs.into_iter().map(|addr| SocketAddr::new(addr, 0)));
+            Ok(addrs)
+        })
+    }
+}
+
+/// Returns the addresses of the records with the given type and the smallest ttl.
+fn parse_doh_answer(answer: &Value, record_type: u64) -> (Vec<IpAddr>, u64) {
+    let records = answer.get("Answer").and_then(Value::as_array).map(|records| records.iter()
+        .filter(|record| record.get("type").and_then(Value::as_u64) == Some(record_type))
+        .filter_map(|record| {
+            let addr = record.get("data").and_then(Value::as_str)?.parse::<IpAddr>().ok()?;
+            Some((addr, record.get("TTL").and_then(Value::as_u64).unwrap_or(MIN_TTL_SECS)))
+        }).collect::<Vec<_>>()).unwrap_or_default();
+    let ttl = records.iter().map(|(_, ttl)| *ttl).min().unwrap_or(MIN_TTL_SECS);
+    (records.into_iter().map(|(addr, _)| addr).collect(), ttl)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use crate::utils::dns_resolver::{parse_doh_answer, DNS_TYPE_A};
+
+    #[test]
+    fn test_parse_doh_answer() {
+        let answer = serde_json::json!({"Status": 0, "Answer": [
+            {"name": "provider.net", "type": 5, "TTL": 300, "data": "cdn.provider.net."},
+            {"name": "cdn.provider.net", "type": 1, "TTL": 120, "data": "203.0.113.7"},
+            {"name": "cdn.provider.net", "type": 1, "TTL": 60, "data": "203.0.113.8"}
+        ]});
+        let (addrs, ttl) = parse_doh_answer(&answer, DNS_TYPE_A);
+        assert_eq!(addrs, vec!["203.0.113.7".parse::<IpAddr>().unwrap(), "203.0.113.8".parse::<IpAddr>().unwrap()]);
+        assert_eq!(ttl, 60);
+        assert!(parse_doh_answer(&serde_json::json!({"Status": 3}), DNS_TYPE_A).0.is_empty());
+    }
+}
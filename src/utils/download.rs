@@ -1,16 +1,18 @@
 use std::cmp::Ordering;
 use std::path::PathBuf;
 use std::thread::sleep;
-use log::{debug, info};
-use crate::m3u_filter_error::M3uFilterError;
+use log::{debug, info, warn};
+use crate::create_m3u_filter_error;
+use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorCategory, M3uFilterErrorKind};
 use crate::model::config::{Config, ConfigInput};
 use crate::model::playlist::{FetchedPlaylist, PlaylistGroup, PlaylistItem, PlaylistItemType, XtreamCluster};
 use crate::model::xmltv::TVGuide;
 use crate::processing::{m3u_parser, xtream_parser};
 use crate::processing::xtream_parser::parse_xtream_series_info;
 use crate::utils::{file_utils, request_utils};
+use crate::utils::request_utils::mask_sensitive_info;
 
-fn prepare_file_path(persist: Option<&String>, working_dir: &str, action: &str) -> Option<PathBuf> {
+pub fn prepare_file_path(persist: Option<&String>, working_dir: &str, action: &str) -> Option<PathBuf> {
     let persist_file: Option<PathBuf> =
         persist.map(|persist_path| file_utils::prepare_persist_path(persist_path.as_str(), action));
     if persist_file.is_some() {
@@ -104,13 +106,84 @@ const ACTIONS: [(XtreamCluster, &str, &str); 3] = [
     (XtreamCluster::Video, "get_vod_categories", "get_vod_streams"),
     (XtreamCluster::Series, "get_series_categories", "get_series")];
 
-pub async fn get_xtream_playlist(input: &ConfigInput, working_dir: &str) -> (Vec<PlaylistGroup>, Vec<M3uFilterError>) {
+fn format_account_date(timestamp: i64) -> String {
+    chrono::DateTime::from_timestamp(timestamp, 0).map_or_else(|| timestamp.to_string(), |date| date.format("%Y-%m-%d %H:%M:%S").to_string())
+}
+
+/// Checks the account before the playlist is downloaded.
+/// Inactive or expired accounts are reported as error, if the provider sends no account info the check is skipped.
+async fn validate_xtream_account(input: &ConfigInput, base_url: &str) -> Result<(), M3uFilterError> {
+    let input_name = input.name.as_ref().map_or_else(|| mask_sensitive_info(&input.url), std::string::ToString::to_string);
+    let content = request_utils::get_input_json_content(input, base_url, None).await?;
+    let Some(account) = xtream_parser::parse_xtream_account_info(&content) else {
+        debug!("No account info for input {input_name}");
+        return Ok(());
+    };
+    if !account.auth {
+        return Err(create_m3u_filter_error!(M3uFilterErrorKind::Notify, "Xtream account for input {input_name} is not authorized").with_category(M3uFilterErrorCategory::Download));
+    }
+    let expires = account.exp_date.map_or_else(|| "never".to_string(), format_account_date);
+    let connections = match (account.active_cons, account.max_connections) {
+        (Some(active), Some(max)) => format!("{active}/{max}"),
+        _ => "unknown".to_string(),
+    };
+    info!("Xtream account for input {input_name}: status {}, expires {expires}, connections {connections}", account.status);
+    if !account.status.is_empty() && !account.status.eq_ignore_ascii_case("active") {
+        return Err(create_m3u_filter_error!(M3uFilterErrorKind::Notify, "Xtream account for input {input_name} is not active: {}", account.status).with_category(M3uFilterErrorCategory::Download));
+    }
+    if account.exp_date.is_some_and(|exp_date| exp_date < chrono::Local::now().timestamp()) {
+        return Err(create_m3u_filter_error!(M3uFilterErrorKind::Notify, "Xtream account for input {input_name} expired at {expires}").with_category(M3uFilterErrorCategory::Download));
+    }
+    if let (Some(active), Some(max)) = (account.active_cons, account.max_connections) {
+        if max > 0 && active >= max {
+            warn!("Xtream account for input {input_name} has no free connections {connections}");
+        }
+    }
+    Ok(())
+}
+
+/// Removes the categories which are not accepted by the `include_groups` and `exclude_groups` of the input,
+/// the streams of removed categories are skipped by the parser.
+fn filter_xtream_categories(input: &ConfigInput, categories: &mut serde_json::Value) {
+    if let serde_json::Value::Array(list) = categories {
+        list.retain(|category| category.get("category_name").and_then(serde_json::Value::as_str).is_none_or(|name| input.accepts_group(name)));
+    }
+}
+
+/// Downloads the streams of each category, used with `include_groups` which usually keeps a small part of the categories.
+/// The per category downloads are not persisted.
+async fn get_xtream_category_streams(input: &ConfigInput, stream_url: &str, categories: &serde_json::Value) -> Result<serde_json::Value, M3uFilterError> {
+    let mut streams = vec![];
+    for category in categories.as_array().into_iter().flatten() {
+        let category_id = match category.get("category_id") {
+            Some(serde_json::Value::String(id)) => id.to_string(),
+            Some(serde_json::Value::Number(id)) => id.to_string(),
+            _ => continue,
+        };
+        if let serde_json::Value::Array(mut category_streams) = request_utils::get_input_json_content(input, &format!("{stream_url}&category_id={category_id}"), None).await? {
+            streams.append(&mut category_streams);
+        }
+    }
+    Ok(serde_json::Value::Array(streams))
+}
+
+/// `clusters` restricts the download to the given clusters, all clusters which are not skipped by the input options otherwise.
+pub async fn get_xtream_playlist(input: &ConfigInput, working_dir: &str, clusters: Option<&[XtreamCluster]>) -> (Vec<PlaylistGroup>, Vec<M3uFilterError>) {
     let mut playlist_groups: Vec<PlaylistGroup> = Vec::new();
     let username = input.username.as_ref().map_or("", |v| v);
     let password = input.password.as_ref().map_or("", |v| v);
     let base_url = format!("{}/player_api.php?username={}&password={}", input.url, username, password);
 
-    let skip_cluster = get_skip_cluster(input);
+    if input.options.as_ref().is_none_or(|options| options.xtream_validate_account) {
+        if let Err(err) = validate_xtream_account(input, &base_url).await {
+            return (playlist_groups, vec![err]);
+        }
+    }
+
+    let mut skip_cluster = get_skip_cluster(input);
+    if let Some(clusters) = clusters {
+        skip_cluster.extend(ACTIONS.iter().map(|(cluster, _, _)| *cluster).filter(|cluster| !clusters.contains(cluster)));
+    }
 
     let mut errors = vec![];
     for (xtream_cluster, category, stream) in &ACTIONS {
@@ -120,10 +193,26 @@ pub async fn get_xtream_playlist(input: &ConfigInput, working_dir: &str) -> (Vec
             let category_file_path = prepare_file_path(input.persist.as_ref(), working_dir, format!("{category}_").as_str());
             let stream_file_path = prepare_file_path(input.persist.as_ref(), working_dir, format!("{stream}_").as_str());
 
-            match futures::join!(
-                request_utils::get_input_json_content(input, category_url.as_str(), category_file_path),
-                request_utils::get_input_json_content(input, stream_url.as_str(), stream_file_path)
-            ) {
+            let contents = if input.t_include_groups.is_some() {
+                match request_utils::get_input_json_content(input, category_url.as_str(), category_file_path).await {
+                    Ok(mut category_content) => {
+                        filter_xtream_categories(input, &mut category_content);
+                        let stream_content = get_xtream_category_streams(input, &stream_url, &category_content).await;
+                        (Ok(category_content), stream_content)
+                    }
+                    Err(err) => (Err(err), Ok(serde_json::Value::Null)),
+                }
+            } else {
+                let (category_content, stream_content) = futures::join!(
+                    request_utils::get_input_json_content(input, category_url.as_str(), category_file_path),
+                    request_utils::get_input_json_content(input, stream_url.as_str(), stream_file_path)
+                );
+                (category_content.map(|mut category_content| {
+                    filter_xtream_categories(input, &mut category_content);
+                    category_content
+                }), stream_content)
+            };
+            match contents {
                 (Ok(category_content), Ok(stream_content)) => {
                     match xtream_parser::parse_xtream(input,
                                                       *xtream_cluster,
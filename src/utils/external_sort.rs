@@ -0,0 +1,176 @@
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::StreamDeserializer;
+
+static SORT_DIR_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+type ChunkReader<T> = StreamDeserializer<'static, serde_json::de::IoRead<BufReader<File>>, T>;
+
+/// Temporary directory of the chunk files, removed when dropped.
+struct SortDir(PathBuf);
+
+impl Drop for SortDir {
+    fn drop(&mut self) {
+        if self.0.exists() {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+}
+
+/// Sorts more items than should be kept in memory. The items are sorted in chunks of `chunk_size`,
+/// every full chunk is written to a temporary file and the files are merged when the items are read.
+/// The sort is stable, equal items keep the order in which they were pushed.
+pub struct ExternalSorter<T, F> {
+    dir: SortDir,
+    chunk_size: usize,
+    compare: F,
+    chunk: Vec<T>,
+    files: Vec<PathBuf>,
+}
+
+impl<T: Serialize + DeserializeOwned, F: Fn(&T, &T) -> Ordering> ExternalSorter<T, F> {
+    /// The chunk files are written to a new directory below `dir`, which is removed when the sorted items were read.
+    pub fn new(dir: &Path, chunk_size: usize, compare: F) -> Self {
+        let counter = SORT_DIR_COUNTER.fetch_add(1, AtomicOrdering::Relaxed);
+        Self {
+            dir: SortDir(dir.join(format!("sort_{}_{counter}", std::process::id()))),
+            chunk_size: chunk_size.max(1),
+            compare,
+            chunk: vec![],
+            files: vec![],
+        }
+    }
+
+    pub fn push(&mut self, item: T) -> std::io::Result<()> {
+        self.chunk.push(item);
+        if self.chunk.len() >= self.chunk_size {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    /// Number of items kept in memory.
+    pub fn buffered(&self) -> usize {
+        self.chunk.len()
+    }
+
+    fn spill(&mut self) -> std::io::Result<()> {
+        if self.files.is_empty() {
+            std::fs::create_dir_all(&self.dir.0)?;
+        }
+        let path = self.dir.0.join(format!("chunk_{}.jsonl", self.files.len()));
+        self.files.push(path.clone());
+        let compare = &self.compare;
+        self.chunk.sort_by(compare);
+        let mut writer = BufWriter::new(File::create(&path)?);
+        for item in self.chunk.drain(..) {
+            serde_json::to_writer(&mut writer, &item)?;
+            writer.write_all(b"\n")?;
+        }
+        writer.flush()
+    }
+
+    /// Returns the sorted items, the items are only kept in memory if no chunk was written.
+    pub fn finish(mut self) -> std::io::Result<SortedItems<T, F>> {
+        if self.files.is_empty() {
+            let compare = &self.compare;
+            self.chunk.sort_by(compare);
+            return Ok(SortedItems { _dir: self.dir, compare: self.compare, source: SortedSource::Memory(self.chunk.into_iter()) });
+        }
+        if !self.chunk.is_empty() {
+            self.spill()?;
+        }
+        let mut readers = vec![];
+        for path in &self.files {
+            let mut reader: ChunkReader<T> = serde_json::Deserializer::from_reader(BufReader::new(File::open(path)?)).into_iter();
+            let head = reader.next().transpose()?;
+            readers.push((reader, head));
+        }
+        Ok(SortedItems { _dir: self.dir, compare: self.compare, source: SortedSource::Files(readers) })
+    }
+}
+
+enum SortedSource<T> {
+    Memory(std::vec::IntoIter<T>),
+    /// the reader of each chunk with its next item
+    Files(Vec<(ChunkReader<T>, Option<T>)>),
+}
+
+/// The merged chunks of the `ExternalSorter`, the temporary files are removed when dropped.
+pub struct SortedItems<T, F> {
+    _dir: SortDir,
+    compare: F,
+    source: SortedSource<T>,
+}
+
+impl<T, F> SortedItems<T, F> {
+    /// Number of items kept in memory, one per chunk file while the chunks are merged.
+    pub fn buffered(&self) -> usize {
+        match &self.source {
+            SortedSource::Memory(items) => items.len(),
+            SortedSource::Files(readers) => readers.iter().filter(|(_, head)| head.is_some()).count(),
+        }
+    }
+}
+
+impl<T: DeserializeOwned, F: Fn(&T, &T) -> Ordering> Iterator for SortedItems<T, F> {
+    type Item = std::io::Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.source {
+            SortedSource::Memory(items) => items.next().map(Ok),
+            SortedSource::Files(readers) => {
+                // the first of equal items is taken, the chunks are in push order
+                let mut next: Option<usize> = None;
+                for (index, (_, head)) in readers.iter().enumerate() {
+                    if let Some(item) = head {
+                        if next.and_then(|next| readers[next].1.as_ref()).is_none_or(|min| (self.compare)(item, min) == Ordering::Less) {
+                            next = Some(index);
+                        }
+                    }
+                }
+                let (reader, head) = &mut readers[next?];
+                let item = head.take();
+                match reader.next().transpose() {
+                    Ok(following) => *head = following,
+                    Err(err) => return Some(Err(err.into())),
+                }
+                item.map(Ok)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::external_sort::ExternalSorter;
+    use crate::utils::test_utils::create_temp_dir;
+
+    #[test]
+    fn test_external_sort() {
+        let temp_dir = create_temp_dir("external_sort");
+        let dir = temp_dir.path();
+        let values = [7, 3, 9, 1, 3, 8, 2, 6, 5, 4, 0];
+        let mut sorter = ExternalSorter::new(dir, 3, |a: &(u32, usize), b: &(u32, usize)| a.0.cmp(&b.0));
+        for (index, value) in values.iter().enumerate() {
+            sorter.push((*value, index)).unwrap();
+        }
+        let sorted: Vec<(u32, usize)> = sorter.finish().unwrap().collect::<std::io::Result<_>>().unwrap();
+        assert_eq!(sorted.iter().map(|(value, _)| *value).collect::<Vec<_>>(), vec![0, 1, 2, 3, 3, 4, 5, 6, 7, 8, 9]);
+        // equal values keep their order
+        assert_eq!(sorted[3..5], [(3, 1), (3, 4)]);
+        assert!(!dir.read_dir().is_ok_and(|mut entries| entries.next().is_some()));
+
+        let mut sorter = ExternalSorter::new(dir, 100, |a: &u32, b: &u32| b.cmp(a));
+        for value in values {
+            sorter.push(value).unwrap();
+        }
+        assert_eq!(sorter.finish().unwrap().map(Result::unwrap).collect::<Vec<_>>(), vec![9, 8, 7, 6, 5, 4, 3, 3, 2, 1, 0]);
+    }
+}
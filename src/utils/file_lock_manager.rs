@@ -1,8 +1,16 @@
 use std::collections::HashMap;
+use std::io::Write;
 use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
-use std::{fmt, io};
+use std::{fmt, fs, io};
 use std::path::{Path, PathBuf};
 
+use log::{error, warn};
+
+use crate::utils::file_utils;
+
+const FILE_JOURNAL: &str = "transaction.journal";
+const STAGED_SUFFIX: &str = ".txn";
+
 #[derive(Clone)]
 pub struct FileLockManager {
     locks: Arc<Mutex<HashMap<PathBuf, Arc<RwLock<()>>>>>,
@@ -35,6 +43,28 @@ impl FileLockManager {
         Ok(FileWriteGuard::new(Arc::clone(&file_lock), guard))
     }
 
+    /// Starts a transaction for the files of a directory, only one transaction per directory runs at a time.
+    /// An interrupted transaction of the directory is completed first.
+    pub fn begin_transaction(&self, dir: &Path) -> io::Result<FileTransaction> {
+        let journal_path = dir.join(FILE_JOURNAL);
+        let guard = self.write_lock(&journal_path)?;
+        replay_journal(&journal_path)?;
+        Ok(FileTransaction {
+            locks: self.clone(),
+            journal_path,
+            files: vec![],
+            committed: false,
+            _guard: guard,
+        })
+    }
+
+    /// Completes a transaction of the directory which was interrupted by a crash after its commit started.
+    pub fn recover_transaction(&self, dir: &Path) -> io::Result<()> {
+        let journal_path = dir.join(FILE_JOURNAL);
+        let _guard = self.write_lock(&journal_path)?;
+        replay_journal(&journal_path)
+    }
+
     // Helper function: retrieves or creates a lock for a file.
     fn get_or_create_lock(&self, path: &Path) -> io::Result<Arc<RwLock<()>>> {
         let mut locks = self.locks.lock().map_err(|_| {
@@ -66,6 +96,85 @@ impl fmt::Debug for FileLockManager {
     }
 }
 
+/// Moves the staged files of the journal into place and removes the journal.
+/// Files which were already moved before the crash are skipped.
+fn replay_journal(journal_path: &Path) -> io::Result<()> {
+    let content = match fs::read_to_string(journal_path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    };
+    warn!("Completing interrupted transaction {}", journal_path.to_str().unwrap_or("?"));
+    for (staged_path, path) in content.lines().filter_map(|line| line.split_once('\t')) {
+        let staged_path = Path::new(staged_path);
+        if staged_path.exists() {
+            file_utils::rename_atomic(staged_path, Path::new(path))?;
+        }
+    }
+    fs::remove_file(journal_path)
+}
+
+/// Writes to the playlist, index and epg files of a target which are replaced together.
+/// The files are written to staging files, `commit` lists them in a journal before they are moved into place,
+/// a crash can't leave an index with the records of another refresh.
+pub struct FileTransaction {
+    locks: FileLockManager,
+    journal_path: PathBuf,
+    /// staged path and destination
+    files: Vec<(PathBuf, PathBuf)>,
+    committed: bool,
+    _guard: FileWriteGuard,
+}
+
+impl FileTransaction {
+    /// Returns the path to write the new content of the file to, it replaces the file on commit.
+    pub fn stage(&mut self, path: &Path) -> PathBuf {
+        let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+        file_name.push(STAGED_SUFFIX);
+        let staged_path = path.with_file_name(file_name);
+        if !self.files.iter().any(|(_, destination)| destination == path) {
+            self.files.push((staged_path.clone(), path.to_path_buf()));
+        }
+        staged_path
+    }
+
+    /// Replaces the files with the written staged files, the readers of the files wait until all files are replaced.
+    pub fn commit(mut self) -> io::Result<()> {
+        let files: Vec<&(PathBuf, PathBuf)> = self.files.iter().filter(|(staged_path, _)| staged_path.exists()).collect();
+        if !files.is_empty() {
+            let journal = files.iter()
+                .map(|(staged_path, path)| format!("{}\t{}\n", staged_path.to_string_lossy(), path.to_string_lossy()))
+                .collect::<String>();
+            file_utils::write_atomic(&self.journal_path, |writer| {
+                writer.write_all(journal.as_bytes())?;
+                writer.flush()?;
+                writer.get_ref().sync_all()
+            })?;
+            let mut destinations: Vec<&PathBuf> = files.iter().map(|(_, path)| path).collect();
+            // same order in all transactions
+            destinations.sort();
+            let _guards = destinations.into_iter().map(|path| self.locks.write_lock(path)).collect::<io::Result<Vec<_>>>()?;
+            replay_journal(&self.journal_path)?;
+        }
+        self.committed = true;
+        Ok(())
+    }
+}
+
+impl Drop for FileTransaction {
+    fn drop(&mut self) {
+        if !self.committed {
+            for (staged_path, _) in &self.files {
+                if let Err(err) = fs::remove_file(staged_path) {
+                    if err.kind() != io::ErrorKind::NotFound {
+                        error!("Failed to remove staged file {}: {err}", staged_path.to_str().unwrap_or("?"));
+                    }
+                }
+            }
+        }
+    }
+}
+
 // Define FileReadGuard to hold both the lock reference and the actual read guard.
 #[allow(dead_code)]
 pub struct FileReadGuard {
@@ -101,3 +210,42 @@ impl FileWriteGuard {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::utils::file_lock_manager::{FileLockManager, FILE_JOURNAL};
+    use crate::utils::test_utils::create_temp_dir;
+
+    #[test]
+    fn test_file_transaction() {
+        let temp_dir = create_temp_dir("transaction");
+        let dir = temp_dir.path();
+        let (db_path, idx_path) = (dir.join("m3u.db"), dir.join("m3u.idx"));
+        fs::write(&db_path, "old records").unwrap();
+        fs::write(&idx_path, "old index").unwrap();
+        let locks = FileLockManager::new();
+
+        // without commit the files are unchanged
+        let mut transaction = locks.begin_transaction(dir).unwrap();
+        fs::write(transaction.stage(&db_path), "new records").unwrap();
+        drop(transaction);
+        assert_eq!(fs::read_to_string(&db_path).unwrap(), "old records");
+
+        let mut transaction = locks.begin_transaction(dir).unwrap();
+        fs::write(transaction.stage(&db_path), "new records").unwrap();
+        fs::write(transaction.stage(&idx_path), "new index").unwrap();
+        transaction.commit().unwrap();
+        assert_eq!(fs::read_to_string(&db_path).unwrap(), "new records");
+        assert_eq!(fs::read_to_string(&idx_path).unwrap(), "new index");
+
+        // a crash after the first file was moved
+        fs::write(dir.join("m3u.idx.txn"), "newer index").unwrap();
+        fs::write(dir.join(FILE_JOURNAL), format!("{}\t{}\n{}\t{}\n", dir.join("m3u.db.txn").display(), db_path.display(),
+                                                  dir.join("m3u.idx.txn").display(), idx_path.display())).unwrap();
+        locks.recover_transaction(dir).unwrap();
+        assert_eq!(fs::read_to_string(&idx_path).unwrap(), "newer index");
+        assert!(!dir.join(FILE_JOURNAL).exists());
+    }
+}
@@ -1,6 +1,6 @@
 use std::fs;
 use std::fs::File;
-use std::io::Write;
+use std::io::{BufWriter, Write};
 use std::path::{Path, PathBuf};
 
 use log::{debug, error};
@@ -85,6 +85,38 @@ pub fn get_default_api_proxy_config_path(config_path: &str) -> String {
     get_default_file_path(config_path, API_PROXY_FILE)
 }
 
+/// `config.yml` becomes `config.<profile>.yml`, the path is returned if the file exists in the config directory.
+fn get_profile_file_path(config_path: &str, file: &str, profile: &str) -> Option<String> {
+    let file_path = Path::new(file);
+    let stem = file_path.file_stem()?.to_str()?;
+    let extension = file_path.extension()?.to_str()?;
+    let path = PathBuf::from(config_path).join(format!("{stem}.{profile}.{extension}"));
+    if path.exists() { path.to_str().map(ToString::to_string) } else { None }
+}
+
+/// The config files of a profile, files which don't exist for the profile fall back to the default files.
+pub struct ProfileFiles {
+    pub config_file: Option<String>,
+    pub sources_file: Option<String>,
+    pub mapping_file: Option<String>,
+    pub api_proxy_file: Option<String>,
+}
+
+impl ProfileFiles {
+    pub fn new(config_path: &str, profile: &str) -> Self {
+        Self {
+            config_file: get_profile_file_path(config_path, CONFIG_FILE, profile),
+            sources_file: get_profile_file_path(config_path, SOURCE_FILE, profile),
+            mapping_file: get_profile_file_path(config_path, MAPPING_FILE, profile),
+            api_proxy_file: get_profile_file_path(config_path, API_PROXY_FILE, profile),
+        }
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.config_file.is_none() && self.sources_file.is_none() && self.mapping_file.is_none() && self.api_proxy_file.is_none()
+    }
+}
+
 pub fn get_working_path(wd: &String) -> String {
     let current_dir = std::env::current_dir().unwrap();
     if wd.is_empty() {
@@ -106,11 +138,6 @@ pub fn get_working_path(wd: &String) -> String {
     }
 }
 
-#[inline]
-pub fn open_file(file_name: &Path) -> Result<File, std::io::Error> {
-    File::open(file_name)
-}
-
 pub fn persist_file(persist_file: Option<PathBuf>, text: &String) {
     if let Some(path_buf) = persist_file {
         let filename = &path_buf.to_str().unwrap_or("?");
@@ -175,4 +202,38 @@ pub fn sanitize_filename(file_name: &str) -> String {
         .chars()
         .map(|c| if c.is_alphanumeric() || c == '_' || c == '-' { c } else { '_' })
         .collect()
-}
\ No newline at end of file
+}
+/// Returns the sibling path used to stage a file or directory before it is swapped into place.
+pub fn get_temp_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".tmp");
+    path.with_file_name(file_name)
+}
+
+/// Moves a staged file over the destination. On the same filesystem the rename is atomic,
+/// readers either see the old or the new content but never a partially written file.
+pub fn rename_atomic(temp_path: &Path, path: &Path) -> std::io::Result<()> {
+    fs::rename(temp_path, path).inspect_err(|_| {
+        let _ = fs::remove_file(temp_path);
+    })
+}
+
+/// Writes a file by writing into a temp file and renaming it afterward.
+pub fn write_atomic<F>(path: &Path, write_fn: F) -> std::io::Result<()>
+where
+    F: FnOnce(&mut BufWriter<File>) -> std::io::Result<()>,
+{
+    let temp_path = get_temp_path(path);
+    let result = File::create(&temp_path).and_then(|file| {
+        let mut writer = BufWriter::new(file);
+        write_fn(&mut writer)?;
+        writer.flush()
+    });
+    match result {
+        Ok(()) => rename_atomic(&temp_path, path),
+        Err(err) => {
+            let _ = fs::remove_file(&temp_path);
+            Err(err)
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use std::sync::LazyLock;
+
+use crate::model::config::FilenameSanitize;
+
+static QUALITY_RE: LazyLock<regex::Regex> = LazyLock::new(|| regex::Regex::new(r"(?i)\b(8K|4K|UHD|FHD|HD|SD)\b").unwrap());
+
+/// Replaces the `{token}` placeholders of the template, unknown tokens are kept.
+pub fn render_template<F>(template: &str, value_of: F) -> String
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        match after.find('}') {
+            Some(end) => {
+                let token = &after[..end];
+                match value_of(token) {
+                    Some(value) => result.push_str(&value),
+                    None => {
+                        result.push('{');
+                        result.push_str(token);
+                        result.push('}');
+                    }
+                }
+                rest = &after[end + 1..];
+            }
+            None => {
+                result.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// `strict` keeps only alphanumeric characters and whitespace,
+/// `safe` replaces the characters which are not allowed in file names on common file systems.
+pub fn sanitize_filename(text: &str, sanitize: FilenameSanitize, underscore_whitespace: bool) -> String {
+    text.chars()
+        .filter_map(|c| match sanitize {
+            FilenameSanitize::Strict => (c.is_alphanumeric() || c.is_whitespace()).then_some(c),
+            FilenameSanitize::Safe => Some(if c.is_control() || matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') { '_' } else { c }),
+            FilenameSanitize::None => Some(if c == '/' || c == '\\' { '_' } else { c }),
+        })
+        .map(|c| if underscore_whitespace && c.is_whitespace() { '_' } else { c })
+        .collect()
+}
+
+/// The quality tag of a channel name like `HD` or `4K`, empty if the name has none.
+pub fn get_quality(name: &str) -> String {
+    QUALITY_RE.find(name).map(|m| m.as_str().to_uppercase()).unwrap_or_default()
+}
+
+/// Resolves the `{target}` and `{date}` tokens of an output filename.
+pub fn resolve_output_filename(filename: &str, target_name: &str) -> String {
+    render_template(filename, |token| match token {
+        "target" => Some(target_name.to_string()),
+        "date" => Some(chrono::Local::now().format("%Y-%m-%d").to_string()),
+        _ => None,
+    })
+}
+
+/// Renders a relative file path, the token values can't create directories.
+/// Separators left over from empty tokens are trimmed and empty path components are removed.
+pub fn render_file_path<F>(template: &str, value_of: F) -> String
+where
+    F: Fn(&str) -> Option<String>,
+{
+    template.split('/')
+        .map(|component| render_template(component, &value_of))
+        .map(|component| component.trim_matches(|c: char| c.is_whitespace() || c == '-' || c == '_').to_string())
+        .filter(|component| !component.is_empty() && component != "." && component != "..")
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::config::FilenameSanitize;
+    use crate::utils::filename_template::{get_quality, render_file_path, render_template, sanitize_filename};
+
+    fn value_of(token: &str) -> Option<String> {
+        match token {
+            "group" => Some("News".to_string()),
+            "name" => Some("CNN".to_string()),
+            "quality" => Some(String::new()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_render_template() {
+        assert_eq!(render_template("{group}/{name}-{quality}.strm", value_of), "News/CNN-.strm");
+        assert_eq!(render_template("{unknown}_{name", value_of), "{unknown}_{name");
+        assert_eq!(render_file_path("{group}/{quality}/{name}-{quality}", value_of), "News/CNN");
+    }
+
+    #[test]
+    fn test_sanitize_filename() {
+        assert_eq!(sanitize_filename("A/B: C!", FilenameSanitize::Strict, false), "AB C");
+        assert_eq!(sanitize_filename("A/B: C!", FilenameSanitize::Safe, true), "A_B__C!");
+        assert_eq!(sanitize_filename("A/B: C!", FilenameSanitize::None, false), "A_B: C!");
+        assert_eq!(get_quality("CNN hd"), "HD");
+        assert_eq!(get_quality("HDTV"), "");
+    }
+}
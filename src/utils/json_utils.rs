@@ -1,12 +1,14 @@
 use std::collections::HashMap;
 use std::fs::File;
-use std::io::{self, BufReader, BufWriter, Error, Read, Write};
+use std::io::{self, BufReader, Error, Read};
 use std::path::Path;
 
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 use serde_json::{self, Deserializer, Value};
 
+use crate::utils::file_utils;
+
 fn read_skipping_ws(mut reader: impl Read) -> io::Result<u8> {
     loop {
         let mut byte = 0u8;
@@ -90,15 +92,7 @@ pub fn json_write_documents_to_file<T>(file: &Path, value: &T) -> Result<(), Err
 where
     T: ?Sized + Serialize,
 {
-    match File::create(file) {
-        Ok(file) => {
-            let mut writer = BufWriter::new(file);
-            serde_json::to_writer(&mut writer, value)?;
-            match writer.flush() {
-                Ok(()) => Ok(()),
-                Err(e) => Err(e)
-            }
-        }
-        Err(e) => Err(e)
-    }
-}
\ No newline at end of file
+    file_utils::write_atomic(file, |writer| {
+        serde_json::to_writer(writer, value).map_err(Error::from)
+    })
+}
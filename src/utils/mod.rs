@@ -3,10 +3,22 @@ pub mod request_utils;
 pub mod download;
 pub mod string_utils;
 pub mod json_utils;
+pub mod yaml_utils;
+pub mod csv_utils;
 pub mod config_reader;
 pub mod default_utils;
 pub mod multi_file_reader;
 pub mod file_lock_manager;
 pub mod compressed_file_reader;
 mod compression_utils;
-pub mod directed_graph;
\ No newline at end of file
+pub mod sd_notify;
+#[cfg(windows)]
+pub mod win_service;
+pub mod config_schema;
+pub mod dns_resolver;
+pub mod filename_template;
+pub mod thread_limits;
+pub mod external_sort;
+pub mod request_limiter;
+#[cfg(test)]
+pub mod test_utils;
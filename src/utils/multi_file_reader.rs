@@ -1,10 +1,16 @@
 use std::fs::File;
 use std::io::{self, BufReader, ErrorKind, Read};
-use std::path::{PathBuf};
+use std::path::{Path, PathBuf};
 
+use log::warn;
+use regex::Regex;
+
+/// Reads the files one after the other, the content of the files is separated by a newline.
 pub struct MultiFileReader {
-    files: Vec<File>,
-    current_reader: Option<BufReader<File>>,
+    files: Vec<(PathBuf, File)>,
+    current_reader: Option<(PathBuf, BufReader<File>)>,
+    separate: bool,
+    skip_unreadable: bool,
 }
 
 impl MultiFileReader {
@@ -12,7 +18,7 @@ impl MultiFileReader {
         let mut files = Vec::new();
         for path in paths {
             match File::open(path) {
-                Ok(file) => { files.push(file); }
+                Ok(file) => { files.push((path.clone(), file)); }
                 Err(err) => {
                     return Err(io::Error::new(ErrorKind::NotFound,format!("Could not find file {} {}", path.to_str().unwrap_or("?"), err)));
                 }
@@ -22,8 +28,28 @@ impl MultiFileReader {
         Ok(Self {
             files,
             current_reader: None,
+            separate: false,
+            skip_unreadable: false,
         })
     }
+
+    /// Files which can't be opened or read are skipped with a warning instead of failing.
+    pub fn new_skip_unreadable(paths: &[PathBuf]) -> Self {
+        let mut files: Vec<(PathBuf, File)> = paths.iter().filter_map(|path| match File::open(path) {
+            Ok(file) => Some((path.clone(), file)),
+            Err(err) => {
+                warn!("Skipping unreadable file {}: {err}", path.to_str().unwrap_or("?"));
+                None
+            }
+        }).collect();
+        files.reverse();
+        Self {
+            files,
+            current_reader: None,
+            separate: false,
+            skip_unreadable: true,
+        }
+    }
 }
 
 impl Read for MultiFileReader {
@@ -33,17 +59,112 @@ impl Read for MultiFileReader {
                 if self.files.is_empty() {
                     return Ok(0);
                 }
-                self.current_reader = Some(BufReader::new(self.files.pop().unwrap()));
+                if self.separate && !buf.is_empty() {
+                    // a file without trailing newline should not be joined with the first line of the next file
+                    self.separate = false;
+                    buf[0] = b'\n';
+                    return Ok(1);
+                }
+                let (path, file) = self.files.pop().unwrap();
+                self.current_reader = Some((path, BufReader::new(file)));
             }
-            let reader = self.current_reader.as_mut().unwrap();
+            let (path, reader) = self.current_reader.as_mut().unwrap();
             match reader.read(buf) {
                 Ok(0) => {
                     // The current reader is exhausted, move to the next one
                     self.current_reader = None;
+                    self.separate = true;
                 }
                 Ok(n) => return Ok(n),
+                Err(err) if self.skip_unreadable => {
+                    warn!("Skipping unreadable file {}: {err}", path.to_str().unwrap_or("?"));
+                    self.current_reader = None;
+                    self.separate = true;
+                }
                 Err(e) => return Err(e),
             }
         }
     }
-}
\ No newline at end of file
+}
+
+fn has_wildcard(segment: &str) -> bool {
+    segment.contains(['*', '?'])
+}
+
+/// `*` matches any characters, `?` one character of a file or directory name.
+fn glob_to_regex(segment: &str) -> Regex {
+    let mut pattern = String::from("^");
+    for c in segment.chars() {
+        match c {
+            '*' => pattern.push_str(".*"),
+            '?' => pattern.push('.'),
+            _ => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    pattern.push('$');
+    Regex::new(&pattern).unwrap()
+}
+
+/// The files matching the glob pattern sorted by path, relative patterns are resolved against `base`.
+/// The wildcards `*` and `?` can be used in each directory and file name of the pattern.
+pub fn expand_glob(base: &Path, pattern: &str) -> Vec<PathBuf> {
+    let mut paths = vec![PathBuf::new()];
+    for component in base.join(pattern).components() {
+        let segment = component.as_os_str().to_string_lossy();
+        if has_wildcard(&segment) {
+            let segment_re = glob_to_regex(&segment);
+            paths = paths.iter()
+                .filter_map(|dir| std::fs::read_dir(dir).ok())
+                .flatten()
+                .filter_map(Result::ok)
+                .filter(|entry| segment_re.is_match(&entry.file_name().to_string_lossy()))
+                .map(|entry| entry.path())
+                .collect();
+            paths.sort();
+        } else {
+            paths.iter_mut().for_each(|path| path.push(component));
+        }
+    }
+    paths.retain(|path| path.is_file());
+    paths
+}
+
+/// The files of all patterns in the order of the patterns, a file matching several patterns is listed once.
+pub fn expand_globs(base: &Path, patterns: &[String]) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = vec![];
+    for file in patterns.iter().flat_map(|pattern| expand_glob(base, pattern)) {
+        if !files.contains(&file) {
+            files.push(file);
+        }
+    }
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use crate::utils::multi_file_reader::{expand_globs, MultiFileReader};
+    use crate::utils::test_utils::create_temp_dir;
+
+    #[test]
+    fn test_expand_globs() {
+        let temp_dir = create_temp_dir("expand_globs");
+        let dir = temp_dir.path();
+        std::fs::create_dir_all(dir.join("de")).unwrap();
+        for file in ["main.m3u", "b.m3u", "a.m3u8", "de/news.m3u", "notes.txt"] {
+            std::fs::write(dir.join(file), format!("#EXTM3U {file}")).unwrap();
+        }
+        let patterns = vec!["main.m3u".to_string(), "*/*.m3u".to_string(), "*.m3u*".to_string(), "missing.m3u".to_string()];
+        let files = expand_globs(dir, &patterns);
+        let names: Vec<String> = files.iter().map(|file| file.strip_prefix(dir).unwrap().to_string_lossy().to_string()).collect();
+        assert_eq!(names, vec!["main.m3u", "de/news.m3u", "a.m3u8", "b.m3u"]);
+
+        let mut paths = files;
+        paths.insert(1, dir.join("missing.m3u"));
+        let mut content = String::new();
+        MultiFileReader::new_skip_unreadable(&paths).read_to_string(&mut content).unwrap();
+        assert_eq!(content, "#EXTM3U main.m3u\n#EXTM3U de/news.m3u\n#EXTM3U a.m3u8\n#EXTM3U b.m3u");
+        assert!(MultiFileReader::new(&paths).is_err());
+    }
+}
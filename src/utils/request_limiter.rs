@@ -0,0 +1,94 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+use reqwest::header::RETRY_AFTER;
+use reqwest::StatusCode;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+/// Limits the requests to a provider to `requests_per_second` and `max_parallel` running requests.
+#[derive(Debug)]
+pub struct RequestLimiter {
+    interval: Option<Duration>,
+    next_slot: Mutex<Option<Instant>>,
+    permits: Option<Semaphore>,
+}
+
+impl RequestLimiter {
+    pub fn new(requests_per_second: Option<f64>, max_parallel: usize) -> Self {
+        Self {
+            interval: requests_per_second.filter(|&rate| rate > 0.0).map(|rate| Duration::from_secs_f64(1.0 / rate)),
+            next_slot: Mutex::new(None),
+            permits: (max_parallel > 0).then(|| Semaphore::new(max_parallel)),
+        }
+    }
+
+    /// Waits until the next request may be sent. The returned permit counts as running request until it is dropped.
+    pub async fn acquire(&self) -> Option<SemaphorePermit<'_>> {
+        let permit = match &self.permits {
+            Some(permits) => permits.acquire().await.ok(),
+            None => None,
+        };
+        if let Some(interval) = self.interval {
+            let wait = self.next_slot.lock().map_or(Duration::ZERO, |mut next_slot| {
+                let now = Instant::now();
+                let slot = next_slot.map_or(now, |next| next.max(now));
+                *next_slot = Some(slot + interval);
+                slot - now
+            });
+            if !wait.is_zero() {
+                actix_rt::time::sleep(wait).await;
+            }
+        }
+        permit
+    }
+}
+
+/// Too many requests and server errors are retried.
+pub fn is_retry_status(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// The `Retry-After` header in seconds, dates are not supported.
+pub fn get_retry_after(response: &reqwest::Response) -> Option<Duration> {
+    response.headers().get(RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .map(Duration::from_secs)
+}
+
+/// Exponential backoff with jitter, the delay is doubled with each attempt up to `max`
+/// and a random value between the half and the full delay is taken, so the clients don't retry at the same time.
+pub fn get_backoff_delay(attempt: u8, initial: Duration, max: Duration) -> Duration {
+    let delay = initial.saturating_mul(1 << u32::from(attempt.saturating_sub(1)).min(16)).min(max);
+    delay.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::utils::request_limiter::{get_backoff_delay, RequestLimiter};
+
+    #[test]
+    fn test_backoff_delay() {
+        let initial = Duration::from_millis(1000);
+        let max = Duration::from_millis(5000);
+        for (attempt, full_delay) in [(1, 1000), (2, 2000), (3, 4000), (4, 5000), (20, 5000)] {
+            let delay = get_backoff_delay(attempt, initial, max);
+            assert!(delay >= Duration::from_millis(full_delay / 2) && delay <= Duration::from_millis(full_delay), "{attempt}: {delay:?}");
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_request_limiter() {
+        let limiter = RequestLimiter::new(Some(20.0), 1);
+        let start = Instant::now();
+        for _ in 0..3 {
+            let permit = limiter.acquire().await;
+            assert!(permit.is_some());
+        }
+        // the third request waits for two intervals of 50ms
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    }
+}
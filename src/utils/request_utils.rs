@@ -3,8 +3,9 @@ use std::fs;
 use std::fs::File;
 use std::io::{BufWriter, Error, ErrorKind, Read, Write};
 use std::path::{Path, PathBuf};
-use std::sync::LazyLock;
-use std::time::Instant;
+use std::net::SocketAddr;
+use std::sync::{Arc, LazyLock};
+use std::time::{Duration, Instant};
 
 use flate2::read::{GzDecoder, ZlibDecoder};
 use futures::StreamExt;
@@ -14,14 +15,15 @@ use reqwest::header::CONTENT_ENCODING;
 use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
 use url::Url;
 
-use crate::create_m3u_filter_error_result;
-use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorKind};
-use crate::model::config::ConfigInput;
+use crate::create_m3u_filter_error;
+use crate::m3u_filter_error::{M3uFilterError, M3uFilterErrorCategory, M3uFilterErrorKind};
+use crate::model::config::{Config, ConfigInput, ConfigInputHttp, DnsConfig, ProxyConfig};
 use crate::model::stats::format_elapsed_time;
 use crate::repository::storage::get_input_storage_path;
 use crate::repository::xtream_repository::FILE_EPG;
 use crate::utils::compression_utils::{is_deflate, is_gzip, ENCODING_DEFLATE, ENCODING_GZIP};
 use crate::utils::file_utils::{get_file_path, persist_file};
+use crate::utils::request_limiter::{get_backoff_delay, get_retry_after, is_retry_status};
 
 pub const fn bytes_to_megabytes(bytes: u64) -> u64 {
     bytes / 1_048_576
@@ -37,7 +39,7 @@ pub async fn get_input_text_content_as_file(input: &ConfigInput, working_dir: &s
             Ok(content) => Ok(content),
             Err(e) => {
                 error!("cant download input url: {}  => {}", mask_sensitive_info(url_str), mask_sensitive_info(e.to_string().as_str()));
-                create_m3u_filter_error_result!(M3uFilterErrorKind::Notify, "Failed to download")
+                Err(M3uFilterError::from_source(M3uFilterErrorKind::Notify, M3uFilterErrorCategory::Download, mask_sensitive_info(&e.to_string()), "Failed to download".to_string()))
             }
         }
     } else {
@@ -50,7 +52,8 @@ pub async fn get_input_text_content_as_file(input: &ConfigInput, working_dir: &s
                             Ok(_) => {}
                             Err(e) => {
                                 error!("cant persist to: {}  => {}", to_file.to_str().unwrap_or("?"), e);
-                                return create_m3u_filter_error_result!(M3uFilterErrorKind::Notify, "Failed to persist: {}  => {}", to_file.to_str().unwrap_or("?"), e);
+                                let message = format!("Failed to persist: {}  => {}", to_file.to_str().unwrap_or("?"), e);
+                                return Err(M3uFilterError::from_source(M3uFilterErrorKind::Notify, M3uFilterErrorCategory::Persistence, e, message));
                             }
                         }
                     };
@@ -58,7 +61,7 @@ pub async fn get_input_text_content_as_file(input: &ConfigInput, working_dir: &s
                     if filepath.exists() {
                         Some(filepath)
                     } else {
-                        return create_m3u_filter_error_result!(M3uFilterErrorKind::Notify, "Failed: file does not exists {filepath:?}");
+                        return Err(create_m3u_filter_error!(M3uFilterErrorKind::Notify, "Failed: file does not exists {filepath:?}").with_category(M3uFilterErrorCategory::Download));
                     }
                 } else {
                     None
@@ -70,7 +73,7 @@ pub async fn get_input_text_content_as_file(input: &ConfigInput, working_dir: &s
         result.map_or_else(|| {
             let msg = format!("cant read input url: {}", mask_sensitive_info(url_str));
             error!("{}", msg);
-            create_m3u_filter_error_result!(M3uFilterErrorKind::Notify, "{}", msg)
+            Err(create_m3u_filter_error!(M3uFilterErrorKind::Notify, "{}", msg).with_category(M3uFilterErrorCategory::Download))
         }, Ok)
     }
 }
@@ -86,7 +89,7 @@ pub async fn get_input_text_content(input: &ConfigInput, working_dir: &String, u
             Ok(content) => Ok(content),
             Err(e) => {
                 error!("cant download input url: {}  => {}", mask_sensitive_info(url_str), mask_sensitive_info(e.to_string().as_str()));
-                create_m3u_filter_error_result!(M3uFilterErrorKind::Notify, "Failed to download")
+                Err(M3uFilterError::from_source(M3uFilterErrorKind::Notify, M3uFilterErrorCategory::Download, mask_sensitive_info(&e.to_string()), "Failed to download".to_string()))
             }
         }
     } else {
@@ -99,7 +102,8 @@ pub async fn get_input_text_content(input: &ConfigInput, working_dir: &String, u
                             Ok(_) => {}
                             Err(e) => {
                                 error!("cant persist to: {}  => {}", to_file.to_str().unwrap_or("?"), e);
-                                return create_m3u_filter_error_result!(M3uFilterErrorKind::Notify, "Failed to persist: {}  => {}", to_file.to_str().unwrap_or("?"), e);
+                                let message = format!("Failed to persist: {}  => {}", to_file.to_str().unwrap_or("?"), e);
+                                return Err(M3uFilterError::from_source(M3uFilterErrorKind::Notify, M3uFilterErrorCategory::Persistence, e, message));
                             }
                         }
                     };
@@ -107,7 +111,8 @@ pub async fn get_input_text_content(input: &ConfigInput, working_dir: &String, u
                     match get_local_file_content(&filepath) {
                         Ok(content) => Some(content),
                         Err(err) => {
-                            return create_m3u_filter_error_result!(M3uFilterErrorKind::Notify, "Failed : {}", err);
+                            let message = format!("Failed : {err}");
+                            return Err(M3uFilterError::from_source(M3uFilterErrorKind::Notify, M3uFilterErrorCategory::Download, err, message));
                         }
                     }
                 } else {
@@ -119,17 +124,119 @@ pub async fn get_input_text_content(input: &ConfigInput, working_dir: &String, u
         result.map_or_else(|| {
             let msg = format!("cant read input url: {}", mask_sensitive_info(url_str));
             error!("{}", msg);
-            create_m3u_filter_error_result!(M3uFilterErrorKind::Notify, "{}", msg)
+            Err(create_m3u_filter_error!(M3uFilterErrorKind::Notify, "{}", msg).with_category(M3uFilterErrorCategory::Download))
         }, Ok)
     }
 }
 
+static DEFAULT_HTTP_CLIENT: LazyLock<reqwest::Client> = LazyLock::new(reqwest::Client::new);
+
+fn create_proxy(proxy: &ProxyConfig) -> Result<reqwest::Proxy, reqwest::Error> {
+    let result = reqwest::Proxy::all(proxy.url.as_str())?;
+    Ok(match (&proxy.username, &proxy.password) {
+        (Some(username), Some(password)) => result.basic_auth(username, password),
+        _ => result,
+    })
+}
+
+/// Creates the client for the http settings of an input, the request timeout is set per request
+/// because streams can't have a total timeout. The proxy of the input overrides the global proxy.
+pub fn create_http_client(http: Option<&ConfigInputHttp>, global_proxy: Option<&ProxyConfig>, dns: Option<&DnsConfig>) -> Result<reqwest::Client, reqwest::Error> {
+    let mut builder = reqwest::Client::builder();
+    if let Some(dns) = dns {
+        for (host, addr) in &dns.t_hosts {
+            // port 0 keeps the port of the url
+            builder = builder.resolve(host, SocketAddr::new(*addr, 0));
+        }
+        if let Some(resolver) = &dns.t_doh_resolver {
+            builder = builder.dns_resolver(Arc::new(resolver.clone()));
+        }
+    }
+    if let Some(proxy) = http.and_then(|http| http.proxy.as_ref()).or(global_proxy) {
+        builder = builder.proxy(create_proxy(proxy)?);
+    }
+    if let Some(http) = http {
+        builder = builder.danger_accept_invalid_certs(http.accept_invalid_certs);
+        if let Some(timeout) = http.timeout {
+            builder = builder.connect_timeout(Duration::from_secs(timeout));
+        }
+    }
+    builder.build()
+}
+
+/// The client of the input, without input the client of the global proxy and dns settings.
+pub fn get_http_client<'a>(cfg: &'a Config, input: Option<&'a ConfigInput>) -> &'a reqwest::Client {
+    input.and_then(|i| i.t_http_client.as_ref()).or(cfg.t_http_client.as_ref()).unwrap_or(&DEFAULT_HTTP_CLIENT)
+}
+
 pub fn get_client_request(input: Option<&ConfigInput>, url: &Url, custom_headers: Option<&HashMap<&str, &[u8]>>) -> reqwest::RequestBuilder {
-    let request = reqwest::Client::new().get(url.clone());
+    let client = input.and_then(|i| i.t_http_client.as_ref()).unwrap_or(&DEFAULT_HTTP_CLIENT);
+    let request = client.get(url.clone());
     let headers = get_request_headers(input.map(|i| &i.headers), custom_headers);
     request.headers(headers)
 }
 
+/// Download request for playlists and epg, with the timeout of the input http settings.
+fn get_download_request(input: &ConfigInput, url: &Url) -> reqwest::RequestBuilder {
+    let request = get_client_request(Some(input), url, None);
+    match input.http.as_ref().and_then(|http| http.timeout) {
+        Some(timeout) => request.timeout(Duration::from_secs(timeout)),
+        None => request,
+    }
+}
+
+/// Sends the request within the rate limits of the input. Failed requests, too many requests and server errors
+/// are retried with exponential backoff, a `Retry-After` of the provider is respected.
+pub async fn send_with_retries(input: Option<&ConfigInput>, request: reqwest::RequestBuilder) -> Result<reqwest::Response, reqwest::Error> {
+    let http = input.and_then(|i| i.http.as_ref());
+    let retries = http.map_or(0, |http| http.retries);
+    let (backoff, backoff_max) = http.map_or((Duration::ZERO, Duration::ZERO), ConfigInputHttp::get_backoff);
+    let limiter = http.and_then(|http| http.t_limiter.as_deref());
+    let mut attempt = 0;
+    loop {
+        let permit = match limiter {
+            Some(limiter) => limiter.acquire().await,
+            None => None,
+        };
+        let retry_request = if attempt < retries { request.try_clone() } else { None };
+        let Some(next_request) = retry_request else {
+            return request.send().await;
+        };
+        attempt += 1;
+        let retry_after = match next_request.send().await {
+            Ok(response) if !is_retry_status(response.status()) => return Ok(response),
+            Ok(response) => {
+                debug!("Request failed with status {}, retry {attempt}/{retries}", response.status());
+                get_retry_after(&response)
+            }
+            Err(err) => {
+                debug!("Request failed {}, retry {attempt}/{retries}", mask_sensitive_info(err.to_string().as_str()));
+                None
+            }
+        };
+        drop(permit);
+        let delay = retry_after.map_or_else(|| get_backoff_delay(attempt, backoff, backoff_max), |retry_after| retry_after.min(backoff_max));
+        actix_rt::time::sleep(delay).await;
+    }
+}
+
+/// Fails with the first header which is not a valid http header name or value.
+pub fn validate_headers<'a>(headers: impl IntoIterator<Item=(&'a String, &'a String)>) -> Result<(), String> {
+    for (name, value) in headers {
+        if HeaderName::from_bytes(name.as_bytes()).is_err() || HeaderValue::from_str(value).is_err() {
+            return Err(format!("{name}: {value}"));
+        }
+    }
+    Ok(())
+}
+
+/// The valid headers of the map, invalid headers are skipped.
+pub fn to_header_map<'a>(headers: impl IntoIterator<Item=(&'a String, &'a String)>) -> HeaderMap {
+    headers.into_iter()
+        .filter_map(|(name, value)| Some((HeaderName::from_bytes(name.as_bytes()).ok()?, HeaderValue::from_str(value).ok()?)))
+        .collect()
+}
+
 pub fn get_request_headers(defined_headers: Option<&HashMap<String, String>>, custom_headers: Option<&HashMap<&str, &[u8]>>) -> HeaderMap {
     let mut headers = HeaderMap::new();
     if let Some(def_headers) = defined_headers {
@@ -181,8 +288,8 @@ fn get_local_file_content(file_path: &PathBuf) -> Result<String, Error> {
 
 async fn get_remote_content_as_file(input: &ConfigInput, url: &Url, file_path: &Path) -> Result<PathBuf, std::io::Error> {
     let start_time = Instant::now();
-    let request = get_client_request(Some(input), url, None);
-    match request.send().await {
+    let request = get_download_request(input, url);
+    match send_with_retries(Some(input), request).await {
         Ok(response) => {
             if response.status().is_success() {
                 // Open a file in write mode
@@ -214,8 +321,8 @@ async fn get_remote_content_as_file(input: &ConfigInput, url: &Url, file_path: &
 
 async fn get_remote_content(input: &ConfigInput, url: &Url) -> Result<String, Error> {
     let start_time = Instant::now();
-    let request = get_client_request(Some(input), url, None);
-    match request.send().await {
+    let request = get_download_request(input, url);
+    match send_with_retries(Some(input), request).await {
         Ok(response) => {
             let is_success = response.status().is_success();
             if is_success {
@@ -340,7 +447,11 @@ async fn download_json_content(input: &ConfigInput, url: &str, persist_filepath:
 pub async fn get_input_json_content(input: &ConfigInput, url: &str, persist_filepath: Option<PathBuf>) -> Result<serde_json::Value, M3uFilterError> {
     match download_json_content(input, url, persist_filepath).await {
         Ok(content) => Ok(content),
-        Err(e) => create_m3u_filter_error_result!(M3uFilterErrorKind::Notify, "cant download input url: {}  => {}", mask_sensitive_info(url), mask_sensitive_info(e.to_string().as_str()))
+        Err(e) => {
+            let source = mask_sensitive_info(e.to_string().as_str());
+            let message = format!("cant download input url: {}  => {source}", mask_sensitive_info(url));
+            Err(M3uFilterError::from_source(M3uFilterErrorKind::Notify, M3uFilterErrorCategory::Download, source, message))
+        }
     }
 }
 //
@@ -367,4 +478,37 @@ pub fn mask_sensitive_info(query: &str) -> String {
     let masked_query = TOKEN_REGEX.replace_all(&masked_query, "$1***");
 
     masked_query.to_string()
-}
\ No newline at end of file
+}
+const DIRECT_STREAM_SCHEMES: [&str; 3] = ["udp://", "rtp://", "rtsp://"];
+
+/// `udp://`, `rtp://` and `rtsp://` streams can't be proxied over http, they are passed to the player.
+pub fn is_direct_stream_url(url: &str) -> bool {
+    DIRECT_STREAM_SCHEMES.iter().any(|scheme| url.get(..scheme.len()).is_some_and(|prefix| prefix.eq_ignore_ascii_case(scheme)))
+}
+
+/// The udpxy relay url of an `udp://` or `rtp://` stream, like `http://192.168.1.1:4022/udp/239.0.0.1:1234`.
+pub fn get_udpxy_url(udpxy_url: &str, url: &str) -> Option<String> {
+    let (scheme, address) = url.split_once("://")?;
+    let scheme = scheme.to_lowercase();
+    if scheme != "udp" && scheme != "rtp" {
+        return None;
+    }
+    // multicast urls are written as udp://@239.0.0.1:1234
+    let address = address.trim_start_matches('@');
+    Some(format!("{}/{scheme}/{address}", udpxy_url.trim_end_matches('/')))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::request_utils::{get_udpxy_url, is_direct_stream_url};
+
+    #[test]
+    fn test_direct_stream_url() {
+        assert!(is_direct_stream_url("udp://@239.0.0.1:1234"));
+        assert!(is_direct_stream_url("RTSP://camera.local/live"));
+        assert!(!is_direct_stream_url("http://provider.tv/live/1.ts"));
+        assert_eq!(get_udpxy_url("http://192.168.1.1:4022/", "udp://@239.0.0.1:1234").unwrap(), "http://192.168.1.1:4022/udp/239.0.0.1:1234");
+        assert_eq!(get_udpxy_url("http://192.168.1.1:4022", "rtp://239.0.0.2:5000").unwrap(), "http://192.168.1.1:4022/rtp/239.0.0.2:5000");
+        assert!(get_udpxy_url("http://192.168.1.1:4022", "rtsp://camera.local/live").is_none());
+    }
+}
@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Duration;
+
+#[cfg(unix)]
+use log::debug;
+use log::warn;
+
+use crate::model::config::Config;
+
+#[cfg(unix)]
+const ENV_NOTIFY_SOCKET: &str = "NOTIFY_SOCKET";
+const ENV_WATCHDOG_USEC: &str = "WATCHDOG_USEC";
+const ENV_WATCHDOG_PID: &str = "WATCHDOG_PID";
+
+/// Id of the next refresh.
+static NEXT_REFRESH_ID: AtomicU64 = AtomicU64::new(1);
+/// Start of the running refreshes by refresh id as unix timestamp, refreshes can run concurrently.
+static RUNNING_REFRESHES: LazyLock<Mutex<HashMap<u64, i64>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Abstract sockets (`@name`) are used by systemd in containers.
+#[cfg(target_os = "linux")]
+fn send_to_abstract(socket: &std::os::unix::net::UnixDatagram, name: &str, state: &str) -> std::io::Result<usize> {
+    use std::os::linux::net::SocketAddrExt;
+    let addr = std::os::unix::net::SocketAddr::from_abstract_name(name.as_bytes())?;
+    socket.send_to_addr(state.as_bytes(), &addr)
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn send_to_abstract(_socket: &std::os::unix::net::UnixDatagram, name: &str, _state: &str) -> std::io::Result<usize> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, format!("abstract socket @{name}")))
+}
+
+#[cfg(unix)]
+fn send(state: &str) {
+    let Ok(socket_path) = std::env::var(ENV_NOTIFY_SOCKET) else {
+        return;
+    };
+    let result = std::os::unix::net::UnixDatagram::unbound().and_then(|socket| match socket_path.strip_prefix('@') {
+        Some(name) => send_to_abstract(&socket, name, state),
+        None => socket.send_to(state.as_bytes(), &socket_path),
+    });
+    if let Err(err) = result {
+        debug!("Failed to notify service manager: {err}");
+    }
+}
+
+#[cfg(not(unix))]
+fn send(_state: &str) {}
+
+/// Tells systemd (`Type=notify`) that the server is accepting connections.
+pub fn notify_ready() {
+    send("READY=1");
+}
+
+pub fn notify_stopping() {
+    send("STOPPING=1");
+}
+
+fn register_refresh(started_at: i64) -> u64 {
+    let refresh_id = NEXT_REFRESH_ID.fetch_add(1, Ordering::Relaxed);
+    if let Ok(mut refreshes) = RUNNING_REFRESHES.lock() {
+        refreshes.insert(refresh_id, started_at);
+    }
+    refresh_id
+}
+
+/// Removes the refresh, returns `true` if no other refresh is running.
+fn unregister_refresh(refresh_id: u64) -> bool {
+    RUNNING_REFRESHES.lock().is_ok_and(|mut refreshes| {
+        refreshes.remove(&refresh_id);
+        refreshes.is_empty()
+    })
+}
+
+/// Marks the start of a refresh, the status is shown by `systemctl status`.
+/// Returns the id of the refresh for `refresh_finished`.
+pub fn refresh_started() -> u64 {
+    let refresh_id = register_refresh(chrono::Utc::now().timestamp());
+    send("STATUS=Refreshing playlists");
+    refresh_id
+}
+
+/// The status is idle when the last running refresh is finished.
+pub fn refresh_finished(refresh_id: u64) {
+    if unregister_refresh(refresh_id) {
+        send("STATUS=Idle");
+    }
+}
+
+/// A refresh counts as hung if one of the running refreshes runs longer than `refresh_timeout` seconds.
+fn is_refresh_hung(refresh_timeout: u64, now: i64) -> bool {
+    RUNNING_REFRESHES.lock().is_ok_and(|refreshes| refreshes.values()
+        .any(|started_at| u64::try_from(now - started_at).is_ok_and(|secs| secs > refresh_timeout)))
+}
+
+/// The watchdog interval requested by systemd with `WatchdogSec=`.
+fn get_watchdog_interval() -> Option<Duration> {
+    if let Ok(pid) = std::env::var(ENV_WATCHDOG_PID) {
+        if pid.trim().parse::<u32>().ok() != Some(std::process::id()) {
+            return None;
+        }
+    }
+    std::env::var(ENV_WATCHDOG_USEC).ok()
+        .and_then(|usec| usec.trim().parse::<u64>().ok())
+        .filter(|usec| *usec > 0)
+        .map(Duration::from_micros)
+}
+
+/// Sends the watchdog keep-alive at half the requested interval. The keep-alive stops while a refresh
+/// hangs longer than `watchdog.refresh_timeout`, so systemd restarts the service.
+pub fn start_watchdog(cfg: &Arc<Config>) {
+    let Some(interval) = get_watchdog_interval() else {
+        return;
+    };
+    let refresh_timeout = cfg.watchdog.as_ref().map(|watchdog| watchdog.refresh_timeout);
+    actix_rt::spawn(async move {
+        let mut hung_reported = false;
+        loop {
+            let hung = refresh_timeout.is_some_and(|timeout| is_refresh_hung(timeout, chrono::Utc::now().timestamp()));
+            if !hung {
+                send("WATCHDOG=1");
+            } else if !hung_reported {
+                warn!("Refresh is running longer than the watchdog refresh_timeout, stopping watchdog keep-alive");
+            }
+            hung_reported = hung;
+            actix_rt::time::sleep(interval / 2).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::sd_notify::{is_refresh_hung, register_refresh, unregister_refresh};
+
+    #[test]
+    fn test_refresh_hung() {
+        let now = 1_700_000_000;
+        assert!(!is_refresh_hung(60, now));
+        let hung = register_refresh(now - 120);
+        let running = register_refresh(now - 10);
+        assert!(is_refresh_hung(60, now));
+        assert!(!is_refresh_hung(300, now));
+        // the other refresh is still hung when the newer one finishes
+        assert!(!unregister_refresh(running));
+        assert!(is_refresh_hung(60, now));
+        assert!(unregister_refresh(hung));
+        assert!(!is_refresh_hung(60, now));
+    }
+}
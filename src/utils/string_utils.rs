@@ -1,33 +1,47 @@
-// other implementations like calculating text_distance on all titles took too much time
-// we keep it now as simple as possible and less memory intensive.
-pub fn get_title_group(text: &str) -> String {
-    let alphabetic_only: String = text.chars().map(|c| if c.is_alphanumeric() { c } else { ' ' }).collect();
-    let parts = alphabetic_only.split_whitespace();
-    let mut combination = String::new();
-    for p in parts {
-        combination = format!("{combination} {p}").trim().to_string();
-        if combination.len() > 2 {
-            return combination;
-        }
-    }
-    text.to_string()
-}
+use std::collections::HashSet;
+use std::rc::Rc;
 
-pub trait Capitalize {
-    fn capitalize(&self) -> String;
+/// Shares the storage of repeated strings like group names and logos while parsing a playlist.
+/// Equal interned strings point to the same allocation and can be compared with `Rc::ptr_eq`.
+#[derive(Default)]
+pub struct StringInterner {
+    pool: HashSet<Rc<String>>,
 }
 
-// Implement the Capitalize trait for &str
-impl Capitalize for &str {
-    fn capitalize(&self) -> String {
-        let mut chars = self.chars();
-        chars.next().map_or_else(String::new, |first_char| first_char.to_uppercase().collect::<String>() + chars.as_str())
+impl StringInterner {
+    pub fn intern(&mut self, value: String) -> Rc<String> {
+        if let Some(existing) = self.pool.get(&value) {
+            return Rc::clone(existing);
+        }
+        let value = Rc::new(value);
+        self.pool.insert(Rc::clone(&value));
+        value
+    }
+
+    pub fn intern_rc(&mut self, value: &Rc<String>) -> Rc<String> {
+        if let Some(existing) = self.pool.get(value) {
+            return Rc::clone(existing);
+        }
+        self.pool.insert(Rc::clone(value));
+        Rc::clone(value)
     }
 }
 
-// Implement the trait for String as well
-impl Capitalize for String {
-    fn capitalize(&self) -> String {
-        self.as_str().capitalize()  // Reuse the &str implementation
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use crate::utils::string_utils::StringInterner;
+
+    #[test]
+    fn test_string_interner() {
+        let mut interner = StringInterner::default();
+        let first = interner.intern("Sports".to_string());
+        let second = interner.intern("Sports".to_string());
+        assert!(Rc::ptr_eq(&first, &second));
+        let logo = Rc::new("http://logo.png".to_string());
+        assert!(Rc::ptr_eq(&interner.intern_rc(&logo), &logo));
+        assert!(Rc::ptr_eq(&interner.intern_rc(&Rc::new("http://logo.png".to_string())), &logo));
+        assert!(!Rc::ptr_eq(&interner.intern("News".to_string()), &first));
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,6 @@
+use tempfile::TempDir;
+
+/// Creates a temp directory for test fixtures, it is deleted when the returned `TempDir` is dropped.
+pub fn create_temp_dir(name: &str) -> TempDir {
+    tempfile::Builder::new().prefix(&format!("m3u_filter_{name}_")).tempdir().unwrap()
+}
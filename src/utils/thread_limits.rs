@@ -0,0 +1,37 @@
+use log::{debug, warn};
+
+use crate::model::config::ProcessingConfig;
+
+/// Sets the nice value and the cpus of the current thread, the threads it starts inherit both.
+#[cfg(target_os = "linux")]
+pub fn apply_processing_limits(cfg: &ProcessingConfig) {
+    // the nice value of a thread is set with its thread id, the process id would change the main thread
+    // SAFETY: the calls only read their arguments
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let thread_id = unsafe { libc::syscall(libc::SYS_gettid) } as libc::id_t;
+    if cfg.nice != 0 && unsafe { libc::setpriority(libc::PRIO_PROCESS, thread_id, cfg.nice) } != 0 {
+        warn!("Failed to set processing nice value {}: {}", cfg.nice, std::io::Error::last_os_error());
+    }
+    if !cfg.cpus.is_empty() {
+        // SAFETY: the cpu set is plain data, the cpus are checked against its size by `ProcessingConfig::prepare`
+        let result = unsafe {
+            let mut cpu_set: libc::cpu_set_t = std::mem::zeroed();
+            for &cpu in &cfg.cpus {
+                libc::CPU_SET(cpu, &mut cpu_set);
+            }
+            libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &cpu_set)
+        };
+        if result != 0 {
+            warn!("Failed to bind processing to cpus {:?}: {}", cfg.cpus, std::io::Error::last_os_error());
+        }
+    }
+    debug!("Processing runs with nice value {} on cpus {:?}", cfg.nice, cfg.cpus);
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn apply_processing_limits(cfg: &ProcessingConfig) {
+    if !cfg.cpus.is_empty() {
+        warn!("Processing cpus are only supported on linux");
+    }
+    debug!("Processing runs with the priority of the api server");
+}
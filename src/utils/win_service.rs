@@ -0,0 +1,105 @@
+use std::ffi::OsString;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use actix_server::ServerHandle;
+use log::{error, info};
+use tokio::sync::Notify;
+use windows_service::service::{ServiceControl, ServiceControlAccept, ServiceExitCode, ServiceState, ServiceStatus, ServiceType};
+use windows_service::service_control_handler::{self, ServiceControlHandlerResult, ServiceStatusHandle};
+use windows_service::{define_windows_service, service_dispatcher};
+
+use crate::api::main_api;
+use crate::model::config::{Config, ProcessTargets};
+
+/// The name the service is registered with, `sc create m3u-filter binPath= "...\m3u-filter.exe --service -p ..."`.
+const SERVICE_NAME: &str = "m3u-filter";
+
+/// The service main is called by the service control manager, the config is read before the dispatcher starts.
+static SERVICE_CONFIG: OnceLock<(Arc<Config>, Arc<ProcessTargets>)> = OnceLock::new();
+/// Notified when the service control manager stops the service.
+static SERVICE_STOP: Notify = Notify::const_new();
+
+define_windows_service!(ffi_service_main, service_main);
+
+/// Runs the server as windows service, returns when the service is stopped.
+/// Fails if the process was not started by the service control manager.
+pub fn run_service(cfg: Arc<Config>, targets: Arc<ProcessTargets>) -> windows_service::Result<()> {
+    let _ = SERVICE_CONFIG.set((cfg, targets));
+    service_dispatcher::start(SERVICE_NAME, ffi_service_main)
+}
+
+/// Stops the servers gracefully when the service is stopped.
+pub fn stop_on_service_stop(handles: Vec<ServerHandle>) {
+    actix_rt::spawn(async move {
+        SERVICE_STOP.notified().await;
+        for handle in handles {
+            handle.stop(true).await;
+        }
+    });
+}
+
+/// Only a running service accepts the stop and shutdown controls.
+fn create_status(state: ServiceState, exit_code: u32) -> ServiceStatus {
+    ServiceStatus {
+        service_type: ServiceType::OWN_PROCESS,
+        current_state: state,
+        controls_accepted: if state == ServiceState::Running { ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN } else { ServiceControlAccept::empty() },
+        exit_code: ServiceExitCode::Win32(exit_code),
+        checkpoint: 0,
+        wait_hint: Duration::ZERO,
+        process_id: None,
+    }
+}
+
+fn set_status(status_handle: &ServiceStatusHandle, state: ServiceState, exit_code: u32) {
+    if let Err(err) = status_handle.set_service_status(create_status(state, exit_code)) {
+        error!("Failed to set windows service status: {err}");
+    }
+}
+
+fn service_main(_arguments: Vec<OsString>) {
+    let Some((cfg, targets)) = SERVICE_CONFIG.get() else {
+        return;
+    };
+    let status_handle = match service_control_handler::register(SERVICE_NAME, |control| match control {
+        ServiceControl::Stop | ServiceControl::Shutdown => {
+            SERVICE_STOP.notify_one();
+            ServiceControlHandlerResult::NoError
+        }
+        ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+        _ => ServiceControlHandlerResult::NotImplemented,
+    }) {
+        Ok(status_handle) => status_handle,
+        Err(err) => {
+            error!("Failed to register windows service control handler: {err}");
+            return;
+        }
+    };
+    set_status(&status_handle, ServiceState::Running, 0);
+    info!("Windows service {SERVICE_NAME} running");
+    let exit_code = match main_api::start_server(Arc::clone(cfg), Arc::clone(targets)) {
+        Ok(()) => 0,
+        Err(err) => {
+            error!("Can't start server: {err}");
+            1
+        }
+    };
+    set_status(&status_handle, ServiceState::Stopped, exit_code);
+}
+
+#[cfg(test)]
+mod tests {
+    use windows_service::service::{ServiceControlAccept, ServiceExitCode, ServiceState};
+
+    use crate::utils::win_service::create_status;
+
+    #[test]
+    fn test_create_status() {
+        let running = create_status(ServiceState::Running, 0);
+        assert_eq!(running.controls_accepted, ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN);
+        let stopped = create_status(ServiceState::Stopped, 1);
+        assert!(stopped.controls_accepted.is_empty());
+        assert_eq!(stopped.exit_code, ServiceExitCode::Win32(1));
+    }
+}
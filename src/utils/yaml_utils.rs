@@ -0,0 +1,189 @@
+use std::env;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use log::warn;
+use regex::Regex;
+use serde_yaml::value::Tag;
+use serde_yaml::Value;
+
+use crate::utils::config_reader::resolve_env_var;
+
+const MAX_INCLUDE_DEPTH: usize = 10;
+
+/// `${VAR}` or `${VAR:-default}`, `$${VAR}` is the escaped literal `${VAR}`.
+/// The `${env:VAR}` syntax of single fields is resolved by the config itself.
+static VAR_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?P<escape>\$)?\$\{(?P<var>[a-zA-Z_][a-zA-Z0-9_]*)(?::-(?P<default>[^}]*))?}").unwrap());
+
+/// Replaces `${VAR}` in a scalar value with the value of the environment variable, unknown variables without default are kept.
+pub fn interpolate_env_vars(value: &str) -> String {
+    VAR_REGEX.replace_all(value, |caps: &regex::Captures| {
+        if caps.name("escape").is_some() {
+            return caps[0][1..].to_string();
+        }
+        let var_name = &caps["var"];
+        env::var(var_name).ok()
+            .or_else(|| caps.name("default").map(|default| default.as_str().to_string()))
+            .unwrap_or_else(|| {
+                warn!("Environment variable {var_name} is not set");
+                caps[0].to_string()
+            })
+    }).to_string()
+}
+
+fn read_include(file_name: &str, base_dir: &Path, depth: usize) -> Result<Value, String> {
+    let path = base_dir.join(file_name.trim());
+    let content = std::fs::read_to_string(&path).map_err(|err| format!("Could not read include file {}: {err}", path.display()))?;
+    let mut value: Value = serde_yaml::from_str(&content)
+        .map_err(|err| format!("Could not parse include file {}: {err}", path.display()))?;
+    // nested includes are relative to the included file
+    resolve_includes(&mut value, path.parent().unwrap_or(base_dir), depth + 1)?;
+    Ok(value)
+}
+
+fn as_include(value: &Value) -> Option<&str> {
+    match value {
+        Value::Tagged(tagged) if tagged.tag == Tag::new("include") => tagged.value.as_str(),
+        _ => None,
+    }
+}
+
+/// Replaces `!include other.yml` with the content of the file, relative paths are resolved against `base_dir`.
+/// An included list inside a list is inserted element by element, a `source.yml` can include one file per provider.
+/// Returns `true` if the document contained includes.
+pub fn resolve_includes(value: &mut Value, base_dir: &Path, depth: usize) -> Result<bool, String> {
+    if depth > MAX_INCLUDE_DEPTH {
+        return Err(format!("Includes are nested deeper than {MAX_INCLUDE_DEPTH} levels"));
+    }
+    if let Some(file_name) = as_include(value) {
+        *value = read_include(file_name, base_dir, depth)?;
+        return Ok(true);
+    }
+    let mut included = false;
+    match value {
+        Value::Sequence(sequence) => {
+            let mut result = Vec::with_capacity(sequence.len());
+            for mut item in sequence.drain(..) {
+                if let Some(file_name) = as_include(&item) {
+                    included = true;
+                    match read_include(file_name, base_dir, depth)? {
+                        Value::Sequence(items) => result.extend(items),
+                        other => result.push(other),
+                    }
+                } else {
+                    included |= resolve_includes(&mut item, base_dir, depth)?;
+                    result.push(item);
+                }
+            }
+            *sequence = result;
+        }
+        Value::Mapping(mapping) => {
+            for (_, item) in mapping.iter_mut() {
+                included |= resolve_includes(item, base_dir, depth)?;
+            }
+        }
+        Value::Tagged(tagged) => included = resolve_includes(&mut tagged.value, base_dir, depth)?,
+        _ => {}
+    }
+    Ok(included)
+}
+
+/// Compares a value of the config file with a value written back, a string matches its interpolated value
+/// and a `null` matches a missing field.
+fn same_value(file_value: &Value, value: &Value) -> bool {
+    match (file_value, value) {
+        (Value::String(file_text), Value::String(text)) => file_text == text || interpolate_env_vars(&resolve_env_var(file_text)) == *text,
+        (Value::Mapping(file_mapping), Value::Mapping(mapping)) => {
+            file_mapping.iter().all(|(key, file_item)| mapping.get(key).is_some_and(|item| same_value(file_item, item)))
+                && mapping.iter().all(|(key, item)| item.is_null() || file_mapping.contains_key(key))
+        }
+        (Value::Sequence(file_sequence), Value::Sequence(sequence)) => {
+            file_sequence.len() == sequence.len() && file_sequence.iter().zip(sequence).all(|(file_item, item)| same_value(file_item, item))
+        }
+        (Value::Tagged(file_tagged), Value::Tagged(tagged)) => file_tagged.tag == tagged.tag && same_value(&file_tagged.value, &tagged.value),
+        _ => file_value == value,
+    }
+}
+
+/// Returns the document to write back into a config file. Unchanged values are taken from the `raw` file content,
+/// they keep their `${VAR}` variables and `!include`s. `resolved` is the raw content with the includes resolved.
+/// A changed include is written inline.
+pub fn preserve_raw_values(raw: &Value, resolved: &Value, value: Value) -> Value {
+    if same_value(resolved, &value) {
+        return raw.clone();
+    }
+    match (raw, resolved, value) {
+        (Value::Mapping(raw_mapping), Value::Mapping(resolved_mapping), Value::Mapping(mapping)) => {
+            Value::Mapping(mapping.into_iter().map(|(key, item)| {
+                let item = match (raw_mapping.get(&key), resolved_mapping.get(&key)) {
+                    (Some(raw_item), Some(resolved_item)) => preserve_raw_values(raw_item, resolved_item, item),
+                    _ => item,
+                };
+                (key, item)
+            }).collect())
+        }
+        // an included list inside a list changes the positions of the items
+        (Value::Sequence(raw_sequence), Value::Sequence(resolved_sequence), Value::Sequence(sequence))
+        if raw_sequence.len() == sequence.len() && resolved_sequence.len() == sequence.len() && !raw_sequence.iter().any(|item| as_include(item).is_some()) => {
+            Value::Sequence(raw_sequence.iter().zip(resolved_sequence).zip(sequence)
+                .map(|((raw_item, resolved_item), item)| preserve_raw_values(raw_item, resolved_item, item)).collect())
+        }
+        (_, _, value) => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::yaml_utils::{interpolate_env_vars, preserve_raw_values, resolve_includes};
+    use crate::utils::test_utils::create_temp_dir;
+
+    #[test]
+    fn test_interpolate_and_include() {
+        std::env::set_var("M3U_FILTER_TEST_PORT", "8901");
+        assert_eq!(interpolate_env_vars("port: ${M3U_FILTER_TEST_PORT}"), "port: 8901");
+        assert_eq!(interpolate_env_vars("host: ${M3U_FILTER_TEST_UNSET:-localhost}"), "host: localhost");
+        assert_eq!(interpolate_env_vars("host: ${M3U_FILTER_TEST_UNSET}"), "host: ${M3U_FILTER_TEST_UNSET}");
+        assert_eq!(interpolate_env_vars("password: ${env:M3U_FILTER_TEST_PORT}"), "password: ${env:M3U_FILTER_TEST_PORT}");
+        assert_eq!(interpolate_env_vars("name: $${M3U_FILTER_TEST_PORT}"), "name: ${M3U_FILTER_TEST_PORT}");
+
+        let temp_dir = create_temp_dir("yaml_include");
+        let dir = temp_dir.path();
+        std::fs::create_dir_all(dir.join("providers")).unwrap();
+        std::fs::write(dir.join("providers/a.yml"), "- inputs: [{url: http://a, port: '${M3U_FILTER_TEST_PORT}'}]\n- inputs: [{url: http://b}]\n").unwrap();
+        std::fs::write(dir.join("providers/c.yml"), "inputs: !include c_inputs.yml\n").unwrap();
+        std::fs::write(dir.join("providers/c_inputs.yml"), "[{url: http://c}]\n").unwrap();
+        let mut document: serde_yaml::Value = serde_yaml::from_str("sources:\n  - !include providers/a.yml\n  - !include providers/c.yml\n").unwrap();
+        assert!(resolve_includes(&mut document, dir, 0).unwrap());
+        let sources = document["sources"].as_sequence().unwrap();
+        assert_eq!(sources.len(), 3);
+        // the included values are interpolated with the document
+        assert_eq!(sources[0]["inputs"][0]["port"].as_str(), Some("${M3U_FILTER_TEST_PORT}"));
+        assert_eq!(sources[2]["inputs"][0]["url"].as_str(), Some("http://c"));
+
+        std::fs::write(dir.join("loop.yml"), "value: !include loop.yml\n").unwrap();
+        let mut document: serde_yaml::Value = serde_yaml::from_str("root: !include loop.yml").unwrap();
+        assert!(resolve_includes(&mut document, dir, 0).is_err());
+    }
+
+    #[test]
+    fn test_preserve_raw_values() {
+        std::env::set_var("M3U_FILTER_TEST_SECRET", "secret");
+        let temp_dir = create_temp_dir("yaml_preserve");
+        let dir = temp_dir.path();
+        std::fs::write(dir.join("api.yml"), "{host: localhost, port: 8901}\n").unwrap();
+        let raw: serde_yaml::Value = serde_yaml::from_str("api: !include api.yml\ntmdb: {api_key: '${M3U_FILTER_TEST_SECRET}'}\nthreads: 1\n").unwrap();
+        let mut resolved = raw.clone();
+        resolve_includes(&mut resolved, dir, 0).unwrap();
+
+        // the resolved value and the variable are both unchanged values
+        for api_key in ["secret", "${M3U_FILTER_TEST_SECRET}"] {
+            let value: serde_yaml::Value = serde_yaml::from_str(&format!("api: {{host: localhost, port: 8901}}\ntmdb: {{api_key: '{api_key}', language: null}}\nthreads: 2\n")).unwrap();
+            let written = preserve_raw_values(&raw, &resolved, value);
+            assert_eq!(serde_yaml::to_string(&written).unwrap(), "api: !include api.yml\ntmdb:\n  api_key: ${M3U_FILTER_TEST_SECRET}\nthreads: 2\n");
+        }
+
+        let value: serde_yaml::Value = serde_yaml::from_str("api: {host: localhost, port: 8902}\ntmdb: {api_key: other}\nthreads: 1\n").unwrap();
+        let written = preserve_raw_values(&raw, &resolved, value);
+        assert_eq!(serde_yaml::to_string(&written).unwrap(), "api:\n  host: localhost\n  port: 8902\ntmdb:\n  api_key: other\nthreads: 1\n");
+    }
+}